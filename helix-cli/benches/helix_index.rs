@@ -205,6 +205,12 @@ fn bench_commit_command(c: &mut Criterion) {
                             allow_empty: false,
                             amend: false,
                             verbose: false,
+                            signoff: false,
+                            no_edit: false,
+                            reset_author: false,
+                            date: None,
+                            metadata: Default::default(),
+                            allow_missing_objects: false,
                         },
                     )
                     .unwrap();
@@ -426,6 +432,12 @@ email = "bench@test.com"
                             allow_empty: false,
                             amend: false,
                             verbose: false,
+                            signoff: false,
+                            no_edit: false,
+                            reset_author: false,
+                            date: None,
+                            metadata: Default::default(),
+                            allow_missing_objects: false,
                         },
                     )
                     .unwrap();
@@ -1,14 +1,17 @@
 // Add command - Stage files using pure Helix storage
 
+use crate::attributes::Attributes;
+use crate::file_mode;
 use crate::helix_index::api::HelixIndexData;
 use crate::helix_index::format::{Entry, EntryFlags};
 use crate::ignore::IgnoreRules;
+use crate::line_endings::LineEndings;
 use crate::sandbox_command::RepoContext;
 use anyhow::{Context, Result};
 use helix_protocol::message::ObjectType;
 use helix_protocol::storage::FsObjectStore;
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
@@ -37,8 +40,8 @@ pub fn add(repo_path: &Path, paths: &[PathBuf], options: AddOptions) -> Result<(
 
     let context = RepoContext::detect(repo_path)?;
 
-    // Use context's index path for loading
-    let mut index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+    // Use context's index path for loading; locked until the new index is written
+    let mut index = HelixIndexData::load_for_update(&context.index_path, &context.repo_root)?;
 
     if options.verbose {
         if context.is_sandbox() {
@@ -97,8 +100,8 @@ pub fn add(repo_path: &Path, paths: &[PathBuf], options: AddOptions) -> Result<(
     // Stage files (parallel hashing + batch blob writes)
     stage_files(&mut index, &files_to_add, &options, &context)?;
 
-    // Persist index to disk
-    index.persist()?;
+    // Journal only the touched entries; the index is compacted on commit
+    index.persist_paths(&files_to_add)?;
 
     if options.verbose {
         println!("Staged {} files", files_to_add.len());
@@ -130,6 +133,7 @@ fn resolve_files_to_add(
 
     // Expand paths (handle ".", directories, globs) - parallel
     let candidate_files = expand_paths_parallel(&context.workdir, paths)?;
+    let candidate_files = canonicalize_candidates(index, candidate_files)?;
 
     if options.verbose {
         println!("Found {} candidate files", candidate_files.len());
@@ -181,9 +185,12 @@ fn resolve_files_to_add(
             // If file is tracked but doesn't exist on disk, it's been deleted
             if !full_path.exists() {
                 // Check if already staged for deletion
-                if let Some(entry) = index.entries().iter().find(|e| &e.path == tracked_path) {
-                    // Only add if not already staged
-                    if !entry.flags.contains(EntryFlags::STAGED) {
+                if let Some(entry) = index.entry(tracked_path) {
+                    // Only add if not already staged, or never checked out
+                    if !entry
+                        .flags
+                        .intersects(EntryFlags::STAGED | EntryFlags::ASSUME_UNCHANGED)
+                    {
                         if options.verbose {
                             println!("  deleted: {}", tracked_path.display());
                         }
@@ -201,6 +208,55 @@ fn resolve_files_to_add(
     Ok(files_to_add)
 }
 
+/// Rewrite candidate paths to the spelling the index should store.
+///
+/// On filesystems that ignore case or Unicode normalization, a path typed as
+/// `readme.md` or in NFD form refers to the same file as an already tracked
+/// `README.md`/NFC entry. Like Git with core.ignorecase, keep the tracked
+/// spelling instead of creating a second entry. Two distinct untracked paths
+/// that would land on the same file are rejected.
+fn canonicalize_candidates(
+    index: &HelixIndexData,
+    candidates: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    let policy = index.path_policy();
+    if policy.is_exact() {
+        return Ok(candidates);
+    }
+
+    let tracked_by_key: HashMap<PathBuf, &Path> = index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .map(|e| (policy.key(&e.path), e.path.as_path()))
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut resolved = Vec::with_capacity(candidates.len());
+    for path in candidates {
+        let path = match tracked_by_key.get(&policy.key(&path)) {
+            Some(existing) => existing.to_path_buf(),
+            None => policy.normalize(&path),
+        };
+        if seen.insert(path.clone()) {
+            resolved.push(path);
+        }
+    }
+
+    if let Some((first, second)) = policy
+        .find_collisions(resolved.iter().map(|p| p.as_path()))
+        .first()
+    {
+        anyhow::bail!(
+            "'{}' and '{}' refer to the same file on this filesystem (core.ignorecase / core.precomposeunicode)",
+            first.display(),
+            second.display()
+        );
+    }
+
+    Ok(resolved)
+}
+
 fn should_add_file(
     relative_path: &Path,
     full_path: &Path,
@@ -257,6 +313,16 @@ fn should_add_file(
 
 /// Stage files by writing to blob storage and updating index
 /// Stage files by writing to blob storage and updating index
+/// Stage exactly `paths` (changed files or deletions) without printing, for callers
+/// such as the status TUI that have already decided what to stage
+pub fn stage_paths(repo_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let context = RepoContext::detect(repo_path)?;
+    let mut index = HelixIndexData::load_for_update(&context.index_path, &context.repo_root)?;
+
+    stage_files(&mut index, paths, &AddOptions::default(), &context)?;
+    index.persist_paths(paths)
+}
+
 fn stage_files(
     index: &mut HelixIndexData,
     files: &[PathBuf],
@@ -274,7 +340,7 @@ fn stage_files(
 
     // Handle deleted files first
     for path in &deleted_files {
-        if let Some(entry) = index.entries_mut().iter_mut().find(|e| &e.path == *path) {
+        if let Some(entry) = index.entry_mut(path) {
             entry.flags.insert(EntryFlags::STAGED);
             entry.flags.insert(EntryFlags::DELETED);
 
@@ -292,13 +358,15 @@ fn stage_files(
         println!("Reading and hashing {} files...", existing_files.len());
     }
 
-    // Read all file contents for existing files
+    // Read all file contents for existing files, with line endings normalized
+    let line_endings = LineEndings::load(&context.repo_root);
     let file_data: Vec<(PathBuf, Vec<u8>, fs::Metadata)> = existing_files
         .iter()
         .map(|path| {
             let full_path = context.workdir.join(path);
             let content = fs::read(&full_path)
                 .with_context(|| format!("Failed to read {}", path.display()))?;
+            let content = line_endings.normalize(&content).into_owned();
             let metadata = fs::metadata(&full_path)
                 .with_context(|| format!("Failed to get metadata for {}", path.display()))?;
             Ok::<_, anyhow::Error>(((*path).clone(), content, metadata))
@@ -325,53 +393,43 @@ fn stage_files(
     }
 
     // Update index entries for existing files
+    let attributes = Attributes::load(&context.workdir);
     for (i, (path, _, metadata)) in file_data.iter().enumerate() {
         let hash = hashes[i];
+        let recorded_mode = index
+            .entries()
+            .iter()
+            .find(|e| &e.path == path)
+            .map(|e| e.file_mode);
 
         let entry = Entry {
             path: path.clone(),
             oid: hash,
-            flags: EntryFlags::TRACKED | EntryFlags::STAGED,
+            flags: EntryFlags::TRACKED
+                | EntryFlags::STAGED
+                | attributes.for_path(path).entry_flags(),
             size: metadata.len(),
             mtime_sec: metadata
                 .modified()?
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs(),
             mtime_nsec: 0,
-            file_mode: get_file_mode(metadata),
+            file_mode: file_mode::mode_from_metadata(metadata, recorded_mode),
             merge_conflict_stage: 0,
             reserved: [0u8; 33],
         };
 
         // Update or insert entry
-        if let Some(existing) = index.entries_mut().iter_mut().find(|e| &e.path == path) {
-            *existing = entry;
-        } else {
-            index.entries_mut().push(entry);
-        }
+        index.upsert_entry(entry);
     }
 
     Ok(())
 }
 
-/// Get file mode (Unix permissions)
+/// Get the index mode for a working tree file, ignoring any previously recorded mode.
+/// See `file_mode::mode_from_metadata` for platforms without an executable bit.
 pub fn get_file_mode(metadata: &fs::Metadata) -> u32 {
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let mode = metadata.permissions().mode();
-        if mode & 0o111 != 0 {
-            0o100755 // Executable
-        } else {
-            0o100644 // Regular file
-        }
-    }
-
-    #[cfg(not(unix))]
-    {
-        let _ = metadata; // Suppress unused warning
-        0o100644 // Default to regular file on Windows
-    }
+    file_mode::mode_from_metadata(metadata, None)
 }
 
 /// Expand paths (handle ".", directories, globs) - parallel
@@ -811,4 +869,51 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_canonicalize_candidates_ignore_case() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        fs::create_dir_all(repo_path.join(".helix"))?;
+
+        crate::path_policy::PathPolicy {
+            ignore_case: true,
+            precompose_unicode: true,
+        }
+        .save(repo_path)?;
+
+        let mut index =
+            HelixIndexData::load_from_path(&repo_path.join(".helix/helix.idx"), repo_path)?;
+        index.entries_mut().push(Entry {
+            path: PathBuf::from("README.md"),
+            oid: [0u8; 32],
+            flags: EntryFlags::TRACKED,
+            size: 0,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            file_mode: 0o100644,
+            merge_conflict_stage: 0,
+            reserved: [0u8; 33],
+        });
+
+        // Existing spelling wins, NFD is stored as NFC
+        let resolved = canonicalize_candidates(
+            &index,
+            vec![PathBuf::from("readme.md"), PathBuf::from("cafe\u{301}.txt")],
+        )?;
+        assert_eq!(
+            resolved,
+            vec![PathBuf::from("README.md"), PathBuf::from("caf\u{e9}.txt")]
+        );
+
+        // Two new paths naming the same file are rejected
+        let err = canonicalize_candidates(
+            &index,
+            vec![PathBuf::from("Notes.txt"), PathBuf::from("notes.txt")],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("refer to the same file"));
+
+        Ok(())
+    }
 }
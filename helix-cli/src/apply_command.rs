@@ -0,0 +1,992 @@
+/*
+`helix apply <patch>` - apply a unified diff to the working tree.
+
+Reads `helix diff` output, `git diff` output or a plain `diff -u` patch,
+including one pasted from an email (text before the first file header and a
+trailing signature are skipped).
+
+Each hunk is placed where its old lines (context and removals) match:
+
+1. At the line the hunk header names, shifted by how far earlier hunks moved.
+2. Failing that, at the nearest matching line above or below (an offset).
+3. Failing that, with up to `fuzz` context lines ignored at each end.
+
+Hunks that still don't fit are written to `<file>.rej` and the rest of the
+file is patched anyway, like `patch` and `git apply --reject`. With --index
+the patched files are staged as well; --reverse undoes a patch; --check only
+reports whether it would apply.
+
+`helix format-patch` output is a mailbox of patches with the author, date,
+message and metadata of the commits they came from. Applied as a whole it's
+just a diff; with --commit each patch is applied and staged in turn and
+committed with what its headers say, stopping at the first one that leaves
+rejects.
+*/
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use helix_protocol::hash::Hash;
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::commit_command::{commit, CommitOptions};
+use crate::format_patch_command::METADATA_HEADER;
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::format::{Entry, EntryFlags};
+use crate::line_endings::LineEndings;
+use crate::sandbox_command::RepoContext;
+use crate::unified_diff::split_lines;
+
+/// Context lines GNU patch may ignore by default
+pub const DEFAULT_FUZZ: usize = 2;
+
+pub struct ApplyOptions {
+    /// Undo the patch instead of applying it
+    pub reverse: bool,
+    /// Stage the patched files too
+    pub index: bool,
+    /// Only check whether the patch applies
+    pub check: bool,
+    /// Most context lines to ignore at each end of a hunk
+    pub fuzz: usize,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        Self {
+            reverse: false,
+            index: false,
+            check: false,
+            fuzz: DEFAULT_FUZZ,
+        }
+    }
+}
+
+/// Changes to one file. A missing path means the file doesn't exist on that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub hunks: Vec<PatchHunk>,
+    /// Binary changes carry no content to apply
+    pub binary: bool,
+}
+
+impl FilePatch {
+    fn display_path(&self) -> &Path {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or(Path::new(""))
+    }
+
+    fn reversed(&self) -> Self {
+        Self {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            hunks: self.hunks.iter().map(PatchHunk::reversed).collect(),
+            binary: self.binary,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchHunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    /// Lines prefixed by ' ', '-' or '+', terminators included
+    pub lines: Vec<(char, String)>,
+}
+
+impl PatchHunk {
+    fn reversed(&self) -> Self {
+        Self {
+            old_start: self.new_start,
+            old_len: self.new_len,
+            new_start: self.old_start,
+            new_len: self.old_len,
+            lines: self
+                .lines
+                .iter()
+                .map(|(prefix, line)| {
+                    let prefix = match prefix {
+                        '-' => '+',
+                        '+' => '-',
+                        other => *other,
+                    };
+                    (prefix, line.clone())
+                })
+                .collect(),
+        }
+    }
+
+    fn write_to(&self, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        );
+        for (prefix, line) in &self.lines {
+            out.push(*prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkOutcome {
+    Applied {
+        /// 1-based line the hunk was applied at
+        line: usize,
+        offset: isize,
+        fuzz: usize,
+    },
+    Rejected,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileOutcome {
+    pub path: PathBuf,
+    pub hunks: Vec<HunkOutcome>,
+    /// Why the whole file couldn't be patched
+    pub error: Option<String>,
+    pub reject_file: Option<PathBuf>,
+}
+
+impl FileOutcome {
+    pub fn rejected(&self) -> usize {
+        self.hunks
+            .iter()
+            .filter(|h| **h == HunkOutcome::Rejected)
+            .count()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.error.is_none() && self.rejected() == 0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ApplyReport {
+    pub files: Vec<FileOutcome>,
+}
+
+impl ApplyReport {
+    pub fn is_clean(&self) -> bool {
+        self.files.iter().all(FileOutcome::is_clean)
+    }
+
+    pub fn print_summary(&self, check: bool) {
+        for file in &self.files {
+            let path = file.path.display();
+            if let Some(error) = &file.error {
+                println!("error: {}: {}", path, error);
+                continue;
+            }
+
+            for (i, hunk) in file.hunks.iter().enumerate() {
+                match hunk {
+                    HunkOutcome::Applied { line, offset, fuzz } if *offset != 0 || *fuzz != 0 => {
+                        let mut note = format!("Hunk #{} succeeded at {}", i + 1, line);
+                        if *fuzz > 0 {
+                            let _ = write!(note, " with fuzz {}", fuzz);
+                        }
+                        if *offset != 0 {
+                            let _ = write!(note, " (offset {} lines)", offset);
+                        }
+                        println!("{}.", note);
+                    }
+                    HunkOutcome::Applied { .. } => {}
+                    HunkOutcome::Rejected => println!("Hunk #{} FAILED.", i + 1),
+                }
+            }
+
+            let verb = if check {
+                "Patch applies to"
+            } else {
+                "Applied patch to"
+            };
+            match (file.rejected(), &file.reject_file) {
+                (0, _) => println!("{} '{}' cleanly.", verb, path),
+                (n, Some(rej)) => println!(
+                    "Applied patch to '{}' with {} reject(s), saved to {}",
+                    path,
+                    n,
+                    rej.display()
+                ),
+                (n, None) => println!(
+                    "{} of {} hunk(s) don't apply to '{}'",
+                    n,
+                    file.hunks.len(),
+                    path
+                ),
+            }
+        }
+    }
+}
+
+/// Apply a patch to the working tree (and index, with `options.index`)
+pub fn apply(repo_path: &Path, patch: &str, options: &ApplyOptions) -> Result<ApplyReport> {
+    let context = RepoContext::detect(repo_path)?;
+    let line_endings = LineEndings::load(&context.repo_root);
+    let store = FsObjectStore::new(&context.repo_root);
+
+    let mut patches = parse_patch(patch)?;
+    if patches.is_empty() {
+        bail!("No file changes found in patch");
+    }
+    if options.reverse {
+        patches = patches.iter().map(FilePatch::reversed).collect();
+    }
+
+    let mut index = if options.index && !options.check {
+        Some(HelixIndexData::load_for_update(
+            &context.index_path,
+            &context.repo_root,
+        )?)
+    } else {
+        None
+    };
+
+    let mut report = ApplyReport::default();
+    for file in &patches {
+        let mut outcome = FileOutcome {
+            path: file.display_path().to_path_buf(),
+            hunks: Vec::new(),
+            error: None,
+            reject_file: None,
+        };
+
+        let patched = match patch_file(&context.workdir, file, line_endings, options) {
+            Ok(patched) => patched,
+            Err(e) => {
+                outcome.error = Some(e.to_string());
+                report.files.push(outcome);
+                continue;
+            }
+        };
+        outcome.hunks = patched.outcomes;
+
+        if !options.check {
+            if let Some(new_path) = &file.new_path {
+                let full_path = context.workdir.join(new_path);
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(
+                    &full_path,
+                    line_endings.to_working_tree(patched.content.as_bytes()),
+                )
+                .with_context(|| format!("Failed to write {}", new_path.display()))?;
+            }
+            if let Some(old_path) = &file.old_path {
+                let full_path = context.workdir.join(old_path);
+                if file.new_path.is_none() && outcome.rejected() > 0 {
+                    // Keep a file we couldn't fully delete, with what did apply
+                    fs::write(
+                        &full_path,
+                        line_endings.to_working_tree(patched.content.as_bytes()),
+                    )?;
+                } else if file.new_path.as_ref() != Some(old_path) {
+                    fs::remove_file(&full_path)
+                        .with_context(|| format!("Failed to remove {}", old_path.display()))?;
+                }
+            }
+
+            if outcome.rejected() > 0 {
+                let mut rej = format!(
+                    "--- a/{}\n+++ b/{}\n",
+                    outcome.path.display(),
+                    outcome.path.display()
+                );
+                for (hunk, result) in file.hunks.iter().zip(&outcome.hunks) {
+                    if *result == HunkOutcome::Rejected {
+                        hunk.write_to(&mut rej);
+                    }
+                }
+                let rej_path = PathBuf::from(format!("{}.rej", outcome.path.display()));
+                fs::write(context.workdir.join(&rej_path), rej)?;
+                outcome.reject_file = Some(rej_path);
+            }
+
+            if let Some(index) = index.as_mut() {
+                stage_result(index, &store, file, &patched.content)?;
+            }
+        }
+
+        report.files.push(outcome);
+    }
+
+    if let Some(mut index) = index {
+        index.persist()?;
+    }
+
+    Ok(report)
+}
+
+/// One patch email from `helix format-patch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailPatch {
+    pub author: String,
+    /// Author date, seconds since the Unix epoch
+    pub date: Option<u64>,
+    /// The subject without its [PATCH] tag, then the body
+    pub message: String,
+    pub metadata: BTreeMap<String, String>,
+    pub diff: String,
+}
+
+/// Apply each patch of a mailbox in order and commit it as its headers describe.
+/// Stops at the first patch that doesn't apply cleanly, with its rejects left in the
+/// working tree and the patches before it committed.
+pub fn apply_mailbox(repo_path: &Path, mailbox: &str, options: &ApplyOptions) -> Result<Vec<Hash>> {
+    if options.reverse || options.check {
+        bail!("--commit can't be used with --reverse or --check");
+    }
+    let mails = parse_mailbox(mailbox);
+    if mails.is_empty() {
+        bail!("No patch emails found; --commit needs `helix format-patch` output");
+    }
+    let options = ApplyOptions {
+        index: true,
+        fuzz: options.fuzz,
+        ..Default::default()
+    };
+
+    let mut commits = Vec::with_capacity(mails.len());
+    for (i, mail) in mails.iter().enumerate() {
+        println!("Applying: {}", mail.message.lines().next().unwrap_or(""));
+        let report = apply(repo_path, &mail.diff, &options)?;
+        if !report.is_clean() {
+            report.print_summary(false);
+            bail!(
+                "Patch {} of {} doesn't apply cleanly. Fix it up and commit, then apply the rest",
+                i + 1,
+                mails.len()
+            );
+        }
+        commits.push(commit(
+            repo_path,
+            CommitOptions {
+                message: mail.message.clone(),
+                author: Some(mail.author.clone()),
+                date: mail.date,
+                metadata: mail.metadata.clone(),
+                ..Default::default()
+            },
+        )?);
+    }
+    Ok(commits)
+}
+
+/// The patch emails in an mbox file. Messages without a From header or a diff are
+/// skipped.
+pub fn parse_mailbox(mailbox: &str) -> Vec<MailPatch> {
+    let mut messages: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in split_lines(mailbox) {
+        if is_mbox_separator(line) {
+            messages.push(Vec::new());
+        } else if let Some(message) = messages.last_mut() {
+            message.push(line);
+        }
+    }
+    messages
+        .iter()
+        .filter_map(|lines| parse_mail(lines))
+        .collect()
+}
+
+/// "From <commit hash> <date>", the line starting each message
+fn is_mbox_separator(line: &str) -> bool {
+    line.strip_prefix("From ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .is_some_and(|hash| hash.len() >= 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn parse_mail(lines: &[&str]) -> Option<MailPatch> {
+    // Headers up to the first blank line; folded ones continue on indented lines
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut i = 0;
+    while let Some(line) = lines.get(i) {
+        let line = line.trim_end_matches(['\n', '\r']);
+        i += 1;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let author = header("From")?.to_string();
+    let date = header("Date")
+        .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+        .and_then(|date| u64::try_from(date.timestamp()).ok());
+    let subject = header("Subject").unwrap_or("");
+    let subject = match subject.strip_prefix('[') {
+        Some(tagged) => tagged
+            .split_once(']')
+            .map_or(subject, |(_, rest)| rest.trim()),
+        None => subject,
+    };
+    let metadata = headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case(METADATA_HEADER))
+        .filter_map(|(_, value)| value.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    // The body runs to the "---" line, or to the diff when there isn't one
+    let rest = &lines[i.min(lines.len())..];
+    let diff_start = rest
+        .iter()
+        .position(|line| line.trim_end() == "---" || line.starts_with("diff --git "))?;
+    let body = rest[..diff_start].concat();
+    let body = body.trim();
+    let diff = rest[diff_start..]
+        .iter()
+        .skip_while(|line| line.trim_end() == "---")
+        .copied()
+        .collect::<String>();
+    if diff.trim().is_empty() {
+        return None;
+    }
+
+    let mut message = format!("{subject}\n");
+    if !body.is_empty() {
+        message.push('\n');
+        message.push_str(body);
+        message.push('\n');
+    }
+    Some(MailPatch {
+        author,
+        date,
+        message,
+        metadata,
+        diff,
+    })
+}
+
+struct PatchedFile {
+    content: String,
+    outcomes: Vec<HunkOutcome>,
+}
+
+fn patch_file(
+    workdir: &Path,
+    file: &FilePatch,
+    line_endings: LineEndings,
+    options: &ApplyOptions,
+) -> Result<PatchedFile> {
+    for path in file.old_path.iter().chain(&file.new_path) {
+        check_path(path)?;
+    }
+    if file.binary {
+        bail!("binary patches can't be applied");
+    }
+
+    let original = match &file.old_path {
+        Some(old_path) => {
+            let bytes = fs::read(workdir.join(old_path))
+                .with_context(|| format!("{} does not exist", old_path.display()))?;
+            String::from_utf8(line_endings.normalize(&bytes).into_owned())
+                .with_context(|| format!("{} is not UTF-8 text", old_path.display()))?
+        }
+        None => String::new(),
+    };
+    if let Some(new_path) = &file.new_path {
+        if file.old_path.as_ref() != Some(new_path) && workdir.join(new_path).exists() {
+            bail!("{} already exists", new_path.display());
+        }
+    }
+
+    let (content, outcomes) = apply_hunks(&original, &file.hunks, options.fuzz);
+
+    let rejected = outcomes.contains(&HunkOutcome::Rejected);
+    if file.new_path.is_none() && !rejected && !content.is_empty() {
+        bail!("file to delete still has content after patching");
+    }
+
+    Ok(PatchedFile { content, outcomes })
+}
+
+/// Only plain relative paths inside the repository
+fn check_path(path: &Path) -> Result<()> {
+    if path.as_os_str().is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "refusing to patch path outside the repository: {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn stage_result(
+    index: &mut HelixIndexData,
+    store: &FsObjectStore,
+    file: &FilePatch,
+    content: &str,
+) -> Result<()> {
+    if let Some(old_path) = &file.old_path {
+        if file.new_path.as_ref() != Some(old_path) {
+            if let Some(entry) = index.entry_mut(old_path) {
+                entry.flags.insert(EntryFlags::DELETED | EntryFlags::STAGED);
+            }
+        }
+    }
+
+    if let Some(new_path) = &file.new_path {
+        let oid = store.write_object(&ObjectType::Blob, content.as_bytes())?;
+        match index.entry_mut(new_path) {
+            Some(entry) => {
+                entry.oid = oid;
+                entry.size = content.len() as u64;
+                entry
+                    .flags
+                    .remove(EntryFlags::DELETED | EntryFlags::MODIFIED | EntryFlags::UNTRACKED);
+                entry.flags.insert(EntryFlags::TRACKED | EntryFlags::STAGED);
+            }
+            None => {
+                let mut entry =
+                    Entry::new(new_path.clone(), content.len() as u64, 0, oid, 0o100644);
+                entry.flags = EntryFlags::TRACKED | EntryFlags::STAGED;
+                index.upsert_entry(entry);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply hunks in order, returning the new content and where each hunk landed
+pub fn apply_hunks(content: &str, hunks: &[PatchHunk], fuzz: usize) -> (String, Vec<HunkOutcome>) {
+    let mut lines: Vec<String> = split_lines(content).into_iter().map(String::from).collect();
+    let mut outcomes = Vec::with_capacity(hunks.len());
+    // How far the file has shifted from the line numbers in the hunk headers
+    let mut shift: isize = 0;
+    // Hunks can't overlap ones already applied
+    let mut min_pos = 0;
+
+    for hunk in hunks {
+        let leading = hunk.lines.iter().take_while(|(p, _)| *p == ' ').count();
+        let trailing = hunk
+            .lines
+            .iter()
+            .rev()
+            .take_while(|(p, _)| *p == ' ')
+            .count();
+
+        let mut applied = None;
+        for f in 0..=fuzz {
+            let skip_start = f.min(leading);
+            let skip_end = f.min(trailing);
+            if f > 0 && skip_start == 0 && skip_end == 0 {
+                break;
+            }
+            let body = &hunk.lines[skip_start..hunk.lines.len() - skip_end];
+            let old: Vec<&str> = body
+                .iter()
+                .filter(|(p, _)| *p != '+')
+                .map(|(_, l)| l.as_str())
+                .collect();
+            let new: Vec<String> = body
+                .iter()
+                .filter(|(p, _)| *p != '-')
+                .map(|(_, l)| l.clone())
+                .collect();
+
+            let header_pos = if hunk.old_len == 0 {
+                hunk.old_start
+            } else {
+                hunk.old_start.saturating_sub(1)
+            } + skip_start;
+            let expected = (header_pos as isize + shift).max(0) as usize;
+
+            if let Some(pos) = find_lines(&lines, &old, expected, min_pos) {
+                let old_len = old.len();
+                let new_len = new.len();
+                lines.splice(pos..pos + old_len, new);
+                shift = pos as isize - header_pos as isize + new_len as isize - old_len as isize;
+                min_pos = pos + new_len;
+                applied = Some(HunkOutcome::Applied {
+                    line: pos + 1 - skip_start,
+                    offset: pos as isize - expected as isize,
+                    fuzz: f,
+                });
+                break;
+            }
+        }
+
+        outcomes.push(applied.unwrap_or(HunkOutcome::Rejected));
+    }
+
+    (lines.concat(), outcomes)
+}
+
+/// Nearest position at or after `min_pos` where `needle` matches, searching
+/// outward from `expected`
+fn find_lines(lines: &[String], needle: &[&str], expected: usize, min_pos: usize) -> Option<usize> {
+    if needle.len() > lines.len() {
+        return None;
+    }
+    let last = lines.len() - needle.len();
+    if min_pos > last {
+        return None;
+    }
+    let expected = expected.clamp(min_pos, last);
+    let matches = |pos: usize| {
+        lines[pos..pos + needle.len()]
+            .iter()
+            .zip(needle)
+            .all(|(a, b)| a == b)
+    };
+
+    for distance in 0..=last - min_pos {
+        if let Some(pos) = expected.checked_add(distance).filter(|&p| p <= last) {
+            if matches(pos) {
+                return Some(pos);
+            }
+        }
+        if let Some(pos) = expected.checked_sub(distance).filter(|&p| p >= min_pos) {
+            if matches(pos) {
+                return Some(pos);
+            }
+        }
+    }
+    None
+}
+
+/// Parse the file patches in a unified diff
+pub fn parse_patch(patch: &str) -> Result<Vec<FilePatch>> {
+    let lines = split_lines(patch);
+    let mut patches: Vec<FilePatch> = Vec::new();
+    // Whether the last patch started with a `diff --git` header that its
+    // ---/+++ lines belong to
+    let mut in_git_header = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim_end_matches(['\n', '\r']);
+
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let (old, new) = parse_git_header(rest);
+            patches.push(FilePatch {
+                old_path: old,
+                new_path: new,
+                hunks: Vec::new(),
+                binary: false,
+            });
+            in_git_header = true;
+        } else if line.starts_with("--- ")
+            && lines
+                .get(i + 1)
+                .is_some_and(|next| next.starts_with("+++ "))
+        {
+            let old = parse_file_header(&line[4..]);
+            let new = parse_file_header(lines[i + 1][4..].trim_end_matches(['\n', '\r']));
+            match patches.last_mut() {
+                Some(current) if in_git_header => {
+                    current.old_path = old;
+                    current.new_path = new;
+                }
+                _ => patches.push(FilePatch {
+                    old_path: old,
+                    new_path: new,
+                    hunks: Vec::new(),
+                    binary: false,
+                }),
+            }
+            in_git_header = false;
+            i += 1;
+        } else if line.starts_with("@@ ") {
+            let current = patches
+                .last_mut()
+                .with_context(|| format!("Hunk without a file header at line {}", i + 1))?;
+            let (hunk, consumed) = parse_hunk(&lines[i..])
+                .with_context(|| format!("Malformed hunk at line {}", i + 1))?;
+            current.hunks.push(hunk);
+            in_git_header = false;
+            i += consumed;
+            continue;
+        } else if let Some(current) = patches.last_mut().filter(|_| in_git_header) {
+            if line.starts_with("new file") {
+                current.old_path = None;
+            } else if line.starts_with("deleted file") {
+                current.new_path = None;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                current.old_path = Some(PathBuf::from(from));
+            } else if let Some(to) = line.strip_prefix("rename to ") {
+                current.new_path = Some(PathBuf::from(to));
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                current.binary = true;
+            }
+        }
+
+        i += 1;
+    }
+
+    Ok(patches)
+}
+
+/// Paths from "a/old b/new"
+fn parse_git_header(rest: &str) -> (Option<PathBuf>, Option<PathBuf>) {
+    match rest.split_once(" b/") {
+        Some((old, new)) => (
+            Some(PathBuf::from(old.strip_prefix("a/").unwrap_or(old))),
+            Some(PathBuf::from(new)),
+        ),
+        None => (None, None),
+    }
+}
+
+/// Path from a ---/+++ line, without the a/ or b/ prefix or a trailing timestamp
+fn parse_file_header(name: &str) -> Option<PathBuf> {
+    let name = name.split('\t').next().unwrap_or(name).trim_end();
+    if name == "/dev/null" {
+        return None;
+    }
+    let name = name
+        .strip_prefix("a/")
+        .or_else(|| name.strip_prefix("b/"))
+        .unwrap_or(name);
+    Some(PathBuf::from(name))
+}
+
+/// Parse one hunk starting at its @@ line; returns it and the lines consumed
+fn parse_hunk(lines: &[&str]) -> Result<(PatchHunk, usize)> {
+    let header = lines[0].trim_end();
+    let ranges = header
+        .strip_prefix("@@ -")
+        .and_then(|rest| rest.split_once(" @@"))
+        .map(|(ranges, _)| ranges)
+        .context("Bad hunk header")?;
+    let (old, new) = ranges.split_once(" +").context("Bad hunk header")?;
+    let (old_start, old_len) = parse_range(old)?;
+    let (new_start, new_len) = parse_range(new)?;
+
+    let mut hunk = PatchHunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+        lines: Vec::new(),
+    };
+
+    let (mut old_seen, mut new_seen) = (0, 0);
+    let mut i = 1;
+    while old_seen < old_len || new_seen < new_len {
+        let Some(&line) = lines.get(i) else {
+            bail!("Hunk ends early");
+        };
+        // Mailers sometimes strip the space from empty context lines
+        let (prefix, text) = match line.chars().next() {
+            Some('\n') | Some('\r') => (' ', line),
+            Some(c @ (' ' | '-' | '+')) => (c, &line[1..]),
+            Some('\\') => {
+                strip_newline(&mut hunk);
+                i += 1;
+                continue;
+            }
+            _ => bail!("Unexpected line in hunk: {}", line.trim_end()),
+        };
+        match prefix {
+            ' ' => {
+                old_seen += 1;
+                new_seen += 1;
+            }
+            '-' => old_seen += 1,
+            _ => new_seen += 1,
+        }
+        hunk.lines.push((prefix, text.to_string()));
+        i += 1;
+    }
+
+    // A "\ No newline at end of file" marker applies to the line before it
+    if lines.get(i).is_some_and(|l| l.starts_with('\\')) {
+        strip_newline(&mut hunk);
+        i += 1;
+    }
+
+    Ok((hunk, i))
+}
+
+fn strip_newline(hunk: &mut PatchHunk) {
+    if let Some((_, line)) = hunk.lines.last_mut() {
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+    }
+}
+
+fn parse_range(range: &str) -> Result<(usize, usize)> {
+    let (start, len) = match range.split_once(',') {
+        Some((start, len)) => (start, len.parse()?),
+        None => (range, 1),
+    };
+    Ok((start.parse()?, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn hunk(old_start: usize, lines: &[&str]) -> PatchHunk {
+        let lines: Vec<(char, String)> = lines
+            .iter()
+            .map(|l| (l.chars().next().unwrap(), format!("{}\n", &l[1..])))
+            .collect();
+        let old_len = lines.iter().filter(|(p, _)| *p != '+').count();
+        let new_len = lines.iter().filter(|(p, _)| *p != '-').count();
+        PatchHunk {
+            old_start,
+            old_len,
+            new_start: old_start,
+            new_len,
+            lines,
+        }
+    }
+
+    #[test]
+    fn test_apply_hunks_with_offset_fuzz_and_reject() {
+        let content = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+
+        // Header says line 2 but the context is at line 4
+        let (out, outcomes) = apply_hunks(content, &[hunk(2, &[" 3", "-4", "+four", " 5"])], 0);
+        assert_eq!(out, "0\n1\n2\n3\nfour\n5\n6\n7\n8\n9\n");
+        assert_eq!(
+            outcomes,
+            vec![HunkOutcome::Applied {
+                line: 4,
+                offset: 2,
+                fuzz: 0
+            }]
+        );
+
+        // Stale outer context only applies with fuzz
+        let stale = hunk(7, &[" x", "-7", "+seven", " y"]);
+        let (_, outcomes) = apply_hunks(content, std::slice::from_ref(&stale), 0);
+        assert_eq!(outcomes, vec![HunkOutcome::Rejected]);
+        let (out, outcomes) = apply_hunks(content, &[stale], 1);
+        assert!(out.contains("6\nseven\n8\n"));
+        assert!(matches!(outcomes[0], HunkOutcome::Applied { fuzz: 1, .. }));
+
+        // Reversing a hunk undoes it
+        let forward = hunk(2, &[" 1", "-2", "+two", "+extra", " 3"]);
+        let (patched, _) = apply_hunks(content, std::slice::from_ref(&forward), 0);
+        let (restored, _) = apply_hunks(&patched, &[forward.reversed()], 0);
+        assert_eq!(restored, content);
+    }
+
+    #[test]
+    fn test_parse_email_patch() -> Result<()> {
+        let patch = "From: Someone <someone@example.com>\n\
+                     Subject: [PATCH] Tweak\n\
+                     \n\
+                     diff --git a/old.txt b/new.txt\n\
+                     similarity index 90%\n\
+                     rename from old.txt\n\
+                     rename to new.txt\n\
+                     --- a/old.txt\n\
+                     +++ b/new.txt\n\
+                     @@ -1,2 +1,2 @@\n\
+                     \x20keep\n\
+                     -old\n\
+                     +new\n\
+                     \\ No newline at end of file\n\
+                     diff --git a/logo.png b/logo.png\n\
+                     Binary files a/logo.png and b/logo.png differ\n\
+                     -- \n\
+                     2.40.0\n";
+
+        let patches = parse_patch(patch)?;
+        assert_eq!(patches.len(), 2);
+        assert_eq!(patches[0].old_path, Some(PathBuf::from("old.txt")));
+        assert_eq!(patches[0].new_path, Some(PathBuf::from("new.txt")));
+        assert_eq!(
+            patches[0].hunks[0].lines,
+            vec![
+                (' ', "keep\n".to_string()),
+                ('-', "old\n".to_string()),
+                ('+', "new".to_string())
+            ]
+        );
+        assert!(patches[1].binary);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_to_working_tree_with_rejects_and_reverse() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        Command::new("git")
+            .args(["init"])
+            .current_dir(repo)
+            .output()?;
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        fs::write(repo.join("a.txt"), "one\ntwo\nthree\n")?;
+        fs::write(repo.join("gone.txt"), "bye\n")?;
+
+        let patch = "--- a/a.txt\n+++ b/a.txt\n\
+                     @@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n\
+                     @@ -10,2 +10,2 @@\n missing\n-line\n+LINE\n\
+                     --- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+fresh\n\
+                     --- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n";
+
+        let report = apply(repo, patch, &ApplyOptions::default())?;
+        assert!(!report.is_clean());
+        assert_eq!(report.files[0].rejected(), 1);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "one\n2\nthree\n");
+        assert!(fs::read_to_string(repo.join("a.txt.rej"))?.contains("+LINE\n"));
+        assert_eq!(fs::read_to_string(repo.join("new.txt"))?, "fresh\n");
+        assert!(!repo.join("gone.txt").exists());
+
+        // Checking doesn't touch the working tree
+        let check = ApplyOptions {
+            reverse: true,
+            check: true,
+            ..Default::default()
+        };
+        apply(repo, patch, &check)?;
+        assert!(repo.join("new.txt").exists());
+
+        // Reverse the hunks that applied
+        let applied = "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n\
+                       --- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+fresh\n";
+        let reverse = ApplyOptions {
+            reverse: true,
+            ..Default::default()
+        };
+        assert!(apply(repo, applied, &reverse)?.is_clean());
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "one\ntwo\nthree\n");
+        assert!(!repo.join("new.txt").exists());
+
+        Ok(())
+    }
+}
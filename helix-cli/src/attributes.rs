@@ -0,0 +1,288 @@
+/*
+Per-path settings from .helixattributes, the Helix counterpart of .gitattributes.
+
+Each line of .helixattributes at the root of the working tree is a pattern and
+the attributes it sets:
+
+  # comments and blank lines are skipped
+  *.png        binary
+  *.psd        binary lockable -chunk
+  CHANGELOG.md merge=union
+  /vendor/     merge=theirs
+  *.bin        filter=lfs
+
+  attr         set
+  -attr        unset
+  !attr        back to unspecified
+  attr=value   set to a value
+
+A pattern without a '/' matches the file name at any depth; one with a '/' is
+matched from the root, a leading '/' only anchoring it, and a trailing '/'
+matches everything under a directory. When several lines match a path, later
+lines win attribute by attribute. Unknown attributes are kept out of the way
+so newer files still load.
+
+  text / -text   diff as text, or summarize as binary, whatever the content
+  binary         -text and merge=binary
+  merge=<driver> text (default), binary, ours, theirs or union (see merge_command)
+  filter=<name>  content filter, for clean/smudge workflows such as LFS
+  -chunk         store and send whole, never as a delta on an earlier version
+  lockable       must be locked before it is edited
+
+`helix add` records text, binary, lockable, -chunk and filtered in each index
+entry's flags, so index-only workflows see them without rereading the file.
+Diff, merge and push look paths up here directly, since they also work on
+commits whose paths aren't in the index.
+*/
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::path::Path;
+
+use crate::helix_index::format::EntryFlags;
+
+pub const ATTRIBUTES_FILE: &str = ".helixattributes";
+
+/// How conflicting changes to a path are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeDriver {
+    /// Both changed the file: conflict, resolved by hand
+    Text,
+    /// As Text, but the content is never shown with conflict markers
+    Binary,
+    /// Keep the target's version
+    Ours,
+    /// Keep the sandbox's (incoming) version
+    Theirs,
+    /// Keep the target's version plus the lines the sandbox added
+    Union,
+}
+
+impl MergeDriver {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "binary" => Some(Self::Binary),
+            "ours" => Some(Self::Ours),
+            "theirs" => Some(Self::Theirs),
+            "union" => Some(Self::Union),
+            _ => None,
+        }
+    }
+}
+
+/// The attributes in effect for one path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathAttributes {
+    /// Some(true) for text, Some(false) for binary, None to look at the content
+    pub text: Option<bool>,
+    pub merge: Option<MergeDriver>,
+    pub filter: Option<String>,
+    /// Some(false) when the file is always stored and sent whole
+    pub chunk: Option<bool>,
+    pub lockable: bool,
+}
+
+impl PathAttributes {
+    /// The flags `helix add` records on the path's index entry
+    pub fn entry_flags(&self) -> EntryFlags {
+        let mut flags = EntryFlags::empty();
+        match self.text {
+            Some(true) => flags |= EntryFlags::ATTR_TEXT,
+            Some(false) => flags |= EntryFlags::ATTR_BINARY,
+            None => {}
+        }
+        if self.lockable {
+            flags |= EntryFlags::ATTR_LOCKABLE;
+        }
+        if self.chunk == Some(false) {
+            flags |= EntryFlags::ATTR_NO_CHUNK;
+        }
+        if self.filter.is_some() {
+            flags |= EntryFlags::ATTR_FILTERED;
+        }
+        flags
+    }
+
+    /// Whether content should be treated as binary, deciding by `is_binary` when the
+    /// attributes don't say
+    pub fn is_binary(&self, content: &[u8]) -> bool {
+        self.text
+            .map(|text| !text)
+            .unwrap_or_else(|| crate::binary::is_binary(content))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Set,
+    Unset,
+    Unspecified,
+    Value(String),
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    matcher: GlobMatcher,
+    settings: Vec<(String, State)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Attributes {
+    rules: Vec<Rule>,
+}
+
+impl Attributes {
+    /// Read .helixattributes from the root of `workdir`. A missing or unreadable file
+    /// gives no attributes.
+    pub fn load(workdir: &Path) -> Self {
+        fs::read_to_string(workdir.join(ATTRIBUTES_FILE))
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let rules = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let matcher = glob_for(fields.next()?)?;
+                let settings = fields.flat_map(setting).collect();
+                Some(Rule { matcher, settings })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The attributes for a repository-relative path
+    pub fn for_path(&self, path: &Path) -> PathAttributes {
+        let mut text = None;
+        let mut merge = None;
+        let mut filter = None;
+        let mut chunk = None;
+        let mut lockable = false;
+
+        for rule in self.rules.iter().filter(|r| r.matcher.is_match(path)) {
+            for (name, state) in &rule.settings {
+                match (name.as_str(), state) {
+                    ("text", State::Set) => text = Some(true),
+                    ("text", State::Unset) => text = Some(false),
+                    ("text", _) => text = None,
+                    ("merge", State::Value(driver)) => merge = MergeDriver::parse(driver),
+                    ("merge", State::Unset) => merge = Some(MergeDriver::Binary),
+                    ("merge", _) => merge = None,
+                    ("filter", State::Value(name)) => filter = Some(name.clone()),
+                    ("filter", _) => filter = None,
+                    ("chunk", State::Set) => chunk = Some(true),
+                    ("chunk", State::Unset) => chunk = Some(false),
+                    ("chunk", _) => chunk = None,
+                    ("lockable", state) => lockable = *state == State::Set,
+                    _ => {}
+                }
+            }
+        }
+
+        PathAttributes {
+            text,
+            merge,
+            filter,
+            chunk,
+            lockable,
+        }
+    }
+}
+
+/// One attribute on a line, with `binary` expanded to what it stands for
+fn setting(field: &str) -> Vec<(String, State)> {
+    let (name, state) = if let Some(name) = field.strip_prefix('-') {
+        (name, State::Unset)
+    } else if let Some(name) = field.strip_prefix('!') {
+        (name, State::Unspecified)
+    } else if let Some((name, value)) = field.split_once('=') {
+        (name, State::Value(value.to_string()))
+    } else {
+        (field, State::Set)
+    };
+
+    if name == "binary" && state == State::Set {
+        return vec![
+            ("text".to_string(), State::Unset),
+            ("merge".to_string(), State::Value("binary".to_string())),
+        ];
+    }
+    vec![(name.to_string(), state)]
+}
+
+fn glob_for(pattern: &str) -> Option<GlobMatcher> {
+    let (pattern, dir) = match pattern.strip_suffix('/') {
+        Some(dir) => (dir, true),
+        None => (pattern, false),
+    };
+    let mut glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{pattern}"),
+    };
+    if dir {
+        glob.push_str("/**");
+    }
+    GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .ok()
+        .map(|g| g.compile_matcher())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_later_lines_win_per_attribute() -> Result<()> {
+        let attributes = Attributes::parse(
+            "# assets\n\
+             *.png binary lockable\n\
+             /vendor/ merge=theirs -chunk\n\
+             docs/*.md text merge=union\n\
+             vendor/logo.png text !lockable filter=lfs\n",
+        );
+
+        let png = attributes.for_path(Path::new("art/icon.png"));
+        assert_eq!(png.text, Some(false));
+        assert_eq!(png.merge, Some(MergeDriver::Binary));
+        assert!(png.lockable);
+        assert!(png.is_binary(b"plain text"));
+        assert_eq!(
+            png.entry_flags(),
+            EntryFlags::ATTR_BINARY | EntryFlags::ATTR_LOCKABLE
+        );
+
+        let logo = attributes.for_path(Path::new("vendor/logo.png"));
+        assert_eq!(logo.text, Some(true));
+        assert_eq!(logo.merge, Some(MergeDriver::Theirs));
+        assert_eq!(logo.filter.as_deref(), Some("lfs"));
+        assert_eq!(logo.chunk, Some(false));
+        assert!(!logo.lockable);
+
+        // Patterns with a slash are matched from the root, and don't cross directories
+        assert_eq!(
+            attributes.for_path(Path::new("docs/guide.md")).merge,
+            Some(MergeDriver::Union)
+        );
+        assert_eq!(
+            attributes.for_path(Path::new("docs/api/index.md")),
+            PathAttributes::default()
+        );
+        assert_eq!(
+            attributes.for_path(Path::new("src/vendor/lib.rs")),
+            PathAttributes::default()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+/*
+Who a new commit is attributed to.
+
+Every command that writes a commit (commit, amend, sandbox commits and
+merges, pull's merge commits) and every place that reports the identity
+(push, doctor) goes through `resolve_author`, so they all agree. Importing
+from Git copies user.name/user.email into helix.toml only when it doesn't
+already name a user. Sources, highest precedence first:
+
+1. `--author "Name <email>"` on the command line
+2. HELIX_AUTHOR_NAME / HELIX_AUTHOR_EMAIL
+3. `[user]` in the repository's helix.toml
+4. `[user]` in the global ~/.helix.toml
+
+The name and email are resolved separately, so HELIX_AUTHOR_EMAIL alone can
+swap the address on a name that comes from config. An email is optional;
+a name is not. When nothing provides one, the error lists every place that
+was checked.
+*/
+use crate::init_command::UserConfig;
+use crate::repo_config;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const AUTHOR_NAME_ENV: &str = "HELIX_AUTHOR_NAME";
+pub const AUTHOR_EMAIL_ENV: &str = "HELIX_AUTHOR_EMAIL";
+
+/// The parts of ~/.helix.toml we read here; other keys are left to `Config`
+#[derive(Deserialize)]
+struct GlobalUser {
+    user: Option<UserConfig>,
+}
+
+/// The author for a new commit, as "Name <email>" (or just "Name")
+pub fn resolve_author(repo_path: &Path, flag: Option<&str>) -> Result<String> {
+    let global = dirs::home_dir().map(|home| home.join(".helix.toml"));
+    resolve(
+        flag,
+        |name| std::env::var(name).ok(),
+        &repo_path.join(repo_config::CONFIG_FILE),
+        global.as_deref(),
+    )
+}
+
+fn resolve(
+    flag: Option<&str>,
+    env: impl Fn(&str) -> Option<String>,
+    repo_config_path: &Path,
+    global_config_path: Option<&Path>,
+) -> Result<String> {
+    if let Some(author) = flag.map(str::trim).filter(|a| !a.is_empty()) {
+        return Ok(author.to_string());
+    }
+
+    let env_user = UserConfig {
+        name: env(AUTHOR_NAME_ENV),
+        email: env(AUTHOR_EMAIL_ENV),
+    };
+    let repo_user = read_user(repo_config_path, true)?;
+    let global_user = match global_config_path {
+        Some(path) => read_user(path, false)?,
+        None => None,
+    };
+
+    let sources = [Some(&env_user), repo_user.as_ref(), global_user.as_ref()];
+    let pick = |field: fn(&UserConfig) -> &Option<String>| {
+        sources
+            .iter()
+            .flatten()
+            .filter_map(|user| field(user).as_deref().map(str::trim))
+            .find(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    match (pick(|u| &u.name), pick(|u| &u.email)) {
+        (Some(name), Some(email)) => Ok(format!("{} <{}>", name, email)),
+        (Some(name), None) => Ok(name),
+        (None, _) => bail!("{}", not_configured(repo_config_path, global_config_path)),
+    }
+}
+
+/// `[user]` from a config file, `None` if the file or section is missing
+fn read_user(path: &Path, is_repo_config: bool) -> Result<Option<UserConfig>> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(None);
+    };
+
+    if is_repo_config {
+        return Ok(repo_config::parse(&content)?.user);
+    }
+    let global: GlobalUser = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e.message()))?;
+    Ok(global.user)
+}
+
+fn not_configured(repo_config_path: &Path, global_config_path: Option<&Path>) -> String {
+    let global = global_config_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("~/.helix.toml"));
+
+    let mut message = String::from("Author not configured. Checked, in order:\n");
+    let _ = writeln!(message, "  1. --author");
+    let _ = writeln!(message, "  2. {} / {}", AUTHOR_NAME_ENV, AUTHOR_EMAIL_ENV);
+    let _ = writeln!(message, "  3. [user] in {}", repo_config_path.display());
+    let _ = writeln!(message, "  4. [user] in {}", global.display());
+    message.push_str(
+        "\nSet one of them, for example in helix.toml:\n\
+         \n\
+         [user]\n\
+         name = \"Your Name\"\n\
+         email = \"you@example.com\"",
+    );
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolve_author_precedence() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_config = temp_dir.path().join("helix.toml");
+        let global_config = temp_dir.path().join("global.toml");
+
+        let no_env = |_: &str| None;
+        let err = resolve(None, no_env, &repo_config, Some(&global_config)).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("--author"));
+        assert!(message.contains(AUTHOR_NAME_ENV));
+        assert!(message.contains(&repo_config.display().to_string()));
+        assert!(message.contains(&global_config.display().to_string()));
+
+        fs::write(
+            &global_config,
+            "model = \"x\"\n[user]\nname = \"Global\"\nemail = \"global@example.com\"\n",
+        )?;
+        assert_eq!(
+            resolve(None, no_env, &repo_config, Some(&global_config))?,
+            "Global <global@example.com>"
+        );
+
+        fs::write(&repo_config, "[user]\nname = \"Repo\"\n")?;
+        assert_eq!(
+            resolve(None, no_env, &repo_config, Some(&global_config))?,
+            "Repo <global@example.com>"
+        );
+
+        let env: HashMap<&str, &str> = [(AUTHOR_EMAIL_ENV, "env@example.com")].into();
+        let env = |name: &str| env.get(name).map(|v| v.to_string());
+        assert_eq!(
+            resolve(None, env, &repo_config, Some(&global_config))?,
+            "Repo <env@example.com>"
+        );
+
+        assert_eq!(
+            resolve(
+                Some("Flag <flag@example.com>"),
+                env,
+                &repo_config,
+                Some(&global_config)
+            )?,
+            "Flag <flag@example.com>"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+/*
+Binary content detection.
+
+A file is treated as binary when its first 8000 bytes contain a NUL, the same
+heuristic Git uses. Binary files are never line-ending converted, and diffs
+summarize them instead of printing their bytes:
+
+  Binary files a/logo.png and b/logo.png differ (12.0 KiB -> 14.5 KiB, +2.5 KiB)
+*/
+
+/// How many leading bytes to scan for a NUL when deciding if content is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// One-line description of a change to a binary file. `None` sizes mean the
+/// file didn't exist on that side.
+pub fn binary_change_summary(
+    old_path: &str,
+    new_path: &str,
+    old_size: Option<u64>,
+    new_size: Option<u64>,
+) -> String {
+    let old_name = match old_size {
+        Some(_) => format!("a/{}", old_path),
+        None => "/dev/null".to_string(),
+    };
+    let new_name = match new_size {
+        Some(_) => format!("b/{}", new_path),
+        None => "/dev/null".to_string(),
+    };
+
+    let old_size = old_size.unwrap_or(0);
+    let new_size = new_size.unwrap_or(0);
+    let delta = new_size as i64 - old_size as i64;
+    let sign = if delta < 0 { "-" } else { "+" };
+
+    format!(
+        "Binary files {} and {} differ ({} -> {}, {}{})",
+        old_name,
+        new_name,
+        format_size(old_size),
+        format_size(new_size),
+        sign,
+        format_size(delta.unsigned_abs())
+    )
+}
+
+/// Human-readable byte count
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary(b""));
+        assert!(is_binary(b"PNG\0\x01\x02"));
+
+        // Only the first 8000 bytes are checked
+        let mut late_nul = vec![b'a'; BINARY_SNIFF_LEN];
+        late_nul.push(0);
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn test_binary_change_summary() {
+        assert_eq!(
+            binary_change_summary(
+                "logo.png",
+                "logo.png",
+                Some(12 * 1024),
+                Some(14 * 1024 + 512)
+            ),
+            "Binary files a/logo.png and b/logo.png differ (12.0 KiB -> 14.5 KiB, +2.5 KiB)"
+        );
+        assert_eq!(
+            binary_change_summary("data.bin", "data.bin", Some(100), None),
+            "Binary files a/data.bin and /dev/null differ (100 B -> 0 B, -100 B)"
+        );
+    }
+}
@@ -0,0 +1,405 @@
+/*
+`helix bisect` - find the commit that introduced a regression by binary search.
+
+  helix bisect start [BAD [GOOD...]]   begin, optionally marking commits at once
+  helix bisect bad [REV]               REV (default HEAD) has the regression
+  helix bisect good [REV...]           these commits don't
+  helix bisect skip [REV...]           these can't be tested; pick another
+  helix bisect run CMD [ARGS...]       mark each candidate by CMD's exit status
+  helix bisect reset                   go back to where `start` was run
+
+Once there is a bad commit and at least one good one, the candidates are the
+commits the bad one has and no good one does. Each step checks out (detached)
+the candidate whose own ancestry holds closest to half of them, so every answer
+halves what is left; the search ends when the bad commit is the only candidate.
+Skipped commits are never checked out, and if only skipped ones stand between
+a good and the bad commit they are all reported as possibly first bad.
+
+State lives in .helix/bisect: `start` holds HEAD as it was, `bad` the bad
+commit, `good` and `skip` one commit per line. `reset` restores HEAD, checks
+its commit out again and deletes the directory.
+
+`run` uses the same exit codes as git bisect run: 0 is good, 125 is skip, 1 to
+127 is bad, and anything else (or a signal) stops the search.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::commit::parse_commit_for_walk;
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::checkout::{checkout_tree_to_path, CheckoutOptions};
+use crate::diff_command::resolve_revision;
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::commit::{read_head, CommitStore};
+use crate::sandbox_command::update_index_from_commit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Good,
+    Bad,
+    Skip,
+}
+
+/// Where a bisection stands after the commits marked so far
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Still waiting for a bad commit, or for a good one
+    NeedMarks,
+    /// Test this commit next; `remaining` commits could still be the first bad one
+    Test { commit: Hash, remaining: usize },
+    /// The first bad commit
+    Found(Hash),
+    /// The first bad commit is one of these, but all except the bad one were skipped
+    OnlySkipped(Vec<Hash>),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// .helix/HEAD when the bisection started
+    start: String,
+    bad: Option<Hash>,
+    good: Vec<Hash>,
+    skip: Vec<Hash>,
+}
+
+fn bisect_dir(repo_path: &Path) -> PathBuf {
+    repo_path.join(".helix").join("bisect")
+}
+
+impl State {
+    fn load(repo_path: &Path) -> Result<Self> {
+        let dir = bisect_dir(repo_path);
+        let start = fs::read_to_string(dir.join("start"))
+            .map_err(|_| anyhow::anyhow!("Not bisecting. Start with 'helix bisect start'"))?;
+        let hashes = |name: &str| -> Result<Vec<Hash>> {
+            match fs::read_to_string(dir.join(name)) {
+                Ok(content) => content.lines().map(|l| hex_to_hash(l.trim())).collect(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+                Err(e) => Err(e.into()),
+            }
+        };
+        Ok(Self {
+            start: start.trim().to_string(),
+            bad: hashes("bad")?.pop(),
+            good: hashes("good")?,
+            skip: hashes("skip")?,
+        })
+    }
+
+    fn save(&self, repo_path: &Path) -> Result<()> {
+        let dir = bisect_dir(repo_path);
+        fs::create_dir_all(&dir)?;
+        let lines =
+            |hashes: &[Hash]| -> String { hashes.iter().map(|h| hash_to_hex(h) + "\n").collect() };
+        fs::write(dir.join("start"), format!("{}\n", self.start))?;
+        fs::write(dir.join("bad"), lines(self.bad.as_slice()))?;
+        fs::write(dir.join("good"), lines(&self.good))?;
+        fs::write(dir.join("skip"), lines(&self.skip))?;
+        Ok(())
+    }
+
+    fn mark(&mut self, mark: Mark, commit: Hash) {
+        match mark {
+            Mark::Bad => self.bad = Some(commit),
+            Mark::Good if !self.good.contains(&commit) => self.good.push(commit),
+            Mark::Skip if !self.skip.contains(&commit) => self.skip.push(commit),
+            _ => {}
+        }
+    }
+}
+
+/// Begin a bisection at the current HEAD, optionally with the bad commit and good ones
+pub fn start(repo_path: &Path, bad: Option<&str>, good: &[String]) -> Result<()> {
+    if bisect_dir(repo_path).join("start").exists() {
+        bail!("Already bisecting. Run 'helix bisect reset' first");
+    }
+    let index = HelixIndexData::load_or_rebuild(repo_path)?;
+    if !index.get_staged().is_empty() {
+        bail!(
+            "You have staged changes. Commit them first, since bisecting checks out other commits"
+        );
+    }
+
+    let head =
+        fs::read_to_string(repo_path.join(".helix").join("HEAD")).context("Failed to read HEAD")?;
+    let mut state = State {
+        start: head.trim().to_string(),
+        ..Default::default()
+    };
+    if let Some(bad) = bad {
+        state.mark(Mark::Bad, resolve_revision(repo_path, bad)?);
+    }
+    for rev in good {
+        state.mark(Mark::Good, resolve_revision(repo_path, rev)?);
+    }
+    state.save(repo_path)?;
+    advance(repo_path, &state)
+}
+
+/// Mark commits (HEAD when `revs` is empty) and move on to the next candidate
+pub fn mark(repo_path: &Path, mark: Mark, revs: &[String]) -> Result<()> {
+    let mut state = State::load(repo_path)?;
+    let revs = if revs.is_empty() {
+        vec!["HEAD".to_string()]
+    } else {
+        revs.to_vec()
+    };
+    for rev in &revs {
+        state.mark(mark, resolve_revision(repo_path, rev)?);
+    }
+    state.save(repo_path)?;
+    advance(repo_path, &state)
+}
+
+/// Go back to the HEAD the bisection started from
+pub fn reset(repo_path: &Path) -> Result<()> {
+    let state = State::load(repo_path)?;
+    let current = read_head(repo_path).ok();
+    fs::write(
+        repo_path.join(".helix").join("HEAD"),
+        format!("{}\n", state.start),
+    )?;
+    let original = read_head(repo_path)?;
+    checkout(repo_path, current, original, false)?;
+    fs::remove_dir_all(bisect_dir(repo_path))?;
+    println!(
+        "Bisect reset; back at {}",
+        state.start.strip_prefix("ref: ").unwrap_or(&state.start)
+    );
+    Ok(())
+}
+
+/// Test each candidate with `command`, marking it by the exit status, until the first
+/// bad commit is found
+pub fn run(repo_path: &Path, command: &[String]) -> Result<()> {
+    let (program, args) = command.split_first().context("No command to run")?;
+    loop {
+        let state = State::load(repo_path)?;
+        let commit = match next_step(repo_path, &state)? {
+            Step::Test { commit, .. } => commit,
+            Step::NeedMarks => {
+                bail!("'bisect run' needs a bad and a good commit; mark them first")
+            }
+            Step::Found(_) | Step::OnlySkipped(_) => return Ok(()),
+        };
+        if read_head(repo_path)? != commit {
+            checkout(repo_path, read_head(repo_path).ok(), commit, true)?;
+        }
+
+        println!("running {}", command.join(" "));
+        let status = Command::new(program)
+            .args(args)
+            .current_dir(repo_path)
+            .status()
+            .with_context(|| format!("Failed to run {program}"))?;
+        let result = match status.code() {
+            Some(0) => Mark::Good,
+            Some(125) => Mark::Skip,
+            Some(1..=127) => Mark::Bad,
+            _ => bail!("{program} exited with {status}; stopping the bisection"),
+        };
+        mark(repo_path, result, &[hash_to_hex(&commit)])?;
+    }
+}
+
+/// Print where the bisection stands and check out the next commit to test
+fn advance(repo_path: &Path, state: &State) -> Result<()> {
+    let store = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+    match next_step(repo_path, state)? {
+        Step::NeedMarks if state.bad.is_none() => {
+            println!("Mark a commit with the regression: helix bisect bad [REV]")
+        }
+        Step::NeedMarks => println!("Mark a commit without it: helix bisect good REV"),
+        Step::Test { commit, remaining } => {
+            let steps = usize::BITS - remaining.leading_zeros();
+            println!(
+                "Bisecting: {} revisions left to test after this (roughly {} steps)",
+                remaining.saturating_sub(1) / 2,
+                steps.saturating_sub(1)
+            );
+            checkout(repo_path, read_head(repo_path).ok(), commit, true)?;
+            let summary = store.read_commit(&commit)?.summary().to_string();
+            println!("[{}] {}", &hash_to_hex(&commit)[..8], summary);
+        }
+        Step::Found(commit) => {
+            println!("{} is the first bad commit", hash_to_hex(&commit));
+            println!("{}", store.read_commit(&commit)?.format(&commit));
+        }
+        Step::OnlySkipped(commits) => {
+            println!("There are only skipped commits left to test.");
+            println!("The first bad commit could be any of:");
+            for commit in commits {
+                println!("{}", hash_to_hex(&commit));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn next_step(repo_path: &Path, state: &State) -> Result<Step> {
+    let Some(bad) = state.bad else {
+        return Ok(Step::NeedMarks);
+    };
+    if state.good.is_empty() {
+        return Ok(Step::NeedMarks);
+    }
+    bisect_step(
+        &FsObjectStore::new(repo_path),
+        bad,
+        &state.good,
+        &state.skip,
+    )
+}
+
+/// The next step for a search between `good` commits and the `bad` one
+pub fn bisect_step(store: &FsObjectStore, bad: Hash, good: &[Hash], skip: &[Hash]) -> Result<Step> {
+    let known_good = ancestors(store, good.to_vec(), &|_| true)?;
+    if known_good.contains_key(&bad) {
+        bail!(
+            "Bad commit {} is an ancestor of a good commit; check which is which",
+            &hash_to_hex(&bad)[..8]
+        );
+    }
+    // Every commit that could be the first bad one, with its parents among them
+    let candidates = ancestors(store, vec![bad], &|hash| !known_good.contains_key(hash))?;
+
+    let skipped: HashSet<&Hash> = skip.iter().collect();
+    let testable: Vec<Hash> = candidates
+        .keys()
+        .filter(|hash| **hash != bad && !skipped.contains(hash))
+        .copied()
+        .collect();
+    if testable.is_empty() {
+        if candidates.len() == 1 {
+            return Ok(Step::Found(bad));
+        }
+        let mut commits: Vec<Hash> = candidates.into_keys().collect();
+        commits.sort();
+        return Ok(Step::OnlySkipped(commits));
+    }
+
+    // The candidate whose ancestry among the candidates is closest to half of them
+    let total = candidates.len();
+    let mut best: Option<(usize, Hash)> = None;
+    for commit in testable {
+        let below = ancestors_within(&candidates, commit);
+        let distance = (2 * below).abs_diff(total);
+        if best.is_none_or(|(d, h)| (distance, commit) < (d, h)) {
+            best = Some((distance, commit));
+        }
+    }
+    let (_, commit) = best.expect("testable is not empty");
+    Ok(Step::Test {
+        commit,
+        remaining: total,
+    })
+}
+
+/// Commits reachable from `tips` through commits `keep` accepts, with their parents
+fn ancestors(
+    store: &FsObjectStore,
+    tips: Vec<Hash>,
+    keep: &dyn Fn(&Hash) -> bool,
+) -> Result<HashMap<Hash, Vec<Hash>>> {
+    let mut seen = HashMap::new();
+    let mut queue = VecDeque::from(tips);
+    while let Some(hash) = queue.pop_front() {
+        if seen.contains_key(&hash) || !keep(&hash) {
+            continue;
+        }
+        let (_, parents) = parse_commit_for_walk(&store.read_object(&ObjectType::Commit, &hash)?)?;
+        queue.extend(parents.iter().copied());
+        seen.insert(hash, parents);
+    }
+    Ok(seen)
+}
+
+/// How many of `graph`'s commits `commit` reaches, itself included
+fn ancestors_within(graph: &HashMap<Hash, Vec<Hash>>, commit: Hash) -> usize {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([commit]);
+    while let Some(hash) = queue.pop_front() {
+        if let Some(parents) = graph.get(&hash) {
+            if seen.insert(hash) {
+                queue.extend(parents.iter().copied());
+            }
+        }
+    }
+    seen.len()
+}
+
+/// Bring the working tree and index from `from` to `commit`, detaching HEAD there
+/// when `detach` is set
+fn checkout(repo_path: &Path, from: Option<Hash>, commit: Hash, detach: bool) -> Result<()> {
+    if detach {
+        fs::write(
+            repo_path.join(".helix").join("HEAD"),
+            hash_to_hex(&commit) + "\n",
+        )?;
+    }
+    let options = CheckoutOptions {
+        verbose: false,
+        force: true,
+    };
+    checkout_tree_to_path(repo_path, &commit, from.as_ref(), repo_path, &options)?;
+    update_index_from_commit(repo_path, &commit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::commit::Commit;
+    use crate::helix_index::tree::{Tree, TreeStore};
+
+    #[test]
+    fn test_bisect_step_halves_the_range_and_handles_skips() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        let tree = TreeStore::for_repo(repo).write(&Tree::new())?;
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+
+        // A straight line of eight commits: 0 good, 7 bad
+        let mut line = Vec::new();
+        for i in 0..8 {
+            let parents = line.last().copied().into_iter().collect();
+            let commit = Commit::new(tree, parents, "T <t@x>".into(), format!("c{i}"));
+            line.push(commits.write_commit(&commit)?);
+        }
+        let store = FsObjectStore::new(repo);
+
+        let Step::Test { commit, remaining } = bisect_step(&store, line[7], &[line[0]], &[])?
+        else {
+            panic!("expected a commit to test");
+        };
+        assert_eq!(remaining, 7);
+        assert!(commit == line[3] || commit == line[4]);
+
+        // Regression came in with c5
+        assert_eq!(
+            bisect_step(&store, line[5], &[line[4]], &[])?,
+            Step::Found(line[5])
+        );
+        assert_eq!(
+            bisect_step(&store, line[6], &[line[4]], &[])?,
+            Step::Test {
+                commit: line[5],
+                remaining: 2
+            }
+        );
+        let mut skipped = vec![line[5], line[6]];
+        skipped.sort();
+        assert_eq!(
+            bisect_step(&store, line[6], &[line[4]], &[line[5]])?,
+            Step::OnlySkipped(skipped)
+        );
+
+        assert!(bisect_step(&store, line[2], &[line[4]], &[]).is_err());
+        Ok(())
+    }
+}
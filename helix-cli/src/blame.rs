@@ -0,0 +1,199 @@
+/*
+Line-by-line attribution of a file to the commits that last changed it.
+
+Starting from the file at a revision, each step diffs it against the same path
+in the first parent. Lines the diff reports as inserted belong to the commit;
+unchanged lines are carried back to their position in the parent's version and
+looked up there. A line is settled once a commit introduces it, or when the
+walk reaches a commit whose parent doesn't have the file (or a root commit).
+
+Only first parents are followed and renames are not tracked, so lines that
+came in through a merge or from a file's old name are attributed to the merge
+or the rename. Authors are shown as .helixmailmap maps them.
+*/
+use anyhow::{bail, Result};
+use helix_protocol::hash::Hash;
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::diff_command::resolve_revision;
+use crate::helix_index::commit::{Commit, CommitStore};
+use crate::mailmap::Mailmap;
+use crate::plumbing_command::lookup_path;
+use crate::promisor;
+use crate::sandbox_command::RepoContext;
+use crate::unified_diff::{diff_lines, split_lines, Op};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    /// 1-based line number in the blamed version
+    pub line: usize,
+    pub commit: Hash,
+    pub author: String,
+    pub author_time: u64,
+    /// The line without its terminator
+    pub text: String,
+}
+
+/// Blame `path` (relative to the repository root) as of `rev`, HEAD by default
+pub fn blame(repo_path: &Path, path: &Path, rev: Option<&str>) -> Result<Vec<BlameLine>> {
+    let context = RepoContext::detect(repo_path)?;
+    let store = FsObjectStore::new(&context.repo_root);
+    let commits = CommitStore::new(&context.repo_root, store.clone())?;
+
+    let start = resolve_revision(repo_path, rev.unwrap_or("HEAD"))?;
+    let mut commit = commits.read_commit(&start)?;
+    let Some(mut blob) = blob_at(&store, &commit, path) else {
+        bail!(
+            "'{}' does not exist in '{}'",
+            path.display(),
+            rev.unwrap_or("HEAD")
+        );
+    };
+
+    promisor::fetch_promised(&context.repo_root, [blob])?;
+    let content = store.read_object(&ObjectType::Blob, &blob)?;
+    let text = String::from_utf8_lossy(&content).into_owned();
+    let final_lines = split_lines(&text);
+
+    let mut owners: Vec<Option<Hash>> = vec![None; final_lines.len()];
+    // (line in the version being looked at, line in the blamed version)
+    let mut pending: Vec<(usize, usize)> = (0..final_lines.len()).map(|i| (i, i)).collect();
+    let mut current = content;
+    let mut seen: HashMap<Hash, Commit> = HashMap::new();
+
+    while !pending.is_empty() {
+        let parent = match commit.parents.first() {
+            Some(hash) => {
+                let parent = commits.read_commit(hash)?;
+                blob_at(&store, &parent, path).map(|b| (parent, b))
+            }
+            None => None,
+        };
+        let Some((parent, parent_blob)) = parent else {
+            for (_, line) in pending.drain(..) {
+                owners[line] = Some(commit.commit_hash);
+            }
+            break;
+        };
+
+        if parent_blob != blob {
+            promisor::fetch_promised(&context.repo_root, [parent_blob])?;
+            let parent_content = store.read_object(&ObjectType::Blob, &parent_blob)?;
+            let old_text = String::from_utf8_lossy(&parent_content);
+            let new_text = String::from_utf8_lossy(&current);
+            let old_lines = split_lines(&old_text);
+            let new_lines = split_lines(&new_text);
+
+            let mut to_parent: HashMap<usize, usize> = HashMap::new();
+            for op in diff_lines(&old_lines, &new_lines) {
+                if let Op::Equal { old, new } = op {
+                    to_parent.insert(new, old);
+                }
+            }
+
+            pending.retain_mut(|(at, line)| match to_parent.get(at) {
+                Some(&old) => {
+                    *at = old;
+                    true
+                }
+                None => {
+                    owners[*line] = Some(commit.commit_hash);
+                    false
+                }
+            });
+            current = parent_content;
+        }
+
+        seen.insert(commit.commit_hash, commit);
+        commit = parent;
+        blob = parent_blob;
+    }
+    seen.insert(commit.commit_hash, commit);
+
+    let mailmap = Mailmap::load(repo_path);
+    final_lines
+        .iter()
+        .zip(owners)
+        .enumerate()
+        .map(|(i, (text, owner))| {
+            let Some(commit) = owner.and_then(|hash| seen.get(&hash)) else {
+                bail!("Line {} was not attributed to a commit", i + 1);
+            };
+            Ok(BlameLine {
+                line: i + 1,
+                commit: commit.commit_hash,
+                author: mailmap.canonical(&commit.author),
+                author_time: commit.author_time,
+                text: text.trim_end_matches(['\n', '\r']).to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The blob at `path` in `commit`, or None when the path isn't a file there
+fn blob_at(store: &FsObjectStore, commit: &Commit, path: &Path) -> Option<Hash> {
+    match lookup_path(store, commit.tree_hash, path) {
+        Ok((ObjectType::Blob, hash)) => Some(hash),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::commit_command::{commit, CommitOptions};
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_blame_attributes_lines() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        let file = PathBuf::from("notes.txt");
+        let commit_with = |content: &str, message: &str| -> Result<Hash> {
+            fs::write(repo.join(&file), content)?;
+            stage_paths(repo, std::slice::from_ref(&file))?;
+            commit(
+                repo,
+                CommitOptions {
+                    message: message.into(),
+                    author: Some("Test User <test@example.com>".into()),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let first = commit_with("one\ntwo\nthree\n", "first")?;
+        let second = commit_with("one\n2\nthree\nfour\n", "second")?;
+
+        let lines = blame(repo, &file, None)?;
+        let owners: Vec<(&str, Hash)> = lines.iter().map(|l| (l.text.as_str(), l.commit)).collect();
+        assert_eq!(
+            owners,
+            vec![
+                ("one", first),
+                ("2", second),
+                ("three", first),
+                ("four", second)
+            ]
+        );
+        assert_eq!(lines[3].line, 4);
+
+        let at_first = blame(
+            repo,
+            &file,
+            Some(&helix_protocol::hash::hash_to_hex(&first)),
+        )?;
+        assert!(at_first.iter().all(|l| l.commit == first));
+        assert!(blame(repo, Path::new("missing.txt"), None).is_err());
+
+        Ok(())
+    }
+}
@@ -5,15 +5,27 @@
 //   helix branch <name>           - Create new branch
 //   helix branch -d <name>        - Delete branch
 //   helix branch -m <old> <new>   - Rename branch
+//   helix branch --list           - Print local branches, then remote-tracking ones
+//   helix switch <name>           - Switch to a branch or sandboxes/<name>
+//   helix switch -c <name>        - Create a branch at HEAD and switch to it
+//   helix switch <remote>/<name>  - Create <name> from the remote-tracking branch,
+//                                   tracking it, and switch to it (plain <name>
+//                                   works too when exactly one remote has it)
 
 use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 
 use crate::branch_tui;
+use crate::branch_tui::app::{reachable_commits, resolve_upstream};
+use crate::helix_index::commit::CommitStore;
 use crate::helix_index::state::{get_branch_upstream, remove_branch_state, set_branch_upstream};
 use crate::sandbox_command::RepoContext;
+use crate::say;
 use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::storage::FsObjectStore;
 
 pub struct BranchOptions {
     pub delete: bool,
@@ -72,13 +84,13 @@ pub fn create_branch(repo_path: &Path, name: &str, options: BranchOptions) -> Re
     }
 
     if options.verbose {
-        println!(
+        say!(
             "Created branch '{}' at commit {}",
             name,
             short_hash(&head_hash)
         );
     } else {
-        println!("Created branch '{}'", name);
+        say!("Created branch '{}'", name);
     }
 
     Ok(())
@@ -103,6 +115,23 @@ pub fn delete_branch(repo_path: &Path, name: &str, options: BranchOptions) -> Re
         ));
     }
 
+    if !options.force {
+        let orphaned = unmerged_commits(repo_path, name)?;
+        if !orphaned.is_empty() {
+            let mut msg = format!(
+                "Branch '{}' is not fully merged into HEAD or its upstream. \
+                 Deleting it would orphan {} commit(s):\n",
+                name,
+                orphaned.len()
+            );
+            for commit in &orphaned {
+                msg.push_str(&format!("  {}\n", commit));
+            }
+            msg.push_str("Use --force to delete it anyway.");
+            return Err(anyhow!(msg));
+        }
+    }
+
     // Delete the branch file
     fs::remove_file(&branch_path).with_context(|| format!("Failed to delete branch '{}'", name))?;
 
@@ -111,12 +140,46 @@ pub fn delete_branch(repo_path: &Path, name: &str, options: BranchOptions) -> Re
     }
 
     if options.verbose {
-        println!("Deleted branch '{}'", name);
+        say!("Deleted branch '{}'", name);
     }
 
     Ok(())
 }
 
+/// Commits reachable from the branch tip but not from HEAD or the branch's
+/// upstream, newest first, formatted as "<short hash> <summary>"
+fn unmerged_commits(repo_path: &Path, name: &str) -> Result<Vec<String>> {
+    let branch_path = repo_path.join(format!(".helix/refs/heads/{}", name));
+    let tip = hex_to_hash(fs::read_to_string(&branch_path)?.trim())?;
+
+    let storage = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+
+    let mut merged = HashSet::new();
+    if let Ok(head) = read_head(repo_path) {
+        merged.extend(reachable_commits(&storage, &head));
+    }
+    if let Some(upstream) = get_branch_upstream(repo_path, name)
+        .and_then(|upstream| resolve_upstream(repo_path, &upstream))
+    {
+        merged.extend(reachable_commits(&storage, &upstream));
+    }
+
+    if merged.contains(&tip) {
+        return Ok(Vec::new());
+    }
+
+    let mut orphaned: Vec<_> = reachable_commits(&storage, &tip)
+        .difference(&merged)
+        .filter_map(|hash| storage.read_commit(hash).ok().map(|c| (*hash, c)))
+        .collect();
+    orphaned.sort_by_key(|(_, commit)| std::cmp::Reverse(commit.commit_time));
+
+    Ok(orphaned
+        .iter()
+        .map(|(hash, commit)| format!("{} {}", short_hash(hash), commit.summary()))
+        .collect())
+}
+
 /// Rename a branch
 pub fn rename_branch(
     repo_path: &Path,
@@ -169,14 +232,64 @@ pub fn rename_branch(
     }
 
     if options.verbose {
-        println!("Renamed branch '{}' to '{}'", old_name, new_name);
+        say!("Renamed branch '{}' to '{}'", old_name, new_name);
     }
 
     Ok(())
 }
 
+#[derive(Debug, Default)]
+pub struct SwitchOptions {
+    /// Create the branch first (`-c`)
+    pub create: bool,
+    /// Create it, resetting an existing branch to HEAD (`-C`)
+    pub force_create: bool,
+    pub verbose: bool,
+}
+
+/// `helix switch`
+pub fn switch(repo_path: &Path, name: &str, options: SwitchOptions) -> Result<()> {
+    if let Some(sandbox_name) = name.strip_prefix("sandboxes/") {
+        if options.create || options.force_create {
+            return Err(anyhow!(
+                "Create sandboxes with 'helix sandbox create {}'",
+                sandbox_name
+            ));
+        }
+        return crate::sandbox_command::switch_sandbox(repo_path, sandbox_name);
+    }
+
+    let repo_root = RepoContext::detect(repo_path)?.repo_root;
+    let exists = repo_root.join(".helix/refs/heads").join(name).exists();
+    if !exists && !options.create && !options.force_create {
+        if let Some(remote_branch) = find_remote_branch(repo_path, name)? {
+            let local = track_remote_branch(repo_path, &remote_branch, BranchOptions::default())?;
+            return switch_branch(repo_path, &local);
+        }
+    }
+
+    if options.create || options.force_create {
+        create_branch(
+            repo_path,
+            name,
+            BranchOptions {
+                force: options.force_create,
+                verbose: options.verbose,
+                ..Default::default()
+            },
+        )?;
+    }
+
+    switch_branch(repo_path, name)
+}
+
 /// Switch to a different branch (checkout)
 pub fn switch_branch(repo_path: &Path, name: &str) -> Result<()> {
+    // In a linked worktree, refs are shared but HEAD is the worktree's own
+    let context = RepoContext::detect(repo_path)?;
+    let repo_path = context.repo_root.as_path();
+    let head_path = context.head_path.as_path();
+
     // Check if it's a sandbox branch
     if name.starts_with("sandboxes/") {
         let sandbox_name = name.strip_prefix("sandboxes/").unwrap();
@@ -191,13 +304,9 @@ pub fn switch_branch(repo_path: &Path, name: &str) -> Result<()> {
         }
 
         // Update HEAD to point to sandbox ref
-        let head_path = repo_path.join(".helix/HEAD");
-        fs::write(
-            &head_path,
-            format!("ref: refs/sandboxes/{}\n", sandbox_name),
-        )?;
+        fs::write(head_path, format!("ref: refs/sandboxes/{}\n", sandbox_name))?;
 
-        println!("Switched to sandbox '{}'", sandbox_name);
+        say!("Switched to sandbox '{}'", sandbox_name);
         return Ok(());
     }
 
@@ -206,17 +315,16 @@ pub fn switch_branch(repo_path: &Path, name: &str) -> Result<()> {
 
     if !branch_path.exists() {
         return Err(anyhow!(
-            "Branch '{}' does not exist. Create it with 'helix branch {}'",
+            "Branch '{}' does not exist. Create it with 'helix switch -c {}'",
             name,
             name
         ));
     }
 
     // Update HEAD to point to new branch
-    let head_path = repo_path.join(".helix/HEAD");
-    fs::write(&head_path, format!("ref: refs/heads/{}\n", name))?;
+    fs::write(head_path, format!("ref: refs/heads/{}\n", name))?;
 
-    println!("Switched to branch '{}'", name);
+    say!("Switched to branch '{}'", name);
 
     Ok(())
 }
@@ -261,6 +369,175 @@ pub fn get_all_branches(start_path: &Path) -> Result<Vec<String>> {
     Ok(branches)
 }
 
+/// Remote-tracking branches as "<remote>/<branch>", from refs/remotes
+pub fn get_remote_branches(start_path: &Path) -> Result<Vec<String>> {
+    let context = RepoContext::detect(start_path)?;
+    let remotes_dir = context.repo_root.join(".helix/refs/remotes");
+
+    let mut branches = Vec::new();
+    if !remotes_dir.exists() {
+        return Ok(branches);
+    }
+
+    for remote in fs::read_dir(&remotes_dir)? {
+        let remote = remote?;
+        if !remote.path().is_dir() {
+            continue;
+        }
+        let remote_name = remote.file_name().to_string_lossy().to_string();
+        for entry in fs::read_dir(remote.path())? {
+            let path = entry?.path();
+            if path.is_file() {
+                if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                    branches.push(format!("{}/{}", remote_name, name));
+                }
+            }
+        }
+    }
+
+    branches.sort();
+    Ok(branches)
+}
+
+/// The remote-tracking branch `name` refers to: "<remote>/<branch>" itself, or
+/// a bare branch name that exactly one remote has
+fn find_remote_branch(repo_path: &Path, name: &str) -> Result<Option<String>> {
+    let remote_branches = get_remote_branches(repo_path)?;
+    if remote_branches.iter().any(|b| b == name) {
+        return Ok(Some(name.to_string()));
+    }
+
+    let matches: Vec<&String> = remote_branches
+        .iter()
+        .filter(|b| b.split_once('/').is_some_and(|(_, branch)| branch == name))
+        .collect();
+    match matches.as_slice() {
+        [only] => Ok(Some(only.to_string())),
+        [] => Ok(None),
+        _ => Err(anyhow!(
+            "'{}' matches several remote-tracking branches ({}). Use 'helix switch <remote>/{}'",
+            name,
+            matches
+                .iter()
+                .map(|b| b.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            name
+        )),
+    }
+}
+
+/// Create a local branch from a remote-tracking branch ("origin/feature" ->
+/// "feature") with its upstream set to it, returning the local name
+pub fn track_remote_branch(
+    repo_path: &Path,
+    remote_branch: &str,
+    options: BranchOptions,
+) -> Result<String> {
+    let (remote, name) = remote_branch
+        .split_once('/')
+        .ok_or_else(|| anyhow!("Expected <remote>/<branch>, got '{}'", remote_branch))?;
+    validate_branch_name(name)?;
+    let context = RepoContext::detect(repo_path)?;
+    let repo_path = context.repo_root.as_path();
+
+    let remote_ref = repo_path
+        .join(".helix/refs/remotes")
+        .join(remote)
+        .join(name);
+    let tip = fs::read_to_string(&remote_ref)
+        .with_context(|| format!("Remote-tracking branch '{}' does not exist", remote_branch))?;
+    let tip = hex_to_hash(tip.trim())?;
+
+    let branch_path = repo_path.join(".helix/refs/heads").join(name);
+    if branch_path.exists() && !options.force {
+        return Err(anyhow!(
+            "Branch '{}' already exists. Switch to it with 'helix switch {}'",
+            name,
+            name
+        ));
+    }
+    fs::create_dir_all(branch_path.parent().unwrap())?;
+    fs::write(&branch_path, format!("{}\n", hash_to_hex(&tip)))?;
+    set_branch_upstream(repo_path, name, remote_branch)?;
+
+    say!("Branch '{}' set up to track '{}'", name, remote_branch);
+    if options.verbose {
+        say!("Created branch '{}' at commit {}", name, short_hash(&tip));
+    }
+
+    Ok(name.to_string())
+}
+
+/// The branches and where they point, as `helix branch --json` prints them
+#[derive(Debug, Serialize)]
+pub struct BranchList {
+    /// None on a detached HEAD
+    pub current: Option<String>,
+    pub branches: Vec<BranchTip>,
+    /// Remote-tracking branches, as "<remote>/<branch>"
+    pub remotes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BranchTip {
+    pub name: String,
+    pub commit: Option<String>,
+}
+
+pub fn list_branches(repo_path: &Path) -> Result<BranchList> {
+    let current = get_current_branch(repo_path)
+        .ok()
+        .filter(|name| !name.starts_with('('));
+    let mut names = get_all_branches(repo_path)?;
+    names.sort();
+
+    let refs_dir = RepoContext::detect(repo_path)?
+        .repo_root
+        .join(".helix/refs");
+    let branches = names
+        .into_iter()
+        .map(|name| {
+            let ref_path = match name.strip_prefix("sandboxes/") {
+                Some(sandbox) => refs_dir.join("sandboxes").join(sandbox),
+                None => refs_dir.join("heads").join(&name),
+            };
+            let commit = fs::read_to_string(ref_path)
+                .ok()
+                .map(|hex| hex.trim().to_string());
+            BranchTip { name, commit }
+        })
+        .collect();
+
+    Ok(BranchList {
+        current,
+        branches,
+        remotes: get_remote_branches(repo_path)?,
+    })
+}
+
+/// `helix branch --list`
+pub fn print_branches(repo_path: &Path) -> Result<()> {
+    let current = get_current_branch(repo_path).unwrap_or_default();
+    let mut branches = get_all_branches(repo_path)?;
+    branches.sort();
+
+    for branch in &branches {
+        let marker = if *branch == current { "*" } else { " " };
+        println!("{} {}", marker, branch);
+    }
+
+    let remote_branches = get_remote_branches(repo_path)?;
+    if !remote_branches.is_empty() {
+        println!();
+        for branch in remote_branches {
+            println!("  remotes/{}", branch);
+        }
+    }
+
+    Ok(())
+}
+
 /// Read HEAD and return the commit hash
 fn read_head(repo_path: &Path) -> Result<Hash> {
     let head_path = repo_path.join(".helix/HEAD");
@@ -290,7 +567,7 @@ fn read_head(repo_path: &Path) -> Result<Hash> {
 }
 
 /// Validate branch name (no special characters, slashes, etc.)
-fn validate_branch_name(name: &str) -> Result<()> {
+pub(crate) fn validate_branch_name(name: &str) -> Result<()> {
     if name.is_empty() {
         return Err(anyhow!("Branch name cannot be empty"));
     }
@@ -395,6 +672,12 @@ email = "test@test.com"
                 allow_empty: false,
                 amend: false,
                 verbose: false,
+                signoff: false,
+                no_edit: false,
+                reset_author: false,
+                date: None,
+                metadata: Default::default(),
+                allow_missing_objects: false,
             },
         )
     }
@@ -501,6 +784,63 @@ email = "test@test.com"
         Ok(())
     }
 
+    #[test]
+    fn test_delete_unmerged_branch_requires_force() -> Result<()> {
+        use crate::add_command::{add, AddOptions};
+
+        let temp_dir = TempDir::new()?;
+        init_test_repo(temp_dir.path())?;
+        make_initial_commit(temp_dir.path())?;
+
+        create_branch(temp_dir.path(), "feature", BranchOptions::default())?;
+        switch_branch(temp_dir.path(), "feature")?;
+
+        fs::write(temp_dir.path().join("feature.txt"), "feature work")?;
+        add(
+            temp_dir.path(),
+            &[PathBuf::from("feature.txt")],
+            AddOptions::default(),
+        )?;
+        let feature_commit = commit(
+            temp_dir.path(),
+            CommitOptions {
+                message: "Feature work".to_string(),
+                author: Some("Test <test@test.com>".to_string()),
+                allow_empty: false,
+                amend: false,
+                verbose: false,
+                signoff: false,
+                no_edit: false,
+                reset_author: false,
+                date: None,
+                metadata: Default::default(),
+                allow_missing_objects: false,
+            },
+        )?;
+
+        switch_branch(temp_dir.path(), "main")?;
+
+        let err = delete_branch(temp_dir.path(), "feature", BranchOptions::default())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("not fully merged"));
+        assert!(err.contains(&short_hash(&feature_commit)));
+        assert!(err.contains("Feature work"));
+        assert!(temp_dir.path().join(".helix/refs/heads/feature").exists());
+
+        delete_branch(
+            temp_dir.path(),
+            "feature",
+            BranchOptions {
+                force: true,
+                ..Default::default()
+            },
+        )?;
+        assert!(!temp_dir.path().join(".helix/refs/heads/feature").exists());
+
+        Ok(())
+    }
+
     #[test]
     fn test_switch_branch() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -524,6 +864,70 @@ email = "test@test.com"
         Ok(())
     }
 
+    #[test]
+    fn test_switch_create() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        init_test_repo(temp_dir.path())?;
+        let head = make_initial_commit(temp_dir.path())?;
+
+        assert!(switch(temp_dir.path(), "feature", SwitchOptions::default()).is_err());
+
+        let create = SwitchOptions {
+            create: true,
+            ..Default::default()
+        };
+        switch(temp_dir.path(), "feature", create)?;
+        assert_eq!(get_current_branch(temp_dir.path())?, "feature");
+        let tip = fs::read_to_string(temp_dir.path().join(".helix/refs/heads/feature"))?;
+        assert_eq!(tip.trim(), hash_to_hex(&head));
+
+        // -c refuses an existing branch, -C resets it
+        let create = SwitchOptions {
+            create: true,
+            ..Default::default()
+        };
+        assert!(switch(temp_dir.path(), "main", create).is_err());
+        let force_create = SwitchOptions {
+            force_create: true,
+            ..Default::default()
+        };
+        switch(temp_dir.path(), "main", force_create)?;
+        assert_eq!(get_current_branch(temp_dir.path())?, "main");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_track_remote_branch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+        let head = make_initial_commit(repo)?;
+
+        // As left behind by a pull from origin
+        fs::create_dir_all(repo.join(".helix/refs/remotes/origin"))?;
+        fs::write(
+            repo.join(".helix/refs/remotes/origin/feature"),
+            hash_to_hex(&head),
+        )?;
+        assert_eq!(get_remote_branches(repo)?, vec!["origin/feature"]);
+
+        switch(repo, "feature", SwitchOptions::default())?;
+        assert_eq!(get_current_branch(repo)?, "feature");
+        assert_eq!(
+            get_branch_upstream(repo, "feature").as_deref(),
+            Some("origin/feature")
+        );
+
+        // Already tracked: switching to origin/feature again refuses to clobber it
+        switch_branch(repo, "main")?;
+        assert!(switch(repo, "origin/feature", SwitchOptions::default()).is_err());
+        switch(repo, "feature", SwitchOptions::default())?;
+        assert_eq!(get_current_branch(repo)?, "feature");
+
+        Ok(())
+    }
+
     #[test]
     fn test_rename_branch() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -551,6 +955,23 @@ email = "test@test.com"
         Ok(())
     }
 
+    #[test]
+    fn test_list_branches_for_json() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        init_test_repo(temp_dir.path())?;
+        let head = make_initial_commit(temp_dir.path())?;
+        create_branch(temp_dir.path(), "feature", BranchOptions::default())?;
+
+        let list = list_branches(temp_dir.path())?;
+        assert_eq!(list.current.as_deref(), Some("main"));
+        let json = serde_json::to_value(&list)?;
+        assert_eq!(json["branches"][0]["name"], "feature");
+        assert_eq!(json["branches"][1]["commit"], hash_to_hex(&head));
+        assert_eq!(json["remotes"], serde_json::json!([]));
+
+        Ok(())
+    }
+
     #[test]
     fn test_rename_current_branch_updates_head() -> Result<()> {
         let temp_dir = TempDir::new()?;
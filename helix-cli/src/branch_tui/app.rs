@@ -1,9 +1,10 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use helix_protocol::commit::read_remote_tracking;
 use helix_protocol::{hash, storage::FsObjectStore};
 use ratatui::{backend::CrosstermBackend, Terminal};
 use std::collections::{HashMap, HashSet};
@@ -11,7 +12,10 @@ use std::io;
 use std::path::Path;
 
 use super::ui;
+use crate::fuzzy::fuzzy_match;
+use crate::helix_index::graph::walk_commits;
 use crate::helix_index::state::get_branch_upstream;
+use crate::tui::{self, BRANCH_KEYS};
 use crate::{
     helix_index::commit::{Commit, CommitStore},
     sandbox_command::RepoContext,
@@ -26,6 +30,16 @@ pub struct BranchInfo {
     pub commit_count: usize,
     pub remote_tracking: Option<String>,
     pub upstream: Option<String>,
+    /// (ahead, behind) commit counts against the upstream, when it resolves
+    pub ahead_behind: Option<(usize, usize)>,
+    /// A remote-tracking branch, named "remotes/<remote>/<branch>"
+    pub is_remote: bool,
+}
+
+impl BranchInfo {
+    pub fn last_commit_time(&self) -> u64 {
+        self.last_commit.as_ref().map_or(0, |c| c.commit_time)
+    }
 }
 
 pub struct App {
@@ -44,6 +58,10 @@ pub struct App {
     pub branch_commit_lists: HashMap<String, Vec<Commit>>,
     pub selected_commit_index: usize,
     pub focus: Focus,
+    /// Typing into the search box, opened with `/`
+    pub search_mode: bool,
+    /// Only branches whose names fuzzily match this are listed
+    pub search_query: String,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -63,6 +81,7 @@ impl App {
             .unwrap_or("unknown")
             .to_string();
 
+        tui::init(repo_path);
         let store = FsObjectStore::new(repo_path);
         let commit_storage = CommitStore::new(repo_path, store)?;
         let current_branch =
@@ -102,11 +121,21 @@ impl App {
             let remote_tracking = get_remote_tracking(repo_path, &branch_name);
 
             // For sandboxes, show base branch; for regular branches, show upstream
-            let upstream = if branch_name.starts_with("sandboxes/") {
-                let sandbox_name = branch_name.strip_prefix("sandboxes/").unwrap();
-                get_sandbox_base_branch(repo_path, sandbox_name)
-            } else {
-                get_branch_upstream(repo_path, &branch_name)
+            let (upstream, upstream_branch) =
+                if let Some(sandbox_name) = branch_name.strip_prefix("sandboxes/") {
+                    (
+                        get_sandbox_base_branch(repo_path, sandbox_name),
+                        sandbox_base_branch_name(repo_path, sandbox_name),
+                    )
+                } else {
+                    let upstream = get_branch_upstream(repo_path, &branch_name);
+                    (upstream.clone(), upstream)
+                };
+
+            let ahead_behind = match (&last_commit_hash, &upstream_branch) {
+                (Some(tip), Some(upstream)) => resolve_upstream(repo_path, upstream)
+                    .map(|upstream_tip| ahead_behind(&commit_storage, tip, &upstream_tip)),
+                _ => None,
             };
 
             branches.push(BranchInfo {
@@ -117,14 +146,38 @@ impl App {
                 commit_count,
                 remote_tracking,
                 upstream,
+                ahead_behind,
+                is_remote: false,
+            });
+        }
+
+        for remote_branch in crate::branch_command::get_remote_branches(repo_path)? {
+            let (remote, name) = remote_branch.split_once('/').unwrap_or_default();
+            let tip = read_remote_tracking(repo_path, remote, name).ok();
+            let last_commit = tip.and_then(|hash| commit_storage.read_commit(&hash).ok());
+            let commit_count = tip.map_or(0, |hash| count_commits(&commit_storage, &hash));
+
+            branches.push(BranchInfo {
+                name: format!("remotes/{}", remote_branch),
+                is_current: false,
+                last_commit_hash: tip,
+                last_commit,
+                commit_count,
+                remote_tracking: None,
+                upstream: None,
+                ahead_behind: None,
+                is_remote: true,
             });
         }
 
-        // Sort: current branch first, then by name
-        branches.sort_by(|a, b| match (a.is_current, b.is_current) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
+        // Sort: local before remote, current branch first, then most recently
+        // committed, then by name
+        branches.sort_by(|a, b| {
+            a.is_remote
+                .cmp(&b.is_remote)
+                .then_with(|| b.is_current.cmp(&a.is_current))
+                .then_with(|| b.last_commit_time().cmp(&a.last_commit_time()))
+                .then_with(|| a.name.cmp(&b.name))
         });
 
         let selected_index = branches.iter().position(|b| b.is_current).unwrap_or(0);
@@ -145,6 +198,8 @@ impl App {
             branch_commit_lists: HashMap::new(),
             selected_commit_index: 0,
             focus: Focus::BranchList,
+            search_mode: false,
+            search_query: String::new(),
         };
 
         // Load commits for the initially selected branch
@@ -159,42 +214,75 @@ impl App {
         self.visible_height = (inner_height / 4).max(1) as usize;
     }
 
+    /// The selected branch, unless the search hides it
     pub fn selected_branch(&self) -> Option<&BranchInfo> {
-        self.branches.get(self.selected_index)
+        self.branches
+            .get(self.selected_index)
+            .filter(|b| fuzzy_match(&self.search_query, &b.name).is_some())
     }
 
-    pub fn next(&mut self) {
-        if !self.branches.is_empty() {
-            self.selected_index = (self.selected_index + 1) % self.branches.len();
-            self.adjust_scroll();
+    /// Indices into `branches` of the ones the search leaves listed
+    pub fn visible_indices(&self) -> Vec<usize> {
+        self.branches
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| fuzzy_match(&self.search_query, &b.name).is_some())
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Step `delta` places through the listed branches, wrapping at either end
+    fn step(&mut self, delta: isize) {
+        let visible = self.visible_indices();
+        if visible.is_empty() {
+            return;
         }
+        let next = match visible.iter().position(|&i| i == self.selected_index) {
+            Some(pos) => (pos as isize + delta).rem_euclid(visible.len() as isize) as usize,
+            None => 0,
+        };
+        self.selected_index = visible[next];
+        self.adjust_scroll();
+    }
+
+    pub fn next(&mut self) {
+        self.step(1);
     }
 
     pub fn previous(&mut self) {
-        if !self.branches.is_empty() {
-            self.selected_index = if self.selected_index == 0 {
-                self.branches.len() - 1
-            } else {
-                self.selected_index - 1
-            };
-            self.adjust_scroll();
-        }
+        self.step(-1);
     }
 
     pub fn go_to_top(&mut self) {
-        if !self.branches.is_empty() {
-            self.selected_index = 0;
+        if let Some(&first) = self.visible_indices().first() {
+            self.selected_index = first;
             self.scroll_offset = 0;
         }
     }
 
     pub fn go_to_bottom(&mut self) {
-        if !self.branches.is_empty() {
-            self.selected_index = self.branches.len() - 1;
+        if let Some(&last) = self.visible_indices().last() {
+            self.selected_index = last;
             self.adjust_scroll();
         }
     }
 
+    /// Re-filter after the query changed, moving to the first match if the selected
+    /// branch no longer matches
+    pub fn update_search(&mut self) -> Result<()> {
+        if self.selected_branch().is_none() {
+            self.go_to_top();
+            self.focus = Focus::BranchList;
+            self.on_branch_selected()?;
+        }
+        Ok(())
+    }
+
+    fn clear_search(&mut self) {
+        self.search_mode = false;
+        self.search_query.clear();
+    }
+
     /// Called whenever the selected branch changes.
     /// Lazily loads commits for that branch into `branch_commit_lists`.
     fn on_branch_selected(&mut self) -> Result<()> {
@@ -206,9 +294,14 @@ impl App {
 
         // Only load if we haven't already cached this branch's commits.
         if !self.branch_commit_lists.contains_key(&branch_name) {
-            let commits = self
-                .commit_storage
-                .load_commits_for_branch(&branch_name, 200)?;
+            let branch = &self.branches[self.selected_index];
+            let commits = match (branch.is_remote, branch.last_commit_hash) {
+                (true, Some(tip)) => walk_commits(&self.commit_storage, &[tip], 200, None)?,
+                (true, None) => Vec::new(),
+                (false, _) => self
+                    .commit_storage
+                    .load_commits_for_branch(&branch_name, 200)?,
+            };
             self.branch_commit_lists.insert(branch_name, commits);
         }
 
@@ -236,7 +329,16 @@ impl App {
 
     pub fn checkout_branch(&mut self) -> Result<()> {
         if let Some(branch) = self.selected_branch() {
-            if !branch.is_current {
+            if let Some(remote_branch) = branch.name.strip_prefix("remotes/") {
+                // Check out as a new local branch tracking the remote one
+                let local = crate::branch_command::track_remote_branch(
+                    &self.repo_path,
+                    remote_branch,
+                    crate::branch_command::BranchOptions::default(),
+                )?;
+                crate::branch_command::switch_branch(&self.repo_path, &local)?;
+                self.should_quit = true;
+            } else if !branch.is_current {
                 crate::branch_command::switch_branch(&self.repo_path, &branch.name)?;
                 self.should_quit = true;
             }
@@ -246,8 +348,8 @@ impl App {
 
     pub fn delete_branch(&mut self) -> Result<()> {
         if let Some(branch) = self.selected_branch() {
-            if branch.is_current {
-                // Can't delete current branch
+            if branch.is_current || branch.is_remote {
+                // Can't delete current branch, and remote ones belong to the remote
                 return Ok(());
             }
 
@@ -285,7 +387,7 @@ impl App {
     }
 
     pub fn rename_branch(&mut self, new_name: String) -> Result<()> {
-        if let Some(branch) = self.selected_branch() {
+        if let Some(branch) = self.selected_branch().filter(|b| !b.is_remote) {
             let old_name = branch.name.clone();
             crate::branch_command::rename_branch(
                 &self.repo_path,
@@ -337,7 +439,10 @@ impl App {
     pub fn run(&mut self) -> Result<()> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(stdout, EnterAlternateScreen)?;
+        if tui::mouse_enabled() {
+            execute!(stdout, EnableMouseCapture)?;
+        }
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
@@ -364,6 +469,47 @@ impl App {
 
             if event::poll(std::time::Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
+                    // Handle typing into the search box
+                    if self.search_mode {
+                        match key.code {
+                            KeyCode::Esc => {
+                                self.clear_search();
+                                if let Err(e) = self.on_branch_selected() {
+                                    eprintln!("Failed to load commits for branch: {}", e);
+                                }
+                            }
+                            KeyCode::Enter => {
+                                self.search_mode = false;
+                            }
+                            KeyCode::Up => {
+                                self.previous();
+                                if let Err(e) = self.on_branch_selected() {
+                                    eprintln!("Failed to load commits for branch: {}", e);
+                                }
+                            }
+                            KeyCode::Down => {
+                                self.next();
+                                if let Err(e) = self.on_branch_selected() {
+                                    eprintln!("Failed to load commits for branch: {}", e);
+                                }
+                            }
+                            KeyCode::Char(c) => {
+                                self.search_query.push(c);
+                                if let Err(e) = self.update_search() {
+                                    eprintln!("Failed to load commits for branch: {}", e);
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                self.search_query.pop();
+                                if let Err(e) = self.update_search() {
+                                    eprintln!("Failed to load commits for branch: {}", e);
+                                }
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+
                     // Handle rename mode
                     if self.rename_mode {
                         match key.code {
@@ -426,17 +572,25 @@ impl App {
                         continue;
                     }
 
+                    // Esc drops a kept filter before it quits
+                    if key.code == KeyCode::Esc && !self.search_query.is_empty() {
+                        self.clear_search();
+                        continue;
+                    }
+
                     // Normal mode
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => {
-                            self.should_quit = true;
+                    match tui::keys().action(&key, BRANCH_KEYS) {
+                        Some("search") => {
+                            self.search_mode = true;
+                            self.search_query.clear();
+                            self.focus = Focus::BranchList;
                         }
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        Some("quit") => {
                             self.should_quit = true;
                         }
 
                         // j / k or Down / Up operate on focused pane
-                        KeyCode::Down | KeyCode::Char('j') => match self.focus {
+                        Some("down") => match self.focus {
                             Focus::BranchList => {
                                 self.next();
                                 if let Err(e) = self.on_branch_selected() {
@@ -447,7 +601,7 @@ impl App {
                                 self.next_commit();
                             }
                         },
-                        KeyCode::Up | KeyCode::Char('k') => match self.focus {
+                        Some("up") => match self.focus {
                             Focus::BranchList => {
                                 self.previous();
                                 if let Err(e) = self.on_branch_selected() {
@@ -460,7 +614,7 @@ impl App {
                         },
 
                         // g / G: top/bottom in focused pane
-                        KeyCode::Char('g') => match self.focus {
+                        Some("top") => match self.focus {
                             Focus::BranchList => {
                                 self.go_to_top();
                                 if let Err(e) = self.on_branch_selected() {
@@ -471,7 +625,7 @@ impl App {
                                 self.first_commit();
                             }
                         },
-                        KeyCode::Char('G') => match self.focus {
+                        Some("bottom") => match self.focus {
                             Focus::BranchList => {
                                 self.go_to_bottom();
                                 if let Err(e) = self.on_branch_selected() {
@@ -484,7 +638,7 @@ impl App {
                         },
 
                         // 👉 move focus to commit list
-                        KeyCode::Right | KeyCode::Char('l') => {
+                        Some("open") => {
                             if self.focus == Focus::BranchList {
                                 if let Some(branch) = self.selected_branch() {
                                     if let Some(commits) =
@@ -499,11 +653,11 @@ impl App {
                         }
 
                         // 👈 move focus back to branches
-                        KeyCode::Left | KeyCode::Char('h') => {
+                        Some("back") => {
                             self.focus = Focus::BranchList;
                         }
 
-                        KeyCode::Char('c') => {
+                        Some("checkout") => {
                             // Checkout branch
                             if let Some(branch) = self.selected_branch() {
                                 if !branch.is_current {
@@ -511,23 +665,23 @@ impl App {
                                 }
                             }
                         }
-                        KeyCode::Char('d') => {
+                        Some("delete") => {
                             // Delete branch
                             if let Some(branch) = self.selected_branch() {
-                                if !branch.is_current {
+                                if !branch.is_current && !branch.is_remote {
                                     self.delete_mode = true;
                                 }
                             }
                         }
-                        KeyCode::Char('r') => {
+                        Some("rename") => {
                             // Rename branch
-                            if let Some(branch) = self.selected_branch() {
+                            if let Some(branch) = self.selected_branch().filter(|b| !b.is_remote) {
                                 let branch_name = branch.name.clone();
                                 self.rename_mode = true;
                                 self.new_branch_name = branch_name;
                             }
                         }
-                        KeyCode::Enter => {
+                        Some("switch") => {
                             // Quick checkout (no confirmation)
                             if let Err(e) = self.checkout_branch() {
                                 eprintln!("Failed to checkout branch: {}", e);
@@ -548,7 +702,11 @@ impl App {
 }
 
 fn count_commits(storage: &CommitStore, start_hash: &[u8; 32]) -> usize {
-    let mut count = 0;
+    reachable_commits(storage, start_hash).len()
+}
+
+/// Every commit reachable from `start_hash`, including itself
+pub(crate) fn reachable_commits(storage: &CommitStore, start_hash: &[u8; 32]) -> HashSet<[u8; 32]> {
     let mut to_visit = vec![*start_hash];
     let mut seen = HashSet::new();
 
@@ -557,8 +715,6 @@ fn count_commits(storage: &CommitStore, start_hash: &[u8; 32]) -> usize {
             continue;
         }
 
-        count += 1;
-
         if let Ok(commit) = storage.read_commit(&hash) {
             for parent in &commit.parents {
                 to_visit.push(*parent);
@@ -566,7 +722,31 @@ fn count_commits(storage: &CommitStore, start_hash: &[u8; 32]) -> usize {
         }
     }
 
-    count
+    seen
+}
+
+/// Commits only on `local` (ahead) and only on `upstream` (behind)
+fn ahead_behind(storage: &CommitStore, local: &[u8; 32], upstream: &[u8; 32]) -> (usize, usize) {
+    let local_commits = reachable_commits(storage, local);
+    let upstream_commits = reachable_commits(storage, upstream);
+
+    (
+        local_commits.difference(&upstream_commits).count(),
+        upstream_commits.difference(&local_commits).count(),
+    )
+}
+
+/// Resolve an upstream name to its tip: a local branch ("main") or a
+/// remote-tracking ref ("origin/main")
+pub(crate) fn resolve_upstream(repo_path: &Path, upstream: &str) -> Option<[u8; 32]> {
+    let refs = repo_path.join(".helix/refs");
+    [
+        refs.join("heads").join(upstream),
+        refs.join("remotes").join(upstream),
+    ]
+    .iter()
+    .find_map(|path| std::fs::read_to_string(path).ok())
+    .and_then(|hex| hash::hex_to_hash(hex.trim()).ok())
 }
 
 /// Get the remote tracking branch for a local branch
@@ -622,6 +802,10 @@ fn get_remote_tracking(repo_path: &Path, branch_name: &str) -> Option<String> {
 }
 
 pub fn get_sandbox_base_branch(repo_path: &Path, sandbox_name: &str) -> Option<String> {
+    sandbox_base_branch_name(repo_path, sandbox_name).map(|b| format!("{} (base)", b))
+}
+
+fn sandbox_base_branch_name(repo_path: &Path, sandbox_name: &str) -> Option<String> {
     use crate::sandbox_command::SandboxManifest;
 
     let sandbox_root = repo_path
@@ -631,5 +815,95 @@ pub fn get_sandbox_base_branch(repo_path: &Path, sandbox_name: &str) -> Option<S
 
     let manifest = SandboxManifest::load(&sandbox_root).ok()?;
 
-    manifest.base_branch.map(|b| format!("{} (base)", b))
+    manifest.base_branch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::TreeBuilder;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ahead_behind() -> Result<()> {
+        let temp = TempDir::new()?;
+        let storage = CommitStore::new(temp.path(), FsObjectStore::new(temp.path()))?;
+        let tree = TreeBuilder::new(temp.path()).build_from_entries(&[])?;
+        let write = |parents: Vec<[u8; 32]>, msg: &str| {
+            storage.write_commit(&Commit::new(tree, parents, "Test".into(), msg.into()))
+        };
+
+        let base = write(vec![], "base")?;
+        let local1 = write(vec![base], "local1")?;
+        let local2 = write(vec![local1], "local2")?;
+        let upstream = write(vec![base], "upstream")?;
+
+        assert_eq!(ahead_behind(&storage, &local2, &upstream), (2, 1));
+        assert_eq!(ahead_behind(&storage, &local2, &local2), (0, 0));
+        assert_eq!(ahead_behind(&storage, &base, &local2), (0, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_filters_branches() -> Result<()> {
+        let temp = TempDir::new()?;
+        let branch = |name: &str| BranchInfo {
+            name: name.to_string(),
+            is_current: name == "main",
+            last_commit_hash: None,
+            last_commit: None,
+            commit_count: 0,
+            remote_tracking: None,
+            upstream: None,
+            ahead_behind: None,
+            is_remote: name.starts_with("remotes/"),
+        };
+        let mut app = App {
+            branches: [
+                "main",
+                "feature/login",
+                "fix-logging",
+                "remotes/origin/main",
+            ]
+            .into_iter()
+            .map(branch)
+            .collect(),
+            selected_index: 0,
+            scroll_offset: 0,
+            should_quit: false,
+            repo_path: temp.path().to_path_buf(),
+            repo_name: "repo".to_string(),
+            commit_storage: CommitStore::new(temp.path(), FsObjectStore::new(temp.path()))?,
+            visible_height: 20,
+            checkout_mode: false,
+            delete_mode: false,
+            rename_mode: false,
+            new_branch_name: String::new(),
+            branch_commit_lists: HashMap::new(),
+            selected_commit_index: 0,
+            focus: Focus::BranchList,
+            search_mode: true,
+            search_query: String::new(),
+        };
+
+        // "main" is filtered out, so the selection moves to the first match
+        app.search_query = "lgn".to_string();
+        app.update_search()?;
+        assert_eq!(app.visible_indices(), vec![1, 2]);
+        assert_eq!(app.selected_branch().unwrap().name, "feature/login");
+
+        // Moving wraps within the matches
+        app.next();
+        assert_eq!(app.selected_branch().unwrap().name, "fix-logging");
+        app.next();
+        assert_eq!(app.selected_branch().unwrap().name, "feature/login");
+
+        app.search_query = "nothing".to_string();
+        app.update_search()?;
+        assert!(app.selected_branch().is_none());
+
+        app.clear_search();
+        assert_eq!(app.visible_indices().len(), 4);
+        Ok(())
+    }
 }
@@ -1,12 +1,16 @@
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
-use super::app::{App, Focus};
+use helix_protocol::hash::hash_to_hex;
+
+use super::app::{App, BranchInfo, Focus};
+use crate::fuzzy::{fuzzy_match, highlight};
+use crate::tui::{format_date, palette, relative_time};
 
 pub fn draw(f: &mut Frame, app: &App) {
     let chunks = Layout::default()
@@ -31,6 +35,11 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         1 => format!(" {} branch ", app.branches.len()),
         _ => format!(" {} branches ", app.branches.len()),
     };
+    let branch_count = if app.search_query.is_empty() {
+        branch_count
+    } else {
+        format!(" {} of{}", app.visible_indices().len(), branch_count)
+    };
 
     let cur_branch = match app.selected_branch() {
         Some(a) => a.name.clone(),
@@ -41,18 +50,18 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         Span::styled(
             repo_text,
             Style::default()
-                .fg(Color::Magenta)
+                .fg(palette().special)
                 .add_modifier(Modifier::BOLD),
         ),
-        Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
+        Span::styled(" │ ", Style::default().fg(palette().muted)),
         Span::styled(
             cur_branch,
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
-        Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
-        Span::styled(branch_count, Style::default().fg(Color::White)),
+        Span::styled(" │ ", Style::default().fg(palette().muted)),
+        Span::styled(branch_count, Style::default().fg(palette().text)),
     ]))
     .block(Block::default().borders(Borders::ALL));
 
@@ -81,83 +90,116 @@ fn draw_branch_list(f: &mut Frame, area: Rect, app: &App) {
             Block::default()
                 .borders(Borders::ALL)
                 .title(" Branches ")
-                .title_style(Style::default().fg(Color::Blue)),
+                .title_style(Style::default().fg(palette().title)),
         );
         f.render_widget(empty_list, area);
         return;
     }
 
-    let items: Vec<ListItem> = app
-        .branches
+    // Remote-tracking branches sort last and get their own section
+    let visible: Vec<(usize, &BranchInfo)> = app
+        .visible_indices()
+        .into_iter()
+        .map(|i| (i, &app.branches[i]))
+        .collect();
+    let local_count = visible.iter().filter(|(_, b)| !b.is_remote).count();
+    let (local, remote) = visible.split_at(local_count);
+
+    let areas = if remote.is_empty() {
+        vec![area]
+    } else {
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area)
+            .to_vec()
+    };
+
+    draw_branch_section(f, areas[0], app, local, " Branches ");
+    if let Some(&remote_area) = areas.get(1) {
+        draw_branch_section(f, remote_area, app, remote, " Remote branches ");
+    }
+}
+
+/// One list of branches, each with its index in `app.branches`
+fn draw_branch_section(
+    f: &mut Frame,
+    area: Rect,
+    app: &App,
+    branches: &[(usize, &BranchInfo)],
+    title: &str,
+) {
+    let items: Vec<ListItem> = branches
         .iter()
-        .enumerate()
-        .map(|(i, branch)| {
-            let is_selected = i == app.selected_index;
-            create_branch_item(branch, is_selected)
-        })
+        .map(|&(i, branch)| create_branch_item(branch, i == app.selected_index, &app.search_query))
         .collect();
 
     let mut state = ListState::default();
-    state.select(Some(app.selected_index));
+    state.select(branches.iter().position(|&(i, _)| i == app.selected_index));
 
     let list = List::new(items)
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title(" Branches ")
-                .title_style(Style::default().fg(Color::Blue)),
+                .title(title.to_string())
+                .title_style(Style::default().fg(palette().title)),
         )
         .highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(palette().selection)
                 .add_modifier(Modifier::BOLD),
         );
 
     f.render_stateful_widget(list, area, &mut state);
 }
 
-fn create_branch_item(branch: &super::app::BranchInfo, is_selected: bool) -> ListItem {
+fn create_branch_item(branch: &BranchInfo, is_selected: bool, query: &str) -> ListItem<'static> {
     let indicator = if branch.is_current { "● " } else { "  " };
 
     let indicator_style = if branch.is_current {
         Style::default()
-            .fg(Color::Green)
+            .fg(palette().added)
             .add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(palette().muted)
     };
 
     let name_style = if is_selected {
         Style::default()
-            .fg(Color::Yellow)
+            .fg(palette().highlight)
             .add_modifier(Modifier::BOLD)
     } else if branch.is_current {
-        Style::default().fg(Color::Green)
+        Style::default().fg(palette().added)
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(palette().text)
     };
 
     let time_str = if let Some(ref commit) = branch.last_commit {
-        format_relative_time(commit.commit_time)
+        format_date(commit.commit_time, relative_time)
     } else {
         "no commits".to_string()
     };
 
-    let line1 = Line::from(vec![
-        Span::styled(indicator, indicator_style),
-        Span::styled(&branch.name, name_style),
-    ]);
+    // What the search matched in the name stands out
+    let matched = palette().search_match();
+    let positions = fuzzy_match(query, &branch.name).unwrap_or_default();
+    let mut line1_spans = vec![Span::styled(indicator, indicator_style)];
+    line1_spans.extend(highlight(&branch.name, &positions, name_style, matched));
+    let line1 = Line::from(line1_spans);
 
-    let line2 = Line::from(vec![
+    let mut line2_spans = vec![
         Span::raw("  "),
         Span::styled(
             format!("{} commits", branch.commit_count),
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(palette().muted),
         ),
-    ]);
+    ];
+    line2_spans.extend(ahead_behind_spans(branch.ahead_behind));
+    let line2 = Line::from(line2_spans);
 
     let upstream_text = match &branch.upstream {
         Some(upstream) => upstream.clone(),
+        None if branch.is_remote => "remote-tracking".to_string(),
         None => {
             // Check if this is a default/root branch
             if branch.name == "main" || branch.name == "master" {
@@ -169,9 +211,9 @@ fn create_branch_item(branch: &super::app::BranchInfo, is_selected: bool) -> Lis
     };
 
     let upstream_color = if branch.upstream.is_some() {
-        Color::Magenta
+        palette().special
     } else {
-        Color::DarkGray // Dimmed for missing/default
+        palette().muted // Dimmed for missing/default
     };
 
     let line3 = Line::from(vec![
@@ -179,17 +221,24 @@ fn create_branch_item(branch: &super::app::BranchInfo, is_selected: bool) -> Lis
         Span::styled(upstream_text, Style::default().fg(upstream_color)),
     ]);
 
-    let line4 = Line::from(vec![
+    let mut line4_spans = vec![
         Span::raw("  "),
-        Span::styled(time_str, Style::default().fg(Color::Cyan)),
-    ]);
+        Span::styled(time_str, Style::default().fg(palette().accent)),
+    ];
+    if let Some(ref commit) = branch.last_commit {
+        line4_spans.push(Span::styled(
+            format!("  {}", commit.summary()),
+            Style::default().fg(palette().subtle),
+        ));
+    }
+    let line4 = Line::from(line4_spans);
 
     let line5 = Line::from(vec![Span::raw("")]);
 
     let lines = vec![line1, line2, line3, line4, line5];
 
     let style = if is_selected {
-        Style::default().bg(Color::DarkGray)
+        Style::default().bg(palette().selection)
     } else {
         Style::default()
     };
@@ -197,13 +246,40 @@ fn create_branch_item(branch: &super::app::BranchInfo, is_selected: bool) -> Lis
     ListItem::new(lines).style(style)
 }
 
+/// "↑2 ↓1" against the upstream; "up to date" when both are zero
+fn ahead_behind_spans(ahead_behind: Option<(usize, usize)>) -> Vec<Span<'static>> {
+    match ahead_behind {
+        Some((0, 0)) => vec![Span::styled(
+            "  up to date",
+            Style::default().fg(palette().muted),
+        )],
+        Some((ahead, behind)) => {
+            let mut spans = Vec::new();
+            if ahead > 0 {
+                spans.push(Span::styled(
+                    format!("  ↑{}", ahead),
+                    Style::default().fg(palette().added),
+                ));
+            }
+            if behind > 0 {
+                spans.push(Span::styled(
+                    format!("  ↓{}", behind),
+                    Style::default().fg(palette().removed),
+                ));
+            }
+            spans
+        }
+        None => Vec::new(),
+    }
+}
+
 fn draw_branch_details(f: &mut Frame, area: Rect, app: &App) {
     if let Some(branch) = app.selected_branch() {
         // Outer border for the whole right-hand panel
         let block = Block::default()
             .borders(Borders::ALL)
             .title(" Details ")
-            .title_style(Style::default().fg(Color::Blue));
+            .title_style(Style::default().fg(palette().title));
 
         let inner = block.inner(area);
         f.render_widget(block, area);
@@ -212,7 +288,7 @@ fn draw_branch_details(f: &mut Frame, area: Rect, app: &App) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(7), // summary
+                Constraint::Length(8), // summary
                 Constraint::Min(0),    // commit list
             ])
             .split(inner);
@@ -225,9 +301,9 @@ fn draw_branch_details(f: &mut Frame, area: Rect, app: &App) {
                 Block::default()
                     .borders(Borders::ALL)
                     .title(" Details ")
-                    .title_style(Style::default().fg(Color::Blue)),
+                    .title_style(Style::default().fg(palette().title)),
             )
-            .style(Style::default().fg(Color::DarkGray));
+            .style(Style::default().fg(palette().muted));
 
         f.render_widget(empty, area);
     }
@@ -242,23 +318,23 @@ fn draw_branch_summary(f: &mut Frame, area: Rect, branch: &super::app::BranchInf
         Span::styled(
             "Title:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
         Span::styled(
             branch.name.clone(),
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         ),
     ]));
 
     // Status (current / normal)
     let status_text = if branch.is_current {
-        ("Current branch", Color::Green)
+        ("Current branch", palette().added)
     } else {
-        ("Local branch", Color::DarkGray)
+        ("Local branch", palette().muted)
     };
 
     lines.push(Line::from(vec![
@@ -266,7 +342,7 @@ fn draw_branch_summary(f: &mut Frame, area: Rect, branch: &super::app::BranchInf
         Span::styled(
             "Status:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
@@ -279,13 +355,13 @@ fn draw_branch_summary(f: &mut Frame, area: Rect, branch: &super::app::BranchInf
         Span::styled(
             "Commits:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
         Span::styled(
             format!("{}", branch.commit_count),
-            Style::default().fg(Color::White),
+            Style::default().fg(palette().text),
         ),
     ]));
 
@@ -296,27 +372,65 @@ fn draw_branch_summary(f: &mut Frame, area: Rect, branch: &super::app::BranchInf
             Span::styled(
                 "Upstream:",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" "),
-            Span::styled(upstream.clone(), Style::default().fg(Color::Magenta)),
+            Span::styled(upstream.clone(), Style::default().fg(palette().special)),
+        ]));
+    }
+
+    if let Some((ahead, behind)) = branch.ahead_behind {
+        lines.push(Line::from(vec![
+            Span::raw(" "),
+            Span::styled(
+                "Ahead/behind:",
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" "),
+            Span::styled(
+                format!("{} ahead, {} behind", ahead, behind),
+                Style::default().fg(palette().text),
+            ),
         ]));
     }
 
     // Last commit age (if known)
     if let Some(ref commit) = branch.last_commit {
-        let age = format_relative_time(commit.commit_time);
+        let age = format_date(commit.commit_time, relative_time);
         lines.push(Line::from(vec![
             Span::raw(" "),
             Span::styled(
                 "Last commit:",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" "),
-            Span::styled(age, Style::default().fg(Color::Cyan)),
+            Span::styled(age, Style::default().fg(palette().accent)),
+        ]));
+
+        let short_hash = branch
+            .last_commit_hash
+            .map(|h| hash_to_hex(&h)[..8].to_string())
+            .unwrap_or_default();
+        lines.push(Line::from(vec![
+            Span::raw(" "),
+            Span::styled(
+                "Tip:",
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" "),
+            Span::styled(short_hash, Style::default().fg(palette().highlight)),
+            Span::raw(" "),
+            Span::styled(
+                commit.summary().to_string(),
+                Style::default().fg(palette().text),
+            ),
         ]));
     }
 
@@ -357,7 +471,7 @@ fn draw_branch_commit_list(f: &mut Frame, area: Rect, branch: &super::app::Branc
 
     if entries.is_empty() {
         let empty = Paragraph::new("No commits on this branch yet")
-            .style(Style::default().fg(Color::DarkGray));
+            .style(Style::default().fg(palette().muted));
         f.render_widget(empty, area);
         return;
     }
@@ -380,11 +494,11 @@ fn draw_branch_commit_list(f: &mut Frame, area: Rect, branch: &super::app::Branc
             Block::default()
                 .borders(Borders::TOP)
                 .title(" Commits ")
-                .title_style(Style::default().fg(Color::Blue)),
+                .title_style(Style::default().fg(palette().title)),
         )
         .highlight_style(
             Style::default()
-                .bg(Color::DarkGray)
+                .bg(palette().selection)
                 .add_modifier(Modifier::BOLD),
         );
 
@@ -395,26 +509,26 @@ fn create_commit_item(entry: &CommitListEntry, is_selected: bool) -> ListItem {
     let bullet = if entry.is_head { "●" } else { "○" };
 
     let bullet_style = if entry.is_head {
-        Style::default().fg(Color::Green)
+        Style::default().fg(palette().added)
     } else {
-        Style::default().fg(Color::DarkGray)
+        Style::default().fg(palette().muted)
     };
 
     let hash_style = Style::default()
-        .fg(Color::Cyan)
+        .fg(palette().accent)
         .add_modifier(Modifier::BOLD);
 
     let summary_style = if is_selected {
         Style::default()
-            .fg(Color::Yellow)
+            .fg(palette().highlight)
             .add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(palette().text)
     };
 
-    let meta_style = Style::default().fg(Color::DarkGray);
+    let meta_style = Style::default().fg(palette().muted);
 
-    let time_str = format_relative_time(entry.timestamp);
+    let time_str = format_date(entry.timestamp, relative_time);
 
     // Line 1: bullet, short hash, summary
     let line1 = Line::from(vec![
@@ -439,7 +553,7 @@ fn create_commit_item(entry: &CommitListEntry, is_selected: bool) -> ListItem {
         meta_spans.push(Span::styled(
             "merge",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         ));
     }
@@ -450,45 +564,94 @@ fn create_commit_item(entry: &CommitListEntry, is_selected: bool) -> ListItem {
 }
 
 fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
-    let help_text = if app.rename_mode {
+    let help_text = if app.search_mode {
+        Line::from(vec![
+            Span::styled(" Search: ", Style::default().fg(palette().accent)),
+            Span::styled(
+                app.search_query.clone(),
+                Style::default().fg(palette().highlight),
+            ),
+            Span::styled("_", Style::default().fg(palette().highlight)),
+            Span::raw("  "),
+            Span::styled("↑/↓", Style::default().fg(palette().accent)),
+            Span::raw(" move  "),
+            Span::styled("Enter", Style::default().fg(palette().added)),
+            Span::raw(" keep filter  "),
+            Span::styled("Esc", Style::default().fg(palette().muted)),
+            Span::raw(" to cancel"),
+        ])
+    } else if app.rename_mode {
         Line::from(vec![
-            Span::styled(" Branch name: ", Style::default().fg(Color::Cyan)),
-            Span::styled(&app.new_branch_name, Style::default().fg(Color::Yellow)),
-            Span::styled("_", Style::default().fg(Color::Yellow)),
+            Span::styled(" Branch name: ", Style::default().fg(palette().accent)),
+            Span::styled(
+                &app.new_branch_name,
+                Style::default().fg(palette().highlight),
+            ),
+            Span::styled("_", Style::default().fg(palette().highlight)),
             Span::raw("  "),
-            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::styled("Enter", Style::default().fg(palette().added)),
             Span::raw(" to confirm  "),
-            Span::styled("Esc", Style::default().fg(Color::DarkGray)),
+            Span::styled("Esc", Style::default().fg(palette().muted)),
             Span::raw(" to cancel"),
         ])
+    } else if app.checkout_mode && app.selected_branch().is_some_and(|b| b.is_remote) {
+        let remote_branch = app
+            .selected_branch()
+            .and_then(|b| b.name.strip_prefix("remotes/"))
+            .unwrap_or("");
+        let local = remote_branch
+            .split_once('/')
+            .map(|(_, name)| name)
+            .unwrap_or("");
+        Line::from(vec![
+            Span::styled(" Checkout ", Style::default().fg(palette().highlight)),
+            Span::styled(
+                remote_branch,
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" as new branch "),
+            Span::styled(
+                local,
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("?  "),
+            Span::styled("y/Enter", Style::default().fg(palette().added)),
+            Span::raw(" yes  "),
+            Span::styled("n/Esc", Style::default().fg(palette().muted)),
+            Span::raw(" no"),
+        ])
     } else if app.checkout_mode {
         Line::from(vec![
-            Span::styled(" Checkout ", Style::default().fg(Color::Yellow)),
+            Span::styled(" Checkout ", Style::default().fg(palette().highlight)),
             Span::styled(
                 app.selected_branch().map(|b| b.name.as_str()).unwrap_or(""),
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw("?  "),
-            Span::styled("y/Enter", Style::default().fg(Color::Green)),
+            Span::styled("y/Enter", Style::default().fg(palette().added)),
             Span::raw(" yes  "),
-            Span::styled("n/Esc", Style::default().fg(Color::DarkGray)),
+            Span::styled("n/Esc", Style::default().fg(palette().muted)),
             Span::raw(" no"),
         ])
     } else if app.delete_mode {
         Line::from(vec![
-            Span::styled(" Delete ", Style::default().fg(Color::Red)),
+            Span::styled(" Delete ", Style::default().fg(palette().removed)),
             Span::styled(
                 app.selected_branch().map(|b| b.name.as_str()).unwrap_or(""),
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw("?  "),
-            Span::styled("y/Enter", Style::default().fg(Color::Green)),
+            Span::styled("y/Enter", Style::default().fg(palette().added)),
             Span::raw(" yes  "),
-            Span::styled("n/Esc", Style::default().fg(Color::DarkGray)),
+            Span::styled("n/Esc", Style::default().fg(palette().muted)),
             Span::raw(" no"),
         ])
     } else {
@@ -496,68 +659,56 @@ fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
             Span::styled(
                 " j/k",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" navigate  "),
             Span::styled(
                 "g/G",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" top/bottom  "),
+            Span::styled(
+                "/",
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" search  "),
             Span::styled(
                 "c",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" checkout  "),
             Span::styled(
                 "d",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" delete  "),
             Span::styled(
                 "r",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" rename  "),
             Span::styled(
                 "q",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" quit "),
         ])
     };
 
-    let footer = Paragraph::new(help_text).style(Style::default().bg(Color::DarkGray));
+    let footer = Paragraph::new(help_text).style(Style::default().bg(palette().selection));
 
     f.render_widget(footer, area);
 }
-
-fn format_relative_time(timestamp: u64) -> String {
-    let now = chrono::Utc::now().timestamp() as u64;
-    let seconds = now.saturating_sub(timestamp);
-
-    if seconds < 60 {
-        format!("{} seconds ago", seconds)
-    } else if seconds < 3600 {
-        format!("{} minutes ago", seconds / 60)
-    } else if seconds < 86400 {
-        format!("{} hours ago", seconds / 3600)
-    } else if seconds < 2592000 {
-        format!("{} days ago", seconds / 86400)
-    } else if seconds < 31536000 {
-        format!("{} months ago", seconds / 2592000)
-    } else {
-        format!("{} years ago", seconds / 31536000)
-    }
-}
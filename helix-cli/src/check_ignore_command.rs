@@ -0,0 +1,62 @@
+/*
+`helix check-ignore <path>...` - debug why paths are (or aren't) ignored.
+
+Prints each given path that is ignored, or with --verbose the rule that
+decided it, in the same format as `git check-ignore -v`:
+
+  <source>:<line>:<pattern>\t<path>
+
+With --verbose, paths re-included by a `!pattern` are printed too, since the
+negation is what decided them.
+*/
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use crate::ignore::{IgnoreMatch, IgnoreRules};
+
+#[derive(Debug, Clone)]
+pub struct CheckIgnoreResult {
+    pub path: PathBuf,
+    pub matched: Option<IgnoreMatch>,
+}
+
+impl CheckIgnoreResult {
+    pub fn is_ignored(&self) -> bool {
+        self.matched.as_ref().is_some_and(|m| m.ignored)
+    }
+}
+
+/// Look up the deciding ignore rule for each path (relative to the repo root)
+pub fn check_ignore(repo_path: &Path, paths: &[PathBuf]) -> Result<Vec<CheckIgnoreResult>> {
+    let rules = IgnoreRules::load(repo_path);
+
+    Ok(paths
+        .iter()
+        .map(|path| CheckIgnoreResult {
+            path: path.clone(),
+            matched: rules.explain(path),
+        })
+        .collect())
+}
+
+/// Print results like `git check-ignore`; returns whether any path was ignored
+pub fn print_results(results: &[CheckIgnoreResult], verbose: bool) -> bool {
+    for result in results {
+        match (&result.matched, verbose) {
+            (Some(m), true) => {
+                let line = m.line.map(|l| l.to_string()).unwrap_or_default();
+                println!(
+                    "{}:{}:{}\t{}",
+                    m.source,
+                    line,
+                    m.pattern,
+                    result.path.display()
+                );
+            }
+            _ if result.is_ignored() => println!("{}", result.path.display()),
+            _ => {}
+        }
+    }
+
+    results.iter().any(CheckIgnoreResult::is_ignored)
+}
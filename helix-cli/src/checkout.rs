@@ -1,15 +1,18 @@
 use anyhow::{bail, Context, Result};
-use helix_protocol::hash::{hash_to_hex, Hash};
+use helix_protocol::hash::{hash_bytes, hash_to_hex, Hash};
 use helix_protocol::message::ObjectType;
 use helix_protocol::storage::FsObjectStore;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-
+use crate::file_mode::{self, SymlinkStrategy};
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::format::EntryFlags;
 use crate::helix_index::tree::{EntryType, Tree};
+use crate::line_endings::LineEndings;
+use crate::path_policy::PathPolicy;
+use crate::promisor::{self, Promised};
 
 pub struct CheckoutOptions {
     pub verbose: bool,
@@ -79,7 +82,21 @@ pub fn checkout_tree_to_path(
     let new_files = collect_tree_files(&store, &tree_hash, Path::new(""))?;
     let new_file_set: HashSet<PathBuf> = new_files.keys().cloned().collect();
 
-    // If we have a before commit, delete files that no longer exist
+    // Paths that differ only in case or Unicode normalization overwrite each other here
+    let policy = PathPolicy::load(repo_path);
+    let mut sorted_paths: Vec<&Path> = new_files.keys().map(|p| p.as_path()).collect();
+    sorted_paths.sort();
+    for (first, second) in policy.find_collisions(sorted_paths) {
+        eprintln!(
+            "warning: '{}' and '{}' collide on this filesystem; only one of them will be checked out",
+            first.display(),
+            second.display()
+        );
+    }
+
+    // If we have a before commit, delete files that no longer exist and leave the ones it
+    // already had with the same content as they are
+    let mut unchanged = HashSet::new();
     if let Some(before) = before_commit {
         let before_bytes = store
             .read_object(&ObjectType::Commit, before)
@@ -87,6 +104,11 @@ pub fn checkout_tree_to_path(
 
         let before_tree_hash = parse_tree_hash_from_commit(&before_bytes)?;
         let before_files = collect_tree_files(&store, &before_tree_hash, Path::new(""))?;
+        unchanged = before_files
+            .iter()
+            .filter(|(path, blob)| new_files.get(*path) == Some(*blob))
+            .map(|(path, _)| path.clone())
+            .collect();
 
         // Delete files that were in before but not in new
         for (path, _) in &before_files {
@@ -105,8 +127,114 @@ pub fn checkout_tree_to_path(
         }
     }
 
+    // Promised blobs the filter now covers (it may have been widened) are fetched in one go;
+    // the rest stay on the remote
+    let filter = promisor::read_filter(repo_path)?;
+    promisor::fetch_promised(
+        repo_path,
+        new_files
+            .iter()
+            .filter(|(path, _)| {
+                filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(&path.to_string_lossy()))
+            })
+            .map(|(_, blob)| *blob),
+    )?;
+
     // Recursively checkout the tree
-    checkout_tree_recursive(&store, dest_path, &tree_hash, Path::new(""), options)
+    let settings = WorkTreeSettings {
+        symlinks: SymlinkStrategy::load(repo_path),
+        line_endings: LineEndings::load(repo_path),
+        promised: Promised::load(repo_path),
+        unchanged,
+    };
+    checkout_tree_recursive(
+        &store,
+        dest_path,
+        &tree_hash,
+        Path::new(""),
+        &settings,
+        options,
+    )
+}
+
+/// Files that moving the working tree from `before` to `after` changes, but whose working
+/// copy or index entry already differs from `before`. Checking out over them with `force`
+/// would lose that uncommitted work.
+pub fn local_changes_in_the_way(
+    repo_path: &Path,
+    before: Option<&Hash>,
+    after: &Hash,
+) -> Result<Vec<PathBuf>> {
+    let store = FsObjectStore::new(repo_path);
+    let files_of = |commit: &Hash| -> Result<HashMap<PathBuf, Hash>> {
+        let bytes = store
+            .read_object(&ObjectType::Commit, commit)
+            .with_context(|| format!("Failed to read commit {}", hash_to_hex(commit)))?;
+        collect_tree_files(&store, &parse_tree_hash_from_commit(&bytes)?, Path::new(""))
+    };
+    let old_files = before.map(files_of).transpose()?.unwrap_or_default();
+    let new_files = files_of(after)?;
+    let touched: BTreeSet<&PathBuf> = old_files
+        .keys()
+        .chain(new_files.keys())
+        .filter(|path| old_files.get(*path) != new_files.get(*path))
+        .collect();
+    if touched.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let index_path = repo_path.join(".helix").join("helix.idx");
+    let index = HelixIndexData::load_from_path(&index_path, repo_path)?;
+    let staged: HashMap<&Path, Hash> = index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .map(|e| (e.path.as_path(), e.oid))
+        .collect();
+    let line_endings = LineEndings::load(repo_path);
+
+    let mut in_the_way = Vec::new();
+    for path in touched {
+        let committed = old_files.get(path);
+        let on_disk = working_blob(&repo_path.join(path), &line_endings)?;
+        // A missing entry is a staged deletion; checking the file out again loses nothing
+        let index_changed = staged
+            .get(path.as_path())
+            .is_some_and(|oid| Some(oid) != committed);
+        let worktree_changed = on_disk.is_some() && on_disk.as_ref() != committed;
+        if index_changed || worktree_changed {
+            in_the_way.push(path.clone());
+        }
+    }
+    Ok(in_the_way)
+}
+
+/// The blob hash of a working tree file as it would be stored, or None if there is no file
+fn working_blob(path: &Path, line_endings: &LineEndings) -> Result<Option<Hash>> {
+    let Ok(meta) = path.symlink_metadata() else {
+        return Ok(None);
+    };
+    if meta.file_type().is_symlink() {
+        let target = fs::read_link(path)?;
+        return Ok(Some(hash_bytes(target.to_string_lossy().as_bytes())));
+    }
+    if !meta.is_file() {
+        return Ok(None);
+    }
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(hash_bytes(&line_endings.normalize(&content))))
+}
+
+/// Repo settings that decide how (and whether) each blob is written out
+struct WorkTreeSettings {
+    symlinks: SymlinkStrategy,
+    line_endings: LineEndings,
+    /// Blobs a partial clone left on the remote; their files are skipped
+    promised: Promised,
+    /// Files the previous commit had with the same content; kept as they are when present
+    unchanged: HashSet<PathBuf>,
 }
 
 /// Collect all files in a tree recursively (path -> blob hash)
@@ -162,6 +290,7 @@ fn checkout_tree_recursive(
     dest_root: &Path,
     tree_hash: &Hash,
     relative_path: &Path,
+    settings: &WorkTreeSettings,
     options: &CheckoutOptions,
 ) -> Result<u64> {
     let tree_bytes = store
@@ -175,14 +304,33 @@ fn checkout_tree_recursive(
         let entry_path = relative_path.join(&entry.name);
         let full_path = dest_root.join(&entry_path);
 
+        if settings.promised.contains(&entry.oid)
+            && !store.has_object(&ObjectType::Blob, &entry.oid)
+        {
+            if options.verbose {
+                println!("  Skipping {} (not fetched)", entry_path.display());
+            }
+            continue;
+        }
+
+        if settings.unchanged.contains(&entry_path) && full_path.symlink_metadata().is_ok() {
+            continue;
+        }
+
         match entry.entry_type {
             EntryType::Tree => {
                 fs::create_dir_all(&full_path).with_context(|| {
                     format!("Failed to create directory {}", full_path.display())
                 })?;
 
-                files_written +=
-                    checkout_tree_recursive(store, dest_root, &entry.oid, &entry_path, options)?;
+                files_written += checkout_tree_recursive(
+                    store,
+                    dest_root,
+                    &entry.oid,
+                    &entry_path,
+                    settings,
+                    options,
+                )?;
             }
             EntryType::File | EntryType::FileExecutable => {
                 let blob_bytes = store
@@ -200,14 +348,14 @@ fn checkout_tree_recursive(
                     continue;
                 }
 
-                fs::write(&full_path, &blob_bytes)
-                    .with_context(|| format!("Failed to write file {}", full_path.display()))?;
+                fs::write(
+                    &full_path,
+                    settings.line_endings.to_working_tree(&blob_bytes),
+                )
+                .with_context(|| format!("Failed to write file {}", full_path.display()))?;
 
-                #[cfg(unix)]
                 if entry.entry_type == EntryType::FileExecutable {
-                    let mut perms = fs::metadata(&full_path)?.permissions();
-                    perms.set_mode(0o755);
-                    fs::set_permissions(&full_path, perms)?;
+                    file_mode::set_executable(&full_path)?;
                 }
 
                 if options.verbose {
@@ -248,9 +396,7 @@ fn checkout_tree_recursive(
                     }
                 }
 
-                #[cfg(unix)]
-                std::os::unix::fs::symlink(&target, &full_path)
-                    .with_context(|| format!("Failed to create symlink {}", full_path.display()))?;
+                file_mode::write_symlink(&target, &full_path, settings.symlinks)?;
 
                 if options.verbose {
                     println!("  {} -> {}", entry_path.display(), target);
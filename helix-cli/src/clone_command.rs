@@ -0,0 +1,387 @@
+/*
+`helix clone <source> [<dir>]` - copy a repository and check out one branch.
+
+The source is anything a remote can be: an HTTP(S) URL, a file:// URL or a
+plain path. The clone is a new repository (as `helix init` makes it) whose
+helix.toml names the source as `origin`, followed by a `helix pull origin
+<branch>`. The branch is --branch, else the one the source's HEAD names (read
+from disk for a local source, from the server's ListRefs answer otherwise),
+else main.
+
+The last path component of an HTTP(S) URL names the repository on the server
+and the rest is the server: `helix clone http://host/app mydir` records
+`url = "http://host"` and `repo = "app"` for origin, so later pushes and pulls
+ask for `app` whatever the directory is called. A URL without a path asks the
+server for the directory's name.
+
+Local sources are cloned fast: every object file is hard-linked into the new
+store (copied when the link fails, e.g. across file systems) and the
+remote-tracking ref is set to the source branch up front, so the pull finds
+itself up to date and only checks the branch out. Objects never change once
+written, so sharing their files is safe.
+
+--filter path:<dir>[,<dir>...] makes a partial clone: every commit and tree is
+fetched, but only the blobs under those paths (see promisor.rs). Filtered
+clones always go through the pull, even from a local source.
+
+A clone that fails part way removes the directory it created.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::commit::write_remote_tracking;
+use helix_protocol::filter::PathFilter;
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::{FsObjectStore, FsRefStore};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::credential;
+use crate::init_command::{init_repo_layout, HelixConfig, RemoteConfig, RemotesTable};
+use crate::pull_command::{pull, PullOptions};
+use crate::repo_config;
+use crate::transport;
+
+#[derive(Debug, Clone, Default)]
+pub struct CloneOptions {
+    /// Branch to check out instead of the source's default
+    pub branch: Option<String>,
+    pub verbose: bool,
+    /// Only fetch the blobs under these paths
+    pub filter: Option<PathFilter>,
+}
+
+/// Clone `source` into `dest` (by default a directory named after the source) and return
+/// the new repository's path
+pub async fn clone(source: &str, dest: Option<&Path>, options: CloneOptions) -> Result<PathBuf> {
+    let url = transport::url_for(source)?;
+    let dest = match dest {
+        Some(dest) => dest.to_path_buf(),
+        None => PathBuf::from(default_dir_name(&url)?),
+    };
+    if dest.exists() && fs::read_dir(&dest)?.next().is_some() {
+        bail!(
+            "Destination {} already exists and is not empty",
+            dest.display()
+        );
+    }
+
+    let created = !dest.exists();
+    fs::create_dir_all(&dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    let dest = dest.canonicalize()?;
+
+    let result = clone_into(&url, &dest, &options).await;
+    if result.is_err() && created {
+        let _ = fs::remove_dir_all(&dest);
+    }
+    result.map(|()| dest)
+}
+
+async fn clone_into(url: &str, dest: &Path, options: &CloneOptions) -> Result<()> {
+    init_repo_layout(dest)?;
+    let origin = origin_for(url);
+    add_origin(dest, origin.clone())?;
+
+    let source = transport::local_path(url);
+    let branch = match (&options.branch, &source) {
+        (Some(branch), _) => Some(branch.clone()),
+        (None, Some(source)) => head_branch(source),
+        (None, None) => remote_head_branch(dest, &origin).await?,
+    }
+    .unwrap_or_else(|| "main".to_string());
+    fs::write(
+        dest.join(".helix").join("HEAD"),
+        format!("ref: refs/heads/{branch}\n"),
+    )?;
+
+    if let Some(source) = source.as_ref().filter(|_| options.filter.is_none()) {
+        let linked = link_objects(source, dest)?;
+        if options.verbose {
+            println!("Linked {linked} objects from {}", source.display());
+        }
+        if let Some(head) = FsRefStore::new(source).get_ref(&format!("refs/heads/{branch}"))? {
+            write_remote_tracking(dest, "origin", &branch, head)?;
+        }
+    }
+
+    println!("Cloning {url} into {}", dest.display());
+    let pull_options = PullOptions {
+        verbose: options.verbose,
+        filter: options.filter.clone(),
+        ..Default::default()
+    };
+    pull(dest, "origin", &branch, pull_options).await?;
+    Ok(())
+}
+
+/// The last path component of the URL, without a `.helix` suffix
+fn default_dir_name(url: &str) -> Result<String> {
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let name = match path.trim_end_matches('/').split_once('/') {
+        Some((_, path)) => path.rsplit('/').next().unwrap_or_default(),
+        None => "",
+    };
+    let name = name.strip_suffix(".helix").unwrap_or(name);
+    if name.is_empty() {
+        bail!("Can't tell a directory name from {url}; give one after the URL");
+    }
+    Ok(name.to_string())
+}
+
+/// The `origin` remote for a clone of `url`. An HTTP(S) URL is split into the server and
+/// the repository its last path component names.
+fn origin_for(url: &str) -> RemoteConfig {
+    match transport::split_repo_url(url) {
+        (server, Some(repo)) => RemoteConfig {
+            repo: Some(repo.to_string()),
+            ..RemoteConfig::new(server)
+        },
+        (url, None) => RemoteConfig::new(url),
+    }
+}
+
+/// Name `origin` as the remote in the new repo's helix.toml
+fn add_origin(repo_path: &Path, origin: RemoteConfig) -> Result<()> {
+    let config_path = repo_path.join(repo_config::CONFIG_FILE);
+    let mut config: HelixConfig = repo_config::parse(&fs::read_to_string(&config_path)?)?;
+    config
+        .remotes
+        .get_or_insert_with(|| RemotesTable {
+            map: Default::default(),
+        })
+        .insert("origin", origin);
+    fs::write(&config_path, toml::to_string_pretty(&config)?)
+        .context("Failed to write helix.toml")?;
+    Ok(())
+}
+
+/// The branch HEAD points at in a local repository
+fn head_branch(repo: &Path) -> Option<String> {
+    let head = fs::read_to_string(repo.join(".helix").join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(str::to_string)
+}
+
+/// The branch the server's HEAD names, when the server says
+async fn remote_head_branch(repo_path: &Path, origin: &RemoteConfig) -> Result<Option<String>> {
+    let token = credential::token_for_remote(repo_path, origin, &origin.url)?;
+    let remote = transport::connect_remote(
+        origin,
+        &origin.url,
+        token.as_deref(),
+        &origin.repo_name(repo_path),
+    )?;
+    let list = remote
+        .ref_list("refs/heads/")
+        .await
+        .context("Failed to list the remote's branches")?;
+    Ok(list
+        .head
+        .and_then(|head| head.strip_prefix("refs/heads/").map(str::to_string)))
+}
+
+/// Hard-link (or copy) every object file of `source` into `dest`'s store, in `dest`'s
+/// layout
+fn link_objects(source: &Path, dest: &Path) -> Result<u64> {
+    let from = FsObjectStore::new(source);
+    let to = FsObjectStore::new(dest);
+    let mut linked = 0;
+    for ty in [
+        ObjectType::Blob,
+        ObjectType::Tree,
+        ObjectType::Commit,
+        ObjectType::Tag,
+    ] {
+        for hash in from.list_object_hashes(&ty)? {
+            let object = from.object_path(&ty, &hash);
+            let target = to.object_path(&ty, &hash);
+            if target.exists() {
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            if fs::hard_link(&object, &target).is_err() {
+                fs::copy(&object, &target)
+                    .with_context(|| format!("Failed to copy object {}", object.display()))?;
+            }
+            linked += 1;
+        }
+    }
+    Ok(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::commit::{Commit, CommitStore};
+    use crate::helix_index::format::{Entry, EntryFlags};
+    use crate::helix_index::tree::TreeBuilder;
+    use helix_protocol::commit::read_remote_tracking;
+    use helix_protocol::message::ObjectType;
+    use helix_protocol::storage::FsObjectStore;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_local_clone_links_objects_and_checks_out() -> Result<()> {
+        let temp = TempDir::new()?;
+        let source = temp.path().join("app.helix");
+        fs::create_dir_all(&source)?;
+        crate::init_command::init_bare_repo(&source)?;
+        fs::write(source.join(".helix/HEAD"), "ref: refs/heads/trunk\n")?;
+
+        let store = FsObjectStore::new(&source);
+        let blob = store.write_object(&ObjectType::Blob, b"hello\n")?;
+        let entry = Entry {
+            path: "hello.txt".into(),
+            oid: blob,
+            flags: EntryFlags::TRACKED,
+            size: 6,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            file_mode: 0o100644,
+            merge_conflict_stage: 0,
+            reserved: [0u8; 33],
+        };
+        let tree = TreeBuilder::new(&source).build_from_entries(&[entry])?;
+        let commit = Commit::new(tree, vec![], "T <t@x>".into(), "first".into());
+        let commit = CommitStore::new(&source, store)?.write_commit(&commit)?;
+        FsRefStore::new(&source).set_ref("refs/heads/trunk", commit)?;
+
+        let dest = clone(
+            source.to_str().unwrap(),
+            Some(&temp.path().join("app")),
+            CloneOptions::default(),
+        )
+        .await?;
+
+        assert_eq!(fs::read_to_string(dest.join("hello.txt"))?, "hello\n");
+        assert_eq!(
+            FsRefStore::new(&dest).get_ref("refs/heads/trunk")?,
+            Some(commit)
+        );
+        assert_eq!(read_remote_tracking(&dest, "origin", "trunk")?, commit);
+        assert_eq!(
+            fs::read_to_string(dest.join(".helix/HEAD"))?,
+            "ref: refs/heads/trunk\n"
+        );
+        let config = fs::read_to_string(dest.join("helix.toml"))?;
+        assert!(config.contains(&format!("file://{}", source.canonicalize()?.display())));
+
+        // A second clone into the same, now non-empty, directory is refused
+        assert!(clone(
+            source.to_str().unwrap(),
+            Some(&dest),
+            CloneOptions::default()
+        )
+        .await
+        .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_filtered_clone_leaves_other_blobs_promised() -> Result<()> {
+        let temp = TempDir::new()?;
+        let source = temp.path().join("app");
+        fs::create_dir_all(&source)?;
+        crate::init_command::init_bare_repo(&source)?;
+
+        let store = FsObjectStore::new(&source);
+        let mut entries = Vec::new();
+        for (path, content) in [("src/main.rs", "fn main() {}\n"), ("video.bin", "frames")] {
+            let mut entry = Entry::from_blob(
+                path.into(),
+                store.write_object(&ObjectType::Blob, content.as_bytes())?,
+                &source,
+            );
+            entry.size = content.len() as u64;
+            entries.push(entry);
+        }
+        let video = entries[1].oid;
+        let tree = TreeBuilder::new(&source).build_from_entries(&entries)?;
+        let commit = Commit::new(tree, vec![], "T <t@x>".into(), "first".into());
+        let commit = CommitStore::new(&source, store)?.write_commit(&commit)?;
+        FsRefStore::new(&source).set_ref("refs/heads/main", commit)?;
+
+        let options = CloneOptions {
+            filter: Some(PathFilter::parse("path:src")?),
+            ..Default::default()
+        };
+        let dest = clone(
+            source.to_str().unwrap(),
+            Some(&temp.path().join("part")),
+            options,
+        )
+        .await?;
+
+        assert_eq!(
+            fs::read_to_string(dest.join("src/main.rs"))?,
+            "fn main() {}\n"
+        );
+        assert!(!dest.join("video.bin").exists());
+        assert!(!FsObjectStore::new(&dest).has_object(&ObjectType::Blob, &video));
+        assert!(crate::promisor::Promised::load(&dest).contains(&video));
+        assert_eq!(
+            crate::promisor::read_filter(&dest)?,
+            Some(PathFilter::parse("path:src")?)
+        );
+
+        // The missing file is in the index, but not as a deletion
+        let index = crate::helix_index::api::HelixIndexData::load_or_rebuild(&dest)?;
+        let entry = index
+            .entries()
+            .iter()
+            .find(|e| e.path == Path::new("video.bin"))
+            .unwrap();
+        assert!(entry.flags.contains(EntryFlags::ASSUME_UNCHANGED));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_dir_name() -> Result<()> {
+        assert_eq!(default_dir_name("file:///srv/helix/app.helix")?, "app");
+        assert_eq!(
+            default_dir_name("https://helix.example.com/team/app/")?,
+            "app"
+        );
+        assert!(default_dir_name("http://127.0.0.1:8080").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_origin_names_the_server_repo() {
+        let origin = origin_for("http://host:8080/app");
+        assert_eq!(origin.url, "http://host:8080");
+        assert_eq!(origin.repo_name(Path::new("/work/mydir")), "app");
+
+        let origin = origin_for("https://helix.example.com/team/app/");
+        assert_eq!(origin.url, "https://helix.example.com/team");
+        assert_eq!(origin.repo.as_deref(), Some("app"));
+
+        // No path: the server is asked for the directory's name
+        let origin = origin_for("http://127.0.0.1:8080");
+        assert_eq!(origin.url, "http://127.0.0.1:8080");
+        assert_eq!(origin.repo_name(Path::new("/work/mydir")), "mydir");
+
+        assert_eq!(origin_for("file:///srv/helix/app").repo, None);
+    }
+
+    #[tokio::test]
+    async fn test_remote_head_branch_comes_from_list_refs() -> Result<()> {
+        let temp = TempDir::new()?;
+        let source = temp.path().join("app");
+        fs::create_dir_all(&source)?;
+        crate::init_command::init_bare_repo(&source)?;
+        fs::write(source.join(".helix/HEAD"), "ref: refs/heads/trunk\n")?;
+
+        let dest = temp.path().join("mydir");
+        fs::create_dir_all(&dest)?;
+        init_repo_layout(&dest)?;
+        let origin = origin_for(&transport::url_for(source.to_str().unwrap())?);
+        assert_eq!(
+            remote_head_branch(&dest, &origin).await?.as_deref(),
+            Some("trunk")
+        );
+        Ok(())
+    }
+}
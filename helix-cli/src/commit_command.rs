@@ -13,18 +13,40 @@
 // helix commit -m "Message" --author "Name"    # Custom author
 // helix commit -m "Message" --amend            # Amend previous
 // helix commit -m "Message" --allow-empty      # Empty commit
+// helix commit -m "Message" --signoff          # Add Signed-off-by
+// helix commit --amend --no-edit               # Amend, keeping the message
+// helix commit --amend --reset-author          # Amend as yourself, now
+// helix commit -m "Message" --date "2024-05-01 12:00:00"
+// helix commit -m "Message" --metadata build-id=4711 --metadata review=42
+// helix commit -m "Message" --allow-missing-objects  # Recovery: parent/tree may be missing
+// helix commit                                 # Write the message in $EDITOR
+//
+// An amend keeps the previous author and author date unless --author,
+// --reset-author or --date say otherwise, and may have nothing staged. It also
+// keeps the previous commit's metadata, with any --metadata added on top.
+//
+// Trailers, templates and message checks come from [commit] in helix.toml
+// (see commit_message.rs).
+//
+// Moving the branch and clearing the index's staged flags go through a commit
+// transaction (helix_index/transaction.rs), so a crash between the two is
+// finished or undone by the next command.
 
+use crate::author::resolve_author;
+use crate::commit_message;
 use crate::helix_index::api::HelixIndexData;
 use crate::helix_index::commit::{Commit, CommitStore};
 use crate::helix_index::format::EntryFlags;
+use crate::helix_index::transaction::{self, CommitTransaction, Recovery};
 use crate::helix_index::tree::TreeBuilder;
-use crate::init_command::HelixConfig;
 use crate::sandbox_command::RepoContext;
+use crate::say;
 use anyhow::{Context, Result};
 use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
 use helix_protocol::storage::FsObjectStore;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct CommitOptions {
     pub message: String,
@@ -32,6 +54,20 @@ pub struct CommitOptions {
     pub allow_empty: bool,
     pub amend: bool,
     pub verbose: bool,
+    /// Add a Signed-off-by trailer for the author
+    pub signoff: bool,
+    /// With amend, keep the previous message (`message` is ignored)
+    pub no_edit: bool,
+    /// With amend, take the author from config and the author date from now
+    pub reset_author: bool,
+    /// Author date, seconds since the Unix epoch (see `parse_date`)
+    pub date: Option<u64>,
+    /// Metadata headers to record on the commit. An amend keeps the previous commit's,
+    /// with these added or replacing them.
+    pub metadata: BTreeMap<String, String>,
+    /// Write the commit even if its tree or parent isn't stored, to recover a repository
+    /// that has already lost objects
+    pub allow_missing_objects: bool,
 }
 
 impl Default for CommitOptions {
@@ -42,18 +78,48 @@ impl Default for CommitOptions {
             allow_empty: false,
             amend: false,
             verbose: false,
+            signoff: false,
+            no_edit: false,
+            reset_author: false,
+            date: None,
+            metadata: BTreeMap::new(),
+            allow_missing_objects: false,
         }
     }
 }
 
-/// Create a commit from staged files
+/// A commit made by `create_commit`
+pub struct CreatedCommit {
+    pub hash: Hash,
+    /// First line of the message
+    pub summary: String,
+    pub files_changed: usize,
+}
+
+/// Create a commit from staged files and print its summary
 pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
+    let created = create_commit(repo_path, options)?;
+
+    let short_hash = hash_to_hex(&created.hash);
+    say!("[{}] {}", &short_hash[..8], created.summary);
+    say!("{} files changed", created.files_changed);
+
+    Ok(created.hash)
+}
+
+/// Create a commit from staged files, printing nothing unless `options.verbose`, e.g. for
+/// the status TUI
+pub fn create_commit(repo_path: &Path, options: CommitOptions) -> Result<CreatedCommit> {
     let context = RepoContext::detect(repo_path)?;
+    recover_interrupted_commit(&context)?;
 
     let object_store = FsObjectStore::new(&context.repo_root);
     let commit_store = CommitStore::new(&context.repo_root, object_store)?;
 
-    if options.message.trim().is_empty() {
+    if (options.no_edit || options.reset_author) && !options.amend {
+        anyhow::bail!("--no-edit and --reset-author only apply with --amend");
+    }
+    if options.message.trim().is_empty() && !options.no_edit {
         anyhow::bail!("Commit message cannot be empty. Use -m <message>");
     }
 
@@ -61,12 +127,12 @@ pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
 
     if options.verbose {
         if context.is_sandbox() {
-            println!(
+            say!(
                 "Committing in sandbox: {}",
                 context.sandbox_name().unwrap_or_default()
             );
         }
-        println!("Loaded index (generation {})", index.generation());
+        say!("Loaded index (generation {})", index.generation());
     }
 
     // get the staged entries
@@ -77,14 +143,15 @@ pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
         .cloned()
         .collect();
 
-    if staged_entries.is_empty() && !options.allow_empty {
+    // Amending may only reword the commit or change its metadata
+    if staged_entries.is_empty() && !options.allow_empty && !options.amend {
         anyhow::bail!("No changes staged for commit. Use 'helix add <files>' to stage changes.");
     }
 
     if options.verbose {
-        println!("Staging area: {} files", staged_entries.len());
+        say!("Staging area: {} files", staged_entries.len());
         for entry in &staged_entries {
-            println!("  {}", entry.path.display());
+            say!("  {}", entry.path.display());
         }
     }
 
@@ -102,10 +169,21 @@ pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
         anyhow::bail!("Cannot amend - no previous commit exists");
     }
 
+    // HEAD can name a commit that was never stored, e.g. after an interrupted import.
+    // Committing on top of it is only for recovery.
+    let head_missing = head_commit_hash.is_some_and(|hash| !commit_store.commit_exists(&hash));
+    if head_missing && !options.allow_missing_objects {
+        anyhow::bail!(
+            "HEAD commit {} is missing from the object store, refusing to commit on top \
+             of it (--allow-missing-objects overrides this)",
+            hash_to_hex(&head_commit_hash.unwrap_or_default())
+        );
+    }
+
     // Build tree from all tracked entries
     // this gives us a snapshot of the tree for every commit which makes it really fast to check out commits and compare them
     if options.verbose {
-        println!(
+        say!(
             "Building tree from {} entries...",
             all_tracked_entries.len()
         );
@@ -116,11 +194,11 @@ pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
         .context("Failed to build tree")?;
 
     if options.verbose {
-        println!("Created tree: {}", hash_to_hex(&tree_hash)[..8].to_string());
+        say!("Created tree: {}", hash_to_hex(&tree_hash)[..8].to_string());
     }
 
     // Check if tree built from index entries would be same as HEAD commit (no changes)
-    if !options.allow_empty && !options.amend {
+    if !options.allow_empty && !options.amend && !head_missing {
         if let Some(head_hash) = head_commit_hash {
             let head_commit_obj = commit_store.read_commit(&head_hash)?;
 
@@ -131,77 +209,113 @@ pub fn commit(repo_path: &Path, options: CommitOptions) -> Result<Hash> {
                 }
 
                 if options.verbose {
-                    println!("Creating first native Helix commit after import.");
+                    say!("Creating first native Helix commit after import.");
                 }
             }
         }
     }
 
-    let author = if let Some(author) = options.author {
-        author
-    } else {
-        get_author(repo_path)?
+    let prev_commit = match head_commit_hash {
+        Some(head_hash) if options.amend => Some(commit_store.read_commit(&head_hash)?),
+        _ => None,
     };
 
-    let commit = if options.amend {
-        let head_hash = head_commit_hash.unwrap();
-        let prev_commit = commit_store.read_commit(&head_hash)?;
-
-        Commit::new(tree_hash, prev_commit.parents, author, options.message)
+    // An amend keeps the previous authorship unless told otherwise
+    let author = match (&options.author, &prev_commit) {
+        (None, Some(prev)) if !options.reset_author => prev.author.clone(),
+        _ => resolve_author(repo_path, options.author.as_deref())?,
+    };
+    let author_time = options.date.or(match &prev_commit {
+        Some(prev) if !options.reset_author => Some(prev.author_time),
+        _ => None,
+    });
+
+    let message = match &prev_commit {
+        Some(prev) if options.no_edit => prev.message.clone(),
+        _ => options.message,
+    };
+    let message = commit_message::finalize(&context.repo_root, &message, &author, options.signoff)?;
+
+    let prev_headers = prev_commit
+        .as_ref()
+        .map(|prev| prev.headers.clone())
+        .unwrap_or_default();
+    let commit = if let Some(prev_commit) = prev_commit {
+        Commit::new(tree_hash, prev_commit.parents, author, message)
     } else if let Some(parent_hash) = head_commit_hash {
         // Normal commit with parent
-        Commit::with_parent(tree_hash, parent_hash, author, options.message)
+        Commit::with_parent(tree_hash, parent_hash, author, message)
     } else {
         // Initial commit
-        Commit::initial(tree_hash, author, options.message)
+        Commit::initial(tree_hash, author, message)
+    };
+    let commit = match author_time {
+        Some(author_time) => commit.with_author_time(author_time),
+        None => commit,
+    };
+    let mut headers = prev_headers;
+    headers.extend(options.metadata);
+    let commit = match headers.is_empty() {
+        true => commit,
+        false => commit.with_headers(headers),
     };
 
     // Store commit
     if options.verbose {
-        println!("Writing commit object...");
+        say!("Writing commit object...");
     }
 
     let commit_hash = commit.get_hash();
 
-    // Write to storage (returns the same hash)
+    // Write to storage (returns the same hash). The parent may be missing after an
+    // interrupted import, which is worth stopping for unless the user is recovering.
+    if !options.allow_missing_objects {
+        commit_store.check_references(&commit).context(
+            "Refusing to write a commit that points at missing objects \
+             (--allow-missing-objects overrides this)",
+        )?;
+    }
     commit_store
-        .write_commit(&commit)
+        .write_commit_unchecked(&commit)
         .context("Failed to write commit")?;
 
     if options.verbose {
-        println!(
+        say!(
             "Created commit: {}",
             hash_to_hex(&commit_hash)[..8].to_string()
         );
     }
 
-    // Update HEAD
-    write_head(&context, commit_hash)?;
+    // Update HEAD, unless another commit moved it while we were building this one, and
+    // then clear the staged flags in the index, as one transaction
+    let txn = CommitTransaction::begin(
+        &context.index_path,
+        &context.repo_root,
+        &head_ref_path(&context)?,
+        head_commit_hash,
+        commit_hash,
+        index.generation(),
+    )?;
+    if let Err(e) = write_head(&context, commit_hash, head_commit_hash) {
+        txn.abort()?;
+        return Err(e);
+    }
 
     if options.verbose {
-        println!("Updated HEAD");
+        say!("Updated HEAD");
     }
 
-    // Clear staged flags in index
-    clear_staged_flags(&context)?;
-
-    // Print commit summary
-    let short_hash = hash_to_hex(&commit_hash);
-    let short_hash = &short_hash[..8];
-
-    if commit.is_initial() {
-        println!("[{}] {}", short_hash, commit.summary());
-        println!("{} files changed", staged_entries.len());
-    } else {
-        println!("[{}] {}", short_hash, commit.summary());
-        println!("{} files changed", staged_entries.len());
-    }
+    txn.complete()?;
 
     if !has_native_commits(&context.repo_root) {
         mark_native_commit_exists(&context.repo_root)?;
     }
 
-    Ok(commit_hash)
+    Ok(CreatedCommit {
+        hash: commit_hash,
+        summary: commit.summary().to_string(),
+        files_changed: staged_entries.len(),
+    })
 }
 
 /// Read current HEAD commit hash
@@ -230,8 +344,33 @@ fn read_head(context: &RepoContext) -> Result<Hash> {
     }
 }
 
-/// Write new HEAD commit hash
-fn write_head(context: &RepoContext, commit_hash: Hash) -> Result<()> {
+/// Finish or undo a commit a crash interrupted, saying which
+pub fn recover_interrupted_commit(context: &RepoContext) -> Result<()> {
+    match transaction::recover(&context.index_path, &context.repo_root)? {
+        Some(Recovery::RolledForward(hash)) => eprintln!(
+            "Finished an interrupted commit ({})",
+            &hash_to_hex(&hash)[..8]
+        ),
+        Some(Recovery::RolledBack(hash)) => eprintln!(
+            "Abandoned an interrupted commit ({}); its changes are still staged",
+            &hash_to_hex(&hash)[..8]
+        ),
+        Some(Recovery::Discarded) | None => {}
+    }
+    Ok(())
+}
+
+/// The file a commit moves: the branch HEAD points to, or HEAD itself when detached
+fn head_ref_path(context: &RepoContext) -> Result<PathBuf> {
+    let content = fs::read_to_string(&context.head_path).unwrap_or_default();
+    Ok(match content.trim().strip_prefix("ref:") {
+        Some(ref_path) => context.repo_root.join(".helix").join(ref_path.trim()),
+        None => context.head_path.clone(),
+    })
+}
+
+/// Write new HEAD commit hash, if HEAD still resolves to `expected`
+fn write_head(context: &RepoContext, commit_hash: Hash, expected: Option<Hash>) -> Result<()> {
     if !context.head_path.exists() {
         // If no HEAD exists, write directly (shouldn't happen normally)
         let hash_hex = hash_to_hex(&commit_hash);
@@ -239,90 +378,93 @@ fn write_head(context: &RepoContext, commit_hash: Hash) -> Result<()> {
         return Ok(());
     }
 
-    let content = fs::read_to_string(&context.head_path)?;
-    let content = content.trim();
-
-    if content.starts_with("ref:") {
-        // Update the branch it points to
-        let ref_path = content.strip_prefix("ref:").unwrap().trim();
-        let full_ref_path = context.repo_root.join(".helix").join(ref_path);
-
-        // Ensure parent directory exists
-        if let Some(parent) = full_ref_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        let hash_hex = hash_to_hex(&commit_hash);
-        fs::write(&full_ref_path, hash_hex)?;
-    } else {
-        // Direct HEAD update (detached HEAD)
-        let hash_hex = hash_to_hex(&commit_hash);
-        fs::write(&context.head_path, hash_hex)?;
+    let current = read_head(context).ok();
+    if current != expected {
+        anyhow::bail!(
+            "HEAD moved to {} while committing; nothing was updated. Try again.",
+            current
+                .map(|h| hash_to_hex(&h)[..8].to_string())
+                .unwrap_or_else(|| "nothing".to_string())
+        );
     }
 
-    Ok(())
+    // The branch it points to, or HEAD itself when detached
+    let ref_path = head_ref_path(context)?;
+    if let Some(parent) = ref_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(&ref_path, &hash_to_hex(&commit_hash))
 }
 
-/// Get author from config or environment
-fn get_author(repo_path: &Path) -> Result<String> {
-    let config_path = repo_path.join("helix.toml");
-
-    if config_path.exists() {
-        let content = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read {}", config_path.display()))?;
+/// Replace `path` via a temp file and rename, so readers never see a partial ref
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".lock");
+    let temp = PathBuf::from(temp);
 
-        // Parse TOML into our struct
-        let config: HelixConfig = toml::from_str(&content)
-            .with_context(|| format!("Failed to parse TOML in {}", config_path.display()))?;
-
-        if let Some(user) = config.user {
-            match (user.name, user.email) {
-                // If both name and email exist, format as "Name <email>"
-                (Some(n), Some(e)) => {
-                    return Ok(format!("{} <{}>", n.trim(), e.trim()));
-                }
-                // If only name exists, use it as-is
-                (Some(n), None) => {
-                    let trimmed = n.trim();
-                    if !trimmed.is_empty() {
-                        return Ok(trimmed.to_string());
-                    }
-                }
-                _ => {}
-            }
-        }
-    }
+    fs::write(&temp, content).with_context(|| format!("Failed to write {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to update {}", path.display()))?;
+    Ok(())
+}
 
-    anyhow::bail!(
-        "Author not configured. Set in helix.toml:\n\
-         \n\
-         [user]\n\
-         name = \"Your Name\"\n\
-         email = \"you@example.com\"\n\
-         \n\
-         Or use environment variables:\n\
-         export HELIX_AUTHOR_NAME=\"Your Name\"\n\
-         export HELIX_AUTHOR_EMAIL=\"your@email.com\""
-    )
+/// Parse a `--date` value into seconds since the Unix epoch.
+///
+/// Accepts `@<seconds>`, RFC 3339 (`2024-05-01T12:00:00+02:00`), RFC 2822, and
+/// `YYYY-MM-DD[ HH:MM[:SS]]` in local time.
+/// Parse a `--metadata key=value` argument. Keys are letters, digits, '-', '_' and '.';
+/// values are a single line.
+pub fn parse_metadata(arg: &str) -> Result<(String, String)> {
+    let (key, value) = arg
+        .split_once('=')
+        .with_context(|| format!("Metadata must be key=value, got '{arg}'"))?;
+    let valid_key = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if key.is_empty() || !key.chars().all(valid_key) {
+        anyhow::bail!("Invalid metadata key '{key}': use letters, digits, '-', '_' and '.'");
+    }
+    if value.contains(['\n', '\r']) {
+        anyhow::bail!("Metadata value for '{key}' must be a single line");
+    }
+    Ok((key.to_string(), value.to_string()))
 }
 
-fn clear_staged_flags(context: &RepoContext) -> Result<()> {
-    let mut index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+pub fn parse_date(date: &str) -> Result<u64> {
+    use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
 
-    // Remove entries that were staged for deletion
-    index
-        .entries_mut()
-        .retain(|entry| !entry.flags.contains(EntryFlags::DELETED));
+    let date = date.trim();
+    let timestamp = if let Some(secs) = date.strip_prefix('@') {
+        secs.parse::<i64>().ok()
+    } else if let Ok(dt) = DateTime::parse_from_rfc3339(date) {
+        Some(dt.timestamp())
+    } else if let Ok(dt) = DateTime::parse_from_rfc2822(date) {
+        Some(dt.timestamp())
+    } else {
+        ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|fmt| NaiveDateTime::parse_from_str(date, fmt).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|d| d.and_hms_opt(0, 0, 0))
+            })
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(|dt| dt.timestamp())
+    };
 
-    // Remove STAGED flag from remaining entries
-    for entry in index.entries_mut() {
-        entry.flags.remove(EntryFlags::STAGED);
+    match timestamp {
+        Some(secs) if secs >= 0 => Ok(secs as u64),
+        _ => anyhow::bail!(
+            "Invalid date '{}'. Use @<unix-seconds>, RFC 3339, or YYYY-MM-DD HH:MM:SS",
+            date
+        ),
     }
+}
 
-    // Persist to correct location
-    index.persist()?;
-
-    Ok(())
+/// The commit HEAD points at, e.g. to start the message from when amending
+pub fn head_commit(repo_path: &Path) -> Result<Commit> {
+    let context = RepoContext::detect(repo_path)?;
+    let commit_store =
+        CommitStore::new(&context.repo_root, FsObjectStore::new(&context.repo_root))?;
+    commit_store.read_commit(&read_head(&context)?)
 }
 
 /// Show what would be committed (dry run)
@@ -675,6 +817,124 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_amend_metadata() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        init_test_repo(repo_path)?;
+        fs::write(
+            repo_path.join("helix.toml"),
+            "[user]\nname = \"Config User\"\nemail = \"config@example.com\"\n\n[ignore]\npatterns = []\n",
+        )?;
+        stage_file(repo_path, "test.txt", b"test")?;
+
+        let first = commit(
+            repo_path,
+            CommitOptions {
+                message: "Original message".to_string(),
+                author: Some("Original Author <orig@example.com>".to_string()),
+                date: Some(parse_date("@1000000000")?),
+                ..Default::default()
+            },
+        )?;
+
+        // Nothing staged: reword-free amend keeps message, author and date
+        let kept = commit(
+            repo_path,
+            CommitOptions {
+                amend: true,
+                no_edit: true,
+                ..Default::default()
+            },
+        )?;
+        let store = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+        let kept_commit = store.read_commit(&kept)?;
+        assert_eq!(kept_commit.message, "Original message");
+        assert_eq!(kept_commit.author, "Original Author <orig@example.com>");
+        assert_eq!(kept_commit.author_time, 1_000_000_000);
+        assert!(kept_commit.parents.is_empty());
+        assert_eq!(kept_commit.tree_hash, store.read_commit(&first)?.tree_hash);
+        assert_eq!(head_commit(repo_path)?.commit_hash, kept);
+
+        let reset = commit(
+            repo_path,
+            CommitOptions {
+                message: "New message".to_string(),
+                amend: true,
+                reset_author: true,
+                ..Default::default()
+            },
+        )?;
+        let reset_commit = store.read_commit(&reset)?;
+        assert_eq!(reset_commit.message, "New message");
+        assert_eq!(reset_commit.author, "Config User <config@example.com>");
+        assert!(reset_commit.author_time > 1_000_000_000);
+
+        assert_eq!(parse_date("2001-09-09T01:46:40Z")?, 1_000_000_000);
+        assert!(parse_date("yesterday-ish").is_err());
+
+        // --no-edit without --amend is rejected
+        assert!(commit(
+            repo_path,
+            CommitOptions {
+                no_edit: true,
+                ..Default::default()
+            },
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_refuses_a_missing_parent() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        init_test_repo(repo_path)?;
+        fs::write(
+            repo_path.join("helix.toml"),
+            "[user]\nname = \"Config User\"\nemail = \"config@example.com\"\n",
+        )?;
+        stage_file(repo_path, "one.txt", b"one")?;
+        let first = commit(
+            repo_path,
+            CommitOptions {
+                message: "First".to_string(),
+                ..Default::default()
+            },
+        )?;
+
+        // As if an interrupted import never stored HEAD's commit
+        let store = FsObjectStore::new(repo_path);
+        store.remove_object(&helix_protocol::message::ObjectType::Commit, &first)?;
+
+        stage_file(repo_path, "two.txt", b"two")?;
+        let options = || CommitOptions {
+            message: "Second".to_string(),
+            ..Default::default()
+        };
+        let err = commit(repo_path, options()).unwrap_err();
+        assert!(
+            err.to_string().contains("missing from the object store"),
+            "{err}"
+        );
+        assert_eq!(read_head(&RepoContext::detect(repo_path)?)?, first);
+
+        let second = commit(
+            repo_path,
+            CommitOptions {
+                allow_missing_objects: true,
+                ..options()
+            },
+        )?;
+        let commits = CommitStore::new(repo_path, store)?;
+        assert_eq!(commits.read_commit(&second)?.parents, vec![first]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_commit_performance() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -0,0 +1,280 @@
+/*
+Commit message preparation: templates, trailers and validation.
+
+Configured from the `[commit]` section of helix.toml:
+
+  [commit]
+  template = ".helix/commit-template.txt"  # starts the message in the editor
+  signoff = true                           # always add Signed-off-by
+  co_authors = ["Ada <ada@example.com>"]   # Co-authored-by on every commit
+  conventional = true                      # feat(scope)!: description
+  subject_pattern = "^[A-Z]+-[0-9]+ "      # regex the subject must match
+  max_subject_length = 72
+  msg_hook = "scripts/check-msg"           # run with the message file path
+
+Every message goes through `finalize`, whether it came from `-m` or the
+editor: trailers are appended first, then the message is validated, then the
+hook runs and may rewrite it.
+*/
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::format::EntryFlags;
+use crate::init_command::{CommitSection, HelixConfig};
+use crate::sandbox_command::RepoContext;
+
+const EDITMSG: &str = "COMMIT_EDITMSG";
+
+const CONVENTIONAL_SUBJECT: &str = r"^[a-z]+(\([^()\s]+\))?!?: \S";
+
+/// The `[commit]` section of helix.toml, or defaults when there is none
+pub fn load_config(repo_root: &Path) -> CommitSection {
+    fs::read_to_string(repo_root.join("helix.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str::<HelixConfig>(&contents).ok())
+        .and_then(|cfg| cfg.commit)
+        .unwrap_or_default()
+}
+
+/// Add configured and requested trailers, validate, and run the message hook
+pub fn finalize(repo_root: &Path, message: &str, author: &str, signoff: bool) -> Result<String> {
+    let config = load_config(repo_root);
+
+    let mut trailers: Vec<String> = config
+        .co_authors
+        .iter()
+        .map(|co_author| format!("Co-authored-by: {}", co_author.trim()))
+        .collect();
+    if signoff || config.signoff {
+        trailers.push(format!("Signed-off-by: {}", author));
+    }
+
+    let message = add_trailers(message, &trailers);
+    validate(&message, &config)?;
+
+    match &config.msg_hook {
+        Some(hook) => run_msg_hook(repo_root, hook, &message),
+        None => Ok(message),
+    }
+}
+
+/// Append trailers to the message's trailer block, skipping ones already present
+pub fn add_trailers(message: &str, trailers: &[String]) -> String {
+    let message = message.trim_end();
+    let existing: Vec<&str> = message.lines().map(str::trim).collect();
+    let missing: Vec<&String> = trailers
+        .iter()
+        .filter(|t| !existing.contains(&t.as_str()))
+        .collect();
+
+    if missing.is_empty() {
+        return message.to_string();
+    }
+
+    let mut out = message.to_string();
+    // Join an existing trailer block instead of starting a new paragraph
+    if !ends_with_trailers(message) {
+        out.push('\n');
+    }
+    for trailer in missing {
+        out.push('\n');
+        out.push_str(trailer);
+    }
+    out
+}
+
+fn ends_with_trailers(message: &str) -> bool {
+    let trailer = Regex::new(r"^[A-Za-z][A-Za-z0-9-]*: \S").unwrap();
+    let paragraphs: Vec<&str> = message.split("\n\n").collect();
+    // The subject alone is never a trailer block
+    paragraphs.len() > 1
+        && paragraphs
+            .last()
+            .is_some_and(|p| p.lines().all(|line| trailer.is_match(line.trim())))
+}
+
+/// Check the message against the configured format rules
+pub fn validate(message: &str, config: &CommitSection) -> Result<()> {
+    let subject = message.lines().next().unwrap_or("").trim();
+
+    if let Some(max) = config.max_subject_length {
+        let length = subject.chars().count();
+        if length > max {
+            bail!(
+                "Commit subject is {} characters long; the limit is {}",
+                length,
+                max
+            );
+        }
+    }
+
+    if config.conventional && !Regex::new(CONVENTIONAL_SUBJECT).unwrap().is_match(subject) {
+        bail!(
+            "Commit subject '{}' is not a Conventional Commit.\n\
+             Expected 'type(scope): description', e.g. 'fix(index): handle empty paths'",
+            subject
+        );
+    }
+
+    if let Some(pattern) = &config.subject_pattern {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid commit.subject_pattern '{}'", pattern))?;
+        if !regex.is_match(subject) {
+            bail!(
+                "Commit subject '{}' does not match commit.subject_pattern '{}'",
+                subject,
+                pattern
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Run `hook` with the path of a file holding the message and return the
+/// (possibly rewritten) message
+fn run_msg_hook(repo_root: &Path, hook: &str, message: &str) -> Result<String> {
+    let path = repo_root.join(".helix").join(EDITMSG);
+    fs::write(&path, format!("{}\n", message))?;
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", hook))
+        .arg("sh")
+        .arg(&path)
+        .current_dir(repo_root)
+        .status()
+        .with_context(|| format!("Failed to run commit.msg_hook '{}'", hook))?;
+
+    if !status.success() {
+        bail!("commit.msg_hook '{}' rejected the commit message", hook);
+    }
+
+    let message = fs::read_to_string(&path)?;
+    Ok(message.trim_end().to_string())
+}
+
+/// Open the editor on `initial` (or else the template) and return the message, without
+/// comment lines. An empty string means the user aborted.
+pub fn edit_message(repo_path: &Path, initial: Option<String>) -> Result<String> {
+    let context = RepoContext::detect(repo_path)?;
+    let config = load_config(&context.repo_root);
+
+    let template = match (initial, &config.template) {
+        (Some(initial), _) => initial,
+        (None, Some(template)) => {
+            let path = context.repo_root.join(template);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read commit template {}", path.display()))?
+        }
+        (None, None) => String::new(),
+    };
+
+    let index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+    let mut text = template.trim_end().to_string();
+    text.push_str("\n\n# Enter the commit message. Lines starting with '#' are ignored,\n");
+    text.push_str("# and an empty message aborts the commit.\n#\n# Changes to be committed:\n");
+    for entry in index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::STAGED))
+    {
+        text.push_str(&format!("#   {}\n", entry.path.display()));
+    }
+
+    let path = context.repo_root.join(".helix").join(EDITMSG);
+    fs::write(&path, text)?;
+
+    let editor = ["HELIX_EDITOR", "VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.trim().is_empty()))
+        .unwrap_or_else(|| "vi".to_string());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+    if !status.success() {
+        bail!("Editor '{}' exited with {}", editor, status);
+    }
+
+    let edited = fs::read_to_string(&path)?;
+    Ok(strip_comments(&edited))
+}
+
+/// Drop `#` lines and surrounding blank lines
+pub fn strip_comments(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trailers_and_validation() -> Result<()> {
+        let trailers = vec!["Signed-off-by: Ada <ada@example.com>".to_string()];
+
+        assert_eq!(
+            add_trailers("fix: thing\n", &trailers),
+            "fix: thing\n\nSigned-off-by: Ada <ada@example.com>"
+        );
+        // Joins an existing trailer block, and doesn't duplicate
+        let with_block = "fix: thing\n\nbody\n\nRefs: #12";
+        assert_eq!(
+            add_trailers(with_block, &trailers),
+            format!("{}\nSigned-off-by: Ada <ada@example.com>", with_block)
+        );
+        let signed = add_trailers(with_block, &trailers);
+        assert_eq!(add_trailers(&signed, &trailers), signed);
+
+        let config = CommitSection {
+            conventional: true,
+            max_subject_length: Some(20),
+            ..Default::default()
+        };
+        assert!(validate("feat(cli)!: add grep", &config).is_ok());
+        assert!(validate("Add grep", &config).is_err());
+        assert!(validate("feat: a subject that is far too long", &config).is_err());
+
+        assert_eq!(
+            strip_comments("# template\nsubject\n\n# Changes\n#   a.rs\n"),
+            "subject"
+        );
+
+        // Config and hook, end to end
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        fs::write(
+            repo.join("helix.toml"),
+            "[ignore]\npatterns = []\n\n[commit]\nco_authors = [\"Bob <bob@example.com>\"]\n\
+             msg_hook = \"sed -i.bak 's/wip/WIP/'\"\n",
+        )?;
+        let message = finalize(repo, "wip: tests", "Ada <ada@example.com>", true)?;
+        assert_eq!(
+            message,
+            "WIP: tests\n\nCo-authored-by: Bob <bob@example.com>\nSigned-off-by: Ada <ada@example.com>"
+        );
+
+        fs::write(
+            repo.join("helix.toml"),
+            "[ignore]\npatterns = []\n\n[commit]\nmsg_hook = \"false\"\n",
+        )?;
+        assert!(finalize(repo, "anything", "Ada <ada@example.com>", false).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+/*
+Tokens for authenticating to a remote.
+
+Push, pull and fetch ask `token_for` before talking to a server and send
+what it finds as `Authorization: Bearer <token>`. Sources, in order:
+
+1. A credential helper, from HELIX_CREDENTIAL_HELPER or `[credential] helper`
+   in helix.toml. A bare name like `keychain` runs `helix-credential-keychain`
+   from PATH; anything containing a space or a slash is run by the shell, so
+   `helper = "/opt/bin/vault-token --role ci"` works too.
+2. `[credential] token` in helix.toml, in plaintext
+3. HELIX_TOKEN
+
+Helpers speak a subset of Git's credential protocol: they are run with `get`
+as their last argument, read `protocol=`, `host=` and `path=` lines describing
+the remote (ended by a blank line) on stdin, and print `token=<token>` on
+stdout. `password=` is accepted in place of `token=`, so most Git credential
+helpers work unchanged. A helper that prints nothing lets the next source
+answer; one that fails is an error, since a configured helper that can't run
+shouldn't quietly turn into an unauthenticated request.
+*/
+use crate::init_command::{CredentialSection, RemoteConfig};
+use crate::repo_config;
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const TOKEN_ENV: &str = "HELIX_TOKEN";
+pub const HELPER_ENV: &str = "HELIX_CREDENTIAL_HELPER";
+
+/// The token to present to `remote_url`, if any source has one
+pub fn token_for(repo_path: &Path, remote_url: &str) -> Result<Option<String>> {
+    let section = match fs::read_to_string(repo_path.join(repo_config::CONFIG_FILE)) {
+        Ok(content) => repo_config::parse(&content)?.credential,
+        Err(_) => None,
+    };
+    resolve(
+        section.unwrap_or_default(),
+        |name| std::env::var(name).ok(),
+        remote_url,
+    )
+}
+
+/// Like `token_for`, but a remote with its own `auth` table uses that in place of
+/// [credential]
+pub fn token_for_remote(
+    repo_path: &Path,
+    remote: &RemoteConfig,
+    remote_url: &str,
+) -> Result<Option<String>> {
+    match &remote.auth {
+        Some(auth) => resolve(auth.clone(), |name| std::env::var(name).ok(), remote_url),
+        None => token_for(repo_path, remote_url),
+    }
+}
+
+fn resolve(
+    section: CredentialSection,
+    env: impl Fn(&str) -> Option<String>,
+    remote_url: &str,
+) -> Result<Option<String>> {
+    if let Some(helper) = env(HELPER_ENV).or(section.helper) {
+        if let Some(token) = run_helper(&helper, remote_url)? {
+            return Ok(Some(token));
+        }
+    }
+
+    Ok(section
+        .token
+        .or_else(|| env(TOKEN_ENV))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+fn run_helper(helper: &str, remote_url: &str) -> Result<Option<String>> {
+    let helper = helper.trim();
+    let mut command = if helper.contains(' ') || helper.contains('/') {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(format!("{} get", helper));
+        command
+    } else {
+        let mut command = Command::new(format!("helix-credential-{}", helper));
+        command.arg("get");
+        command
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run credential helper '{}'", helper))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A helper that doesn't read its input may already have exited
+        let _ = stdin.write_all(describe(remote_url).as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Credential helper '{}' did not finish", helper))?;
+    if !output.status.success() {
+        bail!("Credential helper '{}' failed ({})", helper, output.status);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| *key == "token" || *key == "password")
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+/// The remote as helper input: `protocol=`, `host=` and `path=` lines
+fn describe(remote_url: &str) -> String {
+    let Ok(url) = Url::parse(remote_url) else {
+        return format!("url={}\n\n", remote_url);
+    };
+
+    let mut host = url.host_str().unwrap_or_default().to_string();
+    if let Some(port) = url.port() {
+        host = format!("{}:{}", host, port);
+    }
+    format!(
+        "protocol={}\nhost={}\npath={}\n\n",
+        url.scheme(),
+        host,
+        url.path().trim_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_helper_then_config_then_env() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let helper = temp_dir.path().join("helper");
+        // Answers only for example.com, echoing the path it was asked about
+        fs::write(
+            &helper,
+            "#!/bin/sh\n\
+             [ \"$1\" = get ] || exit 1\n\
+             while read -r line && [ -n \"$line\" ]; do\n\
+               case \"$line\" in host=*) host=${line#host=} ;; path=*) path=${line#path=} ;; esac\n\
+             done\n\
+             [ \"$host\" = example.com:8080 ] && echo \"token=secret-$path\"\n\
+             exit 0\n",
+        )?;
+        fs::set_permissions(&helper, fs::Permissions::from_mode(0o755))?;
+
+        let section = CredentialSection {
+            helper: Some(helper.display().to_string()),
+            token: Some("from-config".to_string()),
+        };
+        let env = |name: &str| (name == TOKEN_ENV).then(|| "from-env".to_string());
+
+        assert_eq!(
+            resolve(section.clone(), env, "http://example.com:8080/team/repo")?,
+            Some("secret-team/repo".to_string())
+        );
+        // The helper has nothing for other hosts, so config answers
+        assert_eq!(
+            resolve(section, env, "http://other.example")?,
+            Some("from-config".to_string())
+        );
+        assert_eq!(
+            resolve(CredentialSection::default(), env, "http://other.example")?,
+            Some("from-env".to_string())
+        );
+        assert_eq!(
+            resolve(
+                CredentialSection::default(),
+                |_| None,
+                "http://other.example"
+            )?,
+            None
+        );
+
+        let broken = CredentialSection {
+            helper: Some("/nonexistent/helper".to_string()),
+            token: None,
+        };
+        assert!(resolve(broken, |_| None, "http://example.com").is_err());
+
+        Ok(())
+    }
+}
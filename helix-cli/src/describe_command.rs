@@ -0,0 +1,208 @@
+/*
+`helix describe` - name a commit after the nearest tag it contains.
+
+  helix describe [REV]      v1.4.0 when REV is tagged v1.4.0, otherwise
+                            v1.4.0-7-g1a2b3c4d: 7 commits on top of v1.4.0,
+                            at the commit whose hash starts 1a2b3c4d
+  --long                    always the long form, even on the tag (v1.4.0-0-g...)
+  --always                  the short hash when no tag is reachable, instead of
+                            an error
+  --dirty[=MARK]            append MARK (default "-dirty") when the working tree
+                            has changes to tracked files; only for HEAD
+  --match GLOB              only consider tags whose name matches GLOB
+
+Lightweight and annotated tags both count. The nearest tag is the one leaving
+the fewest commits that REV has and the tag doesn't; between two tags on the
+same commit, the name that sorts last wins. Build systems can stamp the output
+into what they build: it goes to stdout alone, one line.
+*/
+use anyhow::{bail, Result};
+use globset::Glob;
+use helix_protocol::commit::parse_commit_for_walk;
+use helix_protocol::hash::{hash_bytes, hash_to_hex, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::{FsObjectStore, FsRefStore};
+use helix_protocol::tag::peel_to_commit;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::diff_command::resolve_revision;
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::EntryFlags;
+use crate::line_endings::LineEndings;
+
+#[derive(Debug, Default)]
+pub struct DescribeOptions {
+    pub long: bool,
+    pub always: bool,
+    /// Suffix for a dirty working tree
+    pub dirty: Option<String>,
+    /// Glob tag names must match
+    pub pattern: Option<String>,
+}
+
+pub fn describe(repo_path: &Path, rev: Option<&str>, options: &DescribeOptions) -> Result<String> {
+    if rev.is_some() && options.dirty.is_some() {
+        bail!("--dirty describes the working tree, so it can't be used with a revision");
+    }
+    let commit = resolve_revision(repo_path, rev.unwrap_or("HEAD"))?;
+    let store = FsObjectStore::new(repo_path);
+    let short = hash_to_hex(&commit)[..8].to_string();
+
+    let matcher = options
+        .pattern
+        .as_deref()
+        .map(|p| Glob::new(p).map(|g| g.compile_matcher()))
+        .transpose()?;
+    let mut tags = Vec::new();
+    for (name, target) in FsRefStore::new(repo_path).list_refs("refs/tags/")? {
+        let name = name["refs/tags/".len()..].to_string();
+        if matcher.as_ref().is_some_and(|m| !m.is_match(&name)) {
+            continue;
+        }
+        if let Ok(tagged) = peel_to_commit(&store, &target) {
+            tags.push((name, tagged));
+        }
+    }
+
+    let history = ancestors(&store, commit)?;
+    let nearest = tags
+        .into_iter()
+        .filter(|(_, tagged)| history.contains(tagged))
+        .map(|(name, tagged)| -> Result<(usize, String)> {
+            let tag_history = ancestors(&store, tagged)?;
+            Ok((history.difference(&tag_history).count(), name))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .min_by(|(a, a_name), (b, b_name)| a.cmp(b).then(b_name.cmp(a_name)));
+
+    let mut name = match nearest {
+        Some((0, tag)) if !options.long => tag,
+        Some((distance, tag)) => format!("{tag}-{distance}-g{short}"),
+        None if options.always => short,
+        None => bail!("No tags can describe {short}. Tag a commit it contains, or use --always"),
+    };
+    if let Some(mark) = &options.dirty {
+        if is_dirty(repo_path)? {
+            name.push_str(mark);
+        }
+    }
+    Ok(name)
+}
+
+/// Every commit reachable from `tip`, itself included
+fn ancestors(store: &FsObjectStore, tip: Hash) -> Result<HashSet<Hash>> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([tip]);
+    while let Some(hash) = queue.pop_front() {
+        if !seen.insert(hash) {
+            continue;
+        }
+        let (_, parents) = parse_commit_for_walk(&store.read_object(&ObjectType::Commit, &hash)?)?;
+        queue.extend(parents);
+    }
+    Ok(seen)
+}
+
+/// Whether anything is staged, or a tracked file differs from its index entry on disk.
+/// Untracked files don't count.
+fn is_dirty(repo_path: &Path) -> Result<bool> {
+    let index = HelixIndexData::load_or_rebuild(repo_path)?;
+    let line_endings = LineEndings::load(repo_path);
+    for entry in index.entries() {
+        if entry.flags.contains(EntryFlags::STAGED) {
+            return Ok(true);
+        }
+        if !entry.flags.contains(EntryFlags::TRACKED)
+            || entry.flags.contains(EntryFlags::ASSUME_UNCHANGED)
+        {
+            continue;
+        }
+        let path = repo_path.join(&entry.path);
+        let Ok(metadata) = fs::metadata(&path) else {
+            return Ok(true);
+        };
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if mtime == entry.mtime_sec && metadata.len() == entry.size {
+            continue;
+        }
+        let content = fs::read(&path)?;
+        if hash_bytes(&line_endings.normalize(&content)) != entry.oid {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::commit::{Commit, CommitStore};
+    use crate::helix_index::tree::{Tree, TreeStore};
+
+    #[test]
+    fn test_describe_names_commits_after_the_nearest_tag() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        let tree = TreeStore::for_repo(repo).write(&Tree::new())?;
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+        let mut line: Vec<Hash> = Vec::new();
+        for i in 0..5 {
+            let parents = line.last().copied().into_iter().collect();
+            let commit = Commit::new(tree, parents, "T <t@x>".into(), format!("c{i}"));
+            line.push(commits.write_commit(&commit)?);
+        }
+        let hex = |i: usize| hash_to_hex(&line[i]);
+        let describe_at =
+            |i: usize, options: &DescribeOptions| describe(repo, Some(&hex(i)), options);
+
+        let plain = DescribeOptions::default();
+        assert!(describe_at(4, &plain).is_err());
+        assert_eq!(
+            describe_at(
+                4,
+                &DescribeOptions {
+                    always: true,
+                    ..Default::default()
+                }
+            )?,
+            hex(4)[..8]
+        );
+
+        let refs = FsRefStore::new(repo);
+        refs.set_ref("refs/tags/v1.0", line[1])?;
+        refs.set_ref("refs/tags/v1.1", line[3])?;
+        assert_eq!(describe_at(3, &plain)?, "v1.1");
+        assert_eq!(describe_at(4, &plain)?, format!("v1.1-1-g{}", &hex(4)[..8]));
+        assert_eq!(describe_at(2, &plain)?, format!("v1.0-1-g{}", &hex(2)[..8]));
+        assert_eq!(
+            describe_at(
+                1,
+                &DescribeOptions {
+                    long: true,
+                    ..Default::default()
+                }
+            )?,
+            format!("v1.0-0-g{}", &hex(1)[..8])
+        );
+        assert_eq!(
+            describe_at(
+                4,
+                &DescribeOptions {
+                    pattern: Some("v1.0*".into()),
+                    ..Default::default()
+                }
+            )?,
+            format!("v1.0-3-g{}", &hex(4)[..8])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,675 @@
+/*
+`helix diff` - show changes as unified diffs.
+
+  helix diff                          working tree vs index
+  helix diff --staged                 index vs HEAD
+  helix diff <rev>                    working tree vs <rev>
+  helix diff --staged <rev>           index vs <rev>
+  helix diff <rev1> <rev2>            <rev1> vs <rev2> (also <rev1>..<rev2>)
+  helix diff ... -- <paths>           only files at or under <paths>
+  --ignore-space-change               treat runs of whitespace as equal and
+                                      ignore it at line ends
+  -w, --ignore-all-space              ignore whitespace altogether
+
+Each side is flattened to a path -> blob hash snapshot. Paths only on the old
+side count as deleted and paths only on the new side as added, until rename
+detection pairs them up. Only tracked files are part of the working tree side,
+read with the repository's line-ending policy applied so they hash the way
+`helix add` would store them.
+
+A revision is HEAD, a branch, tag or remote branch name, a full ref path, or a
+full or abbreviated commit hash, optionally followed by any number of `^` and
+`~N` suffixes (first parents).
+
+Binary files are summarized with their size change unless --text is given.
+The text and binary attributes in .helixattributes decide which files are
+binary; files they don't mention are sniffed for NULs.
+
+Ignoring whitespace only changes which lines count as changed; the lines
+printed are the ones in the files. A file whose changes are all whitespace is
+left out, unless it was also added, deleted or renamed.
+*/
+use anyhow::{anyhow, Context, Result};
+use console::style;
+use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::peel_to_commit;
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::attributes::Attributes;
+use crate::binary::{binary_change_summary, is_binary};
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::commit::{read_head, CommitStore};
+use crate::helix_index::format::EntryFlags;
+use crate::helix_index::rename::{detect_renames, DEFAULT_RENAME_THRESHOLD};
+use crate::helix_index::tree::TreeStore;
+use crate::line_endings::LineEndings;
+use crate::lost_found_command::resolve_commit;
+use crate::promisor;
+use crate::sandbox_command::RepoContext;
+use crate::unified_diff::{unified_diff_with, Whitespace};
+
+pub struct DiffOptions {
+    /// Use the index rather than the working tree as the new side
+    pub staged: bool,
+    /// Print binary files as text
+    pub text: bool,
+    /// Unchanged lines around each change
+    pub context: usize,
+    /// Only diff files at or under these repository-relative paths
+    pub paths: Vec<PathBuf>,
+    /// How whitespace counts when comparing lines
+    pub whitespace: Whitespace,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            staged: false,
+            text: false,
+            context: 3,
+            paths: Vec::new(),
+            whitespace: Whitespace::Exact,
+        }
+    }
+}
+
+/// One changed file. A missing path means the file doesn't exist on that side.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub old_path: Option<PathBuf>,
+    pub new_path: Option<PathBuf>,
+    pub old_content: Vec<u8>,
+    pub new_content: Vec<u8>,
+    /// Set for renames
+    pub similarity: Option<f32>,
+    /// The text attribute of the path, overriding what the content looks like
+    pub text: Option<bool>,
+}
+
+impl FileDiff {
+    /// Whether either side is binary, going by the text attribute when the path has one
+    pub fn is_binary(&self) -> bool {
+        self.text
+            .map(|text| !text)
+            .unwrap_or_else(|| is_binary(&self.old_content) || is_binary(&self.new_content))
+    }
+
+    /// Render as a Git-style patch. Empty when whitespace is all that changed and
+    /// `whitespace` ignores it.
+    pub fn render(&self, text: bool, context: usize, whitespace: Whitespace) -> String {
+        let old_name = self.old_path.as_ref().or(self.new_path.as_ref());
+        let new_name = self.new_path.as_ref().or(self.old_path.as_ref());
+        let old_name = old_name
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let new_name = new_name
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "diff --git a/{} b/{}", old_name, new_name);
+        match (&self.old_path, &self.new_path) {
+            (None, Some(_)) => out.push_str("new file\n"),
+            (Some(_), None) => out.push_str("deleted file\n"),
+            _ => {}
+        }
+        if let Some(similarity) = self.similarity {
+            let _ = writeln!(out, "similarity index {:.0}%", similarity * 100.0);
+            let _ = writeln!(out, "rename from {}", old_name);
+            let _ = writeln!(out, "rename to {}", new_name);
+        }
+
+        if self.old_content == self.new_content {
+            return out;
+        }
+
+        if !text && self.is_binary() {
+            let size = |path: &Option<PathBuf>, content: &[u8]| {
+                path.as_ref().map(|_| content.len() as u64)
+            };
+            out.push_str(&binary_change_summary(
+                &old_name,
+                &new_name,
+                size(&self.old_path, &self.old_content),
+                size(&self.new_path, &self.new_content),
+            ));
+            out.push('\n');
+            return out;
+        }
+
+        match &self.old_path {
+            Some(_) => {
+                let _ = writeln!(out, "--- a/{}", old_name);
+            }
+            None => out.push_str("--- /dev/null\n"),
+        }
+        match &self.new_path {
+            Some(_) => {
+                let _ = writeln!(out, "+++ b/{}", new_name);
+            }
+            None => out.push_str("+++ /dev/null\n"),
+        }
+
+        let old = String::from_utf8_lossy(&self.old_content);
+        let new = String::from_utf8_lossy(&self.new_content);
+        let hunks = unified_diff_with(&old, &new, context, whitespace);
+        let modified = self.old_path.is_some() && self.new_path.is_some();
+        if hunks.is_empty() && modified && self.similarity.is_none() {
+            return String::new();
+        }
+        for hunk in hunks {
+            hunk.write_to(&mut out);
+        }
+        out
+    }
+}
+
+/// Files on one side of a diff
+struct Snapshot {
+    files: HashMap<PathBuf, Hash>,
+    /// Working tree content, which isn't in the object store
+    contents: HashMap<Hash, Vec<u8>>,
+    /// Index entries not checked out on purpose (ASSUME_UNCHANGED, e.g. left out by a
+    /// partial clone); a missing file there isn't a deletion
+    not_checked_out: HashSet<PathBuf>,
+}
+
+impl Snapshot {
+    fn from_objects(files: HashMap<PathBuf, Hash>) -> Self {
+        Self {
+            files,
+            contents: HashMap::new(),
+            not_checked_out: HashSet::new(),
+        }
+    }
+}
+
+/// Compute the changed files for `helix diff [revs] [-- paths]`
+pub fn diff(repo_path: &Path, revs: &[String], options: &DiffOptions) -> Result<Vec<FileDiff>> {
+    let context = RepoContext::detect(repo_path)?;
+    let repo_root = &context.repo_root;
+    let store = FsObjectStore::new(repo_root);
+
+    let revs: Vec<String> = match revs {
+        [range] if range.contains("..") => {
+            let (from, to) = range.split_once("..").unwrap();
+            let or_head = |rev: &str| if rev.is_empty() { "HEAD" } else { rev }.to_string();
+            vec![or_head(from), or_head(to)]
+        }
+        _ => revs.to_vec(),
+    };
+
+    let (old, new) = match revs.as_slice() {
+        [] if options.staged => (
+            head_snapshot(repo_root)?,
+            index_snapshot(&context.index_path, repo_root)?,
+        ),
+        [] => {
+            let index = index_snapshot(&context.index_path, repo_root)?;
+            let workdir = workdir_snapshot(&context, &index)?;
+            (index, workdir)
+        }
+        [rev] => {
+            let old = commit_snapshot(repo_root, resolve_revision(repo_root, rev)?)?;
+            let index = index_snapshot(&context.index_path, repo_root)?;
+            let new = if options.staged {
+                index
+            } else {
+                workdir_snapshot(&context, &index)?
+            };
+            (old, new)
+        }
+        [from, to] => {
+            if options.staged {
+                anyhow::bail!("--staged takes at most one revision");
+            }
+            (
+                commit_snapshot(repo_root, resolve_revision(repo_root, from)?)?,
+                commit_snapshot(repo_root, resolve_revision(repo_root, to)?)?,
+            )
+        }
+        _ => anyhow::bail!("Too many revisions (expected at most two)"),
+    };
+
+    let attributes = Attributes::load(&context.workdir);
+    compare(repo_root, &store, &old, &new, &options.paths, &attributes)
+}
+
+/// The changes a commit introduced: its tree against its first parent's, or
+/// against nothing for a root commit
+pub fn commit_diff(repo_path: &Path, commit_hash: &Hash) -> Result<Vec<FileDiff>> {
+    let store = FsObjectStore::new(repo_path);
+    let commit = CommitStore::new(repo_path, store.clone())?.read_commit(commit_hash)?;
+    let old = match commit.parents.first() {
+        Some(parent) => commit_snapshot(repo_path, *parent)?,
+        None => Snapshot::from_objects(HashMap::new()),
+    };
+    let new =
+        Snapshot::from_objects(TreeStore::new(store.clone()).collect_all_files(&commit.tree_hash)?);
+
+    compare(
+        repo_path,
+        &store,
+        &old,
+        &new,
+        &[],
+        &Attributes::load(repo_path),
+    )
+}
+
+/// Print diffs, colored when `console` colors are on (see `output`)
+pub fn print_diff(
+    out: &mut impl std::io::Write,
+    diffs: &[FileDiff],
+    options: &DiffOptions,
+) -> Result<()> {
+    for file in diffs {
+        for line in file
+            .render(options.text, options.context, options.whitespace)
+            .lines()
+        {
+            if line.starts_with("diff --git")
+                || line.starts_with("--- ")
+                || line.starts_with("+++ ")
+            {
+                writeln!(out, "{}", style(line).bold())?;
+            } else if line.starts_with("@@") {
+                writeln!(out, "{}", style(line).cyan())?;
+            } else if line.starts_with('+') {
+                writeln!(out, "{}", style(line).green())?;
+            } else if line.starts_with('-') {
+                writeln!(out, "{}", style(line).red())?;
+            } else {
+                writeln!(out, "{}", line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn compare(
+    repo_path: &Path,
+    store: &FsObjectStore,
+    old: &Snapshot,
+    new: &Snapshot,
+    paths: &[PathBuf],
+    attributes: &Attributes,
+) -> Result<Vec<FileDiff>> {
+    let selected = |path: &Path| paths.is_empty() || paths.iter().any(|p| path.starts_with(p));
+    let read = |snapshot: &Snapshot, hash: &Hash| -> Result<Vec<u8>> {
+        match snapshot.contents.get(hash) {
+            Some(content) => Ok(content.clone()),
+            None => store
+                .read_object(&ObjectType::Blob, hash)
+                .with_context(|| format!("Failed to read blob {}", hash_to_hex(hash))),
+        }
+    };
+
+    let all_paths: BTreeSet<&PathBuf> = old
+        .files
+        .keys()
+        .chain(new.files.keys())
+        .filter(|path| selected(path))
+        .collect();
+
+    // Changed blobs a partial clone left on the remote are fetched in one batch
+    let changed = all_paths.iter().flat_map(|path| {
+        let (a, b) = (old.files.get(*path), new.files.get(*path));
+        if a == b {
+            vec![]
+        } else {
+            a.into_iter().chain(b).copied().collect()
+        }
+    });
+    promisor::fetch_promised(repo_path, changed)?;
+
+    let mut deleted = Vec::new();
+    let mut added = Vec::new();
+    let mut diffs = Vec::new();
+    for path in all_paths {
+        match (old.files.get(path), new.files.get(path)) {
+            (Some(a), Some(b)) if a == b => {}
+            (Some(a), Some(b)) => diffs.push(FileDiff {
+                old_path: Some(path.clone()),
+                new_path: Some(path.clone()),
+                old_content: read(old, a)?,
+                new_content: read(new, b)?,
+                similarity: None,
+                text: None,
+            }),
+            (Some(a), None) => deleted.push((path.clone(), *a)),
+            (None, Some(b)) => added.push((path.clone(), *b)),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    let renames = detect_renames(&deleted, &added, DEFAULT_RENAME_THRESHOLD, |hash| {
+        read(old, hash).or_else(|_| read(new, hash)).ok()
+    });
+    for rename in &renames {
+        let old_hash = old.files[&rename.from];
+        let new_hash = new.files[&rename.to];
+        diffs.push(FileDiff {
+            old_path: Some(rename.from.clone()),
+            new_path: Some(rename.to.clone()),
+            old_content: read(old, &old_hash)?,
+            new_content: read(new, &new_hash)?,
+            similarity: Some(rename.similarity),
+            text: None,
+        });
+    }
+
+    for (path, hash) in &deleted {
+        if renames.iter().any(|r| &r.from == path) {
+            continue;
+        }
+        diffs.push(FileDiff {
+            old_path: Some(path.clone()),
+            new_path: None,
+            old_content: read(old, hash)?,
+            new_content: Vec::new(),
+            similarity: None,
+            text: None,
+        });
+    }
+    for (path, hash) in &added {
+        if renames.iter().any(|r| &r.to == path) {
+            continue;
+        }
+        diffs.push(FileDiff {
+            old_path: None,
+            new_path: Some(path.clone()),
+            old_content: Vec::new(),
+            new_content: read(new, hash)?,
+            similarity: None,
+            text: None,
+        });
+    }
+
+    for diff in &mut diffs {
+        let path = diff.new_path.as_ref().or(diff.old_path.as_ref()).unwrap();
+        diff.text = attributes.for_path(path).text;
+    }
+
+    diffs.sort_by(|a, b| {
+        let key = |d: &FileDiff| d.new_path.clone().or_else(|| d.old_path.clone());
+        key(a).cmp(&key(b))
+    });
+    Ok(diffs)
+}
+
+/// Resolve a revision to a commit hash
+pub fn resolve_revision(repo_path: &Path, rev: &str) -> Result<Hash> {
+    // Split off trailing ^ and ~N suffixes
+    let base_len = rev.find(['^', '~']).unwrap_or(rev.len());
+    let (base, mut suffix) = rev.split_at(base_len);
+
+    let mut hash =
+        resolve_name(repo_path, base).with_context(|| format!("Unknown revision '{}'", rev))?;
+
+    let store = FsObjectStore::new(repo_path);
+    let commits = CommitStore::new(repo_path, store)?;
+    while !suffix.is_empty() {
+        let (steps, rest) = if let Some(rest) = suffix.strip_prefix('^') {
+            (1, rest)
+        } else {
+            let rest = &suffix[1..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let steps = match &rest[..digits] {
+                "" => 1,
+                n => n.parse::<usize>()?,
+            };
+            (steps, &rest[digits..])
+        };
+
+        for _ in 0..steps {
+            let commit = commits.read_commit(&hash)?;
+            hash = *commit
+                .parents
+                .first()
+                .ok_or_else(|| anyhow!("Revision '{}' goes past the first commit", rev))?;
+        }
+        suffix = rest;
+    }
+
+    Ok(hash)
+}
+
+fn resolve_name(repo_path: &Path, name: &str) -> Result<Hash> {
+    if name == "HEAD" || name.is_empty() {
+        return read_head(repo_path);
+    }
+
+    let helix_dir = repo_path.join(".helix");
+    let candidates = [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/remotes/{}", name),
+    ];
+    for candidate in &candidates {
+        let ref_path = helix_dir.join(candidate);
+        if candidate.starts_with("refs/") && ref_path.is_file() {
+            let content = fs::read_to_string(&ref_path)?;
+            let hash = hex_to_hash(content.trim())
+                .with_context(|| format!("Invalid hash in {}", candidate))?;
+            let store = FsObjectStore::new(repo_path);
+            return peel_to_commit(&store, &hash);
+        }
+    }
+
+    if name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return resolve_commit(repo_path, name);
+    }
+
+    anyhow::bail!("No branch, tag or commit named '{}'", name)
+}
+
+fn commit_snapshot(repo_path: &Path, commit_hash: Hash) -> Result<Snapshot> {
+    let store = FsObjectStore::new(repo_path);
+    let commit = CommitStore::new(repo_path, store.clone())?.read_commit(&commit_hash)?;
+    let files = TreeStore::new(store).collect_all_files(&commit.tree_hash)?;
+    Ok(Snapshot::from_objects(files))
+}
+
+/// HEAD's files, or nothing before the first commit
+fn head_snapshot(repo_path: &Path) -> Result<Snapshot> {
+    match read_head(repo_path) {
+        Ok(hash) => commit_snapshot(repo_path, hash),
+        Err(_) => Ok(Snapshot::from_objects(HashMap::new())),
+    }
+}
+
+fn index_snapshot(index_path: &Path, repo_path: &Path) -> Result<Snapshot> {
+    let index = HelixIndexData::load_from_path(index_path, repo_path)?;
+    let files = index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .map(|e| (e.path.clone(), e.oid))
+        .collect();
+    let mut snapshot = Snapshot::from_objects(files);
+    snapshot.not_checked_out = index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::ASSUME_UNCHANGED))
+        .map(|e| e.path.clone())
+        .collect();
+    Ok(snapshot)
+}
+
+/// Tracked files as they are on disk. Content is kept only where it differs
+/// from the index, since everything else can be read from the object store.
+fn workdir_snapshot(context: &RepoContext, index: &Snapshot) -> Result<Snapshot> {
+    let line_endings = LineEndings::load(&context.repo_root);
+
+    let present: Vec<(PathBuf, Hash, Option<Vec<u8>>)> = index
+        .files
+        .par_iter()
+        .filter_map(|(path, index_hash)| {
+            let Ok(content) = fs::read(context.workdir.join(path)) else {
+                let kept = index.not_checked_out.contains(path);
+                return kept.then(|| (path.clone(), *index_hash, None));
+            };
+            let content = line_endings.normalize(&content).into_owned();
+            let hash = hash_bytes(&content);
+            let changed = (hash != *index_hash).then_some(content);
+            Some((path.clone(), hash, changed))
+        })
+        .collect();
+
+    let mut snapshot = Snapshot::from_objects(HashMap::new());
+    for (path, hash, content) in present {
+        snapshot.files.insert(path, hash);
+        if let Some(content) = content {
+            snapshot.contents.insert(hash, content);
+        }
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit_command::{commit, CommitOptions};
+    use crate::helix_index::format::Entry;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn init_repo(path: &Path) -> Result<()> {
+        Command::new("git")
+            .args(["init"])
+            .current_dir(path)
+            .output()?;
+        crate::init_command::init_helix_repo(path, None)?;
+        Ok(())
+    }
+
+    fn stage(repo: &Path, files: &[(&str, &[u8])]) -> Result<()> {
+        let store = FsObjectStore::new(repo);
+        let mut index = HelixIndexData::load_or_rebuild(repo)?;
+        for (name, content) in files {
+            fs::write(repo.join(name), content)?;
+            let oid = store.write_object(&ObjectType::Blob, content)?;
+            index.entries_mut().retain(|e| e.path != Path::new(name));
+            let mut entry = Entry::new(PathBuf::from(name), content.len() as u64, 0, oid, 0o100644);
+            entry.flags = EntryFlags::TRACKED | EntryFlags::STAGED;
+            index.entries_mut().push(entry);
+        }
+        index.persist()
+    }
+
+    fn commit_all(repo: &Path, message: &str) -> Result<Hash> {
+        commit(
+            repo,
+            CommitOptions {
+                message: message.to_string(),
+                author: Some("Test User <test@example.com>".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn render(diffs: &[FileDiff]) -> String {
+        diffs
+            .iter()
+            .map(|d| d.render(false, 3, Whitespace::Exact))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_between_revisions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        init_repo(repo)?;
+
+        stage(repo, &[("a.txt", b"one\ntwo\n"), ("b.txt", b"bee\n")])?;
+        let first = commit_all(repo, "first")?;
+        stage(repo, &[("a.txt", b"one\n2\n"), ("c.txt", b"sea\n")])?;
+        commit_all(repo, "second")?;
+
+        let diffs = diff(
+            repo,
+            &["HEAD~1".into(), "HEAD".into()],
+            &DiffOptions::default(),
+        )?;
+        assert_eq!(
+            render(&diffs),
+            "diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n\
+             diff --git a/c.txt b/c.txt\nnew file\n--- /dev/null\n+++ b/c.txt\n@@ -0,0 +1 @@\n+sea\n"
+        );
+
+        // Same range by hash prefix and range syntax, limited to one path
+        let range = format!("{}..", &hash_to_hex(&first)[..8]);
+        let options = DiffOptions {
+            paths: vec![PathBuf::from("c.txt")],
+            ..Default::default()
+        };
+        let diffs = diff(repo, &[range], &options)?;
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].new_path, Some(PathBuf::from("c.txt")));
+
+        assert!(diff(repo, &["nope".into(), "HEAD".into()], &options).is_err());
+
+        // A commit against its parent, and a root commit against nothing
+        let head = resolve_revision(repo, "HEAD")?;
+        assert_eq!(
+            render(&commit_diff(repo, &head)?),
+            render(&diff(
+                repo,
+                &["HEAD~1".into(), "HEAD".into()],
+                &DiffOptions::default()
+            )?)
+        );
+        let root = commit_diff(repo, &first)?;
+        assert_eq!(root.len(), 2);
+        assert!(root.iter().all(|d| d.old_path.is_none()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_working_tree_staged_and_binary() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        init_repo(repo)?;
+
+        stage(repo, &[("notes.txt", b"hello\n")])?;
+        commit_all(repo, "first")?;
+
+        // Unstaged edit shows up against the index, not with --staged
+        fs::write(repo.join("notes.txt"), "hello\nworld\n")?;
+        let diffs = diff(repo, &[], &DiffOptions::default())?;
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0]
+            .render(false, 3, Whitespace::Exact)
+            .ends_with(" hello\n+world\n"));
+
+        let staged = DiffOptions {
+            staged: true,
+            ..Default::default()
+        };
+        assert!(diff(repo, &[], &staged)?.is_empty());
+
+        stage(repo, &[("logo.png", b"PNG\0\x01\x02")])?;
+        let diffs = diff(repo, &[], &staged)?;
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0]
+            .render(false, 3, Whitespace::Exact)
+            .ends_with("Binary files /dev/null and b/logo.png differ (0 B -> 6 B, +6 B)\n"));
+        assert!(diffs[0]
+            .render(true, 3, Whitespace::Exact)
+            .contains("+++ b/logo.png"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,557 @@
+/*
+`helix doctor` - diagnose common repository problems.
+
+Runs a battery of quick checks and prints a fix for each one that fails:
+
+1. repository  - .helix exists and has the directories Helix expects
+2. config      - helix.toml exists and parses; an author is configured
+3. index       - helix.idx is present, intact and belongs to this repo, and
+                 no stale helix.idx.lock is left behind
+4. import      - a Git repo next to .helix was imported, and the import
+                 got as far as branches and HEAD
+5. HEAD        - HEAD names an existing branch whose commit is in the store
+6. refs        - every branch and tag points at an object in the store
+7. remotes     - every configured remote URL answers (skipped with --offline)
+
+Checks are cheap: objects are looked up, not re-hashed. `helix verify --all`
+is the thorough version and is suggested when objects look broken.
+*/
+use anyhow::Result;
+use console::style;
+use helix_protocol::hash::{hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use walkdir::WalkDir;
+
+use crate::author::resolve_author;
+use crate::helix_index::lock::IndexLock;
+use crate::helix_index::rev_map::RevMap;
+use crate::helix_index::verify::{Verifier, VerifyResult};
+use crate::helix_index::Reader;
+use crate::init_command::HelixConfig;
+use crate::repo_config;
+
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default)]
+pub struct DoctorOptions {
+    /// Skip the remote connectivity checks
+    pub offline: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about it, for warnings and failures
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// No check failed (warnings are allowed)
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.status != Status::Fail)
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+
+    pub fn print_summary(&self) {
+        let width = self.checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            let mark = match check.status {
+                Status::Ok => style("✓").green(),
+                Status::Warn => style("!").yellow(),
+                Status::Fail => style("✗").red(),
+            };
+            println!(
+                "  {} {:<width$}  {}",
+                mark,
+                check.name,
+                check.detail,
+                width = width
+            );
+            if let Some(fix) = &check.fix {
+                println!("    {} {}", style("fix:").cyan(), fix);
+            }
+        }
+
+        let (warnings, failures) = (self.count(Status::Warn), self.count(Status::Fail));
+        println!();
+        if warnings == 0 && failures == 0 {
+            println!("No problems found");
+        } else {
+            println!("{} warning(s), {} failure(s)", warnings, failures);
+        }
+    }
+}
+
+pub async fn doctor(repo_path: &Path, options: DoctorOptions) -> Result<DoctorReport> {
+    let mut report = DoctorReport::default();
+
+    let repo = check_repository(repo_path);
+    let is_repo = repo.status != Status::Fail;
+    report.checks.push(repo);
+    // Nothing else makes sense without .helix
+    if !is_repo {
+        return Ok(report);
+    }
+
+    let config = load_config(repo_path);
+    match &config {
+        Ok((_, warnings)) if warnings.is_empty() => {
+            report.checks.push(Check::ok("config", "helix.toml parses"))
+        }
+        Ok((_, warnings)) => report.checks.extend(warnings.iter().cloned()),
+        Err(check) => report.checks.push(check.clone()),
+    }
+    let config = config.map(|(config, _)| config);
+    report.checks.push(check_author(repo_path));
+    report.checks.push(check_index(repo_path));
+    report.checks.push(check_index_lock(repo_path));
+    if let Some(check) = check_import(repo_path) {
+        report.checks.push(check);
+    }
+
+    let store = FsObjectStore::new(repo_path);
+    report.checks.push(check_head(repo_path, &store));
+    report.checks.push(check_refs(repo_path, &store));
+
+    if let Ok(config) = &config {
+        if options.offline {
+            report
+                .checks
+                .push(Check::ok("remotes", "skipped (--offline)"));
+        } else {
+            report.checks.extend(check_remotes(config).await);
+        }
+    }
+
+    Ok(report)
+}
+
+fn check_repository(repo_path: &Path) -> Check {
+    let helix_dir = repo_path.join(".helix");
+    if !helix_dir.is_dir() {
+        let fix = if repo_path.join(".git").exists() {
+            "Run `helix init` to create a Helix repo and import the Git history"
+        } else {
+            "Run `helix init` to create a Helix repo here"
+        };
+        return Check::fail("repository", "not a Helix repository (no .helix)", fix);
+    }
+
+    let missing: Vec<&str> = [
+        "objects/blobs",
+        "objects/trees",
+        "objects/commits",
+        "refs/heads",
+        "refs/tags",
+    ]
+    .into_iter()
+    .filter(|dir| !helix_dir.join(dir).is_dir())
+    .collect();
+
+    if missing.is_empty() {
+        Check::ok("repository", format!("{}", helix_dir.display()))
+    } else {
+        Check::fail(
+            "repository",
+            format!("missing .helix/{}", missing.join(", .helix/")),
+            "Run `helix init` again; it only creates what is missing",
+        )
+    }
+}
+
+/// The parsed config plus a warning per unknown key
+fn load_config(repo_path: &Path) -> std::result::Result<(HelixConfig, Vec<Check>), Check> {
+    let config_path = repo_path.join("helix.toml");
+    let text = fs::read_to_string(&config_path).map_err(|_| {
+        Check::fail(
+            "config",
+            "helix.toml is missing",
+            "Run `helix init` to write a default helix.toml",
+        )
+    })?;
+    let (config, warnings) = repo_config::check(&text).map_err(|diagnostic| {
+        Check::fail(
+            "config",
+            diagnostic.headline(),
+            diagnostic
+                .suggestion
+                .unwrap_or_else(|| "Fix the value at that line in helix.toml".to_string()),
+        )
+    })?;
+
+    let warnings = warnings
+        .into_iter()
+        .map(|diagnostic| {
+            Check::warn(
+                "config",
+                diagnostic.headline(),
+                diagnostic
+                    .suggestion
+                    .unwrap_or_else(|| "Remove the key; Helix ignores it".to_string()),
+            )
+        })
+        .collect();
+    Ok((config, warnings))
+}
+
+fn check_author(repo_path: &Path) -> Check {
+    match resolve_author(repo_path, None) {
+        Ok(author) => Check::ok("author", author),
+        Err(_) => Check::warn(
+            "author",
+            "no author configured; commits will fail",
+            "Add [user] name = \"...\" and email = \"...\" to helix.toml",
+        ),
+    }
+}
+
+fn check_index(repo_path: &Path) -> Check {
+    match Verifier::new(repo_path).verify() {
+        Ok(VerifyResult::Valid) => {
+            let entries = Reader::new(repo_path)
+                .read()
+                .map(|data| data.entries.len())
+                .unwrap_or(0);
+            Check::ok("index", format!("helix.idx is valid ({} entries)", entries))
+        }
+        Ok(VerifyResult::Missing) => Check::fail(
+            "index",
+            "helix.idx is missing",
+            "Run `helix init` to create an empty index, then `helix add` your files",
+        ),
+        Ok(VerifyResult::WrongRepo) => Check::fail(
+            "index",
+            "helix.idx belongs to a different repository",
+            "Remove .helix/helix.idx and run `helix init`, then `helix add` your files",
+        ),
+        Ok(VerifyResult::Corrupted) | Err(_) => Check::fail(
+            "index",
+            "helix.idx is corrupted",
+            "Remove .helix/helix.idx and run `helix init`, then `helix add` your files",
+        ),
+    }
+}
+
+fn check_index_lock(repo_path: &Path) -> Check {
+    let lock = IndexLock::lock_path(repo_path);
+    if lock.exists() {
+        Check::warn(
+            "index lock",
+            "helix.idx.lock exists; another helix command is running or one crashed",
+            format!(
+                "If no other helix command is running, delete {}",
+                lock.display()
+            ),
+        )
+    } else {
+        Check::ok("index lock", "not held")
+    }
+}
+
+/// Only reported for repos with a .git directory
+fn check_import(repo_path: &Path) -> Option<Check> {
+    if !repo_path.join(".git").exists() || !git_has_commits(repo_path) {
+        return None;
+    }
+
+    if !RevMap::exists(repo_path) {
+        return Some(Check::warn(
+            "import",
+            "Git history was never imported",
+            "Run `helix init` and answer Y to import your Git commits",
+        ));
+    }
+
+    // The mapping is written before branches and HEAD, so a crash in between leaves it
+    // without any branches
+    if !has_any_branch(repo_path) {
+        return Some(Check::fail(
+            "import",
+            "Git commits were imported but branches were not; the import did not finish",
+            "Run `helix import --update` to finish importing branches, tags and HEAD",
+        ));
+    }
+
+    Some(Check::ok("import", "Git history imported"))
+}
+
+fn git_has_commits(repo_path: &Path) -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", "HEAD"])
+        .current_dir(repo_path)
+        .output()
+        .map(|out| out.status.success())
+        .unwrap_or(false)
+}
+
+fn check_head(repo_path: &Path, store: &FsObjectStore) -> Check {
+    let helix_dir = repo_path.join(".helix");
+    let content = match fs::read_to_string(helix_dir.join("HEAD")) {
+        Ok(content) => content,
+        Err(_) => {
+            return Check::fail(
+                "HEAD",
+                ".helix/HEAD is missing",
+                "Run `helix init` to recreate HEAD (it points at refs/heads/main)",
+            )
+        }
+    };
+    let content = content.trim();
+
+    let Some(ref_name) = content.strip_prefix("ref:").map(str::trim) else {
+        // Detached HEAD
+        return match hex_to_hash(content) {
+            Ok(hash) if store.has_object(&ObjectType::Commit, &hash) => {
+                Check::ok("HEAD", format!("detached at {}", &content[..8]))
+            }
+            Ok(_) => Check::fail(
+                "HEAD",
+                format!("detached at {}, which is not in the store", &content[..8]),
+                "Run `helix repair` to fetch it from the remote, or check out a branch",
+            ),
+            Err(_) => Check::fail(
+                "HEAD",
+                "HEAD holds neither a ref nor a commit hash",
+                "Write `ref: refs/heads/main` (or another branch) to .helix/HEAD",
+            ),
+        };
+    };
+
+    let ref_path = helix_dir.join(ref_name);
+    let branch = ref_name.strip_prefix("refs/heads/").unwrap_or(ref_name);
+    match fs::read_to_string(&ref_path) {
+        Err(_) if !has_any_branch(repo_path) => {
+            Check::ok("HEAD", format!("on {} (no commits yet)", branch))
+        }
+        Err(_) => Check::fail(
+            "HEAD",
+            format!("points at {}, which does not exist", ref_name),
+            "Switch to an existing branch (`helix branch` lists them) or commit to create it",
+        ),
+        Ok(target) => match hex_to_hash(target.trim()) {
+            Ok(hash) if store.has_object(&ObjectType::Commit, &hash) => {
+                Check::ok("HEAD", format!("on {}", branch))
+            }
+            Ok(_) => Check::fail(
+                "HEAD",
+                format!("{} points at a commit that is not in the store", ref_name),
+                "Run `helix repair` to fetch missing objects, then `helix verify --all`",
+            ),
+            Err(_) => Check::fail(
+                "HEAD",
+                format!("{} does not contain a commit hash", ref_name),
+                "Run `helix verify --all` and `helix repair`",
+            ),
+        },
+    }
+}
+
+fn has_any_branch(repo_path: &Path) -> bool {
+    WalkDir::new(repo_path.join(".helix/refs/heads"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .any(|e| e.file_type().is_file())
+}
+
+fn check_refs(repo_path: &Path, store: &FsObjectStore) -> Check {
+    let helix_dir = repo_path.join(".helix");
+    let mut total = 0;
+    let mut broken: Vec<String> = Vec::new();
+
+    for entry in WalkDir::new(helix_dir.join("refs"))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        total += 1;
+        let name = entry
+            .path()
+            .strip_prefix(&helix_dir)
+            .unwrap_or(entry.path())
+            .to_string_lossy()
+            .replace('\\', "/");
+        let target: Option<Hash> = fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|text| hex_to_hash(text.trim()).ok());
+        let valid = target.is_some_and(|hash| {
+            store.has_object(&ObjectType::Commit, &hash)
+                || (name.starts_with("refs/tags/") && store.has_object(&ObjectType::Tag, &hash))
+        });
+        if !valid {
+            broken.push(name);
+        }
+    }
+
+    if broken.is_empty() {
+        Check::ok("refs", format!("{} ref(s) resolve", total))
+    } else {
+        Check::fail(
+            "refs",
+            format!(
+                "{} of {} ref(s) broken: {}",
+                broken.len(),
+                total,
+                broken.join(", ")
+            ),
+            "Run `helix repair` to fetch missing objects; `helix verify --all` shows details",
+        )
+    }
+}
+
+/// One check per distinct remote URL in the [remotes] table
+async fn check_remotes(config: &HelixConfig) -> Vec<Check> {
+    let mut urls: Vec<(String, String)> = config
+        .remotes
+        .iter()
+        .flat_map(|remotes| remotes.remotes())
+        .flat_map(|(name, remote)| {
+            let push = remote.push_url.map(|url| (format!("{name} (push)"), url));
+            std::iter::once((name, remote.url)).chain(push)
+        })
+        .collect();
+    urls.sort();
+    urls.dedup_by(|a, b| a.1 == b.1);
+
+    if urls.is_empty() {
+        return vec![Check::ok("remotes", "none configured")];
+    }
+
+    let client = match reqwest::Client::builder().timeout(REMOTE_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return vec![Check::fail(
+                "remotes",
+                format!("could not create HTTP client: {e}"),
+                "Check your TLS and proxy settings",
+            )]
+        }
+    };
+
+    let mut checks = Vec::new();
+    for (key, url) in urls {
+        // Any HTTP answer means a server is listening; older servers 404 on /healthz
+        let probe = format!("{}/healthz", url.trim_end_matches('/'));
+        checks.push(match client.get(&probe).send().await {
+            Ok(resp) if resp.status().is_server_error() => Check::warn(
+                "remote",
+                format!("{} ({}) answered {}", key, url, resp.status()),
+                "The server is up but unhealthy; check its logs and storage",
+            ),
+            Ok(_) => Check::ok("remote", format!("{} ({}) is reachable", key, url)),
+            Err(e) => Check::fail(
+                "remote",
+                format!("{} ({}) is unreachable: {}", key, url, e),
+                "Check the URL in helix.toml [remotes] and that helix-server is running",
+            ),
+        });
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_command::init_helix_repo;
+    use tempfile::TempDir;
+
+    fn status_of(report: &DoctorReport, name: &str) -> Option<Status> {
+        report
+            .checks
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.status)
+    }
+
+    #[tokio::test]
+    async fn test_doctor_reports_problems_with_fixes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        let options = DoctorOptions { offline: true };
+
+        // Not a repo: one failing check that says to run init
+        let report = doctor(repo, options.clone()).await?;
+        assert!(!report.is_ok());
+        assert_eq!(report.checks.len(), 1);
+        assert!(report.checks[0]
+            .fix
+            .as_deref()
+            .unwrap()
+            .contains("helix init"));
+
+        init_helix_repo(repo, None)?;
+        let report = doctor(repo, options.clone()).await?;
+        assert!(report.is_ok(), "{:?}", report.checks);
+        assert_eq!(status_of(&report, "author"), Some(Status::Warn));
+        assert_eq!(status_of(&report, "HEAD"), Some(Status::Ok));
+
+        // A branch pointing at a commit that isn't in the store
+        fs::write(repo.join(".helix/refs/heads/main"), "ab".repeat(32))?;
+        fs::write(repo.join(".helix/helix.idx.lock"), "1\n0\n")?;
+        fs::write(repo.join("helix.toml"), "not = [valid")?;
+        let report = doctor(repo, options).await?;
+        assert!(!report.is_ok());
+        assert_eq!(status_of(&report, "config"), Some(Status::Fail));
+        assert_eq!(status_of(&report, "HEAD"), Some(Status::Fail));
+        assert_eq!(status_of(&report, "refs"), Some(Status::Fail));
+        assert_eq!(status_of(&report, "index lock"), Some(Status::Warn));
+        assert!(report
+            .checks
+            .iter()
+            .filter(|c| c.status != Status::Ok)
+            .all(|c| c.fix.is_some()));
+
+        Ok(())
+    }
+}
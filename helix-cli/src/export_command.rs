@@ -0,0 +1,576 @@
+/*
+`helix export --git` - convert Helix history back into a Git repository.
+
+The inverse of the import in helix_index/sync.rs: every commit reachable from
+a Helix branch or tag is rewritten as a Git commit, with its trees and blobs,
+and the refs are recreated on the Git side:
+
+  .helix/refs/heads/<name>  ->  refs/heads/<name>
+  .helix/refs/tags/<name>   ->  refs/tags/<name> (annotated tags stay annotated)
+  .helix/HEAD               ->  HEAD (symbolic or detached)
+
+Authors, author/commit timestamps and messages are carried over unchanged, so
+exporting the same history twice produces the same Git SHAs. Commit metadata
+(`helix commit --metadata`) becomes one `helix-metadata <key>=<value>` header
+per entry, which the import turns back into metadata. When exporting
+into the repository's own .git, commits listed in the import map
+(.helix/maps/commits) reuse their original Git commits, which keeps imported
+history byte-identical even where the conversion lost something (signatures,
+committer identity), and newly exported commits are added to the map. Commits are
+written parents-first; blobs and trees are converted once and memoized.
+
+The destination defaults to the repository itself (.git next to .helix). Only
+the object database and refs are written; the Git index and working tree are
+left alone. Existing Git refs that point elsewhere are kept unless `force` is
+set.
+*/
+use anyhow::{anyhow, Context, Result};
+use git2::{FileMode, Oid, Repository, Signature, Time};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::Tag;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::helix_index::commit::{Commit, CommitStore, GIT_METADATA_HEADER};
+use crate::helix_index::rev_map::{GitSha, RevMap};
+use crate::helix_index::tree::{EntryType, TreeStore};
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Git repository to write to (created if missing). Defaults to the repo itself.
+    pub dest: Option<PathBuf>,
+    /// Overwrite Git refs that already point at a different commit
+    pub force: bool,
+    pub verbose: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportSummary {
+    pub commits: usize,
+    pub branches: usize,
+    pub tags: usize,
+    /// Refs left alone because the Git side already points elsewhere
+    pub skipped_refs: Vec<String>,
+}
+
+pub fn export_git(repo_path: &Path, options: ExportOptions) -> Result<ExportSummary> {
+    if !repo_path.join(".helix").exists() {
+        anyhow::bail!("Not a helix repository (no .helix directory)");
+    }
+
+    let dest = options
+        .dest
+        .clone()
+        .unwrap_or_else(|| repo_path.to_path_buf());
+    let git_repo = match Repository::open(&dest) {
+        Ok(repo) => repo,
+        Err(_) => Repository::init(&dest)
+            .with_context(|| format!("Failed to create Git repository at {}", dest.display()))?,
+    };
+
+    let mut exporter = Exporter::new(repo_path, &git_repo)?;
+    let mut summary = ExportSummary::default();
+
+    // Exporting into the repository's own .git: commits that came from (or
+    // already went to) it map straight to their Git SHAs
+    let own_repo = same_path(&dest, repo_path);
+    let mut rev_map = RevMap::open(repo_path)?;
+    let mut seeded = 0;
+    if own_repo {
+        let odb = git_repo.odb()?;
+        for (git_sha, helix_hash) in rev_map.entries() {
+            let oid = Oid::from_bytes(&git_sha)?;
+            if odb.exists(oid) {
+                exporter.commits.insert(helix_hash, oid);
+                seeded += 1;
+            }
+        }
+    }
+
+    let branches = read_refs(repo_path, "heads")?;
+    let tags = read_refs(repo_path, "tags")?;
+
+    for (kind, refs) in [("heads", &branches), ("tags", &tags)] {
+        for (name, hash) in refs {
+            let oid = exporter.export_ref_target(hash)?;
+            let git_ref = format!("refs/{}/{}", kind, name);
+
+            if !update_ref(&git_repo, &git_ref, oid, options.force)? {
+                summary.skipped_refs.push(git_ref);
+                continue;
+            }
+            if options.verbose {
+                println!("{} -> {}", git_ref, oid);
+            }
+            if kind == "heads" {
+                summary.branches += 1;
+            } else {
+                summary.tags += 1;
+            }
+        }
+    }
+
+    export_head(repo_path, &git_repo, &mut exporter)?;
+
+    summary.commits = exporter.commits.len() - seeded;
+
+    if own_repo {
+        let exported: Vec<(GitSha, Hash)> = exporter
+            .commits
+            .iter()
+            .filter(|(helix_hash, _)| rev_map.git_for(helix_hash).is_none())
+            .filter_map(|(helix_hash, oid)| Some((oid.as_bytes().try_into().ok()?, *helix_hash)))
+            .collect();
+        rev_map.append(&exported)?;
+    }
+    Ok(summary)
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Converts Helix objects to Git objects, remembering what it already wrote
+struct Exporter<'a> {
+    git: &'a Repository,
+    objects: FsObjectStore,
+    commit_store: CommitStore,
+    trees: TreeStore,
+    commits: HashMap<Hash, Oid>,
+    tree_oids: HashMap<Hash, Oid>,
+    blob_oids: HashMap<Hash, Oid>,
+}
+
+impl<'a> Exporter<'a> {
+    fn new(repo_path: &Path, git: &'a Repository) -> Result<Self> {
+        Ok(Self {
+            git,
+            objects: FsObjectStore::new(repo_path),
+            commit_store: CommitStore::new(repo_path, FsObjectStore::new(repo_path))?,
+            trees: TreeStore::for_repo(repo_path),
+            commits: HashMap::new(),
+            tree_oids: HashMap::new(),
+            blob_oids: HashMap::new(),
+        })
+    }
+
+    /// Export what a ref points at: an annotated tag becomes a Git tag object,
+    /// anything else is a commit
+    fn export_ref_target(&mut self, hash: &Hash) -> Result<Oid> {
+        if !self.objects.has_object(&ObjectType::Tag, hash) {
+            return self.export_commit(hash);
+        }
+
+        let raw = self.objects.read_object(&ObjectType::Tag, hash)?;
+        let tag = Tag::from_bytes(&raw)?;
+        let target = self.export_commit(&tag.target)?;
+
+        let (name, email) = split_author(&tag.tagger);
+        let tagger = Signature::new(&name, &email, &Time::new(tag.tag_time as i64, 0))?;
+
+        Ok(self.git.tag_annotation_create(
+            &tag.name,
+            &self
+                .git
+                .find_object(target, Some(git2::ObjectType::Commit))?,
+            &tagger,
+            &tag.message,
+        )?)
+    }
+
+    /// Export `tip` and all its ancestors, parents before children
+    fn export_commit(&mut self, tip: &Hash) -> Result<Oid> {
+        // Iterative post-order walk so deep histories don't overflow the stack
+        let mut stack: Vec<(Hash, bool)> = vec![(*tip, false)];
+        let mut pending = HashSet::new();
+
+        while let Some((hash, parents_done)) = stack.pop() {
+            if self.commits.contains_key(&hash) {
+                continue;
+            }
+
+            let commit = self
+                .commit_store
+                .read_commit(&hash)
+                .with_context(|| format!("Failed to read commit {}", hash_to_hex(&hash)))?;
+
+            if !parents_done {
+                if !pending.insert(hash) {
+                    continue;
+                }
+                stack.push((hash, true));
+                for parent in &commit.parents {
+                    if !self.commits.contains_key(parent) {
+                        stack.push((*parent, false));
+                    }
+                }
+                continue;
+            }
+
+            let oid = self.write_commit(&commit)?;
+            self.commits.insert(hash, oid);
+        }
+
+        self.commits
+            .get(tip)
+            .copied()
+            .ok_or_else(|| anyhow!("Failed to export commit {}", hash_to_hex(tip)))
+    }
+
+    fn write_commit(&mut self, commit: &Commit) -> Result<Oid> {
+        let tree_oid = self.export_tree(&commit.tree_hash)?;
+        let tree = self.git.find_tree(tree_oid)?;
+
+        let parents = commit
+            .parents
+            .iter()
+            .map(|parent| self.git.find_commit(self.commits[parent]))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+
+        let (name, email) = split_author(&commit.author);
+        let author = Signature::new(&name, &email, &Time::new(commit.author_time as i64, 0))?;
+        let committer = Signature::new(&name, &email, &Time::new(commit.commit_time as i64, 0))?;
+
+        if commit.headers.is_empty() {
+            return Ok(self.git.commit(
+                None,
+                &author,
+                &committer,
+                &commit.message,
+                &tree,
+                &parent_refs,
+            )?);
+        }
+
+        // Metadata goes in extra headers, after committer and before the message
+        let buffer = self.git.commit_create_buffer(
+            &author,
+            &committer,
+            &commit.message,
+            &tree,
+            &parent_refs,
+        )?;
+        let buffer = std::str::from_utf8(&buffer).context("Commit buffer is not UTF-8")?;
+        let (header, message) = buffer
+            .split_once("\n\n")
+            .ok_or_else(|| anyhow!("Malformed commit buffer"))?;
+        let mut raw = format!("{header}\n");
+        for (key, value) in &commit.headers {
+            // Multi-line values continue on lines starting with a space
+            let value = value.replace('\n', "\n ");
+            raw.push_str(&format!("{GIT_METADATA_HEADER} {key}={value}\n"));
+        }
+        raw.push('\n');
+        raw.push_str(message);
+        Ok(self
+            .git
+            .odb()?
+            .write(git2::ObjectType::Commit, raw.as_bytes())?)
+    }
+
+    fn export_tree(&mut self, hash: &Hash) -> Result<Oid> {
+        if let Some(oid) = self.tree_oids.get(hash) {
+            return Ok(*oid);
+        }
+
+        let tree = self
+            .trees
+            .read(hash)
+            .with_context(|| format!("Failed to read tree {}", hash_to_hex(hash)))?;
+
+        let mut builder = self.git.treebuilder(None)?;
+        for entry in &tree.entries {
+            let (oid, mode) = match entry.entry_type {
+                EntryType::Tree => (self.export_tree(&entry.oid)?, FileMode::Tree),
+                EntryType::FileExecutable => {
+                    (self.export_blob(&entry.oid)?, FileMode::BlobExecutable)
+                }
+                EntryType::Symlink => (self.export_blob(&entry.oid)?, FileMode::Link),
+                EntryType::File => (self.export_blob(&entry.oid)?, FileMode::Blob),
+            };
+            builder.insert(&entry.name, oid, mode.into())?;
+        }
+
+        let oid = builder.write()?;
+        self.tree_oids.insert(*hash, oid);
+        Ok(oid)
+    }
+
+    fn export_blob(&mut self, hash: &Hash) -> Result<Oid> {
+        if let Some(oid) = self.blob_oids.get(hash) {
+            return Ok(*oid);
+        }
+
+        let content = self
+            .objects
+            .read_object(&ObjectType::Blob, hash)
+            .with_context(|| format!("Failed to read blob {}", hash_to_hex(hash)))?;
+        let oid = self.git.blob(&content)?;
+
+        self.blob_oids.insert(*hash, oid);
+        Ok(oid)
+    }
+}
+
+/// Mirror .helix/HEAD: symbolic refs stay symbolic, a detached HEAD is exported
+fn export_head(repo_path: &Path, git: &Repository, exporter: &mut Exporter) -> Result<()> {
+    let Ok(content) = fs::read_to_string(repo_path.join(".helix/HEAD")) else {
+        return Ok(());
+    };
+    let content = content.trim();
+
+    if let Some(target) = content.strip_prefix("ref:") {
+        // HEAD of a non-bare repo is the user's checkout; only point it at the
+        // branch when the destination has no checkout yet
+        if git.head().is_err() || git.is_bare() {
+            git.set_head(target.trim())?;
+        }
+    } else if let Ok(hash) = hex_to_hash(content) {
+        let oid = exporter.export_commit(&hash)?;
+        if git.head().is_err() {
+            git.set_head_detached(oid)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Create or move a Git ref. Returns false if it exists elsewhere and `force` is off.
+fn update_ref(git: &Repository, name: &str, oid: Oid, force: bool) -> Result<bool> {
+    if let Ok(existing) = git.find_reference(name) {
+        if existing.target() == Some(oid) {
+            return Ok(true);
+        }
+        if !force {
+            return Ok(false);
+        }
+    }
+
+    git.reference(name, oid, true, "helix export")?;
+    Ok(true)
+}
+
+/// (name, hash) for every ref under .helix/refs/<kind>, nested names kept
+fn read_refs(repo_path: &Path, kind: &str) -> Result<Vec<(String, Hash)>> {
+    let dir = repo_path.join(".helix/refs").join(kind);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut refs = Vec::new();
+    for entry in WalkDir::new(&dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let name = entry
+            .path()
+            .strip_prefix(&dir)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let content = fs::read_to_string(entry.path())?;
+        match hex_to_hash(content.trim()) {
+            Ok(hash) => refs.push((name, hash)),
+            Err(_) => eprintln!("Warning: skipping ref {}/{}: invalid hash", kind, name),
+        }
+    }
+
+    Ok(refs)
+}
+
+/// "Name <email>" -> (name, email). Anything else becomes the name.
+fn split_author(author: &str) -> (String, String) {
+    match (author.find('<'), author.rfind('>')) {
+        (Some(start), Some(end)) if start < end => (
+            author[..start].trim().to_string(),
+            author[start + 1..end].trim().to_string(),
+        ),
+        _ => (author.trim().to_string(), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::{Tree, TreeEntry};
+    use tempfile::TempDir;
+
+    fn write_commit(
+        store: &FsObjectStore,
+        tree: Hash,
+        parents: Vec<Hash>,
+        time: u64,
+        message: &str,
+    ) -> Result<Hash> {
+        let mut commit = Commit::new(
+            tree,
+            parents,
+            "Ada <ada@example.com>".into(),
+            message.into(),
+        );
+        commit.author_time = time;
+        commit.commit_time = time + 5;
+        commit.commit_hash = commit.compute_hash();
+        store.write_object_with_hash(
+            &ObjectType::Commit,
+            &commit.commit_hash,
+            &commit.to_bytes(),
+        )?;
+        Ok(commit.commit_hash)
+    }
+
+    #[test]
+    fn test_export_carries_metadata_headers() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        let store = FsObjectStore::new(&repo);
+        let tree = store.write_object(&ObjectType::Tree, &Tree::new().to_bytes())?;
+
+        let commit = Commit::initial(tree, "Ada <ada@example.com>".into(), "ci".into())
+            .with_headers([("build-id".to_string(), "4711".to_string())].into());
+        let hash = store.write_object(&ObjectType::Commit, &commit.to_bytes())?;
+        fs::write(repo.join(".helix/refs/heads/main"), hash_to_hex(&hash))?;
+
+        let dest = temp.path().join("exported");
+        export_git(
+            &repo,
+            ExportOptions {
+                dest: Some(dest.clone()),
+                ..Default::default()
+            },
+        )?;
+
+        let git = Repository::open(&dest)?;
+        let tip = git.find_reference("refs/heads/main")?.peel_to_commit()?;
+        assert_eq!(
+            tip.header_field_bytes(GIT_METADATA_HEADER)?.as_str(),
+            Some("build-id=4711")
+        );
+        assert_eq!(tip.message(), Some("ci"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_author() {
+        assert_eq!(
+            split_author("Ada Lovelace <ada@example.com>"),
+            ("Ada Lovelace".to_string(), "ada@example.com".to_string())
+        );
+        assert_eq!(split_author("ada"), ("ada".to_string(), String::new()));
+    }
+
+    #[test]
+    fn test_export_git_roundtrip() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        fs::create_dir_all(repo.join(".helix/refs/tags"))?;
+        let store = FsObjectStore::new(&repo);
+
+        let blob = store.write_object(&ObjectType::Blob, b"hello\n")?;
+        let script = store.write_object(&ObjectType::Blob, b"#!/bin/sh\n")?;
+
+        let mut sub = Tree::new();
+        sub.add_entry(TreeEntry::new_file("run.sh".into(), script, 0o100755, 10));
+        let sub_hash = store.write_object(&ObjectType::Tree, &sub.to_bytes())?;
+
+        let mut root = Tree::new();
+        root.add_entry(TreeEntry::new_file("hello.txt".into(), blob, 0o100644, 6));
+        root.add_entry(TreeEntry::new_tree("bin".into(), sub_hash));
+        let root_hash = store.write_object(&ObjectType::Tree, &root.to_bytes())?;
+
+        let first = write_commit(&store, root_hash, vec![], 1_700_000_000, "first")?;
+        let second = write_commit(
+            &store,
+            root_hash,
+            vec![first],
+            1_700_000_100,
+            "second\n\nbody",
+        )?;
+
+        fs::write(repo.join(".helix/HEAD"), "ref: refs/heads/main\n")?;
+        fs::write(repo.join(".helix/refs/heads/main"), hash_to_hex(&second))?;
+        fs::write(repo.join(".helix/refs/tags/v1"), hash_to_hex(&first))?;
+        let annotated = Tag {
+            target: second,
+            name: "v2".into(),
+            tagger: "Ada <ada@example.com>".into(),
+            tag_time: 1_700_000_200,
+            message: "Release two\n".into(),
+        };
+        let annotated_hash = store.write_object(&ObjectType::Tag, &annotated.to_bytes())?;
+        fs::write(
+            repo.join(".helix/refs/tags/v2"),
+            hash_to_hex(&annotated_hash),
+        )?;
+
+        let dest = temp.path().join("exported");
+        let summary = export_git(
+            &repo,
+            ExportOptions {
+                dest: Some(dest.clone()),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!((summary.commits, summary.branches, summary.tags), (2, 1, 2));
+
+        let git = Repository::open(&dest)?;
+        let head = git.head()?;
+        assert_eq!(head.shorthand(), Some("main"));
+
+        let tip = head.peel_to_commit()?;
+        assert_eq!(tip.message(), Some("second\n\nbody"));
+        assert_eq!(tip.author().name(), Some("Ada"));
+        assert_eq!(tip.author().email(), Some("ada@example.com"));
+        assert_eq!(tip.author().when().seconds(), 1_700_000_100);
+        assert_eq!(tip.committer().when().seconds(), 1_700_000_105);
+        assert_eq!(tip.parent_count(), 1);
+
+        let tree = tip.tree()?;
+        let hello = tree.get_name("hello.txt").unwrap();
+        assert_eq!(git.find_blob(hello.id())?.content(), b"hello\n");
+        let run = tree.get_path(Path::new("bin/run.sh"))?;
+        assert_eq!(run.filemode(), i32::from(FileMode::BlobExecutable));
+
+        let tag = git.find_reference("refs/tags/v1")?.peel_to_commit()?;
+        assert_eq!(tag.id(), tip.parent_id(0)?);
+
+        let v2 = git.find_reference("refs/tags/v2")?.peel_to_tag()?;
+        assert_eq!(v2.message(), Some("Release two\n"));
+        assert_eq!(v2.tagger().unwrap().name(), Some("Ada"));
+        assert_eq!(v2.target_id(), tip.id());
+
+        // Same history exports to the same SHAs
+        let again = export_git(
+            &repo,
+            ExportOptions {
+                dest: Some(dest.clone()),
+                ..Default::default()
+            },
+        )?;
+        assert!(again.skipped_refs.is_empty());
+
+        // A Git-side branch that moved is kept unless forced
+        git.reference("refs/heads/main", tag.id(), true, "test")?;
+        let skipped = export_git(
+            &repo,
+            ExportOptions {
+                dest: Some(dest.clone()),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(skipped.skipped_refs, vec!["refs/heads/main".to_string()]);
+
+        Ok(())
+    }
+}
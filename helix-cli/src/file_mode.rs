@@ -0,0 +1,243 @@
+/*
+Platform-aware file modes and symlinks.
+
+Helix stores Git-style modes in the index and in trees:
+
+  0o100644  regular file
+  0o100755  executable file
+  0o120000  symlink (blob content is the link target)
+
+On Unix these map directly onto permission bits and symlinks. Windows has no
+executable bit and creating symlinks needs Developer Mode or admin rights, so:
+
+- The executable bit is taken from what Git / the index already recorded for
+  the path instead of from disk (`mode_from_metadata` with a recorded mode).
+- Symlinks are materialized according to the [core] `symlinks` value in
+  .helix/state:
+
+    native    real symlink (default on Unix)
+    junction  directory junction for directory targets, text file otherwise
+    text      plain file containing the link target (default on Windows,
+              same as Git with core.symlinks=false)
+*/
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+use crate::helix_index::state::get_core_value;
+
+pub const MODE_REGULAR: u32 = 0o100644;
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+pub const MODE_SYMLINK: u32 = 0o120000;
+
+const SYMLINKS_KEY: &str = "symlinks";
+
+/// How symlink entries are written to the working tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkStrategy {
+    Native,
+    Junction,
+    Text,
+}
+
+impl Default for SymlinkStrategy {
+    fn default() -> Self {
+        if cfg!(unix) {
+            Self::Native
+        } else {
+            Self::Text
+        }
+    }
+}
+
+impl SymlinkStrategy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "native" | "true" => Some(Self::Native),
+            "junction" => Some(Self::Junction),
+            "text" | "false" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// Load the configured strategy, falling back to the platform default
+    pub fn load(repo_path: &Path) -> Self {
+        get_core_value(repo_path, SYMLINKS_KEY)
+            .and_then(|v| Self::parse(&v))
+            .unwrap_or_default()
+    }
+}
+
+/// Compute the index mode for a working tree file.
+///
+/// `recorded` is the mode already stored for this path (from Git or a previous
+/// add). Platforms that can't represent the executable bit keep the recorded one.
+pub fn mode_from_metadata(metadata: &fs::Metadata, recorded: Option<u32>) -> u32 {
+    if metadata.file_type().is_symlink() {
+        return MODE_SYMLINK;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = recorded;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            MODE_EXECUTABLE
+        } else {
+            MODE_REGULAR
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        match recorded {
+            Some(MODE_EXECUTABLE) => MODE_EXECUTABLE,
+            _ => MODE_REGULAR,
+        }
+    }
+}
+
+/// Mark a checked out file as executable. No-op where there is no executable bit.
+pub fn set_executable(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        fs::set_permissions(path, perms)
+            .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Create `link` pointing at `target` using the given strategy
+pub fn write_symlink(target: &str, link: &Path, strategy: SymlinkStrategy) -> Result<()> {
+    match strategy {
+        SymlinkStrategy::Native => native_symlink(target, link),
+        SymlinkStrategy::Junction => {
+            let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+            if resolved.is_dir() {
+                junction(&resolved, link)
+            } else {
+                write_text_link(target, link)
+            }
+        }
+        SymlinkStrategy::Text => write_text_link(target, link),
+    }
+}
+
+fn write_text_link(target: &str, link: &Path) -> Result<()> {
+    fs::write(link, target.as_bytes())
+        .with_context(|| format!("Failed to write symlink placeholder {}", link.display()))
+}
+
+#[cfg(unix)]
+fn native_symlink(target: &str, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)
+        .with_context(|| format!("Failed to create symlink {}", link.display()))
+}
+
+#[cfg(windows)]
+fn native_symlink(target: &str, link: &Path) -> Result<()> {
+    let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+    let result = if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    };
+    result.with_context(|| {
+        format!(
+            "Failed to create symlink {} (enable Developer Mode or set symlinks = text in [core])",
+            link.display()
+        )
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn native_symlink(target: &str, link: &Path) -> Result<()> {
+    write_text_link(target, link)
+}
+
+#[cfg(windows)]
+fn junction(target: &Path, link: &Path) -> Result<()> {
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(link)
+        .arg(target)
+        .output()
+        .context("Failed to run mklink")?
+        .status;
+    if !status.success() {
+        anyhow::bail!("Failed to create junction {}", link.display());
+    }
+    Ok(())
+}
+
+/// Junctions only exist on Windows; elsewhere a real symlink is the closest match
+#[cfg(not(windows))]
+fn junction(target: &Path, link: &Path) -> Result<()> {
+    native_symlink(&target.to_string_lossy(), link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(
+            SymlinkStrategy::parse("Native"),
+            Some(SymlinkStrategy::Native)
+        );
+        assert_eq!(
+            SymlinkStrategy::parse("junction"),
+            Some(SymlinkStrategy::Junction)
+        );
+        assert_eq!(SymlinkStrategy::parse("false"), Some(SymlinkStrategy::Text));
+        assert_eq!(SymlinkStrategy::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn test_text_symlink_writes_target() -> Result<()> {
+        let temp = TempDir::new()?;
+        let link = temp.path().join("link");
+
+        write_symlink("../target.txt", &link, SymlinkStrategy::Text)?;
+
+        assert!(!fs::symlink_metadata(&link)?.file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&link)?, "../target.txt");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mode_from_metadata_unix() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new()?;
+        let script = temp.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh")?;
+
+        let meta = fs::metadata(&script)?;
+        // Disk wins over the recorded mode on Unix
+        assert_eq!(
+            mode_from_metadata(&meta, Some(MODE_EXECUTABLE)),
+            MODE_REGULAR
+        );
+
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+        let meta = fs::metadata(&script)?;
+        assert_eq!(mode_from_metadata(&meta, None), MODE_EXECUTABLE);
+
+        write_symlink("run.sh", &temp.path().join("link"), SymlinkStrategy::Native)?;
+        let meta = fs::symlink_metadata(temp.path().join("link"))?;
+        assert_eq!(mode_from_metadata(&meta, None), MODE_SYMLINK);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+/*
+`helix format-patch` - write commits out as patch emails, one per commit, for
+review over email or moving changes between repositories without a remote.
+
+  helix format-patch REV            the commits HEAD has and REV doesn't
+  helix format-patch FROM..TO       the commits TO has and FROM doesn't
+  -o, --output-directory DIR        where to write the files (default: .)
+  --stdout                          print them all as one mailbox instead
+
+Each commit, oldest first, becomes NNNN-<subject>.patch in mbox format:
+
+  From <commit> Mon Sep 17 00:00:00 2001
+  From: Ana Lima <ana@example.com>
+  Date: Tue, 14 Nov 2023 22:13:20 +0000
+  Subject: [PATCH 2/3] Add docs
+  X-Helix-Metadata: review=42
+  Content-Type: text/plain; charset=UTF-8
+
+  The rest of the commit message.
+  ---
+  diff --git a/docs.md b/docs.md
+  ...
+  --
+  helix 0.1.0
+
+The fixed date on the first line marks the file as a patch rather than a
+delivered message, as Git does. One X-Helix-Metadata header carries each of
+the commit's metadata entries. `helix apply --commit` reads all of this back
+and recreates the commits; plain `helix apply`, `git apply` and `patch` just
+apply the diffs.
+
+Merge commits are skipped: a patch shows one side of a change. Binary changes
+are only summarized, so they don't survive the trip.
+*/
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use helix_protocol::hash::{hash_to_hex, Hash};
+use helix_protocol::storage::FsObjectStore;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diff_command::{commit_diff, resolve_revision};
+use crate::helix_index::commit::{Commit, CommitStore};
+use crate::unified_diff::Whitespace;
+
+/// Header naming one commit metadata entry, as key=value
+pub const METADATA_HEADER: &str = "X-Helix-Metadata";
+
+/// Git's marker date for the mbox separator line of a patch
+const MBOX_DATE: &str = "Mon Sep 17 00:00:00 2001";
+
+/// Longest subject part of a patch file name
+const MAX_SLUG_LEN: usize = 52;
+
+/// One commit as a patch email
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub commit: Hash,
+    /// File name, NNNN-<subject>.patch
+    pub file_name: String,
+    pub contents: String,
+}
+
+/// Patches for the non-merge commits in `range`, oldest first
+pub fn format_patch(repo_path: &Path, range: &str) -> Result<Vec<Patch>> {
+    let (from, to) = match range.split_once("..") {
+        Some((from, to)) => (
+            if from.is_empty() { "HEAD" } else { from },
+            if to.is_empty() { "HEAD" } else { to },
+        ),
+        None => (range, "HEAD"),
+    };
+    let from = resolve_revision(repo_path, from)?;
+    let to = resolve_revision(repo_path, to)?;
+    let commits = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+
+    let excluded = oldest_first(&commits, from, &HashSet::new())?
+        .into_iter()
+        .collect();
+    let mut selected = Vec::new();
+    for hash in oldest_first(&commits, to, &excluded)? {
+        let commit = commits.read_commit(&hash)?;
+        if !commit.is_merge() {
+            selected.push(commit);
+        }
+    }
+
+    let total = selected.len();
+    selected
+        .iter()
+        .enumerate()
+        .map(|(i, commit)| {
+            let number = i + 1;
+            Ok(Patch {
+                commit: commit.commit_hash,
+                file_name: format!("{:04}-{}.patch", number, slug(commit.summary())),
+                contents: format_commit(repo_path, commit, number, total)?,
+            })
+        })
+        .collect()
+}
+
+/// Write each patch to `dir`, returning the paths written
+pub fn write_patches(patches: &[Patch], dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    patches
+        .iter()
+        .map(|patch| {
+            let path = dir.join(&patch.file_name);
+            fs::write(&path, &patch.contents)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// One commit as an mbox message, patch `number` of `total`
+fn format_commit(repo_path: &Path, commit: &Commit, number: usize, total: usize) -> Result<String> {
+    let date = DateTime::from_timestamp(commit.author_time as i64, 0)
+        .context("Commit date out of range")?
+        .to_rfc2822();
+    let prefix = if total == 1 {
+        "[PATCH]".to_string()
+    } else {
+        format!("[PATCH {number}/{total}]")
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "From {} {}",
+        hash_to_hex(&commit.commit_hash),
+        MBOX_DATE
+    );
+    let _ = writeln!(out, "From: {}", commit.author);
+    let _ = writeln!(out, "Date: {}", date);
+    let _ = writeln!(out, "Subject: {} {}", prefix, commit.summary());
+    for (key, value) in &commit.headers {
+        let _ = writeln!(out, "{}: {}={}", METADATA_HEADER, key, value);
+    }
+    out.push_str("Content-Type: text/plain; charset=UTF-8\n\n");
+
+    let body = commit
+        .message
+        .split_once('\n')
+        .map(|(_, body)| body.trim())
+        .unwrap_or("");
+    if !body.is_empty() {
+        out.push_str(body);
+        out.push('\n');
+    }
+    out.push_str("---\n");
+    for diff in commit_diff(repo_path, &commit.commit_hash)? {
+        out.push_str(&diff.render(false, 3, Whitespace::Exact));
+    }
+    let _ = write!(out, "-- \nhelix {}\n\n", env!("CARGO_PKG_VERSION"));
+    Ok(out)
+}
+
+/// Commits reachable from `tip` without going into `excluded`, each after its parents
+fn oldest_first(commits: &CommitStore, tip: Hash, excluded: &HashSet<Hash>) -> Result<Vec<Hash>> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    // (commit, whether its parents have been pushed)
+    let mut stack = vec![(tip, false)];
+    while let Some((hash, expanded)) = stack.pop() {
+        if expanded {
+            order.push(hash);
+            continue;
+        }
+        if excluded.contains(&hash) || !seen.insert(hash) {
+            continue;
+        }
+        stack.push((hash, true));
+        // History that was never fetched ends the walk there
+        if let Ok(commit) = commits.read_commit(&hash) {
+            stack.extend(commit.parents.iter().rev().map(|parent| (*parent, false)));
+        }
+    }
+    Ok(order)
+}
+
+/// A subject as a file name: runs of anything but letters, digits, '.' and '_' become
+/// one '-'
+fn slug(subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let mut slug: String = slug.chars().take(MAX_SLUG_LEN).collect();
+    while slug.ends_with(['-', '.']) {
+        slug.pop();
+    }
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_command::parse_mailbox;
+    use crate::helix_index::tree::{Tree, TreeEntry, TreeStore};
+    use helix_protocol::message::ObjectType;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_format_patch_writes_one_mail_per_commit() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        let store = FsObjectStore::new(repo);
+        let commits = CommitStore::new(repo, store.clone())?;
+
+        let mut tree = Tree::new();
+        let mut parents = Vec::new();
+        let mut hashes = Vec::new();
+        let history = [
+            ("base.txt", "Base\n"),
+            ("a.txt", "Add a: the first letter\n\nIt comes before b.\n"),
+            ("b.txt", "Add b\n"),
+        ];
+        for (i, (path, message)) in history.iter().enumerate() {
+            let content = format!("{path}\n");
+            let blob = store.write_object(&ObjectType::Blob, content.as_bytes())?;
+            tree.add_entry(TreeEntry::new_file(
+                path.to_string(),
+                blob,
+                0o100644,
+                content.len() as u64,
+            ));
+            tree.sort();
+            let tree_hash = TreeStore::for_repo(repo).write(&tree)?;
+            let mut commit = Commit::new(
+                tree_hash,
+                parents,
+                "Ana Lima <ana@example.com>".into(),
+                message.to_string(),
+            )
+            .with_author_time(1_700_000_000 + i as u64);
+            if i == 2 {
+                commit = commit.with_headers(BTreeMap::from([("review".into(), "42".into())]));
+            }
+            let hash = commits.write_commit(&commit)?;
+            hashes.push(hash);
+            parents = vec![hash];
+        }
+
+        let range = format!("{}..{}", hash_to_hex(&hashes[0]), hash_to_hex(&hashes[2]));
+        let patches = format_patch(repo, &range)?;
+        let names: Vec<&str> = patches.iter().map(|p| p.file_name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["0001-Add-a-the-first-letter.patch", "0002-Add-b.patch"]
+        );
+
+        let first = &patches[0].contents;
+        assert!(first.starts_with(&format!("From {} {MBOX_DATE}\n", hash_to_hex(&hashes[1]))));
+        assert!(first.contains("\nSubject: [PATCH 1/2] Add a: the first letter\n"));
+        assert!(first.contains("\nDate: Tue, 14 Nov 2023 22:13:21 +0000\n"));
+        assert!(first.contains("\n\nIt comes before b.\n---\ndiff --git a/a.txt b/a.txt\n"));
+
+        // What apply reads back
+        let mails = parse_mailbox(
+            &patches
+                .iter()
+                .map(|p| p.contents.as_str())
+                .collect::<String>(),
+        );
+        assert_eq!(mails.len(), 2);
+        assert_eq!(mails[0].author, "Ana Lima <ana@example.com>");
+        assert_eq!(mails[0].date, Some(1_700_000_001));
+        assert_eq!(
+            mails[0].message,
+            "Add a: the first letter\n\nIt comes before b.\n"
+        );
+        assert_eq!(mails[1].message, "Add b\n");
+        assert_eq!(
+            mails[1].metadata.get("review").map(String::as_str),
+            Some("42")
+        );
+        assert!(mails[1].diff.starts_with("diff --git a/b.txt b/b.txt\n"));
+
+        let written = write_patches(&patches, &repo.join("out"))?;
+        assert_eq!(fs::read_to_string(&written[1])?, patches[1].contents);
+        assert_eq!(slug("  Fix: a/b -- c!  "), "Fix-a-b-c");
+        Ok(())
+    }
+}
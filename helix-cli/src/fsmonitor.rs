@@ -223,8 +223,9 @@ impl FSMonitor {
 
             // TODO: probably update this to just one-time sync on helix initwith the git index and not actually watch it moving forward.
             for path in &event.paths {
-                // Check for helix canonical index changes
-                if path.ends_with(".helix/helix.idx") {
+                // Check for helix canonical index changes (base file or split-index journal)
+                if path.ends_with(".helix/helix.idx") || path.ends_with(".helix/helix.idx.journal")
+                {
                     index_dirty.insert(PathBuf::from(".helix/helix.idx"));
                     helix_index_changed = true;
                     continue;
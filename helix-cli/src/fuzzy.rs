@@ -0,0 +1,86 @@
+/*
+Fuzzy matching for the search boxes in the branch and log TUIs.
+
+A query matches text when its characters appear in the text in order, ignoring
+case, so "fxlg" matches "fix login". Where the query also appears as one run
+that run is preferred, so the highlighted characters are the ones a reader
+expects. Whitespace in the query is ignored.
+*/
+use ratatui::{style::Style, text::Span};
+
+/// Char positions in `text` that `query` matches, or None if it doesn't.
+/// An empty query matches everything with no positions.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(Vec::new());
+    }
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    // A contiguous run, when there is one
+    if let Some(start) = text
+        .windows(query.len())
+        .position(|window| window == query.as_slice())
+    {
+        return Some((start..start + query.len()).collect());
+    }
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut wanted = query.iter().peekable();
+    for (i, c) in text.iter().enumerate() {
+        if wanted.peek() == Some(&c) {
+            positions.push(i);
+            wanted.next();
+        }
+    }
+    wanted.peek().is_none().then_some(positions)
+}
+
+/// `text` as spans, with the chars at `positions` in `matched` and the rest in `style`
+pub fn highlight(
+    text: &str,
+    positions: &[usize],
+    style: Style,
+    matched: Style,
+) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let is_matched = positions.contains(&i);
+        if is_matched != run_matched && !run.is_empty() {
+            let style = if run_matched { matched } else { style };
+            spans.push(Span::styled(std::mem::take(&mut run), style));
+        }
+        run_matched = is_matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, if run_matched { matched } else { style }));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "anything"), Some(vec![]));
+        assert_eq!(fuzzy_match("fxlg", "Fix login"), Some(vec![0, 2, 4, 6]));
+        assert_eq!(fuzzy_match("LOG", "fix login"), Some(vec![4, 5, 6]));
+        // A run wins over the first scattered match
+        assert_eq!(fuzzy_match("main", "my-domain"), Some(vec![5, 6, 7, 8]));
+        assert_eq!(
+            fuzzy_match("feat x", "feature/x"),
+            Some(vec![0, 1, 2, 3, 8])
+        );
+        assert_eq!(fuzzy_match("xyz", "fix login"), None);
+        assert_eq!(fuzzy_match("gol", "login"), None);
+    }
+}
@@ -0,0 +1,105 @@
+/*
+`helix gc` - housekeeping of .helix/objects.
+
+  helix gc      move every object into the configured layout and remove
+                temp files that interrupted writes left behind
+
+Object files are either all in one directory per type (blobs/<hash>), which
+slows down badly once a type has hundreds of thousands of files, or fanned
+out two levels by the start of the hash like Git (blobs/aa/bb/<rest>). The
+layout is set in helix.toml and defaults to fanout; repos made before fanout
+existed stay flat until gc moves them:
+
+  [core]
+  object_layout = "fanout"    # or "flat"
+
+Reads find objects in either layout, so a repo keeps working while gc runs
+and a gc that is interrupted can simply be run again. Temp files are only
+removed once they are an hour old, so writes in progress are left alone.
+Unreachable objects are not pruned.
+*/
+use anyhow::Result;
+use helix_protocol::storage::{FsObjectStore, ObjectLayout, RelayoutReport};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::init_command::HelixConfig;
+
+/// Temp files younger than this may belong to a write still in progress
+const STALE_TEMP_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// The object layout helix.toml asks for, fanout unless it says otherwise
+pub fn configured_layout(repo_path: &Path) -> ObjectLayout {
+    std::fs::read_to_string(repo_path.join("helix.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str::<HelixConfig>(&contents).ok())
+        .and_then(|cfg| cfg.core)
+        .and_then(|core| core.object_layout)
+        .unwrap_or(ObjectLayout::Fanout)
+}
+
+pub fn gc(repo_path: &Path) -> Result<RelayoutReport> {
+    let mut store = FsObjectStore::new(repo_path);
+    let layout = configured_layout(repo_path);
+    let report = store.relayout(layout, STALE_TEMP_AFTER)?;
+
+    println!(
+        "Objects are in the {} layout ({} moved{})",
+        layout,
+        report.moved,
+        if report.duplicates > 0 {
+            format!(", {} duplicate copies removed", report.duplicates)
+        } else {
+            String::new()
+        }
+    );
+    if report.stale_temp_files > 0 {
+        println!(
+            "Removed {} temp files left by interrupted writes",
+            report.stale_temp_files
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::init_command::init_helix_repo;
+    use helix_protocol::message::ObjectType;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_gc_moves_a_flat_store_to_fanout() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        init_helix_repo(repo, None)?;
+        assert_eq!(FsObjectStore::new(repo).layout(), ObjectLayout::Fanout);
+
+        // A repo from before fanout: flat, with no layout recorded
+        fs::remove_file(repo.join(".helix/objects/layout"))?;
+        fs::write(repo.join("a.txt"), "a")?;
+        stage_paths(repo, &[PathBuf::from("a.txt")])?;
+        let store = FsObjectStore::new(repo);
+        assert_eq!(store.layout(), ObjectLayout::Flat);
+        let blobs = store.list_object_hashes(&ObjectType::Blob)?;
+        assert_eq!(blobs.len(), 1);
+
+        let report = gc(repo)?;
+        assert_eq!(report.moved, 1);
+        let store = FsObjectStore::new(repo);
+        assert_eq!(store.layout(), ObjectLayout::Fanout);
+        assert_eq!(store.read_object(&ObjectType::Blob, &blobs[0])?, b"a");
+        assert_eq!(gc(repo)?.moved, 0);
+
+        fs::write(
+            repo.join("helix.toml"),
+            "[core]\nobject_layout = \"flat\"\n",
+        )?;
+        assert_eq!(configured_layout(repo), ObjectLayout::Flat);
+        assert_eq!(gc(repo)?.moved, 1);
+        Ok(())
+    }
+}
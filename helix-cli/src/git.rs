@@ -78,6 +78,31 @@ impl Git {
         Ok(Self::format_diff_for_llm(&diff))
     }
 
+    /// Staged paths, if every staged change is to a binary file.
+    /// There is nothing for the LLM to read in those, so callers skip it.
+    pub fn get_staged_binary_only_files() -> Result<Option<Vec<String>>> {
+        let output = Command::new("git")
+            .args(["diff", "--cached", "--numstat"])
+            .output()
+            .context("Failed to execute git diff --numstat")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow::anyhow!("git diff failed: {}", stderr));
+        }
+
+        // Binary files are listed as "-\t-\t<path>"
+        let mut binary_files = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            match line.strip_prefix("-\t-\t") {
+                Some(path) => binary_files.push(path.to_string()),
+                None => return Ok(None),
+            }
+        }
+
+        Ok((!binary_files.is_empty()).then_some(binary_files))
+    }
+
     /// Format the raw git diff into a more LLM-friendly format
     fn format_diff_for_llm(raw_diff: &str) -> String {
         let mut formatted = String::new();
@@ -98,6 +123,8 @@ impl Git {
                     current_file = file_part.trim_start_matches("b/").to_string();
                 }
                 changes.clear();
+            } else if line.starts_with("Binary files ") {
+                changes.push("  Binary file changed".to_string());
             } else if line.starts_with("+++") || line.starts_with("---") {
                 // Skip these lines
                 continue;
@@ -0,0 +1,296 @@
+/*
+`helix grep` - search tracked content without touching the working tree.
+
+  helix grep <pattern>                 blobs staged in the index
+  helix grep <pattern> <rev>           blobs in <rev>'s tree
+  helix grep <pattern> ... -- <paths>  only files at or under <paths>
+
+Blobs are read from the object store, so any historical version can be
+searched without checking it out. The pattern is a regular expression
+(`regex` crate syntax) unless --fixed-strings is given. Files are searched in
+parallel and reported sorted by path, each followed by its matching lines:
+
+  src/main.rs
+  12:fn main() {
+  40:    main_loop();
+
+Binary blobs only report that they match, unless --text is given.
+*/
+use anyhow::{Context, Result};
+use console::style;
+use helix_protocol::hash::Hash;
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use rayon::prelude::*;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::binary::is_binary;
+use crate::diff_command::resolve_revision;
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::commit::CommitStore;
+use crate::helix_index::format::EntryFlags;
+use crate::helix_index::tree::TreeStore;
+use crate::sandbox_command::RepoContext;
+
+#[derive(Debug, Clone, Default)]
+pub struct GrepOptions {
+    pub ignore_case: bool,
+    /// Treat the pattern as a literal string
+    pub fixed_strings: bool,
+    /// Only match whole words
+    pub word: bool,
+    /// Print only the names of matching files
+    pub files_with_matches: bool,
+    /// Print the number of matching lines per file
+    pub count: bool,
+    /// Search binary files as text
+    pub text: bool,
+    /// Only search files at or under these repository-relative paths
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// 1-based
+    pub number: usize,
+    pub line: String,
+    /// Byte ranges of every match within `line`
+    pub ranges: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    /// Binary blob that matched; `lines` is empty
+    pub binary: bool,
+    pub lines: Vec<LineMatch>,
+}
+
+/// Search the index, or `rev`'s tree when given, for `pattern`
+pub fn grep(
+    repo_path: &Path,
+    pattern: &str,
+    rev: Option<&str>,
+    options: &GrepOptions,
+) -> Result<Vec<FileMatches>> {
+    let context = RepoContext::detect(repo_path)?;
+    let repo_root = &context.repo_root;
+    let store = FsObjectStore::new(repo_root);
+    let regex = build_regex(pattern, options)?;
+
+    let files: HashMap<PathBuf, Hash> = match rev {
+        Some(rev) => {
+            let commit_hash = resolve_revision(repo_root, rev)?;
+            let commit = CommitStore::new(repo_root, store.clone())?.read_commit(&commit_hash)?;
+            TreeStore::new(store.clone()).collect_all_files(&commit.tree_hash)?
+        }
+        None => HelixIndexData::load_from_path(&context.index_path, repo_root)?
+            .entries()
+            .iter()
+            .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+            .map(|e| (e.path.clone(), e.oid))
+            .collect(),
+    };
+
+    let selected =
+        |path: &Path| options.paths.is_empty() || options.paths.iter().any(|p| path.starts_with(p));
+    let files: Vec<(PathBuf, Hash)> = files.into_iter().filter(|(p, _)| selected(p)).collect();
+
+    let mut results: Vec<FileMatches> = files
+        .par_iter()
+        .map(|(path, hash)| {
+            let content = store
+                .read_object(&ObjectType::Blob, hash)
+                .with_context(|| format!("Failed to read blob for {}", path.display()))?;
+            Ok(search_blob(path, &content, &regex, options.text))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(results)
+}
+
+fn build_regex(pattern: &str, options: &GrepOptions) -> Result<Regex> {
+    let mut pattern = if options.fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    if options.word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .build()
+        .with_context(|| format!("Invalid pattern '{}'", pattern))
+}
+
+/// Matches in one blob, or None when it has none
+fn search_blob(path: &Path, content: &[u8], regex: &Regex, text: bool) -> Option<FileMatches> {
+    let content = String::from_utf8_lossy(content);
+
+    if !text && is_binary(content.as_bytes()) {
+        return regex.is_match(&content).then(|| FileMatches {
+            path: path.to_path_buf(),
+            binary: true,
+            lines: Vec::new(),
+        });
+    }
+
+    let lines: Vec<LineMatch> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let ranges: Vec<(usize, usize)> = regex
+                .find_iter(line)
+                .filter(|m| !m.is_empty())
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            // An empty match (e.g. `^`) still selects the line
+            (!ranges.is_empty() || regex.is_match(line)).then(|| LineMatch {
+                number: i + 1,
+                line: line.to_string(),
+                ranges,
+            })
+        })
+        .collect();
+
+    (!lines.is_empty()).then(|| FileMatches {
+        path: path.to_path_buf(),
+        binary: false,
+        lines,
+    })
+}
+
+/// Print results grouped by file, colored when stdout is a terminal
+pub fn print_matches(results: &[FileMatches], options: &GrepOptions) {
+    for (i, file) in results.iter().enumerate() {
+        let path = file.path.display().to_string();
+
+        if options.files_with_matches {
+            println!("{}", style(path).magenta());
+            continue;
+        }
+        if options.count {
+            println!("{}:{}", style(path).magenta(), file.lines.len());
+            continue;
+        }
+        if file.binary {
+            println!("Binary file {} matches", path);
+            continue;
+        }
+
+        if i > 0 {
+            println!();
+        }
+        println!("{}", style(path).magenta().bold());
+        for line in &file.lines {
+            println!(
+                "{}:{}",
+                style(line.number).green(),
+                highlight(&line.line, &line.ranges)
+            );
+        }
+    }
+}
+
+fn highlight(line: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for &(start, end) in ranges {
+        out.push_str(&line[pos..start]);
+        out.push_str(&style(&line[start..end]).red().bold().to_string());
+        pos = end;
+    }
+    out.push_str(&line[pos..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit_command::{commit, CommitOptions};
+    use crate::helix_index::format::Entry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn stage(repo: &Path, files: &[(&str, &[u8])]) -> Result<()> {
+        let store = FsObjectStore::new(repo);
+        let mut index = HelixIndexData::load_or_rebuild(repo)?;
+        for (name, content) in files {
+            fs::write(repo.join(name), content)?;
+            let oid = store.write_object(&ObjectType::Blob, content)?;
+            index.entries_mut().retain(|e| e.path != Path::new(name));
+            let mut entry = Entry::new(PathBuf::from(name), content.len() as u64, 0, oid, 0o100644);
+            entry.flags = EntryFlags::TRACKED | EntryFlags::STAGED;
+            index.entries_mut().push(entry);
+        }
+        index.persist()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_index_and_history() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        stage(
+            repo,
+            &[
+                ("a.rs", b"fn main() {\n    todo!()\n}\n"),
+                ("b.txt", b"nothing here\nTODO: later\n"),
+                ("c.bin", b"\0\x01todo\0"),
+            ],
+        )?;
+        let first = commit(
+            repo,
+            CommitOptions {
+                message: "first".into(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )?;
+        stage(repo, &[("a.rs", b"fn main() {}\n")])?;
+
+        let options = GrepOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let now = grep(repo, "todo", None, &options)?;
+        let paths: Vec<_> = now.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("b.txt"), PathBuf::from("c.bin")]);
+        assert_eq!(now[0].lines[0].number, 2);
+        assert_eq!(now[0].lines[0].ranges, vec![(0, 4)]);
+        assert!(now[1].binary);
+
+        // The committed version of a.rs still has the match
+        let then = grep(
+            repo,
+            "todo!()",
+            Some(&hex::encode(first)),
+            &GrepOptions {
+                fixed_strings: true,
+                paths: vec![PathBuf::from("a.rs")],
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(then.len(), 1);
+        assert_eq!(then[0].lines[0].line, "    todo!()");
+
+        // Whole words only: "here" matches, "her" does not
+        let word = GrepOptions {
+            word: true,
+            ..Default::default()
+        };
+        assert_eq!(grep(repo, "here", None, &word)?.len(), 1);
+        assert!(grep(repo, "her", None, &word)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -1,10 +1,14 @@
 use crate::helix_index::Writer;
 
-use super::format::Header;
-use super::format::{Entry, EntryFlags};
-use super::reader::{HelixIndex, Reader};
+use super::format::{self, Entry, EntryFlags};
+use super::format::{Extensions, Footer, Header, UntrackedCache, FOOTER_SIZE};
+use super::journal::{Journal, JournalRecord};
+use super::lock::IndexLock;
+use super::migrate;
+use super::reader::{self, HelixIndex, Reader};
 use super::sync::SyncEngine;
 use super::verify::{Verifier, VerifyResult};
+use crate::path_policy::PathPolicy;
 use anyhow::{Context, Result};
 use helix_protocol::hash;
 use rayon::prelude::*;
@@ -16,9 +20,25 @@ pub struct HelixIndexData {
     repo_path: PathBuf,
     index_path: PathBuf,
     data: HelixIndex,
+    path_policy: PathPolicy,
+    /// Generation on disk when this was read or last persisted; None if there was no index
+    disk_generation: Option<u64>,
+    /// Held from before the read until this is dropped, by `load_for_update`
+    lock: Option<IndexLock>,
 }
 
 impl HelixIndexData {
+    fn loaded(repo_path: &Path, index_path: impl Into<PathBuf>, data: HelixIndex) -> Self {
+        Self {
+            repo_path: repo_path.to_path_buf(),
+            path_policy: PathPolicy::load(repo_path),
+            index_path: index_path.into(),
+            disk_generation: Some(data.header.generation),
+            data,
+            lock: None,
+        }
+    }
+
     /// Verify the current state of the Helix Index. If it is in a valid state, then load the index.
     /// If it is in an invalid state then rebuild it and load it.
     pub fn load_or_rebuild(repo_path: &Path) -> Result<Self> {
@@ -31,11 +51,7 @@ impl HelixIndexData {
                 let reader = Reader::new(repo_path);
                 let data = reader.read()?;
 
-                Ok(Self {
-                    repo_path: repo_path.to_path_buf(),
-                    index_path: index_path.to_path_buf(),
-                    data,
-                })
+                Ok(Self::loaded(repo_path, index_path.to_path_buf(), data))
             }
             VerifyResult::Missing => {
                 eprintln!("Building helix.idx for the first time...");
@@ -46,6 +62,16 @@ impl HelixIndexData {
                 Self::rebuild_helix_index(repo_path)
             }
             VerifyResult::Corrupted => {
+                let reader = Reader::new(repo_path);
+                if let Ok(data) = reader.read_backup() {
+                    eprintln!(
+                        "helix.idx is corrupted, restoring generation {} from backup...",
+                        data.header.generation
+                    );
+                    Writer::new_canonical(repo_path).restore_backup()?;
+                    return Ok(Self::loaded(repo_path, index_path, data));
+                }
+
                 eprintln!("helix.idx is corrupted, rebuilding...");
                 Self::rebuild_helix_index(repo_path)
             }
@@ -63,48 +89,89 @@ impl HelixIndexData {
         let reader = Reader::new(repo_path);
         let data = reader.read()?;
 
-        Ok(Self {
-            repo_path: repo_path.to_path_buf(),
-            index_path,
-            data,
-        })
+        Ok(Self::loaded(repo_path, index_path, data))
     }
 
     pub fn load_from_path(index_path: &Path, repo_path: &Path) -> Result<Self> {
         if !index_path.exists() {
             // Return empty index if not found
+            let data = HelixIndex {
+                header: Header::new(1, 0),
+                entries: Vec::new(),
+                extensions: Extensions::default(),
+            };
             return Ok(Self {
-                repo_path: repo_path.to_path_buf(),
-                index_path: index_path.to_path_buf(),
-                data: HelixIndex {
-                    header: Header::new(1, 0),
-                    entries: Vec::new(),
-                },
+                disk_generation: None,
+                ..Self::loaded(repo_path, index_path, data)
             });
         }
 
+        // Upgrade an index written by an older helix; it lives at <root>/.helix/helix.idx
+        if let Some(root) = index_path.parent().and_then(|p| p.parent()) {
+            migrate::migrate_in_place(root)?;
+        }
+
         // Read file content
         let content = fs::read(index_path)
             .with_context(|| format!("Failed to read index at {}", index_path.display()))?;
 
         // Reuse the Reader's parse logic
         let reader = Reader::new(repo_path);
-        let data = reader.parse(&content)?;
+        let mut data = reader.parse(&content)?;
 
-        Ok(Self {
-            repo_path: repo_path.to_path_buf(),
-            index_path: index_path.to_path_buf(),
-            data,
-        })
+        // Merge split-index updates stored next to this index
+        let footer = Footer::from_bytes(&content[content.len() - FOOTER_SIZE..])?;
+        Journal::for_index(index_path).apply(&mut data, &footer.checksum)?;
+
+        Ok(Self::loaded(repo_path, index_path.to_path_buf(), data))
+    }
+
+    /// Like `load_from_path`, for a command that is going to change the index: the index
+    /// lock is taken before reading and held until this is dropped, so no other helix
+    /// process can write the index in between and have its changes overwritten.
+    pub fn load_for_update(index_path: &Path, repo_path: &Path) -> Result<Self> {
+        let root = index_path
+            .parent()
+            .and_then(|p| p.parent())
+            .ok_or_else(|| anyhow::anyhow!("Invalid index path"))?;
+        let lock = IndexLock::acquire(root)?;
+        let mut index = Self::load_from_path(index_path, repo_path)?;
+        index.lock = Some(lock);
+        Ok(index)
     }
+
     /// Reload the helix index from disk
     /// Use this after operations that modify .helix/helix.idx (like helix add, helix commit)
     pub fn reload(&mut self) -> Result<()> {
         let reader = Reader::new(&self.repo_path);
         self.data = reader.read()?;
+        self.disk_generation = Some(self.data.header.generation);
         Ok(())
     }
 
+    /// The index lock for a write. An index read without `load_for_update` is only
+    /// written if no one else wrote it since; otherwise their changes would be lost.
+    fn lock_for_write(&self) -> Result<Option<IndexLock>> {
+        if self.lock.is_some() {
+            return Ok(None);
+        }
+        let lock = IndexLock::acquire(self.index_root()?)?;
+        // An index that no longer reads has no changes worth keeping; writing replaces it
+        let Ok(on_disk) = Self::load_from_path(&self.index_path, &self.repo_path) else {
+            return Ok(Some(lock));
+        };
+        let on_disk = on_disk.disk_generation;
+        if on_disk != self.disk_generation {
+            anyhow::bail!(
+                "helix.idx was changed by another helix process after it was read \
+                 (generation {} is now {}); run the command again",
+                self.disk_generation.unwrap_or(0),
+                on_disk.unwrap_or(0)
+            );
+        }
+        Ok(Some(lock))
+    }
+
     /// Persist changes to disk
     ///
     /// This writes the index to .helix/helix.idx with:
@@ -112,23 +179,94 @@ impl HelixIndexData {
     /// - Updated entry count
     /// - Computed checksum
     /// - fsync for durability
+    ///
+    /// The write happens under the index lock. An index read with `load_for_update`
+    /// already holds it; any other fails here if another process wrote the index since
+    /// it was read, rather than dropping that process's changes.
     pub fn persist(&mut self) -> Result<()> {
+        let _lock = self.lock_for_write()?;
         self.data.header.generation += 1;
         self.data.header.entry_count = self.data.entries.len() as u32;
+        format::sort_entries(&mut self.data.entries);
 
-        // Derive root from index_path: /path/.helix/helix.idx -> /path
-        let root = self
-            .index_path
-            .parent() // .helix/
-            .and_then(|p| p.parent()) // /path
-            .ok_or_else(|| anyhow::anyhow!("Invalid index path"))?;
-
+        let root = self.index_root()?;
         let writer = Writer::new_canonical(root);
-        writer.write(&self.data.header, &self.data.entries)?;
+        writer.write_with_extensions(
+            &self.data.header,
+            &self.data.entries,
+            &self.data.extensions,
+        )?;
+        self.disk_generation = Some(self.data.header.generation);
+
+        Ok(())
+    }
+
+    /// Untracked scan results saved by the last `persist`, if any
+    pub fn untracked_cache(&self) -> Option<&UntrackedCache> {
+        self.data.extensions.untracked_cache.as_ref()
+    }
+
+    /// Replace the untracked cache; it is written by the next `persist`
+    pub fn set_untracked_cache(&mut self, cache: Option<UntrackedCache>) {
+        self.data.extensions.untracked_cache = cache;
+    }
+
+    /// Persist only the entries for `paths` by appending them to the split-index journal.
+    ///
+    /// Paths that no longer have an entry are journaled as removals. Falls back to a full
+    /// `persist` (which compacts the journal) when there is no base index yet or the
+    /// journal has grown past half the size of the base.
+    pub fn persist_paths(&mut self, paths: &[PathBuf]) -> Result<()> {
+        let _lock = self.lock_for_write()?;
+        let journal = Journal::for_index(&self.index_path);
+        let base_size = fs::metadata(&self.index_path).map(|m| m.len()).unwrap_or(0);
+
+        if base_size == 0 || journal.size() > base_size / 2 {
+            return self.persist();
+        }
+
+        let wanted: HashSet<&Path> = paths.iter().map(|p| p.as_path()).collect();
+        let mut records: Vec<JournalRecord> = self
+            .data
+            .entries
+            .iter()
+            .filter(|e| wanted.contains(e.path.as_path()))
+            .map(|e| JournalRecord::Upsert(e.clone()))
+            .collect();
+
+        let present: HashSet<&Path> = records
+            .iter()
+            .filter_map(|r| match r {
+                JournalRecord::Upsert(e) => Some(e.path.as_path()),
+                JournalRecord::Remove(_) => None,
+            })
+            .collect();
+        let removed: Vec<PathBuf> = paths
+            .iter()
+            .filter(|p| !present.contains(p.as_path()))
+            .cloned()
+            .collect();
+        records.extend(removed.into_iter().map(JournalRecord::Remove));
+
+        self.data.header.generation += 1;
+        self.data.header.entry_count = self.data.entries.len() as u32;
+        format::sort_entries(&mut self.data.entries);
+
+        let writer = Writer::new_canonical(self.index_root()?);
+        writer.append_journal(&records, self.data.header.generation)?;
+        self.disk_generation = Some(self.data.header.generation);
 
         Ok(())
     }
 
+    /// Derive root from index_path: /path/.helix/helix.idx -> /path
+    fn index_root(&self) -> Result<&Path> {
+        self.index_path
+            .parent() // .helix/
+            .and_then(|p| p.parent()) // /path
+            .ok_or_else(|| anyhow::anyhow!("Invalid index path"))
+    }
+
     /// Apply working tree changes to EntryFlags based on dirty paths from FSMonitor.
     /// dirty paths have some sort of change at the path
     ///
@@ -140,14 +278,15 @@ impl HelixIndexData {
     /// It does NOT touch:
     /// - TRACKED / STAGED (those come from SyncEngine using .git/index + HEAD during import)
     pub fn apply_worktree_changes(&mut self, dirty_paths: &[PathBuf]) -> Result<()> {
-        // Build map of tracked paths -> entry index
+        // Build map of tracked paths -> entry index, keyed the way the filesystem compares names
+        let policy = self.path_policy;
         let index_by_path: HashMap<PathBuf, usize> = if self.data.entries.len() > 1000 {
             self.data
                 .entries
                 .par_iter()
                 .enumerate()
                 .filter(|(_, e)| e.flags.contains(EntryFlags::TRACKED))
-                .map(|(i, e)| (e.path.clone(), i))
+                .map(|(i, e)| (policy.key(&e.path), i))
                 .collect()
         } else {
             self.data
@@ -155,7 +294,7 @@ impl HelixIndexData {
                 .iter()
                 .enumerate()
                 .filter(|(_, e)| e.flags.contains(EntryFlags::TRACKED))
-                .map(|(i, e)| (e.path.clone(), i))
+                .map(|(i, e)| (policy.key(&e.path), i))
                 .collect()
         };
 
@@ -163,7 +302,7 @@ impl HelixIndexData {
             let full_path = self.repo_path.join(rel_path);
             let exists = full_path.exists();
 
-            if let Some(&idx) = index_by_path.get(rel_path) {
+            if let Some(&idx) = index_by_path.get(&policy.key(rel_path)) {
                 // Tracked file: adjust MODIFIED / DELETED bits on a single indexed entry
                 let entry = &mut self.data.entries[idx];
 
@@ -200,16 +339,12 @@ impl HelixIndexData {
 
     pub fn stage_file(&mut self, path: &Path) -> Result<()> {
         // Find the entry
-        let entry = self
-            .entries_mut()
-            .iter_mut()
-            .find(|e| e.path == path)
-            .ok_or_else(|| {
-                anyhow::anyhow!(
-                    "Cannot stage '{}': file is not tracked. Use 'helix add' to track it first.",
-                    path.display()
-                )
-            })?;
+        let entry = self.entry_mut(path).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Cannot stage '{}': file is not tracked. Use 'helix add' to track it first.",
+                path.display()
+            )
+        })?;
 
         // Add STAGED flag
         entry.flags.insert(EntryFlags::STAGED);
@@ -233,13 +368,9 @@ impl HelixIndexData {
 
     pub fn unstage_file(&mut self, path: &Path) -> Result<()> {
         // Find the entry
-        let entry = self
-            .entries_mut()
-            .iter_mut()
-            .find(|e| e.path == path)
-            .ok_or_else(|| {
-                anyhow::anyhow!("Cannot unstage '{}': file is not tracked.", path.display())
-            })?;
+        let entry = self.entry_mut(path).ok_or_else(|| {
+            anyhow::anyhow!("Cannot unstage '{}': file is not tracked.", path.display())
+        })?;
 
         // Remove STAGED flag
         entry.flags.remove(EntryFlags::STAGED);
@@ -367,19 +498,9 @@ impl HelixIndexData {
 
     /// Check if a file is staged
     pub fn is_staged(&self, path: &Path) -> bool {
-        if self.data.entries.len() > 1000 {
-            self.data.entries.par_iter().any(|e| {
-                e.path == path
-                    && e.flags.contains(EntryFlags::STAGED)
-                    && e.flags.contains(EntryFlags::TRACKED)
-            })
-        } else {
-            self.data.entries.iter().any(|e| {
-                e.path == path
-                    && e.flags.contains(EntryFlags::STAGED)
-                    && e.flags.contains(EntryFlags::TRACKED)
-            })
-        }
+        self.entry(path).is_some_and(|e| {
+            e.flags.contains(EntryFlags::STAGED) && e.flags.contains(EntryFlags::TRACKED)
+        })
     }
 
     /// Returns unstaged files
@@ -460,17 +581,36 @@ impl HelixIndexData {
     }
 
     pub fn is_tracked(&self, path: &Path) -> bool {
-        if self.data.entries.len() > 1000 {
-            self.data
-                .entries
-                .par_iter()
-                .any(|e| e.path == path && e.flags.contains(EntryFlags::TRACKED))
-        } else {
-            self.data
-                .entries
-                .iter()
-                .any(|e| e.path == path && e.flags.contains(EntryFlags::TRACKED))
+        self.tracked_path(path).is_some()
+    }
+
+    /// Find the tracked entry path that names the same file as `path` on this
+    /// filesystem. On case-insensitive or normalizing filesystems this may be
+    /// spelled differently from `path`.
+    pub fn tracked_path(&self, path: &Path) -> Option<&Path> {
+        let policy = &self.path_policy;
+        if policy.is_exact() {
+            return self
+                .entry(path)
+                .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+                .map(|e| e.path.as_path());
         }
+
+        let matches =
+            |e: &&Entry| e.flags.contains(EntryFlags::TRACKED) && policy.same_path(&e.path, path);
+
+        let found = if self.data.entries.len() > 1000 {
+            self.data.entries.par_iter().find_any(matches)
+        } else {
+            self.data.entries.iter().find(matches)
+        };
+
+        found.map(|e| e.path.as_path())
+    }
+
+    /// Filesystem path comparison rules recorded for this repo
+    pub fn path_policy(&self) -> PathPolicy {
+        self.path_policy
     }
 
     /// Get all entries (for debugging)
@@ -478,34 +618,61 @@ impl HelixIndexData {
         &self.data.entries
     }
 
+    /// Entries are kept sorted by path; new ones should go through `upsert_entry`
     pub fn entries_mut(&mut self) -> &mut Vec<Entry> {
         &mut self.data.entries
     }
 
+    /// The entry for exactly `path`
+    pub fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.data.get(path)
+    }
+
+    pub fn entry_mut(&mut self, path: &Path) -> Option<&mut Entry> {
+        self.data.get_mut(path)
+    }
+
+    /// Entries at or below the directory `dir`, in path order
+    pub fn entries_under(&self, dir: &Path) -> &[Entry] {
+        self.data.entries_under(dir)
+    }
+
+    /// Replace the entry for `entry.path`, or insert it in path order
+    pub fn upsert_entry(&mut self, entry: Entry) {
+        match reader::find(&self.data.entries, &entry.path) {
+            Ok(pos) => self.data.entries[pos] = entry,
+            Err(pos) => self.data.entries.insert(pos, entry),
+        }
+    }
+
     fn remove_entry_if_exists(&mut self, path: &Path) {
-        self.data.entries.retain(|e| e.path != path);
+        if let Ok(pos) = reader::find(&self.data.entries, path) {
+            self.data.entries.remove(pos);
+        }
     }
 
     fn ensure_untracked_entry(&mut self, path: &Path) -> &mut Entry {
-        // see if an entry already exists
-        if let Some(pos) = self.data.entries.iter().position(|e| e.path == path) {
-            return &mut self.data.entries[pos];
-        }
+        let pos = match reader::find(&self.data.entries, path) {
+            Ok(pos) => return &mut self.data.entries[pos],
+            Err(pos) => pos,
+        };
 
-        self.data.entries.push(Entry {
-            path: path.to_path_buf(),
-            size: 0,
-            mtime_sec: 0,
-            mtime_nsec: 0,
-            flags: EntryFlags::empty(),
-            oid: hash::ZERO_HASH,
-            merge_conflict_stage: 0,
-            file_mode: 0o100644,
-            reserved: [0; 33],
-        });
+        self.data.entries.insert(
+            pos,
+            Entry {
+                path: path.to_path_buf(),
+                size: 0,
+                mtime_sec: 0,
+                mtime_nsec: 0,
+                flags: EntryFlags::empty(),
+                oid: hash::ZERO_HASH,
+                merge_conflict_stage: 0,
+                file_mode: 0o100644,
+                reserved: [0; 33],
+            },
+        );
 
-        let len = self.data.entries.len();
-        &mut self.data.entries[len - 1]
+        &mut self.data.entries[pos]
     }
 }
 
@@ -771,6 +938,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_concurrent_updates_are_not_lost() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        init_test_repo(repo_path)?;
+        HelixIndexData::load_or_rebuild(repo_path)?.persist()?;
+        let index_path = repo_path.join(".helix/helix.idx");
+
+        // Two writers each add their own entries, read-modify-write every time
+        std::thread::scope(|scope| {
+            for writer in ["add", "fsmonitor"] {
+                let index_path = &index_path;
+                scope.spawn(move || -> Result<()> {
+                    for i in 0..10 {
+                        let path = PathBuf::from(format!("{writer}-{i}.txt"));
+                        let mut index = HelixIndexData::load_for_update(index_path, repo_path)?;
+                        index.entries_mut().push(create_test_entry(
+                            path.to_str().unwrap(),
+                            EntryFlags::TRACKED,
+                        ));
+                        index.persist_paths(&[path])?;
+                    }
+                    Ok(())
+                });
+            }
+        });
+
+        let index = HelixIndexData::load_from_path(&index_path, repo_path)?;
+        for writer in ["add", "fsmonitor"] {
+            for i in 0..10 {
+                assert!(index.is_tracked(Path::new(&format!("{writer}-{i}.txt"))));
+            }
+        }
+
+        // Without the lock held, a write over someone else's is refused, not lost
+        let mut stale = HelixIndexData::load_from_path(&index_path, repo_path)?;
+        let mut fresh = HelixIndexData::load_from_path(&index_path, repo_path)?;
+        fresh.entries_mut().clear();
+        fresh.persist()?;
+        let err = stale.persist().unwrap_err();
+        assert!(err.to_string().contains("another helix process"), "{err}");
+
+        Ok(())
+    }
+
     #[test]
     fn test_persist_increments_generation() -> Result<()> {
         let temp_dir = TempDir::new()?;
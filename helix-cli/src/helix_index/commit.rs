@@ -4,12 +4,19 @@ use console::Color;
 use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, Hash};
 use helix_protocol::message::ObjectType;
 use helix_protocol::storage::FsObjectStore;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::helix_index::rename::{detect_renames, DEFAULT_RENAME_THRESHOLD};
 use crate::helix_index::tree::TreeStore;
 
+/// Git commit header that carries one metadata entry, as `<key>=<value>`, through
+/// `helix export` and back in on import
+pub const GIT_METADATA_HEADER: &str = "helix-metadata";
+
 /// Commit - represents a snapshot in history
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Commit {
@@ -20,6 +27,9 @@ pub struct Commit {
     pub author_time: u64,   // Author timestamp (seconds since Unix epoch)
     pub commit_time: u64,   // Committer timestamp (seconds since Unix epoch)
     pub message: String,    // Commit message
+    /// Extra key-value metadata (build IDs, review IDs, ...), part of the hash. Sorted by
+    /// key so the same headers always serialize the same way.
+    pub headers: BTreeMap<String, String>,
 }
 
 impl Commit {
@@ -38,6 +48,7 @@ impl Commit {
             author_time: now,
             commit_time: now,
             message,
+            headers: BTreeMap::new(),
         };
 
         // Compute hash from content (excluding hash field)
@@ -45,6 +56,20 @@ impl Commit {
         commit
     }
 
+    /// Set the author timestamp, e.g. to keep it across an amend
+    pub fn with_author_time(mut self, author_time: u64) -> Self {
+        self.author_time = author_time;
+        self.commit_hash = self.compute_hash();
+        self
+    }
+
+    /// Attach metadata headers, replacing any the commit had
+    pub fn with_headers(mut self, headers: BTreeMap<String, String>) -> Self {
+        self.headers = headers;
+        self.commit_hash = self.compute_hash();
+        self
+    }
+
     /// Compute hash from commit content
     pub fn compute_hash(&self) -> Hash {
         let bytes = self.to_bytes_without_hash();
@@ -83,6 +108,18 @@ impl Commit {
         // Message (variable)
         bytes.extend_from_slice(self.message.as_bytes());
 
+        // Headers, only when there are any, so commits without them hash as they always did:
+        // count (2 bytes), then key length (2 bytes), key, value length (4 bytes), value
+        if !self.headers.is_empty() {
+            bytes.extend_from_slice(&(self.headers.len() as u16).to_le_bytes());
+            for (key, value) in &self.headers {
+                bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(key.as_bytes());
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(value.as_bytes());
+            }
+        }
+
         bytes
     }
 
@@ -198,6 +235,9 @@ impl Commit {
             anyhow::bail!("Commit ended unexpectedly while reading message");
         }
         let message = String::from_utf8(bytes[offset..offset + message_len].to_vec())?;
+        offset += message_len;
+
+        let headers = read_headers(&bytes[offset..])?;
 
         // Create commit and compute hash
         let mut commit = Self {
@@ -208,6 +248,7 @@ impl Commit {
             author_time,
             commit_time,
             message,
+            headers,
         };
 
         // Compute hash from the content
@@ -264,16 +305,51 @@ impl Commit {
         let hash_hex = hash_to_hex(hash);
         let short_hash = &hash_hex[..8];
 
+        let metadata: String = self
+            .headers
+            .iter()
+            .map(|(key, value)| format!("Meta:   {key}={value}\n"))
+            .collect();
         format!(
-            "commit {}\nAuthor: {}\nDate:   {}\n\n    {}",
+            "commit {}\nAuthor: {}\nDate:   {}\n{}\n    {}",
             short_hash,
             self.author,
             format_timestamp(self.author_time),
+            metadata,
             self.message.lines().collect::<Vec<_>>().join("\n    ")
         )
     }
 }
 
+/// The headers section after a commit's message; empty for commits that have none
+fn read_headers(mut bytes: &[u8]) -> Result<BTreeMap<String, String>> {
+    let mut headers = BTreeMap::new();
+    if bytes.is_empty() {
+        return Ok(headers);
+    }
+
+    let mut take = |len: usize, what: &str| -> Result<&[u8]> {
+        if bytes.len() < len {
+            anyhow::bail!("Commit ended unexpectedly while reading {what}");
+        }
+        let (head, rest) = bytes.split_at(len);
+        bytes = rest;
+        Ok(head)
+    };
+    let count = u16::from_le_bytes(take(2, "header count")?.try_into()?);
+    for _ in 0..count {
+        let key_len = u16::from_le_bytes(take(2, "header key length")?.try_into()?) as usize;
+        let key = String::from_utf8(take(key_len, "header key")?.to_vec())?;
+        let value_len = u32::from_le_bytes(take(4, "header value length")?.try_into()?) as usize;
+        let value = String::from_utf8(take(value_len, "header value")?.to_vec())?;
+        headers.insert(key, value);
+    }
+    if !bytes.is_empty() {
+        anyhow::bail!("Commit has {} unexpected trailing bytes", bytes.len());
+    }
+    Ok(headers)
+}
+
 /// Format Unix timestamp as a human-readable UTC string
 pub fn format_timestamp(timestamp: u64) -> String {
     let datetime = DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default();
@@ -300,12 +376,43 @@ impl CommitStore {
         })
     }
 
-    /// Write a commit to storage
+    /// Write a commit to storage. Its tree and parents must already be stored, so a crash
+    /// part way through writing objects can't leave a commit pointing at nothing.
     pub fn write_commit(&self, commit: &Commit) -> Result<Hash> {
+        self.check_references(commit)?;
+        self.write_commit_unchecked(commit)
+    }
+
+    /// Write a commit without checking its tree and parents exist, for recovering a
+    /// repository whose objects are already missing
+    pub fn write_commit_unchecked(&self, commit: &Commit) -> Result<Hash> {
         let bytes = commit.to_bytes();
         self.objects.write_object(&ObjectType::Commit, &bytes)
     }
 
+    /// Fail if the commit's tree or any of its parents isn't stored
+    pub fn check_references(&self, commit: &Commit) -> Result<()> {
+        let commit_hex = &hash_to_hex(&commit.commit_hash)[..8];
+        if !self
+            .objects
+            .has_object(&ObjectType::Tree, &commit.tree_hash)
+        {
+            anyhow::bail!(
+                "Commit {} references tree {} which is not stored",
+                commit_hex,
+                hash_to_hex(&commit.tree_hash)
+            );
+        }
+        if let Some(parent) = commit.parents.iter().find(|p| !self.commit_exists(p)) {
+            anyhow::bail!(
+                "Commit {} references parent {} which is not stored",
+                commit_hex,
+                hash_to_hex(parent)
+            );
+        }
+        Ok(())
+    }
+
     /// Read a commit from storage
     pub fn read_commit(&self, hash: &Hash) -> Result<Commit> {
         let bytes = self.objects.read_object(&ObjectType::Commit, hash)?;
@@ -324,9 +431,57 @@ impl CommitStore {
 
     /// Write multiple commits in parallel
     pub fn write_commits_batch(&self, commits: &[Commit]) -> Result<Vec<Hash>> {
-        let bytes: Vec<Vec<u8>> = commits.iter().map(|c| c.to_bytes()).collect();
-        self.objects
-            .write_objects_batch(&ObjectType::Commit, &bytes)
+        self.write_commits_in_order(commits, false, || {})
+    }
+
+    /// Write a batch of commits parents-first. Each wave holds the commits whose parents
+    /// within the batch were written by an earlier wave, and every commit's references are
+    /// checked before it's written (unless `allow_missing`), so stopping part way through
+    /// never leaves a stored commit whose tree or parents aren't. `on_written` runs once
+    /// per stored commit, e.g. to advance a progress bar.
+    pub fn write_commits_in_order(
+        &self,
+        commits: &[Commit],
+        allow_missing: bool,
+        on_written: impl Fn() + Sync,
+    ) -> Result<Vec<Hash>> {
+        let in_batch: HashSet<Hash> = commits.iter().map(|c| c.commit_hash).collect();
+        let mut written: HashSet<Hash> = HashSet::new();
+        let mut pending: Vec<&Commit> = commits.iter().collect();
+
+        while !pending.is_empty() {
+            let (ready, rest): (Vec<&Commit>, Vec<&Commit>) =
+                pending.into_iter().partition(|commit| {
+                    commit
+                        .parents
+                        .iter()
+                        .all(|p| !in_batch.contains(p) || written.contains(p))
+                });
+            if ready.is_empty() {
+                anyhow::bail!(
+                    "Commit {} is its own ancestor within the batch",
+                    &hash_to_hex(&rest[0].commit_hash)[..8]
+                );
+            }
+
+            ready.par_iter().try_for_each(|commit| {
+                if !allow_missing {
+                    self.check_references(commit)?;
+                }
+                self.objects.write_object_with_hash(
+                    &ObjectType::Commit,
+                    &commit.commit_hash,
+                    &commit.to_bytes(),
+                )?;
+                on_written();
+                Ok::<_, anyhow::Error>(())
+            })?;
+
+            written.extend(ready.iter().map(|c| c.commit_hash));
+            pending = rest;
+        }
+
+        Ok(commits.iter().map(|c| c.commit_hash).collect())
     }
 
     /// Read multiple commits in parallel
@@ -490,16 +645,16 @@ impl CommitStore {
         let tree_store = TreeStore::for_repo(&self.repo_path);
 
         // Get files in this commit's tree
-        let current_files = tree_store.collect_all_files(&commit.tree_hash)?;
-        let current_map: std::collections::HashMap<_, _> = current_files.into_iter().collect();
+        let current_map = tree_store.collect_all_files(&commit.tree_hash)?;
 
         // If initial commit, all files are "added"
         if commit.is_initial() {
             return Ok(current_map
-                .into_iter()
-                .map(|(path, _)| ChangedFile {
+                .into_keys()
+                .map(|path| ChangedFile {
                     path,
                     change_type: ChangeType::Added,
+                    old_path: None,
                 })
                 .collect());
         }
@@ -507,24 +662,21 @@ impl CommitStore {
         // Get parent tree
         let parent_hash = &commit.parents[0];
         let parent_commit = self.read_commit(parent_hash)?;
-        let parent_files = tree_store.collect_all_files(&parent_commit.tree_hash)?;
-        let parent_map: std::collections::HashMap<_, _> = parent_files.into_iter().collect();
+        let parent_map = tree_store.collect_all_files(&parent_commit.tree_hash)?;
 
         let mut changes = Vec::new();
+        let mut added = Vec::new();
+        let mut deleted = Vec::new();
 
         // Find added and modified files
         for (path, hash) in &current_map {
             match parent_map.get(path) {
-                None => {
-                    changes.push(ChangedFile {
-                        path: path.clone(),
-                        change_type: ChangeType::Added,
-                    });
-                }
+                None => added.push((path.clone(), *hash)),
                 Some(parent_hash) if parent_hash != hash => {
                     changes.push(ChangedFile {
                         path: path.clone(),
                         change_type: ChangeType::Modified,
+                        old_path: None,
                     });
                 }
                 _ => {} // Unchanged
@@ -532,26 +684,113 @@ impl CommitStore {
         }
 
         // Find deleted files
-        for (path, _) in &parent_map {
+        for (path, hash) in &parent_map {
             if !current_map.contains_key(path) {
+                deleted.push((path.clone(), *hash));
+            }
+        }
+
+        // A deleted file that reappears elsewhere is a rename
+        let renames = detect_renames(&deleted, &added, DEFAULT_RENAME_THRESHOLD, |hash| {
+            self.objects.read_object(&ObjectType::Blob, hash).ok()
+        });
+        let renamed_from: HashSet<&PathBuf> = renames.iter().map(|r| &r.from).collect();
+        let renamed_to: HashSet<&PathBuf> = renames.iter().map(|r| &r.to).collect();
+
+        for (path, _) in &added {
+            if !renamed_to.contains(path) {
+                changes.push(ChangedFile {
+                    path: path.clone(),
+                    change_type: ChangeType::Added,
+                    old_path: None,
+                });
+            }
+        }
+        for (path, _) in &deleted {
+            if !renamed_from.contains(path) {
                 changes.push(ChangedFile {
                     path: path.clone(),
                     change_type: ChangeType::Deleted,
+                    old_path: None,
                 });
             }
         }
+        for rename in &renames {
+            changes.push(ChangedFile {
+                path: rename.to.clone(),
+                change_type: ChangeType::Renamed,
+                old_path: Some(rename.from.clone()),
+            });
+        }
 
         // Sort by path for consistent display
         changes.sort_by(|a, b| a.path.cmp(&b.path));
 
         Ok(changes)
     }
+
+    /// Commits on HEAD's first-parent history that changed `path`, newest
+    /// first, each with the name the file had in that commit. With `follow`,
+    /// the walk continues under the old name when the file was renamed.
+    pub fn file_history(&self, path: &Path, follow: bool) -> Result<Vec<(Commit, PathBuf)>> {
+        let tree_store = TreeStore::for_repo(&self.repo_path);
+        let mut history = Vec::new();
+
+        let Ok(mut current_hash) = read_head(&self.repo_path) else {
+            return Ok(history);
+        };
+        let mut path = path.to_path_buf();
+        let mut visited = std::collections::HashSet::new();
+
+        while visited.insert(current_hash) {
+            let commit = self.read_commit(&current_hash)?;
+            let files = tree_store.collect_all_files(&commit.tree_hash)?;
+            let Some(blob) = files.get(&path) else {
+                break;
+            };
+
+            let Some(parent_hash) = commit.parents.first().copied() else {
+                history.push((commit, path));
+                break;
+            };
+            let parent = self.read_commit(&parent_hash)?;
+            let parent_files = tree_store.collect_all_files(&parent.tree_hash)?;
+
+            match parent_files.get(&path) {
+                Some(parent_blob) if parent_blob == blob => {}
+                Some(_) => history.push((commit, path.clone())),
+                None => {
+                    let renamed_from = if follow {
+                        self.get_changed_files(&commit)?
+                            .into_iter()
+                            .find(|f| f.change_type == ChangeType::Renamed && f.path == path)
+                            .and_then(|f| f.old_path)
+                    } else {
+                        None
+                    };
+
+                    history.push((commit, path));
+                    match renamed_from {
+                        Some(old_path) => path = old_path,
+                        // The file was created here
+                        None => break,
+                    }
+                }
+            }
+
+            current_hash = parent_hash;
+        }
+
+        Ok(history)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ChangedFile {
     pub path: PathBuf,
     pub change_type: ChangeType,
+    /// Previous path, for renames
+    pub old_path: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -559,6 +798,7 @@ pub enum ChangeType {
     Added,
     Modified,
     Deleted,
+    Renamed,
 }
 
 impl ChangeType {
@@ -567,6 +807,7 @@ impl ChangeType {
             ChangeType::Added => "+",
             ChangeType::Modified => "~",
             ChangeType::Deleted => "-",
+            ChangeType::Renamed => "→",
         }
     }
 
@@ -575,6 +816,7 @@ impl ChangeType {
             ChangeType::Added => Color::Green,
             ChangeType::Modified => Color::Yellow,
             ChangeType::Deleted => Color::Red,
+            ChangeType::Renamed => Color::Cyan,
         }
     }
 }
@@ -612,6 +854,7 @@ pub fn read_head(repo_path: &Path) -> Result<Hash> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helix_index::tree::TreeBuilder;
     use tempfile::TempDir;
 
     fn setup_test_repo(temp_dir: &TempDir) -> Result<(FsObjectStore, CommitStore)> {
@@ -672,6 +915,37 @@ mod tests {
         assert!(commit.is_merge());
     }
 
+    #[test]
+    fn test_commit_headers_roundtrip_and_change_the_hash() -> Result<()> {
+        let plain = Commit::initial([1u8; 32], "Ann <ann@example.com>".into(), "Build".into());
+        let headers = BTreeMap::from([
+            ("build-id".to_string(), "4711".to_string()),
+            (
+                "review".to_string(),
+                "https://review.example.com/42".to_string(),
+            ),
+        ]);
+        let tagged = plain.clone().with_headers(headers.clone());
+        assert_ne!(tagged.get_hash(), plain.get_hash());
+
+        let parsed = Commit::from_bytes(&tagged.to_bytes())?;
+        assert_eq!(parsed.headers, headers);
+        assert_eq!(parsed.get_hash(), tagged.get_hash());
+        assert!(parsed
+            .format(&parsed.commit_hash)
+            .contains("Meta:   build-id=4711\n"));
+
+        // Commits without headers serialize exactly as before they existed
+        assert_eq!(
+            Commit::from_bytes(&plain.to_bytes())?.headers,
+            BTreeMap::new()
+        );
+        let mut truncated = tagged.to_bytes();
+        truncated.pop();
+        assert!(Commit::from_bytes(&truncated).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_commit_hash_deterministic() {
         let commit1 = Commit {
@@ -682,6 +956,7 @@ mod tests {
             author_time: 1234567890,
             commit_time: 1234567890,
             message: "Test commit".to_string(),
+            headers: BTreeMap::new(),
         };
 
         let commit2 = commit1.clone();
@@ -693,9 +968,10 @@ mod tests {
     fn test_commit_storage_write_read() -> Result<()> {
         let temp_dir = TempDir::new()?;
         let (_, loader) = setup_test_repo(&temp_dir)?;
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
 
         let commit = Commit::initial(
-            [1u8; 32],
+            tree,
             "John Doe <john@example.com>".to_string(),
             "Initial commit".to_string(),
         );
@@ -713,9 +989,10 @@ mod tests {
     fn test_commit_storage_deduplication() -> Result<()> {
         let temp_dir = TempDir::new()?;
         let (_, loader) = setup_test_repo(&temp_dir)?;
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
 
         let commit = Commit::initial(
-            [1u8; 32],
+            tree,
             "John Doe <john@example.com>".to_string(),
             "Initial commit".to_string(),
         );
@@ -735,9 +1012,10 @@ mod tests {
     fn test_commit_storage_exists() -> Result<()> {
         let temp_dir = TempDir::new()?;
         let (_, loader) = setup_test_repo(&temp_dir)?;
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
 
         let commit = Commit::initial(
-            [1u8; 32],
+            tree,
             "John Doe <john@example.com>".to_string(),
             "Initial commit".to_string(),
         );
@@ -750,16 +1028,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_commit_requires_tree_and_parents() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (_, loader) = setup_test_repo(&temp_dir)?;
+        let author = "John Doe <john@example.com>".to_string();
+
+        let orphan = Commit::initial([1u8; 32], author.clone(), "No tree".into());
+        let err = loader.write_commit(&orphan).unwrap_err();
+        assert!(err.to_string().contains("references tree"), "{err}");
+        assert!(!loader.commit_exists(&orphan.get_hash()));
+
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
+        let dangling = Commit::with_parent(tree, [2u8; 32], author.clone(), "No parent".into());
+        let err = loader.write_commit(&dangling).unwrap_err();
+        assert!(err.to_string().contains("references parent"), "{err}");
+
+        // Recovery can still write it
+        let hash = loader.write_commit_unchecked(&dangling)?;
+        assert!(loader.commit_exists(&hash));
+
+        let child = Commit::with_parent(tree, hash, author, "Child".into());
+        loader.write_commit(&child)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_write_never_stores_a_commit_without_its_parents() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (_, loader) = setup_test_repo(&temp_dir)?;
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
+        let author = "John Doe <john@example.com>".to_string();
+
+        // A chain whose root points at a parent that was never imported
+        let root = Commit::with_parent(tree, [7u8; 32], author.clone(), "Root".into());
+        let middle = Commit::with_parent(tree, root.get_hash(), author.clone(), "Middle".into());
+        let tip = Commit::with_parent(tree, middle.get_hash(), author.clone(), "Tip".into());
+        let batch = vec![tip.clone(), middle.clone(), root.clone()];
+
+        let err = loader
+            .write_commits_in_order(&batch, false, || {})
+            .unwrap_err();
+        assert!(err.to_string().contains("references parent"), "{err}");
+        for commit in &batch {
+            assert!(!loader.commit_exists(&commit.get_hash()));
+        }
+
+        // Children listed before their parents are still written parents-first
+        let base = Commit::initial(tree, author.clone(), "Base".into());
+        let next = Commit::with_parent(tree, base.get_hash(), author, "Next".into());
+        let written = std::sync::atomic::AtomicUsize::new(0);
+        let hashes = loader.write_commits_in_order(&[next.clone(), base.clone()], false, || {
+            written.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        })?;
+        assert_eq!(hashes, vec![next.get_hash(), base.get_hash()]);
+        assert_eq!(written.into_inner(), 2);
+
+        // Recovery stores the dangling chain anyway
+        loader.write_commits_in_order(&batch, true, || {})?;
+        assert!(loader.commit_exists(&tip.get_hash()));
+        Ok(())
+    }
+
     #[test]
     fn test_commit_batch_operations() -> Result<()> {
         let temp_dir = TempDir::new()?;
         let (_, loader) = setup_test_repo(&temp_dir)?;
+        let tree = TreeBuilder::new(temp_dir.path()).build_from_entries(&[])?;
 
         // Create commits
         let commits: Vec<Commit> = (0..10)
             .map(|i| {
                 Commit::initial(
-                    hash_bytes(format!("tree{}", i).as_bytes()),
+                    tree,
                     format!("Author {} <author{}@example.com>", i, i),
                     format!("Commit {}", i),
                 )
@@ -811,4 +1152,78 @@ mod tests {
 
         Ok(())
     }
+
+    fn commit_files(
+        repo: &Path,
+        loader: &CommitStore,
+        parents: Vec<Hash>,
+        files: &[(&str, &str)],
+    ) -> Result<Hash> {
+        use crate::helix_index::format::Entry;
+
+        let store = FsObjectStore::new(repo);
+        let mut entries = Vec::new();
+        for (path, content) in files {
+            let oid = store.write_object(&ObjectType::Blob, content.as_bytes())?;
+            entries.push(Entry::new(
+                PathBuf::from(path),
+                content.len() as u64,
+                0,
+                oid,
+                0o100644,
+            ));
+        }
+        let tree = TreeBuilder::new(repo).build_from_entries(&entries)?;
+        let commit = Commit::new(tree, parents, "Test <test@test.com>".into(), "msg".into());
+        loader.write_commit(&commit)
+    }
+
+    #[test]
+    fn test_changed_files_and_history_follow_renames() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        let (_, loader) = setup_test_repo(&temp_dir)?;
+
+        let notes = "one\ntwo\nthree\nfour\n";
+        let a = commit_files(repo, &loader, vec![], &[("notes.txt", notes)])?;
+        let b = commit_files(
+            repo,
+            &loader,
+            vec![a],
+            &[("docs/notes.txt", "one\ntwo\nthree\nfive\n")],
+        )?;
+        let c = commit_files(
+            repo,
+            &loader,
+            vec![b],
+            &[("docs/notes.txt", "one\ntwo\nthree\nsix\n")],
+        )?;
+        fs::write(repo.join(".helix/HEAD"), hash_to_hex(&c))?;
+
+        let changes = loader.get_changed_files(&loader.read_commit(&b)?)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::Renamed);
+        assert_eq!(changes[0].path, PathBuf::from("docs/notes.txt"));
+        assert_eq!(changes[0].old_path, Some(PathBuf::from("notes.txt")));
+
+        let path = Path::new("docs/notes.txt");
+        let history: Vec<_> = loader
+            .file_history(path, true)?
+            .into_iter()
+            .map(|(commit, path)| (commit.commit_hash, path))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                (c, PathBuf::from("docs/notes.txt")),
+                (b, PathBuf::from("docs/notes.txt")),
+                (a, PathBuf::from("notes.txt")),
+            ]
+        );
+
+        // Without --follow history stops where the file got its current name
+        assert_eq!(loader.file_history(path, false)?.len(), 2);
+
+        Ok(())
+    }
 }
@@ -1,5 +1,5 @@
 /*
-Binary file format for helix.idx V1.0
+Binary file format for helix.idx V3 (BLAKE3 footer, sorted entries)
 
 ┌─────────────────────────────────────┐
  │ Header                              │
@@ -9,13 +9,34 @@ Binary file format for helix.idx V1.0
  │ ...                                 │
  │ Entry N                             │
  ├─────────────────────────────────────┤
- │ Reserved Metadata                   │
+ │ Extensions (optional)               │
  ├─────────────────────────────────────┤
  │ Footer                              │
  └─────────────────────────────────────┘
+
+The header's version says which format the rest of the file is in. Files
+from older versions are upgraded on first read (see migrate.rs); a change to
+this layout bumps VERSION and adds a migration for it.
+
+Entries are sorted by path, compared component by component the way PathBuf
+orders them, with no two entries for the same path. Writers sort, readers
+reject a file that isn't in order, and lookups rely on it: one path is a
+binary search, and everything under a directory is one contiguous run.
+
+The footer is a BLAKE3 checksum of everything before it. Writers keep the
+previous generation alongside as helix.idx.bak so a reader that finds a
+checksum mismatch can fall back to it.
+
+Each extension is a 4-byte signature, a u32 payload length and the payload.
+Extensions hold data that can be recomputed, so readers skip signatures they
+don't know and writers that don't carry an extension along simply drop it.
+
+  UNTR  untracked cache: per-directory results of the untracked scan, keyed
+        by the directory's mtime (see untracked.rs)
 */
 
 use helix_protocol::hash::Hash;
+use rayon::prelude::*;
 use std::{
     path::{Path, PathBuf},
     str::Utf8Error,
@@ -24,7 +45,7 @@ use std::{
 use crate::add_command::get_file_mode;
 
 pub const MAGIC: [u8; 4] = *b"HLIX";
-pub const VERSION: u32 = 1;
+pub const VERSION: u32 = 3;
 pub const FOOTER_SIZE: usize = 32;
 pub const ENTRY_RESERVED_SIZE: usize = 64;
 
@@ -32,7 +53,7 @@ pub const ENTRY_RESERVED_SIZE: usize = 64;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Header {
     pub magic: [u8; 4],
-    pub version: u32,    // 1
+    pub version: u32,    // 3
     pub generation: u64, // Incremented on every write
     pub checksum: Hash,  // Checksum of entire file; 32 bytes
     pub entry_count: u32,
@@ -161,6 +182,24 @@ pub struct Entry {
     pub reserved: [u8; 33],
 }
 
+/// Sort entries into the order helix.idx stores them in
+pub fn sort_entries(entries: &mut [Entry]) {
+    // Parallel sort for large datasets
+    if entries.len() > 10000 {
+        entries.par_sort_by(|a, b| a.path.cmp(&b.path));
+    } else {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+/// Position of the first entry that doesn't come strictly after the one before it
+pub fn first_out_of_order(entries: &[Entry]) -> Option<usize> {
+    entries
+        .windows(2)
+        .position(|pair| pair[0].path >= pair[1].path)
+        .map(|i| i + 1)
+}
+
 impl Entry {
     pub const ENTRY_MAX_SIZE: usize = 296;
     pub const ENTRY_MAX_PATH_LEN: usize = 200;
@@ -365,12 +404,14 @@ bitflags::bitflags! {
         const IGNORED = 1 << 7;
         const SYMLINK = 1 << 8;
 
+        // From .helixattributes when the entry was added (see attributes.rs)
+        const ATTR_TEXT     = 1 << 9;
+        const ATTR_BINARY   = 1 << 10;
+        const ATTR_LOCKABLE = 1 << 11;
+        const ATTR_NO_CHUNK = 1 << 12;
+        const ATTR_FILTERED = 1 << 13;
+
         // Reserved for future use
-        const RESERVED1  = 1 << 9;
-        const RESERVED2  = 1 << 10;
-        const RESERVED3  = 1 << 11;
-        const RESERVED4  = 1 << 12;
-        const RESERVED5  = 1 << 13;
         const RESERVED6  = 1 << 14;
         const RESERVED7  = 1 << 15;
     }
@@ -389,9 +430,184 @@ impl EntryFlags {
     }
 }
 
+pub const UNTRACKED_CACHE_SIGNATURE: [u8; 4] = *b"UNTR";
+
+/// Optional data stored between the entries and the footer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions {
+    pub untracked_cache: Option<UntrackedCache>,
+}
+
+impl Extensions {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        if let Some(cache) = &self.untracked_cache {
+            let payload = cache.to_bytes();
+            buf.extend_from_slice(&UNTRACKED_CACHE_SIGNATURE);
+            buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            buf.extend_from_slice(&payload);
+        }
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let mut extensions = Self::default();
+        let mut cursor = Cursor::new(bytes);
+
+        while !cursor.is_empty() {
+            let signature: [u8; 4] = cursor.take(4)?.try_into().unwrap();
+            let len = cursor.u32()? as usize;
+            let payload = cursor.take(len)?;
+            if signature == UNTRACKED_CACHE_SIGNATURE {
+                extensions.untracked_cache = Some(UntrackedCache::from_bytes(payload)?);
+            }
+        }
+
+        Ok(extensions)
+    }
+}
+
+/// What the untracked scan found in each directory, reused while the
+/// directory's mtime, its .gitignore and its tracked entries are unchanged
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UntrackedCache {
+    /// Fingerprint of the repository-wide ignore sources the scan used
+    pub ignore_key: Hash,
+    pub dirs: Vec<CachedDir>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedDir {
+    /// Relative to the working tree root, empty for the root itself
+    pub path: PathBuf,
+    pub mtime_sec: u64,
+    pub mtime_nsec: u32,
+    /// Hash of the directory's own .gitignore, zero when it has none
+    pub gitignore: Hash,
+    /// Hash of the names of the tracked entries directly inside it
+    pub tracked: Hash,
+    pub untracked: Vec<CachedUntracked>,
+}
+
+/// An untracked entry directly inside a cached directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedUntracked {
+    pub name: String,
+    /// For a collapsed directory, an unignored file inside it (relative to
+    /// the root) whose existence shows the directory is still worth reporting
+    pub witness: Option<PathBuf>,
+}
+
+impl UntrackedCache {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&self.ignore_key);
+        buf.extend_from_slice(&(self.dirs.len() as u32).to_le_bytes());
+
+        for dir in &self.dirs {
+            put_str(&mut buf, &dir.path.to_string_lossy());
+            buf.extend_from_slice(&dir.mtime_sec.to_le_bytes());
+            buf.extend_from_slice(&dir.mtime_nsec.to_le_bytes());
+            buf.extend_from_slice(&dir.gitignore);
+            buf.extend_from_slice(&dir.tracked);
+            buf.extend_from_slice(&(dir.untracked.len() as u32).to_le_bytes());
+            for entry in &dir.untracked {
+                put_str(&mut buf, &entry.name);
+                let witness = entry
+                    .witness
+                    .as_ref()
+                    .map(|w| w.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                put_str(&mut buf, &witness);
+            }
+        }
+
+        buf
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let mut cursor = Cursor::new(bytes);
+        let ignore_key = cursor.hash()?;
+        let dir_count = cursor.u32()?;
+
+        let mut dirs = Vec::new();
+        for _ in 0..dir_count {
+            let path = PathBuf::from(cursor.str()?);
+            let mtime_sec = u64::from_le_bytes(cursor.take(8)?.try_into().unwrap());
+            let mtime_nsec = cursor.u32()?;
+            let gitignore = cursor.hash()?;
+            let tracked = cursor.hash()?;
+
+            let entry_count = cursor.u32()?;
+            let mut untracked = Vec::new();
+            for _ in 0..entry_count {
+                let name = cursor.str()?.to_string();
+                let witness = cursor.str()?;
+                untracked.push(CachedUntracked {
+                    name,
+                    witness: (!witness.is_empty()).then(|| PathBuf::from(witness)),
+                });
+            }
+
+            dirs.push(CachedDir {
+                path,
+                mtime_sec,
+                mtime_nsec,
+                gitignore,
+                tracked,
+                untracked,
+            });
+        }
+
+        Ok(Self { ignore_key, dirs })
+    }
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Bounds-checked reads from an extension payload
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        if self.bytes.len() < len {
+            return Err(FormatError::InvalidExtension("Truncated extension".into()));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn hash(&mut self) -> Result<Hash, FormatError> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    fn str(&mut self) -> Result<&'a str, FormatError> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(FormatError::InvalidPathEncoding)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Footer {
-    /// SHA-256 checksum of header + all entries
+    /// BLAKE3 checksum of header + all entries
     pub checksum: [u8; 32],
 }
 
@@ -400,6 +616,11 @@ impl Footer {
         Self { checksum }
     }
 
+    /// Compute the footer for the serialized header + entries
+    pub fn compute(data: &[u8]) -> Self {
+        Self::new(*blake3::hash(data).as_bytes())
+    }
+
     pub fn to_bytes(&self) -> [u8; FOOTER_SIZE] {
         self.checksum
     }
@@ -433,6 +654,9 @@ pub enum FormatError {
     #[error("Invalid footer: {0}")]
     InvalidFooter(String),
 
+    #[error("Invalid extension: {0}")]
+    InvalidExtension(String),
+
     #[error("Checksum mismatch")]
     ChecksumMismatch,
 
@@ -617,7 +841,7 @@ mod tests {
     #[test]
     fn test_header_constants() {
         assert_eq!(MAGIC, *b"HLIX");
-        assert_eq!(VERSION, 2, "Version should be 2 for BLAKE3 format");
+        assert_eq!(VERSION, 3, "Version should be 3 for sorted entries");
         assert_eq!(Header::HEADER_SIZE, 128);
     }
 
@@ -627,6 +851,46 @@ mod tests {
         assert_eq!(Entry::ENTRY_MAX_PATH_LEN, 200);
     }
 
+    #[test]
+    fn test_extensions_roundtrip() {
+        let cache = UntrackedCache {
+            ignore_key: hash_bytes(b"ignore"),
+            dirs: vec![CachedDir {
+                path: PathBuf::from("src"),
+                mtime_sec: 1234567890,
+                mtime_nsec: 42,
+                gitignore: [0u8; 32],
+                tracked: hash_bytes(b"lib.rs"),
+                untracked: vec![
+                    CachedUntracked {
+                        name: "new.rs".to_string(),
+                        witness: None,
+                    },
+                    CachedUntracked {
+                        name: "gen/".to_string(),
+                        witness: Some(PathBuf::from("src/gen/out.rs")),
+                    },
+                ],
+            }],
+        };
+        let extensions = Extensions {
+            untracked_cache: Some(cache),
+        };
+
+        let mut bytes = extensions.to_bytes();
+        assert_eq!(Extensions::from_bytes(&bytes).unwrap(), extensions);
+        assert_eq!(Extensions::from_bytes(&[]).unwrap(), Extensions::default());
+
+        // Unknown extensions are skipped, truncated ones rejected
+        let mut unknown = b"ZZZZ".to_vec();
+        unknown.extend_from_slice(&3u32.to_le_bytes());
+        unknown.extend_from_slice(b"abc");
+        unknown.extend_from_slice(&bytes);
+        assert_eq!(Extensions::from_bytes(&unknown).unwrap(), extensions);
+        bytes.pop();
+        assert!(Extensions::from_bytes(&bytes).is_err());
+    }
+
     #[test]
     fn test_multiple_entries_roundtrip() {
         let entries = vec![
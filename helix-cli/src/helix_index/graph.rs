@@ -0,0 +1,261 @@
+/*
+Commit graph layout for the log view, in the spirit of `git log --graph`.
+
+Commits are walked newest first across every parent, not just the first one,
+so merged branches show up. Each commit is then laid out on "lanes": a lane is
+a column waiting for a particular commit to appear. A commit takes the lane
+that was waiting for it (or a fresh one if it is a branch tip), and hands the
+lane on to its first parent. Further parents of a merge open new lanes to the
+right. When a parent is already awaited on another lane (a fork point), the two
+lanes join so every commit is awaited at most once; the leftmost one carries
+on, which keeps the main line of history in the first column.
+
+Every commit gets three strings of the same width:
+
+  node          ● in the commit's lane, │ for lanes passing by
+  edges         the turn from this commit into its parents' lanes
+  continuation  the lanes after this commit, to pad out taller rows
+
+  merge     fork      branch
+  ◉         ● │       │ ●
+  ├─╮       ├─╯       │ │
+  │ │       │         │ │
+*/
+use anyhow::Result;
+use helix_protocol::hash::Hash;
+use std::collections::{BinaryHeap, HashSet};
+
+use super::commit::{Commit, CommitStore};
+
+/// Glyphs for one commit's rows in the graph column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphRow {
+    pub node: String,
+    pub edges: String,
+    pub continuation: String,
+}
+
+/// Commits reachable from `tips` through any parent, newest first, stopping
+/// at `stop_at` (excluded) and after `limit` commits
+pub fn walk_commits(
+    store: &CommitStore,
+    tips: &[Hash],
+    limit: usize,
+    stop_at: Option<&Hash>,
+) -> Result<Vec<Commit>> {
+    let mut queue: BinaryHeap<(u64, Hash)> = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    let mut commits = Vec::new();
+
+    let mut enqueue = |hash: Hash, queue: &mut BinaryHeap<(u64, Hash)>| {
+        if Some(&hash) == stop_at || !seen.insert(hash) {
+            return;
+        }
+        // Unreadable commits (e.g. history that was never fetched) end the walk there
+        if let Ok(commit) = store.read_commit(&hash) {
+            queue.push((commit.commit_time, hash));
+        }
+    };
+
+    for tip in tips {
+        enqueue(*tip, &mut queue);
+    }
+
+    while commits.len() < limit {
+        let Some((_, hash)) = queue.pop() else {
+            break;
+        };
+        let commit = store.read_commit(&hash)?;
+        for parent in &commit.parents {
+            enqueue(*parent, &mut queue);
+        }
+        commits.push(commit);
+    }
+
+    Ok(commits)
+}
+
+/// Lay out `commits` (newest first) as graph rows, one per commit
+pub fn graph_rows(commits: &[Commit]) -> Vec<GraphRow> {
+    let mut lanes: Vec<Option<Hash>> = Vec::new();
+    commits
+        .iter()
+        .map(|commit| next_row(&mut lanes, commit))
+        .collect()
+}
+
+fn next_row(lanes: &mut Vec<Option<Hash>>, commit: &Commit) -> GraphRow {
+    let col = match lanes.iter().position(|l| l == &Some(commit.commit_hash)) {
+        Some(col) => col,
+        None => free_lane(lanes, 0),
+    };
+    lanes[col] = Some(commit.commit_hash);
+    let before = lanes.clone();
+
+    // Hand the lane on to the parents
+    lanes[col] = None;
+    let mut targets = Vec::new();
+    // Lanes to the right that close into this one
+    let mut joins = Vec::new();
+    for (i, parent) in commit.parents.iter().enumerate() {
+        let target = match lanes.iter().position(|l| l == &Some(*parent)) {
+            Some(existing) if i == 0 && existing > col => {
+                lanes[existing] = None;
+                joins.push(existing);
+                col
+            }
+            Some(existing) => existing,
+            None if i == 0 => col,
+            None => free_lane(lanes, col + 1),
+        };
+        lanes[target] = Some(*parent);
+        targets.push(target);
+    }
+    while lanes.last() == Some(&None) {
+        lanes.pop();
+    }
+
+    let width = before.len().max(lanes.len());
+    let node_glyph = if commit.parents.len() > 1 {
+        '◉'
+    } else {
+        '●'
+    };
+    let node = render(&lane_cells(&before, width, Some((col, node_glyph))), &[]);
+    let continuation = render(&lane_cells(lanes, width, None), &[]);
+
+    // Lanes passing by stay vertical; the commit's own lane turns into its parents'
+    let mut cells: Vec<char> = (0..width)
+        .map(|i| {
+            let passing = i != col && before.get(i).is_some_and(|l| l.is_some());
+            if passing || (i == col && targets.contains(&col)) {
+                '│'
+            } else {
+                ' '
+            }
+        })
+        .collect();
+    let mut spans = Vec::new();
+    for &target in targets.iter().filter(|&&t| t != col) {
+        let existing = before.get(target).is_some_and(|l| l.is_some());
+        cells[target] = match (target > col, existing) {
+            (true, true) => '┤',
+            (true, false) => '╮',
+            (false, true) => '├',
+            (false, false) => '╭',
+        };
+        let (from, to) = (col.min(target), col.max(target));
+        for cell in cells.iter_mut().take(to).skip(from + 1) {
+            *cell = if *cell == '│' { '┼' } else { '─' };
+        }
+        spans.push((from, to));
+    }
+    for &join in &joins {
+        cells[join] = '╯';
+        for cell in cells.iter_mut().take(join).skip(col + 1) {
+            *cell = if *cell == '│' { '┼' } else { '─' };
+        }
+        spans.push((col, join));
+    }
+    let down = targets.contains(&col);
+    let right = !joins.is_empty() || targets.iter().any(|&t| t > col);
+    let left = targets.iter().any(|&t| t < col);
+    cells[col] = match (down, left, right) {
+        (true, true, true) => '┼',
+        (true, false, true) => '├',
+        (true, true, false) => '┤',
+        (false, true, true) => '┴',
+        (false, false, true) => '╰',
+        (false, true, false) => '╯',
+        (true, false, false) => '│',
+        (false, false, false) => ' ',
+    };
+    let edges = render(&cells, &spans);
+
+    GraphRow {
+        node,
+        edges,
+        continuation,
+    }
+}
+
+/// First empty lane at or after `from`, adding one if there is none
+fn free_lane(lanes: &mut Vec<Option<Hash>>, from: usize) -> usize {
+    match (from..lanes.len()).find(|&i| lanes[i].is_none()) {
+        Some(i) => i,
+        None => {
+            lanes.resize(lanes.len().max(from), None);
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
+
+fn lane_cells(lanes: &[Option<Hash>], width: usize, node: Option<(usize, char)>) -> Vec<char> {
+    (0..width)
+        .map(|i| match node {
+            Some((col, glyph)) if col == i => glyph,
+            _ if lanes.get(i).is_some_and(|l| l.is_some()) => '│',
+            _ => ' ',
+        })
+        .collect()
+}
+
+/// Join cells with a gap between each, drawn as ─ inside a horizontal span
+fn render(cells: &[char], spans: &[(usize, usize)]) -> String {
+    let mut out = String::new();
+    for (i, cell) in cells.iter().enumerate() {
+        out.push(*cell);
+        let joined = spans.iter().any(|&(from, to)| from <= i && i < to);
+        out.push(if joined { '─' } else { ' ' });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(name: u8, parents: &[&Commit]) -> Commit {
+        let mut commit = Commit::new(
+            [0u8; 32],
+            parents.iter().map(|p| p.commit_hash).collect(),
+            "Test User <test@example.com>".to_string(),
+            format!("commit {}", name),
+        );
+        commit.commit_hash = [name; 32];
+        commit
+    }
+
+    #[test]
+    fn test_fork_and_merge_layout() {
+        // root <- base <- main1 ------ merge
+        //              \- feature ---/
+        let root = commit(1, &[]);
+        let base = commit(2, &[&root]);
+        let main1 = commit(3, &[&base]);
+        let feature = commit(4, &[&base]);
+        let merge = commit(5, &[&main1, &feature]);
+
+        let rows = graph_rows(&[merge, feature, main1, base, root]);
+        let drawn: Vec<(&str, &str)> = rows
+            .iter()
+            .map(|r| (r.node.trim_end(), r.edges.trim_end()))
+            .collect();
+
+        assert_eq!(
+            drawn,
+            vec![
+                ("◉", "├─╮"),
+                ("│ ●", "│ │"),
+                ("● │", "├─╯"),
+                ("●", "│"),
+                ("●", ""),
+            ]
+        );
+        assert!(rows
+            .iter()
+            .all(|r| r.node.chars().count() == r.edges.chars().count()
+                && r.edges.chars().count() == r.continuation.chars().count()));
+    }
+}
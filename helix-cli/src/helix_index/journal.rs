@@ -0,0 +1,435 @@
+/*
+Split index: append-only journal of entry updates on top of helix.idx
+
+Rewriting every entry of a 100k+ file index on each `helix add` is wasteful when
+only a handful of entries changed. Instead, small updates are appended to
+.helix/helix.idx.journal and merged with the base index whenever it is read.
+A full write of helix.idx (e.g. on commit) compacts the journal away.
+
+┌─────────────────────────────────────┐
+│ Header (48 bytes)                   │
+│  magic "HJNL" | version u32         │
+│  base generation u64                │
+│  base footer checksum [32]          │
+├─────────────────────────────────────┤
+│ Record: op u8 | len u32 | payload   │
+│         | blake3(op, len, payload)  │
+│ ...                                 │
+└─────────────────────────────────────┘
+
+Ops:
+- Upsert: payload is a serialized Entry
+- Remove: payload is the UTF-8 path
+- Commit: payload is the resulting generation (u64). Records are only applied
+  once their batch is closed by a Commit, so a torn append is ignored.
+
+The journal is bound to a single base by its footer checksum; if helix.idx is
+replaced without clearing the journal (crash mid-compaction) it is ignored.
+*/
+use super::format::{self, Entry, Header, FOOTER_SIZE};
+use super::reader::HelixIndex;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const JOURNAL_MAGIC: [u8; 4] = *b"HJNL";
+pub const JOURNAL_VERSION: u32 = 1;
+const JOURNAL_HEADER_SIZE: usize = 48;
+const RECORD_CHECKSUM_SIZE: usize = 32;
+
+const OP_UPSERT: u8 = 1;
+const OP_REMOVE: u8 = 2;
+const OP_COMMIT: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalRecord {
+    Upsert(Entry),
+    Remove(PathBuf),
+}
+
+pub struct Journal {
+    path: PathBuf,
+    index_path: PathBuf,
+}
+
+impl Journal {
+    /// Journal for the canonical index of the repo at `repo_path`
+    pub fn new(repo_path: &Path) -> Self {
+        Self::for_index(&repo_path.join(".helix").join("helix.idx"))
+    }
+
+    /// Journal stored next to an arbitrary index file (e.g. a sandbox index)
+    pub fn for_index(index_path: &Path) -> Self {
+        Self {
+            path: index_path.with_file_name("helix.idx.journal"),
+            index_path: index_path.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Size of the journal on disk (0 if missing)
+    pub fn size(&self) -> u64 {
+        fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0)
+    }
+
+    /// Remove the journal. Called after its records were folded into a full index write.
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).context("Failed to remove index journal"),
+        }
+    }
+
+    /// Append one batch of records, closed by a Commit record for `generation`.
+    /// The caller must hold the index lock.
+    pub fn append(&self, records: &[JournalRecord], generation: u64) -> Result<()> {
+        let mut buf = Vec::new();
+        for record in records {
+            match record {
+                JournalRecord::Upsert(entry) => {
+                    encode_record(&mut buf, OP_UPSERT, &entry.to_bytes()?);
+                }
+                JournalRecord::Remove(path) => {
+                    let path = path
+                        .to_str()
+                        .ok_or_else(|| anyhow::anyhow!("Non UTF-8 path: {}", path.display()))?;
+                    encode_record(&mut buf, OP_REMOVE, path.as_bytes());
+                }
+            }
+        }
+        encode_record(&mut buf, OP_COMMIT, &generation.to_le_bytes());
+
+        // Journal whose header itself was torn is recreated from scratch
+        let valid_len = if self.path.exists() {
+            self.valid_len()?
+        } else {
+            0
+        };
+
+        let mut file = if valid_len > 0 {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)
+                .context("Failed to open index journal")?;
+            // Drop any torn tail from a crashed append before adding new records
+            file.set_len(valid_len)?;
+            file.seek(SeekFrom::End(0))?;
+            file
+        } else {
+            let mut file = File::create(&self.path).context("Failed to create index journal")?;
+            file.write_all(&self.base_header()?)?;
+            file
+        };
+
+        file.write_all(&buf)
+            .context("Failed to append to index journal")?;
+        file.sync_all().context("Failed to fsync index journal")?;
+        Ok(())
+    }
+
+    /// Apply committed journal batches to `index` if the journal belongs to `base_checksum`.
+    /// Returns the number of records applied.
+    pub fn apply(&self, index: &mut HelixIndex, base_checksum: &[u8; 32]) -> Result<usize> {
+        let Some((generation, records)) = self.read_committed(base_checksum)? else {
+            return Ok(0);
+        };
+
+        apply_records(&mut index.entries, &records);
+        index.header.generation = generation;
+        index.header.entry_count = index.entries.len() as u32;
+        Ok(records.len())
+    }
+
+    /// Read all committed records. Returns None when the journal is missing or stale.
+    fn read_committed(
+        &self,
+        base_checksum: &[u8; 32],
+    ) -> Result<Option<(u64, Vec<JournalRecord>)>> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context("Failed to read index journal"),
+        };
+
+        if data.len() < JOURNAL_HEADER_SIZE || data[0..4] != JOURNAL_MAGIC {
+            bail!("Invalid index journal header");
+        }
+        let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        if version != JOURNAL_VERSION {
+            bail!("Unsupported index journal version: {}", version);
+        }
+        if data[16..48] != base_checksum[..] {
+            return Ok(None);
+        }
+
+        let mut committed = Vec::new();
+        let mut pending = Vec::new();
+        let mut generation = None;
+
+        for (op, payload) in RecordIter::new(&data[JOURNAL_HEADER_SIZE..]) {
+            match op {
+                OP_UPSERT => pending.push(JournalRecord::Upsert(Entry::from_bytes(payload)?)),
+                OP_REMOVE => {
+                    let path = std::str::from_utf8(payload).context("Invalid journal path")?;
+                    pending.push(JournalRecord::Remove(PathBuf::from(path)));
+                }
+                OP_COMMIT if payload.len() == 8 => {
+                    generation = Some(u64::from_le_bytes(payload.try_into().unwrap()));
+                    committed.append(&mut pending);
+                }
+                _ => bail!("Invalid index journal record type {}", op),
+            }
+        }
+
+        Ok(generation.map(|g| (g, committed)))
+    }
+
+    /// Length of the journal up to and including the last committed batch
+    fn valid_len(&self) -> Result<u64> {
+        let mut data = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut data)?;
+        if data.len() < JOURNAL_HEADER_SIZE {
+            return Ok(0);
+        }
+
+        let mut offset = JOURNAL_HEADER_SIZE;
+        let mut valid = JOURNAL_HEADER_SIZE;
+        for (op, payload) in RecordIter::new(&data[JOURNAL_HEADER_SIZE..]) {
+            offset += 1 + 4 + payload.len() + RECORD_CHECKSUM_SIZE;
+            if op == OP_COMMIT {
+                valid = offset;
+            }
+        }
+        Ok(valid as u64)
+    }
+
+    /// Build the journal header for the index currently on disk
+    fn base_header(&self) -> Result<[u8; JOURNAL_HEADER_SIZE]> {
+        let mut file = File::open(&self.index_path).context("Failed to open helix.idx")?;
+
+        let mut header_bytes = [0u8; Header::HEADER_SIZE];
+        file.read_exact(&mut header_bytes)?;
+        let header = Header::from_bytes(&header_bytes)?;
+
+        let mut footer = [0u8; FOOTER_SIZE];
+        file.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+        file.read_exact(&mut footer)?;
+
+        let mut buf = [0u8; JOURNAL_HEADER_SIZE];
+        buf[0..4].copy_from_slice(&JOURNAL_MAGIC);
+        buf[4..8].copy_from_slice(&JOURNAL_VERSION.to_le_bytes());
+        buf[8..16].copy_from_slice(&header.generation.to_le_bytes());
+        buf[16..48].copy_from_slice(&footer);
+        Ok(buf)
+    }
+}
+
+/// Merge records into entries: upserts replace in place or add the path, removals drop
+/// it. Entries stay sorted by path.
+pub fn apply_records(entries: &mut Vec<Entry>, records: &[JournalRecord]) {
+    let mut positions: HashMap<PathBuf, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.path.clone(), i))
+        .collect();
+    let mut removed = false;
+    let mut added = false;
+
+    for record in records {
+        match record {
+            JournalRecord::Upsert(entry) => match positions.get(&entry.path) {
+                Some(&i) => entries[i] = entry.clone(),
+                None => {
+                    positions.insert(entry.path.clone(), entries.len());
+                    entries.push(entry.clone());
+                    added = true;
+                }
+            },
+            JournalRecord::Remove(path) => {
+                if let Some(i) = positions.remove(path) {
+                    // Mark for removal; compacted below to keep positions stable
+                    entries[i].path = PathBuf::new();
+                    removed = true;
+                }
+            }
+        }
+    }
+
+    if removed {
+        entries.retain(|e| !e.path.as_os_str().is_empty());
+    }
+    if added {
+        format::sort_entries(entries);
+    }
+}
+
+fn encode_record(buf: &mut Vec<u8>, op: u8, payload: &[u8]) {
+    let start = buf.len();
+    buf.push(op);
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(payload);
+    let checksum = blake3::hash(&buf[start..]);
+    buf.extend_from_slice(checksum.as_bytes());
+}
+
+/// Iterates well-formed records, stopping at the first truncated or corrupt one
+struct RecordIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RecordIter<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.len() < 5 {
+            return None;
+        }
+        let op = self.data[0];
+        let len = u32::from_le_bytes(self.data[1..5].try_into().unwrap()) as usize;
+        let end = 5usize.checked_add(len)?;
+        if self.data.len() < end + RECORD_CHECKSUM_SIZE {
+            return None;
+        }
+        if blake3::hash(&self.data[..end]).as_bytes()[..]
+            != self.data[end..end + RECORD_CHECKSUM_SIZE]
+        {
+            return None;
+        }
+        let payload = &self.data[5..end];
+        self.data = &self.data[end + RECORD_CHECKSUM_SIZE..];
+        Some((op, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::{Reader, Writer};
+    use helix_protocol::hash::hash_bytes;
+    use std::fs::OpenOptions;
+    use tempfile::TempDir;
+
+    fn entry(path: &str, content: &[u8]) -> Entry {
+        Entry::new(PathBuf::from(path), 10, 100, hash_bytes(content), 0o100644)
+    }
+
+    #[test]
+    fn test_reader_merges_journal() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(
+            &Header::new(1, 2),
+            &[entry("a.txt", b"a"), entry("b.txt", b"b")],
+        )?;
+
+        writer.append_journal(
+            &[
+                JournalRecord::Upsert(entry("a.txt", b"a2")),
+                JournalRecord::Remove(PathBuf::from("b.txt")),
+                JournalRecord::Upsert(entry("c.txt", b"c")),
+            ],
+            2,
+        )?;
+
+        let index = Reader::new(repo_path).read()?;
+        assert_eq!(index.header.generation, 2);
+        assert_eq!(index.header.entry_count, 2);
+        let paths: Vec<_> = index.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("a.txt"), PathBuf::from("c.txt")]);
+        assert_eq!(index.entries[0].oid, hash_bytes(b"a2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_write_compacts_journal() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, 1), &[entry("a.txt", b"a")])?;
+        writer.append_journal(&[JournalRecord::Upsert(entry("b.txt", b"b"))], 2)?;
+
+        let journal = Journal::new(repo_path);
+        assert!(journal.exists());
+
+        let index = Reader::new(repo_path).read()?;
+        writer.write(&index.header, &index.entries)?;
+        assert!(!journal.exists());
+
+        let index = Reader::new(repo_path).read()?;
+        assert_eq!(index.entries.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_torn_batch_is_ignored() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, 0), &[])?;
+        writer.append_journal(&[JournalRecord::Upsert(entry("a.txt", b"a"))], 2)?;
+
+        // Simulate a crash halfway through the next append
+        let journal = Journal::new(repo_path);
+        let mut partial = Vec::new();
+        encode_record(&mut partial, OP_UPSERT, &entry("b.txt", b"b").to_bytes()?);
+        let mut file = OpenOptions::new().append(true).open(journal.path())?;
+        file.write_all(&partial[..partial.len() / 2])?;
+        drop(file);
+
+        let index = Reader::new(repo_path).read()?;
+        assert_eq!(index.entries.len(), 1);
+        assert_eq!(index.header.generation, 2);
+
+        // Next append truncates the torn tail
+        writer.append_journal(&[JournalRecord::Upsert(entry("c.txt", b"c"))], 3)?;
+        let index = Reader::new(repo_path).read()?;
+        assert_eq!(index.entries.len(), 2);
+        assert_eq!(index.header.generation, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_journal_is_ignored() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, 0), &[])?;
+        writer.append_journal(&[JournalRecord::Upsert(entry("a.txt", b"a"))], 2)?;
+        let saved = fs::read(Journal::new(repo_path).path())?;
+
+        // Base replaced, then an old journal reappears (crash before it was cleared)
+        writer.write(&Header::new(5, 0), &[])?;
+        fs::write(Journal::new(repo_path).path(), saved)?;
+
+        let index = Reader::new(repo_path).read()?;
+        assert!(index.entries.is_empty());
+        assert_eq!(index.header.generation, 5);
+
+        Ok(())
+    }
+}
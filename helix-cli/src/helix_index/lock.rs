@@ -0,0 +1,287 @@
+/*
+Advisory lock for .helix/helix.idx, mirroring Git's index.lock semantics.
+
+A writer creates .helix/helix.idx.lock with O_CREAT|O_EXCL before touching the
+index and removes it when done. Any other process that finds the lock waits
+briefly for it to go away and then fails with a clear error.
+
+A lock is stale, and broken automatically, only when the process that created it
+is no longer running (Linux only); a slow holder keeps its lock however old it
+is. Breaking renames the lock aside and checks it is still the dead process's
+before deleting it, so a lock another process just re-created is put back.
+
+A command that changes the index holds the lock from before it reads the index
+until the new index is renamed into place (HelixIndexData::load_for_update), so
+the lock is reentrant on the thread that holds it: the Writer taking it again for
+the physical write nests inside instead of waiting on itself.
+*/
+use anyhow::{bail, Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait for another process to release the lock
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+thread_local! {
+    /// Locks this thread holds, by lock file, and how many guards hold each
+    static HELD: RefCell<HashMap<PathBuf, usize>> = RefCell::new(HashMap::new());
+}
+
+/// Held for the duration of an index write. The lock file is removed when the last
+/// guard for it on this thread is dropped.
+#[derive(Debug)]
+pub struct IndexLock {
+    path: PathBuf,
+    /// Key in HELD
+    key: PathBuf,
+}
+
+impl IndexLock {
+    /// Path of the lock file for the repo at `repo_path`
+    pub fn lock_path(repo_path: &Path) -> PathBuf {
+        repo_path.join(".helix").join("helix.idx.lock")
+    }
+
+    /// Acquire the index lock, waiting up to LOCK_TIMEOUT for a concurrent writer to finish
+    pub fn acquire(repo_path: &Path) -> Result<Self> {
+        Self::acquire_with_timeout(repo_path, LOCK_TIMEOUT)
+    }
+
+    pub fn acquire_with_timeout(repo_path: &Path, timeout: Duration) -> Result<Self> {
+        let path = Self::lock_path(repo_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create .helix directory")?;
+        }
+
+        // Already ours: nest
+        let key = fs::canonicalize(&path)
+            .ok()
+            .or_else(|| {
+                let parent = fs::canonicalize(path.parent()?).ok()?;
+                Some(parent.join(path.file_name()?))
+            })
+            .unwrap_or_else(|| path.clone());
+        let nested = HELD.with(|held| match held.borrow_mut().get_mut(&key) {
+            Some(count) => {
+                *count += 1;
+                true
+            }
+            None => false,
+        });
+        if nested {
+            return Ok(Self { path, key });
+        }
+
+        let start = std::time::Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    // Best-effort: record owner so stale locks can be detected
+                    let _ = writeln!(file, "{}\n{}", std::process::id(), now);
+                    HELD.with(|held| held.borrow_mut().insert(key.clone(), 1));
+                    return Ok(Self { path, key });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if let Some(contents) = stale_contents(&path) {
+                        break_stale(&path, &contents);
+                        continue;
+                    }
+
+                    if start.elapsed() >= timeout {
+                        bail!(
+                            "Unable to create '{}': File exists.\n\n\
+                             Another helix process seems to be running in this repository.\n\
+                             Please make sure all processes are terminated then try again.\n\
+                             If it still fails, a helix process may have crashed in this\n\
+                             repository earlier: remove the file manually to continue.",
+                            path.display()
+                        );
+                    }
+
+                    thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("Failed to create lock {}", path.display()))
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for IndexLock {
+    fn drop(&mut self) {
+        let last = HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            let count = held.get_mut(&self.key).map(|count| {
+                *count -= 1;
+                *count
+            });
+            if count == Some(0) {
+                held.remove(&self.key);
+            }
+            count.unwrap_or(0) == 0
+        });
+        if last {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The contents of a lock file left behind by a dead process, or None if its owner may
+/// still be running
+fn stale_contents(path: &Path) -> Option<String> {
+    // Lock vanished or is unreadable; let the next create attempt decide
+    let contents = fs::read_to_string(path).ok()?;
+    // No pid yet: the owner is between creating the file and writing to it
+    let pid = contents.lines().next()?.trim().parse::<u32>().ok()?;
+    (process_alive(pid) == Some(false)).then_some(contents)
+}
+
+/// Remove the lock at `path` if it still holds `contents`. Another process may have broken
+/// it first and taken the lock since, so it is moved aside before it is checked; a lock that
+/// turns out to be someone else's goes back. Returns whether the stale lock was removed.
+fn break_stale(path: &Path, contents: &str) -> bool {
+    static BREAKS: AtomicUsize = AtomicUsize::new(0);
+    let aside = path.with_extension(format!(
+        "lock.stale-{}-{}",
+        std::process::id(),
+        BREAKS.fetch_add(1, Ordering::Relaxed)
+    ));
+    if fs::rename(path, &aside).is_err() {
+        // Already broken by someone else
+        return false;
+    }
+    let ours = fs::read_to_string(&aside).is_ok_and(|moved| moved == contents);
+    if !ours {
+        // Doesn't replace a lock taken meanwhile
+        let _ = fs::hard_link(&aside, path);
+    }
+    let _ = fs::remove_file(&aside);
+    ours
+}
+
+/// Returns None when liveness can't be determined on this platform
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> Option<bool> {
+    Some(Path::new("/proc").join(pid.to_string()).exists())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let lock = IndexLock::acquire(repo_path)?;
+        assert!(IndexLock::lock_path(repo_path).exists());
+
+        // Anyone but the holding thread (see test_lock_nests_on_the_holding_thread)
+        let other = repo_path.to_path_buf();
+        let err = std::thread::spawn(move || {
+            IndexLock::acquire_with_timeout(&other, Duration::from_millis(50))
+                .unwrap_err()
+                .to_string()
+        })
+        .join()
+        .unwrap();
+        assert!(err.contains("helix.idx.lock"));
+        assert!(err.contains("Another helix process"));
+
+        drop(lock);
+        assert!(!IndexLock::lock_path(repo_path).exists());
+
+        let _lock = IndexLock::acquire(repo_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_nests_on_the_holding_thread() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let outer = IndexLock::acquire(repo_path)?;
+        let inner = IndexLock::acquire_with_timeout(repo_path, Duration::from_millis(50))?;
+        drop(inner);
+        assert!(IndexLock::lock_path(repo_path).exists());
+
+        drop(outer);
+        assert!(!IndexLock::lock_path(temp_dir.path()).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_lock_of_a_running_process_is_kept() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        fs::create_dir_all(repo_path.join(".helix"))?;
+
+        // Taken long ago by a process that is still running (this one)
+        let contents = format!("{}\n0\n", std::process::id());
+        fs::write(IndexLock::lock_path(repo_path), &contents)?;
+
+        assert!(IndexLock::acquire_with_timeout(repo_path, Duration::from_millis(50)).is_err());
+        assert_eq!(
+            fs::read_to_string(IndexLock::lock_path(repo_path))?,
+            contents
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_breaking_keeps_a_lock_taken_meanwhile() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        fs::create_dir_all(repo_path.join(".helix"))?;
+        let path = IndexLock::lock_path(repo_path);
+
+        // Seen stale, but another process broke it and took the lock before us
+        let live = format!("{}\n0\n", std::process::id());
+        fs::write(&path, &live)?;
+        assert!(!break_stale(&path, "4294967295\n0\n"));
+        assert_eq!(fs::read_to_string(&path)?, live);
+
+        fs::write(&path, "4294967295\n0\n")?;
+        assert!(break_stale(&path, "4294967295\n0\n"));
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(repo_path.join(".helix"))?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_lock_is_broken() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        fs::create_dir_all(repo_path.join(".helix"))?;
+
+        // Lock left behind by a crashed process long ago
+        fs::write(IndexLock::lock_path(repo_path), "4294967295\n0\n")?;
+
+        let _lock = IndexLock::acquire_with_timeout(repo_path, Duration::from_millis(50))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+/*
+Upgrading helix.idx written by older versions of helix.
+
+The header's version field says which format a file is in. Each change to the
+format adds a Migration to MIGRATIONS that turns a file of version `from` into
+one of `from + 1`, so an index several versions old is upgraded by running
+every migration from its version up to VERSION in order.
+
+Readers upgrade helix.idx in place the first time they find an old one. The
+original file is kept as .helix/helix.idx.v<N> (N being its version) before
+anything is rewritten, and the upgraded index goes through the canonical
+writer, so it is fsynced, replaced atomically and compacts any journal that
+was recorded against the old file. An index newer than this binary
+understands is left alone with an error asking for a newer helix.
+
+`helix index migrate --check` reports what would happen without writing.
+
+  v1 -> v2  footer checksum changed from SHA-256 to BLAKE3
+  v2 -> v3  entries sorted by path, one per path
+*/
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::format::{Entry, Footer, Header, FOOTER_SIZE, MAGIC, VERSION};
+use super::journal::Journal;
+use super::reader::Reader;
+use super::writer::Writer;
+
+#[derive(Debug)]
+pub struct Migration {
+    /// Version this migration upgrades from, to `from + 1`
+    pub from: u32,
+    pub summary: &'static str,
+    upgrade: fn(Vec<u8>) -> Result<Vec<u8>>,
+}
+
+/// Every migration, oldest first
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        summary: "footer checksum changed from SHA-256 to BLAKE3",
+        upgrade: v1_to_v2,
+    },
+    Migration {
+        from: 2,
+        summary: "entries sorted by path, one per path",
+        upgrade: v2_to_v3,
+    },
+];
+
+/// The format version of an index file, from its header
+pub fn version_of(bytes: &[u8]) -> Result<u32> {
+    if bytes.len() < 8 || bytes[0..4] != MAGIC {
+        bail!("Not a helix index (bad magic)");
+    }
+    Ok(u32::from_le_bytes(bytes[4..8].try_into().unwrap()))
+}
+
+/// The migrations that bring an index at `version` up to VERSION
+pub fn pending(version: u32) -> Result<Vec<&'static Migration>> {
+    if version > VERSION {
+        bail!(
+            "helix.idx is in index format v{version}, but this helix only understands up to v{VERSION}. Upgrade helix."
+        );
+    }
+    (version..VERSION)
+        .map(|from| {
+            MIGRATIONS
+                .iter()
+                .find(|m| m.from == from)
+                .with_context(|| format!("No migration from index format v{from}"))
+        })
+        .collect()
+}
+
+/// Run the migrations an index file needs, returning it in the current format
+pub fn upgrade(mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+    for migration in pending(version_of(&bytes)?)? {
+        bytes = (migration.upgrade)(bytes).with_context(|| {
+            format!(
+                "Failed to migrate helix.idx from v{} to v{}",
+                migration.from,
+                migration.from + 1
+            )
+        })?;
+    }
+    Ok(bytes)
+}
+
+/// Where the original of an index migrated from `version` is kept
+pub fn backup_path(repo_path: &Path, version: u32) -> PathBuf {
+    repo_path
+        .join(".helix")
+        .join(format!("helix.idx.v{version}"))
+}
+
+/// The version of the repo's helix.idx, reading only its header
+pub fn index_version(repo_path: &Path) -> Result<u32> {
+    let index_path = repo_path.join(".helix").join("helix.idx");
+    let mut header = [0u8; 8];
+    File::open(&index_path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .with_context(|| format!("Failed to read {}", index_path.display()))?;
+    version_of(&header)
+}
+
+/// Upgrade the repo's helix.idx in place if it is in an older format. Returns the
+/// version it was migrated from, or None if it was already current (or doesn't exist).
+pub fn migrate_in_place(repo_path: &Path) -> Result<Option<u32>> {
+    let index_path = repo_path.join(".helix").join("helix.idx");
+    if !index_path.exists() {
+        return Ok(None);
+    }
+    let version = index_version(repo_path)?;
+    if pending(version)?.is_empty() {
+        return Ok(None);
+    }
+
+    let original = fs::read(&index_path).context("Failed to read helix.idx")?;
+    let old_footer = Footer::from_bytes(&original[original.len().saturating_sub(FOOTER_SIZE)..])?;
+    let upgraded = upgrade(original.clone())?;
+
+    let backup = backup_path(repo_path, version);
+    fs::write(&backup, &original)
+        .with_context(|| format!("Failed to back up helix.idx to {}", backup.display()))?;
+
+    let mut index = Reader::new(repo_path).parse(&upgraded)?;
+    Journal::new(repo_path).apply(&mut index, &old_footer.checksum)?;
+    Writer::new_canonical(repo_path).write_with_extensions(
+        &index.header,
+        &index.entries,
+        &index.extensions,
+    )?;
+    Ok(Some(version))
+}
+
+/// v1 was v2 with a SHA-256 footer. Indexes over 1MB were summed in 256KB chunks, the
+/// footer being the SHA-256 of the chunks' SHA-256s.
+fn v1_to_v2(mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if bytes.len() < 8 + FOOTER_SIZE {
+        bail!("Index file too small");
+    }
+    let body_len = bytes.len() - FOOTER_SIZE;
+    let (body, footer) = bytes.split_at(body_len);
+    let whole: [u8; 32] = Sha256::digest(body).into();
+    let chunked: [u8; 32] = body
+        .chunks(256 * 1024)
+        .fold(Sha256::new(), |mut hasher, chunk| {
+            hasher.update(Sha256::digest(chunk));
+            hasher
+        })
+        .finalize()
+        .into();
+    if footer != whole && footer != chunked {
+        bail!("v1 checksum mismatch");
+    }
+
+    bytes[4..8].copy_from_slice(&2u32.to_le_bytes());
+    let footer = Footer::compute(&bytes[..body_len]);
+    bytes[body_len..].copy_from_slice(&footer.to_bytes());
+    Ok(bytes)
+}
+
+/// v2 kept entries in the order they were added and could hold a path twice; lookups
+/// found the first, later writes touched the last. v3 keeps the last, sorted by path.
+fn v2_to_v3(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if bytes.len() < Header::HEADER_SIZE + FOOTER_SIZE {
+        bail!("Index file too small");
+    }
+    let body_len = bytes.len() - FOOTER_SIZE;
+    if Footer::compute(&bytes[..body_len]).to_bytes()[..] != bytes[body_len..] {
+        bail!("v2 checksum mismatch");
+    }
+
+    let count = u32::from_le_bytes(bytes[48..52].try_into().unwrap()) as usize;
+    let entries_end = Header::HEADER_SIZE + count * Entry::ENTRY_MAX_SIZE;
+    if entries_end > body_len {
+        bail!("Entries run past the footer");
+    }
+    let mut entries = bytes[Header::HEADER_SIZE..entries_end]
+        .chunks(Entry::ENTRY_MAX_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            Entry::from_bytes(chunk)
+                .map(|entry| (entry.path, chunk))
+                .with_context(|| format!("Failed to parse entry {}", i))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Stable, so of a path's entries the last added stays last
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries.reverse();
+    entries.dedup_by(|(a, _), (b, _)| a == b);
+    entries.reverse();
+
+    let mut upgraded = bytes[..Header::HEADER_SIZE].to_vec();
+    upgraded[4..8].copy_from_slice(&3u32.to_le_bytes());
+    upgraded[48..52].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (_, chunk) in &entries {
+        upgraded.extend_from_slice(chunk);
+    }
+    upgraded.extend_from_slice(&bytes[entries_end..body_len]);
+    let footer = Footer::compute(&upgraded);
+    upgraded.extend_from_slice(&footer.to_bytes());
+    Ok(upgraded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::format::{Entry, Header};
+
+    /// A v1 index as the old writer made it
+    fn v1_index(entries: &[Entry]) -> Result<Vec<u8>> {
+        let mut header = Header::new(7, entries.len() as u32);
+        header.version = 1;
+        let mut bytes = header.to_bytes().to_vec();
+        for entry in entries {
+            bytes.extend_from_slice(&entry.to_bytes()?);
+        }
+        let footer: [u8; 32] = Sha256::digest(&bytes).into();
+        bytes.extend_from_slice(&footer);
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_v1_index_is_upgraded_on_first_read() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        let entry = Entry::new("src/main.rs".into(), 12, 1_700_000_000, [3u8; 32], 0o100644);
+        let original = v1_index(std::slice::from_ref(&entry))?;
+        fs::write(repo.join(".helix/helix.idx"), &original)?;
+
+        assert_eq!(index_version(repo)?, 1);
+        assert_eq!(pending(1)?.len(), 2);
+
+        let index = Reader::new(repo).read()?;
+        assert_eq!(index.header.version, VERSION);
+        assert_eq!(index.header.generation, 7);
+        assert_eq!(index.entries, vec![entry]);
+        assert_eq!(index_version(repo)?, VERSION);
+        assert_eq!(fs::read(backup_path(repo, 1))?, original);
+
+        // Nothing left to do the second time
+        assert_eq!(migrate_in_place(repo)?, None);
+
+        // A tampered v1 file isn't silently accepted
+        let mut bad = original.clone();
+        bad[Header::HEADER_SIZE] ^= 0xff;
+        assert!(upgrade(bad).is_err());
+
+        // Nor is one from the future
+        let mut future = original;
+        future[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let err = pending(version_of(&future)?).unwrap_err();
+        assert!(err.to_string().contains("Upgrade helix"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_v2_entries_are_sorted() -> Result<()> {
+        let entry = |path: &str, size| Entry::new(path.into(), size, 0, [1u8; 32], 0o100644);
+        let mut header = Header::new(3, 4);
+        header.version = 2;
+        let mut bytes = header.to_bytes().to_vec();
+        for e in [
+            entry("src/main.rs", 1),
+            entry("README.md", 2),
+            entry("src/main.rs", 3),
+            entry("src/lib.rs", 4),
+        ] {
+            bytes.extend_from_slice(&e.to_bytes()?);
+        }
+        let footer = Footer::compute(&bytes);
+        bytes.extend_from_slice(&footer.to_bytes());
+
+        let upgraded = upgrade(bytes)?;
+        assert_eq!(version_of(&upgraded)?, VERSION);
+        let temp = tempfile::tempdir()?;
+        let index = Reader::new(temp.path()).parse(&upgraded)?;
+        assert_eq!(index.header.entry_count, 3);
+        assert_eq!(
+            index
+                .entries
+                .iter()
+                .map(|e| (e.path.to_string_lossy().into_owned(), e.size))
+                .collect::<Vec<_>>(),
+            vec![
+                ("README.md".to_string(), 2),
+                ("src/lib.rs".to_string(), 4),
+                ("src/main.rs".to_string(), 3),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,10 +1,18 @@
 pub mod api;
 pub mod commit;
 pub mod format;
+pub mod graph;
+pub mod journal;
+pub mod lock;
+pub mod migrate;
 pub mod reader;
+pub mod rename;
+pub mod rev_map;
 pub mod state;
 pub mod sync;
+pub mod transaction;
 pub mod tree;
+pub mod untracked;
 pub mod verify;
 pub mod writer;
 
@@ -1,12 +1,12 @@
 /// Defines functions and methods to read from the helix.index canonical file and the cached, memory-mapped representation of the helix.index file
 use crate::helix_index::EntryFlags;
 
-use super::format::{Entry, Footer, FormatError, Header, FOOTER_SIZE};
+use super::format::{self, Entry, Extensions, Footer, FormatError, Header, FOOTER_SIZE};
+use super::journal::Journal;
+use super::migrate;
 use anyhow::{Context, Result};
 use memmap2::Mmap;
 use rayon::prelude::*;
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -15,18 +15,18 @@ pub struct Reader {
     repo_path: PathBuf,
 }
 
-/// Canonical helix index
+/// Canonical helix index. Entries are sorted by path (see format.rs).
 #[derive(Debug, Clone)]
 pub struct HelixIndex {
     pub header: Header,
     pub entries: Vec<Entry>,
+    pub extensions: Extensions,
 }
 
-/// Cached, optimized view of helix.idx (mmap + indices)
+/// Cached, optimized view of helix.idx (mmap + sorted entries)
 pub struct CachedHelixIndex {
     _mmap: Mmap, // Keep mmap alive
     data: HelixIndex,
-    path_index: HashMap<PathBuf, usize>,
 }
 
 impl Reader {
@@ -37,21 +37,81 @@ impl Reader {
     }
 
     /// Read and parse helix.idx into a memory mapped file for fast reads
+    ///
+    /// If helix.idx fails to parse or its checksum doesn't match, the previous
+    /// generation in helix.idx.bak is used instead (when it is valid).
+    /// Updates in helix.idx.journal are merged on top of the base.
     pub fn read(&self) -> Result<HelixIndex> {
+        let (_mmap, data) = self.load()?;
+        Ok(data)
+    }
+
+    /// Read the previous generation kept alongside helix.idx by canonical writes
+    pub fn read_backup(&self) -> Result<HelixIndex> {
+        let (_mmap, data) = self.load_file(&self.repo_path.join(".helix/helix.idx.bak"))?;
+        Ok(data)
+    }
+
+    /// Map and parse helix.idx, falling back to helix.idx.bak on corruption
+    fn load(&self) -> Result<(Mmap, HelixIndex)> {
         let index_path = self.repo_path.join(".helix/helix.idx");
 
         if !index_path.exists() {
             anyhow::bail!("helix.idx does not exist at {}", index_path.display());
         }
+        // An index written by an older helix is upgraded before anything reads it
+        migrate::migrate_in_place(&self.repo_path)?;
+
+        match self.load_file(&index_path) {
+            Ok(loaded) => Ok(loaded),
+            Err(err) => {
+                let backup_path = self.repo_path.join(".helix/helix.idx.bak");
+                match self.load_file(&backup_path) {
+                    Ok(loaded) => {
+                        eprintln!(
+                            "warning: helix.idx is corrupted ({:#}), using previous generation {}",
+                            err, loaded.1.header.generation
+                        );
+                        Ok(loaded)
+                    }
+                    Err(_) => Err(err),
+                }
+            }
+        }
+    }
+
+    fn load_file(&self, path: &Path) -> Result<(Mmap, HelixIndex)> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to mmap {}", path.display()))?;
 
-        let file = File::open(&index_path).context("Failed to open helix.idx")?;
+        let mut data = self.parse(&mmap)?;
 
-        let mmap = unsafe { Mmap::map(&file) }.context("Failed to mmap helix.idx")?;
+        // Merge split-index updates made on top of this base
+        let footer = Footer::from_bytes(&mmap[mmap.len() - FOOTER_SIZE..])?;
+        Journal::new(&self.repo_path).apply(&mut data, &footer.checksum)?;
 
-        self.parse(&mmap)
+        Ok((mmap, data))
     }
 
+    /// Parse helix.idx, rejecting entries that aren't sorted by path
     pub fn parse(&self, data: &[u8]) -> Result<HelixIndex> {
+        let index = self.parse_unchecked(data)?;
+        if let Some(i) = format::first_out_of_order(&index.entries) {
+            return Err(FormatError::InvalidEntry(format!(
+                "entry {} ({}) is out of order",
+                i,
+                index.entries[i].path.display()
+            ))
+            .into());
+        }
+        Ok(index)
+    }
+
+    /// Parse helix.idx without checking entry order, for repairing one that is wrong
+    pub fn parse_unchecked(&self, data: &[u8]) -> Result<HelixIndex> {
         if data.len() < Header::HEADER_SIZE + FOOTER_SIZE {
             anyhow::bail!("Index file too small");
         }
@@ -77,84 +137,35 @@ impl Reader {
             offset += Entry::ENTRY_MAX_SIZE;
         }
 
+        if offset > entries_end {
+            anyhow::bail!("Entries run past the footer");
+        }
+        // Extensions only hold caches, so bytes that don't parse as extensions
+        // (an older writer, a miscounted header) are dropped rather than
+        // failing the whole index; the checksum still covers them
+        let extensions = Extensions::from_bytes(&data[offset..entries_end]).unwrap_or_default();
+
         // Parse footer
         let footer = Footer::from_bytes(&data[data.len() - FOOTER_SIZE..])
             .context("Failed to parse footer")?;
 
-        // Verify checksum (parallel for large data)
-        let computed_checksum = if data.len() > 1_000_000 {
-            // Parallel checksum for large indexes (>1MB)
-            self.parallel_checksum(&data[0..data.len() - FOOTER_SIZE])
-        } else {
-            // Sequential for small indexes
-            let mut hasher = Sha256::new();
-            hasher.update(&data[0..data.len() - FOOTER_SIZE]);
-            hasher.finalize().into()
-        };
+        // Verify checksum
+        let computed_checksum = Footer::compute(&data[0..data.len() - FOOTER_SIZE]).checksum;
 
         if computed_checksum != footer.checksum {
             return Err(FormatError::ChecksumMismatch.into());
         }
 
-        Ok(HelixIndex { header, entries })
-    }
-
-    fn parallel_checksum(&self, data: &[u8]) -> [u8; 32] {
-        // Split data into chunks for parallel hashing
-        const CHUNK_SIZE: usize = 256 * 1024; // 256KB chunks
-
-        let chunks: Vec<_> = data.chunks(CHUNK_SIZE).collect();
-
-        // Hash each chunk in parallel
-        let chunk_hashes: Vec<_> = chunks
-            .par_iter()
-            .map(|chunk| {
-                let mut hasher = Sha256::new();
-                hasher.update(chunk);
-                hasher.finalize()
-            })
-            .collect();
-
-        // Combine chunk hashes
-        let mut final_hasher = Sha256::new();
-        for hash in chunk_hashes {
-            final_hasher.update(&hash);
-        }
-
-        final_hasher.finalize().into()
+        Ok(HelixIndex {
+            header,
+            entries,
+            extensions,
+        })
     }
 
     pub fn read_cached(&self) -> Result<CachedHelixIndex> {
-        let index_path = self.repo_path.join(".helix/helix.idx");
-
-        if !index_path.exists() {
-            anyhow::bail!("helix.idx does not exist at {}", index_path.display());
-        }
-
-        let file = File::open(&index_path).context("Failed to open helix.idx")?;
-        let mmap = unsafe { Mmap::map(&file) }.context("Failed to mmap helix.idx")?;
-
-        let data = self.parse(&mmap)?;
-
-        let path_index: HashMap<PathBuf, usize> = if data.entries.len() > 1000 {
-            data.entries
-                .par_iter()
-                .enumerate()
-                .map(|(i, entry)| (entry.path.clone(), i))
-                .collect()
-        } else {
-            data.entries
-                .iter()
-                .enumerate()
-                .map(|(i, entry)| (entry.path.clone(), i))
-                .collect()
-        };
-
-        Ok(CachedHelixIndex {
-            _mmap: mmap,
-            data,
-            path_index,
-        })
+        let (mmap, data) = self.load()?;
+        Ok(data.into_cached(mmap))
     }
 
     pub fn exists(&self) -> bool {
@@ -162,6 +173,7 @@ impl Reader {
     }
 
     pub fn read_header(&self) -> Result<Header> {
+        migrate::migrate_in_place(&self.repo_path)?;
         let index_path = self.repo_path.join(".helix/helix.idx");
         let mut file = File::open(&index_path).context("Failed to open helix.idx")?;
 
@@ -184,16 +196,14 @@ impl Reader {
 }
 
 impl CachedHelixIndex {
-    /// Get entry by path (O(1) lookup)
+    /// Get entry by path (O(log n) lookup)
     pub fn get(&self, path: &Path) -> Option<&Entry> {
-        self.path_index
-            .get(path)
-            .and_then(|&idx| self.data.entries.get(idx))
+        self.data.get(path)
     }
 
     /// Check if path exists in index
     pub fn contains(&self, path: &Path) -> bool {
-        self.path_index.contains_key(path)
+        self.data.get(path).is_some()
     }
 
     /// Get all entries
@@ -247,11 +257,7 @@ impl CachedHelixIndex {
 
     /// Get entries in a specific directory
     pub fn entries_in_dir(&self, dir: &Path) -> impl ParallelIterator<Item = &Entry> {
-        let dir = dir.to_path_buf();
-        self.data
-            .entries
-            .par_iter()
-            .filter(move |e| e.path.starts_with(&dir))
+        self.data.entries_under(dir).par_iter()
     }
 
     /// Collect staged paths
@@ -299,40 +305,44 @@ impl CachedHelixIndex {
 }
 
 impl HelixIndex {
-    /// Get entry by path (O(n) lookup)
+    /// Get entry by path (O(log n) lookup)
     pub fn get(&self, path: &Path) -> Option<&Entry> {
-        self.entries.iter().find(|e| e.path == path)
+        find(&self.entries, path).ok().map(|i| &self.entries[i])
     }
 
-    /// Get mutable entry by path (O(n) lookup)
+    /// Get mutable entry by path (O(log n) lookup)
     pub fn get_mut(&mut self, path: &Path) -> Option<&mut Entry> {
-        self.entries.iter_mut().find(|e| e.path == path)
+        find(&self.entries, path).ok().map(|i| &mut self.entries[i])
+    }
+
+    /// Entries at or below `dir`; the whole index for an empty path
+    pub fn entries_under(&self, dir: &Path) -> &[Entry] {
+        entries_under(&self.entries, dir)
     }
 
     /// Build a cached index from this data
     pub fn into_cached(self, mmap: Mmap) -> CachedHelixIndex {
-        let path_index: HashMap<PathBuf, usize> = if self.entries.len() > 1000 {
-            self.entries
-                .par_iter()
-                .enumerate()
-                .map(|(i, entry)| (entry.path.clone(), i))
-                .collect()
-        } else {
-            self.entries
-                .iter()
-                .enumerate()
-                .map(|(i, entry)| (entry.path.clone(), i))
-                .collect()
-        };
-
         CachedHelixIndex {
             _mmap: mmap,
             data: self,
-            path_index,
         }
     }
 }
 
+/// Binary search sorted `entries` for `path`: its position, or where it would go
+pub fn find(entries: &[Entry], path: &Path) -> Result<usize, usize> {
+    entries.binary_search_by(|e| e.path.as_path().cmp(path))
+}
+
+/// The run of sorted `entries` at or below `dir`. Paths order component by
+/// component, so `dir` and everything inside it sort together: "a", "a/b",
+/// "a/c", and only then "a-b" and "a.txt".
+pub fn entries_under<'a>(entries: &'a [Entry], dir: &Path) -> &'a [Entry] {
+    let start = entries.partition_point(|e| e.path.as_path() < dir);
+    let len = entries[start..].partition_point(|e| e.path.starts_with(dir));
+    &entries[start..start + len]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -341,6 +351,46 @@ mod tests {
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_read_falls_back_to_previous_generation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, 0), &[])?;
+        let entry = Entry::new(PathBuf::from("a.txt"), 10, 100, hash::ZERO_HASH, 0o100644);
+        writer.write(&Header::new(2, 1), &[entry])?;
+
+        // Simulate a torn write of the latest generation
+        let index_path = repo_path.join(".helix/helix.idx");
+        let mut contents = fs::read(&index_path)?;
+        contents[Header::HEADER_SIZE + 10] ^= 0xFF;
+        fs::write(&index_path, contents)?;
+
+        let index = Reader::new(repo_path).read()?;
+        assert_eq!(index.header.generation, 1);
+        assert!(index.entries.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_corrupted_without_backup_fails() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        Writer::new_canonical(repo_path).write(&Header::new(1, 0), &[])?;
+
+        let index_path = repo_path.join(".helix/helix.idx");
+        let mut contents = fs::read(&index_path)?;
+        contents[20] ^= 0xFF;
+        fs::write(&index_path, contents)?;
+
+        assert!(Reader::new(repo_path).read().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_parallel_filtering() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -456,4 +506,75 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_sorted_entries_lookup_and_ranges() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let paths = [
+            "src/main.rs",
+            "a.txt",
+            "src-old/x.rs",
+            "src/bin/cli.rs",
+            "src.txt",
+        ];
+        let entries: Vec<_> = paths
+            .iter()
+            .map(|p| Entry::new(PathBuf::from(p), 1, 100, hash::ZERO_HASH, 0o100644))
+            .collect();
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, entries.len() as u32), &entries)?;
+
+        let index = Reader::new(repo_path).read()?;
+        let names = |entries: &[Entry]| -> Vec<String> {
+            entries
+                .iter()
+                .map(|e| e.path.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(
+            names(&index.entries),
+            [
+                "a.txt",
+                "src/bin/cli.rs",
+                "src/main.rs",
+                "src-old/x.rs",
+                "src.txt"
+            ]
+        );
+        assert_eq!(
+            names(index.entries_under(Path::new("src"))),
+            ["src/bin/cli.rs", "src/main.rs"]
+        );
+        assert_eq!(
+            names(index.entries_under(Path::new("src/bin"))),
+            ["src/bin/cli.rs"]
+        );
+        assert!(index.entries_under(Path::new("sr")).is_empty());
+        assert_eq!(index.entries_under(Path::new("")).len(), 5);
+        assert!(index.get(Path::new("src/main.rs")).is_some());
+        assert!(index.get(Path::new("src")).is_none());
+
+        // The writer refuses a path twice
+        let twice = vec![entries[0].clone(), entries[1].clone(), entries[0].clone()];
+        assert!(writer.write(&Header::new(2, 3), &twice).is_err());
+
+        // And the reader refuses a file that isn't in order
+        let mut bytes = Header::new(3, 2).to_bytes().to_vec();
+        bytes.extend_from_slice(&entries[0].to_bytes()?);
+        bytes.extend_from_slice(&entries[1].to_bytes()?);
+        let footer = Footer::compute(&bytes);
+        bytes.extend_from_slice(&footer.to_bytes());
+        assert!(Reader::new(repo_path).parse(&bytes).is_err());
+        assert_eq!(
+            Reader::new(repo_path)
+                .parse_unchecked(&bytes)?
+                .entries
+                .len(),
+            2
+        );
+
+        Ok(())
+    }
 }
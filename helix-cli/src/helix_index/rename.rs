@@ -0,0 +1,200 @@
+/*
+Rename detection between two snapshots.
+
+Trees only record paths, so a moved file shows up as one path deleted and
+another added. Deleted and added files are paired up in two passes, like Git:
+
+1. Exact: identical blob hashes. Cheap, and covers plain `mv`.
+2. Inexact: remaining pairs scored by line similarity (bytes in lines both
+   sides share, over the total size). Each deleted file goes to the best-scoring
+   added file at or above the threshold; best pairs are taken first.
+
+The inexact pass is quadratic, so it is skipped when either side has more than
+MAX_INEXACT_CANDIDATES files (Git's diff.renameLimit serves the same purpose).
+*/
+use helix_protocol::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// Minimum similarity for an inexact rename (Git's default is 50%)
+pub const DEFAULT_RENAME_THRESHOLD: f32 = 0.5;
+
+const MAX_INEXACT_CANDIDATES: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// 1.0 for an exact rename
+    pub similarity: f32,
+}
+
+/// Pair deleted files with added files they were renamed to.
+///
+/// `read_blob` loads content for the inexact pass; blobs it can't load are
+/// only matched exactly.
+pub fn detect_renames(
+    deleted: &[(PathBuf, Hash)],
+    added: &[(PathBuf, Hash)],
+    threshold: f32,
+    read_blob: impl Fn(&Hash) -> Option<Vec<u8>>,
+) -> Vec<Rename> {
+    let mut renames = Vec::new();
+    let mut used_added: HashSet<usize> = HashSet::new();
+    let mut used_deleted: HashSet<usize> = HashSet::new();
+
+    // Exact pass: prefer the added path with the same file name
+    let mut added_by_hash: HashMap<&Hash, Vec<usize>> = HashMap::new();
+    for (i, (_, hash)) in added.iter().enumerate() {
+        added_by_hash.entry(hash).or_default().push(i);
+    }
+    for (d, (from, hash)) in deleted.iter().enumerate() {
+        let Some(candidates) = added_by_hash.get(hash) else {
+            continue;
+        };
+        let free = candidates
+            .iter()
+            .copied()
+            .filter(|i| !used_added.contains(i));
+        let best = free
+            .clone()
+            .find(|&i| added[i].0.file_name() == from.file_name())
+            .or_else(|| free.clone().next());
+
+        if let Some(a) = best {
+            used_added.insert(a);
+            used_deleted.insert(d);
+            renames.push(Rename {
+                from: from.clone(),
+                to: added[a].0.clone(),
+                similarity: 1.0,
+            });
+        }
+    }
+
+    let remaining_deleted: Vec<usize> = (0..deleted.len())
+        .filter(|d| !used_deleted.contains(d))
+        .collect();
+    let remaining_added: Vec<usize> = (0..added.len())
+        .filter(|a| !used_added.contains(a))
+        .collect();
+
+    if remaining_deleted.is_empty()
+        || remaining_added.is_empty()
+        || remaining_deleted.len() > MAX_INEXACT_CANDIDATES
+        || remaining_added.len() > MAX_INEXACT_CANDIDATES
+    {
+        return renames;
+    }
+
+    // Inexact pass
+    let added_content: HashMap<usize, Vec<u8>> = remaining_added
+        .iter()
+        .filter_map(|&a| read_blob(&added[a].1).map(|content| (a, content)))
+        .collect();
+
+    let mut scored = Vec::new();
+    for &d in &remaining_deleted {
+        let Some(old) = read_blob(&deleted[d].1) else {
+            continue;
+        };
+        for (&a, new) in &added_content {
+            let score = similarity(&old, new);
+            if score >= threshold {
+                scored.push((score, d, a));
+            }
+        }
+    }
+
+    // Best pairs first; ties broken by path order so results are stable
+    scored.sort_by(|x, y| {
+        y.0.total_cmp(&x.0)
+            .then_with(|| deleted[x.1].0.cmp(&deleted[y.1].0))
+            .then_with(|| added[x.2].0.cmp(&added[y.2].0))
+    });
+
+    for (score, d, a) in scored {
+        if used_deleted.contains(&d) || used_added.contains(&a) {
+            continue;
+        }
+        used_deleted.insert(d);
+        used_added.insert(a);
+        renames.push(Rename {
+            from: deleted[d].0.clone(),
+            to: added[a].0.clone(),
+            similarity: score,
+        });
+    }
+
+    renames
+}
+
+/// How alike two files are, from 0.0 to 1.0: the bytes of lines they have in
+/// common (counting repeats) over their average size
+pub fn similarity(old: &[u8], new: &[u8]) -> f32 {
+    if old.is_empty() && new.is_empty() {
+        return 1.0;
+    }
+
+    let mut lines: HashMap<&[u8], usize> = HashMap::new();
+    for line in old.split_inclusive(|&b| b == b'\n') {
+        *lines.entry(line).or_default() += 1;
+    }
+
+    let mut common = 0;
+    for line in new.split_inclusive(|&b| b == b'\n') {
+        if let Some(count) = lines.get_mut(line) {
+            if *count > 0 {
+                *count -= 1;
+                common += line.len();
+            }
+        }
+    }
+
+    (2 * common) as f32 / (old.len() + new.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity(b"a\nb\n", b"a\nb\n"), 1.0);
+        assert_eq!(similarity(b"a\n", b"b\n"), 0.0);
+        assert!((similarity(b"a\nb\nc\nd\n", b"a\nb\nc\nx\n") - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_detect_exact_and_inexact_renames() {
+        let blobs: HashMap<Hash, &[u8]> = HashMap::from([
+            ([1u8; 32], b"same\ncontent\n".as_slice()),
+            ([2u8; 32], b"one\ntwo\nthree\nfour\n".as_slice()),
+            ([3u8; 32], b"one\ntwo\nthree\nfive\n".as_slice()),
+            ([4u8; 32], b"unrelated\n".as_slice()),
+            ([5u8; 32], b"something else\n".as_slice()),
+        ]);
+        let read = |hash: &Hash| blobs.get(hash).map(|content| content.to_vec());
+
+        let deleted = vec![
+            (PathBuf::from("old/lib.rs"), [1u8; 32]),
+            (PathBuf::from("notes.txt"), [2u8; 32]),
+            (PathBuf::from("gone.txt"), [4u8; 32]),
+        ];
+        let added = vec![
+            (PathBuf::from("new/lib.rs"), [1u8; 32]),
+            (PathBuf::from("docs/notes.txt"), [3u8; 32]),
+            (PathBuf::from("fresh.txt"), [5u8; 32]),
+        ];
+
+        let renames = detect_renames(&deleted, &added, DEFAULT_RENAME_THRESHOLD, read);
+
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].from, PathBuf::from("old/lib.rs"));
+        assert_eq!(renames[0].to, PathBuf::from("new/lib.rs"));
+        assert_eq!(renames[0].similarity, 1.0);
+        assert_eq!(renames[1].from, PathBuf::from("notes.txt"));
+        assert_eq!(renames[1].to, PathBuf::from("docs/notes.txt"));
+        assert!(renames[1].similarity < 1.0);
+    }
+}
@@ -0,0 +1,313 @@
+/*
+Persistent Git SHA <-> Helix hash map for imported (and exported) commits.
+
+Stored in `.helix/maps/commits` as a small binary file:
+
+  "HXRM" | u32 version (1) | records...
+
+where each record is a 20-byte Git SHA-1 followed by the 32-byte Helix hash.
+Records are only ever appended while an import runs, so a crash loses at most
+the record being written; a truncated trailing record is ignored on load.
+`save` rewrites the file sorted by Git SHA through a temp file and rename, so
+the same history always produces the same bytes.
+
+Repositories imported before this file existed kept the map as text in
+`.helix/git-commit-mapping` ("<helix hex> <git hex>" per line). `open` reads
+that file when there's no binary map yet and moves it over.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A Git object id (SHA-1)
+pub type GitSha = [u8; 20];
+
+const MAGIC: &[u8; 4] = b"HXRM";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 8;
+const RECORD_SIZE: usize = 20 + 32;
+
+pub struct RevMap {
+    path: PathBuf,
+    to_helix: HashMap<GitSha, Hash>,
+    to_git: HashMap<Hash, GitSha>,
+}
+
+impl RevMap {
+    /// Where the map lives in a repository
+    pub fn path_for(repo_path: &Path) -> PathBuf {
+        repo_path.join(".helix").join("maps").join("commits")
+    }
+
+    fn legacy_path(repo_path: &Path) -> PathBuf {
+        repo_path.join(".helix").join("git-commit-mapping")
+    }
+
+    /// Whether a map was ever written (i.e. Git history was imported)
+    pub fn exists(repo_path: &Path) -> bool {
+        Self::path_for(repo_path).exists() || Self::legacy_path(repo_path).exists()
+    }
+
+    /// Load the map, empty if there isn't one yet
+    pub fn open(repo_path: &Path) -> Result<Self> {
+        let mut map = Self {
+            path: Self::path_for(repo_path),
+            to_helix: HashMap::new(),
+            to_git: HashMap::new(),
+        };
+
+        if map.path.exists() {
+            let data = fs::read(&map.path)
+                .with_context(|| format!("Failed to read {}", map.path.display()))?;
+            map.parse(&data)?;
+            return Ok(map);
+        }
+
+        let legacy = Self::legacy_path(repo_path);
+        if legacy.exists() {
+            for line in fs::read_to_string(&legacy)?.lines() {
+                let Some((helix_hex, git_hex)) = line.split_once(' ') else {
+                    continue;
+                };
+                let git: GitSha = hex::decode(git_hex.trim())?
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Invalid Git SHA in {}", legacy.display()))?;
+                map.insert(git, hex_to_hash(helix_hex)?);
+            }
+            map.save()?;
+            fs::remove_file(&legacy)?;
+        }
+
+        Ok(map)
+    }
+
+    fn parse(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() < HEADER_SIZE || &data[..4] != MAGIC {
+            bail!("{} is not a Helix commit map", self.path.display());
+        }
+        let version = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        if version != VERSION {
+            bail!(
+                "{} has unsupported version {}",
+                self.path.display(),
+                version
+            );
+        }
+
+        // A torn final record from an interrupted append is dropped
+        for record in data[HEADER_SIZE..].chunks_exact(RECORD_SIZE) {
+            let git: GitSha = record[..20].try_into().unwrap();
+            let helix: Hash = record[20..].try_into().unwrap();
+            self.insert(git, helix);
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.to_helix.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_helix.is_empty()
+    }
+
+    pub fn helix_for(&self, git: &GitSha) -> Option<Hash> {
+        self.to_helix.get(git).copied()
+    }
+
+    pub fn git_for(&self, helix: &Hash) -> Option<GitSha> {
+        self.to_git.get(helix).copied()
+    }
+
+    /// Record a pair in memory; `append` or `save` writes it out
+    pub fn insert(&mut self, git: GitSha, helix: Hash) {
+        if let Some(old) = self.to_helix.insert(git, helix) {
+            self.to_git.remove(&old);
+        }
+        self.to_git.insert(helix, git);
+    }
+
+    /// Pairs sorted by Git SHA
+    pub fn entries(&self) -> Vec<(GitSha, Hash)> {
+        let mut entries: Vec<(GitSha, Hash)> =
+            self.to_helix.iter().map(|(g, h)| (*g, *h)).collect();
+        entries.sort();
+        entries
+    }
+
+    /// Insert `pairs` and append them to the file, creating it if needed
+    pub fn append(&mut self, pairs: &[(GitSha, Hash)]) -> Result<()> {
+        if pairs.is_empty() {
+            return Ok(());
+        }
+        if !self.path.exists() {
+            for (git, helix) in pairs {
+                self.insert(*git, *helix);
+            }
+            return self.save();
+        }
+
+        let mut bytes = Vec::with_capacity(pairs.len() * RECORD_SIZE);
+        for (git, helix) in pairs {
+            self.insert(*git, *helix);
+            bytes.extend_from_slice(git);
+            bytes.extend_from_slice(helix);
+        }
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Drop every pair, in memory and on disk
+    pub fn clear(&mut self) -> Result<()> {
+        self.to_helix.clear();
+        self.to_git.clear();
+        self.save()
+    }
+
+    /// Rewrite the file from memory, sorted
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let entries = self.entries();
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + entries.len() * RECORD_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        for (git, helix) in &entries {
+            bytes.extend_from_slice(git);
+            bytes.extend_from_slice(helix);
+        }
+
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, &bytes).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Translate a full or abbreviated id from either side. A 40-character
+    /// id is looked up as a Git SHA and a 64-character one as a Helix hash;
+    /// shorter prefixes are matched against both, and must be unambiguous.
+    pub fn lookup(&self, id: &str) -> Result<RevMapping> {
+        let id = id.trim().to_lowercase();
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("'{}' is not a commit id", id);
+        }
+
+        if id.len() == 40 {
+            let git: GitSha = hex::decode(&id)?.try_into().unwrap();
+            return self
+                .helix_for(&git)
+                .map(|helix| RevMapping::ToHelix(git, helix))
+                .with_context(|| format!("Git commit {} was not imported", id));
+        }
+        if id.len() == 64 {
+            let helix = hex_to_hash(&id)?;
+            return self
+                .git_for(&helix)
+                .map(|git| RevMapping::ToGit(helix, git))
+                .with_context(|| format!("Helix commit {} has no Git counterpart", id));
+        }
+
+        let mut matches: Vec<RevMapping> = self
+            .to_helix
+            .iter()
+            .filter(|(git, _)| hex::encode(git).starts_with(&id))
+            .map(|(git, helix)| RevMapping::ToHelix(*git, *helix))
+            .chain(
+                self.to_git
+                    .iter()
+                    .filter(|(helix, _)| hash_to_hex(helix).starts_with(&id))
+                    .map(|(helix, git)| RevMapping::ToGit(*helix, *git)),
+            )
+            .collect();
+
+        match matches.len() {
+            0 => bail!("No mapped commit matches '{}'", id),
+            1 => Ok(matches.remove(0)),
+            n => bail!("'{}' is ambiguous ({} mapped commits match)", id, n),
+        }
+    }
+}
+
+/// Result of `RevMap::lookup`: the id that matched and its counterpart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevMapping {
+    ToHelix(GitSha, Hash),
+    ToGit(Hash, GitSha),
+}
+
+impl RevMapping {
+    /// The id on the other side, in hex
+    pub fn counterpart_hex(&self) -> String {
+        match self {
+            RevMapping::ToHelix(_, helix) => hash_to_hex(helix),
+            RevMapping::ToGit(_, git) => hex::encode(git),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rev_map_persists_and_translates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+
+        // A map left behind by an older import is migrated
+        fs::write(
+            repo.join(".helix/git-commit-mapping"),
+            format!(
+                "{} {}\n",
+                hash_to_hex(&[1u8; 32]),
+                hex::encode([0xaau8; 20])
+            ),
+        )?;
+        let mut map = RevMap::open(repo)?;
+        assert_eq!(map.helix_for(&[0xaa; 20]), Some([1u8; 32]));
+        assert!(!repo.join(".helix/git-commit-mapping").exists());
+
+        map.append(&[([0xbb; 20], [2u8; 32])])?;
+        let written = fs::read(RevMap::path_for(repo))?;
+
+        // Half a record from an interrupted append is ignored
+        let mut torn = written.clone();
+        torn.extend_from_slice(&[0xcc; 30]);
+        fs::write(RevMap::path_for(repo), &torn)?;
+
+        let map = RevMap::open(repo)?;
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.git_for(&[2u8; 32]), Some([0xbb; 20]));
+
+        // Saving is deterministic: sorted records, no torn tail
+        map.save()?;
+        assert_eq!(fs::read(RevMap::path_for(repo))?, written);
+
+        assert_eq!(
+            map.lookup(&hex::encode([0xbbu8; 20]))?,
+            RevMapping::ToHelix([0xbb; 20], [2u8; 32])
+        );
+        assert_eq!(
+            map.lookup("0202")?.counterpart_hex(),
+            hex::encode([0xbbu8; 20])
+        );
+        assert!(map.lookup("ff").is_err());
+        assert!(map.lookup("zz").is_err());
+
+        Ok(())
+    }
+}
@@ -4,8 +4,12 @@
 //   [branch "feature"]
 //   upstream = main
 //
+//   [core]
+//   ignorecase = true
+//
 // State stored here:
 //   - Upstream branch relationships
+//   - Repo-local settings detected from the working filesystem ([core])
 //   - Future per-branch metadata
 
 use anyhow::{Context, Result};
@@ -15,6 +19,7 @@ use std::path::Path;
 use crate::init_command::create_directory_structure;
 
 const SECTION_PREFIX: &str = "branch";
+const CORE_SECTION: &str = "core";
 
 fn state_path(repo_path: &Path) -> std::path::PathBuf {
     repo_path.join(".helix/state")
@@ -78,6 +83,21 @@ pub fn remove_branch_state(repo_path: &Path, branch_name: &str) -> Result<()> {
     save_state(repo_path, &ini)
 }
 
+/// Set a key in the [core] section
+pub fn set_core_value(repo_path: &Path, key: &str, value: &str) -> Result<()> {
+    let mut ini = load_state(repo_path)?;
+    ini.with_section(Some(CORE_SECTION)).set(key, value);
+    save_state(repo_path, &ini)
+}
+
+/// Get a key from the [core] section
+pub fn get_core_value(repo_path: &Path, key: &str) -> Option<String> {
+    let ini = load_state(repo_path).ok()?;
+    ini.section(Some(CORE_SECTION))
+        .and_then(|sec| sec.get(key))
+        .map(|s| s.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,6 +172,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_core_values_alongside_branches() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+
+        set_branch_upstream(repo, "feature", "main")?;
+        set_core_value(repo, "ignorecase", "true")?;
+
+        assert_eq!(get_core_value(repo, "ignorecase"), Some("true".to_string()));
+        assert_eq!(get_core_value(repo, "missing"), None);
+        assert_eq!(
+            get_branch_upstream(repo, "feature"),
+            Some("main".to_string())
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_nonexistent_branch() -> Result<()> {
         let temp = TempDir::new()?;
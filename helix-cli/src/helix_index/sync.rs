@@ -1,9 +1,10 @@
 /*
 Sync engine for bootstrapping a Helix repo from an existing Git repo.
 
-This module is only used during `helix init`. It performs a one-shot import of
-Git state into Helix’s own storage. After that, Helix runs independently of
-`.git`.
+This module is used by `helix init`, which performs a full import of Git state
+into Helix’s own storage, and by `helix import --update`, which brings over
+only what changed in Git since then. Between imports, Helix runs independently
+of `.git`.
 
 High-level responsibilities
 ---------------------------
@@ -18,13 +19,15 @@ SyncEngine::import_from_git wires together the full import pipeline:
    - Walks commits with gix (oldest to newest).
    - Builds Helix commits (Helix_Commit) and trees (TreeBuilder).
    - Writes them to `.helix/objects/commits` and `.helix/objects/trees`.
-   - Builds a git_hash_to_helix_hash map (Git SHA -> Helix commit hash).
+   - Builds a git_hash_to_helix_hash map (Git SHA -> Helix commit hash),
+     persisted in `.helix/maps/commits`.
    - Updates `.helix/HEAD` to point at the latest commit.
 4. Imports refs:
-   - Branches: copies `.git/refs/heads/...` to `.helix/refs/heads/...`
-     using the Git->Helix commit map.
-   - Tags: copies `.git/refs/tags/...` to `.helix/refs/tags/...`
-     (nested paths preserved), also via the Git->Helix map.
+   - Branches: copies Git branches (loose or packed, read through gix) to
+     `.helix/refs/heads/...` using the Git->Helix commit map.
+   - Tags: copies Git tags to `.helix/refs/tags/...` (nested paths
+     preserved), also via the Git->Helix map. Annotated tags become Helix tag
+     objects; if the annotation can't be kept the tag is peeled to its commit.
    - HEAD:
      - If symbolic (for example `ref: refs/heads/main`), mirror it into `.helix/HEAD`.
      - If detached, rewrite the Git SHA to the corresponding Helix commit hash.
@@ -33,6 +36,20 @@ SyncEngine::import_from_git wires together the full import pipeline:
    - Writes them into `helix.toml` under a `[remotes]` table as
      `[remotes.<name>]` entries with URLs and refspecs.
 
+Incremental updates
+-------------------
+The Git SHA <-> Helix hash map is kept in `.helix/maps/commits` (see
+rev_map.rs; `helix rev-map` translates ids). SyncEngine::import_update walks back
+from every Git branch and tag until it reaches mapped commits, converts only
+the new ones, and moves Helix refs that still point at imported commits. Refs
+carrying Helix-only commits are reported and left alone.
+
+The first import fills the same file as it goes: commits are converted in
+batches, and each finished batch is stored and appended to the mapping. When
+the cancel flag (Ctrl-C) is set, the import stops before the next batch and
+leaves `.helix/import-in-progress` behind; SyncEngine::resume_import reruns it
+skipping every commit already mapped.
+
 After this import
 -----------------
 - `.helix/helix.idx` is the canonical index; Helix never writes `.git/index`.
@@ -60,40 +77,92 @@ Flags are derived during import_git_index as follows:
 - UNTRACKED -> not handled here; only set later by FSMonitor / working-tree
                discovery for paths not in helix.idx.
 
+Blob contents are never held in memory whole where it can be avoided: loose
+Git objects are decompressed straight into Helix storage in chunks, working
+tree files are hashed and copied as streams, and index entries unchanged from
+a HEAD whose blobs Helix already stores aren't read at all. Only packed
+objects, which gix must rebuild from deltas, are loaded in one piece.
+
 This file is intentionally read-only with respect to Git: it never mutates
 `.git/`. It only reads Git state and materializes the corresponding Helix view
 under `.helix/` and `helix.toml`.
 */
 
-use super::commit::Commit as Helix_Commit;
+use super::commit::{Commit as Helix_Commit, CommitStore, GIT_METADATA_HEADER};
 use super::format::{Entry, EntryFlags, Header};
 use super::reader::Reader;
+use super::rev_map::{GitSha, RevMap};
 use super::state::set_branch_upstream;
-use super::tree::TreeBuilder;
+use super::tree::{Tree, TreeEntry, TreeStore};
 use super::writer::Writer;
 use crate::ignore::IgnoreRules;
 use crate::index::GitIndex;
-use crate::init_command::{HelixConfig, IgnoreSection, RemotesTable};
+use crate::init_command::{HelixConfig, IgnoreSection, RemoteConfig, RemotesTable};
 use anyhow::{Context, Result};
 use console::style;
+use dashmap::DashMap;
 use gix::revision::walk::Sorting;
 use gix::{ObjectId, Repository};
-use hash::compute_blob_oid;
-use helix_protocol::hash::{self, hash_to_hex, Hash, ZERO_HASH};
+use hash::compute_blob_oid_stream;
+use helix_protocol::hash::{self, Hash, ZERO_HASH};
 use helix_protocol::message::ObjectType;
 use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::{peel_to_commit, Tag};
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::{BufRead, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use toml::{value::Table, Value};
-use walkdir::WalkDir;
+
+/// Commits converted and saved together by `helix init`. Mapping state is
+/// written after each batch, so an interrupted import loses at most one.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// Present while `helix init` is importing; lets `helix init --resume` pick up
+/// an import that was interrupted
+const IMPORT_MARKER: &str = ".helix/import-in-progress";
 
 pub struct SyncEngine {
     repo_path: PathBuf,
+    cancel: Arc<AtomicBool>,
+    allow_missing_objects: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct UpdateSummary {
+    pub commits_count: usize,
+    /// Full ref names ("refs/heads/main") moved to the Git tip
+    pub refs_updated: Vec<String>,
+    /// Refs not moved because they have Helix-only commits
+    pub refs_skipped: Vec<String>,
+    /// Set when the current branch moved and the index was re-imported
+    pub files_count: Option<usize>,
+}
+
+/// Git objects already converted during this import, shared across workers.
+/// Trees map to None when they contain no files (only submodules).
+#[derive(Default)]
+struct ImportCache {
+    blobs: DashMap<ObjectId, (Hash, u64)>,
+    trees: DashMap<ObjectId, Option<Hash>>,
+}
+
+/// A Git commit read on a worker thread, before its Helix hash is known
+struct GitCommitData {
+    id: ObjectId,
+    parents: Vec<ObjectId>,
+    tree_hash: Hash,
+    author: String,
+    author_time: u64,
+    commit_time: u64,
+    message: String,
+    headers: BTreeMap<String, String>,
 }
 
 pub struct ImportSummary {
@@ -107,12 +176,46 @@ impl SyncEngine {
     pub fn new(repo_path: &Path) -> Self {
         Self {
             repo_path: repo_path.to_path_buf(),
+            cancel: Arc::default(),
+            allow_missing_objects: false,
         }
     }
 
+    /// Stop the import at the next commit batch once `cancel` is set (e.g. by
+    /// a Ctrl-C handler). Everything up to the last finished batch is kept.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Store imported commits even when their tree or a parent is missing, for
+    /// recovering from a store that has already lost objects
+    pub fn with_allow_missing_objects(mut self, allow: bool) -> Self {
+        self.allow_missing_objects = allow;
+        self
+    }
+
+    /// Whether an earlier import was interrupted before it finished
+    pub fn import_interrupted(&self) -> bool {
+        self.repo_path.join(IMPORT_MARKER).exists()
+    }
+
     pub fn import_from_git(&self) -> Result<()> {
+        self.run_import(false)
+    }
+
+    /// Continue an interrupted import, skipping commits it already converted
+    pub fn resume_import(&self) -> Result<()> {
+        if !self.import_interrupted() {
+            anyhow::bail!("No interrupted import to resume");
+        }
+        self.run_import(true)
+    }
+
+    fn run_import(&self, resume: bool) -> Result<()> {
         let _ = wait_for_git_lock(&self.repo_path, Duration::from_secs(1));
         let store = FsObjectStore::new(&self.repo_path);
+        fs::write(self.repo_path.join(IMPORT_MARKER), "")?;
 
         println!("\n  {}", style("Helix").bold());
         println!(
@@ -129,9 +232,19 @@ impl SyncEngine {
         main_pb.set_message("Analyzing Git history...");
         main_pb.enable_steady_tick(Duration::from_millis(80));
 
+        tracing::debug!(repo = %self.repo_path.display(), resume, "git import");
         let file_count = self.import_git_index(&store)?;
-        let mapping = self.import_git_commits(&store, &main_pb)?;
+        tracing::debug!(files = file_count, "imported git index");
+        let mapping = match self.import_git_commits(&store, &main_pb, resume) {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                main_pb.finish_and_clear();
+                tracing::debug!(error = %format!("{e:#}"), "commit import failed");
+                return Err(e);
+            }
+        };
         let commit_count = mapping.len();
+        tracing::debug!(commits = commit_count, "imported git commits");
 
         self.import_git_branches(&mapping)?;
         self.import_git_tags(&mapping)?;
@@ -139,11 +252,7 @@ impl SyncEngine {
 
         let remote_count = self.import_git_remotes()?;
         let author = self.import_git_config()?;
-
-        let mapping_path = self.repo_path.join(".helix/git-commit-mapping");
-        if mapping_path.exists() {
-            fs::remove_file(&mapping_path).ok();
-        }
+        fs::remove_file(self.repo_path.join(IMPORT_MARKER))?;
 
         main_pb.finish_and_clear();
 
@@ -194,6 +303,114 @@ impl SyncEngine {
         Ok(())
     }
 
+    /// Import Git commits made since the last import and move refs to match.
+    ///
+    /// Commits already listed in .helix/maps/commits are skipped, so only
+    /// new commits (and their trees and blobs) are converted. A Helix branch or
+    /// tag is only moved if it doesn't exist yet or still points at a commit that
+    /// came from Git; refs with Helix-only commits on them are left alone.
+    pub fn import_update(&self) -> Result<UpdateSummary> {
+        let _ = wait_for_git_lock(&self.repo_path, Duration::from_secs(1));
+        let store = FsObjectStore::new(&self.repo_path);
+
+        let mut mapping = self.load_git_helix_mapping()?;
+        if mapping.is_empty() {
+            anyhow::bail!(
+                "No previous Git import found (.helix/maps/commits is missing). \
+                 Run `helix init` in a Git repository to import it first."
+            );
+        }
+        let imported: HashSet<[u8; 32]> = mapping.values().copied().collect();
+
+        let repo = gix::open(&self.repo_path)?;
+
+        // (ref name, ref target, Git commit) for every branch and tag, packed or loose
+        let mut git_refs: Vec<(String, ObjectId, ObjectId)> = Vec::new();
+        for mut reference in repo.references()?.all()?.filter_map(Result::ok) {
+            let name = reference.name().as_bstr().to_string();
+            if !name.starts_with("refs/heads/") && !name.starts_with("refs/tags/") {
+                continue;
+            }
+            let Some(target) = reference.try_id().map(|id| id.detach()) else {
+                continue;
+            };
+            if let Ok(commit) = reference.peel_to_commit() {
+                git_refs.push((name, target, commit.id));
+            }
+        }
+
+        // Walk back from each tip until reaching already-imported history,
+        // emitting parents before children
+        let mut new_commits: Vec<ObjectId> = Vec::new();
+        let mut visited: HashSet<ObjectId> = HashSet::new();
+        for (_, _, tip) in &git_refs {
+            let mut stack = vec![(*tip, false)];
+            while let Some((id, parents_done)) = stack.pop() {
+                if parents_done {
+                    new_commits.push(id);
+                    continue;
+                }
+                if mapping.contains_key(id.as_bytes()) || !visited.insert(id) {
+                    continue;
+                }
+
+                stack.push((id, true));
+                for parent in repo.find_commit(id)?.parent_ids() {
+                    stack.push((parent.detach(), false));
+                }
+            }
+        }
+
+        let helix_commits = self.convert_git_commits(&repo, &new_commits, &mut mapping)?;
+
+        self.store_imported_commits(&store, &helix_commits)?;
+        self.save_git_helix_mapping(&mapping)?;
+
+        let mut summary = UpdateSummary {
+            commits_count: helix_commits.len(),
+            ..Default::default()
+        };
+
+        for (name, target, commit_id) in &git_refs {
+            let helix_hash = if name.starts_with("refs/tags/") {
+                self.helix_tag_target(&repo, &store, *target, &mapping)?
+            } else {
+                mapping.get(commit_id.as_bytes()).copied()
+            };
+            let Some(helix_hash) = helix_hash else {
+                continue;
+            };
+
+            let ref_path = self.repo_path.join(".helix").join(name);
+            let current = fs::read_to_string(&ref_path)
+                .ok()
+                .and_then(|hex| hash::hex_to_hash(hex.trim()).ok());
+
+            match current {
+                Some(current) if current == helix_hash => continue,
+                Some(current) if !imported.contains(&peel_to_commit(&store, &current)?) => {
+                    summary.refs_skipped.push(name.clone());
+                    continue;
+                }
+                _ => {}
+            }
+
+            fs::create_dir_all(ref_path.parent().unwrap())?;
+            fs::write(&ref_path, hash::hash_to_hex(&helix_hash))?;
+            summary.refs_updated.push(name.clone());
+        }
+
+        // The working tree follows Git; refresh the index if our branch moved
+        let helix_head = fs::read_to_string(self.repo_path.join(".helix/HEAD"))?;
+        if let Some(current_ref) = helix_head.trim().strip_prefix("ref:") {
+            if summary.refs_updated.iter().any(|r| r == current_ref.trim()) {
+                summary.files_count = Some(self.import_git_index(&store)?);
+            }
+        }
+
+        Ok(summary)
+    }
+
     fn import_git_config(&self) -> Result<Option<String>> {
         let repo = gix::open(&self.repo_path)?;
         let config = repo.config_snapshot();
@@ -238,6 +455,15 @@ impl SyncEngine {
             .as_table_mut()
             .context("[user] is not a table in helix.toml")?;
 
+        // An identity already set in helix.toml outranks Git's
+        let has_name = user_table
+            .get("name")
+            .and_then(Value::as_str)
+            .is_some_and(|name| !name.trim().is_empty());
+        if has_name {
+            return Ok(None);
+        }
+
         user_table.insert("name".to_string(), Value::String(author_name.clone()));
         user_table.insert("email".to_string(), Value::String(author_email.clone()));
 
@@ -251,65 +477,63 @@ impl SyncEngine {
         Ok(Some(format!("{} <{}>", author_name, author_email)))
     }
 
-    // TODO: parallelize this as we walk the directory, we get the branch, transform it to a helix branch, get it's upstream adn then add it to the helix state
+    /// Copy Git branches (loose and packed) to .helix/refs/heads, nested names preserved
     fn import_git_branches(
         &self,
         git_hash_to_helix_hash: &HashMap<Vec<u8>, [u8; 32]>,
     ) -> Result<()> {
-        let git_refs_dir = self.repo_path.join(".git/refs/heads");
-        if !git_refs_dir.exists() {
-            return Ok(());
-        }
+        let repo = gix::open(&self.repo_path)?;
 
         // Track all branches we import for state file population
         let mut imported_branches = Vec::new();
 
-        for entry in WalkDir::new(&git_refs_dir) {
-            let entry = entry?;
-            if !entry.file_type().is_file() {
+        for reference in repo.references()?.local_branches()? {
+            let mut reference =
+                reference.map_err(|e| anyhow::anyhow!("Invalid Git branch ref: {}", e))?;
+            let full_name = reference.name().as_bstr().to_string();
+            let Some(branch_name) = full_name.strip_prefix("refs/heads/") else {
                 continue;
-            }
+            };
 
-            let git_sha = fs::read_to_string(entry.path())?.trim().to_string();
-            let git_sha_bytes: Vec<u8> = hex::decode(&git_sha)?;
+            let git_id = reference.peel_to_id()?.detach();
 
             // Convert Git SHA to Helix hash
-            let helix_hash = match git_hash_to_helix_hash.get(&git_sha_bytes) {
-                Some(hash) => hash,
-                None => {
-                    for (i, key) in git_hash_to_helix_hash.keys().take(3).enumerate() {
-                        eprintln!("  {}: {}", i, hex::encode(key));
-                    }
-                    continue;
-                }
+            let Some(helix_hash) = git_hash_to_helix_hash.get(git_id.as_bytes()) else {
+                eprintln!(
+                    "⚠️ Branch {} references unknown commit {} — skipping",
+                    branch_name, git_id
+                );
+                continue;
             };
 
             // Preserve branch path structure
-            let rel_path = entry.path().strip_prefix(&git_refs_dir)?;
-            let helix_ref_path = self.repo_path.join(".helix/refs/heads").join(rel_path);
+            let helix_ref_path = self.repo_path.join(".helix/refs/heads").join(branch_name);
 
             fs::create_dir_all(helix_ref_path.parent().unwrap())?;
             fs::write(&helix_ref_path, hash::hash_to_hex(helix_hash))?;
 
             // Track branch name for state import
-            if let Some(branch_name) = rel_path.to_str() {
-                imported_branches.push(branch_name.to_string());
-            }
+            imported_branches.push(branch_name.to_string());
         }
 
         // Import upstream tracking information from Git config
-        self.import_branch_upstream_tracking(&imported_branches)?;
+        self.import_branch_upstream_tracking(&repo, &imported_branches)?;
 
         Ok(())
     }
 
-    /// Import upstream tracking information from .git/config to .helix/state
-    fn import_branch_upstream_tracking(&self, branch_names: &[String]) -> Result<()> {
-        let git_config_path = self.repo_path.join(".git/config");
+    /// Import upstream tracking information from the Git config to .helix/state
+    fn import_branch_upstream_tracking(
+        &self,
+        repo: &Repository,
+        branch_names: &[String],
+    ) -> Result<()> {
+        // Worktrees share the config of the main repository
+        let git_config_path = repo.common_dir().join("config");
 
         // Read git config if it exists
         let git_config = if git_config_path.exists() {
-            Some(fs::read_to_string(&git_config_path).context("Failed to read .git/config")?)
+            Some(fs::read_to_string(&git_config_path).context("Failed to read Git config")?)
         } else {
             None
         };
@@ -395,27 +619,23 @@ impl SyncEngine {
     }
 
     fn import_git_head(&self, git_hash_to_helix_hash: &HashMap<Vec<u8>, [u8; 32]>) -> Result<()> {
-        let git_head = self.repo_path.join(".git/HEAD");
+        let repo = gix::open(&self.repo_path)?;
         let helix_head = self.repo_path.join(".helix/HEAD");
 
-        let head_content = fs::read_to_string(&git_head)?;
-
         // Ensure .helix directory exists
         if let Some(parent) = helix_head.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        if head_content.starts_with("ref:") {
-            // Symbolic reference (e.g., "ref: refs/heads/main")
-            // Preserve it exactly
-            fs::write(&helix_head, head_content)?;
+        if let Some(branch) = repo.head_name()? {
+            // Symbolic reference (e.g., "ref: refs/heads/main"), possibly unborn
+            fs::write(&helix_head, format!("ref: {}\n", branch.as_bstr()))?;
         } else {
-            // Detached HEAD: file contains raw commit SHA from Git
-            let git_sha = head_content.trim();
-            let git_sha_bytes = hex::decode(git_sha)?;
+            // Detached HEAD: points straight at a Git commit
+            let git_id = repo.head_id()?.detach();
 
             let helix_hash = git_hash_to_helix_hash
-                .get(&git_sha_bytes)
+                .get(git_id.as_bytes())
                 .context("HEAD points to unknown commit")?;
 
             fs::write(&helix_head, hash::hash_to_hex(helix_hash))?;
@@ -445,8 +665,12 @@ impl SyncEngine {
         } else {
             HelixConfig {
                 user: None,
+                core: None,
                 remotes: None,
                 ignore: IgnoreSection::default(),
+                commit: None,
+                credential: None,
+                ui: None,
             }
         };
 
@@ -482,26 +706,25 @@ impl SyncEngine {
                 _ => continue,
             };
 
-            // Process Pull
-            if let Some(pull_url) = remote.url(gix::remote::Direction::Fetch) {
-                let url_str = pull_url.to_bstring().to_string();
-                remotes_table
-                    .map
-                    .insert(format!("{}_pull", name_str), format_url(url_str));
-            }
-
-            // Process Push
+            let fetch_url = remote
+                .url(gix::remote::Direction::Fetch)
+                .map(|url| format_url(url.to_bstring().to_string()));
             let push_url = remote
                 .url(gix::remote::Direction::Push)
-                .or_else(|| remote.url(gix::remote::Direction::Fetch));
-
-            if let Some(url) = push_url {
-                let url_str = url.to_bstring().to_string();
-                remotes_table
-                    .map
-                    .insert(format!("{}_push", name_str), format_url(url_str));
-                imported_count += 1;
-            }
+                .map(|url| format_url(url.to_bstring().to_string()));
+
+            // A push URL is only recorded when it differs from the fetch URL
+            let Some(url) = fetch_url.clone().or_else(|| push_url.clone()) else {
+                continue;
+            };
+            remotes_table.insert(
+                &name_str.to_string(),
+                RemoteConfig {
+                    push_url: push_url.filter(|push| *push != url),
+                    ..RemoteConfig::new(url)
+                },
+            );
+            imported_count += 1;
         }
 
         if imported_count > 0 {
@@ -512,46 +735,85 @@ impl SyncEngine {
         Ok(imported_count)
     }
 
+    /// Copy Git tags (loose and packed) to .helix/refs/tags, nested names preserved
     fn import_git_tags(&self, git_hash_to_helix_hash: &HashMap<Vec<u8>, [u8; 32]>) -> Result<()> {
-        let git_tags_dir = self.repo_path.join(".git/refs/tags");
-        if !git_tags_dir.exists() {
-            return Ok(());
-        }
+        let repo = gix::open(&self.repo_path)?;
+        let store = FsObjectStore::new(&self.repo_path);
 
-        for entry in WalkDir::new(&git_tags_dir) {
-            let entry = entry?;
-            if !entry.file_type().is_file() {
+        for reference in repo.references()?.tags()? {
+            let reference = reference.map_err(|e| anyhow::anyhow!("Invalid Git tag ref: {}", e))?;
+            let full_name = reference.name().as_bstr().to_string();
+            let Some(name) = full_name.strip_prefix("refs/tags/") else {
+                continue;
+            };
+            let Some(id) = reference.try_id() else {
                 continue;
-            }
-
-            let git_sha = fs::read_to_string(entry.path())?.trim().to_string();
-            let git_sha_bytes = hex::decode(&git_sha)?;
-
-            let helix_hash = match git_hash_to_helix_hash.get(&git_sha_bytes) {
-                Some(h) => h,
-                None => {
-                    eprintln!(
-                        "⚠️ Tag {:?} references unknown commit {} — skipping",
-                        entry.path(),
-                        git_sha
-                    );
-                    continue;
-                }
             };
 
-            // ✅ Preserve relative path under .git/refs/tags
-            let rel_path = entry.path().strip_prefix(&git_tags_dir)?;
-            let helix_tag_path = self.repo_path.join(".helix/refs/tags").join(rel_path);
+            let helix_hash =
+                match self.helix_tag_target(&repo, &store, id.detach(), git_hash_to_helix_hash)? {
+                    Some(h) => h,
+                    None => {
+                        eprintln!(
+                            "⚠️ Tag {} references unknown commit {} — skipping",
+                            name, id
+                        );
+                        continue;
+                    }
+                };
+
+            let helix_tag_path = self.repo_path.join(".helix/refs/tags").join(name);
 
             fs::create_dir_all(helix_tag_path.parent().unwrap())?;
-            fs::write(&helix_tag_path, hash::hash_to_hex(helix_hash))?;
+            fs::write(&helix_tag_path, hash::hash_to_hex(&helix_hash))?;
         }
 
         Ok(())
     }
 
+    /// What a Helix tag ref should hold for a Git tag ref pointing at `id`.
+    ///
+    /// Annotated tags on commits become Helix tag objects carrying the tagger,
+    /// date and message (PGP signatures are dropped). When the annotation can't
+    /// be preserved (no tagger, or a tag of a tag or tree) the tag is peeled to
+    /// its commit instead. None if the commit wasn't imported.
+    fn helix_tag_target(
+        &self,
+        repo: &Repository,
+        store: &FsObjectStore,
+        id: ObjectId,
+        git_to_helix: &HashMap<Vec<u8>, [u8; 32]>,
+    ) -> Result<Option<Hash>> {
+        let object = repo.find_object(id)?;
+        if object.kind != gix::object::Kind::Tag {
+            return Ok(git_to_helix.get(id.as_bytes()).copied());
+        }
+
+        {
+            let git_tag = object.clone().into_tag();
+            let decoded = git_tag.decode()?;
+            let target = git_to_helix.get(decoded.target().as_bytes());
+
+            if let (gix::object::Kind::Commit, Some(target), Some(tagger)) =
+                (decoded.target_kind, target, decoded.tagger)
+            {
+                let tag = Tag {
+                    target: *target,
+                    name: decoded.name.to_string(),
+                    tagger: format!("{} <{}>", tagger.name, tagger.email),
+                    tag_time: tagger.time()?.seconds as u64,
+                    message: decoded.message.to_string(),
+                };
+                return Ok(Some(store.write_object(&ObjectType::Tag, &tag.to_bytes())?));
+            }
+        }
+
+        let commit = object.peel_to_commit()?;
+        Ok(git_to_helix.get(commit.id.as_bytes()).copied())
+    }
+
     fn import_git_index(&self, store: &FsObjectStore) -> Result<usize> {
-        let git_index_path = self.repo_path.join(".git/index");
+        let git_index_path = git_index_path(&self.repo_path);
 
         // Handle brand-new repo with no .git/index yet, return new empty Helix index
         if !&git_index_path.exists() {
@@ -573,7 +835,7 @@ impl SyncEngine {
             (0, 0)
         };
 
-        let git_index = GitIndex::open(&self.repo_path)?;
+        let git_index = GitIndex::open_at(&git_index_path)?;
         let index_entries: Vec<_> = git_index.entries().collect();
         let total = index_entries.len();
 
@@ -584,6 +846,7 @@ impl SyncEngine {
         let ignore_rules = IgnoreRules::load(&self.repo_path);
 
         let head_tree = self.load_full_head_tree()?;
+        let helix_head_blobs = self.load_helix_head_blobs(store);
 
         // only show if more than 1000 entries otherwise it flickers and looks weird
         let pb = if total > 1000 {
@@ -628,6 +891,7 @@ impl SyncEngine {
                     self.build_helix_entry_from_git_entry(
                         &e,
                         &head_tree,
+                        &helix_head_blobs,
                         local_repo,
                         store,
                         is_first_import,
@@ -649,10 +913,20 @@ impl SyncEngine {
         Ok(entries.len())
     }
 
+    /// Build the Helix index entry for one `.git/index` entry, copying its blob
+    /// into Helix storage without loading the whole file into memory.
+    ///
+    /// The blob is taken from the first of:
+    /// - the Helix HEAD tree, when the entry matches Git's HEAD and the blob is
+    ///   already stored (nothing is read at all)
+    /// - the loose object in `.git/objects`, decompressed as a stream
+    /// - the working tree file, when its content is the entry's blob
+    /// - the packed object, which gix has to materialize to resolve deltas
     fn build_helix_entry_from_git_entry(
         &self,
         git_index_entry: &crate::index::IndexEntry,
         head_tree: &HashMap<PathBuf, Vec<u8>>,
+        helix_head_blobs: &HashMap<PathBuf, Hash>,
         repo: &Repository,
         store: &FsObjectStore,
         is_first_import: bool,
@@ -665,20 +939,6 @@ impl SyncEngine {
         let git_index_entry_oid: &[u8; 20] = git_index_entry.oid.as_bytes();
         let git_object_id: ObjectId = ObjectId::from(*git_index_entry_oid);
 
-        let blob_content: Vec<u8> = match repo.find_object(git_object_id) {
-            Ok(obj) => obj
-                .try_into_blob()
-                .map(|b| b.data.to_vec())
-                .unwrap_or_else(|_| fs::read(&full_entry_path).unwrap_or_default()),
-            Err(_) => {
-                if full_entry_path.exists() && full_entry_path.is_file() {
-                    fs::read(&full_entry_path)?
-                } else {
-                    Vec::new()
-                }
-            }
-        };
-
         // STAGED logic: on first import, stage everything
         // On re-import, only stage if changed from HEAD
         if is_first_import {
@@ -693,21 +953,58 @@ impl SyncEngine {
             }
         }
 
-        let helix_oid: [u8; 32] = store.write_object(&ObjectType::Blob, &blob_content)?;
-
         let was_in_head = head_tree.contains_key(&entry_path);
 
-        // MODIFIED check: working tree vs index
-        if full_entry_path.exists() && full_entry_path.is_file() {
-            let working_content = fs::read(&full_entry_path)?;
-            let working_git_oid = compute_blob_oid(&working_content);
-            if &working_git_oid != git_index_entry_oid {
-                flags |= EntryFlags::MODIFIED;
+        // MODIFIED check: working tree vs index. Matching stat data from
+        // .git/index means Git already checked the content, as in `git status`
+        let metadata = fs::metadata(&full_entry_path).ok().filter(|m| m.is_file());
+        let worktree_matches = match &metadata {
+            Some(metadata) => {
+                let mtime = metadata
+                    .modified()?
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_secs();
+                let stat_clean =
+                    mtime == git_index_entry.mtime && metadata.len() == git_index_entry.size;
+                stat_clean
+                    || compute_blob_oid_stream(&full_entry_path)?.as_slice()
+                        == git_index_entry_oid.as_slice()
             }
-        } else if was_in_head {
+            None => false,
+        };
+        if metadata.is_some() && !worktree_matches {
+            flags |= EntryFlags::MODIFIED;
+        } else if metadata.is_none() && was_in_head {
             flags |= EntryFlags::DELETED;
         }
 
+        let unchanged_from_head = head_tree
+            .get(&entry_path)
+            .is_some_and(|head_git_oid| head_git_oid.as_slice() == git_index_entry_oid);
+        let stored = helix_head_blobs
+            .get(&entry_path)
+            .filter(|hash| unchanged_from_head && store.has_object(&ObjectType::Blob, hash));
+
+        let helix_oid = match stored {
+            Some(hash) => *hash,
+            None => match copy_loose_blob(repo, store, git_object_id)? {
+                Some((hash, _)) => hash,
+                None if worktree_matches => {
+                    let file = fs::File::open(&full_entry_path)?;
+                    store.write_object_from_reader(&ObjectType::Blob, file)?.0
+                }
+                None => {
+                    let content = repo
+                        .find_object(git_object_id)
+                        .ok()
+                        .and_then(|obj| obj.try_into_blob().ok())
+                        .map(|blob| blob.detach().data)
+                        .unwrap_or_default();
+                    store.write_object(&ObjectType::Blob, &content)?
+                }
+            },
+        };
+
         let (mtime_sec, file_size) = if full_entry_path.exists() {
             let metadata = fs::metadata(&full_entry_path)?;
             let mtime = metadata
@@ -732,6 +1029,18 @@ impl SyncEngine {
         })
     }
 
+    /// Blobs of the Helix HEAD commit by path, empty before the first commit
+    /// import. Lets a re-import reuse blobs it already stored.
+    fn load_helix_head_blobs(&self, store: &FsObjectStore) -> HashMap<PathBuf, Hash> {
+        let Ok(head) = super::commit::read_head(&self.repo_path) else {
+            return HashMap::new();
+        };
+        super::commit::CommitStore::new(&self.repo_path, store.clone())
+            .and_then(|commits| commits.read_commit(&head))
+            .and_then(|commit| TreeStore::new(store.clone()).collect_all_files(&commit.tree_hash))
+            .unwrap_or_default()
+    }
+
     /// Get the current repo's HEAD commit and return a hashmap of all paths in the tree
     /// TODO: optimize with git commit import process, right now too seperate processes sequentially but can be combined which would only load the repo once and traverse the tree once to get commmit paths and objects
     fn load_full_head_tree(&self) -> Result<HashMap<PathBuf, Vec<u8>>> {
@@ -768,19 +1077,27 @@ impl SyncEngine {
         Ok(map)
     }
 
-    // TODO: parallelize the two different passes here: 1. to compute all committ hashes inparallel, 2. build final commits with correct parents in parallel
+    /// Convert every commit reachable from a Git branch, oldest first, in
+    /// batches of IMPORT_BATCH_SIZE. After each batch its commits are stored
+    /// and appended to .helix/maps/commits, and the cancel flag is
+    /// checked; with `resume`, commits already in the mapping are skipped.
     fn import_git_commits(
         &self,
         store: &FsObjectStore,
         pb: &ProgressBar,
+        resume: bool,
     ) -> Result<HashMap<Vec<u8>, [u8; 32]>> {
         let repo = gix::open(&self.repo_path)?;
 
         let mut seen = HashSet::new();
-        let mut git_hash_to_helix_hash: HashMap<Vec<u8>, [u8; 32]> = HashMap::new();
-        let mut collected_git_commits: Vec<(Vec<u8>, gix::Commit)> = Vec::new();
+        let mut git_hash_to_helix_hash: HashMap<Vec<u8>, [u8; 32]> = if resume {
+            self.load_git_helix_mapping()?
+        } else {
+            HashMap::new()
+        };
+        let mut collected_git_commits: Vec<(ObjectId, i64)> = Vec::new();
 
-        pb.set_message(format!("Importing commits and files..."));
+        pb.set_message("Importing commits and files...");
 
         // Collect commits from all branches (including head)
         let refs = repo.references()?;
@@ -807,39 +1124,68 @@ impl SyncEngine {
 
             for commit_result in commit_iter.all()? {
                 let commit_info = commit_result?;
-                let git_id_bytes = commit_info.id().as_bytes().to_vec();
 
                 // Skip if already seen (handles merge commits and shared history)
-                if !seen.insert(git_id_bytes.clone()) {
+                if !seen.insert(commit_info.id) {
                     continue;
                 }
 
-                let git_commit = commit_info.object()?;
-
-                collected_git_commits.push((git_id_bytes, git_commit));
+                let commit_time = commit_info.object()?.time()?.seconds;
+                collected_git_commits.push((commit_info.id, commit_time));
             }
         }
 
         // Sort oldest → newest by commit time
-        collected_git_commits.sort_by_key(|(_, c)| c.time().unwrap().seconds);
-
-        // Build Helix commits in oldest to newest order
-        let mut helix_commits: Vec<Helix_Commit> = Vec::with_capacity(collected_git_commits.len());
+        collected_git_commits.sort_by_key(|(_, time)| *time);
+        let total = collected_git_commits.len();
+        let ids: Vec<ObjectId> = collected_git_commits
+            .into_iter()
+            .map(|(id, _)| id)
+            .filter(|id| !git_hash_to_helix_hash.contains_key(id.as_bytes()))
+            .collect();
+        // Commit times can tie or be skewed, and a batch can only convert
+        // commits whose parents are already mapped
+        let ids = parents_first(&repo, ids)?;
 
-        for (_i, (git_id_bytes, git_commit)) in collected_git_commits.into_iter().enumerate() {
-            let helix_commit = self.build_helix_commit_from_git_commit(
-                &git_commit,
-                &repo,
-                &git_hash_to_helix_hash,
-            )?;
+        let mut rev_map = RevMap::open(&self.repo_path)?;
+        if !resume {
+            rev_map.clear()?;
+        }
 
-            // Now we know this commit's helix hash, so map git → helix for children
-            git_hash_to_helix_hash.insert(git_id_bytes, helix_commit.commit_hash);
+        let stored_pb = ProgressBar::new(total as u64);
+        stored_pb.set_style(
+            ProgressStyle::with_template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
+             {pos}/{len} commits ({eta})",
+            )?
+            .progress_chars(">-"),
+        );
+        stored_pb.set_position((total - ids.len()) as u64);
+
+        for batch in ids.chunks(IMPORT_BATCH_SIZE) {
+            if self.cancel.load(Ordering::Relaxed) {
+                stored_pb.abandon();
+                anyhow::bail!(
+                    "Import interrupted after {} of {} commits. \
+                     Run `helix init --resume` to continue.",
+                    stored_pb.position(),
+                    total
+                );
+            }
 
-            helix_commits.push(helix_commit);
+            let helix_commits =
+                self.convert_git_commits(&repo, batch, &mut git_hash_to_helix_hash)?;
+            self.write_commits(store, &helix_commits, &stored_pb)?;
+            let converted: Vec<(GitSha, Hash)> = batch
+                .iter()
+                .filter_map(|id| {
+                    let helix_hash = git_hash_to_helix_hash.get(id.as_bytes())?;
+                    Some((id.as_bytes().try_into().ok()?, *helix_hash))
+                })
+                .collect();
+            rev_map.append(&converted)?;
         }
-
-        self.store_imported_commits(&store, &helix_commits)?;
+        stored_pb.finish_with_message("commits stored");
 
         // Update HEAD to point to the current branch's commit
         if let Ok(mut head_ref) = repo.head() {
@@ -851,155 +1197,250 @@ impl SyncEngine {
             }
         }
 
-        self.save_git_helix_mapping(&git_hash_to_helix_hash)?;
+        rev_map.save()?;
 
         Ok(git_hash_to_helix_hash)
     }
 
+    /// Replace the stored commit map with `mapping`
     fn save_git_helix_mapping(&self, mapping: &HashMap<Vec<u8>, [u8; 32]>) -> Result<()> {
-        let mapping_path = self.repo_path.join(".helix/git-commit-mapping");
-
-        let mut content = String::new();
+        let mut rev_map = RevMap::open(&self.repo_path)?;
         for (git_sha, helix_hash) in mapping {
-            let git_hex = hex::encode(git_sha);
-            let helix_hex = hash_to_hex(helix_hash);
-            // Write: helix_hash (64 chars) THEN git_sha (40 chars)
-            content.push_str(&format!("{} {}\n", helix_hex, git_hex));
+            if let Ok(git_sha) = git_sha.as_slice().try_into() {
+                rev_map.insert(git_sha, *helix_hash);
+            }
         }
+        rev_map.save()
+    }
 
-        fs::write(&mapping_path, content)?;
-        Ok(())
+    /// Read the stored commit map back into a Git SHA -> Helix hash map
+    fn load_git_helix_mapping(&self) -> Result<HashMap<Vec<u8>, [u8; 32]>> {
+        Ok(RevMap::open(&self.repo_path)?
+            .entries()
+            .into_iter()
+            .map(|(git_sha, helix_hash)| (git_sha.to_vec(), helix_hash))
+            .collect())
+    }
+
+    /// Convert Git commits to Helix commits, listed parents before children.
+    ///
+    /// Trees don't depend on each other, so every commit's tree is converted
+    /// on a rayon worker with its own repo handle. Converted blobs and trees
+    /// are shared through a concurrent cache keyed by Git OID, so each object
+    /// is read from Git and written to Helix once no matter how many commits
+    /// contain it. Commit hashes depend on their parents' hashes, so only that
+    /// cheap last step runs in order.
+    fn convert_git_commits(
+        &self,
+        repo: &Repository,
+        ids: &[ObjectId],
+        git_to_helix: &mut HashMap<Vec<u8>, [u8; 32]>,
+    ) -> Result<Vec<Helix_Commit>> {
+        let thread_safe_repo = repo.clone().into_sync();
+        let cache = ImportCache::default();
+
+        let git_commits: Vec<GitCommitData> = ids
+            .par_iter()
+            .map_init(
+                || thread_safe_repo.to_thread_local(),
+                |local_repo, id| self.read_git_commit(local_repo, *id, &cache),
+            )
+            .collect::<Result<_>>()?;
+
+        let mut helix_commits = Vec::with_capacity(git_commits.len());
+        for data in git_commits {
+            let parents: Vec<[u8; 32]> = data
+                .parents
+                .iter()
+                .filter_map(|parent| git_to_helix.get(parent.as_bytes()).copied())
+                .collect();
+
+            let mut commit = Helix_Commit {
+                commit_hash: ZERO_HASH,
+                tree_hash: data.tree_hash,
+                parents,
+                author: data.author,
+                author_time: data.author_time,
+                commit_time: data.commit_time,
+                message: data.message,
+                headers: data.headers,
+            };
+            commit.commit_hash = commit.compute_hash();
+
+            // Now we know this commit's helix hash, so map git → helix for children
+            git_to_helix.insert(data.id.as_bytes().to_vec(), commit.commit_hash);
+            helix_commits.push(commit);
+        }
+
+        Ok(helix_commits)
     }
 
-    fn build_helix_commit_from_git_commit(
+    /// Read a Git commit's metadata and convert its tree to a Helix tree
+    fn read_git_commit(
         &self,
-        git_commit: &gix::Commit,
-        repo: &gix::Repository,
-        git_to_helix: &HashMap<Vec<u8>, [u8; 32]>,
-    ) -> Result<Helix_Commit> {
+        repo: &Repository,
+        id: ObjectId,
+        cache: &ImportCache,
+    ) -> Result<GitCommitData> {
+        let git_commit = repo.find_commit(id)?;
+
         let message = git_commit.message()?;
         let author_name = git_commit.author()?.name.to_string();
         let author_email = git_commit.author()?.email.to_string();
         let author_timestamp = git_commit.author()?.time()?.seconds;
         let commit_time = git_commit.time()?.seconds;
+        // Metadata a Helix commit carried when `helix export` wrote it
+        let headers = git_commit
+            .decode()?
+            .extra_headers
+            .iter()
+            .filter(|(name, _)| *name == GIT_METADATA_HEADER)
+            .filter_map(|(_, value)| {
+                let (key, value) = std::str::from_utf8(value).ok()?.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
 
         let full_message = format!(
             "{}{}{}",
-            message.title.to_string(),
+            message.title,
             if message.body.is_some() { "\n\n" } else { "" },
             message.body.map(|b| b.to_string()).unwrap_or_default()
         );
 
-        let parent_commits: Vec<[u8; 32]> = git_commit
-            .parent_ids()
-            .filter_map(|git_parent_id| {
-                let git_parent_bytes = git_parent_id.as_bytes().to_vec();
-                git_to_helix.get(&git_parent_bytes).copied()
-            })
-            .collect();
-
-        let tree_id = git_commit.tree()?.id;
-        let tree_object = repo.find_object(tree_id)?;
-        let git_tree = tree_object.into_tree();
-
-        let mut recorder = gix::traverse::tree::Recorder::default();
-        git_tree
-            .traverse()
-            .breadthfirst(&mut recorder)
-            .context("Failed to traverse tree")?;
-
-        let tree = self.build_helix_tree_from_recorder(recorder, repo)?;
+        let tree_id = git_commit.tree_id()?.detach();
+        let tree_hash = match self.convert_git_tree(repo, tree_id, cache)? {
+            Some(hash) => hash,
+            None => TreeStore::for_repo(&self.repo_path).write(&Tree::new())?,
+        };
 
-        let mut commit = Helix_Commit {
-            commit_hash: ZERO_HASH,
-            tree_hash: tree.into(),
-            parents: parent_commits,
+        Ok(GitCommitData {
+            id,
+            parents: git_commit.parent_ids().map(|p| p.detach()).collect(),
+            tree_hash,
             author: format!("{} <{}>", &author_name, author_email),
             author_time: author_timestamp as u64,
             commit_time: commit_time as u64,
             message: full_message,
-        };
-
-        commit.commit_hash = commit.compute_hash();
-
-        Ok(commit)
+            headers,
+        })
     }
 
-    /// Build Helix tree from gix Recorder (same pattern as load_full_head_tree)
-    fn build_helix_tree_from_recorder(
+    /// Convert a Git tree to a Helix tree, reusing every subtree that an
+    /// earlier commit already converted. Returns None for trees without files,
+    /// which Helix doesn't store (TreeBuilder only creates directories that
+    /// contain files).
+    fn convert_git_tree(
         &self,
-        recorder: gix::traverse::tree::Recorder,
-        repo: &gix::Repository,
-    ) -> Result<Hash> {
-        let blob_storage = FsObjectStore::new(&self.repo_path);
+        repo: &Repository,
+        tree_id: ObjectId,
+        cache: &ImportCache,
+    ) -> Result<Option<Hash>> {
+        if let Some(known) = cache.trees.get(&tree_id) {
+            return Ok(*known);
+        }
 
-        // Convert gix records to Helix Entry format
-        let entries: Vec<Entry> = recorder
-            .records
+        let git_tree = repo.find_object(tree_id)?.try_into_tree()?;
+        let git_entries: Vec<(gix::object::tree::EntryMode, String, ObjectId)> = git_tree
+            .decode()
+            .context("Failed to decode tree")?
+            .entries
             .iter()
-            .filter_map(|record| {
-                // Only process blobs (files), skip trees (directories)
-                if !record.mode.is_blob() && !record.mode.is_link() {
-                    return None;
+            .map(|e| (e.mode, e.filename.to_string(), e.oid.to_owned()))
+            .collect();
+
+        let blob_storage = FsObjectStore::new(&self.repo_path);
+        let mut tree = Tree::new();
+
+        for (mode, name, oid) in git_entries {
+            if mode.is_tree() {
+                if let Some(subtree) = self.convert_git_tree(repo, oid, cache)? {
+                    tree.add_entry(TreeEntry::new_tree(name, subtree));
                 }
+                continue;
+            }
 
-                let path = PathBuf::from(record.filepath.to_string());
-
-                // Read actual blob content from Git and store it in Helix
-                let blob_content = match repo.find_object(record.oid) {
-                    Ok(obj) => match obj.try_into_blob() {
-                        Ok(blob) => blob.data.to_vec(),
-                        Err(_) => {
-                            eprintln!("Warning: Failed to read blob for {}", record.filepath);
-                            return None;
-                        }
-                    },
-                    Err(_) => {
-                        eprintln!("Warning: Failed to find object for {}", record.filepath);
-                        return None;
-                    }
-                };
+            // Only process blobs (files) and symlinks, skip submodules
+            if !mode.is_blob() && !mode.is_link() {
+                continue;
+            }
 
-                // Write blob content to Helix storage and get the BLAKE3 hash
-                let oid = match blob_storage.write_object(&ObjectType::Blob, &blob_content) {
-                    Ok(hash) => hash,
-                    Err(e) => {
-                        eprintln!(
-                            "Warning: Failed to write blob for {}: {}",
-                            record.filepath, e
-                        );
-                        return None;
-                    }
-                };
+            let (blob_hash, size) = match cache.blobs.get(&oid) {
+                Some(known) => *known,
+                None => {
+                    let Some(converted) =
+                        self.convert_blob(repo, &blob_storage, oid, name.as_str().into())
+                    else {
+                        continue;
+                    };
+                    cache.blobs.insert(oid, converted);
+                    converted
+                }
+            };
 
-                // Determine file mode
-                let file_mode = if record.mode.is_link() {
-                    0o120000 // Symlink
-                } else if record.mode.is_executable() {
-                    0o100755 // Executable
-                } else {
-                    0o100644 // Regular file
-                };
+            // Determine file mode
+            let file_mode = if mode.is_link() {
+                0o120000 // Symlink
+            } else if mode.is_executable() {
+                0o100755 // Executable
+            } else {
+                0o100644 // Regular file
+            };
 
-                Some(Entry {
-                    path,
-                    oid,
-                    flags: EntryFlags::TRACKED,
-                    size: blob_content.len() as u64,
-                    mtime_sec: 0,
-                    mtime_nsec: 0,
-                    file_mode,
-                    merge_conflict_stage: 0,
-                    reserved: [0u8; 33],
-                })
-            })
-            .collect();
+            tree.add_entry(TreeEntry::new_file(name, blob_hash, file_mode, size));
+        }
 
-        // Use TreeBuilder to create the tree structure
-        let tree_builder = TreeBuilder::new(&self.repo_path);
-        let tree_hash = tree_builder.build_from_entries(&entries)?;
+        let hash = if tree.entries.is_empty() {
+            None
+        } else {
+            tree.sort();
+            Some(TreeStore::for_repo(&self.repo_path).write(&tree)?)
+        };
 
-        Ok(tree_hash.into())
+        cache.trees.insert(tree_id, hash);
+        Ok(hash)
+    }
+
+    /// Copy one blob from Git into Helix storage, returning its BLAKE3 hash and size
+    fn convert_blob(
+        &self,
+        repo: &gix::Repository,
+        blob_storage: &FsObjectStore,
+        git_oid: ObjectId,
+        filepath: &gix::bstr::BStr,
+    ) -> Option<(Hash, u64)> {
+        // Loose objects are streamed; packed ones have to be read whole
+        match copy_loose_blob(repo, blob_storage, git_oid) {
+            Ok(Some(converted)) => return Some(converted),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Warning: Failed to write blob for {}: {}", filepath, e);
+                return None;
+            }
+        }
+
+        let blob_content = match repo.find_object(git_oid) {
+            Ok(obj) => match obj.try_into_blob() {
+                Ok(blob) => blob.detach().data,
+                Err(_) => {
+                    eprintln!("Warning: Failed to read blob for {}", filepath);
+                    return None;
+                }
+            },
+            Err(_) => {
+                eprintln!("Warning: Failed to find object for {}", filepath);
+                return None;
+            }
+        };
+
+        // Write blob content to Helix storage and get the BLAKE3 hash
+        match blob_storage.write_object(&ObjectType::Blob, &blob_content) {
+            Ok(hash) => Some((hash, blob_content.len() as u64)),
+            Err(e) => {
+                eprintln!("Warning: Failed to write blob for {}: {}", filepath, e);
+                None
+            }
+        }
     }
 
     fn store_imported_commits(
@@ -1016,15 +1457,26 @@ impl SyncEngine {
             .progress_chars(">-"),
         );
 
-        for commit in commits {
-            let raw = commit.to_bytes();
-            store.write_object_with_hash(&ObjectType::Commit, &commit.commit_hash, &raw)?;
-        }
+        self.write_commits(store, commits, &pb)?;
         pb.finish_with_message("commits stored");
 
         Ok(())
     }
 
+    fn write_commits(
+        &self,
+        store: &FsObjectStore,
+        commits: &[Helix_Commit],
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        CommitStore::new(&self.repo_path, store.clone())?.write_commits_in_order(
+            commits,
+            self.allow_missing_objects,
+            || pb.inc(1),
+        )?;
+        Ok(())
+    }
+
     /// Update HEAD to point to the latest imported commit
     fn update_head_to_commit(&self, commit_hash: [u8; 32]) -> Result<()> {
         let head_path = self.repo_path.join(".helix").join("HEAD");
@@ -1058,8 +1510,76 @@ impl SyncEngine {
     }
 }
 
+/// Where Git keeps the index for this working tree. In a linked worktree `.git`
+/// is a file pointing into the main repository, so ask gix rather than assume.
+fn git_index_path(repo_path: &Path) -> PathBuf {
+    gix::open(repo_path)
+        .map(|repo| repo.index_path())
+        .unwrap_or_else(|_| repo_path.join(".git/index"))
+}
+
+/// Reorder `ids` so each commit comes after any of its parents in the list,
+/// otherwise keeping their order
+fn parents_first(repo: &Repository, ids: Vec<ObjectId>) -> Result<Vec<ObjectId>> {
+    let wanted: HashSet<ObjectId> = ids.iter().copied().collect();
+    let mut placed: HashSet<ObjectId> = HashSet::with_capacity(ids.len());
+    let mut ordered = Vec::with_capacity(ids.len());
+
+    for id in ids {
+        let mut stack = vec![(id, false)];
+        while let Some((id, parents_done)) = stack.pop() {
+            if parents_done {
+                ordered.push(id);
+                continue;
+            }
+            if !wanted.contains(&id) || !placed.insert(id) {
+                continue;
+            }
+
+            stack.push((id, true));
+            for parent in repo.find_commit(id)?.parent_ids() {
+                stack.push((parent.detach(), false));
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// Stream a loose Git blob into Helix storage, decompressing it in chunks.
+/// None when the object isn't loose (it's packed, or missing).
+fn copy_loose_blob(
+    repo: &Repository,
+    store: &FsObjectStore,
+    oid: ObjectId,
+) -> Result<Option<(Hash, u64)>> {
+    let hex = oid.to_hex().to_string();
+    let path = repo
+        .common_dir()
+        .join("objects")
+        .join(&hex[..2])
+        .join(&hex[2..]);
+    let Ok(file) = fs::File::open(&path) else {
+        return Ok(None);
+    };
+
+    // Loose objects are zlib("<kind> <size>\0<content>")
+    let mut reader = std::io::BufReader::new(flate2::read::ZlibDecoder::new(file));
+    let mut header = Vec::new();
+    reader.read_until(0, &mut header)?;
+    let size = header
+        .strip_suffix(b"\0")
+        .and_then(|h| h.strip_prefix(b"blob "))
+        .and_then(|size| std::str::from_utf8(size).ok()?.parse::<u64>().ok())
+        .with_context(|| format!("{} is not a loose blob", hex))?;
+
+    let (hash, copied) = store.write_object_from_reader(&ObjectType::Blob, reader.take(size))?;
+    anyhow::ensure!(copied == size, "Loose object {} is truncated", hex);
+    Ok(Some((hash, size)))
+}
+
 fn wait_for_git_lock(repo_path: &Path, timeout: Duration) -> Result<()> {
-    let lock_path = repo_path.join(".git/index.lock");
+    let lock_path = git_index_path(repo_path).with_extension("lock");
     let start = Instant::now();
 
     while lock_path.exists() {
@@ -1075,6 +1595,7 @@ fn wait_for_git_lock(repo_path: &Path, timeout: Duration) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use helix_protocol::hash::hash_to_hex;
     use std::fs;
     use std::process::Command;
     use tempfile::TempDir;
@@ -1130,6 +1651,48 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_import_copies_loose_and_packed_blobs() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        let big: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+        fs::write(repo.join("packed.bin"), &big)?;
+        git(repo, &["add", "packed.bin"])?;
+        git(repo, &["commit", "-q", "-m", "first"])?;
+        git(repo, &["gc", "-q"])?;
+
+        fs::write(repo.join("loose.txt"), "loose\n")?;
+        git(repo, &["add", "loose.txt"])?;
+        fs::write(repo.join("loose.txt"), "edited after add\n")?;
+
+        SyncEngine::new(repo).import_from_git()?;
+
+        let store = FsObjectStore::new(repo);
+        let data = Reader::new(repo).read()?;
+        let blob = |path: &str| -> Result<Vec<u8>> {
+            let entry = data
+                .entries
+                .iter()
+                .find(|e| e.path == Path::new(path))
+                .expect("entry imported");
+            store.read_object(&ObjectType::Blob, &entry.oid)
+        };
+
+        assert_eq!(blob("packed.bin")?, big);
+        // The staged content, not what's on disk now
+        assert_eq!(blob("loose.txt")?, b"loose\n");
+
+        // A re-import finds HEAD's blobs already stored
+        SyncEngine::new(repo).import_from_git()?;
+        let reimported = Reader::new(repo).read()?;
+        let oids = |entries: &[Entry]| entries.iter().map(|e| e.oid).collect::<Vec<_>>();
+        assert_eq!(oids(&reimported.entries), oids(&data.entries));
+
+        Ok(())
+    }
+
     #[test]
     fn test_import_empty_repo() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -1470,7 +2033,7 @@ mod tests {
         let syncer = SyncEngine::new(temp_dir.path());
         let main_pb = ProgressBar::new_spinner();
         let store = FsObjectStore::new(temp_dir.path());
-        syncer.import_git_commits(&store, &main_pb)?;
+        syncer.import_git_commits(&store, &main_pb, false)?;
         let commit_reader = CommitStore::new(temp_dir.path(), store)?;
 
         assert_eq!(
@@ -1497,7 +2060,7 @@ mod tests {
         // Import commits
         let syncer = SyncEngine::new(temp_dir.path());
         let store = FsObjectStore::new(temp_dir.path());
-        syncer.import_git_commits(&store, &main_pb)?;
+        syncer.import_git_commits(&store, &main_pb, false)?;
 
         let commit_reader = CommitStore::new(temp_dir.path(), store)?;
 
@@ -1525,7 +2088,7 @@ mod tests {
         let syncer = SyncEngine::new(repo_dir);
         let main_pb = ProgressBar::new_spinner();
         let store = FsObjectStore::new(repo_dir);
-        syncer.import_git_commits(&store, &main_pb)?;
+        syncer.import_git_commits(&store, &main_pb, false)?;
         let commit_reader = CommitStore::new(repo_dir, store)?;
         let commits = &commit_reader.list_commits()?;
 
@@ -1600,6 +2163,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_import_git_commits_reuses_unchanged_subtrees() -> Result<()> {
+        use super::super::tree::TreeBuilder;
+
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        fs::create_dir_all(repo.join("docs"))?;
+        fs::create_dir_all(repo.join("src"))?;
+        fs::write(repo.join("docs/guide.md"), "guide")?;
+        fs::write(repo.join("src/main.rs"), "fn main() {}")?;
+        git(repo, &["add", "."])?;
+        git(repo, &["commit", "-m", "first"])?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        fs::write(repo.join("src/main.rs"), "fn main() { run() }")?;
+        git(repo, &["add", "."])?;
+        git(repo, &["commit", "-m", "second"])?;
+
+        let commits = import_commits(repo)?;
+        assert_eq!(commits.len(), 2);
+
+        let trees = TreeStore::for_repo(repo);
+        let subtree = |commit: &Helix_Commit, name: &str| -> Result<[u8; 32]> {
+            let root = trees.read(&commit.tree_hash)?;
+            Ok(root.entries.iter().find(|e| e.name == name).unwrap().oid)
+        };
+
+        // docs/ didn't change, so both commits point at the same Helix tree
+        assert_eq!(subtree(&commits[0], "docs")?, subtree(&commits[1], "docs")?);
+        assert_ne!(subtree(&commits[0], "src")?, subtree(&commits[1], "src")?);
+
+        // Converted trees hash the same as trees built from index entries
+        let store = FsObjectStore::new(repo);
+        let entries: Vec<Entry> = [
+            ("docs/guide.md", "guide"),
+            ("src/main.rs", "fn main() { run() }"),
+        ]
+        .iter()
+        .map(|(path, content)| -> Result<Entry> {
+            let oid = store.write_object(&ObjectType::Blob, content.as_bytes())?;
+            Ok(Entry::new(
+                PathBuf::from(path),
+                content.len() as u64,
+                0,
+                oid,
+                0o100644,
+            ))
+        })
+        .collect::<Result<_>>()?;
+        assert_eq!(
+            TreeBuilder::new(repo).build_from_entries(&entries)?,
+            commits[1].tree_hash
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_import_git_commits_preserves_tree_structure() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -1781,7 +2403,7 @@ mod tests {
         let engine = SyncEngine::new(repo);
         let main_pb = ProgressBar::new_spinner();
         let store = FsObjectStore::new(repo);
-        let git_to_helix = engine.import_git_commits(&store, &main_pb)?;
+        let git_to_helix = engine.import_git_commits(&store, &main_pb, false)?;
 
         // There should be an entry for HEAD
         let helix_hash_from_map = git_to_helix
@@ -1815,7 +2437,7 @@ mod tests {
         let engine = SyncEngine::new(repo);
         let main_pb = ProgressBar::new_spinner();
         let store = FsObjectStore::new(repo);
-        let git_to_helix = engine.import_git_commits(&store, &main_pb)?;
+        let git_to_helix = engine.import_git_commits(&store, &main_pb, false)?;
 
         // Find HEAD Git SHA
         let output = Command::new("git")
@@ -2780,4 +3402,246 @@ key = "value"
 
         Ok(())
     }
+
+    #[test]
+    fn test_interrupted_import_resumes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(repo.join(name), name)?;
+            git(repo, &["add", name])?;
+            git(repo, &["commit", "-q", "-m", name])?;
+        }
+
+        // A full import to compare against
+        SyncEngine::new(repo).import_from_git()?;
+        let complete = SyncEngine::new(repo).load_git_helix_mapping()?;
+        assert_eq!(complete.len(), 3);
+        assert!(!SyncEngine::new(repo).import_interrupted());
+
+        // Ctrl-C before the first batch: nothing converted, resumable
+        let cancel = Arc::new(AtomicBool::new(true));
+        let interrupted = SyncEngine::new(repo).with_cancel(cancel);
+        let err = interrupted.import_from_git().unwrap_err();
+        assert!(err.to_string().contains("helix init --resume"));
+        assert!(interrupted.import_interrupted());
+        assert!(interrupted.load_git_helix_mapping()?.is_empty());
+
+        // Pretend one batch had finished: resuming converts only the rest
+        let (git_sha, helix_hash) = complete.iter().next().unwrap();
+        RevMap::open(repo)?.append(&[(git_sha.as_slice().try_into()?, *helix_hash)])?;
+        SyncEngine::new(repo).resume_import()?;
+
+        assert!(!SyncEngine::new(repo).import_interrupted());
+        assert_eq!(SyncEngine::new(repo).load_git_helix_mapping()?, complete);
+        assert!(SyncEngine::new(repo).resume_import().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_update_brings_in_new_commits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        fs::write(repo.join("a.txt"), "a")?;
+        git(repo, &["add", "a.txt"])?;
+        git(repo, &["commit", "-m", "first"])?;
+        git(repo, &["branch", "side"])?;
+
+        let syncer = SyncEngine::new(repo);
+        syncer.import_from_git()?;
+
+        let head = fs::read_to_string(repo.join(".git/HEAD"))?;
+        let branch_ref = head.trim().strip_prefix("ref: ").unwrap().to_string();
+
+        // Nothing new in Git yet
+        let summary = syncer.import_update()?;
+        assert_eq!(summary.commits_count, 0);
+        assert!(summary.refs_updated.is_empty());
+
+        fs::write(repo.join("b.txt"), "b")?;
+        git(repo, &["add", "b.txt"])?;
+        git(repo, &["commit", "-m", "second"])?;
+
+        // Give `side` a Helix-only commit, then move it in Git too
+        let store = FsObjectStore::new(repo);
+        let side_path = repo.join(".helix/refs/heads/side");
+        let side_base = hash::hex_to_hash(fs::read_to_string(&side_path)?.trim())?;
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+        let base = commits.read_commit(&side_base)?;
+        let local = Helix_Commit::new(
+            base.tree_hash,
+            vec![side_base],
+            "Test <test@test.com>".into(),
+            "helix only".into(),
+        );
+        store.write_object_with_hash(&ObjectType::Commit, &local.commit_hash, &local.to_bytes())?;
+        fs::write(&side_path, hash_to_hex(&local.commit_hash))?;
+
+        git(repo, &["checkout", "-q", "side"])?;
+        fs::write(repo.join("c.txt"), "c")?;
+        git(repo, &["add", "c.txt"])?;
+        git(repo, &["commit", "-m", "side work"])?;
+        git(repo, &["checkout", "-q", "-"])?;
+
+        let summary = syncer.import_update()?;
+        assert_eq!(summary.commits_count, 2);
+        assert_eq!(summary.refs_updated, vec![branch_ref.clone()]);
+        assert_eq!(summary.refs_skipped, vec!["refs/heads/side".to_string()]);
+        assert!(summary.files_count.is_some());
+
+        let tip =
+            hash::hex_to_hash(fs::read_to_string(repo.join(".helix").join(&branch_ref))?.trim())?;
+        let tip = commits.read_commit(&tip)?;
+        assert_eq!(tip.message, "second\n");
+        assert_eq!(commits.read_commit(&tip.parents[0])?.message, "first\n");
+
+        // Helix-only work on `side` is preserved
+        assert_eq!(
+            fs::read_to_string(&side_path)?.trim(),
+            hash_to_hex(&local.commit_hash)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_refuses_commits_whose_parent_is_missing() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        fs::write(repo.join("a.txt"), "a")?;
+        git(repo, &["add", "a.txt"])?;
+        git(repo, &["commit", "-m", "first"])?;
+
+        let syncer = SyncEngine::new(repo);
+        syncer.import_from_git()?;
+
+        // Lose the imported commit, as if the store was damaged since
+        let head = fs::read_to_string(repo.join(".git/HEAD"))?;
+        let branch_ref = head.trim().strip_prefix("ref: ").unwrap().to_string();
+        let first =
+            hash::hex_to_hash(fs::read_to_string(repo.join(".helix").join(&branch_ref))?.trim())?;
+        let store = FsObjectStore::new(repo);
+        fs::remove_file(store.object_path(&ObjectType::Commit, &first))?;
+
+        for (name, text) in [("b.txt", "b"), ("c.txt", "c")] {
+            fs::write(repo.join(name), text)?;
+            git(repo, &["add", name])?;
+            git(repo, &["commit", "-m", name])?;
+        }
+
+        let err = syncer.import_update().unwrap_err();
+        assert!(format!("{err:#}").contains("references parent"), "{err:#}");
+
+        // Neither new commit was stored, so nothing points at the lost one
+        let stored = walkdir::WalkDir::new(repo.join(".helix/objects/commits"))
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .count();
+        assert_eq!(stored, 0);
+
+        // Recovery imports them anyway
+        let summary = SyncEngine::new(repo)
+            .with_allow_missing_objects(true)
+            .import_update()?;
+        assert_eq!(summary.commits_count, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_annotated_and_lightweight_tags() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        init_test_repo(repo)?;
+
+        fs::write(repo.join("a.txt"), "a")?;
+        git(repo, &["add", "a.txt"])?;
+        git(repo, &["commit", "-m", "first"])?;
+        git(repo, &["tag", "-a", "v1.0", "-m", "Release one"])?;
+        git(repo, &["tag", "light"])?;
+        git(repo, &["tag", "-a", "nested/v2", "-m", "Nested"])?;
+
+        SyncEngine::new(repo).import_from_git()?;
+
+        let store = FsObjectStore::new(repo);
+        let read_ref = |name: &str| -> Result<Hash> {
+            hash::hex_to_hash(fs::read_to_string(repo.join(".helix/refs/tags").join(name))?.trim())
+        };
+        let head = read_ref("light")?;
+        assert!(store.has_object(&ObjectType::Commit, &head));
+
+        let annotated = read_ref("v1.0")?;
+        let tag = Tag::from_bytes(&store.read_object(&ObjectType::Tag, &annotated)?)?;
+        assert_eq!(tag.name, "v1.0");
+        assert_eq!(tag.target, head);
+        assert_eq!(tag.tagger, "Test <test@test.com>");
+        assert_eq!(tag.message, "Release one\n");
+        assert!(tag.tag_time > 0);
+
+        assert_eq!(peel_to_commit(&store, &read_ref("nested/v2")?)?, head);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_packed_refs_and_worktree() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(&repo)?;
+        init_test_repo(&repo)?;
+
+        fs::write(repo.join("a.txt"), "a")?;
+        git(&repo, &["add", "a.txt"])?;
+        git(&repo, &["commit", "-m", "first"])?;
+        git(&repo, &["branch", "-M", "main"])?;
+        git(&repo, &["branch", "feature/packed"])?;
+        git(&repo, &["tag", "-a", "v1.0", "-m", "Release one"])?;
+        git(&repo, &["pack-refs", "--all"])?;
+        assert!(!repo.join(".git/refs/heads/feature/packed").exists());
+
+        SyncEngine::new(&repo).import_from_git()?;
+
+        let head = fs::read_to_string(repo.join(".helix/refs/heads/main"))?;
+        let packed = fs::read_to_string(repo.join(".helix/refs/heads/feature/packed"))?;
+        assert_eq!(head, packed);
+        assert!(repo.join(".helix/refs/tags/v1.0").exists());
+
+        // A linked worktree has a `.git` file instead of a directory
+        let worktree = temp_dir.path().join("wt");
+        git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                "wt-branch",
+                worktree.to_str().unwrap(),
+            ],
+        )?;
+        assert!(worktree.join(".git").is_file());
+
+        let mapping = SyncEngine::new(&repo).load_git_helix_mapping()?;
+        let engine = SyncEngine::new(&worktree);
+        engine.import_git_branches(&mapping)?;
+        engine.import_git_head(&mapping)?;
+
+        assert_eq!(
+            fs::read_to_string(worktree.join(".helix/HEAD"))?,
+            "ref: refs/heads/wt-branch\n"
+        );
+        assert_eq!(
+            fs::read_to_string(worktree.join(".helix/refs/heads/wt-branch"))?,
+            head
+        );
+
+        Ok(())
+    }
 }
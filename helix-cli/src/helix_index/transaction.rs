@@ -0,0 +1,290 @@
+/*
+Commit transactions: moving a branch and updating the index as one step.
+
+A commit writes its blobs, trees and commit object first. Objects are content
+addressed, so until something refers to them a crash only leaves garbage for gc.
+The two writes that matter are the branch (or detached HEAD) moving to the new
+commit and the index dropping its staged flags. A crash between them would
+leave a branch that has the commit with the index still staging it, so the
+journal .helix/commit.txn (next to the index, so sandboxes and worktrees have
+their own) records the step before either happens:
+
+  ref /repo/.helix/refs/heads/main    the file that moves
+  old <hex> | none                    what it held before
+  new <hex>                           the commit
+  generation 41                       the index generation being replaced
+
+The journal is written to a temp file, fsynced and renamed into place, then the
+ref is flipped (also by rename), then the index is persisted, and the journal
+is removed last. Whatever command runs next calls `recover` first:
+
+- ref at `new`, index still at `generation`: the commit happened but the index
+  didn't catch up, so its staged flags are cleared now (roll forward)
+- ref at `old`: the commit never became visible; the index is left staged and
+  the commit can simply be run again (roll back)
+- anything else: the index was already persisted, or the ref was moved by
+  something else since; the journal is just dropped
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::api::HelixIndexData;
+use super::format::EntryFlags;
+
+const JOURNAL_FILE: &str = "commit.txn";
+
+/// What `recover` found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// The ref had moved; the index was brought up to date
+    RolledForward(Hash),
+    /// The ref never moved; the commit was abandoned
+    RolledBack(Hash),
+    /// The journal was stale and removed
+    Discarded,
+}
+
+/// An in-progress commit: the journal is on disk until `complete` or `abort`
+#[derive(Debug)]
+pub struct CommitTransaction {
+    journal_path: PathBuf,
+    index_path: PathBuf,
+    repo_root: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct JournalContents {
+    ref_path: PathBuf,
+    old: Option<Hash>,
+    new: Hash,
+    generation: u64,
+}
+
+impl CommitTransaction {
+    /// Record that `ref_path` is about to move from `old` to `new`, replacing index
+    /// `generation`
+    pub fn begin(
+        index_path: &Path,
+        repo_root: &Path,
+        ref_path: &Path,
+        old: Option<Hash>,
+        new: Hash,
+        generation: u64,
+    ) -> Result<Self> {
+        let journal_path = journal_path(index_path);
+        if journal_path.exists() {
+            bail!(
+                "Another commit is in progress ({} exists)",
+                journal_path.display()
+            );
+        }
+        let contents = JournalContents {
+            ref_path: ref_path.to_path_buf(),
+            old,
+            new,
+            generation,
+        };
+        write_durable(&journal_path, &contents.to_string())?;
+
+        Ok(Self {
+            journal_path,
+            index_path: index_path.to_path_buf(),
+            repo_root: repo_root.to_path_buf(),
+        })
+    }
+
+    /// The ref has moved: catch the index up and close the journal
+    pub fn complete(self) -> Result<()> {
+        clear_staged(&self.index_path, &self.repo_root)?;
+        remove_journal(&self.journal_path)
+    }
+
+    /// The ref didn't move: close the journal without touching the index
+    pub fn abort(self) -> Result<()> {
+        remove_journal(&self.journal_path)
+    }
+}
+
+/// Finish or undo a commit that was interrupted, if the index at `index_path` has one
+pub fn recover(index_path: &Path, repo_root: &Path) -> Result<Option<Recovery>> {
+    let journal_path = journal_path(index_path);
+    let Ok(text) = fs::read_to_string(&journal_path) else {
+        return Ok(None);
+    };
+
+    let recovery = match JournalContents::parse(&text) {
+        Some(journal) => {
+            let current = fs::read_to_string(&journal.ref_path)
+                .ok()
+                .and_then(|hex| hex_to_hash(hex.trim()).ok());
+            let generation = HelixIndexData::load_from_path(index_path, repo_root)?.generation();
+
+            if current == Some(journal.new) && generation == journal.generation {
+                clear_staged(index_path, repo_root)?;
+                Recovery::RolledForward(journal.new)
+            } else if current == journal.old {
+                Recovery::RolledBack(journal.new)
+            } else {
+                Recovery::Discarded
+            }
+        }
+        // The journal is renamed into place, so it is never torn; one that doesn't
+        // parse isn't ours to act on
+        None => Recovery::Discarded,
+    };
+
+    remove_journal(&journal_path)?;
+    Ok(Some(recovery))
+}
+
+fn journal_path(index_path: &Path) -> PathBuf {
+    index_path.with_file_name(JOURNAL_FILE)
+}
+
+/// The index after a commit: entries staged for deletion are gone and nothing is staged
+fn clear_staged(index_path: &Path, repo_root: &Path) -> Result<()> {
+    let mut index = HelixIndexData::load_for_update(index_path, repo_root)?;
+    index
+        .entries_mut()
+        .retain(|entry| !entry.flags.contains(EntryFlags::DELETED));
+    for entry in index.entries_mut() {
+        entry.flags.remove(EntryFlags::STAGED);
+    }
+    index.persist()
+}
+
+fn write_durable(path: &Path, contents: &str) -> Result<()> {
+    let temp = path.with_extension("txn.new");
+    let mut file =
+        File::create(&temp).with_context(|| format!("Failed to create {}", temp.display()))?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+        .with_context(|| format!("Failed to fsync {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to write {}", path.display()))?;
+    if let Some(dir) = path.parent() {
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to fsync {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+fn remove_journal(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl std::fmt::Display for JournalContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "ref {}", self.ref_path.display())?;
+        match &self.old {
+            Some(old) => writeln!(f, "old {}", hash_to_hex(old))?,
+            None => writeln!(f, "old none")?,
+        }
+        writeln!(f, "new {}", hash_to_hex(&self.new))?;
+        writeln!(f, "generation {}", self.generation)
+    }
+}
+
+impl JournalContents {
+    fn parse(text: &str) -> Option<Self> {
+        let mut ref_path = None;
+        let mut old = None;
+        let mut new = None;
+        let mut generation = None;
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "ref" => ref_path = Some(PathBuf::from(value)),
+                "old" if value == "none" => old = Some(None),
+                "old" => old = Some(Some(hex_to_hash(value).ok()?)),
+                "new" => new = Some(hex_to_hash(value).ok()?),
+                "generation" => generation = Some(value.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(Self {
+            ref_path: ref_path?,
+            old: old?,
+            new: new?,
+            generation: generation?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::{add, AddOptions};
+    use crate::init_command::init_helix_repo;
+
+    fn staged_count(repo: &Path) -> Result<usize> {
+        let index = HelixIndexData::load_from_path(&repo.join(".helix/helix.idx"), repo)?;
+        Ok(index
+            .entries()
+            .iter()
+            .filter(|e| e.flags.contains(EntryFlags::STAGED))
+            .count())
+    }
+
+    #[test]
+    fn test_recover_interrupted_commit() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        init_helix_repo(repo, None)?;
+        fs::write(repo.join("a.txt"), "a")?;
+        add(repo, &[PathBuf::from("a.txt")], AddOptions::default())?;
+        assert_eq!(staged_count(repo)?, 1);
+
+        let index_path = repo.join(".helix/helix.idx");
+        let ref_path = repo.join(".helix/refs/heads/main");
+        let generation = HelixIndexData::load_from_path(&index_path, repo)?.generation();
+        let commit = [7u8; 32];
+
+        // Crash before the ref moved: nothing to finish
+        let txn = CommitTransaction::begin(&index_path, repo, &ref_path, None, commit, generation)?;
+        assert!(
+            CommitTransaction::begin(&index_path, repo, &ref_path, None, commit, generation)
+                .is_err()
+        );
+        drop(txn);
+        assert_eq!(
+            recover(&index_path, repo)?,
+            Some(Recovery::RolledBack(commit))
+        );
+        assert_eq!(staged_count(repo)?, 1);
+        assert_eq!(recover(&index_path, repo)?, None);
+
+        // Crash after the ref moved: the index catches up
+        CommitTransaction::begin(&index_path, repo, &ref_path, None, commit, generation)?;
+        fs::write(&ref_path, hash_to_hex(&commit))?;
+        assert_eq!(
+            recover(&index_path, repo)?,
+            Some(Recovery::RolledForward(commit))
+        );
+        assert_eq!(staged_count(repo)?, 0);
+        assert!(!journal_path(&index_path).exists());
+
+        // Crash after the index was written: just the journal is left
+        let generation = HelixIndexData::load_from_path(&index_path, repo)?.generation();
+        let txn = CommitTransaction::begin(
+            &index_path,
+            repo,
+            &ref_path,
+            Some(commit),
+            [8u8; 32],
+            generation - 1,
+        )?;
+        drop(txn);
+        fs::write(&ref_path, hash_to_hex(&[8u8; 32]))?;
+        assert_eq!(recover(&index_path, repo)?, Some(Recovery::Discarded));
+        Ok(())
+    }
+}
@@ -12,8 +12,32 @@
 // - Contains entries for files (Files) and subdirectories (trees)
 // - Entries are sorted by name for deterministic hashing
 // - Trees are immutable once created
+//
+// Canonical serialization (format version 1). A tree's hash is the BLAKE3 hash of
+// these bytes, and commits name trees by that hash, so the encoding must never change;
+// the golden vectors in the tests below pin it.
+//
+//   tree  = count entry*              count: u32 little-endian number of entries
+//   entry = type mode size name_len name oid
+//     type      u8      0 file, 1 executable file, 2 tree, 3 symlink
+//     mode      u32 LE  0o100644, 0o100755, 0o040000 or 0o120000 (see file_mode.rs);
+//                       the type is derived from the mode, trees always 0o040000
+//     size      u64 LE  file size in bytes, 0 for trees
+//     name_len  u16 LE  length of name in bytes
+//     name      UTF-8   one path component: not empty, no '/' or NUL
+//     oid       32 bytes  hash of the blob or subtree
+//
+// There are no separators or padding: every field is fixed width or length-prefixed.
+// Entries are ordered by the bytes of their names (not by type, and with no trailing
+// '/' on trees as Git uses), and a name appears at most once. `Tree::to_bytes` always
+// writes that order and `TreeStore::write` refuses anything else non-canonical.
+//
+// A later format would start with helix_protocol::commit::TREE_VERSION_MARKER and a
+// version byte, so its trees can sit next to version 1 trees; version 1 itself is
+// never marked. Readers refuse versions they don't know.
 
 use anyhow::Result;
+use helix_protocol::commit::check_tree_format;
 use helix_protocol::hash::{hash_bytes, Hash};
 use helix_protocol::message::ObjectType;
 use helix_protocol::storage::FsObjectStore;
@@ -203,15 +227,55 @@ impl Tree {
         hash_bytes(&bytes)
     }
 
-    /// Serialize tree to bytes
+    /// Fail if the tree can't be serialized canonically: a name that isn't a single
+    /// path component, a name used twice, or a tree entry with a file's mode or size
+    pub fn check_canonical(&self) -> Result<()> {
+        let mut names: Vec<&str> = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            if entry.name.is_empty() || entry.name.contains(['/', '\0']) {
+                anyhow::bail!("Invalid tree entry name {:?}", entry.name);
+            }
+            if entry.name.len() > u16::MAX as usize {
+                anyhow::bail!("Tree entry name is too long: {}", entry.name);
+            }
+            let expected_type = match entry.entry_type {
+                EntryType::Tree => EntryType::Tree,
+                _ => EntryType::from_mode(entry.mode),
+            };
+            if entry.entry_type != expected_type
+                || (entry.entry_type == EntryType::Tree
+                    && (entry.mode != 0o040000 || entry.size != 0))
+            {
+                anyhow::bail!(
+                    "Tree entry {} has type {:?} but mode {:o} and size {}",
+                    entry.name,
+                    entry.entry_type,
+                    entry.mode,
+                    entry.size
+                );
+            }
+            names.push(&entry.name);
+        }
+        names.sort_unstable();
+        if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            anyhow::bail!("Tree has more than one entry named {}", pair[0]);
+        }
+        Ok(())
+    }
+
+    /// Serialize tree to bytes, with entries in canonical order whatever order they
+    /// were added in
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
         // Entry count (4 bytes)
         bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
 
+        let mut entries: Vec<&TreeEntry> = self.entries.iter().collect();
+        entries.sort();
+
         // Entries (variable)
-        for entry in &self.entries {
+        for entry in entries {
             let entry_bytes = entry.to_bytes();
             bytes.extend_from_slice(&entry_bytes);
         }
@@ -221,6 +285,7 @@ impl Tree {
 
     /// Deserialize tree from bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        check_tree_format(bytes)?;
         if bytes.len() < 4 {
             anyhow::bail!("Tree too short: {} bytes", bytes.len());
         }
@@ -281,6 +346,7 @@ impl TreeStore {
 
     /// Write tree to storage
     pub fn write(&self, tree: &Tree) -> Result<Hash> {
+        tree.check_canonical()?;
         let bytes = tree.to_bytes();
         self.objects.write_object(&ObjectType::Tree, &bytes)
     }
@@ -303,6 +369,9 @@ impl TreeStore {
 
     /// Write multiple trees in parallel
     pub fn write_batch(&self, trees: &[Tree]) -> Result<Vec<Hash>> {
+        for tree in trees {
+            tree.check_canonical()?;
+        }
         let bytes: Vec<Vec<u8>> = trees.iter().map(|t| t.to_bytes()).collect();
         self.objects.write_objects_batch(&ObjectType::Tree, &bytes)
     }
@@ -469,10 +538,10 @@ impl TreeBuilder {
 
                     // Sort and store tree
                     tree.sort();
-                    let tree_hash = self.store.write(&tree).unwrap();
-                    (dir.clone(), tree_hash)
+                    let tree_hash = self.store.write(&tree)?;
+                    Ok((dir.clone(), tree_hash))
                 })
-                .collect();
+                .collect::<Result<_>>()?;
 
             // Add results to tree_hashes
             for (dir, hash) in results {
@@ -736,4 +805,121 @@ mod tests {
 
         Ok(())
     }
+
+    /// Trees the golden vectors are taken from, with entries added out of order
+    fn golden_trees() -> (Tree, Tree) {
+        let empty = Tree::new();
+        let mut tree = Tree::new();
+        tree.add_entry(TreeEntry::new_tree("src".to_string(), empty.hash()));
+        tree.add_entry(TreeEntry::new_file(
+            "run.sh".to_string(),
+            hash_bytes(b"#!/bin/sh\n"),
+            0o100755,
+            10,
+        ));
+        tree.add_entry(TreeEntry::new_file(
+            "README.md".to_string(),
+            hash_bytes(b"hello"),
+            0o100644,
+            5,
+        ));
+        tree.add_entry(TreeEntry::new_file(
+            "link".to_string(),
+            hash_bytes(b"target"),
+            0o120000,
+            6,
+        ));
+        (empty, tree)
+    }
+
+    /// These hashes are in every repository's history. If this test fails, the tree
+    /// encoding changed: restore it, or add a new format version instead.
+    #[test]
+    fn test_tree_hash_golden_vectors() -> Result<()> {
+        use helix_protocol::hash::hex_to_hash;
+
+        let mut one = Tree::new();
+        one.add_entry(TreeEntry::new_file(
+            "a".to_string(),
+            [0xab; 32],
+            0o100644,
+            3,
+        ));
+        let expected = [
+            "01000000",         // one entry
+            "00",               // file
+            "a4810000",         // mode 0o100644
+            "0300000000000000", // size 3
+            "0100",             // name length 1
+            "61",               // "a"
+        ]
+        .concat()
+            + &"ab".repeat(32);
+        assert_eq!(
+            one.to_bytes()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>(),
+            expected
+        );
+
+        let (empty, tree) = golden_trees();
+        assert_eq!(empty.to_bytes(), [0, 0, 0, 0]);
+        assert_eq!(
+            empty.hash(),
+            hex_to_hash("ec2bd03bf86b935fa34d71ad7ebb049f1f10f87d343e521511d8f9e6625620cd")?
+        );
+        assert_eq!(
+            tree.hash(),
+            hex_to_hash("ddc3d20b20dcd4c44ac9d4b7b4b708280fcb3aa63d45b315206dd180c21652a9")?
+        );
+
+        // Insertion order doesn't matter; names order by their bytes
+        let mut sorted = tree.clone();
+        sorted.sort();
+        assert_eq!(sorted.hash(), tree.hash());
+        let names: Vec<_> = Tree::from_bytes(&tree.to_bytes())?
+            .entries
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, ["README.md", "link", "run.sh", "src"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tree_format_version_and_canonical_checks() -> Result<()> {
+        use helix_protocol::commit::{parse_tree_entries, TREE_VERSION_MARKER};
+
+        let temp_dir = TempDir::new()?;
+        let store = setup_test_repo(&temp_dir)?;
+        let (_, tree) = golden_trees();
+        let bytes = tree.to_bytes();
+
+        // A later format is refused, not misread; version 1 is never marked
+        for version in [2u8, 1] {
+            let mut marked = TREE_VERSION_MARKER.to_vec();
+            marked.push(version);
+            marked.extend_from_slice(&bytes);
+            assert!(Tree::from_bytes(&marked).is_err());
+            assert!(parse_tree_entries(&marked).is_err());
+        }
+
+        let mut duplicate = tree.clone();
+        duplicate.add_entry(TreeEntry::new_tree("link".to_string(), [0u8; 32]));
+        assert!(store.write(&duplicate).is_err());
+
+        let mut nested = Tree::new();
+        nested.add_entry(TreeEntry::new_file(
+            "a/b".to_string(),
+            [0u8; 32],
+            0o100644,
+            0,
+        ));
+        assert!(store.write(&nested).is_err());
+
+        assert_eq!(store.write(&tree)?, tree.hash());
+        Ok(())
+    }
 }
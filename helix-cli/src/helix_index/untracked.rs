@@ -0,0 +1,372 @@
+/*
+Finding untracked files for status.
+
+Walking every file of a large untracked directory (node_modules before it is
+ignored, a build output dir) is the slow part of status, and listing them all
+is noise. Like `git status`, a directory that holds no tracked entries is
+reported as a single "dir/" entry without walking the rest of it: its contents
+are only looked at until the first file that isn't ignored turns up. A
+directory whose contents are all ignored, or that is empty, isn't reported.
+
+Ignored directories are never entered, so as in Git a `!pattern` can't
+re-include a file inside one; negations still apply to files in untracked
+directories, which is why the first-file check asks the ignore rules.
+
+Untracked cache
+---------------
+
+Creating or removing a file changes its directory's mtime, so a directory
+whose mtime hasn't moved still has the untracked entries it had last time.
+`scan_untracked_cached` keeps each directory's results in the index's UNTR
+extension and only reads the directories where something changed, making a
+scan O(changed directories) even without fsmonitor. A cached directory is
+reused only if all of these still hold:
+
+  - its mtime is the same, and it was old enough when cached that a change in
+    the same clock tick couldn't have been missed
+  - its own .gitignore, and those of the directories above it, are unchanged,
+    as are the repository-wide ignore sources (helix.toml, Git's excludes)
+  - the set of tracked names directly inside it is the same, since staging or
+    unstaging a file doesn't touch the directory
+  - each collapsed directory's witness (the unignored file that showed it
+    was worth reporting) still exists and is still not ignored
+*/
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+use super::api::HelixIndexData;
+use super::format::{CachedDir, CachedUntracked, EntryFlags, UntrackedCache};
+use crate::ignore::IgnoreRules;
+
+/// Directories modified this recently aren't cached: another change within
+/// the same mtime tick would leave the mtime as it is
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Untracked, non-ignored paths under `workdir`, sorted. Directories without
+/// tracked entries are collapsed to one path ending in '/'.
+pub fn scan_untracked(
+    workdir: &Path,
+    index: &HelixIndexData,
+    rules: &IgnoreRules,
+) -> Result<Vec<PathBuf>> {
+    Ok(scan_untracked_cached(workdir, index, rules, None)?.0)
+}
+
+/// `scan_untracked`, reusing the results in `cache` for directories that
+/// haven't changed. Also returns the cache to keep for the next scan.
+pub fn scan_untracked_cached(
+    workdir: &Path,
+    index: &HelixIndexData,
+    rules: &IgnoreRules,
+    cache: Option<&UntrackedCache>,
+) -> Result<(Vec<PathBuf>, UntrackedCache)> {
+    let ignore_key = rules.fingerprint();
+    let previous: HashMap<&Path, &CachedDir> = cache
+        .filter(|c| c.ignore_key == ignore_key)
+        .map(|c| c.dirs.iter().map(|d| (d.path.as_path(), d)).collect())
+        .unwrap_or_default();
+
+    let tracked = tracked_paths(index);
+    // Tracked names directly inside each directory, subdirectories included
+    let mut tracked_children: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    for path in &tracked {
+        for ancestor in path.ancestors() {
+            let (Some(parent), Some(name)) = (ancestor.parent(), ancestor.file_name()) else {
+                continue;
+            };
+            tracked_children
+                .entry(parent.to_path_buf())
+                .or_default()
+                .push(name.to_string_lossy().into_owned());
+        }
+    }
+    for names in tracked_children.values_mut() {
+        names.sort();
+        names.dedup();
+    }
+
+    let racy_after = SystemTime::now()
+        .checked_sub(RACY_WINDOW)
+        .unwrap_or(UNIX_EPOCH);
+    let mut next = UntrackedCache {
+        ignore_key,
+        dirs: Vec::new(),
+    };
+    let mut untracked = Vec::new();
+
+    // (directory, whether the .gitignore files above it are unchanged)
+    let mut dirs = vec![(PathBuf::new(), true)];
+    while let Some((dir, parents_unchanged)) = dirs.pop() {
+        let full_dir = workdir.join(&dir);
+        let Ok(metadata) = fs::metadata(&full_dir) else {
+            continue;
+        };
+        let mtime = metadata.modified().unwrap_or(UNIX_EPOCH);
+        let (mtime_sec, mtime_nsec) = split_time(mtime);
+        let gitignore = fs::read(full_dir.join(".gitignore"))
+            .map(|content| *blake3::hash(&content).as_bytes())
+            .unwrap_or([0u8; 32]);
+        let children = tracked_children.get(&dir);
+        let tracked_key =
+            *blake3::hash(children.map_or(String::new(), |c| c.join("\0")).as_bytes()).as_bytes();
+
+        let old = previous.get(dir.as_path());
+        let reusable = old.filter(|old| {
+            parents_unchanged
+                && old.mtime_sec == mtime_sec
+                && old.mtime_nsec == mtime_nsec
+                && old.gitignore == gitignore
+                && old.tracked == tracked_key
+                && old.untracked.iter().all(|entry| match &entry.witness {
+                    Some(witness) => {
+                        workdir.join(witness).symlink_metadata().is_ok()
+                            && !rules.should_ignore(witness)
+                    }
+                    None => true,
+                })
+        });
+
+        let entries = match reusable {
+            Some(old) => old.untracked.clone(),
+            None => scan_dir(workdir, &dir, &tracked, children, rules)?,
+        };
+        for entry in &entries {
+            untracked.push(dir.join(&entry.name));
+        }
+
+        // Tracked subdirectories are walked either way; only they can hold
+        // more untracked entries
+        let gitignore_unchanged =
+            parents_unchanged && old.is_some_and(|o| o.gitignore == gitignore);
+        for name in children.into_iter().flatten() {
+            let child = dir.join(name);
+            if workdir.join(&child).is_dir() && !rules.should_ignore(&child) {
+                dirs.push((child, gitignore_unchanged));
+            }
+        }
+
+        if mtime < racy_after {
+            next.dirs.push(CachedDir {
+                path: dir,
+                mtime_sec,
+                mtime_nsec,
+                gitignore,
+                tracked: tracked_key,
+                untracked: entries,
+            });
+        }
+    }
+
+    untracked.sort();
+    next.dirs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((untracked, next))
+}
+
+/// The untracked, non-ignored files a path from `scan_untracked` stands for:
+/// every such file under a collapsed directory, or the file itself
+pub fn expand_untracked(workdir: &Path, path: &Path, rules: &IgnoreRules) -> Vec<PathBuf> {
+    if !workdir.join(path).is_dir() {
+        return vec![path.to_path_buf()];
+    }
+    unignored_files(workdir, path, rules)
+        .filter_map(|p| p.strip_prefix(workdir).ok().map(Path::to_path_buf))
+        .collect()
+}
+
+/// Read one directory: its untracked files, and its untracked subdirectories
+/// collapsed to "name/"
+fn scan_dir(
+    workdir: &Path,
+    dir: &Path,
+    tracked: &HashSet<PathBuf>,
+    tracked_children: Option<&Vec<String>>,
+    rules: &IgnoreRules,
+) -> Result<Vec<CachedUntracked>> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(workdir.join(dir))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == ".git" || name == ".helix" {
+            continue;
+        }
+        let rel = dir.join(&name);
+        if rules.should_ignore(&rel) {
+            continue;
+        }
+        let name = name.to_string_lossy().into_owned();
+
+        if entry.file_type()?.is_dir() {
+            if tracked_children.is_some_and(|c| c.contains(&name)) {
+                continue;
+            }
+            let witness = unignored_files(workdir, &rel, rules)
+                .next()
+                .and_then(|p| p.strip_prefix(workdir).ok().map(Path::to_path_buf));
+            if let Some(witness) = witness {
+                entries.push(CachedUntracked {
+                    name: format!("{}/", name),
+                    witness: Some(witness),
+                });
+            }
+        } else if !tracked.contains(&rel) {
+            entries.push(CachedUntracked {
+                name,
+                witness: None,
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+fn tracked_paths(index: &HelixIndexData) -> HashSet<PathBuf> {
+    index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .map(|e| e.path.clone())
+        .collect()
+}
+
+fn split_time(time: SystemTime) -> (u64, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs(), since_epoch.subsec_nanos())
+}
+
+/// Files under `dir` that aren't ignored, without entering ignored directories
+fn unignored_files<'a>(
+    workdir: &'a Path,
+    dir: &Path,
+    rules: &'a IgnoreRules,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let ignored = move |path: &Path| {
+        path.strip_prefix(workdir)
+            .map(|rel| rules.should_ignore(rel))
+            .unwrap_or(true)
+    };
+
+    WalkDir::new(workdir.join(dir))
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(move |e| {
+            let name = e.file_name();
+            e.depth() == 0 || (name != ".git" && name != ".helix" && !ignored(e.path()))
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| !e.file_type().is_dir())
+        .map(|e| e.into_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_untracked_directories_are_collapsed() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        for (path, content) in [
+            ("src/lib.rs", "tracked"),
+            ("src/new.rs", "untracked file in a tracked dir"),
+            ("node_modules/a/index.js", "one"),
+            ("node_modules/b/index.js", "two"),
+            ("logs/debug.out", "all ignored"),
+            ("notes/keep.out", "re-included"),
+            (".gitignore", "*.out\n!keep.out\n"),
+        ] {
+            let full = repo.join(path);
+            fs::create_dir_all(full.parent().unwrap())?;
+            fs::write(full, content)?;
+        }
+        fs::create_dir_all(repo.join("empty"))?;
+        stage_paths(repo, &[PathBuf::from("src/lib.rs")])?;
+
+        let index = HelixIndexData::load_or_rebuild(repo)?;
+        let rules = IgnoreRules::load(repo);
+        let untracked = scan_untracked(repo, &index, &rules)?;
+        assert_eq!(
+            untracked,
+            vec![
+                PathBuf::from(".gitignore"),
+                PathBuf::from("helix.toml"),
+                PathBuf::from("node_modules/"),
+                PathBuf::from("notes/"),
+                PathBuf::from("src/new.rs"),
+            ]
+        );
+
+        let mut expanded = expand_untracked(repo, Path::new("node_modules/"), &rules);
+        expanded.sort();
+        assert_eq!(
+            expanded,
+            vec![
+                PathBuf::from("node_modules/a/index.js"),
+                PathBuf::from("node_modules/b/index.js"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_untracked_cache_reuse_and_invalidation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        fs::create_dir_all(repo.join("src"))?;
+        fs::write(repo.join("src/lib.rs"), "tracked")?;
+        fs::write(repo.join("src/new.rs"), "untracked")?;
+        stage_paths(repo, &[PathBuf::from("src/lib.rs")])?;
+
+        // Age the directories past the racy window so they get cached
+        let old = SystemTime::now() - Duration::from_secs(60);
+        for dir in ["", "src"] {
+            fs::File::open(repo.join(dir))?.set_modified(old)?;
+        }
+
+        let rules = IgnoreRules::load(repo);
+        let mut index = HelixIndexData::load_or_rebuild(repo)?;
+        let (first, cache) = scan_untracked_cached(repo, &index, &rules, None)?;
+        assert_eq!(cache.dirs.len(), 2);
+
+        // The cache survives a round trip through helix.idx
+        index.set_untracked_cache(Some(cache.clone()));
+        index.persist()?;
+        let index = HelixIndexData::load_or_rebuild(repo)?;
+        assert_eq!(index.untracked_cache(), Some(&cache));
+
+        // An unchanged directory is answered from the cache: a file slipped in
+        // without moving the mtime goes unseen, as it would in Git
+        fs::write(repo.join("src/sneaky.rs"), "")?;
+        fs::File::open(repo.join("src"))?.set_modified(old)?;
+        let (second, _) = scan_untracked_cached(repo, &index, &rules, index.untracked_cache())?;
+        assert_eq!(second, first);
+
+        // A real change moves the mtime and the directory is read again
+        fs::write(repo.join("src/another.rs"), "")?;
+        let (third, _) = scan_untracked_cached(repo, &index, &rules, index.untracked_cache())?;
+        assert!(third.contains(&PathBuf::from("src/sneaky.rs")));
+        assert!(third.contains(&PathBuf::from("src/another.rs")));
+
+        // Staging a file changes the directory's tracked set without touching it
+        fs::File::open(repo.join("src"))?.set_modified(old)?;
+        let (_, cache) = scan_untracked_cached(repo, &index, &rules, None)?;
+        stage_paths(repo, &[PathBuf::from("src/new.rs")])?;
+        fs::File::open(repo.join("src"))?.set_modified(old)?;
+        let index = HelixIndexData::load_or_rebuild(repo)?;
+        let (fourth, _) = scan_untracked_cached(repo, &index, &rules, Some(&cache))?;
+        assert!(!fourth.contains(&PathBuf::from("src/new.rs")));
+
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
 // Verification logic for detecting corruption in helix.idx
-
+//
+// `verify` checks that the file is there and intact (format and checksum).
+// `check_entries` looks at what it holds, for `helix index verify`: the header's
+// entry count against the entries in the file, flags that contradict each other,
+// paths listed twice and entries out of path order. `fix_entries` repairs what
+// has an obvious repair and writes the index back.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::helix_index::Reader;
-use anyhow::Result;
+use crate::helix_index::format::{Entry, EntryFlags, Extensions, Footer, FOOTER_SIZE};
+use crate::helix_index::journal::Journal;
+use crate::helix_index::{Header, Reader, Writer};
+use anyhow::{Context, Result};
 
 /// Verification result for helix.idx integrity
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -40,6 +51,12 @@ impl Verifier {
             return Ok(VerifyResult::Missing);
         }
 
+        // Parse the canonical file directly; Reader::read would fall back to the backup
+        let data = fs::read(self.repo_path.join(".helix/helix.idx"))?;
+        if reader.parse(&data).is_err() {
+            return Ok(VerifyResult::Corrupted);
+        }
+
         Ok(VerifyResult::Valid)
     }
 
@@ -52,6 +69,234 @@ impl Verifier {
     pub fn generation(&self) -> Result<u64> {
         Reader::new(&self.repo_path).generation()
     }
+
+    /// Problems with the entries of an intact index
+    pub fn check_entries(&self) -> Result<Vec<IndexIssue>> {
+        let loaded = self.load_entries()?;
+        Ok(find_issues(&loaded))
+    }
+
+    /// Repair the fixable problems `check_entries` finds and write the index back.
+    /// Returns the problems fixed; run `check_entries` again for what's left.
+    pub fn fix_entries(&self) -> Result<Vec<IndexIssue>> {
+        let mut loaded = self.load_entries()?;
+        let fixed: Vec<IndexIssue> = find_issues(&loaded)
+            .into_iter()
+            .filter(IndexIssue::fixable)
+            .collect();
+        if fixed.is_empty() {
+            return Ok(fixed);
+        }
+
+        // The last entry for a path is the newest; flags lose the half of each pair
+        // that can't be true
+        let mut seen = HashSet::new();
+        let mut entries: Vec<Entry> = loaded
+            .entries
+            .drain(..)
+            .rev()
+            .filter(|entry| seen.insert(entry.path.clone()))
+            .collect();
+        for entry in &mut entries {
+            for rule in FLAG_RULES {
+                if let (true, Some(drop)) = (rule.matches(entry.flags), rule.drop) {
+                    entry.flags.remove(drop);
+                }
+            }
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut header = loaded.header;
+        header.generation += 1;
+        header.entry_count = entries.len() as u32;
+        Writer::new_canonical(&self.repo_path).write_with_extensions(
+            &header,
+            &entries,
+            &loaded.extensions,
+        )?;
+        Ok(fixed)
+    }
+
+    /// The entries as readers see them: the base file, including any records past the
+    /// header's count, with the split-index journal applied
+    fn load_entries(&self) -> Result<LoadedEntries> {
+        let index_path = self.repo_path.join(".helix/helix.idx");
+        let data = fs::read(&index_path)
+            .with_context(|| format!("Failed to read {}", index_path.display()))?;
+        let mut index = Reader::new(&self.repo_path)
+            .parse_unchecked(&data)
+            .context("helix.idx is corrupted; `helix verify` and `helix repair` can help")?;
+
+        // A header that undercounts leaves whole entries where the extensions go. The
+        // journal keeps the header's count in step with the entries it adds, so this
+        // is the file's own count.
+        let counted = index.header.entry_count;
+        let entries_start = Header::HEADER_SIZE + counted as usize * Entry::ENTRY_MAX_SIZE;
+        let rest = &data[entries_start..data.len() - FOOTER_SIZE];
+        if !rest.is_empty() && Extensions::from_bytes(rest).is_err() {
+            let uncounted: Option<Vec<Entry>> = rest
+                .chunks(Entry::ENTRY_MAX_SIZE)
+                .map(|chunk| Entry::from_bytes(chunk).ok())
+                .collect();
+            if let Some(uncounted) = uncounted {
+                index.entries.extend(uncounted);
+            }
+        }
+        let in_file = index.entries.len();
+
+        let footer = Footer::from_bytes(&data[data.len() - FOOTER_SIZE..])?;
+        Journal::for_index(&index_path).apply(&mut index, &footer.checksum)?;
+
+        Ok(LoadedEntries {
+            header: index.header,
+            counted,
+            in_file,
+            entries: index.entries,
+            extensions: index.extensions,
+        })
+    }
+}
+
+struct LoadedEntries {
+    header: Header,
+    /// The entry count in helix.idx's own header
+    counted: u32,
+    /// Entries in helix.idx itself, before the journal
+    in_file: usize,
+    entries: Vec<Entry>,
+    extensions: Extensions,
+}
+
+/// Something wrong with the entries of an index whose checksum is fine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexIssue {
+    /// The header counts a different number of entries than the file holds
+    EntryCount { header: u32, actual: usize },
+    /// Flags that can't all be true of one file
+    InvalidFlags {
+        path: PathBuf,
+        problem: &'static str,
+    },
+    /// More than one entry for a path
+    DuplicatePath(PathBuf),
+    /// Entries aren't in path order, first noticed at this path
+    Unsorted(PathBuf),
+}
+
+impl IndexIssue {
+    /// Whether `fix_entries` repairs it
+    pub fn fixable(&self) -> bool {
+        match self {
+            IndexIssue::InvalidFlags { problem, .. } => FLAG_RULES
+                .iter()
+                .any(|rule| rule.problem == *problem && rule.drop.is_some()),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for IndexIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexIssue::EntryCount { header, actual } => write!(
+                f,
+                "header counts {} entries but the file holds {}",
+                header, actual
+            ),
+            IndexIssue::InvalidFlags { path, problem } => {
+                write!(f, "{}: {}", path.display(), problem)
+            }
+            IndexIssue::DuplicatePath(path) => {
+                write!(f, "{}: listed more than once", path.display())
+            }
+            IndexIssue::Unsorted(path) => {
+                write!(f, "{}: entries out of order from here", path.display())
+            }
+        }
+    }
+}
+
+/// Flags that contradict each other, and which one a fix drops
+struct FlagRule {
+    flags: EntryFlags,
+    /// Whether any of `flags` is enough, rather than all of them
+    any: bool,
+    problem: &'static str,
+    drop: Option<EntryFlags>,
+}
+
+impl FlagRule {
+    fn matches(&self, flags: EntryFlags) -> bool {
+        match self.any {
+            true => flags.intersects(self.flags),
+            false => flags.contains(self.flags),
+        }
+    }
+}
+
+const FLAG_RULES: &[FlagRule] = &[
+    // A file that is gone has no working tree changes
+    FlagRule {
+        flags: EntryFlags::DELETED.union(EntryFlags::MODIFIED),
+        any: false,
+        problem: "both deleted and modified",
+        drop: Some(EntryFlags::MODIFIED),
+    },
+    FlagRule {
+        flags: EntryFlags::TRACKED.union(EntryFlags::UNTRACKED),
+        any: false,
+        problem: "both tracked and untracked",
+        drop: Some(EntryFlags::UNTRACKED),
+    },
+    // Binary is the safe guess: its content is never converted
+    FlagRule {
+        flags: EntryFlags::ATTR_TEXT.union(EntryFlags::ATTR_BINARY),
+        any: false,
+        problem: "both text and binary",
+        drop: Some(EntryFlags::ATTR_TEXT),
+    },
+    FlagRule {
+        flags: EntryFlags::RESERVED6.union(EntryFlags::RESERVED7),
+        any: true,
+        problem: "reserved flags set",
+        drop: Some(EntryFlags::RESERVED6.union(EntryFlags::RESERVED7)),
+    },
+    // Whether it should be tracked or not isn't something to guess
+    FlagRule {
+        flags: EntryFlags::STAGED.union(EntryFlags::UNTRACKED),
+        any: false,
+        problem: "both staged and untracked",
+        drop: None,
+    },
+];
+
+fn find_issues(loaded: &LoadedEntries) -> Vec<IndexIssue> {
+    let mut issues = Vec::new();
+    if loaded.counted as usize != loaded.in_file {
+        issues.push(IndexIssue::EntryCount {
+            header: loaded.counted,
+            actual: loaded.in_file,
+        });
+    }
+
+    let mut seen = HashSet::new();
+    let mut unsorted = None;
+    for (i, entry) in loaded.entries.iter().enumerate() {
+        for rule in FLAG_RULES.iter().filter(|rule| rule.matches(entry.flags)) {
+            issues.push(IndexIssue::InvalidFlags {
+                path: entry.path.clone(),
+                problem: rule.problem,
+            });
+        }
+        if !seen.insert(&entry.path) {
+            issues.push(IndexIssue::DuplicatePath(entry.path.clone()));
+        }
+        if unsorted.is_none() && i > 0 && loaded.entries[i - 1].path > entry.path {
+            unsorted = Some(entry.path.clone());
+        }
+    }
+    issues.extend(unsorted.map(IndexIssue::Unsorted));
+    issues
 }
 
 #[cfg(test)]
@@ -62,7 +307,6 @@ mod tests {
         writer::Writer,
     };
     use helix_protocol::hash;
-    use std::fs;
     use std::path::PathBuf;
     use std::process::Command;
     use tempfile::TempDir;
@@ -199,6 +443,84 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_check_and_fix_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir_all(temp_dir.path().join(".helix"))?;
+
+        let entry = |path: &str, flags: EntryFlags| {
+            let mut entry = Entry::new(PathBuf::from(path), 1, 100, hash::ZERO_HASH, 0o100644);
+            entry.flags = flags;
+            entry
+        };
+        let entries = vec![
+            entry(
+                "b.txt",
+                EntryFlags::TRACKED | EntryFlags::DELETED | EntryFlags::MODIFIED,
+            ),
+            entry("a.txt", EntryFlags::TRACKED),
+            entry("a.txt", EntryFlags::TRACKED | EntryFlags::STAGED),
+            entry("c.txt", EntryFlags::STAGED | EntryFlags::UNTRACKED),
+        ];
+        // Out of order, with a header that undercounts what follows it; the writer
+        // won't produce that, so the file is put together by hand
+        let mut bytes = Header::new(7, 2).to_bytes().to_vec();
+        for entry in &entries {
+            bytes.extend_from_slice(&entry.to_bytes()?);
+        }
+        let footer = Footer::compute(&bytes);
+        bytes.extend_from_slice(&footer.to_bytes());
+        fs::write(temp_dir.path().join(".helix/helix.idx"), bytes)?;
+
+        let verifier = Verifier::new(temp_dir.path());
+        assert_eq!(verifier.verify()?, VerifyResult::Corrupted);
+        let issues = verifier.check_entries()?;
+        assert_eq!(
+            issues,
+            [
+                IndexIssue::EntryCount {
+                    header: 2,
+                    actual: 4
+                },
+                IndexIssue::InvalidFlags {
+                    path: "b.txt".into(),
+                    problem: "both deleted and modified"
+                },
+                IndexIssue::DuplicatePath("a.txt".into()),
+                IndexIssue::InvalidFlags {
+                    path: "c.txt".into(),
+                    problem: "both staged and untracked"
+                },
+                IndexIssue::Unsorted("a.txt".into()),
+            ]
+        );
+
+        assert_eq!(verifier.fix_entries()?.len(), 4);
+        assert_eq!(verifier.verify()?, VerifyResult::Valid);
+        assert_eq!(
+            verifier.check_entries()?,
+            [IndexIssue::InvalidFlags {
+                path: "c.txt".into(),
+                problem: "both staged and untracked"
+            }]
+        );
+        let index = Reader::new(temp_dir.path()).read()?;
+        assert_eq!(index.header.generation, 8);
+        let paths: Vec<_> = index
+            .entries
+            .iter()
+            .map(|e| e.path.to_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["a.txt", "b.txt", "c.txt"]);
+        // The newest a.txt, and b.txt without the impossible flag
+        assert!(index.entries[0].flags.contains(EntryFlags::STAGED));
+        assert_eq!(
+            index.entries[1].flags,
+            EntryFlags::TRACKED | EntryFlags::DELETED
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_exists() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -29,10 +29,14 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::helix_index::{format::Footer, Entry, Header};
+use crate::helix_index::{
+    format::{self, Extensions, Footer},
+    journal::{Journal, JournalRecord},
+    lock::IndexLock,
+    Entry, Header,
+};
 use anyhow::{Context, Result};
 use rayon::prelude::*;
-use sha2::{Digest, Sha256};
 use std::io::BufWriter;
 use std::sync::mpsc;
 use std::thread;
@@ -70,30 +74,63 @@ impl Writer {
 
     /// Write complete index atomically (immutable operation)
     ///
+    /// Holds `.helix/helix.idx.lock` for the duration of the write.
+    ///
     /// For canonical index (durable=true):
     /// 1. Stream entries to buffered writer
     /// 2. fsync() to ensure durability (slower but safe)
     /// 3. fsync() directory to ensure rename is durable
-    /// 4. Atomic rename
+    /// 4. Keep the previous generation as helix.idx.bak
+    /// 5. Atomic rename
     ///
     /// For cached index (durable=false):
     /// 1. Stream entries to buffered writer  
     /// 2. flush() only (10x faster, safe for derived cache)
     /// 3. Atomic rename
     pub fn write(&self, header: &Header, entries: &[Entry]) -> Result<()> {
+        self.write_with_extensions(header, entries, &Extensions::default())
+    }
+
+    /// `write`, with `extensions` stored between the entries and the footer
+    ///
+    /// Entries are written sorted by path whatever order they are passed in; two
+    /// entries for one path are an error.
+    pub fn write_with_extensions(
+        &self,
+        header: &Header,
+        entries: &[Entry],
+        extensions: &Extensions,
+    ) -> Result<()> {
+        let sorted;
+        let entries = match format::first_out_of_order(entries) {
+            None => entries,
+            Some(_) => {
+                let mut copy = entries.to_vec();
+                format::sort_entries(&mut copy);
+                if let Some(i) = format::first_out_of_order(&copy) {
+                    anyhow::bail!("Duplicate entry for path: {}", copy[i].path.display());
+                }
+                sorted = copy;
+                &sorted[..]
+            }
+        };
+        let extensions = extensions.to_bytes();
         let helix_dir = self.repo_path.join(".helix");
         let index_path = helix_dir.join("helix.idx");
         let temp_path = helix_dir.join("helix.idx.new");
 
         fs::create_dir_all(&helix_dir).context("Failed to create .helix directory")?;
 
+        // Serialize concurrent writers (e.g. add racing fsmonitor); released on return
+        let _lock = IndexLock::acquire(&self.repo_path)?;
+
         // Choose strategy based on size
         if entries.len() > 10000 {
             // For huge indexes: parallel checksum with streaming writes
-            self.write_large_index(&temp_path, header, entries)?;
+            self.write_large_index(&temp_path, header, entries, &extensions)?;
         } else {
             // For normal indexes: simple streaming
-            self.write_streaming(&temp_path, header, entries)?;
+            self.write_streaming(&temp_path, header, entries, &extensions)?;
         }
 
         // Ensure durability if required
@@ -105,22 +142,62 @@ impl Writer {
             dir.sync_all().context("Failed to fsync .helix directory")?;
         }
 
+        // Keep the previous generation so readers can recover from a corrupted index
+        if self.durable && index_path.exists() {
+            self.backup_current(&index_path)?;
+        }
+
         // Atomic rename
         fs::rename(&temp_path, &index_path).context("Failed to rename temp file to index")?;
 
+        // Journal records are folded into the full write; a stale journal left by a
+        // crash here is ignored by readers since it names the previous base
+        Journal::new(&self.repo_path).clear()?;
+
+        Ok(())
+    }
+
+    /// Append entry updates to the split-index journal instead of rewriting helix.idx.
+    /// Readers merge the journal on load; the next full `write` compacts it.
+    pub fn append_journal(&self, records: &[JournalRecord], generation: u64) -> Result<()> {
+        let _lock = IndexLock::acquire(&self.repo_path)?;
+
+        if !self.exists() {
+            anyhow::bail!("Cannot journal updates without a base helix.idx");
+        }
+
+        Journal::new(&self.repo_path).append(records, generation)
+    }
+
+    /// Point helix.idx.bak at the current index. A hard link is enough since the
+    /// rename that follows replaces the directory entry, not the file contents.
+    fn backup_current(&self, index_path: &Path) -> Result<()> {
+        let backup_path = self.backup_path();
+        if backup_path.exists() {
+            fs::remove_file(&backup_path).context("Failed to remove old index backup")?;
+        }
+        if fs::hard_link(index_path, &backup_path).is_err() {
+            fs::copy(index_path, &backup_path).context("Failed to back up index")?;
+        }
         Ok(())
     }
 
     /// Streaming write for normal-sized indexes (most common case)
     /// Optimized for minimal memory usage and syscall overhead
-    fn write_streaming(&self, temp_path: &Path, header: &Header, entries: &[Entry]) -> Result<()> {
+    fn write_streaming(
+        &self,
+        temp_path: &Path,
+        header: &Header,
+        entries: &[Entry],
+        extensions: &[u8],
+    ) -> Result<()> {
         use std::io::BufWriter;
 
         let file = File::create(temp_path).context("Failed to create temp index file")?;
         // Use 1MB buffer to minimize syscalls (Linux optimal buffer size is typically 128KB-1MB)
         let mut writer = BufWriter::with_capacity(1024 * 1024, file);
 
-        let mut hasher = Sha256::new();
+        let mut hasher = blake3::Hasher::new();
 
         // Write and hash header
         let header_bytes = header.to_bytes();
@@ -136,9 +213,11 @@ impl Writer {
             }
         }
 
+        writer.write_all(extensions)?;
+        hasher.update(extensions);
+
         // Write footer
-        let checksum: [u8; 32] = hasher.finalize().into();
-        let footer = Footer::new(checksum);
+        let footer = Footer::new(*hasher.finalize().as_bytes());
         writer.write_all(&footer.to_bytes())?;
 
         if self.durable {
@@ -173,6 +252,7 @@ impl Writer {
         temp_path: &Path,
         header: &Header,
         entries: &[Entry],
+        extensions: &[u8],
     ) -> Result<()> {
         let file = File::create(temp_path).context("Failed to create temp index file")?;
         let mut writer = BufWriter::with_capacity(2 * 1024 * 1024, file); // 2MB buffer for large writes
@@ -186,7 +266,7 @@ impl Writer {
 
         // Spawn hasher thread
         let hasher_handle = thread::spawn(move || {
-            let mut hasher = Sha256::new();
+            let mut hasher = blake3::Hasher::new();
 
             // Hash header (received inline)
             if let Ok(header_data) = rx.recv() {
@@ -198,8 +278,7 @@ impl Writer {
                 hasher.update(&data);
             }
 
-            let checksum: [u8; 32] = hasher.finalize().into();
-            checksum
+            *hasher.finalize().as_bytes()
         });
 
         // Send header to hasher
@@ -231,6 +310,9 @@ impl Writer {
             }
         }
 
+        writer.write_all(extensions)?;
+        tx.send(extensions.to_vec()).ok();
+
         // Close channel and wait for checksum
         drop(tx);
         let checksum = hasher_handle
@@ -261,6 +343,29 @@ impl Writer {
         self.repo_path.join(".helix/helix.idx")
     }
 
+    /// Replace helix.idx with the previous generation kept in helix.idx.bak
+    pub fn restore_backup(&self) -> Result<()> {
+        let helix_dir = self.repo_path.join(".helix");
+        let temp_path = helix_dir.join("helix.idx.new");
+
+        let _lock = IndexLock::acquire(&self.repo_path)?;
+
+        fs::copy(self.backup_path(), &temp_path).context("Failed to copy index backup")?;
+        if self.durable {
+            File::open(&temp_path)
+                .and_then(|f| f.sync_all())
+                .context("Failed to fsync restored index")?;
+        }
+        fs::rename(&temp_path, self.index_path()).context("Failed to restore index backup")?;
+
+        Ok(())
+    }
+
+    /// Get the path of the previous-generation backup
+    pub fn backup_path(&self) -> PathBuf {
+        self.repo_path.join(".helix/helix.idx.bak")
+    }
+
     /// Create a new builder for incremental updates
     pub fn builder(&self, header: Header) -> IndexBuilder {
         IndexBuilder {
@@ -339,12 +444,7 @@ impl IndexBuilder {
 
     /// Sort entries by path (required before commit)
     pub fn sort_entries(&mut self) -> &mut Self {
-        // Parallel sort for large datasets
-        if self.entries.len() > 10000 {
-            self.entries.par_sort_by(|a, b| a.path.cmp(&b.path));
-        } else {
-            self.entries.sort_by(|a, b| a.path.cmp(&b.path));
-        }
+        format::sort_entries(&mut self.entries);
         self
     }
 
@@ -474,6 +574,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_canonical_write_keeps_previous_generation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        let writer = Writer::new_canonical(repo_path);
+        writer.write(&Header::new(1, 0), &[])?;
+        assert!(!writer.backup_path().exists());
+
+        writer.write(&Header::new(2, 0), &[])?;
+        assert!(writer.backup_path().exists());
+
+        let reader = crate::helix_index::Reader::new(repo_path);
+        assert_eq!(reader.read()?.header.generation, 2);
+        assert_eq!(reader.read_backup()?.header.generation, 1);
+
+        writer.restore_backup()?;
+        assert_eq!(reader.read()?.header.generation, 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_builder_add_entry() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -1,20 +1,56 @@
+use gix::glob::pattern::Case;
+use gix::ignore::search::Ignore;
+use gix::ignore::Search;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::init_command::HelixConfig;
 
 /// Ignore rules from multiple sources with clear precedence:
 /// 1. Built-in patterns (always apply)
-/// 2. .gitignore (repo-level git rules)
-/// 3. helix.toml (repo-level helix rules)
-/// 4. ~/.helix.toml (user-level helix rules)
-
-#[derive(Debug, Clone)]
+/// 2. helix.toml (repo-level helix rules)
+/// 3. ~/.helix.toml (user-level helix rules)
+/// 4. Git excludes, with full gitignore semantics (negation, directory-only
+///    and anchored patterns). Deeper .gitignore files win over shallower ones,
+///    which win over .git/info/exclude, which wins over core.excludesFile.
+///
+/// Helix patterns only ever add to what is ignored; a `!pattern` in a
+/// .gitignore can re-include a file Git would ignore, but not one of these.
+
+#[derive(Debug, Clone, Default)]
 pub struct IgnoreRules {
     globset: GlobSet,
+    gitignore: Search,
+    root: PathBuf,
+}
+
+const BUILT_IN_PATTERNS: &[&str] = &[
+    // Git internal directory
+    ".git",
+    ".git/**",
+    // Helix internal directory
+    ".helix",
+    ".helix/**",
+    // Helix cache directory
+    ".helix/cache",
+    ".helix/cache/**",
+    // Temporary files during index writes
+    "**/.helix.idx.new",
+    ".helix.idx.new",
+];
+
+/// The rule that decided whether a path is ignored, for `helix check-ignore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoreMatch {
+    /// "built-in", or the file the pattern came from
+    pub source: String,
+    /// Line within `source`, for gitignore files
+    pub line: Option<usize>,
+    pub pattern: String,
+    /// False when a negated (`!`) pattern re-included the path
+    pub ignored: bool,
 }
 
 impl IgnoreRules {
@@ -22,7 +58,6 @@ impl IgnoreRules {
         let mut builder = GlobSetBuilder::new();
 
         Self::add_built_in_patterns(&mut builder);
-        Self::add_gitignore_patterns(&mut builder, repo_path);
         Self::add_helix_repo_patterns(&mut builder, repo_path);
         Self::add_helix_global_patterns(&mut builder);
 
@@ -33,44 +68,79 @@ impl IgnoreRules {
             fallback.build().unwrap()
         });
 
-        Self { globset }
+        let mut rules = Self {
+            globset,
+            gitignore: Search::default(),
+            root: repo_path.to_path_buf(),
+        };
+        rules.load_git_excludes();
+        rules
     }
 
     /// Built-in patterns that always apply
     /// These cover Helix internal files
     fn add_built_in_patterns(builder: &mut GlobSetBuilder) {
-        // Git internal directory
-        Self::add_pattern(builder, ".git");
-        Self::add_pattern(builder, ".git/**");
-
-        // Helix internal directory
-        Self::add_pattern(builder, ".helix");
-        Self::add_pattern(builder, ".helix/**");
-
-        // Helix cache directory
-        Self::add_pattern(builder, ".helix/cache");
-        Self::add_pattern(builder, ".helix/cache/**");
-
-        // Temporary files during index writes
-        Self::add_pattern(builder, "**/.helix.idx.new");
-        Self::add_pattern(builder, ".helix.idx.new");
+        for pattern in BUILT_IN_PATTERNS {
+            Self::add_pattern(builder, pattern);
+        }
     }
 
-    /// Load patterns from .gitignore
-    fn add_gitignore_patterns(builder: &mut GlobSetBuilder, repo_path: &Path) {
-        let gitignore_path = repo_path.join(".gitignore");
+    /// Load Git's excludes: the global excludes file, .git/info/exclude, and
+    /// every .gitignore in the tree. Like Git, .gitignore files inside ignored
+    /// directories are not read.
+    fn load_git_excludes(&mut self) {
+        let mut buf = Vec::new();
+
+        self.gitignore = match gix::open(&self.root) {
+            Ok(repo) => {
+                let excludes_file = repo
+                    .config_snapshot()
+                    .trusted_path("core.excludesFile")
+                    .and_then(Result::ok)
+                    .map(|path| path.into_owned())
+                    .or_else(default_excludes_file);
+                Search::from_git_dir(repo.git_dir(), excludes_file, &mut buf, Ignore::default())
+                    .unwrap_or_default()
+            }
+            Err(_) => {
+                // Not a Git repository, but the user's global excludes still apply
+                let mut search = Search::default();
+                if let Some(path) = default_excludes_file() {
+                    if let Ok(bytes) = fs::read(&path) {
+                        search.add_patterns_buffer(&bytes, path, None, Ignore::default());
+                    }
+                }
+                search
+            }
+        };
 
-        if let Ok(file) = File::open(gitignore_path) {
-            let reader = BufReader::new(file);
-            for line in reader.lines().flatten() {
-                let line = line.trim();
+        // Parents are loaded before children, so deeper files take precedence
+        let mut dirs = vec![PathBuf::new()];
+        while let Some(dir) = dirs.pop() {
+            let abs_dir = self.root.join(&dir);
+
+            let gitignore_path = abs_dir.join(".gitignore");
+            if let Ok(bytes) = fs::read(&gitignore_path) {
+                self.gitignore.add_patterns_buffer(
+                    &bytes,
+                    gitignore_path,
+                    Some(&self.root),
+                    Ignore::default(),
+                );
+            }
 
-                // Skip empty lines and comments
-                if line.is_empty() || line.starts_with('#') {
+            let Ok(entries) = fs::read_dir(&abs_dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
                     continue;
                 }
-
-                Self::add_pattern(builder, line);
+                let rel_path = dir.join(entry.file_name());
+                if self.globset.is_match(&rel_path) || self.is_git_ignored(&rel_path, true) {
+                    continue;
+                }
+                dirs.push(rel_path);
             }
         }
     }
@@ -83,28 +153,37 @@ impl IgnoreRules {
 
     /// Load patterns from ~/.helix.toml (global)
     fn add_helix_global_patterns(builder: &mut GlobSetBuilder) {
-        if let Ok(home) = env::var("HOME") {
-            let config_path = Path::new(&home).join(".helix.toml");
+        if let Some(config_path) = Self::helix_global_config() {
             Self::add_helix_toml_patterns(builder, &config_path);
         }
     }
 
+    fn helix_global_config() -> Option<PathBuf> {
+        env::var("HOME")
+            .ok()
+            .map(|home| Path::new(&home).join(".helix.toml"))
+    }
+
     fn add_helix_toml_patterns(builder: &mut GlobSetBuilder, path: &Path) {
+        for pattern in Self::helix_toml_patterns(path) {
+            Self::add_pattern(builder, &pattern);
+        }
+    }
+
+    fn helix_toml_patterns(path: &Path) -> Vec<String> {
         if !path.exists() {
-            return;
+            return Vec::new();
         }
 
         let Ok(contents) = std::fs::read_to_string(path) else {
-            return;
+            return Vec::new();
         };
 
         let Ok(cfg) = toml::from_str::<HelixConfig>(&contents) else {
-            return;
+            return Vec::new();
         };
 
-        for pattern in cfg.ignore.patterns {
-            Self::add_pattern(builder, &pattern);
-        }
+        cfg.ignore.patterns
     }
 
     /// Add a pattern to the builder, normalizing it for proper matching
@@ -171,12 +250,132 @@ impl IgnoreRules {
         patterns
     }
 
+    /// Fingerprint of the repository-wide ignore sources: both helix.toml
+    /// files, .git/info/exclude and the default global excludes file. The
+    /// .gitignore files in the tree aren't included; anything caching ignore
+    /// decisions per directory checks those itself.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let sources = [
+            Some(self.root.join("helix.toml")),
+            Self::helix_global_config(),
+            Some(self.root.join(".git/info/exclude")),
+            default_excludes_file(),
+        ];
+
+        let mut hasher = blake3::Hasher::new();
+        for source in sources {
+            let contents = source.and_then(|path| fs::read(path).ok());
+            // Length-prefixed so moving a pattern between files changes the fingerprint
+            let contents = contents.unwrap_or_default();
+            hasher.update(&(contents.len() as u64).to_le_bytes());
+            hasher.update(&contents);
+        }
+        *hasher.finalize().as_bytes()
+    }
+
     /// Check if a path should be ignored
     pub fn should_ignore(&self, path: &Path) -> bool {
-        self.globset.is_match(path)
+        let path = self.relative(path);
+        if self.globset.is_match(path) {
+            return true;
+        }
+
+        let is_dir = self.root.join(path).is_dir();
+        self.is_git_ignored(path, is_dir)
+    }
+
+    /// Which rule decides whether `path` is ignored, if any
+    pub fn explain(&self, path: &Path) -> Option<IgnoreMatch> {
+        let path = self.relative(path);
+
+        if self.globset.is_match(path) {
+            let built_in = BUILT_IN_PATTERNS
+                .iter()
+                .map(|pattern| ("built-in".to_string(), pattern.to_string()));
+            let repo_config = self.root.join("helix.toml");
+            let helix = [Some(repo_config), Self::helix_global_config()]
+                .into_iter()
+                .flatten()
+                .flat_map(|config| {
+                    let source = config.display().to_string();
+                    Self::helix_toml_patterns(&config)
+                        .into_iter()
+                        .map(move |pattern| (source.clone(), pattern))
+                });
+
+            return built_in
+                .chain(helix)
+                .find(|(_, pattern)| {
+                    let mut builder = GlobSetBuilder::new();
+                    Self::add_pattern(&mut builder, pattern);
+                    builder.build().is_ok_and(|set| set.is_match(path))
+                })
+                .map(|(source, pattern)| IgnoreMatch {
+                    source,
+                    line: None,
+                    pattern,
+                    ignored: true,
+                });
+        }
+
+        let is_dir = self.root.join(path).is_dir();
+        self.git_match(path, is_dir)
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+
+    fn is_git_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        self.git_match(path, is_dir)
+            .is_some_and(|matched| matched.ignored)
+    }
+
+    /// Match `path` against the Git excludes. As in Git, once a parent
+    /// directory is ignored nothing below it can be re-included.
+    fn git_match(&self, path: &Path, is_dir: bool) -> Option<IgnoreMatch> {
+        let depth = path.components().count();
+        let mut prefix = PathBuf::new();
+
+        for (i, component) in path.components().enumerate() {
+            prefix.push(component);
+            let last = i + 1 == depth;
+
+            let rel_path = gix::path::to_unix_separators_on_windows(gix::path::into_bstr(&prefix));
+            let Some(matched) = self.gitignore.pattern_matching_relative_path(
+                rel_path.as_ref(),
+                Some(!last || is_dir),
+                Case::Sensitive,
+            ) else {
+                continue;
+            };
+
+            let ignored = !matched.pattern.is_negative();
+            if ignored || last {
+                return Some(IgnoreMatch {
+                    source: matched
+                        .source
+                        .map(|source| {
+                            let source = source.strip_prefix(&self.root).unwrap_or(source);
+                            source.display().to_string()
+                        })
+                        .unwrap_or_default(),
+                    line: Some(matched.sequence_number),
+                    pattern: matched.pattern.to_string(),
+                    ignored,
+                });
+            }
+        }
+
+        None
     }
 }
 
+/// Git's default core.excludesFile: $XDG_CONFIG_HOME/git/ignore or ~/.config/git/ignore
+fn default_excludes_file() -> Option<PathBuf> {
+    gix::path::env::xdg_config("ignore", &mut |name| env::var_os(name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,6 +423,7 @@ mod tests {
         IgnoreRules::add_pattern(&mut builder, ".helix/*");
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         assert!(rules.should_ignore(Path::new(".helix")));
@@ -238,6 +438,7 @@ mod tests {
         IgnoreRules::add_pattern(&mut builder, ".git");
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         assert!(rules.should_ignore(Path::new(".git")));
@@ -251,6 +452,7 @@ mod tests {
         IgnoreRules::add_pattern(&mut builder, "HEAD");
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         assert!(rules.should_ignore(Path::new("HEAD")));
@@ -264,6 +466,7 @@ mod tests {
         IgnoreRules::add_pattern(&mut builder, "*.log");
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         assert!(rules.should_ignore(Path::new("debug.log")));
@@ -277,6 +480,7 @@ mod tests {
         IgnoreRules::add_built_in_patterns(&mut builder);
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         assert!(!rules.should_ignore(Path::new("src/main.rs")));
@@ -342,6 +546,7 @@ patterns = [
         IgnoreRules::add_built_in_patterns(&mut builder);
         let rules = IgnoreRules {
             globset: builder.build().unwrap(),
+            ..Default::default()
         };
 
         // Should ignore .helix directory
@@ -363,4 +568,74 @@ patterns = [
         assert!(!rules.should_ignore(Path::new("src/main.rs")));
         assert!(!rules.should_ignore(Path::new("README.md")));
     }
+
+    #[test]
+    fn test_gitignore_negation_and_directory_only_patterns() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo_path = temp_dir.path();
+
+        std::fs::create_dir_all(repo_path.join("build")).unwrap();
+        std::fs::create_dir_all(repo_path.join("src")).unwrap();
+        std::fs::write(
+            repo_path.join(".gitignore"),
+            "*.log\n!keep.log\nbuild/\n/root_only.txt\n",
+        )
+        .unwrap();
+
+        let rules = IgnoreRules::load(repo_path);
+
+        assert!(rules.should_ignore(Path::new("debug.log")));
+        assert!(!rules.should_ignore(Path::new("keep.log")));
+        assert!(rules.should_ignore(Path::new("build")));
+        assert!(rules.should_ignore(Path::new("build/out.o")));
+        // Directory-only pattern doesn't match a file of the same name
+        assert!(!rules.should_ignore(Path::new("src/build")));
+        // Anchored pattern only matches at the root
+        assert!(rules.should_ignore(Path::new("root_only.txt")));
+        assert!(!rules.should_ignore(Path::new("src/root_only.txt")));
+    }
+
+    #[test]
+    fn test_nested_gitignore_and_info_exclude() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo_path = temp_dir.path();
+        std::process::Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(repo_path)
+            .output()
+            .unwrap();
+
+        std::fs::create_dir_all(repo_path.join("docs/drafts")).unwrap();
+        std::fs::create_dir_all(repo_path.join("vendor")).unwrap();
+        std::fs::write(repo_path.join(".gitignore"), "*.tmp\nvendor/\n").unwrap();
+        std::fs::write(repo_path.join("docs/.gitignore"), "!important.tmp\n*.md\n").unwrap();
+        // Inside an ignored directory, so never read
+        std::fs::write(repo_path.join("vendor/.gitignore"), "!*\n").unwrap();
+        std::fs::write(repo_path.join(".git/info/exclude"), "secrets.env\n").unwrap();
+
+        let rules = IgnoreRules::load(repo_path);
+
+        assert!(rules.should_ignore(Path::new("scratch.tmp")));
+        assert!(!rules.should_ignore(Path::new("docs/important.tmp")));
+        assert!(rules.should_ignore(Path::new("docs/drafts/intro.md")));
+        assert!(!rules.should_ignore(Path::new("README.md")));
+        assert!(rules.should_ignore(Path::new("vendor/lib.rs")));
+        assert!(rules.should_ignore(Path::new("secrets.env")));
+
+        let matched = rules.explain(Path::new("docs/drafts/intro.md")).unwrap();
+        assert_eq!(matched.source, "docs/.gitignore");
+        assert_eq!(matched.line, Some(2));
+        assert_eq!(matched.pattern, "*.md");
+        assert!(matched.ignored);
+
+        let matched = rules.explain(Path::new("docs/important.tmp")).unwrap();
+        assert_eq!(matched.pattern, "!important.tmp");
+        assert!(!matched.ignored);
+
+        assert_eq!(
+            rules.explain(Path::new(".helix/HEAD")).unwrap().source,
+            "built-in"
+        );
+        assert_eq!(rules.explain(Path::new("README.md")), None);
+    }
 }
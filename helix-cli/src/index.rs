@@ -88,7 +88,12 @@ pub struct IndexEntryIter<'a> {
 impl GitIndex {
     // open and memory map .git/index
     pub fn open(repo_root: &Path) -> Result<Self> {
-        let mmap = ReadOnlyMmap::open(&repo_root.join(".git/index"))?;
+        Self::open_at(&repo_root.join(".git/index"))
+    }
+
+    // open and memory map an index file wherever it lives (e.g. a worktree's git dir)
+    pub fn open_at(index_path: &Path) -> Result<Self> {
+        let mmap = ReadOnlyMmap::open(index_path)?;
         let buf = mmap.bytes();
 
         if buf.len() < 12 {
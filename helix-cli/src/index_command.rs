@@ -0,0 +1,128 @@
+/*
+`helix index` - maintenance of .helix/helix.idx itself.
+
+  migrate          upgrade an index written by an older helix to the current
+                   format, keeping the original as .helix/helix.idx.v<N>.
+                   Reading the index does this anyway; this just does it now.
+  migrate --check  report the index's format version and the migrations it
+                   needs, without writing. Fails if any are needed, so
+                   scripts can check a repo before rolling out a new helix.
+  verify           check the entries of an intact index: the header's entry
+                   count, flags that contradict each other (e.g. deleted and
+                   modified), paths listed twice and entries out of order.
+                   Fails if anything is wrong.
+  verify --fix     also repair what has an obvious repair: recount, drop the
+                   impossible flag, keep the newest entry for a path, sort.
+                   Staged-but-untracked entries are left for `helix add` or
+                   `helix restore --staged` to settle.
+
+Corruption of the file itself (format, checksum) is `helix verify`'s job.
+*/
+use anyhow::{bail, Result};
+use std::path::Path;
+
+use crate::helix_index::format::VERSION;
+use crate::helix_index::migrate;
+use crate::helix_index::verify::Verifier;
+
+pub fn migrate(repo_path: &Path, check: bool) -> Result<()> {
+    if !repo_path.join(".helix").join("helix.idx").exists() {
+        bail!(
+            "No index at {}",
+            repo_path.join(".helix/helix.idx").display()
+        );
+    }
+    let version = migrate::index_version(repo_path)?;
+    let pending = migrate::pending(version)?;
+    if pending.is_empty() {
+        println!("helix.idx is up to date (format v{VERSION})");
+        return Ok(());
+    }
+
+    if check {
+        println!("helix.idx is format v{version}; this helix writes v{VERSION}:");
+        for migration in &pending {
+            println!(
+                "  v{} -> v{}  {}",
+                migration.from,
+                migration.from + 1,
+                migration.summary
+            );
+        }
+        bail!("helix.idx needs migrating; run `helix index migrate`");
+    }
+
+    migrate::migrate_in_place(repo_path)?;
+    println!(
+        "Migrated helix.idx from v{version} to v{VERSION} (original kept as {})",
+        migrate::backup_path(repo_path, version).display()
+    );
+    Ok(())
+}
+
+pub fn verify(repo_path: &Path, fix: bool) -> Result<()> {
+    let verifier = Verifier::new(repo_path);
+    if !verifier.exists() {
+        bail!(
+            "No index at {}",
+            repo_path.join(".helix/helix.idx").display()
+        );
+    }
+
+    if fix {
+        let fixed = verifier.fix_entries()?;
+        for issue in &fixed {
+            println!("fixed: {}", issue);
+        }
+    }
+
+    let issues = verifier.check_entries()?;
+    if issues.is_empty() {
+        println!(
+            "helix.idx is consistent (generation {})",
+            verifier.generation()?
+        );
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let fixable = issues.iter().filter(|issue| issue.fixable()).count();
+    match fixable {
+        0 => bail!("helix.idx has {} problem(s)", issues.len()),
+        _ => bail!(
+            "helix.idx has {} problem(s), {} fixable with `helix index verify --fix`",
+            issues.len(),
+            fixable
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::format::Header;
+    use sha2::{Digest, Sha256};
+    use std::fs;
+
+    #[test]
+    fn test_check_leaves_an_old_index_alone() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        let mut header = Header::new(1, 0);
+        header.version = 1;
+        let mut bytes = header.to_bytes().to_vec();
+        let footer: [u8; 32] = Sha256::digest(&bytes).into();
+        bytes.extend_from_slice(&footer);
+        fs::write(repo.join(".helix/helix.idx"), &bytes)?;
+
+        assert!(migrate(repo, true).is_err());
+        assert_eq!(fs::read(repo.join(".helix/helix.idx"))?, bytes);
+
+        migrate(repo, false)?;
+        assert_eq!(migrate::index_version(repo)?, VERSION);
+        migrate(repo, true)?;
+        Ok(())
+    }
+}
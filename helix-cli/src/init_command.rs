@@ -7,7 +7,8 @@ existing Git repository, and printing next-step guidance.
 
 High-level flow
 ---------------
-`init_helix_repo` is the main entry point. It:
+`init_helix_repo` is the main entry point. Steps 1-4 are `init_repo_layout`,
+which `helix clone` uses on its own. It:
 
 1. Creates the `.helix` directory tree and object/ref subdirectories
    (create_directory_structure).
@@ -43,12 +44,27 @@ Actual import from Git is performed by `import_from_git`, which:
   were imported.
 - Prints a short summary including the file count and elapsed time.
 
+Ctrl-C during the import doesn't kill the process: it stops after the batch of
+commits being converted, with everything before it stored and recorded in
+`.helix/maps/commits`. `resume_import` (`helix init --resume`) then runs
+the import again, converting only the commits that aren't mapped yet.
+
+Bare repositories
+-----------------
+`init_bare_repo` (`helix init --bare`) creates only what a server stores:
+`.helix/HEAD`, `.helix/objects` and `.helix/refs`, plus the `.helix/bare`
+marker. There is no index, state file or helix.toml, because nothing is ever
+checked out there; the repo changes only by being pushed to, typically as a
+`file://` remote. RepoContext reports the marker as `bare`, and commands that
+need a working tree refuse to run in such a repo.
+
 Filesystem helpers
 ------------------
 - `create_directory_structure`:
   Creates `.helix`, `.helix/objects` and subdirectories for blobs/trees/commits,
   plus `.helix/refs` and subdirectories for heads/tags. All calls are safe and
-  idempotent: existing directories are left untouched.
+  idempotent: existing directories are left untouched. A new object store
+  records the layout from `[core] object_layout` (fanout by default).
 
 - `create_empty_index`:
   Creates `.helix/helix.idx` with an empty index and generation 1 if it does not
@@ -73,28 +89,79 @@ All functions are designed to be safe to call multiple times: running
 data, and will only create missing pieces.
 */
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use console::style;
+use helix_protocol::storage::{FsObjectStore, ObjectLayout};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs,
     io::{stdin, BufRead},
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, Arc},
 };
 
+use crate::gc_command::configured_layout;
 use crate::helix_index::{sync::SyncEngine, Header, Writer};
+use crate::line_endings::LineEndings;
+use crate::output::ColorMode;
+use crate::path_policy::PathPolicy;
+use crate::tui::{DateFormat, KeyBindings, Theme};
 
 pub fn init_helix_repo(repo_path: &Path, auto: Option<String>) -> Result<()> {
+    init_repo_layout(repo_path)?;
+    detect_git(repo_path, auto)?;
+
+    Ok(())
+}
+
+/// Everything `helix init` creates on disk, without the Git import or any output
+pub fn init_repo_layout(repo_path: &Path) -> Result<()> {
     create_directory_structure(repo_path)?;
+    PathPolicy::init(repo_path)?;
     create_empty_index(repo_path)?;
     create_head_file(repo_path)?;
     create_repo_config(repo_path)?;
-    detect_git(repo_path, auto)?;
 
     Ok(())
 }
 
+/// Marker file in `.helix` that makes a repository bare
+pub const BARE_MARKER: &str = "bare";
+
+/// Create a bare repository: `.helix` with HEAD, objects and refs, and no working tree,
+/// index or helix.toml. It only ever changes by being pushed to, e.g. as a `file://` remote.
+pub fn init_bare_repo(repo_path: &Path) -> Result<()> {
+    if repo_path.join(".helix").join("helix.idx").exists() {
+        bail!(
+            "{} already holds a repository with a working tree",
+            repo_path.display()
+        );
+    }
+    let helix_dir = repo_path.join(".helix");
+    fs::create_dir_all(&helix_dir).context("Failed to create .helix directory")?;
+    create_store_dirs(&helix_dir)?;
+    create_head_file(repo_path)?;
+    fs::write(helix_dir.join(BARE_MARKER), "").context("Failed to mark repository as bare")?;
+
+    println!(
+        "  {} Initialized empty bare Helix repository in {}",
+        style("✓").green(),
+        repo_path.display()
+    );
+    println!(
+        "  Add it as a remote with {} under {}",
+        style(format!("url = \"file://{}\"", repo_path.display())).cyan(),
+        style("[remotes.origin]").cyan()
+    );
+    Ok(())
+}
+
+/// Whether the repository at `repo_path` is bare (no working tree)
+pub fn is_bare_repo(repo_path: &Path) -> bool {
+    repo_path.join(".helix").join(BARE_MARKER).is_file()
+}
+
 pub fn detect_git(repo_path: &Path, auto: Option<String>) -> Result<()> {
     let git_path = repo_path.join(".git");
     let stdin = stdin();
@@ -138,6 +205,12 @@ pub fn detect_git_with_reader<R: BufRead>(
     mut reader: R,
     auto: Option<String>,
 ) -> Result<()> {
+    if SyncEngine::new(repo_path).import_interrupted() {
+        println!(
+            "An earlier import was interrupted; `helix init --resume` continues it. \
+             Importing now starts over."
+        );
+    }
     println!("Detected existing Git repo. Do you want to import your Git commits to Helix? (Y/N).");
 
     if auto.is_some() {
@@ -157,11 +230,29 @@ pub fn detect_git_with_reader<R: BufRead>(
 }
 
 fn import_from_git(repo_path: &Path) -> Result<()> {
-    let sync = SyncEngine::new(repo_path);
-    sync.import_from_git()
-        .context("Failed to import Git index")?;
+    run_interruptible_import(repo_path, SyncEngine::import_from_git)
+}
 
-    Ok(())
+/// `helix init --resume`: continue a Git import that was interrupted
+pub fn resume_import(repo_path: &Path) -> Result<()> {
+    run_interruptible_import(repo_path, SyncEngine::resume_import)
+}
+
+/// Run an import with Ctrl-C stopping it after the current batch of commits,
+/// rather than killing the process halfway through writing its state
+fn run_interruptible_import(
+    repo_path: &Path,
+    import: impl FnOnce(&SyncEngine) -> Result<()>,
+) -> Result<()> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let handler = signal_hook::flag::register(signal_hook::consts::SIGINT, cancel.clone())
+        .context("Failed to install Ctrl-C handler")?;
+
+    let sync = SyncEngine::new(repo_path).with_cancel(cancel);
+    let result = import(&sync);
+    signal_hook::low_level::unregister(handler);
+
+    result.context("Failed to import Git index")
 }
 
 pub fn create_directory_structure(repo_path: &Path) -> Result<()> {
@@ -180,6 +271,12 @@ pub fn create_directory_structure(repo_path: &Path) -> Result<()> {
         fs::write(&state_file, header).context("Failed to create .helix/state file")?;
     }
 
+    create_store_dirs(&helix_dir)
+}
+
+/// `objects/` and `refs/` with their subdirectories, the part of `.helix` every repo has
+fn create_store_dirs(helix_dir: &Path) -> Result<()> {
+    let new_store = !helix_dir.join("objects").exists();
     let objects_dirs = [
         helix_dir.join("objects"),
         helix_dir.join("objects/blobs"),
@@ -193,6 +290,12 @@ pub fn create_directory_structure(repo_path: &Path) -> Result<()> {
                 .with_context(|| format!("Failed to create {}", dir.display()))?;
         }
     }
+    // An existing store keeps its layout until `helix gc` moves it
+    if new_store {
+        let repo_path = helix_dir.parent().unwrap_or(helix_dir);
+        FsObjectStore::at_dir(helix_dir.join("objects"))
+            .set_layout(configured_layout(repo_path))?;
+    }
 
     let refs_dirs = [
         helix_dir.join("refs"),
@@ -244,8 +347,68 @@ fn create_head_file(repo_path: &Path) -> Result<()> {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HelixConfig {
     pub user: Option<UserConfig>,
+    pub core: Option<CoreSection>,
     pub remotes: Option<RemotesTable>,
+    #[serde(default)]
     pub ignore: IgnoreSection,
+    pub commit: Option<CommitSection>,
+    pub credential: Option<CredentialSection>,
+    pub ui: Option<UiSection>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CoreSection {
+    /// Line-ending policy: auto, lf, crlf or none
+    #[serde(default)]
+    pub line_endings: LineEndings,
+    /// How .helix/objects spreads object files: fanout (the default) or flat.
+    /// Existing repos move over on `helix gc`.
+    pub object_layout: Option<ObjectLayout>,
+}
+
+/// Commit message template, trailers and validation
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitSection {
+    /// File whose contents start the message in the editor, relative to the repo root
+    pub template: Option<PathBuf>,
+    /// Always add a Signed-off-by trailer, as if --signoff were given
+    pub signoff: bool,
+    /// Co-authored-by trailers added to every commit ("Name <email>")
+    pub co_authors: Vec<String>,
+    /// Require Conventional Commits subjects: `type(scope)!: description`
+    pub conventional: bool,
+    /// Regex the subject line must match
+    pub subject_pattern: Option<String>,
+    pub max_subject_length: Option<usize>,
+    /// Command run with the message file as its argument; a non-zero exit rejects the
+    /// commit, and edits it makes to the file are kept
+    pub msg_hook: Option<String>,
+}
+
+/// Where push and pull get a token for the remote; see `credential`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CredentialSection {
+    /// Program asked for the token first: a bare name runs `helix-credential-<name>`
+    pub helper: Option<String>,
+    /// Plaintext token, used when there's no helper or it has no answer
+    pub token: Option<String>,
+}
+
+/// Color and paging for diff and log output (see `output`), and the TUIs' theme, dates
+/// and keys (see `tui`)
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSection {
+    pub color: ColorMode,
+    /// Pager command, run by the shell; empty or "cat" to print directly
+    pub pager: Option<String>,
+    pub theme: Theme,
+    pub date_format: Option<DateFormat>,
+    pub keys: KeyBindings,
+    /// Mouse clicks, scrolling and dragging in the TUIs; on unless set to false
+    pub mouse: Option<bool>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -254,10 +417,127 @@ pub struct UserConfig {
     pub email: Option<String>,
 }
 
+/// The [remotes] table, one `[remotes.<name>]` table per remote. Older helix.toml files
+/// gave each remote bare URL keys instead (`origin_push = "..."`, and `origin_pull` when
+/// importing from Git found a separate fetch URL); `get` still reads those.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RemotesTable {
     #[serde(flatten)]
-    pub map: HashMap<String, String>,
+    pub map: HashMap<String, RemoteEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RemoteEntry {
+    Remote(RemoteConfig),
+    /// A bare URL, as older helix.toml files wrote them
+    Url(String),
+}
+
+/// How to reach one remote
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    pub url: String,
+    /// Where pushes go, when that isn't `url`
+    pub push_url: Option<String>,
+    /// The repository's name on the server, when it isn't this directory's name
+    pub repo: Option<String>,
+    /// Where this remote's token comes from, in place of [credential]
+    pub auth: Option<CredentialSection>,
+    /// Compress pushed objects and ask for compressed responses (default true);
+    /// --no-compress turns it off for one command
+    pub compression: Option<bool>,
+    /// Seconds to wait for a response before giving up
+    pub timeout_secs: Option<u64>,
+    pub tls: Option<TlsSection>,
+}
+
+/// Certificate checks for an HTTPS remote
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSection {
+    /// PEM file of extra CA certificates to trust, e.g. for a server with a private CA
+    pub ca_file: Option<PathBuf>,
+    /// Accept any certificate. Only for testing against a throwaway server.
+    pub insecure: bool,
+}
+
+impl RemoteConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The URL pushes go to
+    pub fn push_target(&self) -> &str {
+        self.push_url.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The repository to ask the server for: `repo`, else the name of the directory at
+    /// `repo_path`
+    pub fn repo_name(&self, repo_path: &Path) -> String {
+        self.repo.clone().unwrap_or_else(|| {
+            repo_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+impl RemotesTable {
+    /// The remote called `name`, from its table or from the older `<name>_push` and
+    /// `<name>_pull` keys
+    pub fn get(&self, name: &str) -> Option<RemoteConfig> {
+        match self.map.get(name) {
+            Some(RemoteEntry::Remote(remote)) => return Some(remote.clone()),
+            Some(RemoteEntry::Url(url)) => return Some(RemoteConfig::new(url)),
+            None => {}
+        }
+
+        let legacy = |suffix: &str| match self.map.get(&format!("{name}_{suffix}")) {
+            Some(RemoteEntry::Url(url)) => Some(url.clone()),
+            _ => None,
+        };
+        let push = legacy("push");
+        let url = legacy("pull").or_else(|| push.clone())?;
+        Some(RemoteConfig {
+            push_url: push.filter(|push| *push != url),
+            ..RemoteConfig::new(url)
+        })
+    }
+
+    /// Add or replace the remote called `name`, dropping any older keys for it
+    pub fn insert(&mut self, name: &str, remote: RemoteConfig) {
+        self.map.remove(&format!("{name}_push"));
+        self.map.remove(&format!("{name}_pull"));
+        self.map
+            .insert(name.to_string(), RemoteEntry::Remote(remote));
+    }
+
+    /// Every configured remote by name, sorted, whichever way it was written
+    pub fn remotes(&self) -> Vec<(String, RemoteConfig)> {
+        let mut names: Vec<&str> = self
+            .map
+            .iter()
+            .map(|(key, entry)| match entry {
+                RemoteEntry::Url(_) => key
+                    .strip_suffix("_push")
+                    .or_else(|| key.strip_suffix("_pull"))
+                    .unwrap_or(key),
+                RemoteEntry::Remote(_) => key,
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .filter_map(|name| Some((name.to_string(), self.get(name)?)))
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -275,9 +555,13 @@ fn create_repo_config(repo_path: &Path) -> Result<()> {
 
     let config = HelixConfig {
         user: None,
+        core: None,
         remotes: Some(RemotesTable {
             map: HashMap::new(),
         }),
+        commit: None,
+        credential: None,
+        ui: None,
         ignore: IgnoreSection {
             patterns: vec![
                 "target/".to_string(),
@@ -294,7 +578,9 @@ fn create_repo_config(repo_path: &Path) -> Result<()> {
 
     let final_output = format!(
         "# Helix repository configuration\n\
-         # Settings here override the global settings in ~/.helix.toml\n\n\
+         # Settings here override the global settings in ~/.helix.toml\n\
+         # The [user] identity can be overridden per command with --author, or with\n\
+         # HELIX_AUTHOR_NAME / HELIX_AUTHOR_EMAIL in the environment\n\n\
          {}",
         toml_string
     );
@@ -308,9 +594,9 @@ fn create_repo_config(repo_path: &Path) -> Result<()> {
 mod tests {
     use super::*;
     use crate::helix_index::{EntryFlags, Reader};
-    use std::{
-        collections::HashMap, os::unix::fs::PermissionsExt, path::PathBuf, process::Command,
-    };
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+    use std::{collections::HashMap, path::PathBuf, process::Command};
     use tempfile::TempDir;
 
     fn init_test_repo(path: &Path) -> Result<()> {
@@ -415,6 +701,88 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_remotes_read_tables_and_older_keys() -> Result<()> {
+        let text = r#"
+[remotes]
+origin_pull = "https://example.com/app"
+origin_push = "https://push.example.com/app"
+backup_push = "file:///mnt/backup/app"
+
+[remotes.mirror]
+url = "https://mirror.example.com/app"
+timeout_secs = 5
+compression = false
+auth = { token = "mirror-token" }
+tls = { ca_file = "certs/ca.pem" }
+"#;
+        let mut remotes = crate::repo_config::parse(text)?.remotes.unwrap();
+
+        let origin = remotes.get("origin").unwrap();
+        assert_eq!(origin.url, "https://example.com/app");
+        assert_eq!(origin.push_target(), "https://push.example.com/app");
+        let backup = remotes.get("backup").unwrap();
+        assert_eq!(backup, RemoteConfig::new("file:///mnt/backup/app"));
+        let mirror = remotes.get("mirror").unwrap();
+        assert_eq!(mirror.push_target(), "https://mirror.example.com/app");
+        assert_eq!(mirror.timeout_secs, Some(5));
+        assert_eq!(mirror.compression, Some(false));
+        assert_eq!(mirror.auth.unwrap().token.as_deref(), Some("mirror-token"));
+        assert_eq!(
+            mirror.tls.unwrap().ca_file,
+            Some(PathBuf::from("certs/ca.pem"))
+        );
+        assert!(remotes.get("upstream").is_none());
+
+        // Rewriting a remote replaces its older keys with a table
+        remotes.insert("origin", RemoteConfig::new("https://example.com/app"));
+        let names: Vec<String> = remotes
+            .remotes()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["backup", "mirror", "origin"]);
+        let written = toml::to_string_pretty(&remotes)?;
+        assert!(!written.contains("origin_pull"));
+        let reread: RemotesTable = toml::from_str(&written)?;
+        assert_eq!(
+            reread.get("origin"),
+            Some(RemoteConfig::new("https://example.com/app"))
+        );
+        assert_eq!(reread.get("mirror").unwrap().timeout_secs, Some(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_bare_has_no_work_tree() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+
+        init_bare_repo(repo_path)?;
+
+        assert!(is_bare_repo(repo_path));
+        assert!(repo_path.join(".helix/objects/commits").is_dir());
+        assert!(repo_path.join(".helix/refs/heads").is_dir());
+        assert_eq!(
+            fs::read_to_string(repo_path.join(".helix/HEAD"))?,
+            "ref: refs/heads/main\n"
+        );
+        assert!(!repo_path.join(".helix/helix.idx").exists());
+        assert!(!repo_path.join("helix.toml").exists());
+
+        let context = crate::sandbox_command::RepoContext::detect(repo_path)?;
+        assert!(context.bare);
+        assert!(context.require_work_tree().is_err());
+
+        // A repo with a working tree can't be turned bare
+        let normal = TempDir::new()?;
+        init_helix_repo(normal.path(), Some("n".to_string()))?;
+        assert!(init_bare_repo(normal.path()).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_init_idempotent() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -530,6 +898,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
     #[test]
     fn test_init_in_existing_git_repo_migrate_multi_file() -> Result<()> {
         let temp_dir = TempDir::new()?;
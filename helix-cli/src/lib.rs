@@ -1,20 +1,63 @@
 pub mod add_command;
+pub mod apply_command;
+pub mod attributes;
+pub mod author;
+pub mod binary;
+pub mod bisect_command;
+pub mod blame;
 pub mod branch_command;
 pub mod branch_tui;
+pub mod check_ignore_command;
 pub mod checkout;
+pub mod clone_command;
 pub mod commit_command;
+pub mod commit_message;
+pub mod credential;
+pub mod describe_command;
+pub mod diff_command;
+pub mod doctor_command;
+pub mod export_command;
+pub mod file_mode;
+pub mod format_patch_command;
 pub mod fsmonitor;
-pub mod handshake;
+pub mod fuzzy;
+pub mod gc_command;
+pub mod grep_command;
 pub mod helix_index;
 pub mod ignore;
 pub mod index;
+pub mod index_command;
 pub mod init_command;
+pub mod line_endings;
+pub mod lost_found_command;
+pub mod ls_files_command;
+pub mod ls_remote_command;
+pub mod mailmap;
 pub mod merge_command;
 pub mod merge_tui;
+pub mod notes_command;
+pub mod output;
+pub mod path_policy;
+pub mod plumbing_command;
+pub mod promisor;
 pub mod pull_command;
 pub mod push_command;
+pub mod remote_refs;
+pub mod repair_command;
+pub mod repo_config;
+pub mod restore;
+pub mod rev_map_command;
 pub mod sandbox_command;
 pub mod sandbox_tui;
+pub mod serve_command;
+pub mod shortlog_command;
+pub mod size_command;
+pub mod trace;
+pub mod transport;
+pub mod tui;
+pub mod unified_diff;
+pub mod verify_command;
+pub mod worktree_command;
 
 use std::result;
 
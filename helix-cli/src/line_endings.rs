@@ -0,0 +1,131 @@
+/*
+Line-ending normalization, the Helix counterpart of Git's core.autocrlf.
+
+A Windows checkout with CRLF files and a Linux checkout with LF files would
+otherwise hash the same text differently and show every file as MODIFIED.
+helix.toml picks a policy for the repository:
+
+  [core]
+  line_endings = "auto"
+
+  policy  stored (add/status/hash)  working tree (checkout)
+  none    as-is                     as-is
+  lf      CRLF -> LF                LF
+  crlf    CRLF -> LF                LF -> CRLF
+  auto    CRLF -> LF                CRLF on Windows, LF elsewhere
+
+Files that look binary (see `binary::is_binary`) are never converted.
+Anything hashing working tree content to compare with the index or a tree must
+go through `normalize`, and anything writing blobs to the working tree through
+`to_working_tree`.
+*/
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::path::Path;
+
+use crate::binary::is_binary;
+use crate::init_command::HelixConfig;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    Auto,
+    Lf,
+    Crlf,
+    #[default]
+    None,
+}
+
+impl LineEndings {
+    /// Load the policy from helix.toml, defaulting to no conversion
+    pub fn load(repo_path: &Path) -> Self {
+        std::fs::read_to_string(repo_path.join("helix.toml"))
+            .ok()
+            .and_then(|contents| toml::from_str::<HelixConfig>(&contents).ok())
+            .and_then(|cfg| cfg.core)
+            .map(|core| core.line_endings)
+            .unwrap_or_default()
+    }
+
+    /// Content as it is stored and hashed
+    pub fn normalize<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        if *self == Self::None || is_binary(content) || !content.contains(&b'\r') {
+            return Cow::Borrowed(content);
+        }
+
+        let mut out = Vec::with_capacity(content.len());
+        let mut bytes = content.iter().peekable();
+        while let Some(&byte) = bytes.next() {
+            if byte == b'\r' && bytes.peek() == Some(&&b'\n') {
+                continue;
+            }
+            out.push(byte);
+        }
+        Cow::Owned(out)
+    }
+
+    /// Content as it is written to the working tree
+    pub fn to_working_tree<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        let crlf = match self {
+            Self::Crlf => true,
+            Self::Auto => cfg!(windows),
+            Self::Lf | Self::None => false,
+        };
+        if !crlf || is_binary(content) || !content.contains(&b'\n') {
+            return Cow::Borrowed(content);
+        }
+
+        let mut out = Vec::with_capacity(content.len() + content.len() / 16);
+        let mut previous = None;
+        for &byte in content {
+            if byte == b'\n' && previous != Some(b'\r') {
+                out.push(b'\r');
+            }
+            out.push(byte);
+            previous = Some(byte);
+        }
+        Cow::Owned(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_normalize_and_checkout_conversion() {
+        let crlf = b"one\r\ntwo\r\n";
+        let lf = b"one\ntwo\n";
+
+        assert_eq!(LineEndings::None.normalize(crlf).as_ref(), crlf);
+        assert_eq!(LineEndings::Lf.normalize(crlf).as_ref(), lf);
+        assert_eq!(LineEndings::Auto.normalize(crlf).as_ref(), lf);
+        // A lone CR isn't a line ending
+        assert_eq!(LineEndings::Crlf.normalize(b"a\rb\r\n").as_ref(), b"a\rb\n");
+
+        assert_eq!(LineEndings::Crlf.to_working_tree(lf).as_ref(), crlf);
+        assert_eq!(LineEndings::Crlf.to_working_tree(crlf).as_ref(), crlf);
+        assert_eq!(LineEndings::Lf.to_working_tree(lf).as_ref(), lf);
+
+        // Binary content is left alone
+        let binary = b"\0\r\n\n";
+        assert_eq!(LineEndings::Crlf.normalize(binary).as_ref(), binary);
+        assert_eq!(LineEndings::Crlf.to_working_tree(binary).as_ref(), binary);
+    }
+
+    #[test]
+    fn test_load_from_helix_toml() -> Result<()> {
+        let temp = TempDir::new()?;
+        assert_eq!(LineEndings::load(temp.path()), LineEndings::None);
+
+        std::fs::write(
+            temp.path().join("helix.toml"),
+            "[core]\nline_endings = \"crlf\"\n\n[ignore]\npatterns = []\n",
+        )?;
+        assert_eq!(LineEndings::load(temp.path()), LineEndings::Crlf);
+
+        Ok(())
+    }
+}
@@ -7,4 +7,26 @@ pub enum Action {
     PageDown,
     GoToTop,
     GoToBottom,
+    /// Open the file pane, or a file's diff
+    Open,
+    /// Close the diff view or file pane
+    Back,
+}
+
+impl Action {
+    /// The action for a name in `tui::LOG_KEYS`; the others are handled by the event loop
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "quit" => Action::Quit,
+            "up" => Action::MoveUp,
+            "down" => Action::MoveDown,
+            "page_up" => Action::PageUp,
+            "page_down" => Action::PageDown,
+            "top" => Action::GoToTop,
+            "bottom" => Action::GoToBottom,
+            "open" => Action::Open,
+            "back" => Action::Back,
+            _ => return None,
+        })
+    }
 }
@@ -1,24 +1,46 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use helix_cli::branch_command::get_all_branches;
+use helix_cli::diff_command::{commit_diff, FileDiff};
+use helix_cli::fuzzy::fuzzy_match;
+use helix_cli::unified_diff::Whitespace;
 use helix_cli::{
     branch_command::get_current_branch,
-    helix_index::commit::{ChangedFile, Commit, CommitStore},
+    helix_index::commit::{read_head, ChangedFile, Commit, CommitStore},
+    helix_index::graph::{graph_rows, walk_commits, GraphRow},
+    mailmap::Mailmap,
     sandbox_command::{RepoContext, SandboxManifest},
+    tui::{self, LOG_KEYS},
 };
-use helix_protocol::hash::hex_to_hash;
+use helix_protocol::hash::{hash_to_hex, hex_to_hash};
+use helix_protocol::tag::peel_to_commit;
 use helix_protocol::{hash::Hash, storage::FsObjectStore};
-use ratatui::{backend::CrosstermBackend, Terminal};
-use std::path::Path;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Position, Rect},
+    Terminal,
+};
+use std::path::{Path, PathBuf};
 use std::{collections::HashMap, io};
 
 use super::actions::Action;
 use super::ui;
 
+/// Which pane the keys drive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Focus {
+    Commits,
+    Files,
+    Diff,
+}
+
 pub struct App {
     pub commits: Vec<Commit>,
     pub selected_index: usize,
@@ -42,13 +64,58 @@ pub struct App {
     pub pending_checkout_hash: Option<Hash>,
     pub changed_files_cache: HashMap<Hash, Vec<ChangedFile>>,
     pub commit_branches: HashMap<Hash, Vec<String>>,
+    /// Graph column glyphs, parallel to `commits`
+    pub graph: Vec<GraphRow>,
+    /// Where the history walk starts and stops
+    pub tips: Vec<Hash>,
+    pub stop_at: Option<Hash>,
+    pub repo_root: PathBuf,
+    pub focus: Focus,
+    /// Per-file diffs of the commit whose file pane is open
+    pub file_diffs: Vec<FileDiff>,
+    pub selected_file: usize,
+    /// Rendered patch of the selected file, shown in the diff view
+    pub diff_lines: Vec<String>,
+    pub diff_scroll: usize,
+    pub diff_height: usize,
+    /// How the diff view compares lines, cycled with `w`
+    pub whitespace: Whitespace,
+    pub message: Option<String>,
+    /// Authors are shown as .helixmailmap maps them
+    pub mailmap: Mailmap,
+    /// Where the last frame put things, for the mouse
+    pub layout: LogLayout,
+    /// The splitter between the timeline and the details is being dragged
+    pub dragging_split: bool,
 }
 
+/// Areas of the last frame and what was scrolled into them
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LogLayout {
+    /// Timeline and details together; the splitter is between them
+    pub main: Rect,
+    pub timeline: Rect,
+    /// Position in `visible_commits` of the first commit shown in the timeline
+    pub timeline_top: usize,
+    /// The file pane, or the diff when it's open in its place
+    pub details: Rect,
+    pub files: Rect,
+    pub files_offset: usize,
+}
+
+/// Lines each commit takes in the timeline
+pub const TIMELINE_ITEM_HEIGHT: u16 = 5;
+
+/// Narrowest share of the width either side of the splitter keeps
+const MIN_SPLIT: f32 = 0.15;
+
 impl App {
-    pub fn new(start_path: &Path) -> Result<Self> {
+    /// Open the log for the current branch, or for every branch and tag with `all`
+    pub fn new(start_path: &Path, all: bool) -> Result<Self> {
         let context = RepoContext::detect(start_path)?;
         let repo_path = &context.repo_root;
 
+        tui::init(repo_path);
         let store = FsObjectStore::new(repo_path);
         let loader = CommitStore::new(repo_path, store)?;
         let current_branch_name = get_current_branch(&start_path)?;
@@ -65,12 +132,22 @@ impl App {
         let base_commit_hash: Option<Hash> =
             base_commit_hex.as_deref().map(hex_to_hash).transpose()?;
 
-        // Load commits for current branch, stopping at base if sandbox
-        let commits = loader.load_commits_for_branch_until(
-            &current_branch_name,
-            50,
-            base_commit_hash.as_ref(),
-        )?;
+        let commit_branches = build_commit_branch_map(repo_path)?;
+
+        // Walk from the current branch (or every labeled commit), stopping at base if sandbox
+        let mut tips: Vec<Hash> = if all {
+            commit_branches.keys().copied().collect()
+        } else {
+            branch_tip(repo_path, &current_branch_name)
+                .or_else(|| read_head(repo_path).ok())
+                .into_iter()
+                .collect()
+        };
+        tips.sort();
+        let mailmap = Mailmap::load(repo_path);
+        let mut commits = walk_commits(&loader, &tips, 50, base_commit_hash.as_ref())?;
+        apply_mailmap(&mailmap, &mut commits);
+        let graph = graph_rows(&commits);
 
         let total_loaded = commits.len();
 
@@ -85,8 +162,6 @@ impl App {
             .map(|(branch, ahead, behind)| (Some(branch), ahead, behind))
             .unwrap_or((None, 0, 0));
 
-        let commit_branches = build_commit_branch_map(repo_path)?;
-
         Ok(Self {
             commits,
             selected_index: 0,
@@ -110,6 +185,21 @@ impl App {
             pending_checkout_hash: None,
             changed_files_cache: HashMap::new(),
             commit_branches,
+            graph,
+            tips,
+            stop_at: base_commit_hash,
+            repo_root: repo_path.clone(),
+            focus: Focus::Commits,
+            file_diffs: Vec::new(),
+            selected_file: 0,
+            diff_lines: Vec::new(),
+            diff_scroll: 0,
+            whitespace: Whitespace::Exact,
+            diff_height: 20,
+            message: None,
+            mailmap,
+            layout: LogLayout::default(),
+            dragging_split: false,
         })
     }
 
@@ -119,16 +209,12 @@ impl App {
             return;
         }
 
-        let query = self.search_query.to_lowercase();
+        let query = &self.search_query;
         self.filtered_indices = self
             .commits
             .iter()
             .enumerate()
-            .filter(|(_, commit)| {
-                commit.summary().to_lowercase().contains(&query)
-                    || commit.author.to_lowercase().contains(&query)
-                    || commit.message.to_lowercase().contains(&query)
-            })
+            .filter(|(_, commit)| commit_matches(query, commit))
             .map(|(idx, _)| idx)
             .collect();
 
@@ -186,6 +272,15 @@ impl App {
             return Ok(());
         }
 
+        match self.focus {
+            Focus::Files => return self.handle_files_action(action),
+            Focus::Diff => {
+                self.handle_diff_action(action);
+                return Ok(());
+            }
+            Focus::Commits => {}
+        }
+
         // Get the list we're navigating
         let visible = self.visible_commits();
         let visible_count = visible.len();
@@ -198,6 +293,8 @@ impl App {
             Action::Quit => {
                 self.should_quit = true;
             }
+            Action::Open => self.open_files(),
+            Action::Back => {}
             Action::MoveUp => {
                 // Find current position in visible list
                 if let Some(pos) = visible
@@ -294,6 +391,170 @@ impl App {
         Ok(())
     }
 
+    /// Open the file pane for the selected commit
+    fn open_files(&mut self) {
+        let Some(commit) = self.get_selected_commit() else {
+            return;
+        };
+        match commit_diff(&self.repo_root, &commit.commit_hash) {
+            Ok(diffs) if diffs.is_empty() => {
+                self.message = Some("No files changed in this commit".to_string());
+            }
+            Ok(diffs) => {
+                self.file_diffs = diffs;
+                self.selected_file = 0;
+                self.focus = Focus::Files;
+                self.message = None;
+            }
+            Err(e) => self.message = Some(format!("Failed to diff commit: {}", e)),
+        }
+    }
+
+    fn handle_files_action(&mut self, action: Action) -> Result<()> {
+        let last = self.file_diffs.len().saturating_sub(1);
+        match action {
+            Action::MoveUp => self.selected_file = self.selected_file.saturating_sub(1),
+            Action::MoveDown => self.selected_file = (self.selected_file + 1).min(last),
+            Action::PageUp => self.selected_file = self.selected_file.saturating_sub(10),
+            Action::PageDown => self.selected_file = (self.selected_file + 10).min(last),
+            Action::GoToTop => self.selected_file = 0,
+            Action::GoToBottom => self.selected_file = last,
+            Action::Open => {
+                if self.file_diffs.get(self.selected_file).is_some() {
+                    self.render_selected_diff();
+                    self.diff_scroll = 0;
+                    self.focus = Focus::Diff;
+                }
+            }
+            Action::Back => {
+                self.focus = Focus::Commits;
+                self.file_diffs.clear();
+            }
+            Action::Quit => self.should_quit = true,
+        }
+        Ok(())
+    }
+
+    fn render_selected_diff(&mut self) {
+        if let Some(file) = self.file_diffs.get(self.selected_file) {
+            self.diff_lines = file
+                .render(false, 3, self.whitespace)
+                .lines()
+                .map(String::from)
+                .collect();
+        }
+    }
+
+    /// Exact, then ignoring changes in whitespace, then ignoring all of it
+    fn cycle_whitespace(&mut self) {
+        let (whitespace, label) = match self.whitespace {
+            Whitespace::Exact => (Whitespace::IgnoreChange, "Ignoring whitespace changes"),
+            Whitespace::IgnoreChange => (Whitespace::IgnoreAll, "Ignoring all whitespace"),
+            Whitespace::IgnoreAll => (Whitespace::Exact, "Showing whitespace changes"),
+        };
+        self.whitespace = whitespace;
+        self.render_selected_diff();
+        self.diff_scroll = self
+            .diff_scroll
+            .min(self.diff_lines.len().saturating_sub(1));
+        self.message = Some(label.to_string());
+    }
+
+    fn handle_diff_action(&mut self, action: Action) {
+        let max_scroll = self
+            .diff_lines
+            .len()
+            .saturating_sub(self.diff_height.max(1));
+        let page = self.diff_height.max(1);
+        self.diff_scroll = match action {
+            Action::MoveUp => self.diff_scroll.saturating_sub(1),
+            Action::MoveDown => self.diff_scroll + 1,
+            Action::PageUp => self.diff_scroll.saturating_sub(page),
+            Action::PageDown => self.diff_scroll + page,
+            Action::GoToTop => 0,
+            Action::GoToBottom => max_scroll,
+            Action::Back => {
+                self.focus = Focus::Files;
+                0
+            }
+            Action::Open => self.diff_scroll,
+            Action::Quit => {
+                self.should_quit = true;
+                self.diff_scroll
+            }
+        }
+        .min(max_scroll);
+    }
+
+    /// Clicks select a commit or file, the wheel moves through the list under the
+    /// pointer or scrolls the diff, and dragging the splitter resizes the panes
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+        if self.branch_name_mode {
+            return Ok(());
+        }
+        let position = Position::new(mouse.column, mouse.row);
+        let layout = self.layout;
+        let splitter = layout.main.x + (layout.main.width as f32 * self.split_ratio) as u16;
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left)
+                if layout.main.contains(position) && mouse.column.abs_diff(splitter) <= 1 =>
+            {
+                self.dragging_split = true;
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_split => {
+                let offset = mouse.column.saturating_sub(layout.main.x) as f32;
+                self.split_ratio =
+                    (offset / layout.main.width.max(1) as f32).clamp(MIN_SPLIT, 1.0 - MIN_SPLIT);
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging_split = false,
+            MouseEventKind::Down(MouseButton::Left) if layout.timeline.contains(position) => {
+                let row = mouse.row.saturating_sub(layout.timeline.y + 1) / TIMELINE_ITEM_HEIGHT;
+                let clicked = self
+                    .visible_commits()
+                    .get(layout.timeline_top + row as usize)
+                    .map(|(idx, _)| *idx);
+                if let Some(idx) = clicked {
+                    // Picking another commit closes its files
+                    self.focus = Focus::Commits;
+                    self.file_diffs.clear();
+                    self.selected_index = idx;
+                    self.adjust_scroll();
+                }
+            }
+            MouseEventKind::Down(MouseButton::Left)
+                if self.focus == Focus::Files && layout.files.contains(position) =>
+            {
+                let row = mouse.row.saturating_sub(layout.files.y + 1) as usize;
+                let file = layout.files_offset + row;
+                if file < self.file_diffs.len() {
+                    self.selected_file = file;
+                }
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let action = if mouse.kind == MouseEventKind::ScrollDown {
+                    Action::MoveDown
+                } else {
+                    Action::MoveUp
+                };
+                let over_focus = match self.focus {
+                    Focus::Commits => layout.timeline.contains(position),
+                    Focus::Files => layout.files.contains(position),
+                    Focus::Diff => layout.details.contains(position),
+                };
+                // A few lines a notch in the diff, one row in the lists
+                let steps = if self.focus == Focus::Diff { 3 } else { 1 };
+                if over_focus {
+                    for _ in 0..steps {
+                        self.handle_action(action)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn adjust_scroll(&mut self) {
         // Ensure visible_height is at least 1
         let visible_height = self.visible_height.max(1);
@@ -316,10 +577,13 @@ impl App {
     fn load_more_commits(&mut self) -> Result<()> {
         if self.total_loaded < self.commits.len() + 50 {
             let new_limit = self.total_loaded + 50;
-            let new_commits = self.loader.load_commits(new_limit)?;
+            let mut new_commits =
+                walk_commits(&self.loader, &self.tips, new_limit, self.stop_at.as_ref())?;
+            apply_mailmap(&self.mailmap, &mut new_commits);
 
             if new_commits.len() > self.commits.len() {
                 self.commits = new_commits;
+                self.graph = graph_rows(&self.commits);
                 self.total_loaded = self.commits.len();
             }
         }
@@ -330,7 +594,10 @@ impl App {
     pub fn run(&mut self) -> Result<()> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(stdout, EnterAlternateScreen)?;
+        if tui::mouse_enabled() {
+            execute!(stdout, EnableMouseCapture)?;
+        }
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
@@ -357,7 +624,11 @@ impl App {
             })?;
 
             if event::poll(std::time::Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
+                let event = event::read()?;
+                if let Event::Mouse(mouse) = event {
+                    self.handle_mouse(mouse)?;
+                }
+                if let Event::Key(key) = event {
                     // Handle branch name input mode
                     if self.branch_name_mode {
                         match key.code {
@@ -397,9 +668,11 @@ impl App {
                         continue;
                     }
 
-                    // Handle search mode input
+                    // Handle search mode input; the arrows move through the matches
                     if self.search_mode {
                         match key.code {
+                            KeyCode::Up => self.handle_action(Action::MoveUp)?,
+                            KeyCode::Down => self.handle_action(Action::MoveDown)?,
                             KeyCode::Esc => {
                                 self.search_mode = false;
                                 self.search_query.clear();
@@ -421,12 +694,21 @@ impl App {
                         continue;
                     }
 
-                    let action = match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::Quit)
+                    self.message = None;
+                    if key.code == KeyCode::Esc {
+                        // Esc backs out of a pane, then drops a kept filter, before it quits
+                        if self.focus != Focus::Commits {
+                            self.handle_action(Action::Back)?;
+                            continue;
+                        }
+                        if !self.search_query.is_empty() {
+                            self.search_query.clear();
+                            self.filtered_indices.clear();
+                            continue;
                         }
-                        KeyCode::Char('c') => {
+                    }
+                    let action = match tui::keys().action(&key, LOG_KEYS) {
+                        Some("checkout") => {
                             if let Some(commit) = self.get_selected_commit() {
                                 let hash = commit.commit_hash;
                                 let short_hash = commit.get_short_hash();
@@ -436,34 +718,22 @@ impl App {
                             }
                             continue;
                         }
-                        KeyCode::Char('s') => {
+                        Some("search") if self.focus == Focus::Commits => {
                             self.search_mode = true;
-                            continue;
-                        }
-                        KeyCode::Char('/') => {
                             self.search_query.clear();
                             self.filtered_indices.clear();
                             continue;
                         }
-                        KeyCode::Char('v') => {
-                            self.vim_mode = true;
+                        Some("whitespace") if self.focus == Focus::Diff => {
+                            self.cycle_whitespace();
                             continue;
                         }
-                        KeyCode::Char('j') | KeyCode::Down => Some(Action::MoveDown),
-                        KeyCode::Char('k') | KeyCode::Up => Some(Action::MoveUp),
-                        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::PageDown)
-                        }
-                        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::PageUp)
+                        Some(name) => Action::from_name(name),
+                        None if key.code == KeyCode::Char('v') => {
+                            self.vim_mode = true;
+                            continue;
                         }
-                        KeyCode::Char('g') => Some(Action::GoToTop),
-                        KeyCode::Char('G') => Some(Action::GoToBottom),
-                        KeyCode::PageDown => Some(Action::PageDown),
-                        KeyCode::PageUp => Some(Action::PageUp),
-                        KeyCode::Home => Some(Action::GoToTop),
-                        KeyCode::End => Some(Action::GoToBottom),
-                        _ => None,
+                        None => None,
                     };
                     if let Some(action) = action {
                         self.handle_action(action)?;
@@ -480,26 +750,75 @@ impl App {
     }
 }
 
+/// Whether a search matches a commit: fuzzily on its message or author, or as a
+/// prefix of its hash
+pub fn commit_matches(query: &str, commit: &Commit) -> bool {
+    fuzzy_match(query, &commit.message).is_some()
+        || fuzzy_match(query, &commit.author).is_some()
+        || hash_matches(query, commit).is_some()
+}
+
+/// How many leading hash characters a search matches, if it's a prefix of the hash
+pub fn hash_matches(query: &str, commit: &Commit) -> Option<usize> {
+    let query = query.trim().to_lowercase();
+    (!query.is_empty() && hash_to_hex(&commit.commit_hash).starts_with(&query))
+        .then_some(query.len())
+}
+
+/// Show each commit's author in canonical form
+fn apply_mailmap(mailmap: &Mailmap, commits: &mut [Commit]) {
+    if mailmap.is_empty() {
+        return;
+    }
+    for commit in commits {
+        commit.author = mailmap.canonical(&commit.author);
+    }
+}
+
+/// Branch and tag labels for each commit they point at
 fn build_commit_branch_map(repo_path: &Path) -> Result<HashMap<Hash, Vec<String>>> {
     let mut map: HashMap<Hash, Vec<String>> = HashMap::new();
 
     let branches = get_all_branches(repo_path)?;
 
     for branch_name in branches {
-        // Determine ref path based on branch type
-        let ref_path = if branch_name.starts_with("sandboxes/") {
-            let sandbox_name = branch_name.strip_prefix("sandboxes/").unwrap();
-            repo_path.join(".helix/refs/sandboxes").join(sandbox_name)
-        } else {
-            repo_path.join(".helix/refs/heads").join(&branch_name)
-        };
+        if let Some(hash) = branch_tip(repo_path, &branch_name) {
+            map.entry(hash).or_default().push(branch_name);
+        }
+    }
 
-        if let Ok(hash_hex) = std::fs::read_to_string(&ref_path) {
-            if let Ok(hash) = hex_to_hash(hash_hex.trim()) {
-                map.entry(hash).or_insert_with(Vec::new).push(branch_name);
-            }
+    let tags_dir = repo_path.join(".helix/refs/tags");
+    let store = FsObjectStore::new(repo_path);
+    for entry in walkdir::WalkDir::new(&tags_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        let Ok(name) = entry.path().strip_prefix(&tags_dir) else {
+            continue;
+        };
+        let target = std::fs::read_to_string(entry.path())
+            .ok()
+            .and_then(|hex| hex_to_hash(hex.trim()).ok())
+            .and_then(|hash| peel_to_commit(&store, &hash).ok());
+        if let Some(hash) = target {
+            map.entry(hash)
+                .or_default()
+                .push(format!("tag: {}", name.display()));
         }
     }
 
     Ok(map)
 }
+
+fn branch_tip(repo_path: &Path, branch_name: &str) -> Option<Hash> {
+    // Determine ref path based on branch type
+    let ref_path = match branch_name.strip_prefix("sandboxes/") {
+        Some(sandbox_name) => repo_path.join(".helix/refs/sandboxes").join(sandbox_name),
+        None => repo_path.join(".helix/refs/heads").join(branch_name),
+    };
+
+    let hash_hex = std::fs::read_to_string(&ref_path).ok()?;
+    hex_to_hash(hash_hex.trim()).ok()
+}
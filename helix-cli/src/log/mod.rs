@@ -3,15 +3,68 @@ pub mod app;
 pub mod ui;
 
 use anyhow::Result;
+use console::style;
+use helix_cli::helix_index::commit::CommitStore;
+use helix_cli::mailmap::Mailmap;
+use helix_protocol::storage::FsObjectStore;
+use std::io::Write;
 use std::path::Path;
 
-pub fn run(repo_path: Option<&Path>) -> Result<()> {
+pub fn run(repo_path: Option<&Path>, all: bool) -> Result<()> {
     let repo_path = repo_path
         .map(|p| p.to_path_buf())
         .unwrap_or_else(|| std::env::current_dir().expect("Failed to get current directory"));
 
-    let mut app = app::App::new(&repo_path)?;
+    let mut app = app::App::new(&repo_path, all)?;
     app.run()?;
 
     Ok(())
 }
+
+/// `helix log --oneline`: the current branch's first-parent history, one commit per line
+pub fn print_oneline(out: &mut impl Write, repo_path: &Path) -> Result<()> {
+    let store = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+    for commit in store.load_commits(usize::MAX)? {
+        writeln!(
+            out,
+            "{} {}",
+            style(commit.get_short_hash()).yellow(),
+            commit.summary()
+        )?;
+    }
+    Ok(())
+}
+
+/// `helix log -- <file>`: print the commits that changed one file
+pub fn print_file_history(
+    out: &mut impl Write,
+    repo_path: &Path,
+    file: &Path,
+    follow: bool,
+) -> Result<()> {
+    let store = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+    let history = store.file_history(file, follow)?;
+
+    let mailmap = Mailmap::load(repo_path);
+    let mut shown_path = file.to_path_buf();
+    for (commit, path) in &history {
+        let mut commit = commit.clone();
+        commit.author = mailmap.canonical(&commit.author);
+        if path != &shown_path {
+            writeln!(
+                out,
+                "renamed: {} -> {}\n",
+                path.display(),
+                shown_path.display()
+            )?;
+            shown_path = path.clone();
+        }
+        writeln!(out, "{}\n", commit.format(&commit.commit_hash))?;
+    }
+
+    if history.is_empty() {
+        writeln!(out, "No commits touch {}", file.display())?;
+    }
+
+    Ok(())
+}
@@ -1,16 +1,19 @@
 // ui command for log.rs
 
+use helix_cli::fuzzy::{fuzzy_match, highlight};
 use helix_cli::helix_index::commit::{format_timestamp, ChangeType, ChangedFile, Commit};
+use helix_cli::helix_index::graph::GraphRow;
+use helix_cli::tui::{format_date, palette};
 use helix_protocol::hash::hash_to_hex;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
 
-use super::app::App;
+use super::app::{hash_matches, App, Focus};
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -55,25 +58,25 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         Span::styled(
             repo_text,
             Style::default()
-                .fg(Color::Magenta)
+                .fg(palette().special)
                 .add_modifier(Modifier::BOLD),
         ),
-        Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
+        Span::styled(" │ ", Style::default().fg(palette().muted)),
         Span::styled(
             branch_text,
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
-        Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
-        Span::styled(commit_count, Style::default().fg(Color::White)),
-        Span::styled(" │ ", Style::default().fg(Color::DarkGray)),
-        Span::styled(last_commit_text, Style::default().fg(Color::DarkGray)),
+        Span::styled(" │ ", Style::default().fg(palette().muted)),
+        Span::styled(commit_count, Style::default().fg(palette().text)),
+        Span::styled(" │ ", Style::default().fg(palette().muted)),
+        Span::styled(last_commit_text, Style::default().fg(palette().muted)),
     ]))
     .block(
         Block::default().borders(Borders::ALL).title_style(
             Style::default()
-                .fg(Color::Blue)
+                .fg(palette().title)
                 .add_modifier(Modifier::BOLD),
         ),
     );
@@ -94,11 +97,15 @@ fn draw_main_content(f: &mut Frame, area: Rect, app: &mut App) {
         ])
         .split(area);
 
+    app.layout.main = area;
+    app.layout.timeline = chunks[0];
+    app.layout.details = chunks[1];
+    app.layout.files = Rect::default();
     draw_timeline(f, chunks[0], app);
     draw_details(f, chunks[1], app);
 }
 
-fn draw_timeline(f: &mut Frame, area: Rect, app: &App) {
+fn draw_timeline(f: &mut Frame, area: Rect, app: &mut App) {
     let inner_height = area.height.saturating_sub(2) as usize;
 
     let visible_commits = app.visible_commits();
@@ -109,7 +116,7 @@ fn draw_timeline(f: &mut Frame, area: Rect, app: &App) {
             Block::default()
                 .borders(Borders::ALL)
                 .title(" Commits ")
-                .title_style(Style::default().fg(Color::Blue)),
+                .title_style(Style::default().fg(palette().title)),
         );
         f.render_widget(list, area);
         return;
@@ -148,12 +155,29 @@ fn draw_timeline(f: &mut Frame, area: Rect, app: &App) {
     let visible_start = scroll_offset;
     let visible_end = (visible_start + inner_height).min(visible_commits.len());
 
-    let items: Vec<ListItem> = visible_commits[visible_start..visible_end]
+    // The graph only makes sense over the unfiltered history
+    let shown = &visible_commits[visible_start..visible_end];
+    let graph_for = |idx: usize| {
+        if app.filtered_indices.is_empty() {
+            app.graph.get(idx)
+        } else {
+            None
+        }
+    };
+    let graph_width = shown
+        .iter()
+        .filter_map(|(idx, _)| graph_for(*idx))
+        .map(|row| row.node.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let items: Vec<ListItem> = shown
         .iter()
         .map(|(actual_idx, commit)| {
             let is_selected = *actual_idx == app.selected_index;
             let branches = app.commit_branches.get(&commit.commit_hash);
-            create_timeline_item(commit, is_selected, branches)
+            let graph = graph_for(*actual_idx).map(|row| graph_column(row, graph_width));
+            create_timeline_item(commit, is_selected, branches, graph, &app.search_query)
         })
         .collect();
 
@@ -161,17 +185,40 @@ fn draw_timeline(f: &mut Frame, area: Rect, app: &App) {
         Block::default()
             .borders(Borders::ALL)
             .title(" Timeline ")
-            .title_style(Style::default().fg(Color::Blue)),
+            .title_style(Style::default().fg(palette().title)),
     );
 
     f.render_widget(list, area);
+    app.layout.timeline_top = visible_start;
+}
+
+/// The graph glyphs for each of a timeline item's five lines, padded to `width`
+fn graph_column(row: &GraphRow, width: usize) -> [String; 5] {
+    let pad = |s: &str| {
+        let mut s: String = s.chars().take(width).collect();
+        let len = s.chars().count();
+        s.extend(std::iter::repeat_n(' ', width - len + 1));
+        s
+    };
+    [
+        pad(&row.node),
+        pad(&row.edges),
+        pad(&row.continuation),
+        pad(&row.continuation),
+        pad(&row.continuation),
+    ]
 }
 
 fn create_timeline_item(
     commit: &Commit,
     is_selected: bool,
     branches: Option<&Vec<String>>,
+    graph: Option<[String; 5]>,
+    query: &str,
 ) -> ListItem<'static> {
+    let matched = palette().search_match();
+    let positions = |text: &str| fuzzy_match(query, text).unwrap_or_default();
+
     let current_user_email = std::env::var("USER").unwrap_or_default();
     let is_current_user = commit.author.contains(&current_user_email)
         || commit
@@ -179,12 +226,12 @@ fn create_timeline_item(
             .to_lowercase()
             .contains(&current_user_email.to_lowercase());
 
-    let time_str = format_timestamp(commit.commit_time);
+    let time_str = format_date(commit.commit_time, format_timestamp);
     let author_indicator = if is_current_user { "●" } else { "○" };
     let author_color = if is_current_user {
-        Color::Cyan
+        palette().accent
     } else {
-        Color::Gray
+        palette().subtle
     };
 
     let line1 = Line::from(vec![
@@ -196,16 +243,29 @@ fn create_timeline_item(
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
-        Span::styled(time_str, Style::default().fg(Color::White)),
+        Span::styled(time_str, Style::default().fg(palette().text)),
     ]);
 
-    // Line 2: author and hash only
-    let line2 = Line::from(vec![
-        Span::raw("   "),
-        Span::styled(commit.author.clone(), Style::default().fg(author_color)),
-        Span::raw(" · "),
-        Span::styled(commit.get_short_hash(), Style::default().fg(Color::Green)),
-    ]);
+    // Line 2: author and hash only, with what the search matched highlighted
+    let short_hash = commit.get_short_hash();
+    let hash_matched = hash_matches(query, commit)
+        .unwrap_or(0)
+        .min(short_hash.len());
+    let mut line2_spans = vec![Span::raw("   ")];
+    line2_spans.extend(highlight(
+        &commit.author,
+        &positions(&commit.author),
+        Style::default().fg(author_color),
+        matched,
+    ));
+    line2_spans.push(Span::raw(" · "));
+    line2_spans.extend(highlight(
+        &short_hash,
+        &(0..hash_matched).collect::<Vec<_>>(),
+        Style::default().fg(palette().added),
+        matched,
+    ));
+    let line2 = Line::from(line2_spans);
 
     // Line 3: branch tags (if any)
     let line3 = if let Some(branch_list) = branches {
@@ -217,9 +277,11 @@ fn create_timeline_item(
             }
 
             let (tag_color, prefix) = if branch.starts_with("sandboxes/") {
-                (Color::Magenta, "⎇ ")
+                (palette().special, "⎇ ")
+            } else if branch.starts_with("tag: ") {
+                (palette().highlight, "")
             } else {
-                (Color::Cyan, "")
+                (palette().accent, "")
             };
 
             spans.push(Span::styled(
@@ -244,23 +306,38 @@ fn create_timeline_item(
 
     let summary_style = if is_selected {
         Style::default()
-            .fg(Color::Yellow)
+            .fg(palette().highlight)
             .add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(palette().text)
     };
 
-    let line4 = Line::from(vec![
-        Span::raw("   "),
-        Span::styled(summary_display, summary_style),
-    ]);
+    let mut line4_spans = vec![Span::raw("   ")];
+    line4_spans.extend(highlight(
+        &summary_display,
+        &positions(&summary_display),
+        summary_style,
+        matched,
+    ));
+    let line4 = Line::from(line4_spans);
 
     let line5 = Line::from(vec![Span::raw("")]);
 
-    let lines = vec![line1, line2, line3, line4, line5];
+    let mut lines = vec![line1, line2, line3, line4, line5];
+    if let Some(graph) = graph {
+        for (line, glyphs) in lines.iter_mut().zip(graph) {
+            line.spans.insert(
+                0,
+                Span::styled(
+                    format!(" {}", glyphs),
+                    Style::default().fg(palette().special),
+                ),
+            );
+        }
+    }
 
     let style = if is_selected {
-        Style::default().bg(Color::DarkGray)
+        Style::default().bg(palette().selection)
     } else {
         Style::default()
     };
@@ -269,6 +346,23 @@ fn create_timeline_item(
 }
 
 fn draw_details(f: &mut Frame, area: Rect, app: &mut App) {
+    match app.focus {
+        Focus::Commits => {}
+        Focus::Files => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .split(area);
+            draw_commit_summary(f, chunks[0], app);
+            draw_files(f, chunks[1], app);
+            return;
+        }
+        Focus::Diff => {
+            draw_diff(f, area, app);
+            return;
+        }
+    }
+
     if let Some(commit) = app.get_selected_commit().cloned() {
         let changed_files = app.get_selected_changed_files();
         let branches = app.commit_branches.get(&commit.commit_hash).cloned();
@@ -280,7 +374,7 @@ fn draw_details(f: &mut Frame, area: Rect, app: &mut App) {
                 Block::default()
                     .borders(Borders::ALL)
                     .title(" Details ")
-                    .title_style(Style::default().fg(Color::Blue)),
+                    .title_style(Style::default().fg(palette().title)),
             )
             .wrap(Wrap { trim: false });
 
@@ -288,12 +382,132 @@ fn draw_details(f: &mut Frame, area: Rect, app: &mut App) {
     } else {
         let empty = Paragraph::new("No commit selected")
             .block(Block::default().borders(Borders::ALL).title(" Details "))
-            .style(Style::default().fg(Color::DarkGray));
+            .style(Style::default().fg(palette().muted));
 
         f.render_widget(empty, area);
     }
 }
 
+/// Commit details without the file list, above the file pane
+fn draw_commit_summary(f: &mut Frame, area: Rect, app: &App) {
+    let Some(commit) = app.get_selected_commit() else {
+        return;
+    };
+    let branches = app.commit_branches.get(&commit.commit_hash);
+    let paragraph = Paragraph::new(format_commit_details(commit, None, branches))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Details ")
+                .title_style(Style::default().fg(palette().title)),
+        )
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(paragraph, area);
+}
+
+fn draw_files(f: &mut Frame, area: Rect, app: &mut App) {
+    let items: Vec<ListItem> = app
+        .file_diffs
+        .iter()
+        .map(|diff| {
+            let (symbol, color) = match (&diff.old_path, &diff.new_path) {
+                (None, _) => ("+", palette().added),
+                (_, None) => ("-", palette().removed),
+                _ if diff.similarity.is_some() => ("→", palette().accent),
+                _ => ("~", palette().highlight),
+            };
+            let path = match (&diff.old_path, &diff.new_path) {
+                (Some(old), Some(new)) if old != new => {
+                    format!("{} -> {}", old.display(), new.display())
+                }
+                (_, Some(path)) | (Some(path), None) => path.display().to_string(),
+                (None, None) => String::new(),
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(" "),
+                Span::styled(
+                    symbol,
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(" "),
+                Span::raw(path),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Files ({}) ", app.file_diffs.len()))
+                .title_style(Style::default().fg(palette().title))
+                .border_style(Style::default().fg(palette().accent)),
+        )
+        .highlight_style(
+            Style::default()
+                .bg(palette().selection)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    let mut state = ListState::default();
+    state.select(Some(app.selected_file));
+    f.render_stateful_widget(list, area, &mut state);
+    app.layout.files = area;
+    app.layout.files_offset = state.offset();
+}
+
+fn draw_diff(f: &mut Frame, area: Rect, app: &mut App) {
+    app.diff_height = area.height.saturating_sub(2) as usize;
+
+    let lines: Vec<Line> = app
+        .diff_lines
+        .iter()
+        .skip(app.diff_scroll)
+        .take(app.diff_height)
+        .map(|line| {
+            let style = if line.starts_with("diff --git")
+                || line.starts_with("--- ")
+                || line.starts_with("+++ ")
+            {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else if line.starts_with("@@") {
+                Style::default().fg(palette().accent)
+            } else if line.starts_with('+') {
+                Style::default().fg(palette().added)
+            } else if line.starts_with('-') {
+                Style::default().fg(palette().removed)
+            } else {
+                Style::default()
+            };
+            Line::from(Span::styled(line.as_str(), style))
+        })
+        .collect();
+
+    let title = app
+        .file_diffs
+        .get(app.selected_file)
+        .and_then(|d| d.new_path.as_ref().or(d.old_path.as_ref()))
+        .map(|p| format!(" {} ", p.display()))
+        .unwrap_or_default();
+    let position = format!(
+        " {}/{} ",
+        (app.diff_scroll + 1).min(app.diff_lines.len()),
+        app.diff_lines.len()
+    );
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_style(Style::default().fg(palette().title))
+            .title_bottom(Line::from(position).right_aligned())
+            .border_style(Style::default().fg(palette().accent)),
+    );
+
+    f.render_widget(paragraph, area);
+}
+
 fn format_commit_details(
     commit: &Commit,
     changed_files: Option<&[ChangedFile]>,
@@ -307,7 +521,7 @@ fn format_commit_details(
         Span::styled(
             commit.summary().to_string(),
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         ),
     ]));
@@ -320,7 +534,7 @@ fn format_commit_details(
                 Span::styled(
                     "Branches:",
                     Style::default()
-                        .fg(Color::Cyan)
+                        .fg(palette().accent)
                         .add_modifier(Modifier::BOLD),
                 ),
                 Span::raw(" "),
@@ -328,9 +542,9 @@ fn format_commit_details(
 
             for branch in branch_list {
                 let (color, icon) = if branch.starts_with("sandboxes/") {
-                    (Color::Magenta, "⎇")
+                    (palette().special, "⎇")
                 } else {
-                    (Color::Cyan, "●")
+                    (palette().accent, "●")
                 };
 
                 lines.push(Line::from(vec![
@@ -355,7 +569,7 @@ fn format_commit_details(
         for line in message_body.lines() {
             lines.push(Line::from(vec![
                 Span::raw(" "),
-                Span::styled(line.to_string(), Style::default().fg(Color::White)),
+                Span::styled(line.to_string(), Style::default().fg(palette().text)),
             ]));
         }
         lines.push(Line::from(""));
@@ -367,15 +581,18 @@ fn format_commit_details(
         Span::styled(
             "Commit:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw("  "),
-        Span::styled(commit.get_short_hash(), Style::default().fg(Color::Green)),
+        Span::styled(
+            commit.get_short_hash(),
+            Style::default().fg(palette().added),
+        ),
         Span::raw(" ("),
         Span::styled(
             hash_to_hex(&commit.commit_hash),
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(palette().muted),
         ),
         Span::raw(")"),
     ]));
@@ -386,11 +603,11 @@ fn format_commit_details(
         Span::styled(
             "Author:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" "),
-        Span::styled(commit.author.clone(), Style::default().fg(Color::White)),
+        Span::styled(commit.author.clone(), Style::default().fg(palette().text)),
     ]));
 
     // Date
@@ -399,19 +616,36 @@ fn format_commit_details(
         Span::styled(
             "Date:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw("   "),
         Span::styled(
-            format_timestamp(commit.commit_time),
-            Style::default().fg(Color::White),
+            format_date(commit.commit_time, format_timestamp),
+            Style::default().fg(palette().text),
         ),
         Span::raw(" ("),
-        Span::styled(commit.relative_time(), Style::default().fg(Color::DarkGray)),
+        Span::styled(commit.relative_time(), Style::default().fg(palette().muted)),
         Span::raw(")"),
     ]));
 
+    // Metadata headers
+    for (key, value) in &commit.headers {
+        lines.push(Line::from(vec![
+            Span::raw(" "),
+            Span::styled(
+                "Meta:",
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("   "),
+            Span::styled(key.clone(), Style::default().fg(palette().highlight)),
+            Span::raw("="),
+            Span::styled(value.clone(), Style::default().fg(palette().text)),
+        ]));
+    }
+
     lines.push(Line::from(""));
 
     // Tree hash
@@ -420,13 +654,13 @@ fn format_commit_details(
         Span::styled(
             "Tree:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw("   "),
         Span::styled(
             hash_to_hex(&commit.tree_hash)[..8].to_string(),
-            Style::default().fg(Color::Magenta),
+            Style::default().fg(palette().special),
         ),
     ]));
 
@@ -437,7 +671,7 @@ fn format_commit_details(
             Span::styled(
                 "Parents:",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
         ]));
@@ -447,7 +681,7 @@ fn format_commit_details(
                 Span::raw("   "),
                 Span::styled(
                     hash_to_hex(parent)[..8].to_string(),
-                    Style::default().fg(Color::Blue),
+                    Style::default().fg(palette().title),
                 ),
             ]));
         }
@@ -461,7 +695,7 @@ fn format_commit_details(
             Span::styled(
                 "⚠ Merge commit",
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(palette().highlight)
                     .add_modifier(Modifier::BOLD),
             ),
         ]));
@@ -481,26 +715,41 @@ fn format_commit_details(
             .iter()
             .filter(|f| f.change_type == ChangeType::Deleted)
             .count();
+        let renamed = files
+            .iter()
+            .filter(|f| f.change_type == ChangeType::Renamed)
+            .count();
 
         lines.push(Line::from(vec![
             Span::raw(" "),
             Span::styled(
                 "Changes:",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" "),
             Span::styled(
                 format!("{} files", files.len()),
-                Style::default().fg(Color::White),
+                Style::default().fg(palette().text),
             ),
             Span::raw(" ("),
-            Span::styled(format!("+{}", added), Style::default().fg(Color::Green)),
+            Span::styled(format!("+{}", added), Style::default().fg(palette().added)),
             Span::raw(" "),
-            Span::styled(format!("~{}", modified), Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("~{}", modified),
+                Style::default().fg(palette().highlight),
+            ),
             Span::raw(" "),
-            Span::styled(format!("-{}", deleted), Style::default().fg(Color::Red)),
+            Span::styled(
+                format!("-{}", deleted),
+                Style::default().fg(palette().removed),
+            ),
+            Span::raw(" "),
+            Span::styled(
+                format!("→{}", renamed),
+                Style::default().fg(palette().accent),
+            ),
             Span::raw(")"),
         ]));
 
@@ -509,9 +758,15 @@ fn format_commit_details(
         for file in files.iter().take(20) {
             // Limit to 20 files
             let (symbol, color) = match file.change_type {
-                ChangeType::Added => ("+", Color::Green),
-                ChangeType::Modified => ("~", Color::Yellow),
-                ChangeType::Deleted => ("-", Color::Red),
+                ChangeType::Added => ("+", palette().added),
+                ChangeType::Modified => ("~", palette().highlight),
+                ChangeType::Deleted => ("-", palette().removed),
+                ChangeType::Renamed => ("→", palette().accent),
+            };
+
+            let path = match &file.old_path {
+                Some(old_path) => format!("{} -> {}", old_path.display(), file.path.display()),
+                None => file.path.display().to_string(),
             };
 
             lines.push(Line::from(vec![
@@ -521,10 +776,7 @@ fn format_commit_details(
                     Style::default().fg(color).add_modifier(Modifier::BOLD),
                 ),
                 Span::raw(" "),
-                Span::styled(
-                    file.path.display().to_string(),
-                    Style::default().fg(Color::White),
-                ),
+                Span::styled(path, Style::default().fg(palette().text)),
             ]));
         }
 
@@ -533,7 +785,7 @@ fn format_commit_details(
                 Span::raw("   "),
                 Span::styled(
                     format!("... and {} more files", files.len() - 20),
-                    Style::default().fg(Color::DarkGray),
+                    Style::default().fg(palette().muted),
                 ),
             ]));
         }
@@ -547,30 +799,73 @@ fn format_commit_details(
 fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
     let help_text = if app.branch_name_mode {
         Line::from(vec![
-            Span::styled(" Branch name: ", Style::default().fg(Color::Cyan)),
-            Span::styled(&app.branch_name_input, Style::default().fg(Color::Yellow)),
-            Span::styled("_", Style::default().fg(Color::Yellow)),
+            Span::styled(" Branch name: ", Style::default().fg(palette().accent)),
+            Span::styled(
+                &app.branch_name_input,
+                Style::default().fg(palette().highlight),
+            ),
+            Span::styled("_", Style::default().fg(palette().highlight)),
             Span::raw("  "),
-            Span::styled("Enter", Style::default().fg(Color::Green)),
+            Span::styled("Enter", Style::default().fg(palette().added)),
             Span::raw(" to checkout  "),
-            Span::styled("Esc", Style::default().fg(Color::DarkGray)),
+            Span::styled("Esc", Style::default().fg(palette().muted)),
             Span::raw(" to cancel"),
         ])
     } else if app.search_mode {
         Line::from(vec![
-            Span::styled(" Search: ", Style::default().fg(Color::Cyan)),
-            Span::styled(&app.search_query, Style::default().fg(Color::Yellow)),
-            Span::styled("_", Style::default().fg(Color::Yellow)),
-            Span::raw("  "),
-            Span::styled("Esc", Style::default().fg(Color::DarkGray)),
+            Span::styled(" Search: ", Style::default().fg(palette().accent)),
+            Span::styled(&app.search_query, Style::default().fg(palette().highlight)),
+            Span::styled("_", Style::default().fg(palette().highlight)),
+            Span::styled(
+                format!(
+                    "  {} of {}  ",
+                    app.visible_commits().len(),
+                    app.commits.len()
+                ),
+                Style::default().fg(palette().text),
+            ),
+            Span::styled("↑/↓", Style::default().fg(palette().accent)),
+            Span::raw(" move  "),
+            Span::styled("Enter", Style::default().fg(palette().added)),
+            Span::raw(" keep filter  "),
+            Span::styled("Esc", Style::default().fg(palette().muted)),
             Span::raw(" to cancel"),
         ])
+    } else if let Some(message) = &app.message {
+        Line::from(vec![Span::styled(
+            format!(" {}", message),
+            Style::default().fg(palette().highlight),
+        )])
+    } else if app.focus != Focus::Commits {
+        let key = Style::default()
+            .fg(palette().accent)
+            .add_modifier(Modifier::BOLD);
+        let (open, scroll) = if app.focus == Focus::Files {
+            (" show diff  ", " select file  ")
+        } else {
+            ("", " scroll  ")
+        };
+        let mut spans = vec![Span::styled(" j/k", key), Span::raw(scroll)];
+        if !open.is_empty() {
+            spans.push(Span::styled("Enter", key));
+            spans.push(Span::raw(open));
+        } else {
+            spans.push(Span::styled("w", key));
+            spans.push(Span::raw(" whitespace  "));
+        }
+        spans.extend([
+            Span::styled("Esc/h", key),
+            Span::raw(" back  "),
+            Span::styled("q", key),
+            Span::raw(" quit "),
+        ]);
+        Line::from(spans)
     } else if app.vim_mode {
         Line::from(vec![
             Span::styled(
                 " VIM MODE ",
                 Style::default()
-                    .fg(Color::Magenta)
+                    .fg(palette().special)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" (not implemented yet)"),
@@ -580,42 +875,49 @@ fn draw_footer(f: &mut Frame, area: Rect, app: &App) {
             Span::styled(
                 " j/k",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" navigate  "),
             Span::styled(
                 "g/G",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" top/bottom  "),
+            Span::styled(
+                "Enter",
+                Style::default()
+                    .fg(palette().accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(" files  "),
             Span::styled(
                 "c",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" checkout  "),
             Span::styled(
-                "s",
+                "/",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
-            Span::raw(" type to search  "),
+            Span::raw(" search  "),
             Span::styled(
                 "q",
                 Style::default()
-                    .fg(Color::Cyan)
+                    .fg(palette().accent)
                     .add_modifier(Modifier::BOLD),
             ),
             Span::raw(" quit "),
         ])
     };
 
-    let footer = Paragraph::new(help_text).style(Style::default().bg(Color::DarkGray));
+    let footer = Paragraph::new(help_text).style(Style::default().bg(palette().selection));
 
     f.render_widget(footer, area);
 }
@@ -0,0 +1,271 @@
+/*
+`helix fsck --lost-found` - find and recover orphaned commits.
+
+Amending, resetting or deleting a branch with --force leaves commits in the
+object store that no ref points to anymore. This scans every commit object,
+walks history from all roots, and reports what is left over:
+
+  roots  = HEAD, every file under .helix/refs, and every sandbox HEAD
+  lost   = all commits - commits reachable from roots
+
+Only the tips of lost history are listed (lost commits that no other lost
+commit has as a parent); their ancestors come back with them when a tip is
+recovered to a new branch.
+*/
+use anyhow::{anyhow, Context, Result};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::peel_to_commit;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::branch_command::validate_branch_name;
+use crate::helix_index::commit::{Commit, CommitStore};
+
+/// Tip of a chain of commits no ref can reach
+#[derive(Debug, Clone)]
+pub struct LostCommit {
+    pub commit: Commit,
+    /// Unreachable commits in this tip's history, the tip included
+    pub orphaned: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LostFoundReport {
+    /// Newest first
+    pub tips: Vec<LostCommit>,
+    /// Total number of unreachable commits
+    pub unreachable: usize,
+}
+
+impl LostFoundReport {
+    pub fn print_summary(&self) {
+        if self.tips.is_empty() {
+            println!("No lost commits");
+            return;
+        }
+
+        for lost in &self.tips {
+            let commit = &lost.commit;
+            println!("lost commit {}", hash_to_hex(&commit.commit_hash));
+            println!("Author: {}", commit.author);
+            println!("Date:   {}", commit.relative_time());
+            if lost.orphaned > 1 {
+                println!(
+                    "        ({} unreachable commits in its history)",
+                    lost.orphaned
+                );
+            }
+            println!();
+            println!("    {}", commit.summary());
+            println!();
+        }
+
+        println!(
+            "Found {} unreachable commit(s) in {} lost chain(s)",
+            self.unreachable,
+            self.tips.len()
+        );
+        println!("Recover one with: helix fsck --lost-found --recover <commit> --branch <name>");
+    }
+}
+
+/// List the tips of all history that no ref, HEAD or sandbox points to
+pub fn find_lost_commits(repo_path: &Path) -> Result<LostFoundReport> {
+    if !repo_path.join(".helix").exists() {
+        anyhow::bail!("Not a helix repository (no .helix directory)");
+    }
+
+    let store = FsObjectStore::new(repo_path);
+    let all: HashSet<Hash> = store
+        .list_object_hashes(&ObjectType::Commit)?
+        .into_iter()
+        .collect();
+
+    let storage = CommitStore::new(repo_path, store)?;
+
+    let mut reachable = HashSet::new();
+    for root in collect_roots(repo_path)? {
+        walk(&storage, root, &mut reachable);
+    }
+
+    let lost: HashSet<Hash> = all.difference(&reachable).copied().collect();
+
+    let commits: Vec<Commit> = lost
+        .iter()
+        .filter_map(|hash| storage.read_commit(hash).ok())
+        .collect();
+
+    // A lost commit that another lost commit builds on isn't a tip
+    let parents: HashSet<Hash> = commits
+        .iter()
+        .flat_map(|commit| commit.parents.iter().copied())
+        .collect();
+
+    let mut tips: Vec<LostCommit> = commits
+        .into_iter()
+        .filter(|commit| !parents.contains(&commit.commit_hash))
+        .map(|commit| {
+            let mut history = HashSet::new();
+            walk(&storage, commit.commit_hash, &mut history);
+            let orphaned = history.intersection(&lost).count();
+            LostCommit { commit, orphaned }
+        })
+        .collect();
+
+    tips.sort_by_key(|lost| std::cmp::Reverse(lost.commit.commit_time));
+
+    Ok(LostFoundReport {
+        tips,
+        unreachable: lost.len(),
+    })
+}
+
+/// Point a new branch at a lost commit. `commit` may be an abbreviated hash.
+pub fn recover_commit(repo_path: &Path, commit: &str, branch: &str) -> Result<Hash> {
+    validate_branch_name(branch)?;
+
+    let branch_path = repo_path.join(".helix/refs/heads").join(branch);
+    if branch_path.exists() {
+        anyhow::bail!("Branch '{}' already exists", branch);
+    }
+
+    let hash = resolve_commit(repo_path, commit)?;
+
+    fs::create_dir_all(branch_path.parent().unwrap())?;
+    fs::write(&branch_path, format!("{}\n", hash_to_hex(&hash)))
+        .with_context(|| format!("Failed to create branch '{}'", branch))?;
+
+    Ok(hash)
+}
+
+/// Expand a full or abbreviated hex hash to the one commit object it names
+pub(crate) fn resolve_commit(repo_path: &Path, prefix: &str) -> Result<Hash> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.len() < 4 {
+        anyhow::bail!(
+            "Commit hash '{}' is too short (need at least 4 characters)",
+            prefix
+        );
+    }
+
+    let store = FsObjectStore::new(repo_path);
+    let matches: Vec<Hash> = store
+        .list_object_hashes(&ObjectType::Commit)?
+        .into_iter()
+        .filter(|hash| hash_to_hex(hash).starts_with(&prefix))
+        .collect();
+
+    match matches.as_slice() {
+        [hash] => Ok(*hash),
+        [] => Err(anyhow!("No commit matches '{}'", prefix)),
+        _ => Err(anyhow!(
+            "Commit hash '{}' is ambiguous ({} matches)",
+            prefix,
+            matches.len()
+        )),
+    }
+}
+
+/// HEAD, every ref under .helix/refs (annotated tags peeled), and every sandbox HEAD
+fn collect_roots(repo_path: &Path) -> Result<Vec<Hash>> {
+    let helix_dir = repo_path.join(".helix");
+    let store = FsObjectStore::new(repo_path);
+    let mut roots = Vec::new();
+
+    let mut heads = vec![helix_dir.join("HEAD")];
+    let sandboxes_dir = helix_dir.join("sandboxes");
+    if sandboxes_dir.exists() {
+        for entry in fs::read_dir(&sandboxes_dir)? {
+            heads.push(entry?.path().join("HEAD"));
+        }
+    }
+
+    // Symbolic HEADs are covered by the refs they point to
+    for head in heads {
+        if let Ok(content) = fs::read_to_string(&head) {
+            if let Ok(hash) = hex_to_hash(content.trim()) {
+                roots.push(hash);
+            }
+        }
+    }
+
+    let refs_dir = helix_dir.join("refs");
+    if refs_dir.exists() {
+        for entry in WalkDir::new(&refs_dir) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Ok(hash) = hex_to_hash(fs::read_to_string(entry.path())?.trim()) {
+                roots.push(peel_to_commit(&store, &hash).unwrap_or(hash));
+            }
+        }
+    }
+
+    Ok(roots)
+}
+
+/// Add `start` and all its ancestors to `seen`. Missing commits end the walk.
+fn walk(storage: &CommitStore, start: Hash, seen: &mut HashSet<Hash>) {
+    let mut to_visit = vec![start];
+
+    while let Some(hash) = to_visit.pop() {
+        if !seen.insert(hash) {
+            continue;
+        }
+
+        if let Ok(commit) = storage.read_commit(&hash) {
+            to_visit.extend(commit.parents.iter().copied());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::Tree;
+    use tempfile::TempDir;
+
+    fn write_commit(store: &FsObjectStore, parents: Vec<Hash>, message: &str) -> Result<Hash> {
+        let tree = store.write_object(&ObjectType::Tree, &Tree::new().to_bytes())?;
+        let commit = Commit::new(tree, parents, "Test <test@test.com>".into(), message.into());
+        store.write_object(&ObjectType::Commit, &commit.to_bytes())
+    }
+
+    #[test]
+    fn test_find_and_recover_lost_commits() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        let store = FsObjectStore::new(repo);
+
+        // main: a <- b, plus a lost chain a <- c <- d that was amended away
+        let a = write_commit(&store, vec![], "a")?;
+        let b = write_commit(&store, vec![a], "b")?;
+        let c = write_commit(&store, vec![a], "c")?;
+        let d = write_commit(&store, vec![c], "d")?;
+
+        fs::write(repo.join(".helix/HEAD"), "ref: refs/heads/main\n")?;
+        fs::write(repo.join(".helix/refs/heads/main"), hash_to_hex(&b))?;
+
+        let report = find_lost_commits(repo)?;
+        assert_eq!(report.unreachable, 2);
+        assert_eq!(report.tips.len(), 1);
+        assert_eq!(report.tips[0].commit.commit_hash, d);
+        assert_eq!(report.tips[0].orphaned, 2);
+
+        let recovered = recover_commit(repo, &hash_to_hex(&d)[..10], "rescued")?;
+        assert_eq!(recovered, d);
+        assert!(recover_commit(repo, &hash_to_hex(&d), "rescued").is_err());
+
+        let report = find_lost_commits(repo)?;
+        assert!(report.tips.is_empty());
+        assert_eq!(report.unreachable, 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+/*
+`helix ls-files` - list paths in the index, for scripts and editors.
+
+  helix ls-files               every tracked path
+  helix ls-files --staged      paths with staged changes
+  helix ls-files --modified    tracked paths changed in the working tree
+  helix ls-files --deleted     tracked paths missing from the working tree
+  helix ls-files --untracked   paths on disk that aren't tracked or ignored
+  helix ls-files src docs      only paths at or under these (repo-relative)
+
+Filters combine as a union. The flags come from helix.idx, refreshed against
+the working tree first (the stored MODIFIED/DELETED bits are only as fresh as
+the last status run), so the answer matches what `helix status` would show.
+As with `git ls-files -m`, a deleted file counts as modified. With -z paths
+are NUL-terminated instead of newline-terminated.
+*/
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::format::{Entry, EntryFlags};
+use crate::ignore::IgnoreRules;
+use crate::line_endings::LineEndings;
+use crate::sandbox_command::RepoContext;
+
+#[derive(Debug, Default, Clone)]
+pub struct LsFilesOptions {
+    pub staged: bool,
+    pub modified: bool,
+    pub deleted: bool,
+    pub untracked: bool,
+    /// Terminate paths with NUL instead of newline
+    pub zero: bool,
+    /// Only paths at or under these, relative to the repo root; all when empty
+    pub paths: Vec<PathBuf>,
+}
+
+impl LsFilesOptions {
+    fn any_filter(&self) -> bool {
+        self.staged || self.modified || self.deleted || self.untracked
+    }
+}
+
+/// Paths matching the filters in `options`, sorted
+pub fn ls_files(repo_path: &Path, options: &LsFilesOptions) -> Result<Vec<PathBuf>> {
+    let context = RepoContext::detect(repo_path)?;
+    let mut index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+    refresh_worktree_flags(&mut index, &context);
+
+    // Each pathspec is one contiguous run of the sorted entries
+    let entries: Vec<&Entry> = if options.paths.is_empty() {
+        index.entries().iter().collect()
+    } else {
+        options
+            .paths
+            .iter()
+            .flat_map(|path| index.entries_under(path))
+            .collect()
+    };
+
+    let mut paths: HashSet<PathBuf> = entries
+        .into_iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .filter(|e| {
+            !options.any_filter()
+                || (options.staged && e.flags.contains(EntryFlags::STAGED))
+                || (options.modified && e.flags.contains(EntryFlags::MODIFIED))
+                || (options.deleted && e.flags.contains(EntryFlags::DELETED))
+        })
+        .map(|e| e.path.clone())
+        .collect();
+
+    if options.untracked {
+        paths.extend(
+            untracked_paths(&index, &context)?
+                .into_iter()
+                .filter(|path| {
+                    options.paths.is_empty() || options.paths.iter().any(|p| path.starts_with(p))
+                }),
+        );
+    }
+
+    let mut paths: Vec<PathBuf> = paths.into_iter().collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Print `paths`, one per line or NUL-terminated
+pub fn print_files(paths: &[PathBuf], zero: bool) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    let terminator = if zero { b'\0' } else { b'\n' };
+    for path in paths {
+        out.write_all(path.to_string_lossy().as_bytes())?;
+        out.write_all(&[terminator])?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Recompute MODIFIED / DELETED for tracked entries from the working tree,
+/// in memory only. Same check as the status view: stat data first, then a
+/// content hash when it doesn't match.
+fn refresh_worktree_flags(index: &mut HelixIndexData, context: &RepoContext) {
+    let line_endings = LineEndings::load(&context.repo_root);
+
+    for entry in index.entries_mut() {
+        if !entry.flags.contains(EntryFlags::TRACKED) {
+            continue;
+        }
+        entry
+            .flags
+            .remove(EntryFlags::MODIFIED | EntryFlags::DELETED);
+
+        let full_path = context.workdir.join(&entry.path);
+        let Ok(metadata) = fs::symlink_metadata(&full_path) else {
+            entry
+                .flags
+                .insert(EntryFlags::DELETED | EntryFlags::MODIFIED);
+            continue;
+        };
+
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if mtime == entry.mtime_sec && metadata.len() == entry.size {
+            continue;
+        }
+
+        let content = if metadata.file_type().is_symlink() {
+            fs::read_link(&full_path).map(|t| t.to_string_lossy().into_owned().into_bytes())
+        } else {
+            fs::read(&full_path)
+        };
+        let changed = content
+            .map(|c| helix_protocol::hash::hash_bytes(&line_endings.normalize(&c)) != entry.oid)
+            .unwrap_or(true);
+        if changed {
+            entry.flags.insert(EntryFlags::MODIFIED);
+        }
+    }
+}
+
+/// Files in the working tree that aren't tracked and aren't ignored
+fn untracked_paths(index: &HelixIndexData, context: &RepoContext) -> Result<Vec<PathBuf>> {
+    let rules = IgnoreRules::load(&context.repo_root);
+    let workdir = &context.workdir;
+    let mut untracked = Vec::new();
+
+    let walker = WalkDir::new(workdir)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| {
+            let name = e.file_name();
+            if name == ".git" || name == ".helix" {
+                return false;
+            }
+            match e.path().strip_prefix(workdir) {
+                Ok(rel) if e.file_type().is_dir() && !rel.as_os_str().is_empty() => {
+                    !rules.should_ignore(rel)
+                }
+                _ => true,
+            }
+        });
+
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(workdir)?;
+        if index.is_tracked(rel) || rules.should_ignore(rel) {
+            continue;
+        }
+        untracked.push(rel.to_path_buf());
+    }
+
+    Ok(untracked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::commit_command::{commit, CommitOptions};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ls_files_filters() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        for name in ["clean.txt", "edited.txt", "gone.txt"] {
+            fs::write(repo.join(name), format!("{}\n", name))?;
+        }
+        let committed: Vec<PathBuf> = ["clean.txt", "edited.txt", "gone.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        stage_paths(repo, &committed)?;
+        commit(
+            repo,
+            CommitOptions {
+                message: "first".into(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )?;
+
+        fs::write(repo.join("edited.txt"), "changed\n")?;
+        fs::remove_file(repo.join("gone.txt"))?;
+        fs::write(repo.join("new.txt"), "new\n")?;
+        stage_paths(repo, &[PathBuf::from("new.txt")])?;
+        fs::write(repo.join(".gitignore"), "*.log\n")?;
+        fs::write(repo.join("debug.log"), "noise\n")?;
+
+        let list = |options: LsFilesOptions| -> Result<Vec<String>> {
+            Ok(ls_files(repo, &options)?
+                .iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect())
+        };
+
+        assert_eq!(
+            list(LsFilesOptions::default())?,
+            vec!["clean.txt", "edited.txt", "gone.txt", "new.txt"]
+        );
+        assert_eq!(
+            list(LsFilesOptions {
+                staged: true,
+                ..Default::default()
+            })?,
+            vec!["new.txt"]
+        );
+        assert_eq!(
+            list(LsFilesOptions {
+                modified: true,
+                ..Default::default()
+            })?,
+            vec!["edited.txt", "gone.txt"]
+        );
+        assert_eq!(
+            list(LsFilesOptions {
+                deleted: true,
+                ..Default::default()
+            })?,
+            vec!["gone.txt"]
+        );
+        assert_eq!(
+            list(LsFilesOptions {
+                untracked: true,
+                ..Default::default()
+            })?,
+            vec![".gitignore", "helix.toml"]
+        );
+
+        Ok(())
+    }
+}
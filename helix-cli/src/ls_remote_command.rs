@@ -0,0 +1,116 @@
+/*
+`helix ls-remote [<remote>]` - list the refs on a remote without pulling.
+
+Prints one ref per line, in the same format as `git ls-remote`:
+
+  <hash>\t<ref name>
+
+<remote> is a name from the [remotes] table of helix.toml (default origin),
+a URL or a path. --heads and --tags limit the listing to branches or tags; given
+both, both are shown. Exits 1 when nothing matches, so scripts can test for
+a branch with `helix ls-remote --heads origin feature`.
+*/
+use anyhow::Result;
+use helix_protocol::hash::{hash_to_hex, Hash};
+use std::path::Path;
+
+use crate::credential;
+use crate::push_command::resolve_remote;
+use crate::remote_refs::list_remote_refs;
+
+#[derive(Debug, Clone, Default)]
+pub struct LsRemoteOptions {
+    pub heads: bool,
+    pub tags: bool,
+    /// Only refs whose name ends with one of these path components
+    pub patterns: Vec<String>,
+}
+
+/// The remote's refs, filtered by `options`
+pub async fn ls_remote(
+    repo_path: &Path,
+    remote: &str,
+    options: &LsRemoteOptions,
+) -> Result<Vec<(String, Hash)>> {
+    let remote_config = resolve_remote(repo_path, remote)?;
+    let remote_url = remote_config.url.clone();
+    let token = credential::token_for(repo_path, &remote_url)?;
+    let repo_name = remote_config.repo_name(repo_path);
+
+    // Only ask for what's wanted when a single kind was requested
+    let prefix = match (options.heads, options.tags) {
+        (true, false) => "refs/heads/",
+        (false, true) => "refs/tags/",
+        _ => "refs/",
+    };
+    let refs = list_remote_refs(&remote_url, token.as_deref(), &repo_name, prefix).await?;
+
+    Ok(filter_refs(refs, options))
+}
+
+fn filter_refs(refs: Vec<(String, Hash)>, options: &LsRemoteOptions) -> Vec<(String, Hash)> {
+    refs.into_iter()
+        .filter(|(name, _)| {
+            (options.heads == options.tags)
+                || (options.heads && name.starts_with("refs/heads/"))
+                || (options.tags && name.starts_with("refs/tags/"))
+        })
+        .filter(|(name, _)| {
+            options.patterns.is_empty()
+                || options
+                    .patterns
+                    .iter()
+                    .any(|pattern| name == pattern || name.ends_with(&format!("/{}", pattern)))
+        })
+        .collect()
+}
+
+/// Print refs like `git ls-remote`
+pub fn print_refs(refs: &[(String, Hash)]) {
+    for (name, hash) in refs {
+        println!("{}\t{}", hash_to_hex(hash), name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_refs() {
+        let refs = vec![
+            ("refs/heads/feature/login".to_string(), [1u8; 32]),
+            ("refs/heads/main".to_string(), [2u8; 32]),
+            ("refs/tags/v1.0".to_string(), [3u8; 32]),
+        ];
+        let names = |options: LsRemoteOptions| -> Vec<String> {
+            filter_refs(refs.clone(), &options)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        };
+
+        assert_eq!(names(LsRemoteOptions::default()).len(), 3);
+        assert_eq!(
+            names(LsRemoteOptions {
+                tags: true,
+                ..Default::default()
+            }),
+            vec!["refs/tags/v1.0"]
+        );
+        assert_eq!(
+            names(LsRemoteOptions {
+                heads: true,
+                patterns: vec!["login".to_string()],
+                ..Default::default()
+            }),
+            vec!["refs/heads/feature/login"]
+        );
+        // A partial component doesn't match
+        assert!(names(LsRemoteOptions {
+            patterns: vec!["ain".to_string()],
+            ..Default::default()
+        })
+        .is_empty());
+    }
+}
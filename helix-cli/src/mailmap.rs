@@ -0,0 +1,172 @@
+/*
+Canonical author identities from .helixmailmap, the Helix counterpart of
+.mailmap.
+
+Histories imported from Git often spell one person several ways. Each line of
+.helixmailmap at the root of the working tree says how to show one of them:
+
+  # comments and blank lines are skipped
+  Ana Lima <ana@example.com>                          fix the name for an email
+  <ana@example.com> <ana@old-laptop.local>            fix the email
+  Ana Lima <ana@example.com> <alima@corp.example>     fix both for an email
+  Ana Lima <ana@example.com> ana <ana@corp.example>   only for that name and email
+
+Emails are compared without regard to case, names exactly. A line naming both
+a name and an email to match wins over one naming only the email, whatever
+their order. Only what is shown changes: commits, and their hashes, keep the
+author they were made with. log, blame and shortlog read identities through
+here.
+*/
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const MAILMAP_FILE: &str = ".helixmailmap";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Replacement {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Mailmap {
+    /// Lowercased commit email -> replacement for any name
+    by_email: HashMap<String, Replacement>,
+    /// (commit name, lowercased commit email) -> replacement
+    by_name_and_email: HashMap<(String, String), Replacement>,
+}
+
+impl Mailmap {
+    /// Read .helixmailmap from the root of `workdir`. A missing or unreadable file maps
+    /// nothing.
+    pub fn load(workdir: &Path) -> Self {
+        fs::read_to_string(workdir.join(MAILMAP_FILE))
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut mailmap = Self::default();
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let idents = parse_idents(line);
+            let (replacement, (match_name, match_email)) = match idents.as_slice() {
+                // Name <email>: the proper name for that email
+                [(name, Some(email))] if name.is_some() => (
+                    Replacement {
+                        name: name.clone(),
+                        email: None,
+                    },
+                    (None, email.clone()),
+                ),
+                [(name, Some(email)), (match_name, Some(match_email))] => (
+                    Replacement {
+                        name: name.clone(),
+                        email: Some(email.clone()),
+                    },
+                    (match_name.clone(), match_email.clone()),
+                ),
+                _ => continue,
+            };
+            let key = match_email.to_lowercase();
+            match match_name {
+                Some(name) => {
+                    mailmap.by_name_and_email.insert((name, key), replacement);
+                }
+                None => {
+                    mailmap.by_email.insert(key, replacement);
+                }
+            }
+        }
+        mailmap
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_email.is_empty() && self.by_name_and_email.is_empty()
+    }
+
+    /// The canonical form of an author string, "Name <email>" or just "Name"
+    pub fn canonical(&self, author: &str) -> String {
+        let Some((name, email)) = split_author(author) else {
+            return author.to_string();
+        };
+        let key = email.to_lowercase();
+        let replacement = self
+            .by_name_and_email
+            .get(&(name.to_string(), key.clone()))
+            .or_else(|| self.by_email.get(&key));
+        match replacement {
+            Some(r) => format!(
+                "{} <{}>",
+                r.name.as_deref().unwrap_or(name),
+                r.email.as_deref().unwrap_or(email)
+            ),
+            None => author.to_string(),
+        }
+    }
+}
+
+/// "Name <email>" as (name, email); None when there's no email
+fn split_author(author: &str) -> Option<(&str, &str)> {
+    let (name, rest) = author.rsplit_once('<')?;
+    Some((name.trim(), rest.strip_suffix('>')?.trim()))
+}
+
+/// The `Name <email>` pairs on a line, name and email each optional
+fn parse_idents(mut line: &str) -> Vec<(Option<String>, Option<String>)> {
+    let mut idents = Vec::new();
+    while !line.is_empty() {
+        let (name, email, rest) = match line.split_once('<') {
+            Some((name, rest)) => match rest.split_once('>') {
+                Some((email, rest)) => (name, Some(email.trim().to_string()), rest),
+                None => return idents,
+            },
+            None => (line, None, ""),
+        };
+        let name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+        idents.push((name, email));
+        line = rest.trim();
+    }
+    idents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mailmap_forms() {
+        let mailmap = Mailmap::parse(
+            "# team\n\
+             Ana Lima <ana@example.com>\n\
+             <ana@example.com> <ana@old-laptop.local>\n\
+             Bo Chen <bo@example.com> <BO@corp.example> # old address\n\
+             Ana Lima <ana@example.com> ana <ana@corp.example>\n",
+        );
+
+        assert_eq!(
+            mailmap.canonical("alima <ana@example.com>"),
+            "Ana Lima <ana@example.com>"
+        );
+        assert_eq!(
+            mailmap.canonical("Ana <ana@old-laptop.local>"),
+            "Ana <ana@example.com>"
+        );
+        assert_eq!(
+            mailmap.canonical("bchen <bo@Corp.Example>"),
+            "Bo Chen <bo@example.com>"
+        );
+        assert_eq!(
+            mailmap.canonical("ana <ana@corp.example>"),
+            "Ana Lima <ana@example.com>"
+        );
+        // Only that name is mapped for that email
+        assert_eq!(
+            mailmap.canonical("Someone <ana@corp.example>"),
+            "Someone <ana@corp.example>"
+        );
+        assert_eq!(mailmap.canonical("no email"), "no email");
+        assert!(Mailmap::parse("").is_empty());
+    }
+}
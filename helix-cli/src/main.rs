@@ -1,11 +1,25 @@
 use clap::{Parser, Subcommand};
 use helix_cli::{
-    add_command, branch_command, commit_command,
-    init_command::init_helix_repo,
+    add_command, apply_command, bisect_command, branch_command, check_ignore_command,
+    clone_command, commit_command, commit_message, describe_command, diff_command, doctor_command,
+    export_command, format_patch_command, gc_command, grep_command,
+    helix_index::sync::SyncEngine,
+    index_command,
+    init_command::{init_bare_repo, init_helix_repo, resume_import},
+    lost_found_command, ls_files_command, ls_remote_command, notes_command, output,
+    plumbing_command, promisor,
     pull_command::{self, pull},
-    push_command::{self, push},
-    sandbox_command::{self, CreateOptions},
+    push_command::{self, push, push_refs},
+    repair_command, repo_config, restore, rev_map_command,
+    sandbox_command::{self, CreateOptions, RepoContext},
+    serve_command, shortlog_command, size_command, trace,
+    unified_diff::Whitespace,
+    verify_command, worktree_command,
 };
+use helix_client::RemoteError;
+use helix_protocol::filter::PathFilter;
+use helix_protocol::hash::hash_to_hex;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
 mod config;
@@ -25,6 +39,21 @@ struct Args {
     command: Option<Commands>,
     #[arg(short, long, global = true)]
     branch: Option<String>,
+    /// Don't contact remotes: blobs a partial clone left on the remote aren't fetched (commands
+    /// that need one fail) and doctor skips its remote checks
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Print diff and log output directly instead of through a pager
+    #[arg(long, global = true)]
+    no_pager: bool,
+    /// Print the result of commit, push, pull, branch and status as JSON, for scripts
+    #[arg(long, global = true)]
+    json: bool,
+    /// Log what helix does to stderr, and keep it in .helix/logs if the command fails.
+    /// Takes an optional filter of per-module levels, e.g. helix_cli::push_command=trace,info
+    #[arg(long, global = true, value_name = "FILTER", num_args = 0..=1, require_equals = true,
+          default_missing_value = trace::DEFAULT_FILTER)]
+    trace: Option<String>,
     #[arg(short, long)]
     auto: bool,
     #[arg(short, long)]
@@ -34,6 +63,89 @@ struct Args {
     files: Vec<String>,
 }
 
+#[derive(Subcommand, Debug)]
+enum WorktreeCommands {
+    /// Check out a branch into a new working directory
+    Add {
+        path: PathBuf,
+        branch: String,
+        /// Check out the branch even if another worktree has it
+        #[arg(short, long)]
+        force: bool,
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// List the main and linked worktrees
+    List {},
+    /// Delete a linked worktree
+    Remove {
+        path: PathBuf,
+        /// Remove even if the worktree has staged changes
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IndexCommands {
+    /// Upgrade an index written by an older helix to the current format
+    Migrate {
+        /// Only report whether a migration is needed; fails if one is
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check the index's entries: counts, flags, duplicate paths and order
+    Verify {
+        /// Repair what can be repaired and write the index back
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BisectCommands {
+    /// Begin a search, optionally marking the bad commit and good ones
+    Start {
+        bad: Option<String>,
+        good: Vec<String>,
+    },
+    /// Mark a commit (default: HEAD) as having the regression
+    Bad { rev: Option<String> },
+    /// Mark commits (default: HEAD) as free of the regression
+    Good { revs: Vec<String> },
+    /// Leave commits (default: HEAD) that can't be tested out of the search
+    Skip { revs: Vec<String> },
+    /// Mark each candidate by running a command: 0 good, 125 skip, 1-127 bad
+    Run {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// End the search and go back to where it started
+    Reset {},
+}
+
+#[derive(Subcommand, Debug)]
+enum NotesCommands {
+    /// Attach a note to a commit
+    Add {
+        /// Commit to annotate (default: HEAD)
+        #[arg(default_value = "HEAD")]
+        rev: String,
+        #[arg(short, long)]
+        message: String,
+        /// Replace the commit's existing note
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Print a commit's note
+    Show {
+        #[arg(default_value = "HEAD")]
+        rev: String,
+    },
+    /// List annotated commits, as `<note blob> <commit>`
+    List {},
+}
+
 #[derive(Subcommand, Debug)]
 enum SandboxCommands {
     /// Create a new sandbox from HEAD (or specified commit)
@@ -82,15 +194,85 @@ enum Commands {
     Init {
         #[arg(value_name = "PATH")]
         path: Option<PathBuf>,
+        /// Continue a Git import that was interrupted with Ctrl-C
+        #[arg(long)]
+        resume: bool,
+        /// Create a bare repository (objects and refs only, no working tree) to push to
+        #[arg(long, conflicts_with = "resume")]
+        bare: bool,
     },
     Log {
         #[arg(value_name = "PATH")]
         path: Option<PathBuf>,
+        /// Graph every branch and tag, not just the current branch
+        #[arg(long)]
+        all: bool,
+        /// Keep following the file's history across renames (with -- <FILE>)
+        #[arg(long, requires = "file")]
+        follow: bool,
+        /// Print the branch's history one commit per line instead of opening the viewer
+        #[arg(long, conflicts_with_all = ["all", "file"])]
+        oneline: bool,
+        /// Only show commits that changed this file
+        #[arg(last = true, value_name = "FILE")]
+        file: Option<PathBuf>,
     },
     Status {
         #[arg(value_name = "PATH")]
         path: Option<PathBuf>,
     },
+    /// Show changes between the working tree, the index and commits
+    Diff {
+        /// <REV>, <REV1> <REV2> or <REV1>..<REV2>
+        #[arg(value_name = "REV")]
+        revs: Vec<String>,
+        /// Compare the index instead of the working tree
+        #[arg(long, alias = "cached")]
+        staged: bool,
+        /// Show binary files as text
+        #[arg(long)]
+        text: bool,
+        /// Lines of context around each change
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        context: usize,
+        /// Ignore changes in the amount of whitespace, and whitespace at line ends
+        #[arg(long)]
+        ignore_space_change: bool,
+        /// Ignore whitespace when comparing lines
+        #[arg(short = 'w', long, conflicts_with = "ignore_space_change")]
+        ignore_all_space: bool,
+        /// Only show changes to these paths
+        #[arg(last = true, value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+    /// Search tracked files in the index or a commit
+    Grep {
+        /// Regular expression to search for
+        pattern: String,
+        /// Search this commit's tree instead of the index
+        #[arg(value_name = "REV")]
+        rev: Option<String>,
+        #[arg(short, long)]
+        ignore_case: bool,
+        /// Treat the pattern as a literal string
+        #[arg(short = 'F', long)]
+        fixed_strings: bool,
+        /// Only match whole words
+        #[arg(short, long)]
+        word_regexp: bool,
+        /// Only print the names of matching files
+        #[arg(short = 'l', long)]
+        files_with_matches: bool,
+        /// Print the number of matching lines per file
+        #[arg(short, long)]
+        count: bool,
+        /// Search binary files as text
+        #[arg(short = 'a', long)]
+        text: bool,
+        /// Only search these paths
+        #[arg(last = true, value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
     Commit {
         #[arg(short, long)]
         message: Option<String>,
@@ -102,6 +284,24 @@ enum Commands {
         amend: bool,
         #[arg(short, long)]
         verbose: bool,
+        /// Add a Signed-off-by trailer
+        #[arg(short, long)]
+        signoff: bool,
+        /// Keep the previous commit's message when amending
+        #[arg(long, requires = "amend", conflicts_with = "message")]
+        no_edit: bool,
+        /// Take the author from config and the date from now when amending
+        #[arg(long, requires = "amend", conflicts_with = "author")]
+        reset_author: bool,
+        /// Author date: @<unix-seconds>, RFC 3339, or "YYYY-MM-DD HH:MM:SS"
+        #[arg(long)]
+        date: Option<String>,
+        /// Record key=value metadata on the commit, e.g. a build or review ID (repeatable)
+        #[arg(long, value_name = "KEY=VALUE", value_parser = commit_command::parse_metadata)]
+        metadata: Vec<(String, String)>,
+        /// Write the commit even if its parent or tree is missing from the store (recovery)
+        #[arg(long)]
+        allow_missing_objects: bool,
     },
     Add {
         #[arg(required = true)]
@@ -113,6 +313,7 @@ enum Commands {
         #[arg(short, long)]
         force: bool,
     },
+    /// List, create, rename and delete branches
     Branch {
         name: Option<String>,
         new_name: Option<String>,
@@ -129,47 +330,521 @@ enum Commands {
         #[arg(short, long)]
         verbose: bool,
     },
+    /// Print an object: a commit, tree, blob or tag
+    CatFile {
+        /// Hash (or prefix), revision, or <rev>:<path>
+        object: String,
+        /// Print the object's type
+        #[arg(short = 't', conflicts_with = "size")]
+        show_type: bool,
+        /// Print the object's size in bytes
+        #[arg(short = 's')]
+        size: bool,
+    },
+    /// Compute the blob hash of files
+    HashObject {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Write the blobs to the object store
+        #[arg(short)]
+        write: bool,
+        /// Hash the files as they are, without line-ending normalization
+        #[arg(long)]
+        no_filters: bool,
+    },
+    /// List the contents of a tree
+    LsTree {
+        /// Commit, tag or tree
+        rev: String,
+        /// Only list this directory, or this file
+        path: Option<PathBuf>,
+        /// Recurse into subtrees
+        #[arg(short)]
+        recursive: bool,
+        /// Only print paths
+        #[arg(long)]
+        name_only: bool,
+    },
+    /// List tracked paths, or those matching the given filters
+    LsFiles {
+        /// Paths with staged changes
+        #[arg(short, long)]
+        staged: bool,
+        /// Tracked paths changed in the working tree (including deleted ones)
+        #[arg(short, long)]
+        modified: bool,
+        /// Tracked paths missing from the working tree
+        #[arg(short, long)]
+        deleted: bool,
+        /// Paths that are neither tracked nor ignored
+        #[arg(short = 'o', long)]
+        untracked: bool,
+        /// Terminate paths with NUL instead of newline
+        #[arg(short = 'z')]
+        zero: bool,
+        /// Only list paths at or under these
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+    /// Translate a commit id between Git and Helix after an import
+    RevMap {
+        /// Git SHA or Helix hash, full or abbreviated
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+    /// Switch to a branch or sandbox
+    Switch {
+        /// Branch name, or sandboxes/<name>
+        name: String,
+        /// Create the branch at HEAD first
+        #[arg(short = 'c', long, conflicts_with = "force_create")]
+        create: bool,
+        /// Create the branch at HEAD, resetting it if it exists
+        #[arg(short = 'C', long)]
+        force_create: bool,
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Restore files in the working tree from the index, or the index from HEAD
+    Restore {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Unstage: restore the index from HEAD
+        #[arg(short = 'S', long)]
+        staged: bool,
+        /// Restore the working tree too (the default without --staged)
+        #[arg(short = 'W', long)]
+        worktree: bool,
+    },
     Push {
+        /// Remote name from helix.toml, or a URL or path to push to directly
         remote: String,
-        branch: String,
+        #[arg(required_unless_present_any = ["all", "tags", "notes"])]
+        branch: Option<String>,
+        /// Delete the branch on the remote instead of pushing it
+        #[arg(short, long, requires = "branch", conflicts_with_all = ["all", "tags", "notes"])]
+        delete: bool,
+        /// Push every local branch, all or nothing
+        #[arg(long, conflicts_with = "branch")]
+        all: bool,
+        /// Push every local tag, all or nothing (with a branch or --all, those too)
+        #[arg(long)]
+        tags: bool,
+        /// Push every notes namespace under refs/notes/, like --tags
+        #[arg(long)]
+        notes: bool,
         #[arg(short, long)]
         force: bool,
         #[arg(short, long)]
         verbose: bool,
         #[arg(short = 'n', long)]
         dry_run: bool,
+        /// Send everything uncompressed (for debugging the wire protocol)
+        #[arg(long)]
+        no_compress: bool,
     },
     Pull {
+        /// Remote name from helix.toml, or a URL or path to pull from directly
         remote: String,
         branch: String,
         #[arg(short, long)]
         verbose: bool,
         #[arg(short = 'n', long)]
         dry_run: bool,
+        /// Ask the server not to compress the response (for debugging the wire protocol)
+        #[arg(long)]
+        no_compress: bool,
+        /// Only update the branch if it can be fast-forwarded
+        #[arg(long, conflicts_with = "rebase")]
+        ff_only: bool,
+        /// Replay local commits on top of the remote branch instead of merging
+        #[arg(long)]
+        rebase: bool,
+        /// Delete remote-tracking refs for branches deleted on the server (with -n, only list them)
+        #[arg(short, long)]
+        prune: bool,
+        /// Only fetch file contents under these paths, e.g. path:src/ (kept for later pulls)
+        #[arg(long, value_name = "SPEC")]
+        filter: Option<String>,
+        /// Also fetch the remote's notes and merge them into the local ones
+        #[arg(long)]
+        notes: bool,
+        /// Accept commits whose parents or trees are missing on both sides (recovery)
+        #[arg(long)]
+        allow_missing_objects: bool,
+        /// Overwrite uncommitted changes to files the pull updates
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Copy a repository into a new directory and check out a branch
+    Clone {
+        /// URL or path of the repository to clone
+        source: String,
+        /// Directory to clone into (default: named after the source)
+        #[arg(value_name = "DIR")]
+        dest: Option<PathBuf>,
+        #[arg(short, long)]
+        verbose: bool,
+        /// Only fetch file contents under these paths, e.g. path:src/; other files are
+        /// left out of the checkout
+        #[arg(long, value_name = "SPEC")]
+        filter: Option<String>,
+    },
+    /// List the branches and tags on a remote without pulling
+    LsRemote {
+        /// Remote name from helix.toml, a URL or a path
+        #[arg(default_value = "origin")]
+        remote: String,
+        /// Only show refs whose name ends with one of these
+        #[arg(value_name = "PATTERN")]
+        patterns: Vec<String>,
+        /// Only show branches
+        #[arg(long)]
+        heads: bool,
+        /// Only show tags
+        #[arg(short, long)]
+        tags: bool,
+    },
+    /// Replace corrupt or missing objects with copies from a remote
+    Repair {
+        #[arg(default_value = "origin")]
+        remote: String,
+        #[arg(short, long)]
+        verbose: bool,
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+    /// Export Helix history to another format
+    Export {
+        /// Write commits, branches and tags to a Git repository
+        #[arg(long)]
+        git: bool,
+        /// Destination Git repository (defaults to this repository's .git)
+        #[arg(value_name = "PATH")]
+        dest: Option<PathBuf>,
+        /// Overwrite Git refs that point at a different commit
+        #[arg(short, long)]
+        force: bool,
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Import from the Git repository this Helix repo was initialized from
+    Import {
+        /// Only import Git commits made since the last import, and update refs
+        #[arg(long)]
+        update: bool,
+        /// Store commits even if their parent or tree is missing from the store (recovery)
+        #[arg(long)]
+        allow_missing_objects: bool,
+    },
+    /// Apply a unified diff to the working tree
+    Apply {
+        /// Patch file (reads stdin when omitted or "-")
+        #[arg(value_name = "PATCH")]
+        patch: Option<PathBuf>,
+        /// Undo the patch
+        #[arg(short = 'R', long)]
+        reverse: bool,
+        /// Stage the patched files as well
+        #[arg(long)]
+        index: bool,
+        /// Only check whether the patch applies
+        #[arg(long)]
+        check: bool,
+        /// Most context lines a hunk may ignore at each end to apply
+        #[arg(long, default_value_t = apply_command::DEFAULT_FUZZ)]
+        fuzz: usize,
+        /// Commit each patch of a `helix format-patch` mailbox with its author, date and message
+        #[arg(long, conflicts_with_all = ["reverse", "check"])]
+        commit: bool,
+    },
+    /// Write commits as patch emails, one file per commit
+    FormatPatch {
+        /// REV for the commits HEAD has and REV doesn't, or FROM..TO
+        range: String,
+        /// Directory to write the patch files to
+        #[arg(
+            short,
+            long = "output-directory",
+            value_name = "DIR",
+            default_value = "."
+        )]
+        output_directory: PathBuf,
+        /// Print the patches as one mailbox instead of writing files
+        #[arg(long, conflicts_with = "output_directory")]
+        stdout: bool,
+    },
+    /// Show which ignore rule matches each path
+    CheckIgnore {
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+        /// Print the source file, line and pattern that matched
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Check repository integrity
+    #[command(visible_alias = "fsck")]
+    Verify {
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+        /// Re-hash all objects and check every tree, commit, ref and index reference
+        #[arg(long)]
+        all: bool,
+        /// List commits that no branch, tag, HEAD or sandbox can reach
+        #[arg(long, conflicts_with = "all")]
+        lost_found: bool,
+        /// Create a branch at a lost commit (use with --lost-found and --branch)
+        #[arg(long, value_name = "COMMIT", requires_all = ["lost_found", "branch"])]
+        recover: Option<String>,
+        /// Name of the branch to create for --recover
+        #[arg(long, value_name = "NAME", requires = "recover")]
+        branch: Option<String>,
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Check the repository, config and remotes for common problems
+    Doctor {
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+    /// Show what takes up space in the repository: objects, largest files, growth
+    Size {
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+        /// How many of the largest blobs and directories to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// How many recent commits to report growth for
+        #[arg(long, default_value_t = 10)]
+        commits: usize,
+    },
+    /// Answer status, diff, blame and log queries for editor plugins over JSON-RPC
+    Serve {
+        /// Serve this repository to local clients (the only mode for now)
+        #[arg(long, required = true)]
+        local: bool,
+        /// Unix socket to listen on (default: .helix/helix.sock)
+        #[arg(long, conflicts_with = "stdio")]
+        socket: Option<PathBuf>,
+        /// Talk to a single client over stdin/stdout instead of a socket
+        #[arg(long)]
+        stdio: bool,
     },
     /// Manage sandboxes for isolated agent workspaces
     Sandbox {
         #[command(subcommand)]
         command: SandboxCommands,
     },
+    /// Manage additional working directories for this repository
+    Worktree {
+        #[command(subcommand)]
+        command: WorktreeCommands,
+    },
+    /// Tidy the object store: move objects into the configured layout
+    Gc {},
+    /// Maintain the index file itself
+    Index {
+        #[command(subcommand)]
+        command: IndexCommands,
+    },
+    /// Summarize commits by author, e.g. for release notes or contribution counts
+    Shortlog {
+        /// REV or FROM..TO (default: HEAD)
+        range: Option<String>,
+        /// Only count commits per author
+        #[arg(short, long)]
+        summary: bool,
+        /// Sort by number of commits instead of by author
+        #[arg(short, long)]
+        numbered: bool,
+        /// Show each author's email
+        #[arg(short, long)]
+        email: bool,
+        #[arg(long)]
+        no_merges: bool,
+        /// Don't map authors through .helixmailmap
+        #[arg(long)]
+        no_mailmap: bool,
+        /// Only count commits that changed these paths
+        #[arg(last = true, value_name = "PATH")]
+        paths: Vec<PathBuf>,
+    },
+    /// Name a commit after the nearest tag it contains, e.g. v1.4.0-7-g1a2b3c4d
+    Describe {
+        /// Commit to describe (default: HEAD)
+        rev: Option<String>,
+        /// Use the long form even on a tagged commit
+        #[arg(long)]
+        long: bool,
+        /// Fall back to the short hash when no tag is reachable
+        #[arg(long)]
+        always: bool,
+        /// Append MARK when the working tree has changes (default: -dirty)
+        #[arg(long, value_name = "MARK", num_args = 0..=1, default_missing_value = "-dirty", require_equals = true)]
+        dirty: Option<String>,
+        /// Only consider tags matching this glob
+        #[arg(long = "match", value_name = "GLOB")]
+        pattern: Option<String>,
+    },
+    /// Binary-search history for the commit that introduced a regression
+    Bisect {
+        #[command(subcommand)]
+        command: BisectCommands,
+    },
+    /// Annotate existing commits without rewriting them
+    Notes {
+        /// Notes namespace, under refs/notes/ (default: commits)
+        #[arg(long = "ref", global = true, value_name = "NAMESPACE")]
+        namespace: Option<String>,
+        #[command(subcommand)]
+        command: NotesCommands,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    trace::install_panic_hook();
+    if let Err(err) = run().await {
+        // Errors from the server get specific guidance and exit codes
+        let remote = err.downcast_ref::<RemoteError>();
+        let hint = remote.and_then(|remote| remote.hint());
+
+        if output::is_json() {
+            let mut error = serde_json::json!({ "error": format!("{err:#}") });
+            if let Some(hint) = &hint {
+                error["hint"] = hint.to_string().into();
+            }
+            println!("{error}");
+        } else {
+            eprintln!("Error: {err:?}");
+            if let Some(hint) = &hint {
+                eprintln!("hint: {hint}");
+            }
+        }
+        if let Some(path) = trace::write_error_log(&err) {
+            eprintln!("Trace written to {}", path.display());
+        }
+        std::process::exit(remote.map_or(1, RemoteError::exit_code));
+    }
+}
+
+async fn run() -> Result<()> {
     let args = Args::parse();
+    promisor::set_offline(args.offline);
+    output::set_no_pager(args.no_pager);
+    output::set_json(args.json);
+    if let Some(filter) = &args.trace {
+        trace::init(filter)?;
+    }
+    tracing::debug!("helix {}", std::env::args().collect::<Vec<_>>().join(" "));
+
+    // Finish or undo a commit that a crash interrupted before anything reads the index
+    let cwd = std::env::current_dir()?;
+    if let Ok(context) = RepoContext::detect(&cwd) {
+        commit_command::recover_interrupted_commit(&context)?;
+    }
 
     match args.command {
-        Some(Commands::Log { path }) => {
+        Some(Commands::Log {
+            path,
+            all,
+            follow,
+            oneline,
+            file,
+        }) => {
             let repo_path = resolve_repo_path(path.as_deref())?;
-            log::run(Some(&repo_path))?;
+            match file {
+                Some(file) => {
+                    let mut out = output::Output::start(&repo_path);
+                    log::print_file_history(&mut out, &repo_path, &file, follow)?
+                }
+                None if oneline => {
+                    let mut out = output::Output::start(&repo_path);
+                    log::print_oneline(&mut out, &repo_path)?
+                }
+                None => log::run(Some(&repo_path), all)?,
+            }
         }
         Some(Commands::Status { path }) => {
-            let repo_path = resolve_repo_path(path.as_deref())?;
-            status::run(Some(&repo_path))?;
+            let repo_path = resolve_work_tree(path.as_deref())?;
+            if args.json {
+                output::print_json(&serve_command::status_report(&repo_path)?)?;
+            } else {
+                status::run(Some(&repo_path))?;
+            }
+        }
+        Some(Commands::Diff {
+            revs,
+            staged,
+            text,
+            context,
+            ignore_space_change,
+            ignore_all_space,
+            paths,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+
+            let whitespace = if ignore_all_space {
+                Whitespace::IgnoreAll
+            } else if ignore_space_change {
+                Whitespace::IgnoreChange
+            } else {
+                Whitespace::Exact
+            };
+            let options = diff_command::DiffOptions {
+                staged,
+                text,
+                context,
+                paths: paths.iter().map(|p| repo_relative(&repo_path, p)).collect(),
+                whitespace,
+            };
+            let diffs = diff_command::diff(&repo_path, &revs, &options)?;
+            let mut out = output::Output::start(&repo_path);
+            diff_command::print_diff(&mut out, &diffs, &options)?;
         }
-        Some(Commands::Init { path }) => {
+        Some(Commands::Grep {
+            pattern,
+            rev,
+            ignore_case,
+            fixed_strings,
+            word_regexp,
+            files_with_matches,
+            count,
+            text,
+            paths,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+
+            let options = grep_command::GrepOptions {
+                ignore_case,
+                fixed_strings,
+                word: word_regexp,
+                files_with_matches,
+                count,
+                text,
+                paths: paths.iter().map(|p| repo_relative(&repo_path, p)).collect(),
+            };
+            let results = grep_command::grep(&repo_path, &pattern, rev.as_deref(), &options)?;
+            grep_command::print_matches(&results, &options);
+
+            // Like grep, exit 1 when nothing matched
+            if results.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Init { path, resume, bare }) => {
+            // A bare repo is usually made somewhere new, e.g. /srv/helix/app
+            if let (true, Some(path)) = (bare, &path) {
+                std::fs::create_dir_all(path)?;
+            }
             let repo_path = resolve_repo_path(path.as_deref())?;
-            init_helix_repo(&repo_path, None)?;
+            if bare {
+                init_bare_repo(&repo_path)?;
+            } else if resume {
+                resume_import(&repo_path)?;
+            } else {
+                init_helix_repo(&repo_path, None)?;
+            }
         }
         Some(Commands::Branch {
             name,
@@ -190,7 +865,11 @@ async fn main() -> Result<()> {
                 verbose,
             };
 
-            if (name.is_none() && !delete && !rename) || list {
+            if args.json && (list || (name.is_none() && !delete && !rename)) {
+                output::print_json(&branch_command::list_branches(&repo_path)?)?;
+            } else if list {
+                branch_command::print_branches(&repo_path)?;
+            } else if name.is_none() && !delete && !rename {
                 branch_command::run_branch_tui(Some(&repo_path))?;
             } else if let Some(branch_name) = name {
                 // Handle sandbox branches first (before validation)
@@ -208,29 +887,45 @@ async fn main() -> Result<()> {
                         eprintln!("Error: Cannot rename sandboxes. Destroy and recreate instead.");
                         std::process::exit(1);
                     } else {
-                        // Switch to sandbox
-                        sandbox_command::switch_sandbox(&repo_path, sandbox_name)?;
+                        eprintln!(
+                            "Error: Sandboxes are created with 'helix sandbox create'. \
+                             To switch to one, use 'helix switch {}'",
+                            branch_name
+                        );
+                        std::process::exit(1);
                     }
                 } else if delete {
                     branch_command::delete_branch(&repo_path, &branch_name, options)?;
+                    if args.json {
+                        output::print_json(&serde_json::json!({
+                            "action": "deleted",
+                            "branch": branch_name,
+                        }))?;
+                    }
                 } else if rename {
                     if let Some(new) = new_name {
                         branch_command::rename_branch(&repo_path, &branch_name, &new, options)?;
+                        if args.json {
+                            output::print_json(&serde_json::json!({
+                                "action": "renamed",
+                                "branch": new,
+                                "from": branch_name,
+                            }))?;
+                        }
                     } else {
                         eprintln!("Error: --rename requires two branch names");
                         eprintln!("Usage: helix branch --rename <old-name> <new-name>");
                         std::process::exit(1);
                     }
                 } else {
-                    // Regular branch - check if exists
-                    let branch_exists = repo_path
-                        .join(format!(".helix/refs/heads/{}", branch_name))
-                        .exists();
-
-                    if branch_exists {
-                        branch_command::switch_branch(&repo_path, &branch_name)?;
-                    } else {
-                        branch_command::create_branch(&repo_path, &branch_name, options)?;
+                    branch_command::create_branch(&repo_path, &branch_name, options)?;
+                    if args.json {
+                        let tip = diff_command::resolve_revision(&repo_path, &branch_name)?;
+                        output::print_json(&serde_json::json!({
+                            "action": "created",
+                            "branch": branch_name,
+                            "commit": hash_to_hex(&tip),
+                        }))?;
                     }
                 }
             } else {
@@ -238,13 +933,108 @@ async fn main() -> Result<()> {
                 std::process::exit(1);
             }
         }
+        Some(Commands::CatFile {
+            object,
+            show_type,
+            size,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let mode = if show_type {
+                plumbing_command::CatFileMode::Type
+            } else if size {
+                plumbing_command::CatFileMode::Size
+            } else {
+                plumbing_command::CatFileMode::Pretty
+            };
+            let out = plumbing_command::cat_file(&repo_path, &object, mode)?;
+            std::io::Write::write_all(&mut std::io::stdout(), &out)?;
+        }
+        Some(Commands::HashObject {
+            files,
+            write,
+            no_filters,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let options = plumbing_command::HashObjectOptions { write, no_filters };
+            for file in files {
+                let hash = plumbing_command::hash_object(&repo_path, &file, &options)?;
+                println!("{}", hash_to_hex(&hash));
+            }
+        }
+        Some(Commands::LsTree {
+            rev,
+            path,
+            recursive,
+            name_only,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let options = plumbing_command::LsTreeOptions {
+                recursive,
+                name_only,
+            };
+            let path = path.map(|p| repo_relative(&repo_path, &p));
+            let entries = plumbing_command::ls_tree(&repo_path, &rev, path.as_deref(), &options)?;
+            plumbing_command::print_ls_tree(&entries, &options);
+        }
+        Some(Commands::LsFiles {
+            staged,
+            modified,
+            deleted,
+            untracked,
+            zero,
+            paths,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+            let options = ls_files_command::LsFilesOptions {
+                staged,
+                modified,
+                deleted,
+                untracked,
+                zero,
+                paths,
+            };
+            let paths = ls_files_command::ls_files(&repo_path, &options)?;
+            ls_files_command::print_files(&paths, options.zero)?;
+        }
+        Some(Commands::RevMap { id }) => {
+            let repo_path = resolve_repo_path(None)?;
+            println!("{}", rev_map_command::rev_map(&repo_path, &id)?);
+        }
+        Some(Commands::Switch {
+            name,
+            create,
+            force_create,
+            verbose,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+            let options = branch_command::SwitchOptions {
+                create,
+                force_create,
+                verbose,
+            };
+            branch_command::switch(&repo_path, &name, options)?;
+        }
+        Some(Commands::Restore {
+            paths,
+            staged,
+            worktree,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+            let options = restore::RestoreOptions { staged, worktree };
+            let restored = restore::restore(&repo_path, &paths, &options)?;
+            println!(
+                "Restored {} file{}",
+                restored.len(),
+                if restored.len() == 1 { "" } else { "s" }
+            );
+        }
         Some(Commands::Add {
             paths,
             verbose,
             dry_run,
             force,
         }) => {
-            let repo_path = resolve_repo_path(None)?;
+            let repo_path = resolve_work_tree(None)?;
 
             let options = add_command::AddOptions {
                 verbose,
@@ -260,8 +1050,30 @@ async fn main() -> Result<()> {
             allow_empty,
             amend,
             verbose,
+            signoff,
+            no_edit,
+            reset_author,
+            date,
+            metadata,
+            allow_missing_objects,
         }) => {
-            let repo_path = resolve_repo_path(None)?;
+            let repo_path = resolve_work_tree(None)?;
+            let date = date.map(|d| commit_command::parse_date(&d)).transpose()?;
+
+            // Without -m, write the message in an editor when there's a terminal to show it on
+            let message = match message {
+                Some(msg) => Some(msg),
+                None if no_edit => Some(String::new()),
+                None if std::io::stdin().is_terminal() => {
+                    let initial = match amend {
+                        true => Some(commit_command::head_commit(&repo_path)?.message),
+                        false => None,
+                    };
+                    Some(commit_message::edit_message(&repo_path, initial)?)
+                        .filter(|m| !m.is_empty())
+                }
+                None => None,
+            };
 
             if let Some(msg) = message {
                 let options = commit_command::CommitOptions {
@@ -270,9 +1082,26 @@ async fn main() -> Result<()> {
                     allow_empty,
                     amend,
                     verbose,
+                    signoff,
+                    no_edit,
+                    reset_author,
+                    date,
+                    metadata: metadata.into_iter().collect(),
+                    allow_missing_objects,
                 };
 
-                commit_command::commit(&repo_path, options)?;
+                let hash = commit_command::commit(&repo_path, options)?;
+                if args.json {
+                    let commit = commit_command::head_commit(&repo_path)?;
+                    output::print_json(&serde_json::json!({
+                        "commit": hash_to_hex(&hash),
+                        "parents": commit.parents.iter().map(hash_to_hex).collect::<Vec<_>>(),
+                        "author": commit.author,
+                        "summary": commit.summary(),
+                    }))?;
+                }
+            } else if args.json {
+                anyhow::bail!("Aborting commit due to empty commit message");
             } else {
                 commit_command::show_staged(&repo_path)?;
                 eprintln!();
@@ -284,9 +1113,14 @@ async fn main() -> Result<()> {
         Some(Commands::Push {
             remote,
             branch,
+            all,
+            tags,
+            notes,
+            delete,
             force,
             verbose,
             dry_run,
+            no_compress,
         }) => {
             let repo_path = resolve_repo_path(None)?;
 
@@ -294,25 +1128,299 @@ async fn main() -> Result<()> {
                 verbose,
                 dry_run,
                 force,
+                no_compress,
             };
 
-            push(&repo_path, &remote, &branch, options).await?;
+            let prefixes: Vec<&str> = [
+                (all, "refs/heads/"),
+                (tags, "refs/tags/"),
+                (notes, notes_command::NOTES_PREFIX),
+            ]
+            .into_iter()
+            .filter_map(|(wanted, prefix)| wanted.then_some(prefix))
+            .collect();
+
+            let report = match branch {
+                Some(branch) if prefixes.is_empty() && !delete => {
+                    push(&repo_path, &remote, &branch, options).await?
+                }
+                branch => {
+                    push_refs(
+                        &repo_path,
+                        &remote,
+                        branch.as_deref(),
+                        &prefixes,
+                        delete,
+                        options,
+                    )
+                    .await?
+                }
+            };
+            if args.json {
+                output::print_json(&report)?;
+            }
         }
         Some(Commands::Pull {
             remote,
             branch,
             verbose,
             dry_run,
+            no_compress,
+            ff_only,
+            rebase,
+            prune,
+            filter,
+            notes,
+            allow_missing_objects,
+            force,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+
+            let options = pull_command::PullOptions {
+                verbose,
+                dry_run,
+                no_compress,
+                ff_only,
+                rebase,
+                prune,
+                filter: filter.as_deref().map(PathFilter::parse).transpose()?,
+                allow_missing_objects,
+                force,
+            };
+
+            let report = pull(&repo_path, &remote, &branch, options).await?;
+            if notes && !dry_run {
+                notes_command::pull_notes(&repo_path, &remote, no_compress, verbose).await?;
+            }
+            if args.json {
+                output::print_json(&report)?;
+            }
+        }
+        Some(Commands::Clone {
+            source,
+            dest,
+            verbose,
+            filter,
+        }) => {
+            // -b/--branch picks the branch to check out instead of the source's default
+            let options = clone_command::CloneOptions {
+                branch: args.branch,
+                verbose,
+                filter: filter.as_deref().map(PathFilter::parse).transpose()?,
+            };
+            clone_command::clone(&source, dest.as_deref(), options).await?;
+        }
+        Some(Commands::LsRemote {
+            remote,
+            patterns,
+            heads,
+            tags,
         }) => {
             let repo_path = resolve_repo_path(None)?;
 
-            let options = pull_command::PullOptions { verbose, dry_run };
+            let options = ls_remote_command::LsRemoteOptions {
+                heads,
+                tags,
+                patterns,
+            };
+            let refs = ls_remote_command::ls_remote(&repo_path, &remote, &options).await?;
+            ls_remote_command::print_refs(&refs);
 
-            pull(&repo_path, &remote, &branch, options).await?;
+            if refs.is_empty() {
+                std::process::exit(1);
+            }
         }
-        Some(Commands::Sandbox { command }) => {
+        Some(Commands::Repair {
+            remote,
+            verbose,
+            dry_run,
+        }) => {
             let repo_path = resolve_repo_path(None)?;
 
+            let options = repair_command::RepairOptions { verbose, dry_run };
+            let report = repair_command::repair(&repo_path, &remote, options).await?;
+
+            if !report.unrepaired.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Export {
+            git,
+            dest,
+            force,
+            verbose,
+        }) => {
+            if !git {
+                anyhow::bail!("Specify an export format, e.g. `helix export --git`");
+            }
+            let repo_path = resolve_repo_path(None)?;
+
+            let options = export_command::ExportOptions {
+                dest,
+                force,
+                verbose,
+            };
+            let summary = export_command::export_git(&repo_path, options)?;
+
+            println!(
+                "Exported {} commits, {} branches and {} tags to Git",
+                summary.commits, summary.branches, summary.tags
+            );
+            for git_ref in &summary.skipped_refs {
+                println!("Skipped {} (points elsewhere in Git; use --force)", git_ref);
+            }
+        }
+        Some(Commands::Import {
+            update,
+            allow_missing_objects,
+        }) => {
+            if !update {
+                anyhow::bail!(
+                    "`helix init` performs the initial import; use `helix import --update` \
+                     to bring in new Git commits"
+                );
+            }
+            let repo_path = resolve_work_tree(None)?;
+
+            let summary = SyncEngine::new(&repo_path)
+                .with_allow_missing_objects(allow_missing_objects)
+                .import_update()?;
+
+            println!("Imported {} new commit(s) from Git", summary.commits_count);
+            for name in &summary.refs_updated {
+                println!("  updated {}", name);
+            }
+            for name in &summary.refs_skipped {
+                println!("  skipped {} (has Helix-only commits)", name);
+            }
+            if let Some(files) = summary.files_count {
+                println!("Refreshed index ({} tracked files)", files);
+            }
+        }
+        Some(Commands::Apply {
+            patch,
+            reverse,
+            index,
+            check,
+            fuzz,
+            commit,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+
+            let patch = match patch {
+                Some(path) if path != Path::new("-") => std::fs::read_to_string(&path)?,
+                _ => std::io::read_to_string(std::io::stdin())?,
+            };
+            let options = apply_command::ApplyOptions {
+                reverse,
+                index,
+                check,
+                fuzz,
+            };
+            if commit {
+                apply_command::apply_mailbox(&repo_path, &patch, &options)?;
+                return Ok(());
+            }
+            let report = apply_command::apply(&repo_path, &patch, &options)?;
+            report.print_summary(check);
+            if !report.is_clean() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::FormatPatch {
+            range,
+            output_directory,
+            stdout,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+
+            let patches = format_patch_command::format_patch(&repo_path, &range)?;
+            if patches.is_empty() {
+                println!("No commits to format");
+            } else if stdout {
+                for patch in &patches {
+                    print!("{}", patch.contents);
+                }
+            } else {
+                for path in format_patch_command::write_patches(&patches, &output_directory)? {
+                    println!("{}", path.display());
+                }
+            }
+        }
+        Some(Commands::CheckIgnore { paths, verbose }) => {
+            let repo_path = resolve_repo_path(None)?;
+
+            let results = check_ignore_command::check_ignore(&repo_path, &paths)?;
+            if !check_ignore_command::print_results(&results, verbose) {
+                // Like git check-ignore: exit 1 when nothing is ignored
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Doctor { path }) => {
+            // Doctor reports config problems itself instead of stopping on them
+            let repo_path = locate_repo(path.as_deref())?;
+            let options = doctor_command::DoctorOptions {
+                offline: args.offline,
+            };
+            let report = doctor_command::doctor(&repo_path, options).await?;
+            report.print_summary();
+
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Size { path, top, commits }) => {
+            let repo_path = resolve_repo_path(path.as_deref())?;
+            let options = size_command::SizeOptions { top, commits };
+            size_command::size(&repo_path, options)?.print_summary();
+        }
+        Some(Commands::Serve {
+            local: _,
+            socket,
+            stdio,
+        }) => {
+            let repo_path = resolve_work_tree(None)?;
+            if stdio {
+                serve_command::serve_stdio(&repo_path)?;
+            } else {
+                serve_command::serve_socket(&repo_path, socket.as_deref())?;
+            }
+        }
+        Some(Commands::Verify {
+            path,
+            all,
+            lost_found,
+            recover,
+            branch,
+            verbose,
+        }) => {
+            let repo_path = resolve_repo_path(path.as_deref())?;
+
+            if lost_found {
+                if let (Some(commit), Some(branch)) = (recover, branch) {
+                    let hash = lost_found_command::recover_commit(&repo_path, &commit, &branch)?;
+                    println!(
+                        "Created branch '{}' at {}",
+                        branch,
+                        &hash_to_hex(&hash)[..8]
+                    );
+                } else {
+                    lost_found_command::find_lost_commits(&repo_path)?.print_summary();
+                }
+                return Ok(());
+            }
+
+            let options = verify_command::VerifyOptions { all, verbose };
+            let report = verify_command::verify(&repo_path, options)?;
+            report.print_summary();
+
+            if !report.is_ok() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Sandbox { command }) => {
+            let repo_path = resolve_work_tree(None)?;
+
             match command {
                 SandboxCommands::Create {
                     name,
@@ -371,6 +1479,116 @@ async fn main() -> Result<()> {
                 }
             }
         }
+        Some(Commands::Worktree { command }) => {
+            let repo_path = resolve_work_tree(None)?;
+
+            match command {
+                WorktreeCommands::Add {
+                    path,
+                    branch,
+                    force,
+                    verbose,
+                } => {
+                    let options = worktree_command::WorktreeOptions { force, verbose };
+                    worktree_command::add_worktree(&repo_path, &path, &branch, options)?;
+                }
+                WorktreeCommands::List {} => {
+                    let worktrees = worktree_command::list_worktrees(&repo_path)?;
+                    worktree_command::print_worktrees(&repo_path, &worktrees)?;
+                }
+                WorktreeCommands::Remove { path, force } => {
+                    worktree_command::remove_worktree(&repo_path, &path, force)?;
+                }
+            }
+        }
+        Some(Commands::Gc {}) => {
+            let repo_path = resolve_repo_path(None)?;
+            gc_command::gc(&repo_path)?;
+        }
+        Some(Commands::Index { command }) => {
+            let repo_path = resolve_repo_path(None)?;
+
+            match command {
+                IndexCommands::Migrate { check } => index_command::migrate(&repo_path, check)?,
+                IndexCommands::Verify { fix } => index_command::verify(&repo_path, fix)?,
+            }
+        }
+        Some(Commands::Shortlog {
+            range,
+            summary,
+            numbered,
+            email,
+            no_merges,
+            no_mailmap,
+            paths,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let options = shortlog_command::ShortlogOptions {
+                numbered,
+                email,
+                no_merges,
+                no_mailmap,
+                paths: paths.iter().map(|p| repo_relative(&repo_path, p)).collect(),
+            };
+            let authors = shortlog_command::shortlog(&repo_path, range.as_deref(), &options)?;
+            shortlog_command::print_shortlog(&authors, summary);
+        }
+        Some(Commands::Describe {
+            rev,
+            long,
+            always,
+            dirty,
+            pattern,
+        }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let options = describe_command::DescribeOptions {
+                long,
+                always,
+                dirty,
+                pattern,
+            };
+            println!(
+                "{}",
+                describe_command::describe(&repo_path, rev.as_deref(), &options)?
+            );
+        }
+        Some(Commands::Bisect { command }) => {
+            let repo_path = resolve_work_tree(None)?;
+
+            match command {
+                BisectCommands::Start { bad, good } => {
+                    bisect_command::start(&repo_path, bad.as_deref(), &good)?
+                }
+                BisectCommands::Bad { rev } => {
+                    let revs: Vec<String> = rev.into_iter().collect();
+                    bisect_command::mark(&repo_path, bisect_command::Mark::Bad, &revs)?
+                }
+                BisectCommands::Good { revs } => {
+                    bisect_command::mark(&repo_path, bisect_command::Mark::Good, &revs)?
+                }
+                BisectCommands::Skip { revs } => {
+                    bisect_command::mark(&repo_path, bisect_command::Mark::Skip, &revs)?
+                }
+                BisectCommands::Run { command } => bisect_command::run(&repo_path, &command)?,
+                BisectCommands::Reset {} => bisect_command::reset(&repo_path)?,
+            }
+        }
+        Some(Commands::Notes { namespace, command }) => {
+            let repo_path = resolve_repo_path(None)?;
+            let notes_ref = notes_command::notes_ref(namespace.as_deref());
+
+            match command {
+                NotesCommands::Add {
+                    rev,
+                    message,
+                    force,
+                } => {
+                    notes_command::add(&repo_path, &notes_ref, &rev, &message, force)?;
+                }
+                NotesCommands::Show { rev } => notes_command::show(&repo_path, &notes_ref, &rev)?,
+                NotesCommands::List {} => notes_command::print_list(&repo_path, &notes_ref)?,
+            }
+        }
         None => {
             // Default behavior when no command specified
             println!("Helix - AI-native version control");
@@ -384,7 +1602,21 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// The repository a command runs in, with its helix.toml checked first
 fn resolve_repo_path(path: Option<&Path>) -> Result<PathBuf> {
+    let repo_path = locate_repo(path)?;
+    repo_config::validate(&repo_path)?;
+    Ok(repo_path)
+}
+
+/// `resolve_repo_path` for commands that need a working tree, which bare repos don't have
+fn resolve_work_tree(path: Option<&Path>) -> Result<PathBuf> {
+    let repo_path = resolve_repo_path(path)?;
+    RepoContext::detect(&repo_path)?.require_work_tree()?;
+    Ok(repo_path)
+}
+
+fn locate_repo(path: Option<&Path>) -> Result<PathBuf> {
     let repo_path = match path {
         Some(p) => p.to_path_buf(),
         None => std::env::current_dir()?,
@@ -392,3 +1624,13 @@ fn resolve_repo_path(path: Option<&Path>) -> Result<PathBuf> {
 
     Ok(repo_path.canonicalize()?)
 }
+
+/// Make a path given on the command line relative to the repository root
+fn repo_relative(repo_path: &Path, path: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().unwrap_or_else(|_| repo_path.to_path_buf());
+    let absolute = cwd.join(path);
+    absolute
+        .strip_prefix(repo_path)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
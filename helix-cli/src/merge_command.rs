@@ -6,6 +6,11 @@
 //! - Merge analysis to classify changes and detect conflicts
 //! - Conflict marker generation for text files
 //! - Merge commit creation with two parents
+//!
+//! A path both sides changed is a conflict unless its merge attribute in
+//! .helixattributes picks a driver that settles it: `ours` keeps the target's
+//! version, `theirs` the sandbox's, and `union` the target's plus the lines the
+//! sandbox added. Those only apply when both sides still have the file.
 
 use anyhow::{Context, Result};
 use helix_protocol::hash::{hash_to_hex, Hash};
@@ -15,10 +20,13 @@ use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::attributes::{Attributes, MergeDriver};
+use crate::binary::is_binary;
 use crate::helix_index::commit::{Commit, CommitStore};
 use crate::helix_index::format::{Entry, EntryFlags, Header};
 use crate::helix_index::tree::TreeStore;
 use crate::helix_index::writer::Writer;
+use crate::unified_diff::{diff_lines, split_lines, Op};
 
 /// A change detected between two trees
 #[derive(Debug, Clone)]
@@ -236,6 +244,7 @@ pub fn analyze_merge(
         .cloned()
         .collect();
 
+    let attributes = Attributes::load(repo_path);
     let mut auto_resolved = Vec::new();
     let mut conflicts = Vec::new();
 
@@ -256,7 +265,11 @@ pub fn analyze_merge(
             // Both changed - need to check if it's a conflict
             (Some(tc), Some(sc)) => {
                 if let Some(conflict) = detect_conflict(&path, tc, sc, base_entry) {
-                    conflicts.push(conflict);
+                    let driver = attributes.for_path(&path).merge;
+                    match resolve_with_driver(&store, driver, &conflict, tc, sc)? {
+                        Some(resolved) => auto_resolved.push(resolved),
+                        None => conflicts.push(conflict),
+                    }
                 } else {
                     // Same change in both - take either one
                     auto_resolved.push(change_to_resolved(tc));
@@ -394,6 +407,61 @@ fn detect_conflict(
     }
 }
 
+/// Settle a conflict with the path's merge driver, if it has one that can
+fn resolve_with_driver(
+    store: &FsObjectStore,
+    driver: Option<MergeDriver>,
+    conflict: &MergeConflict,
+    target_change: &TreeChange,
+    sandbox_change: &TreeChange,
+) -> Result<Option<ResolvedEntry>> {
+    let (Some(target), Some(sandbox)) = (conflict.target, conflict.sandbox) else {
+        return Ok(None);
+    };
+    match driver {
+        Some(MergeDriver::Ours) => Ok(Some(change_to_resolved(target_change))),
+        Some(MergeDriver::Theirs) => Ok(Some(change_to_resolved(sandbox_change))),
+        Some(MergeDriver::Union) => {
+            let read = |hash: &Hash| store.read_object(&ObjectType::Blob, hash);
+            let base = conflict
+                .base
+                .as_ref()
+                .map(read)
+                .transpose()?
+                .unwrap_or_default();
+            let Some(merged) = union_merge(&base, &read(&target)?, &read(&sandbox)?) else {
+                return Ok(None);
+            };
+            let mut resolved = change_to_resolved(target_change);
+            resolved.blob_hash = Some(store.write_object(&ObjectType::Blob, &merged)?);
+            Ok(Some(resolved))
+        }
+        Some(MergeDriver::Text | MergeDriver::Binary) | None => Ok(None),
+    }
+}
+
+/// The target's content followed by the lines the sandbox added to the base, or None
+/// when any side isn't text
+fn union_merge(base: &[u8], target: &[u8], sandbox: &[u8]) -> Option<Vec<u8>> {
+    if [base, target, sandbox].iter().any(|c| is_binary(c)) {
+        return None;
+    }
+    let base = std::str::from_utf8(base).ok()?;
+    let sandbox = std::str::from_utf8(sandbox).ok()?;
+    let sandbox_lines = split_lines(sandbox);
+
+    let mut merged = target.to_vec();
+    if !merged.is_empty() && !merged.ends_with(b"\n") {
+        merged.push(b'\n');
+    }
+    for op in diff_lines(&split_lines(base), &sandbox_lines) {
+        if let Op::Insert { new } = op {
+            merged.extend_from_slice(sandbox_lines[new].as_bytes());
+        }
+    }
+    Some(merged)
+}
+
 fn get_new_hash(change: &TreeChange) -> Option<Hash> {
     match change {
         TreeChange::Added { blob_hash, .. } => Some(*blob_hash),
@@ -466,6 +534,33 @@ pub fn execute_merge(
     let store = FsObjectStore::new(repo_path);
     let commit_store = CommitStore::new(repo_path, store.clone())?;
 
+    let merged_tree_hash = build_merged_tree(repo_path, analysis, resolutions)?;
+
+    let merge_commit = Commit::new(
+        merged_tree_hash,
+        vec![*target_commit_hash, *sandbox_commit_hash],
+        author.to_string(),
+        message.to_string(),
+    );
+
+    let commit_hash = commit_store.write_commit(&merge_commit)?;
+
+    Ok(MergeResult {
+        commit_hash,
+        merged_tree_hash,
+        conflicts_resolved: analysis.conflicts.len(),
+        files_changed: analysis.auto_resolved.len() + analysis.conflicts.len(),
+    })
+}
+
+/// Apply the analysis and conflict resolutions to the base tree and write the result
+pub fn build_merged_tree(
+    repo_path: &Path,
+    analysis: &MergeAnalysis,
+    resolutions: &HashMap<PathBuf, ConflictResolution>,
+) -> Result<Hash> {
+    let store = FsObjectStore::new(repo_path);
+
     // Verify all conflicts are resolved
     for conflict in &analysis.conflicts {
         if !resolutions.contains_key(&conflict.path) {
@@ -539,21 +634,5 @@ pub fn execute_merge(
 
     // Build tree from entries
     let tree_builder = crate::helix_index::tree::TreeBuilder::new(repo_path);
-    let merged_tree_hash = tree_builder.build_from_entries(&entries)?;
-
-    let merge_commit = Commit::new(
-        merged_tree_hash,
-        vec![*target_commit_hash, *sandbox_commit_hash],
-        author.to_string(),
-        message.to_string(),
-    );
-
-    let commit_hash = commit_store.write_commit(&merge_commit)?;
-
-    Ok(MergeResult {
-        commit_hash,
-        merged_tree_hash,
-        conflicts_resolved: analysis.conflicts.len(),
-        files_changed: analysis.auto_resolved.len() + analysis.conflicts.len(),
-    })
+    tree_builder.build_from_entries(&entries)
 }
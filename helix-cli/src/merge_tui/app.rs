@@ -49,6 +49,8 @@ pub struct App {
     pub author: String,
     pub diff_scroll: usize,
     pub diff_max_scroll: usize,
+    /// Commit message for the merge; defaults to a sandbox merge message
+    pub message: Option<String>,
 }
 
 impl App {
@@ -122,9 +124,15 @@ impl App {
             author: author.to_string(),
             diff_scroll: 0,
             diff_max_scroll: 0,
+            message: None,
         })
     }
 
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
     pub fn has_conflicts(&self) -> bool {
         !self.conflicts.is_empty()
     }
@@ -397,10 +405,12 @@ impl App {
             .filter_map(|c| c.resolution.clone().map(|r| (c.conflict.path.clone(), r)))
             .collect();
 
-        let message = format!(
-            "Merge sandbox '{}' into '{}'",
-            self.sandbox_name, self.target_branch
-        );
+        let message = self.message.clone().unwrap_or_else(|| {
+            format!(
+                "Merge sandbox '{}' into '{}'",
+                self.sandbox_name, self.target_branch
+            )
+        });
 
         execute_merge(
             &self.repo_path,
@@ -0,0 +1,373 @@
+/*
+`helix notes` - annotations attached to commits that already exist.
+
+A commit's hash covers its message, so anything learned after the fact (a
+review link, a benchmark result, "reverted in abc123") can't go in it. Notes
+hold that instead, without rewriting history.
+
+Notes live in a namespace, a ref under .helix/refs/notes/ (refs/notes/commits
+unless --ref says otherwise). The ref points at an ordinary commit whose tree
+has one file per annotated commit, named by the commit's full hex hash and
+holding the note's text. Every change to the notes is a new commit on that
+ref, so they have history of their own and move between repos like a branch.
+
+  helix notes add [-m MSG] [-f] [REV]   annotate REV (default HEAD); -f replaces
+                                        a note that is already there
+  helix notes show [REV]                print REV's note
+  helix notes list                      every annotated commit and its note's blob
+
+Notes are only exchanged when asked for: `helix push <remote> --notes` pushes
+every namespace like --tags pushes tags, and `helix pull <remote> <branch>
+--notes` fetches the remote's namespaces and merges them into the local ones.
+Two repos that annotated different commits end up with both notes; when both
+changed the note on the same commit, the merged note is the local text, a blank
+line, then the remote text.
+*/
+use anyhow::{anyhow, bail, Context, Result};
+use helix_client::RemoteError;
+use helix_protocol::commit::{is_ancestor, merge_base};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::{ObjectType, RpcMessage};
+use helix_protocol::storage::{FsObjectStore, FsRefStore};
+use helix_protocol::validate::IncomingObjects;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::author::resolve_author;
+use crate::credential;
+use crate::diff_command::resolve_revision;
+use crate::helix_index::commit::{Commit, CommitStore};
+use crate::helix_index::tree::{Tree, TreeEntry, TreeStore};
+use crate::push_command::resolve_remote;
+use crate::transport;
+
+pub const NOTES_PREFIX: &str = "refs/notes/";
+pub const DEFAULT_NOTES_REF: &str = "refs/notes/commits";
+
+/// The full ref for a namespace given as `review` or `refs/notes/review`
+pub fn notes_ref(namespace: Option<&str>) -> String {
+    match namespace {
+        None => DEFAULT_NOTES_REF.to_string(),
+        Some(name) if name.starts_with(NOTES_PREFIX) => name.to_string(),
+        Some(name) => format!("{NOTES_PREFIX}{name}"),
+    }
+}
+
+/// Annotated commit -> note blob, as recorded by one notes commit
+type NoteMap = BTreeMap<Hash, Hash>;
+
+/// Attach `message` to the commit `rev` names. A commit with a note keeps it unless
+/// `force` is set.
+pub fn add(
+    repo_path: &Path,
+    notes_ref: &str,
+    rev: &str,
+    message: &str,
+    force: bool,
+) -> Result<Hash> {
+    let commit = resolve_revision(repo_path, rev)?;
+    let refs = FsRefStore::new(repo_path);
+    let tip = refs.get_ref(notes_ref)?;
+    let mut notes = read_notes(repo_path, tip)?;
+
+    if notes.contains_key(&commit) && !force {
+        bail!(
+            "Commit {} already has a note in {notes_ref} (use -f to replace it)",
+            &hash_to_hex(&commit)[..12]
+        );
+    }
+    let mut text = message.trim_end().to_string();
+    text.push('\n');
+    let blob = FsObjectStore::new(repo_path).write_object(&ObjectType::Blob, text.as_bytes())?;
+    notes.insert(commit, blob);
+
+    let author = resolve_author(repo_path, None)?;
+    let summary = format!("Notes added for {}", &hash_to_hex(&commit)[..12]);
+    let new_tip = write_notes(
+        repo_path,
+        &notes,
+        tip.into_iter().collect(),
+        author,
+        summary,
+    )?;
+    refs.set_ref(notes_ref, new_tip)?;
+    Ok(new_tip)
+}
+
+/// The note on the commit `rev` names, if it has one
+pub fn read_note(repo_path: &Path, notes_ref: &str, rev: &str) -> Result<Option<String>> {
+    let commit = resolve_revision(repo_path, rev)?;
+    let notes = read_notes(repo_path, FsRefStore::new(repo_path).get_ref(notes_ref)?)?;
+    notes
+        .get(&commit)
+        .map(|blob| read_blob(repo_path, blob))
+        .transpose()
+}
+
+/// Every annotated commit in a namespace and its note's blob, sorted by commit hash
+pub fn list(repo_path: &Path, notes_ref: &str) -> Result<Vec<(Hash, Hash)>> {
+    let notes = read_notes(repo_path, FsRefStore::new(repo_path).get_ref(notes_ref)?)?;
+    Ok(notes.into_iter().collect())
+}
+
+pub fn show(repo_path: &Path, notes_ref: &str, rev: &str) -> Result<()> {
+    match read_note(repo_path, notes_ref, rev)? {
+        Some(note) => print!("{note}"),
+        None => bail!("No note for {rev} in {notes_ref}"),
+    }
+    Ok(())
+}
+
+pub fn print_list(repo_path: &Path, notes_ref: &str) -> Result<()> {
+    for (commit, blob) in list(repo_path, notes_ref)? {
+        println!("{} {}", hash_to_hex(&blob), hash_to_hex(&commit));
+    }
+    Ok(())
+}
+
+/// Bring `theirs`, a notes commit fetched from elsewhere, into the local `notes_ref`.
+/// Fast-forwards when one side has everything; otherwise records a merge commit whose
+/// notes are the three-way merge of both sides. Returns the new tip.
+pub fn merge_notes(repo_path: &Path, notes_ref: &str, theirs: Hash) -> Result<Hash> {
+    let store = FsObjectStore::new(repo_path);
+    let refs = FsRefStore::new(repo_path);
+    let ours = match refs.get_ref(notes_ref)? {
+        Some(ours) => ours,
+        None => {
+            refs.set_ref(notes_ref, theirs)?;
+            return Ok(theirs);
+        }
+    };
+    if is_ancestor(&store, theirs, ours)? {
+        return Ok(ours);
+    }
+    if is_ancestor(&store, ours, theirs)? {
+        refs.set_ref(notes_ref, theirs)?;
+        return Ok(theirs);
+    }
+
+    let base = read_notes(repo_path, merge_base(&store, ours, theirs)?)?;
+    let local = read_notes(repo_path, Some(ours))?;
+    let remote = read_notes(repo_path, Some(theirs))?;
+
+    let mut merged = NoteMap::new();
+    let commits: BTreeSet<&Hash> = local.keys().chain(remote.keys()).collect();
+    for commit in commits {
+        let (b, l, r) = (base.get(commit), local.get(commit), remote.get(commit));
+        let blob = match (l, r) {
+            (Some(l), Some(r)) if l == r => *l,
+            (Some(l), Some(r)) if b == Some(l) => *r,
+            (Some(l), Some(r)) if b == Some(r) => *l,
+            (Some(l), Some(r)) => {
+                let text = format!("{}\n{}", read_blob(repo_path, l)?, read_blob(repo_path, r)?);
+                store.write_object(&ObjectType::Blob, text.as_bytes())?
+            }
+            // Removed on one side and untouched on the other stays removed
+            (Some(kept), None) | (None, Some(kept)) if b == Some(kept) => continue,
+            (Some(kept), None) | (None, Some(kept)) => *kept,
+            (None, None) => continue,
+        };
+        merged.insert(*commit, blob);
+    }
+
+    let author = resolve_author(repo_path, None)?;
+    let message = format!("Notes merged into {notes_ref}");
+    let tip = write_notes(repo_path, &merged, vec![ours, theirs], author, message)?;
+    refs.set_ref(notes_ref, tip)?;
+    Ok(tip)
+}
+
+/// Fetch every notes namespace on a remote and merge each into the local one of the
+/// same name
+pub async fn pull_notes(
+    repo_path: &Path,
+    remote_name: &str,
+    no_compress: bool,
+    verbose: bool,
+) -> Result<()> {
+    let remote_config = resolve_remote(repo_path, remote_name)?;
+    let remote_url = remote_config.url.clone();
+    let token = credential::token_for(repo_path, &remote_url)?;
+    let repo_name = remote_config.repo_name(repo_path);
+    let remote = transport::connect(&remote_url, token.as_deref(), &repo_name)
+        .with_compression(!no_compress);
+
+    let store = FsObjectStore::new(repo_path);
+    let refs = FsRefStore::new(repo_path);
+    for (notes_ref, remote_tip) in remote.list_refs(NOTES_PREFIX).await? {
+        let local_tip = refs.get_ref(&notes_ref)?;
+        if local_tip == Some(remote_tip) {
+            continue;
+        }
+        if !store.has_object(&ObjectType::Commit, &remote_tip) {
+            let mut response = remote.pull(&notes_ref, local_tip, None).await?;
+            let mut incoming = IncomingObjects::new(&store);
+            let mut objects = Vec::new();
+            loop {
+                match response.next().await {
+                    Ok(RpcMessage::PullObject(obj)) => {
+                        incoming
+                            .accept(&obj.object_type, &obj.hash, &obj.data)
+                            .context("Rejected notes object from remote; nothing was written")?;
+                        objects.push(obj);
+                    }
+                    Ok(RpcMessage::PullDone) => {}
+                    Ok(RpcMessage::PullAck(_)) => break,
+                    Ok(RpcMessage::Error(err)) => return Err(RemoteError::from(err).into()),
+                    Ok(other) => bail!("Unexpected message: {:?}", other),
+                    Err(e) => bail!("Error reading message: {}", e),
+                }
+            }
+            if !incoming.has(&ObjectType::Commit, &remote_tip) {
+                bail!(
+                    "{notes_ref} {} was not received; nothing was written",
+                    &hash_to_hex(&remote_tip)[..12]
+                );
+            }
+            for obj in &objects {
+                if !store.has_object(&obj.object_type, &obj.hash) {
+                    store.write_object_compressed_with_hash(
+                        &obj.object_type,
+                        &obj.hash,
+                        &obj.data,
+                    )?;
+                }
+            }
+        }
+
+        let tip = merge_notes(repo_path, &notes_ref, remote_tip)?;
+        if verbose || Some(tip) != local_tip {
+            println!(
+                "Updated {notes_ref} from {remote_name} ({})",
+                &hash_to_hex(&tip)[..12]
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The notes a notes commit records; none for no commit
+fn read_notes(repo_path: &Path, tip: Option<Hash>) -> Result<NoteMap> {
+    let Some(tip) = tip else {
+        return Ok(NoteMap::new());
+    };
+    let commits = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+    let tree = TreeStore::for_repo(repo_path).read(&commits.read_commit(&tip)?.tree_hash)?;
+    tree.entries
+        .iter()
+        .map(|entry| {
+            let commit = hex_to_hash(&entry.name)
+                .map_err(|_| anyhow!("Notes tree has a non-commit entry '{}'", entry.name))?;
+            Ok((commit, entry.oid))
+        })
+        .collect()
+}
+
+/// Record `notes` as a new notes commit on top of `parents`
+fn write_notes(
+    repo_path: &Path,
+    notes: &NoteMap,
+    parents: Vec<Hash>,
+    author: String,
+    message: String,
+) -> Result<Hash> {
+    let store = FsObjectStore::new(repo_path);
+    let mut tree = Tree::new();
+    for (commit, blob) in notes {
+        let size = store.read_object(&ObjectType::Blob, blob)?.len() as u64;
+        tree.add_entry(TreeEntry::new_file(
+            hash_to_hex(commit),
+            *blob,
+            0o100644,
+            size,
+        ));
+    }
+    tree.sort();
+    let tree_hash = TreeStore::for_repo(repo_path).write(&tree)?;
+    let commits = CommitStore::new(repo_path, store)?;
+    commits.write_commit(&Commit::new(tree_hash, parents, author, message))
+}
+
+fn read_blob(repo_path: &Path, blob: &Hash) -> Result<String> {
+    let bytes = FsObjectStore::new(repo_path).read_object(&ObjectType::Blob, blob)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn commit(repo: &Path, parents: Vec<Hash>, message: &str) -> Result<Hash> {
+        let tree = TreeStore::for_repo(repo).write(&Tree::new())?;
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+        commits.write_commit(&Commit::new(
+            tree,
+            parents,
+            "T <t@x>".to_string(),
+            message.to_string(),
+        ))
+    }
+
+    #[test]
+    fn test_notes_add_show_and_merge_concurrent_notes() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        fs::write(
+            repo.join("helix.toml"),
+            "[user]\nname = \"T\"\nemail = \"t@x\"\n",
+        )?;
+        let first = commit(repo, vec![], "first")?;
+        let second = commit(repo, vec![first], "second")?;
+        let (first_hex, second_hex) = (hash_to_hex(&first), hash_to_hex(&second));
+
+        add(
+            repo,
+            DEFAULT_NOTES_REF,
+            &first_hex,
+            "reviewed by ana",
+            false,
+        )?;
+        assert_eq!(
+            read_note(repo, DEFAULT_NOTES_REF, &first_hex)?.as_deref(),
+            Some("reviewed by ana\n")
+        );
+        assert!(add(repo, DEFAULT_NOTES_REF, &first_hex, "again", false).is_err());
+        assert_eq!(read_note(repo, DEFAULT_NOTES_REF, &second_hex)?, None);
+        let shared = FsRefStore::new(repo).get_ref(DEFAULT_NOTES_REF)?.unwrap();
+
+        // Another clone annotates the second commit and changes the first one's note
+        let theirs_ref = "refs/notes/theirs";
+        FsRefStore::new(repo).set_ref(theirs_ref, shared)?;
+        add(repo, theirs_ref, &second_hex, "benchmarks: 12ms", false)?;
+        let theirs = add(repo, theirs_ref, &first_hex, "ci passed", true)?;
+
+        // Meanwhile the first commit's note changed here too
+        add(
+            repo,
+            DEFAULT_NOTES_REF,
+            &first_hex,
+            "reviewed by ana and bo",
+            true,
+        )?;
+        let merged = merge_notes(repo, DEFAULT_NOTES_REF, theirs)?;
+
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+        assert_eq!(commits.read_commit(&merged)?.parents.len(), 2);
+        assert_eq!(list(repo, DEFAULT_NOTES_REF)?.len(), 2);
+        assert_eq!(
+            read_note(repo, DEFAULT_NOTES_REF, &first_hex)?.as_deref(),
+            Some("reviewed by ana and bo\n\nci passed\n")
+        );
+        assert_eq!(
+            read_note(repo, DEFAULT_NOTES_REF, &second_hex)?.as_deref(),
+            Some("benchmarks: 12ms\n")
+        );
+
+        // Merging what we already have changes nothing
+        assert_eq!(merge_notes(repo, DEFAULT_NOTES_REF, theirs)?, merged);
+        assert_eq!(notes_ref(Some("review")), "refs/notes/review");
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+/*
+How commands print: color and paging for the ones that print a lot, decided
+the way Git does, and JSON for automation.
+
+Output is colored when stdout is a terminal and NO_COLOR isn't set (to
+anything non-empty). helix.toml can settle it either way:
+
+  [ui]
+  color = "auto"      # the default
+  color = "always"    # also when piped, e.g. into `less -R`
+  color = "never"
+  pager = "less -S"
+
+Output to a terminal goes through a pager: $HELIX_PAGER, then [ui] pager,
+then $PAGER, then `less`. The command is run by the shell, so it may carry
+arguments; an empty one or "cat" prints directly, as does --no-pager. LESS
+defaults to FRX: colors pass through (R), the text stays on screen after
+quitting (X), and less exits right away when it all fits on one screen (F).
+
+Quitting the pager before the end closes the pipe, and so does `| head`.
+Writes after that are dropped rather than failing the command.
+
+With the global --json flag, commit, push, pull, branch and status print one
+JSON object on stdout instead, and their usual messages (`say!`) go to stderr.
+Exit codes don't change. The objects:
+
+  commit  {"commit": HASH, "parents": [HASH], "author": "Name <email>",
+           "summary": "..."}
+  push    {"remote": "origin", "objects": 12, "dry_run": false,
+           "refs": [{"ref": "refs/heads/main", "target": HASH | null,
+                     "status": "updated" | "deleted" | "up-to-date" |
+                               "not-pushed" | "would-push" | "would-delete"}]}
+  pull    {"remote": "origin", "branch": "main", "objects": 12,
+           "outcome": "dry-run" | "no-remote-branch" | "up-to-date" |
+                      "checked-out" | "fast-forward" | "merged" | "rebased",
+           "head": HASH | null}
+  branch  {"current": "main", "branches": [{"name": "main", "commit": HASH}],
+           "remotes": ["origin/main"]}              listing (with no name too)
+          {"action": "created" | "deleted" | "renamed", "branch": "new",
+           "from": "old" (renamed only), "commit": HASH (created only)}
+  status  {"staged": [PATH], "modified": [PATH], "deleted": [PATH],
+           "untracked": [PATH]}                     untracked directories end in /
+
+A command that fails prints {"error": "...", "hint": "..." (when there is
+one)} instead of the error text.
+*/
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::init_command::{HelixConfig, UiSection};
+
+/// LESS when it isn't set already
+const DEFAULT_LESS: &str = "FRX";
+
+static NO_PAGER: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for a command's messages: stdout, or stderr with --json so that stdout
+/// only carries the JSON result
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::is_json() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Only on a terminal, and not with NO_COLOR
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Print straight to stdout even on a terminal
+pub fn set_no_pager(no_pager: bool) {
+    NO_PAGER.store(no_pager, Ordering::Relaxed);
+}
+
+/// Print results as JSON (--json)
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print a command's result as a single line of JSON
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Whether to color, given the configured mode, NO_COLOR and whether stdout is a
+/// terminal
+pub fn use_color(mode: ColorMode, no_color: Option<&str>, is_terminal: bool) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => is_terminal && no_color.is_none_or(str::is_empty),
+    }
+}
+
+/// The pager to run: $HELIX_PAGER, [ui] pager, $PAGER, then less. None for an empty one
+/// or cat.
+pub fn pager_command(
+    helix_pager: Option<String>,
+    configured: Option<&str>,
+    pager: Option<String>,
+) -> Option<String> {
+    let command = helix_pager
+        .or_else(|| configured.map(String::from))
+        .or(pager)
+        .unwrap_or_else(|| "less".to_string());
+    let command = command.trim();
+    (!command.is_empty() && command != "cat").then(|| command.to_string())
+}
+
+pub(crate) fn load_ui(repo_path: &Path) -> UiSection {
+    std::fs::read_to_string(repo_path.join("helix.toml"))
+        .ok()
+        .and_then(|contents| toml::from_str::<HelixConfig>(&contents).ok())
+        .and_then(|config| config.ui)
+        .unwrap_or_default()
+}
+
+/// Stdout for a command's output. Starting it decides whether `console::style` colors,
+/// and on a terminal opens the pager; dropping it waits for the pager to exit.
+pub struct Output {
+    pager: Option<Child>,
+}
+
+impl Output {
+    pub fn start(repo_path: &Path) -> Self {
+        let ui = load_ui(repo_path);
+        let is_terminal = io::stdout().is_terminal();
+        let no_color = env::var("NO_COLOR").ok();
+        console::set_colors_enabled(use_color(ui.color, no_color.as_deref(), is_terminal));
+
+        let pager = if is_terminal && !NO_PAGER.load(Ordering::Relaxed) {
+            pager_command(
+                env::var("HELIX_PAGER").ok(),
+                ui.pager.as_deref(),
+                env::var("PAGER").ok(),
+            )
+            .and_then(|command| spawn_pager(&command).ok())
+        } else {
+            None
+        };
+        Self { pager }
+    }
+}
+
+fn spawn_pager(command: &str) -> io::Result<Child> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        shell.env("LESS", DEFAULT_LESS);
+    }
+    shell.spawn()
+}
+
+/// A closed pipe means the reader has seen enough
+fn ignore_closed_pipe<T>(result: io::Result<T>, done: T) -> io::Result<T> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(done),
+        other => other,
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match self.pager.as_mut().and_then(|pager| pager.stdin.as_mut()) {
+            Some(stdin) => stdin.write(buf),
+            None => io::stdout().write(buf),
+        };
+        ignore_closed_pipe(result, buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match self.pager.as_mut().and_then(|pager| pager.stdin.as_mut()) {
+            Some(stdin) => stdin.flush(),
+            None => io::stdout().flush(),
+        };
+        ignore_closed_pipe(result, ())
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let _ = self.flush();
+        if let Some(mut pager) = self.pager.take() {
+            // Closing its input lets the pager know the output is complete
+            drop(pager.stdin.take());
+            let _ = pager.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_and_pager_choice() {
+        assert!(use_color(ColorMode::Auto, None, true));
+        assert!(use_color(ColorMode::Auto, Some(""), true));
+        assert!(!use_color(ColorMode::Auto, Some("1"), true));
+        assert!(!use_color(ColorMode::Auto, None, false));
+        assert!(use_color(ColorMode::Always, Some("1"), false));
+        assert!(!use_color(ColorMode::Never, None, true));
+
+        let ui: UiSection = toml::from_str("color = \"always\"\npager = \"more\"").unwrap();
+        assert_eq!(ui.color, ColorMode::Always);
+
+        assert_eq!(pager_command(None, None, None).as_deref(), Some("less"));
+        assert_eq!(
+            pager_command(None, ui.pager.as_deref(), Some("most".into())).as_deref(),
+            Some("more")
+        );
+        assert_eq!(
+            pager_command(Some("less -S".into()), Some("more"), None).as_deref(),
+            Some("less -S")
+        );
+        assert_eq!(pager_command(None, None, Some("cat".into())), None);
+        assert_eq!(pager_command(Some(" ".into()), None, None), None);
+    }
+}
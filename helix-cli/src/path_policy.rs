@@ -0,0 +1,245 @@
+/*
+Path normalization for case-insensitive filesystems and Unicode normalization forms.
+
+macOS and Windows filesystems usually treat `README.md` and `readme.md` as the
+same file, and macOS additionally treats NFC (`é`) and NFD (`e` + U+0301)
+spellings of a name as the same file. Comparing index paths byte-for-byte with
+what the filesystem reports then produces duplicate or missing entries.
+
+`helix init` probes the working filesystem and records the result in the
+[core] section of .helix/state (mirroring Git's core.ignorecase and
+core.precomposeunicode):
+
+  [core]
+  ignorecase = true
+  precomposeunicode = true
+
+- precomposeunicode: paths are stored in the index in NFC form
+- ignorecase: paths that differ only in case refer to the same entry
+
+`PathPolicy::key` gives the comparison key used by add/status/checkout to
+match working tree paths against index entries.
+*/
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+use crate::helix_index::state::{get_core_value, set_core_value};
+
+const IGNORE_CASE_KEY: &str = "ignorecase";
+const PRECOMPOSE_UNICODE_KEY: &str = "precomposeunicode";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PathPolicy {
+    /// Filesystem treats paths that differ only in case as the same file
+    pub ignore_case: bool,
+    /// Filesystem treats NFC/NFD spellings as the same file; store paths as NFC
+    pub precompose_unicode: bool,
+}
+
+impl PathPolicy {
+    /// Probe the filesystem behavior by creating scratch files inside `dir`
+    pub fn detect(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let pid = std::process::id();
+
+        // Case sensitivity: create a mixed-case name, look it up lower-cased
+        let mixed = dir.join(format!("CaseProbe-{}", pid));
+        fs::write(&mixed, b"").context("Failed to create filesystem probe")?;
+        let ignore_case = dir.join(format!("caseprobe-{}", pid)).exists();
+        let _ = fs::remove_file(&mixed);
+
+        // Unicode normalization: create an NFC name, look it up in NFD form
+        let nfc = dir.join(format!("unicode-probe-\u{e9}-{}", pid));
+        fs::write(&nfc, b"").context("Failed to create filesystem probe")?;
+        let precompose_unicode = dir.join(format!("unicode-probe-e\u{301}-{}", pid)).exists();
+        let _ = fs::remove_file(&nfc);
+
+        Ok(Self {
+            ignore_case,
+            precompose_unicode,
+        })
+    }
+
+    /// Load the policy recorded at init. Repos created before it was recorded use the
+    /// byte-exact default.
+    pub fn load(repo_path: &Path) -> Self {
+        let flag = |key| {
+            get_core_value(repo_path, key)
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false)
+        };
+
+        Self {
+            ignore_case: flag(IGNORE_CASE_KEY),
+            precompose_unicode: flag(PRECOMPOSE_UNICODE_KEY),
+        }
+    }
+
+    pub fn save(&self, repo_path: &Path) -> Result<()> {
+        set_core_value(repo_path, IGNORE_CASE_KEY, &self.ignore_case.to_string())?;
+        set_core_value(
+            repo_path,
+            PRECOMPOSE_UNICODE_KEY,
+            &self.precompose_unicode.to_string(),
+        )
+    }
+
+    /// Detect and save the policy unless one was already recorded
+    pub fn init(repo_path: &Path) -> Result<Self> {
+        if get_core_value(repo_path, IGNORE_CASE_KEY).is_some() {
+            return Ok(Self::load(repo_path));
+        }
+
+        let policy = Self::detect(&repo_path.join(".helix"))?;
+        policy.save(repo_path)?;
+        Ok(policy)
+    }
+
+    /// True when paths are compared byte-for-byte
+    pub fn is_exact(&self) -> bool {
+        !self.ignore_case && !self.precompose_unicode
+    }
+
+    /// Path as it should be stored in the index
+    pub fn normalize(&self, path: &Path) -> PathBuf {
+        if !self.precompose_unicode {
+            return path.to_path_buf();
+        }
+
+        match path.to_str() {
+            Some(s) if !is_nfc(s) => PathBuf::from(s.nfc().collect::<String>()),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Key under which two paths refer to the same file on this filesystem
+    pub fn key(&self, path: &Path) -> PathBuf {
+        if self.is_exact() {
+            return path.to_path_buf();
+        }
+
+        let normalized = self.normalize(path);
+        if !self.ignore_case {
+            return normalized;
+        }
+
+        match normalized.to_str() {
+            Some(s) => PathBuf::from(s.to_lowercase()),
+            None => normalized,
+        }
+    }
+
+    /// Whether `a` and `b` name the same file on this filesystem
+    pub fn same_path(&self, a: &Path, b: &Path) -> bool {
+        a == b || (!self.is_exact() && self.key(a) == self.key(b))
+    }
+
+    /// Find distinct paths that map to the same file on this filesystem.
+    /// Returns pairs of (first seen, colliding path).
+    pub fn find_collisions<'a, I>(&self, paths: I) -> Vec<(PathBuf, PathBuf)>
+    where
+        I: IntoIterator<Item = &'a Path>,
+    {
+        if self.is_exact() {
+            return Vec::new();
+        }
+
+        let mut seen: HashMap<PathBuf, &Path> = HashMap::new();
+        let mut collisions = Vec::new();
+
+        for path in paths {
+            match seen.get(&self.key(path)) {
+                Some(existing) if *existing != path => {
+                    collisions.push((existing.to_path_buf(), path.to_path_buf()));
+                }
+                Some(_) => {}
+                None => {
+                    seen.insert(self.key(path), path);
+                }
+            }
+        }
+
+        collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const INSENSITIVE: PathPolicy = PathPolicy {
+        ignore_case: true,
+        precompose_unicode: true,
+    };
+
+    #[test]
+    fn test_exact_policy_keeps_paths() {
+        let policy = PathPolicy::default();
+        let nfd = Path::new("cafe\u{301}.txt");
+
+        assert_eq!(policy.normalize(nfd), nfd);
+        assert_eq!(policy.key(Path::new("README.md")), Path::new("README.md"));
+        assert!(!policy.same_path(Path::new("README.md"), Path::new("readme.md")));
+    }
+
+    #[test]
+    fn test_precompose_normalizes_to_nfc() {
+        let policy = PathPolicy {
+            ignore_case: false,
+            precompose_unicode: true,
+        };
+
+        let nfd = Path::new("docs/cafe\u{301}.txt");
+        let nfc = Path::new("docs/caf\u{e9}.txt");
+
+        assert_eq!(policy.normalize(nfd), nfc);
+        assert!(policy.same_path(nfd, nfc));
+        assert!(!policy.same_path(Path::new("A.txt"), Path::new("a.txt")));
+    }
+
+    #[test]
+    fn test_ignore_case_collisions() {
+        let paths = [
+            Path::new("README.md"),
+            Path::new("src/main.rs"),
+            Path::new("readme.md"),
+            Path::new("README.md"),
+        ];
+
+        let collisions = INSENSITIVE.find_collisions(paths);
+        assert_eq!(
+            collisions,
+            vec![(PathBuf::from("README.md"), PathBuf::from("readme.md"))]
+        );
+        assert!(PathPolicy::default().find_collisions(paths).is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+
+        assert_eq!(PathPolicy::load(repo), PathPolicy::default());
+
+        INSENSITIVE.save(repo)?;
+        assert_eq!(PathPolicy::load(repo), INSENSITIVE);
+
+        // init keeps the recorded policy
+        assert_eq!(PathPolicy::init(repo)?, INSENSITIVE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_leaves_no_probe_files() -> Result<()> {
+        let temp = TempDir::new()?;
+        PathPolicy::detect(temp.path())?;
+        assert_eq!(fs::read_dir(temp.path())?.count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,419 @@
+/*
+Low-level object commands for scripting and debugging.
+
+  helix cat-file <object>             pretty-print a commit, tree, blob or tag
+  helix cat-file -t|-s <object>       its type or size
+  helix hash-object [-w] <file>...    blob hash of a file, optionally storing it
+  helix ls-tree [-r] <rev> [path]     entries of a commit's tree, or a subtree
+
+An object is named by a full or abbreviated (4+ characters) hash of any type,
+a revision (HEAD, a branch, HEAD~2, ...), or <rev>:<path> for the blob or tree
+at a path in that revision. Everything reads FsObjectStore and TreeStore
+directly, without going through the index.
+
+hash-object applies the repository's line-ending policy, like `helix add`, so
+the hash matches what would be staged; --no-filters hashes the bytes as they
+are.
+*/
+use anyhow::{anyhow, bail, Context, Result};
+use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::Tag;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::diff_command::resolve_revision;
+use crate::helix_index::commit::Commit;
+use crate::helix_index::tree::{EntryType, Tree, TreeEntry, TreeStore};
+use crate::line_endings::LineEndings;
+use crate::sandbox_command::RepoContext;
+
+const OBJECT_TYPES: [ObjectType; 4] = [
+    ObjectType::Commit,
+    ObjectType::Tree,
+    ObjectType::Blob,
+    ObjectType::Tag,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CatFileMode {
+    #[default]
+    Pretty,
+    Type,
+    Size,
+}
+
+#[derive(Debug, Default)]
+pub struct HashObjectOptions {
+    /// Store the blob in the object store
+    pub write: bool,
+    /// Hash the file as-is, without line-ending normalization
+    pub no_filters: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LsTreeOptions {
+    pub recursive: bool,
+    pub name_only: bool,
+}
+
+/// One line of `helix ls-tree`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsTreeEntry {
+    pub mode: u32,
+    pub object_type: ObjectType,
+    pub oid: Hash,
+    pub path: PathBuf,
+}
+
+pub fn type_name(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blob",
+        ObjectType::Tree => "tree",
+        ObjectType::Commit => "commit",
+        ObjectType::Tag => "tag",
+    }
+}
+
+/// Resolve an object name to its type and hash, in the repository at `repo_path`
+pub fn find_object(repo_path: &Path, spec: &str) -> Result<(ObjectType, Hash)> {
+    let store = FsObjectStore::new(repo_path);
+
+    if let Some((rev, path)) = spec.split_once(':') {
+        let (ty, hash) = find_object(repo_path, if rev.is_empty() { "HEAD" } else { rev })?;
+        let tree = root_tree(&store, &ty, &hash)?;
+        return lookup_path(&store, tree, Path::new(path))
+            .with_context(|| format!("Path '{}' does not exist in '{}'", path, rev));
+    }
+
+    let is_hex = spec.len() >= 4 && spec.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        let prefix = spec.to_lowercase();
+        let mut matches = Vec::new();
+        for ty in OBJECT_TYPES {
+            if prefix.len() == 64 {
+                let hash = hex_to_hash(&prefix)?;
+                if store.has_object(&ty, &hash) {
+                    matches.push((ty, hash));
+                }
+                continue;
+            }
+            matches.extend(
+                store
+                    .list_object_hashes(&ty)?
+                    .into_iter()
+                    .filter(|hash| hash_to_hex(hash).starts_with(&prefix))
+                    .map(|hash| (ty.clone(), hash)),
+            );
+        }
+        match matches.len() {
+            1 => return Ok(matches.remove(0)),
+            0 => {}
+            n => bail!("Object name '{}' is ambiguous ({} matches)", spec, n),
+        }
+    }
+
+    let commit = resolve_revision(repo_path, spec)
+        .map_err(|_| anyhow!("Not a valid object name: '{}'", spec))?;
+    Ok((ObjectType::Commit, commit))
+}
+
+/// `helix cat-file`: the bytes to write to stdout
+pub fn cat_file(repo_path: &Path, spec: &str, mode: CatFileMode) -> Result<Vec<u8>> {
+    let repo_path = &RepoContext::detect(repo_path)?.repo_root;
+    let (ty, hash) = find_object(repo_path, spec)?;
+    let store = FsObjectStore::new(repo_path);
+    let raw = store
+        .read_object(&ty, &hash)
+        .with_context(|| format!("Failed to read {} {}", type_name(&ty), hash_to_hex(&hash)))?;
+
+    match mode {
+        CatFileMode::Type => Ok(format!("{}\n", type_name(&ty)).into_bytes()),
+        CatFileMode::Size => Ok(format!("{}\n", raw.len()).into_bytes()),
+        CatFileMode::Pretty => pretty(&ty, &raw),
+    }
+}
+
+fn pretty(ty: &ObjectType, raw: &[u8]) -> Result<Vec<u8>> {
+    let mut out = String::new();
+    match ty {
+        ObjectType::Blob => return Ok(raw.to_vec()),
+        ObjectType::Tree => {
+            let tree = Tree::from_bytes(raw)?;
+            for entry in &tree.entries {
+                let _ = writeln!(
+                    out,
+                    "{:06o} {} {}\t{}",
+                    entry.mode,
+                    type_name(&entry_object_type(entry)),
+                    hash_to_hex(&entry.oid),
+                    entry.name
+                );
+            }
+        }
+        ObjectType::Commit => {
+            let commit = Commit::from_bytes(raw)?;
+            let _ = writeln!(out, "tree {}", hash_to_hex(&commit.tree_hash));
+            for parent in &commit.parents {
+                let _ = writeln!(out, "parent {}", hash_to_hex(parent));
+            }
+            let _ = writeln!(out, "author {} {}", commit.author, commit.author_time);
+            let _ = writeln!(out, "committed {}", commit.commit_time);
+            for (key, value) in &commit.headers {
+                let _ = writeln!(out, "meta {key}={value}");
+            }
+            let _ = write!(out, "\n{}\n", commit.message);
+        }
+        ObjectType::Tag => {
+            let tag = Tag::from_bytes(raw)?;
+            let _ = writeln!(out, "object {}", hash_to_hex(&tag.target));
+            let _ = writeln!(out, "type commit");
+            let _ = writeln!(out, "tag {}", tag.name);
+            let _ = writeln!(out, "tagger {} {}", tag.tagger, tag.tag_time);
+            let _ = write!(out, "\n{}\n", tag.message);
+        }
+    }
+    Ok(out.into_bytes())
+}
+
+/// `helix hash-object`
+pub fn hash_object(repo_path: &Path, file: &Path, options: &HashObjectOptions) -> Result<Hash> {
+    let content = fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+
+    let (hash, content) = if options.no_filters {
+        (hash_bytes(&content), content)
+    } else {
+        let root = RepoContext::detect(repo_path)
+            .map(|c| c.repo_root)
+            .unwrap_or_else(|_| repo_path.to_path_buf());
+        let normalized = LineEndings::load(&root).normalize(&content).into_owned();
+        (hash_bytes(&normalized), normalized)
+    };
+
+    if options.write {
+        let context = RepoContext::detect(repo_path)?;
+        FsObjectStore::new(&context.repo_root).write_object(&ObjectType::Blob, &content)?;
+    }
+
+    Ok(hash)
+}
+
+/// `helix ls-tree`: entries of `rev`'s tree, or of the subtree at `path`
+pub fn ls_tree(
+    repo_path: &Path,
+    rev: &str,
+    path: Option<&Path>,
+    options: &LsTreeOptions,
+) -> Result<Vec<LsTreeEntry>> {
+    let repo_path = &RepoContext::detect(repo_path)?.repo_root;
+    let store = FsObjectStore::new(repo_path);
+    let (ty, hash) = find_object(repo_path, rev)?;
+    let root = root_tree(&store, &ty, &hash)?;
+
+    let (base, tree_hash) = match path.filter(|p| !p.as_os_str().is_empty()) {
+        Some(path) => match lookup_path(&store, root, path)? {
+            (ObjectType::Tree, hash) => (path.to_path_buf(), hash),
+            // A file names just itself
+            _ => {
+                let parent = path.parent().unwrap_or(Path::new(""));
+                let (_, parent_hash) = lookup_path(&store, root, parent)?;
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string());
+                let entries = list_tree(&store, &parent_hash, parent, false)?;
+                return Ok(entries
+                    .into_iter()
+                    .filter(|e| e.path.file_name().map(|n| n.to_string_lossy().to_string()) == name)
+                    .collect());
+            }
+        },
+        None => (PathBuf::new(), root),
+    };
+
+    list_tree(&store, &tree_hash, &base, options.recursive)
+}
+
+pub fn print_ls_tree(entries: &[LsTreeEntry], options: &LsTreeOptions) {
+    for entry in entries {
+        if options.name_only {
+            println!("{}", entry.path.display());
+        } else {
+            println!(
+                "{:06o} {} {}\t{}",
+                entry.mode,
+                type_name(&entry.object_type),
+                hash_to_hex(&entry.oid),
+                entry.path.display()
+            );
+        }
+    }
+}
+
+fn list_tree(
+    store: &FsObjectStore,
+    tree_hash: &Hash,
+    base: &Path,
+    recursive: bool,
+) -> Result<Vec<LsTreeEntry>> {
+    let tree = TreeStore::new(store.clone()).read(tree_hash)?;
+    let mut out = Vec::new();
+    for entry in &tree.entries {
+        let path = base.join(&entry.name);
+        let object_type = entry_object_type(entry);
+        if recursive && object_type == ObjectType::Tree {
+            out.extend(list_tree(store, &entry.oid, &path, true)?);
+        } else {
+            out.push(LsTreeEntry {
+                mode: entry.mode,
+                object_type,
+                oid: entry.oid,
+                path,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// The root tree of a commit, tag or tree
+fn root_tree(store: &FsObjectStore, ty: &ObjectType, hash: &Hash) -> Result<Hash> {
+    match ty {
+        ObjectType::Tree => Ok(*hash),
+        ObjectType::Commit => Ok(Commit::from_bytes(&store.read_object(ty, hash)?)?.tree_hash),
+        ObjectType::Tag => {
+            let tag = Tag::from_bytes(&store.read_object(ty, hash)?)?;
+            root_tree(store, &ObjectType::Commit, &tag.target)
+        }
+        ObjectType::Blob => bail!("{} is a blob, not a tree-ish", hash_to_hex(hash)),
+    }
+}
+
+/// Walk `path` down from the tree `root`
+pub fn lookup_path(store: &FsObjectStore, root: Hash, path: &Path) -> Result<(ObjectType, Hash)> {
+    let trees = TreeStore::new(store.clone());
+    let mut current = (ObjectType::Tree, root);
+
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            Component::CurDir => continue,
+            _ => bail!("Unsupported path component in '{}'", path.display()),
+        };
+        if current.0 != ObjectType::Tree {
+            bail!("'{}' is not a directory", path.display());
+        }
+        let tree = trees.read(&current.1)?;
+        let entry = tree
+            .entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| anyhow!("'{}' not found", path.display()))?;
+        current = (entry_object_type(entry), entry.oid);
+    }
+
+    Ok(current)
+}
+
+fn entry_object_type(entry: &TreeEntry) -> ObjectType {
+    match entry.entry_type {
+        EntryType::Tree => ObjectType::Tree,
+        _ => ObjectType::Blob,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::commit_command::{commit, CommitOptions};
+    use crate::helix_index::api::HelixIndexData;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plumbing_commands() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        fs::create_dir_all(repo.join("src"))?;
+        fs::write(repo.join("README"), "hello\n")?;
+        fs::write(repo.join("src/lib.rs"), "fn main() {}\n")?;
+        let paths = [PathBuf::from("README"), PathBuf::from("src/lib.rs")];
+        stage_paths(repo, &paths)?;
+        let head = commit(
+            repo,
+            CommitOptions {
+                message: "first".into(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )?;
+
+        // hash-object agrees with what add staged, and -w stores it
+        let index = HelixIndexData::load_or_rebuild(repo)?;
+        let staged = index
+            .entries()
+            .iter()
+            .find(|e| e.path == paths[1])
+            .unwrap()
+            .oid;
+        let options = HashObjectOptions::default();
+        assert_eq!(
+            hash_object(repo, &repo.join("src/lib.rs"), &options)?,
+            staged
+        );
+        fs::write(repo.join("new.txt"), "new\n")?;
+        let write = HashObjectOptions {
+            write: true,
+            ..Default::default()
+        };
+        let new = hash_object(repo, &repo.join("new.txt"), &write)?;
+        assert_eq!(
+            cat_file(repo, &hash_to_hex(&new)[..10], CatFileMode::Pretty)?,
+            b"new\n"
+        );
+
+        // cat-file by revision, hash prefix and rev:path
+        assert_eq!(cat_file(repo, "HEAD", CatFileMode::Type)?, b"commit\n");
+        let pretty = String::from_utf8(cat_file(repo, &hash_to_hex(&head), CatFileMode::Pretty)?)?;
+        assert!(pretty.starts_with("tree "));
+        assert!(pretty.contains("author Test User <test@example.com>"));
+        assert!(pretty.ends_with("\nfirst\n"));
+        assert_eq!(
+            cat_file(repo, "HEAD:src/lib.rs", CatFileMode::Pretty)?,
+            b"fn main() {}\n"
+        );
+        assert_eq!(cat_file(repo, "HEAD:src", CatFileMode::Type)?, b"tree\n");
+        assert!(cat_file(repo, "HEAD:missing", CatFileMode::Pretty).is_err());
+
+        // ls-tree at the root, recursively, and for a subtree or single file
+        let names = |entries: Vec<LsTreeEntry>| -> Vec<String> {
+            entries
+                .iter()
+                .map(|e| e.path.display().to_string())
+                .collect()
+        };
+        let options = LsTreeOptions::default();
+        assert_eq!(
+            names(ls_tree(repo, "HEAD", None, &options)?),
+            ["README", "src"]
+        );
+        let recursive = LsTreeOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(ls_tree(repo, "HEAD", None, &recursive)?),
+            ["README", "src/lib.rs"]
+        );
+        let src = ls_tree(repo, "HEAD", Some(Path::new("src")), &options)?;
+        assert_eq!(names(src.clone()), ["src/lib.rs"]);
+        assert_eq!(src[0].oid, staged);
+        assert_eq!(src[0].mode, 0o100644);
+        assert_eq!(
+            names(ls_tree(repo, "HEAD", Some(Path::new("README")), &options)?),
+            ["README"]
+        );
+
+        Ok(())
+    }
+}
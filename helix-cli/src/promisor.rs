@@ -0,0 +1,365 @@
+/*
+Partial clones: blobs a remote has promised.
+
+`helix clone --filter path:src/` (or `helix pull --filter`) asks the remote for
+every commit and tree, but only for the blobs under the given paths. Two files in
+.helix remember that:
+
+  filter    the filter spec, e.g. "path:src", sent again with every later pull
+  promised  one hex blob hash per line: blobs the trees reference but the remote
+            left out
+
+  promisor  the remote (name or URL) the filtered pull came from
+
+Checkout leaves the files whose blob is promised out of the working tree, and
+their index entries are marked ASSUME_UNCHANGED so status doesn't report them as
+deleted. A promised blob that later turns up in the store is used like any other.
+
+When checkout (of a path the filter now covers), diff, blame or restore needs a
+promised blob's content, `fetch_promised` asks the promisor remote for it with
+the FetchObject RPC, in batches. Blobs the remote turns out not to have are
+remembered in .helix/promised-missing for a day, so they fail fast instead of
+costing a round trip every time. With --offline nothing is fetched: a command
+that needs a promised blob fails straight away.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::filter::PathFilter;
+use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::credential;
+use crate::push_command::resolve_remote;
+use crate::transport;
+
+pub const FILTER_FILE: &str = "filter";
+pub const PROMISED_FILE: &str = "promised";
+pub const REMOTE_FILE: &str = "promisor";
+pub const MISSING_FILE: &str = "promised-missing";
+
+/// Blobs asked for in one FetchObject request
+const FETCH_BATCH: usize = 256;
+
+/// How long a blob the remote didn't have is not asked for again
+const MISSING_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Set by the global --offline flag
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Never contact the promisor remote; commands that need a promised blob fail instead
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+fn helix_file(repo_path: &Path, name: &str) -> PathBuf {
+    repo_path.join(".helix").join(name)
+}
+
+/// The filter this repo was cloned or last pulled with, if any
+pub fn read_filter(repo_path: &Path) -> Result<Option<PathFilter>> {
+    let path = helix_file(repo_path, FILTER_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let spec = fs::read_to_string(&path).context("Failed to read .helix/filter")?;
+    PathFilter::parse(spec.trim())
+        .map(Some)
+        .context("Invalid .helix/filter")
+}
+
+pub fn write_filter(repo_path: &Path, filter: &PathFilter) -> Result<()> {
+    fs::write(helix_file(repo_path, FILTER_FILE), format!("{filter}\n"))
+        .context("Failed to write .helix/filter")
+}
+
+/// Remember which remote promised the blobs, for fetching them later
+pub fn write_remote(repo_path: &Path, remote: &str) -> Result<()> {
+    fs::write(helix_file(repo_path, REMOTE_FILE), format!("{remote}\n"))
+        .context("Failed to write .helix/promisor")
+}
+
+fn read_remote(repo_path: &Path) -> Option<String> {
+    let remote = fs::read_to_string(helix_file(repo_path, REMOTE_FILE)).ok()?;
+    Some(remote.trim().to_string()).filter(|r| !r.is_empty())
+}
+
+/// Record blobs the remote left out of a filtered pull
+pub fn add_promised(repo_path: &Path, blobs: &[Hash]) -> Result<()> {
+    let known = Promised::load(repo_path);
+    let mut new: Vec<&Hash> = blobs.iter().filter(|b| !known.contains(b)).collect();
+    if new.is_empty() {
+        return Ok(());
+    }
+    new.sort();
+    new.dedup();
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(helix_file(repo_path, PROMISED_FILE))
+        .context("Failed to open .helix/promised")?;
+    let lines: String = new
+        .iter()
+        .map(|b| format!("{}\n", hash_to_hex(b)))
+        .collect();
+    file.write_all(lines.as_bytes())
+        .context("Failed to write .helix/promised")
+}
+
+/// The blobs recorded as promised; empty for a full clone
+#[derive(Debug, Clone, Default)]
+pub struct Promised {
+    blobs: HashSet<Hash>,
+}
+
+impl Promised {
+    /// Unreadable lines are skipped, so a damaged file only costs the blobs it names
+    pub fn load(repo_path: &Path) -> Self {
+        let blobs = fs::read_to_string(helix_file(repo_path, PROMISED_FILE))
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| hex_to_hash(line.trim()).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { blobs }
+    }
+
+    pub fn contains(&self, blob: &Hash) -> bool {
+        self.blobs.contains(blob)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+}
+
+/// Fetch the promised blobs among `blobs` that aren't in the local store yet, and return
+/// how many were fetched. Blobs that aren't promised are left alone, so callers still report
+/// those as missing the usual way.
+pub fn fetch_promised(repo_path: &Path, blobs: impl IntoIterator<Item = Hash>) -> Result<usize> {
+    let promised = Promised::load(repo_path);
+    if promised.is_empty() {
+        return Ok(0);
+    }
+    let store = FsObjectStore::new(repo_path);
+    let mut wanted: Vec<Hash> = blobs
+        .into_iter()
+        .filter(|b| promised.contains(b) && !store.has_object(&ObjectType::Blob, b))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if wanted.is_empty() {
+        return Ok(0);
+    }
+    wanted.sort();
+
+    if OFFLINE.load(Ordering::Relaxed) {
+        bail!(
+            "{} needed blob(s) are only on the remote (partial clone), e.g. {}; run without --offline to fetch them",
+            wanted.len(),
+            hash_to_hex(&wanted[0])
+        );
+    }
+
+    let mut missing = MissingCache::load(repo_path);
+    if let Some(known) = wanted.iter().find(|b| missing.is_recent(b)) {
+        bail!(
+            "Blob {} was promised by the remote, but it didn't have it when last asked",
+            hash_to_hex(known)
+        );
+    }
+
+    let Some(remote) = read_remote(repo_path) else {
+        bail!("Blobs are promised by a remote, but .helix/promisor doesn't name it");
+    };
+    let remote_config = resolve_remote(repo_path, &remote)?;
+    let remote_url = remote_config.url.clone();
+    let token = credential::token_for(repo_path, &remote_url)?;
+    let repo_name = remote_config.repo_name(repo_path);
+
+    let mut fetched = 0;
+    for batch in wanted.chunks(FETCH_BATCH) {
+        let request: Vec<(ObjectType, Hash)> =
+            batch.iter().map(|b| (ObjectType::Blob, *b)).collect();
+        // A fresh connection per batch: each runs on its own runtime
+        let received = block_on(async {
+            transport::connect(&remote_url, token.as_deref(), &repo_name)
+                .fetch_objects(&request)
+                .await
+        })??;
+        for obj in received {
+            if obj.object_type != ObjectType::Blob || !batch.contains(&obj.hash) {
+                continue;
+            }
+            let raw =
+                zstd::decode_all(&obj.data[..]).context("Failed to decompress fetched blob")?;
+            if hash_bytes(&raw) != obj.hash {
+                bail!("Remote sent a bad copy of blob {}", hash_to_hex(&obj.hash));
+            }
+            store.write_object_compressed_with_hash(&obj.object_type, &obj.hash, &obj.data)?;
+            fetched += 1;
+        }
+    }
+
+    let absent: Vec<Hash> = wanted
+        .into_iter()
+        .filter(|b| !store.has_object(&ObjectType::Blob, b))
+        .collect();
+    if let Some(first) = absent.first() {
+        missing.record(&absent);
+        missing.save(repo_path)?;
+        bail!(
+            "Remote '{remote}' doesn't have {} promised blob(s), e.g. {}",
+            absent.len(),
+            hash_to_hex(first)
+        );
+    }
+    Ok(fetched)
+}
+
+/// Run `future` to completion from sync code, whether or not a runtime is already running on
+/// this thread
+fn block_on<F: std::future::Future + Send>(future: F) -> Result<F::Output>
+where
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| -> Result<F::Output> {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                Ok(runtime.block_on(future))
+            })
+            .join()
+            .map_err(|_| anyhow::anyhow!("Blob fetch thread panicked"))?
+    })
+}
+
+/// Blobs the remote didn't have, with when it was asked (seconds since the epoch)
+struct MissingCache {
+    asked: HashMap<Hash, u64>,
+}
+
+impl MissingCache {
+    fn load(repo_path: &Path) -> Self {
+        let asked = fs::read_to_string(helix_file(repo_path, MISSING_FILE))
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| {
+                        let (hex, secs) = line.split_once(' ')?;
+                        Some((hex_to_hash(hex).ok()?, secs.parse().ok()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { asked }
+    }
+
+    fn is_recent(&self, blob: &Hash) -> bool {
+        self.asked
+            .get(blob)
+            .is_some_and(|&asked| now_secs().saturating_sub(asked) < MISSING_TTL_SECS)
+    }
+
+    fn record(&mut self, blobs: &[Hash]) {
+        let now = now_secs();
+        self.asked
+            .retain(|_, asked| now.saturating_sub(*asked) < MISSING_TTL_SECS);
+        for blob in blobs {
+            self.asked.insert(*blob, now);
+        }
+    }
+
+    fn save(&self, repo_path: &Path) -> Result<()> {
+        let lines: String = self
+            .asked
+            .iter()
+            .map(|(blob, asked)| format!("{} {asked}\n", hash_to_hex(blob)))
+            .collect();
+        fs::write(helix_file(repo_path, MISSING_FILE), lines)
+            .context("Failed to write .helix/promised-missing")
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_filter_and_promised_blobs_persist() -> Result<()> {
+        let temp = TempDir::new()?;
+        fs::create_dir_all(temp.path().join(".helix"))?;
+
+        assert_eq!(read_filter(temp.path())?, None);
+        let filter = PathFilter::parse("path:src/")?;
+        write_filter(temp.path(), &filter)?;
+        assert_eq!(read_filter(temp.path())?, Some(filter));
+
+        assert!(Promised::load(temp.path()).is_empty());
+        add_promised(temp.path(), &[[1u8; 32], [2u8; 32]])?;
+        add_promised(temp.path(), &[[2u8; 32], [3u8; 32]])?;
+        let promised = Promised::load(temp.path());
+        assert!(promised.contains(&[1u8; 32]) && promised.contains(&[3u8; 32]));
+        assert!(!promised.contains(&[4u8; 32]));
+
+        // Each blob is written once
+        let lines = fs::read_to_string(temp.path().join(".helix/promised"))?;
+        assert_eq!(lines.lines().count(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_promised_blobs_from_remote() -> Result<()> {
+        let temp = TempDir::new()?;
+        let remote = temp.path().join("remote");
+        crate::init_command::init_bare_repo(&remote)?;
+        let blob = FsObjectStore::new(&remote).write_object(&ObjectType::Blob, b"big file")?;
+        let gone = hash_bytes(b"never pushed");
+
+        let repo = temp.path().join("repo");
+        fs::create_dir_all(repo.join(".helix"))?;
+        add_promised(&repo, &[blob, gone])?;
+        write_remote(&repo, &format!("file://{}", remote.display()))?;
+
+        // Blobs that aren't promised are left to the caller
+        assert_eq!(fetch_promised(&repo, [hash_bytes(b"other")])?, 0);
+
+        set_offline(true);
+        let offline = fetch_promised(&repo, [blob]);
+        set_offline(false);
+        assert!(offline.is_err());
+
+        assert_eq!(fetch_promised(&repo, [blob, blob])?, 1);
+        assert_eq!(
+            FsObjectStore::new(&repo).read_object(&ObjectType::Blob, &blob)?,
+            b"big file"
+        );
+        assert_eq!(fetch_promised(&repo, [blob])?, 0);
+
+        // The remote lacks `gone`; that's remembered, so the next ask fails without a request
+        assert!(fetch_promised(&repo, [gone]).is_err());
+        assert!(MissingCache::load(&repo).is_recent(&gone));
+        fs::remove_dir_all(&remote)?;
+        let err = fetch_promised(&repo, [gone]).unwrap_err();
+        assert!(err.to_string().contains("when last asked"), "{err}");
+        Ok(())
+    }
+}
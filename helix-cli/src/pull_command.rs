@@ -1,17 +1,77 @@
-use anyhow::{bail, Context, Result};
-use helix_protocol::commit::{read_remote_tracking, write_remote_tracking};
-use helix_protocol::hash::hash_to_hex;
-use helix_protocol::message::{read_message, write_message, Hello, PullRequest, RpcMessage};
+use anyhow::{anyhow, bail, Context, Result};
+use helix_client::RemoteError;
+use helix_protocol::commit::{
+    is_ancestor, merge_base, read_local_ref, read_remote_tracking, write_remote_tracking,
+};
+use helix_protocol::filter::PathFilter;
+use helix_protocol::hash::{hash_to_hex, Hash};
+use helix_protocol::message::{ObjectType, RpcMessage};
 use helix_protocol::storage::FsObjectStore;
+use helix_protocol::validate::IncomingObjects;
 use rayon::prelude::*;
-use std::{fs, io::Cursor, path::Path};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::{fs, path::Path};
 
-use crate::checkout::checkout_tree;
+use crate::author::resolve_author;
+use crate::branch_command::get_current_branch;
+use crate::checkout::{checkout_tree_to_path, local_changes_in_the_way, CheckoutOptions};
+use crate::credential;
+use crate::helix_index::commit::{Commit, CommitStore};
+use crate::merge_command::{analyze_merge, build_merged_tree, execute_merge};
+use crate::merge_tui::app::App;
+use crate::promisor;
 use crate::push_command::resolve_remote_and_ref;
+use crate::remote_refs;
+use crate::sandbox_command::update_index_from_commit;
+use crate::say;
+use crate::transport;
 
 pub struct PullOptions {
     pub verbose: bool,
     pub dry_run: bool,
+    pub no_compress: bool,
+    /// Refuse to merge or rebase; only fast-forward
+    pub ff_only: bool,
+    /// Replay local commits on top of the remote instead of merging
+    pub rebase: bool,
+    /// Delete remote-tracking refs for branches the server no longer has
+    pub prune: bool,
+    /// Fetch only the blobs under these paths (and remember the filter for later pulls)
+    pub filter: Option<PathFilter>,
+    /// Accept objects that reference trees or parents neither sent nor stored locally,
+    /// to recover a repository that has already lost objects
+    pub allow_missing_objects: bool,
+    /// Overwrite uncommitted changes to files the pull updates
+    pub force: bool,
+}
+
+/// What a pull did, as `helix pull --json` prints it
+#[derive(Debug, Clone, Serialize)]
+pub struct PullReport {
+    pub remote: String,
+    pub branch: String,
+    /// Objects fetched
+    pub objects: usize,
+    pub outcome: PullOutcome,
+    /// The local branch's head afterwards
+    pub head: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullOutcome {
+    DryRun,
+    NoRemoteBranch,
+    UpToDate,
+    /// There was no local branch, so the remote one was checked out
+    CheckedOut,
+    FastForward,
+    Merged,
+    Rebased,
+    /// The branch isn't checked out, so only its remote-tracking ref moved
+    Fetched,
 }
 
 impl Default for PullOptions {
@@ -19,6 +79,13 @@ impl Default for PullOptions {
         Self {
             verbose: false,
             dry_run: false,
+            no_compress: false,
+            ff_only: false,
+            rebase: false,
+            prune: false,
+            filter: None,
+            allow_missing_objects: false,
+            force: false,
         }
     }
 }
@@ -28,17 +95,22 @@ pub async fn pull(
     remote_name: &str,
     branch: &str,
     options: PullOptions,
-) -> Result<()> {
+) -> Result<PullReport> {
     if !repo_path.join(".helix").exists() {
         bail!("Not a Helix repo (no .helix directory)");
     }
 
-    let (remote_url, ref_name) = resolve_remote_and_ref(repo_path, remote_name, branch)?;
-    let last_known_remote = read_remote_tracking(repo_path, remote_name, branch).ok();
+    let (remote_config, ref_name) = resolve_remote_and_ref(repo_path, remote_name, branch)?;
+    let remote_url = remote_config.url.clone();
+    let token = credential::token_for_remote(repo_path, &remote_config, &remote_url)?;
+    let tracked = !transport::is_url(remote_name);
+    let last_known_remote = read_remote_tracking(repo_path, remote_name, branch)
+        .ok()
+        .filter(|_| tracked);
 
     if options.verbose {
-        println!("Pulling {ref_name} from {remote_name} at {remote_url}");
-        println!(
+        say!("Pulling {ref_name} from {remote_name} at {remote_url}");
+        say!(
             "  last_known_remote = {}",
             last_known_remote
                 .as_ref()
@@ -47,102 +119,137 @@ pub async fn pull(
         );
     }
 
-    if options.dry_run {
-        println!("(dry run) Would pull from {}/{}", remote_name, branch);
-        return Ok(());
+    if options.prune && !tracked {
+        bail!("--prune needs a remote named in helix.toml, not a URL or path");
+    }
+    if options.prune {
+        let pruned = remote_refs::prune(
+            repo_path,
+            remote_name,
+            &remote_url,
+            &remote_config.repo_name(repo_path),
+            token.as_deref(),
+            options.dry_run,
+        )
+        .await?;
+        let verb = if options.dry_run {
+            "Would prune"
+        } else {
+            "Pruned"
+        };
+        for stale in pruned {
+            say!("{verb} {remote_name}/{stale}");
+        }
     }
 
-    // Build pull request
-    let mut buf = Vec::new();
-
-    write_message(
-        &mut buf,
-        &RpcMessage::Hello(Hello {
-            client_version: "helix-cli".into(),
-        }),
-    )?;
-
-    write_message(
-        &mut buf,
-        &RpcMessage::PullRequest(PullRequest {
-            repo: repo_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned(),
-            ref_name: ref_name.clone(),
-            last_known_remote,
-        }),
-    )?;
-
-    // Send request
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(format!("{remote_url}/rpc/pull"))
-        .body(buf)
-        .send()
-        .await
-        .with_context(|| {
-            format!("Remote server at {remote_url} is unreachable. Is the Helix server running?")
-        })?;
-
-    let status = resp.status();
-    if !status.is_success() {
-        bail!("Server returned error: {}", status);
+    if options.dry_run {
+        say!("(dry run) Would pull from {}/{}", remote_name, branch);
+        return Ok(PullReport {
+            remote: remote_name.to_string(),
+            branch: branch.to_string(),
+            objects: 0,
+            outcome: PullOutcome::DryRun,
+            head: read_local_ref(repo_path, &ref_name)
+                .ok()
+                .map(|h| hash_to_hex(&h)),
+        });
     }
 
-    let bytes = resp.bytes().await?;
-    let mut cursor = Cursor::new(bytes.to_vec());
+    // A repo cloned with a filter keeps using it
+    let filter = match &options.filter {
+        Some(filter) => Some(filter.clone()),
+        None => promisor::read_filter(repo_path)?,
+    };
+
+    tracing::debug!(
+        remote = remote_name,
+        url = %remote_url,
+        ref_name = %ref_name,
+        last_known_remote = ?last_known_remote.as_ref().map(hash_to_hex),
+        filtered = filter.is_some(),
+        "pull"
+    );
+    let repo_name = remote_config.repo_name(repo_path);
+    let remote =
+        transport::connect_remote(&remote_config, &remote_url, token.as_deref(), &repo_name)?
+            .with_compression(!options.no_compress && remote_config.compression.unwrap_or(true));
+    let mut response = remote
+        .pull(&ref_name, last_known_remote, filter.as_ref())
+        .await?;
 
-    // Collect objects for parallel writes
+    // Collect objects for parallel writes. Nothing touches the store until the whole
+    // stream has been validated, so a bad object can't leave a torn local state.
     let store = FsObjectStore::new(repo_path);
+    let mut incoming = IncomingObjects::new(&store);
+    if filter.is_some() {
+        incoming = incoming.allow_promised_blobs();
+    }
+    if options.allow_missing_objects {
+        incoming = incoming.allow_missing_references();
+    }
     let mut objects_to_write = Vec::new();
 
     loop {
-        match read_message(&mut cursor) {
+        match response.next().await {
             Ok(RpcMessage::PullObject(obj)) => {
-                // Data is zstd-compressed from server
-                // Decompress to verify hash
-                let raw = zstd::decode_all(&obj.data[..])
-                    .context("Failed to decompress object for hash verification")?;
-
-                let computed = blake3::hash(&raw);
-                if computed.as_bytes() != &obj.hash {
-                    bail!(
-                        "Hash mismatch: expected {}, got {}",
-                        hash_to_hex(&obj.hash),
-                        hex::encode(computed.as_bytes())
-                    );
-                }
+                // Objects arrive dependencies-first: check hash and references on arrival
+                incoming
+                    .accept(&obj.object_type, &obj.hash, &obj.data)
+                    .context("Rejected object from remote; nothing was written")?;
 
                 objects_to_write.push(obj);
 
                 if options.verbose && objects_to_write.len() % 100 == 0 {
-                    println!("  Received {} objects...", objects_to_write.len());
+                    say!("  Received {} objects...", objects_to_write.len());
                 }
             }
             Ok(RpcMessage::PullDone) => {
                 if options.verbose {
-                    println!("Received PullDone");
+                    say!("Received PullDone");
                 }
                 break;
             }
             Ok(RpcMessage::PullAck(ack)) => {
+                tracing::debug!(?ack, "early PullAck");
                 if ack.ref_not_found {
-                    println!(
+                    say!(
                         "Remote branch '{}' does not exist on '{}'.",
-                        branch, remote_name
+                        branch,
+                        remote_name
                     );
-                    return Ok(());
+                    return Ok(PullReport {
+                        remote: remote_name.to_string(),
+                        branch: branch.to_string(),
+                        objects: 0,
+                        outcome: PullOutcome::NoRemoteBranch,
+                        head: read_local_ref(repo_path, &ref_name)
+                            .ok()
+                            .map(|h| hash_to_hex(&h)),
+                    });
                 }
                 if ack.up_to_date {
-                    println!("Already up to date.");
-                    return Ok(());
+                    // Nothing new on the remote, but an earlier --ff-only pull may have
+                    // left the local branch behind the remote-tracking ref
+                    let (outcome, head) = integrate_remote(
+                        repo_path,
+                        &ref_name,
+                        remote_name,
+                        branch,
+                        ack.new_remote_head,
+                        &options,
+                    )?;
+                    return Ok(PullReport {
+                        remote: remote_name.to_string(),
+                        branch: branch.to_string(),
+                        objects: 0,
+                        outcome,
+                        head: head.map(|h| hash_to_hex(&h)),
+                    });
                 }
                 bail!("Unexpected PullAck before PullDone");
             }
             Ok(RpcMessage::Error(err)) => {
-                bail!("Server error: {} - {}", err.code, err.message);
+                return Err(RemoteError::from(err).into());
             }
             Ok(other) => {
                 bail!("Unexpected message: {:?}", other);
@@ -153,27 +260,11 @@ pub async fn pull(
         }
     }
 
-    // Write objects in parallel (store compressed bytes directly)
-    let object_count = objects_to_write.len();
-    if options.verbose {
-        println!("Writing {} objects to store...", object_count);
-    }
-
-    objects_to_write
-        .par_iter()
-        .try_for_each(|obj| -> Result<()> {
-            if !store.has_object(&obj.object_type, &obj.hash) {
-                // Write compressed bytes directly - no recompression needed
-                store.write_object_compressed_with_hash(&obj.object_type, &obj.hash, &obj.data)?;
-            }
-            Ok(())
-        })?;
-
     // Read final PullAck
-    let new_remote_head = match read_message(&mut cursor) {
+    let new_remote_head = match response.next().await {
         Ok(RpcMessage::PullAck(ack)) => {
             if options.verbose {
-                println!(
+                say!(
                     "PullAck: {} objects, new head: {}",
                     ack.sent_objects,
                     hash_to_hex(&ack.new_remote_head)
@@ -182,7 +273,7 @@ pub async fn pull(
             ack.new_remote_head
         }
         Ok(RpcMessage::Error(err)) => {
-            bail!("Server error: {} - {}", err.code, err.message);
+            return Err(RemoteError::from(err).into());
         }
         Ok(other) => {
             bail!("Expected PullAck, got {:?}", other);
@@ -192,30 +283,504 @@ pub async fn pull(
         }
     };
 
-    write_remote_tracking(repo_path, remote_name, branch, new_remote_head)?;
+    if !incoming.has(&ObjectType::Commit, &new_remote_head) {
+        bail!(
+            "Remote head {} was not received; nothing was written",
+            hash_to_hex(&new_remote_head)
+        );
+    }
 
-    let local_ref_path = repo_path.join(".helix").join(&ref_name);
+    // Write objects in parallel (store compressed bytes directly)
+    let object_count = objects_to_write.len();
+    tracing::debug!(
+        objects = object_count,
+        new_remote_head = %hash_to_hex(&new_remote_head),
+        "pull stream complete"
+    );
+    if options.verbose {
+        say!("Writing {} objects to store...", object_count);
+    }
+
+    objects_to_write
+        .par_iter()
+        .try_for_each(|obj| -> Result<()> {
+            if !store.has_object(&obj.object_type, &obj.hash) {
+                // Write compressed bytes directly - no recompression needed
+                store.write_object_compressed_with_hash(&obj.object_type, &obj.hash, &obj.data)?;
+            }
+            Ok(())
+        })?;
+
+    if let Some(filter) = &options.filter {
+        promisor::write_filter(repo_path, filter)?;
+    }
+    let promised = incoming.promised();
+    if !promised.is_empty() {
+        if options.verbose {
+            say!("{} blobs outside the filter are promised", promised.len());
+        }
+        promisor::add_promised(repo_path, &promised)?;
+        // A URL, not a path relative to wherever pull was run
+        let promisor_remote = if tracked { remote_name } else { &remote_url };
+        promisor::write_remote(repo_path, promisor_remote)?;
+    }
+
+    let missing = incoming.missing();
+    if !missing.is_empty() {
+        eprintln!(
+            "Warning: {} referenced objects are missing; history behind them is incomplete",
+            missing.len()
+        );
+    }
+
+    // Refs move only after every object is verified and stored
+    if tracked {
+        write_remote_tracking(repo_path, remote_name, branch, new_remote_head)?;
+    }
+
+    say!(
+        "Pulled {} objects from {}/{}",
+        object_count,
+        remote_name,
+        branch
+    );
+
+    let (outcome, head) = integrate_remote(
+        repo_path,
+        &ref_name,
+        remote_name,
+        branch,
+        new_remote_head,
+        &options,
+    )?;
+    Ok(PullReport {
+        remote: remote_name.to_string(),
+        branch: branch.to_string(),
+        objects: object_count,
+        outcome,
+        head: head.map(|h| hash_to_hex(&h)),
+    })
+}
+
+/// Bring the checked-out branch up to date with the fetched remote head: fast-forward when
+/// possible, otherwise merge (or rebase) unless --ff-only was given. Any other branch is
+/// left alone, since only the checked-out one matches the working tree. Returns what
+/// happened and the branch's head afterwards.
+fn integrate_remote(
+    repo_path: &Path,
+    ref_name: &str,
+    remote_name: &str,
+    branch: &str,
+    remote_head: Hash,
+    options: &PullOptions,
+) -> Result<(PullOutcome, Option<Hash>)> {
+    let store = FsObjectStore::new(repo_path);
+    let upstream = format!("{remote_name}/{branch}");
+
+    tracing::debug!(ref_name, remote_head = %hash_to_hex(&remote_head), "integrate remote");
+    if get_current_branch(repo_path).ok().as_deref() != Some(branch) {
+        say!("'{branch}' is not checked out; fetched {upstream} without changing it.");
+        return Ok((
+            PullOutcome::Fetched,
+            read_local_ref(repo_path, ref_name).ok(),
+        ));
+    }
+
+    // No local branch yet: take the remote as-is
+    let Ok(local_head) = read_local_ref(repo_path, ref_name) else {
+        update_branch(repo_path, ref_name, None, remote_head, options)?;
+        return Ok((PullOutcome::CheckedOut, Some(remote_head)));
+    };
+
+    if local_head == remote_head || is_ancestor(&store, remote_head, local_head)? {
+        say!("Already up to date.");
+        return Ok((PullOutcome::UpToDate, Some(local_head)));
+    }
+
+    if is_ancestor(&store, local_head, remote_head)? {
+        update_branch(repo_path, ref_name, Some(local_head), remote_head, options)?;
+        say!(
+            "Fast-forward {}..{}",
+            &hash_to_hex(&local_head)[..8],
+            &hash_to_hex(&remote_head)[..8]
+        );
+        return Ok((PullOutcome::FastForward, Some(remote_head)));
+    }
+
+    let base = merge_base(&store, local_head, remote_head)?.ok_or_else(|| {
+        anyhow!("'{branch}' and '{upstream}' have unrelated histories; refusing to merge them")
+    })?;
+
+    if options.ff_only {
+        bail!(
+            "Not possible to fast-forward: '{branch}' and '{upstream}' have diverged \
+             (common ancestor {}).\n\
+             The remote changes were fetched into {upstream}. To integrate them, run one of:\n  \
+             helix pull {remote_name} {branch}            # merge\n  \
+             helix pull --rebase {remote_name} {branch}   # replay your commits on top",
+            &hash_to_hex(&base)[..8]
+        );
+    }
+
+    let (outcome, new_head) = if options.rebase {
+        let new_head = rebase_onto(repo_path, local_head, base, remote_head)?;
+        say!(
+            "Rebased '{}' onto {} ({})",
+            branch,
+            upstream,
+            &hash_to_hex(&new_head)[..8]
+        );
+        (PullOutcome::Rebased, new_head)
+    } else {
+        let new_head = merge_remote(repo_path, branch, &upstream, base, local_head, remote_head)?;
+        (PullOutcome::Merged, new_head)
+    };
+
+    update_branch(repo_path, ref_name, Some(local_head), new_head, options)?;
+    Ok((outcome, Some(new_head)))
+}
+
+/// Three-way merge of the remote head into the local branch. Conflicts open the merge
+/// TUI when attached to a terminal; otherwise the pull stops before touching the branch.
+fn merge_remote(
+    repo_path: &Path,
+    branch: &str,
+    upstream: &str,
+    base: Hash,
+    local_head: Hash,
+    remote_head: Hash,
+) -> Result<Hash> {
+    let author = resolve_author(repo_path, None)?;
+    let message = format!("Merge {upstream} into {branch}");
+    let analysis = analyze_merge(repo_path, &base, &local_head, &remote_head)?;
+
+    if !analysis.has_conflicts() {
+        let result = execute_merge(
+            repo_path,
+            &analysis,
+            &HashMap::new(),
+            &local_head,
+            &remote_head,
+            &author,
+            &message,
+        )?;
+        say!(
+            "Merged {} into '{}' ({} files changed)",
+            upstream,
+            branch,
+            result.files_changed
+        );
+        return Ok(result.commit_hash);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        let paths: Vec<String> = analysis
+            .conflicts
+            .iter()
+            .map(|c| format!("  {}", c.path.display()))
+            .collect();
+        bail!(
+            "Merging {upstream} into '{branch}' has conflicts:\n{}\n\
+             '{branch}' was not changed. Run `helix pull` from a terminal to resolve them \
+             interactively.",
+            paths.join("\n")
+        );
+    }
+
+    let mut app = App::new(
+        repo_path,
+        branch,
+        upstream,
+        base,
+        local_head,
+        remote_head,
+        &author,
+    )?
+    .with_message(message);
+
+    match app.run()? {
+        Some(result) => {
+            say!(
+                "Merged {} into '{}' ({} conflicts resolved, {} files changed)",
+                upstream,
+                branch,
+                result.conflicts_resolved,
+                result.files_changed
+            );
+            Ok(result.commit_hash)
+        }
+        None => bail!("Merge cancelled; '{branch}' was not changed"),
+    }
+}
+
+/// Replay the local commits since `base` on top of `onto`, oldest first.
+/// Stops without changing anything if a commit doesn't apply cleanly.
+fn rebase_onto(repo_path: &Path, local_head: Hash, base: Hash, onto: Hash) -> Result<Hash> {
+    let commit_store = CommitStore::new(repo_path, FsObjectStore::new(repo_path))?;
+
+    let mut to_replay = Vec::new();
+    let mut cursor = local_head;
+    while cursor != base {
+        let commit = commit_store.read_commit(&cursor)?;
+        let [parent] = commit.parents[..] else {
+            bail!(
+                "Cannot rebase: {} is a merge commit. Run `helix pull` without --rebase to merge instead.",
+                &hash_to_hex(&cursor)[..8]
+            );
+        };
+        to_replay.push((cursor, parent, commit));
+        cursor = parent;
+    }
+    to_replay.reverse();
+
+    let mut new_head = onto;
+    for (hash, parent, commit) in to_replay {
+        let analysis = analyze_merge(repo_path, &parent, &new_head, &hash)?;
+        if analysis.has_conflicts() {
+            let paths: Vec<String> = analysis
+                .conflicts
+                .iter()
+                .map(|c| format!("  {}", c.path.display()))
+                .collect();
+            bail!(
+                "Rebase stopped: {} \"{}\" conflicts with the remote in:\n{}\n\
+                 Nothing was changed. Run `helix pull` without --rebase to merge instead.",
+                &hash_to_hex(&hash)[..8],
+                commit.summary(),
+                paths.join("\n")
+            );
+        }
+
+        let tree = build_merged_tree(repo_path, &analysis, &HashMap::new())?;
+        let mut replayed = Commit::new(tree, vec![new_head], commit.author, commit.message);
+        replayed.author_time = commit.author_time;
+        new_head = commit_store.write_commit(&replayed)?;
+    }
+
+    Ok(new_head)
+}
+
+/// Point the branch at `new_head` and update the working tree and index to match. Refuses,
+/// without --force, when that would overwrite uncommitted changes.
+fn update_branch(
+    repo_path: &Path,
+    ref_name: &str,
+    old_head: Option<Hash>,
+    new_head: Hash,
+    options: &PullOptions,
+) -> Result<()> {
+    if !options.force {
+        let in_the_way = local_changes_in_the_way(repo_path, old_head.as_ref(), &new_head)?;
+        if !in_the_way.is_empty() {
+            let paths: Vec<String> = in_the_way
+                .iter()
+                .map(|p| format!("  {}", p.display()))
+                .collect();
+            bail!(
+                "The pull would overwrite uncommitted changes to:\n{}\n\
+                 {ref_name} was not changed. Commit or restore them first, or pass --force \
+                 to overwrite them.",
+                paths.join("\n")
+            );
+        }
+    }
+
+    let local_ref_path = repo_path.join(".helix").join(ref_name);
     if let Some(parent) = local_ref_path.parent() {
         fs::create_dir_all(parent)?;
     }
-    fs::write(&local_ref_path, hash_to_hex(&new_remote_head) + "\n")?;
+    fs::write(&local_ref_path, hash_to_hex(&new_head) + "\n")?;
 
-    // Checkout the tree to working directory
-    let checkout_opts = crate::checkout::CheckoutOptions {
+    // Passing the old head lets checkout remove files deleted upstream
+    let checkout_opts = CheckoutOptions {
         verbose: options.verbose,
         force: true,
     };
-    let files_checked_out = checkout_tree(repo_path, &new_remote_head, &checkout_opts)?;
+    let files_checked_out = checkout_tree_to_path(
+        repo_path,
+        &new_head,
+        old_head.as_ref(),
+        repo_path,
+        &checkout_opts,
+    )?;
+    update_index_from_commit(repo_path, &new_head)?;
 
-    println!(
-        "Pulled {} objects from {}/{}",
-        object_count, remote_name, branch
-    );
-    println!(
+    say!(
         "Checked out {} files at {}",
         files_checked_out,
-        &hash_to_hex(&new_remote_head)[..8]
+        &hash_to_hex(&new_head)[..8]
     );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::format::{Entry, EntryFlags};
+    use crate::helix_index::tree::TreeBuilder;
+    use tempfile::TempDir;
+
+    const REF: &str = "refs/heads/main";
+
+    fn setup_repo() -> Result<TempDir> {
+        let temp = TempDir::new()?;
+        fs::create_dir_all(temp.path().join(".helix/refs/heads"))?;
+        fs::write(temp.path().join(".helix/HEAD"), format!("ref: {REF}\n"))?;
+        fs::write(
+            temp.path().join("helix.toml"),
+            "[user]\nname = \"Test\"\nemail = \"test@example.com\"\n\n[ignore]\n",
+        )?;
+        Ok(temp)
+    }
+
+    fn commit(repo: &Path, files: &[(&str, &str)], parents: Vec<Hash>) -> Result<Hash> {
+        let store = FsObjectStore::new(repo);
+        let mut entries = Vec::new();
+        for (path, content) in files {
+            entries.push(Entry {
+                path: (*path).into(),
+                oid: store.write_object(&ObjectType::Blob, content.as_bytes())?,
+                flags: EntryFlags::TRACKED,
+                size: content.len() as u64,
+                mtime_sec: 0,
+                mtime_nsec: 0,
+                file_mode: 0o100644,
+                merge_conflict_stage: 0,
+                reserved: [0u8; 33],
+            });
+        }
+        let tree = TreeBuilder::new(repo).build_from_entries(&entries)?;
+        let commit = Commit::new(tree, parents, "Test <test@example.com>".into(), "c".into());
+        CommitStore::new(repo, store)?.write_commit(&commit)
+    }
+
+    /// Move the branch to `head` with the working tree and index to match, like a hard reset
+    fn set_local(repo: &Path, head: Hash) -> Result<()> {
+        let old = read_local_ref(repo, REF).ok();
+        fs::write(repo.join(".helix").join(REF), hash_to_hex(&head))?;
+        let force = CheckoutOptions {
+            verbose: false,
+            force: true,
+        };
+        checkout_tree_to_path(repo, &head, old.as_ref(), repo, &force)?;
+        update_index_from_commit(repo, &head)
+    }
+
+    fn integrate(repo: &Path, remote_head: Hash, options: &PullOptions) -> Result<PullOutcome> {
+        let (outcome, _) = integrate_remote(repo, REF, "origin", "main", remote_head, options)?;
+        Ok(outcome)
+    }
+
+    #[test]
+    fn test_pull_fast_forward_and_ff_only() -> Result<()> {
+        let temp = setup_repo()?;
+        let repo = temp.path();
+
+        let c1 = commit(repo, &[("a.txt", "1")], vec![])?;
+        let c2 = commit(repo, &[("a.txt", "2")], vec![c1])?;
+        set_local(repo, c1)?;
+
+        assert_eq!(
+            integrate(repo, c2, &PullOptions::default())?,
+            PullOutcome::FastForward
+        );
+        assert_eq!(read_local_ref(repo, REF)?, c2);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "2");
+
+        // Local and remote both move on from c2
+        let local = commit(repo, &[("a.txt", "2"), ("b.txt", "local")], vec![c2])?;
+        let remote = commit(repo, &[("a.txt", "3")], vec![c2])?;
+        set_local(repo, local)?;
+
+        let options = PullOptions {
+            ff_only: true,
+            ..Default::default()
+        };
+        let err = integrate(repo, remote, &options).unwrap_err();
+        assert!(err.to_string().contains("diverged"));
+        assert_eq!(read_local_ref(repo, REF)?, local);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pull_keeps_uncommitted_changes() -> Result<()> {
+        let temp = setup_repo()?;
+        let repo = temp.path();
+
+        let c1 = commit(repo, &[("a.txt", "1"), ("b.txt", "1")], vec![])?;
+        let c2 = commit(repo, &[("a.txt", "2"), ("b.txt", "1")], vec![c1])?;
+        set_local(repo, c1)?;
+        fs::write(repo.join("a.txt"), "my edit")?;
+
+        let err = integrate(repo, c2, &PullOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("a.txt"), "{err}");
+        assert_eq!(read_local_ref(repo, REF)?, c1);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "my edit");
+
+        // Edits to files the pull doesn't touch are fine
+        fs::write(repo.join("a.txt"), "1")?;
+        fs::write(repo.join("b.txt"), "my edit")?;
+        integrate(repo, c2, &PullOptions::default())?;
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "2");
+        assert_eq!(fs::read_to_string(repo.join("b.txt"))?, "my edit");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pull_leaves_other_branches_alone() -> Result<()> {
+        let temp = setup_repo()?;
+        let repo = temp.path();
+
+        let c1 = commit(repo, &[("a.txt", "1")], vec![])?;
+        let c2 = commit(repo, &[("a.txt", "2")], vec![c1])?;
+        set_local(repo, c1)?;
+        fs::write(repo.join(".helix/refs/heads/topic"), hash_to_hex(&c1))?;
+
+        let (outcome, head) = integrate_remote(
+            repo,
+            "refs/heads/topic",
+            "origin",
+            "topic",
+            c2,
+            &PullOptions::default(),
+        )?;
+        assert_eq!(outcome, PullOutcome::Fetched);
+        assert_eq!(head, Some(c1));
+        assert_eq!(read_local_ref(repo, "refs/heads/topic")?, c1);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pull_diverged_merge_and_rebase() -> Result<()> {
+        let temp = setup_repo()?;
+        let repo = temp.path();
+        let commits = CommitStore::new(repo, FsObjectStore::new(repo))?;
+
+        let base = commit(repo, &[("a.txt", "1")], vec![])?;
+        let local = commit(repo, &[("a.txt", "1"), ("b.txt", "local")], vec![base])?;
+        let remote = commit(repo, &[("a.txt", "2")], vec![base])?;
+
+        set_local(repo, local)?;
+        integrate(repo, remote, &PullOptions::default())?;
+        let merged = commits.read_commit(&read_local_ref(repo, REF)?)?;
+        assert_eq!(merged.parents, vec![local, remote]);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "2");
+        assert_eq!(fs::read_to_string(repo.join("b.txt"))?, "local");
+
+        set_local(repo, local)?;
+        let options = PullOptions {
+            rebase: true,
+            ..Default::default()
+        };
+        integrate(repo, remote, &options)?;
+        let rebased = commits.read_commit(&read_local_ref(repo, REF)?)?;
+        assert_eq!(rebased.parents, vec![remote]);
+        assert_eq!(fs::read_to_string(repo.join("a.txt"))?, "2");
+        assert_eq!(fs::read_to_string(repo.join("b.txt"))?, "local");
+
+        Ok(())
+    }
+}
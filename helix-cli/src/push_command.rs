@@ -1,23 +1,85 @@
 use anyhow::{bail, Context, Result};
+use helix_client::{upload_batches, Outgoing, RemoteError};
 use helix_protocol::commit::{
-    compute_objects_to_push, read_local_ref, read_remote_tracking, write_remote_tracking,
+    compute_objects_to_push, is_ancestor, read_local_ref, read_remote_tracking,
+    walk_commits_between, write_remote_tracking,
 };
-use helix_protocol::hash::hash_to_hex;
-use helix_protocol::message::{
-    read_message, write_message, Hello, PushObject, PushRequest, RpcMessage,
-};
-use helix_protocol::storage::FsObjectStore;
+use helix_protocol::delta;
+use helix_protocol::hash::{hash_to_hex, Hash, ZERO_HASH};
+use helix_protocol::message::{ErrorCode, ObjectType, PushDelta, PushRef, RefStatus};
+use helix_protocol::storage::{FsObjectStore, FsRefStore};
+use helix_protocol::tag::peel_to_commit;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::Cursor;
-use std::path::Path;
-
-use crate::handshake::push_handshake;
-use crate::init_command::HelixConfig;
+use std::path::{Path, PathBuf};
+
+use crate::attributes::Attributes;
+use crate::author::resolve_author;
+use crate::credential;
+use crate::helix_index::tree::TreeStore;
+use crate::init_command::{HelixConfig, RemoteConfig};
+use crate::remote_refs::delete_tracking_ref;
+use crate::repo_config;
+use crate::say;
+use crate::transport;
 
 pub struct PushOptions {
     pub verbose: bool,
     pub dry_run: bool,
     pub force: bool,
+    pub no_compress: bool,
+}
+
+/// What a push did, as `helix push --json` prints it
+#[derive(Debug, Clone, Serialize)]
+pub struct PushReport {
+    pub remote: String,
+    pub refs: Vec<PushedRef>,
+    /// Objects sent
+    pub objects: usize,
+    pub dry_run: bool,
+}
+
+impl PushReport {
+    fn new(remote: &str, dry_run: bool) -> Self {
+        Self {
+            remote: remote.to_string(),
+            refs: Vec::new(),
+            objects: 0,
+            dry_run,
+        }
+    }
+
+    fn with_ref(mut self, name: &str, target: Hash, status: PushStatus) -> Self {
+        self.refs.push(PushedRef {
+            name: name.to_string(),
+            target: (target != ZERO_HASH).then(|| hash_to_hex(&target)),
+            status,
+        });
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PushedRef {
+    #[serde(rename = "ref")]
+    pub name: String,
+    /// None when the ref is deleted
+    pub target: Option<String>,
+    pub status: PushStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PushStatus {
+    Updated,
+    Deleted,
+    UpToDate,
+    /// The server left it alone, e.g. because another ref in the request failed
+    NotPushed,
+    WouldPush,
+    WouldDelete,
 }
 
 impl Default for PushOptions {
@@ -26,6 +88,7 @@ impl Default for PushOptions {
             verbose: false,
             dry_run: false,
             force: false,
+            no_compress: false,
         }
     }
 }
@@ -35,137 +98,526 @@ pub async fn push(
     remote_name: &str,
     branch: &str,
     options: PushOptions,
-) -> Result<()> {
+) -> Result<PushReport> {
     if !repo_path.join(".helix").exists() {
         bail!("Not a Helix repo (no .helix directory)");
     }
 
-    let (remote_url, ref_name) = resolve_remote_and_ref(&repo_path, remote_name, branch)?;
+    let (remote_config, ref_name) = resolve_remote_and_ref(&repo_path, remote_name, branch)?;
+    let remote_url = remote_config.push_target().to_string();
+    let token = credential::token_for_remote(repo_path, &remote_config, &remote_url)?;
 
     let new_target =
         read_local_ref(&repo_path, &ref_name).context("Failed to read local branch head")?;
 
-    let old_target = read_remote_tracking(repo_path, remote_name, branch).ok();
+    let tracked = !transport::is_url(remote_name);
+    let old_target = read_remote_tracking(repo_path, remote_name, branch)
+        .ok()
+        .filter(|_| tracked);
+    tracing::debug!(
+        remote = remote_name,
+        url = %remote_url,
+        ref_name = %ref_name,
+        new_target = %hash_to_hex(&new_target),
+        old_target = ?old_target.as_ref().map(hash_to_hex),
+        "push"
+    );
 
     if options.verbose {
-        println!("Pushing {ref_name} to {remote_name} at {remote_url}");
-        println!(
+        say!("Pushing {ref_name} to {remote_name} at {remote_url}");
+        say!(
             "  old_target = {}",
             old_target
                 .as_ref()
                 .map(hash_to_hex)
                 .unwrap_or_else(|| "<none>".to_string())
         );
-        println!("  new_target = {}", hash_to_hex(&new_target));
+        say!("  new_target = {}", hash_to_hex(&new_target));
     }
 
-    let server_head = push_handshake(
-        &remote_url,
-        &repo_path.file_name().unwrap_or_default().to_string_lossy(),
-        &ref_name,
-        new_target,
-        old_target,
-    )
-    .await?;
+    let repo_name = remote_config.repo_name(repo_path);
+    let remote =
+        transport::connect_remote(&remote_config, &remote_url, token.as_deref(), &repo_name)?
+            .with_compression(!options.no_compress && remote_config.compression.unwrap_or(true))
+            // Lets the server's post-receive hooks say who pushed
+            .with_pusher(resolve_author(repo_path, None).ok());
+    let server_head = remote.handshake(&ref_name, new_target, old_target).await?;
+    say!("Connected to the server!");
+    say!(
+        "Server is currently at: {}",
+        server_head
+            .as_ref()
+            .map(hash_to_hex)
+            .unwrap_or_else(|| hash_to_hex(&ZERO_HASH))
+    );
+    let features = remote.features();
+    tracing::debug!(
+        server_head = ?server_head.as_ref().map(hash_to_hex),
+        ?features,
+        "handshake done"
+    );
 
     if options.dry_run {
-        println!("(dry run) Would push from {} to {}", remote_name, branch);
-        return Ok(());
+        say!("(dry run) Would push from {} to {}", remote_name, branch);
+        return Ok(PushReport::new(remote_name, true).with_ref(
+            &ref_name,
+            new_target,
+            PushStatus::WouldPush,
+        ));
     }
 
     // Compute objects to send to server
     // TODO: as we create the objects, we should write them to the buffer at the same time instead of doing it in sequence
     let store = FsObjectStore::new(repo_path);
-    let objects = compute_objects_to_push(&store, new_target, server_head)?;
+    let haves: Vec<Hash> = server_head
+        .into_iter()
+        .chain(confirmed_tracking(&store, old_target, server_head))
+        .collect();
+    let objects = compute_objects_to_push(&store, &[new_target], &haves)?;
+    tracing::debug!(
+        objects = objects.len(),
+        haves = haves.len(),
+        "objects to push"
+    );
 
     if objects.is_empty() {
-        println!("Everything up to date.");
-        return Ok(());
+        say!("Everything up to date.");
+        return Ok(PushReport::new(remote_name, false).with_ref(
+            &ref_name,
+            new_target,
+            PushStatus::UpToDate,
+        ));
     }
 
     if options.verbose {
-        println!("Sending {} objects...", objects.len());
-    }
-
-    let mut buf = Vec::new();
-
-    write_message(
-        &mut buf,
-        &RpcMessage::Hello(Hello {
-            client_version: "helix-cli".into(),
-        }),
-    )?;
-
-    write_message(
-        &mut buf,
-        &RpcMessage::PushRequest(PushRequest {
-            repo: repo_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned(),
-            ref_name: ref_name.clone(),
-            old_target: old_target.unwrap_or([0u8; 32]),
-            new_target,
-        }),
-    )?;
-
-    // write objects to the buf to send to the server
-    for (object_type, hash, data) in objects {
-        write_message(
-            &mut buf,
-            &RpcMessage::PushObject(PushObject {
-                object_type,
-                hash,
-                data,
-            }),
-        )?;
+        say!("Sending {} objects...", objects.len());
     }
 
-    write_message(&mut buf, &RpcMessage::PushDone)?;
+    // The body is only compressed for servers that said they can read zstd frames
+    if options.verbose && !options.no_compress && features.compression {
+        say!("Using compressed transfer");
+    }
+    let attributes = Attributes::load(repo_path);
+    let objects = thin_objects(&store, objects, &haves, features.deltas, &attributes)?;
+    if options.verbose && features.deltas {
+        let count = objects
+            .iter()
+            .filter(|o| matches!(o, Outgoing::Delta(_)))
+            .count();
+        say!("Sending {count} blobs as deltas");
+    }
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(format!("{remote_url}/rpc/push"))
-        .body(buf)
-        .send()
-        .await
-        .with_context(|| "Connection to server lost during data transfer.")?;
+    // The server rejects non-fast-forward pushes unless old_target matches its head.
+    // --force claims the head we just saw, so whatever is there gets overwritten.
+    let expected_remote = if options.force {
+        server_head
+    } else {
+        old_target
+    };
+
+    let update = PushRef {
+        ref_name: ref_name.clone(),
+        old_target: expected_remote.unwrap_or([0u8; 32]),
+        new_target,
+    };
+
+    // Servers that support resuming take the objects in batches that can each be retried
+    // on their own; the ref only moves in the final request, once every object is stored
+    let sent: Result<u64> = async {
+        if features.resume {
+            let batches = upload_batches(&objects, UPLOAD_BATCH_BYTES);
+            let mut received = 0;
+            for (i, batch) in batches.iter().enumerate() {
+                tracing::debug!(
+                    batch = i + 1,
+                    of = batches.len(),
+                    objects = batch.len(),
+                    "upload"
+                );
+                if options.verbose {
+                    say!(
+                        "Uploading batch {}/{} ({} objects)",
+                        i + 1,
+                        batches.len(),
+                        batch.len()
+                    );
+                }
+                received += remote.upload(batch).await?.received_objects;
+            }
+            remote.push(&update, &[]).await?;
+            Ok(received)
+        } else {
+            Ok(remote.push(&update, &objects).await?.received_objects)
+        }
+    }
+    .await;
+    let received_objects = sent
+        .inspect_err(|e| tracing::debug!(error = %format!("{e:#}"), "push failed"))
+        .map_err(|e| name_rejected_object(e, repo_path, &[(new_target, server_head)]))?;
+
+    say!("Pushed {received_objects} objects to {remote_name}/{branch}");
+    if tracked {
+        write_remote_tracking(repo_path, remote_name, branch, new_target)?;
+    }
+    let mut report =
+        PushReport::new(remote_name, false).with_ref(&ref_name, new_target, PushStatus::Updated);
+    report.objects = received_objects as usize;
+    Ok(report)
+}
+
+/// Push several refs in one all-or-nothing request: every local ref under each of
+/// `prefixes` (refs/heads/ for --all, refs/tags/ for --tags, refs/notes/ for --notes),
+/// plus `branch` if given. The server either moves all of them or none, and reports on
+/// each. With `delete`, `branch` is deleted on the server instead.
+pub async fn push_refs(
+    repo_path: &Path,
+    remote_name: &str,
+    branch: Option<&str>,
+    prefixes: &[&str],
+    delete: bool,
+    options: PushOptions,
+) -> Result<PushReport> {
+    if !repo_path.join(".helix").exists() {
+        bail!("Not a Helix repo (no .helix directory)");
+    }
 
-    let status = resp.status();
-    let bytes = resp.bytes().await?;
+    let remote_config = resolve_remote(repo_path, remote_name)?;
+    let remote_url = remote_config.push_target().to_string();
+    let token = credential::token_for_remote(repo_path, &remote_config, &remote_url)?;
+    let repo_name = remote_config.repo_name(repo_path);
+
+    let tracked = !transport::is_url(remote_name);
+    let local_refs = FsRefStore::new(repo_path);
+    let mut local = Vec::new();
+    if let (Some(branch), true) = (branch, delete) {
+        local.push((format!("refs/heads/{branch}"), ZERO_HASH));
+    } else if let Some(branch) = branch {
+        let ref_name = format!("refs/heads/{branch}");
+        let target =
+            read_local_ref(repo_path, &ref_name).context("Failed to read local branch head")?;
+        local.push((ref_name, target));
+    }
+    for prefix in prefixes {
+        local.extend(local_refs.list_refs(prefix)?);
+    }
 
-    let mut cursor = Cursor::new(bytes.to_vec());
+    let remote =
+        transport::connect_remote(&remote_config, &remote_url, token.as_deref(), &repo_name)?
+            .with_compression(!options.no_compress && remote_config.compression.unwrap_or(true))
+            .with_pusher(resolve_author(repo_path, None).ok());
+    let server_refs: HashMap<String, Hash> = remote.list_refs("refs/").await?.into_iter().collect();
+    if let (Some(branch), true) = (branch, delete) {
+        if !server_refs.contains_key(&format!("refs/heads/{branch}")) {
+            bail!("{remote_name} has no branch '{branch}'");
+        }
+    }
 
-    let msg = read_message(&mut cursor)?;
+    let updates = plan_ref_updates(&local, &server_refs, options.force, |ref_name| {
+        let branch = ref_name.strip_prefix("refs/heads/")?;
+        read_remote_tracking(repo_path, remote_name, branch)
+            .ok()
+            .filter(|_| tracked)
+    });
+    if updates.is_empty() {
+        say!("Everything up to date.");
+        let report = PushReport::new(remote_name, options.dry_run);
+        return Ok(local.iter().fold(report, |report, (name, target)| {
+            report.with_ref(name, *target, PushStatus::UpToDate)
+        }));
+    }
 
-    match msg {
-        RpcMessage::PushAck(ack) if status.is_success() => {
-            println!(
-                "Pushed {} objects to {remote_name}/{branch}",
-                ack.received_objects
-            );
-            write_remote_tracking(repo_path, remote_name, branch, new_target)?;
-            Ok(())
+    if options.dry_run || options.verbose {
+        let (pushing, deleting) = if options.dry_run {
+            ("Would push", "Would delete")
+        } else {
+            ("Pushing", "Deleting")
+        };
+        for update in &updates {
+            if update.new_target == ZERO_HASH {
+                say!("{deleting} {}", update.ref_name);
+            } else {
+                say!(
+                    "{pushing} {} -> {}",
+                    update.ref_name,
+                    &hash_to_hex(&update.new_target)[..12]
+                );
+            }
+        }
+    }
+    if options.dry_run {
+        let report = PushReport::new(remote_name, true);
+        return Ok(updates.iter().fold(report, |report, update| {
+            let status = match update.new_target {
+                ZERO_HASH => PushStatus::WouldDelete,
+                _ => PushStatus::WouldPush,
+            };
+            report.with_ref(&update.ref_name, update.new_target, status)
+        }));
+    }
+
+    // Objects reachable from the new targets but not from anything the server advertised or
+    // we last saw there; tag objects go after the commits they point at
+    let store = FsObjectStore::new(repo_path);
+    let mut wants = Vec::new();
+    let mut tags = Vec::new();
+    let mut tips = Vec::new();
+    for update in updates.iter().filter(|u| u.new_target != ZERO_HASH) {
+        if store.has_object(&ObjectType::Tag, &update.new_target) {
+            let data = store.read_object_compressed(&ObjectType::Tag, &update.new_target)?;
+            tags.push((ObjectType::Tag, update.new_target, data));
         }
-        RpcMessage::Error(err) => {
-            bail!("Remote error {}: {}", err.code, err.message);
+        let commit = peel_to_commit(&store, &update.new_target)?;
+        let server_commit = server_refs
+            .get(&update.ref_name)
+            .map(|hash| peel_to_commit(&store, hash).unwrap_or(*hash));
+        tips.push((commit, server_commit));
+        wants.push(commit);
+    }
+    let mut haves: Vec<Hash> = server_refs
+        .values()
+        .map(|hash| peel_to_commit(&store, hash).unwrap_or(*hash))
+        .collect();
+    haves.extend(updates.iter().filter_map(|u| {
+        let branch = u.ref_name.strip_prefix("refs/heads/")?;
+        let tracking = read_remote_tracking(repo_path, remote_name, branch)
+            .ok()
+            .filter(|_| tracked);
+        confirmed_tracking(&store, tracking, server_refs.get(&u.ref_name).copied())
+    }));
+    let mut objects = compute_objects_to_push(&store, &wants, &haves)?;
+    let mut seen: HashSet<Hash> = HashSet::new();
+    tags.retain(|(_, hash, _)| seen.insert(*hash));
+    objects.extend(tags);
+    if options.verbose {
+        say!("Sending {} objects...", objects.len());
+    }
+
+    let features = remote.features();
+    let attributes = Attributes::load(repo_path);
+    let objects = thin_objects(&store, objects, &haves, features.deltas, &attributes)?;
+
+    // Same batching as a single-ref push: objects first, refs last
+    let ack = async {
+        if features.resume {
+            for batch in upload_batches(&objects, UPLOAD_BATCH_BYTES) {
+                remote.upload(batch).await?;
+            }
+            remote.push_refs(updates, &[]).await
+        } else {
+            remote.push_refs(updates, &objects).await
         }
-        other => {
-            bail!(
-                "Unexpected response from server: {:?} (status {status})",
-                other
-            );
+    }
+    .await
+    .map_err(|e| name_rejected_object(e, repo_path, &tips))?;
+
+    let mut report = PushReport::new(remote_name, false);
+    let mut rejected = 0;
+    for result in &ack.results {
+        let target = local
+            .iter()
+            .find(|(name, _)| *name == result.ref_name)
+            .map(|(_, hash)| *hash);
+        match &result.status {
+            RefStatus::Updated | RefStatus::UpToDate => {
+                let (status, label) = match result.status {
+                    RefStatus::UpToDate => (PushStatus::UpToDate, "up to date"),
+                    _ if delete => (PushStatus::Deleted, "deleted"),
+                    _ => (PushStatus::Updated, "updated"),
+                };
+                say!("  {} {}", result.ref_name, label);
+                report = report.with_ref(&result.ref_name, target.unwrap_or(ZERO_HASH), status);
+                let branch = result
+                    .ref_name
+                    .strip_prefix("refs/heads/")
+                    .filter(|_| tracked);
+                match (branch, target) {
+                    (Some(branch), Some(ZERO_HASH))
+                        if read_remote_tracking(repo_path, remote_name, branch).is_ok() =>
+                    {
+                        delete_tracking_ref(repo_path, remote_name, branch)?;
+                    }
+                    (Some(branch), Some(target)) if target != ZERO_HASH => {
+                        write_remote_tracking(repo_path, remote_name, branch, target)?
+                    }
+                    _ => {}
+                }
+            }
+            RefStatus::Rejected { message, .. } => {
+                rejected += 1;
+                say!("! {} rejected: {}", result.ref_name, message);
+            }
+            RefStatus::Skipped => {
+                say!("  {} not pushed", result.ref_name);
+                report = report.with_ref(
+                    &result.ref_name,
+                    target.unwrap_or(ZERO_HASH),
+                    PushStatus::NotPushed,
+                );
+            }
+        }
+    }
+    if rejected > 0 {
+        bail!(
+            "{} of {} refs were rejected, so nothing was pushed",
+            rejected,
+            ack.results.len()
+        );
+    }
+
+    if delete {
+        return Ok(report);
+    }
+    // With resumable uploads the objects went ahead of the final request, so the
+    // ack's count only covers that request
+    say!("Pushed {} objects to {remote_name}", objects.len());
+    report.objects = objects.len();
+    Ok(report)
+}
+
+/// Servers name an object over their size quota only by hash. Add the path of the file it
+/// holds, found in the commits being pushed (each tip back to the server's head), so the
+/// user knows what to take out.
+fn name_rejected_object(
+    err: anyhow::Error,
+    repo_path: &Path,
+    tips: &[(Hash, Option<Hash>)],
+) -> anyhow::Error {
+    let Some(remote) = err.downcast_ref::<RemoteError>() else {
+        return err;
+    };
+    let ErrorCode::ObjectTooLarge { hash, .. } = remote.code else {
+        return err;
+    };
+    match find_blob_path(repo_path, tips, &hash) {
+        Some(path) => RemoteError {
+            code: remote.code.clone(),
+            message: format!("{} ({})", remote.message, path.display()),
+        }
+        .into(),
+        None => err,
+    }
+}
+
+fn find_blob_path(repo_path: &Path, tips: &[(Hash, Option<Hash>)], blob: &Hash) -> Option<PathBuf> {
+    let store = FsObjectStore::new(repo_path);
+    let trees = TreeStore::for_repo(repo_path);
+    for (tip, server_head) in tips {
+        for commit in walk_commits_between(&store, *tip, *server_head).ok()? {
+            let files = trees.collect_all_files(&commit.tree_hash).ok()?;
+            if let Some((path, _)) = files.into_iter().find(|(_, hash)| hash == blob) {
+                return Some(path);
+            }
         }
     }
+    None
 }
 
-/// Resolve remote URL and ref name from helix.toml
+/// The updates to send for `local` refs: those the server doesn't already have at the
+/// same hash. Branches expect the server to be at our remote-tracking ref (from
+/// `tracking`), tags to not exist yet; with `force`, both expect what the server has now.
+/// Deletions (ZERO_HASH) of a branch we don't track expect what the server has, too.
+fn plan_ref_updates(
+    local: &[(String, Hash)],
+    server: &HashMap<String, Hash>,
+    force: bool,
+    tracking: impl Fn(&str) -> Option<Hash>,
+) -> Vec<PushRef> {
+    local
+        .iter()
+        .filter(|(ref_name, target)| server.get(ref_name) != Some(target))
+        .map(|(ref_name, target)| {
+            let old_target = if force {
+                server.get(ref_name).copied()
+            } else if *target == ZERO_HASH {
+                tracking(ref_name).or_else(|| server.get(ref_name).copied())
+            } else {
+                tracking(ref_name)
+            };
+            PushRef {
+                ref_name: ref_name.clone(),
+                old_target: old_target.unwrap_or([0u8; 32]),
+                new_target: *target,
+            }
+        })
+        .collect()
+}
+
+/// Upper bound on the object bytes sent in one /rpc/upload request
+const UPLOAD_BATCH_BYTES: usize = 8 * 1024 * 1024;
+
+/// With `deltas` (the server accepts PushDelta), send each blob whose path held another blob
+/// in one of the `haves` as a delta against that blob, when the delta is smaller than the
+/// compressed blob. That turns a small edit to a large file into a small push.
+fn thin_objects(
+    store: &FsObjectStore,
+    objects: Vec<(ObjectType, Hash, Vec<u8>)>,
+    haves: &[Hash],
+    deltas: bool,
+    attributes: &Attributes,
+) -> Result<Vec<Outgoing>> {
+    // Files marked -chunk are always sent whole
+    let deltify = |path: &str| attributes.for_path(Path::new(path)).chunk != Some(false);
+    let bases = if deltas {
+        delta::pick_bases(store, &objects, haves, &deltify)?
+    } else {
+        HashMap::new()
+    };
+    objects
+        .into_iter()
+        .map(|(object_type, hash, data)| {
+            let Some(base) = bases.get(&hash).filter(|_| object_type == ObjectType::Blob) else {
+                return Ok(Outgoing::Whole(object_type, hash, data));
+            };
+            let encoded = delta::encode(
+                &store.read_object(&ObjectType::Blob, base)?,
+                &store.read_object(&ObjectType::Blob, &hash)?,
+            );
+            Ok(if encoded.len() < data.len() {
+                Outgoing::Delta(PushDelta {
+                    object_type,
+                    hash,
+                    base: *base,
+                    delta: encoded,
+                })
+            } else {
+                Outgoing::Whole(object_type, hash, data)
+            })
+        })
+        .collect()
+}
+
+/// Resolve the remote's settings and the ref name from helix.toml
 pub fn resolve_remote_and_ref(
     repo_path: &Path,
     remote_name: &str,
     branch: &str,
-) -> Result<(String, String)> {
+) -> Result<(RemoteConfig, String)> {
+    let remote = resolve_remote(repo_path, remote_name)?;
+    let ref_name = format!("refs/heads/{branch}");
+
+    Ok((remote, ref_name))
+}
+
+/// The remote-tracking value of a ref, if the server still has it: its head is that commit
+/// or descends from it. A remote that was emptied or replaced may not, and then nothing
+/// may be left out of the push on its account.
+fn confirmed_tracking(
+    store: &FsObjectStore,
+    tracking: Option<Hash>,
+    server_head: Option<Hash>,
+) -> Option<Hash> {
+    let (tracking, server_head) = (tracking?, server_head?);
+    let server_commit = peel_to_commit(store, &server_head).unwrap_or(server_head);
+    (tracking == server_commit || is_ancestor(store, tracking, server_commit).unwrap_or(false))
+        .then_some(tracking)
+}
+
+/// Look up `remote_name` in the [remotes] table of helix.toml. A URL or path is a remote
+/// with default settings.
+pub fn resolve_remote(repo_path: &Path, remote_name: &str) -> Result<RemoteConfig> {
+    if transport::is_url(remote_name) {
+        return Ok(RemoteConfig::new(transport::url_for(remote_name)?));
+    }
     let config_path = repo_path.join("helix.toml");
 
     if !config_path.exists() {
@@ -175,24 +627,301 @@ pub fn resolve_remote_and_ref(
     let config_text = fs::read_to_string(&config_path)
         .with_context(|| format!("Failed to read {}", config_path.display()))?;
 
-    let parsed_config: HelixConfig = toml::from_str(&config_text)
-        .with_context(|| format!("Failed to parse {}", config_path.display()))?;
+    let parsed_config: HelixConfig = repo_config::parse(&config_text)?;
 
     let remotes = parsed_config
         .remotes
         .ok_or_else(|| anyhow::anyhow!("Missing [remotes] section in helix.toml"))?;
 
-    let push_key = format!("{}_push", remote_name);
-
-    let remote_url = remotes.map.get(&push_key).cloned().ok_or_else(|| {
+    remotes.get(remote_name).ok_or_else(|| {
         anyhow::anyhow!(
-            "Remote '{}' not found. Expected key '{}' in [remotes] table.",
+            "Remote '{}' not found. Expected a [remotes.{}] table with its url.",
+            remote_name,
             remote_name,
-            push_key,
         )
-    })?;
+    })
+}
 
-    let ref_name = format!("refs/heads/{branch}");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::commit::{Commit, CommitStore};
+    use crate::helix_index::format::{Entry, EntryFlags};
+    use crate::helix_index::tree::TreeBuilder;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_push_report_json() -> Result<()> {
+        let report = PushReport::new("origin", false).with_ref(
+            "refs/heads/main",
+            ZERO_HASH,
+            PushStatus::UpToDate,
+        );
+        assert_eq!(
+            serde_json::to_string(&report)?,
+            r#"{"remote":"origin","refs":[{"ref":"refs/heads/main","target":null,"status":"up-to-date"}],"objects":0,"dry_run":false}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejected_object_is_named_by_path() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        let store = FsObjectStore::new(repo);
+
+        let big = store.write_object(&ObjectType::Blob, b"pretend this is huge")?;
+        let entry = Entry {
+            path: "assets/video.mp4".into(),
+            oid: big,
+            flags: EntryFlags::TRACKED,
+            size: 20,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            file_mode: 0o100644,
+            merge_conflict_stage: 0,
+            reserved: [0u8; 33],
+        };
+        let tree = TreeBuilder::new(repo).build_from_entries(&[entry])?;
+        let commit = Commit::new(tree, vec![], "T <t@x>".into(), "add video".into());
+        let commit = CommitStore::new(repo, store)?.write_commit(&commit)?;
+
+        let rejected = || -> anyhow::Error {
+            RemoteError {
+                code: ErrorCode::ObjectTooLarge {
+                    hash: big,
+                    size: 20,
+                    limit: 10,
+                },
+                message: "Object is too large".to_string(),
+            }
+            .into()
+        };
+        let err = name_rejected_object(rejected(), repo, &[(commit, None)]);
+        assert_eq!(
+            err.downcast_ref::<RemoteError>().unwrap().message,
+            "Object is too large (assets/video.mp4)"
+        );
+
+        // Commits the server already has aren't searched
+        let err = name_rejected_object(rejected(), repo, &[(commit, Some(commit))]);
+        assert_eq!(
+            err.downcast_ref::<RemoteError>().unwrap().message,
+            "Object is too large"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_push_to_file_remotes() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("app");
+        let bare = temp.path().join("app.helix");
+        fs::create_dir_all(&repo)?;
+        fs::create_dir_all(&bare)?;
+        crate::init_command::init_bare_repo(&bare)?;
+        fs::write(
+            repo.join("helix.toml"),
+            format!(
+                "[user]\nname = \"T\"\nemail = \"t@x\"\n\n[remotes]\norigin_push = \"file://{}\"\n",
+                bare.display()
+            ),
+        )?;
+
+        let store = FsObjectStore::new(&repo);
+        let tree = TreeBuilder::new(&repo).build_from_entries(&[])?;
+        let commit = Commit::new(tree, vec![], "T <t@x>".into(), "first".into());
+        let commit = CommitStore::new(&repo, store)?.write_commit(&commit)?;
+        FsRefStore::new(&repo).set_ref("refs/heads/main", commit)?;
+
+        push(&repo, "origin", "main", PushOptions::default()).await?;
+
+        assert_eq!(
+            FsRefStore::new(&bare).get_ref("refs/heads/main")?,
+            Some(commit)
+        );
+        assert!(FsObjectStore::new(&bare).has_object(&ObjectType::Commit, &commit));
+        assert_eq!(read_remote_tracking(&repo, "origin", "main")?, commit);
+
+        // A repo with a working tree, given by path, won't have its checked-out branch moved
+        let checkout = temp.path().join("checkout");
+        fs::create_dir_all(&checkout)?;
+        crate::init_command::init_repo_layout(&checkout)?;
+        let err = push(
+            &repo,
+            checkout.to_str().unwrap(),
+            "main",
+            PushOptions::default(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RemoteError>().map(|e| e.code.clone()),
+            Some(ErrorCode::BranchCheckedOut)
+        );
+        assert!(FsRefStore::new(&checkout)
+            .get_ref("refs/heads/main")?
+            .is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_push_to_an_empty_remote_ignores_the_tracking_ref() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("app");
+        let bare = temp.path().join("app.helix");
+        fs::create_dir_all(&repo)?;
+        fs::create_dir_all(&bare)?;
+        crate::init_command::init_bare_repo(&bare)?;
+        fs::write(
+            repo.join("helix.toml"),
+            format!(
+                "[user]\nname = \"T\"\nemail = \"t@x\"\n\n[remotes]\norigin_push = \"file://{}\"\n",
+                bare.display()
+            ),
+        )?;
+
+        let store = FsObjectStore::new(&repo);
+        let tree = TreeBuilder::new(&repo).build_from_entries(&[])?;
+        let commit = Commit::new(tree, vec![], "T <t@x>".into(), "first".into());
+        let commit = CommitStore::new(&repo, store)?.write_commit(&commit)?;
+        FsRefStore::new(&repo).set_ref("refs/heads/main", commit)?;
+        // Pushed to whatever the remote used to point at
+        write_remote_tracking(&repo, "origin", "main", commit)?;
+
+        let report = push(&repo, "origin", "main", PushOptions::default()).await?;
+
+        assert_eq!(report.refs[0].status, PushStatus::Updated);
+        assert_eq!(
+            FsRefStore::new(&bare).get_ref("refs/heads/main")?,
+            Some(commit)
+        );
+        assert!(FsObjectStore::new(&bare).has_object(&ObjectType::Commit, &commit));
+        assert!(FsObjectStore::new(&bare).has_object(&ObjectType::Tree, &tree));
+
+        Ok(())
+    }
 
-    Ok((remote_url, ref_name))
+    #[tokio::test]
+    async fn test_changed_blob_is_pushed_as_delta() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path().join("app");
+        let bare = temp.path().join("app.helix");
+        fs::create_dir_all(&repo)?;
+        fs::create_dir_all(&bare)?;
+        crate::init_command::init_bare_repo(&bare)?;
+        let store = FsObjectStore::new(&repo);
+        let commit_file = |content: &[u8], parents: Vec<Hash>| -> Result<(Hash, Hash)> {
+            let blob = store.write_object(&ObjectType::Blob, content)?;
+            let entry = Entry {
+                path: "assets/model.bin".into(),
+                oid: blob,
+                flags: EntryFlags::TRACKED,
+                size: content.len() as u64,
+                mtime_sec: 0,
+                mtime_nsec: 0,
+                file_mode: 0o100644,
+                merge_conflict_stage: 0,
+                reserved: [0u8; 33],
+            };
+            let tree = TreeBuilder::new(&repo).build_from_entries(&[entry])?;
+            let commit = Commit::new(tree, parents, "T <t@x>".into(), "model".into());
+            let commit = CommitStore::new(&repo, store.clone())?.write_commit(&commit)?;
+            FsRefStore::new(&repo).set_ref("refs/heads/main", commit)?;
+            Ok((commit, blob))
+        };
+
+        // Content zstd can't shrink, so only a delta makes the second push small
+        let mut seed = 1u64;
+        let mut model: Vec<u8> = (0..256 * 1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 56) as u8
+            })
+            .collect();
+        let (first, _) = commit_file(&model, vec![])?;
+        let remote = bare.to_str().unwrap();
+        push(&repo, remote, "main", PushOptions::default()).await?;
+
+        model[1000..1010].copy_from_slice(b"retrained!");
+        let (second, blob) = commit_file(&model, vec![first])?;
+        let objects = compute_objects_to_push(&store, &[second], &[first])?;
+        let outgoing = thin_objects(&store, objects, &[first], true, &Attributes::default())?;
+        let deltas: Vec<&PushDelta> = outgoing
+            .iter()
+            .filter_map(|o| match o {
+                Outgoing::Delta(delta) => Some(delta),
+                Outgoing::Whole(..) => None,
+            })
+            .collect();
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].hash, blob);
+        assert!(deltas[0].delta.len() < 1024);
+
+        push(&repo, remote, "main", PushOptions::default()).await?;
+        assert_eq!(
+            FsRefStore::new(&bare).get_ref("refs/heads/main")?,
+            Some(second)
+        );
+        assert_eq!(
+            FsObjectStore::new(&bare).read_object(&ObjectType::Blob, &blob)?,
+            model
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_ref_updates() {
+        let local = vec![
+            ("refs/heads/main".to_string(), [2u8; 32]),
+            ("refs/heads/same".to_string(), [5u8; 32]),
+            ("refs/tags/v1".to_string(), [3u8; 32]),
+        ];
+        let server: HashMap<String, Hash> = [
+            ("refs/heads/main".to_string(), [9u8; 32]),
+            ("refs/heads/same".to_string(), [5u8; 32]),
+            ("refs/tags/v1".to_string(), [4u8; 32]),
+        ]
+        .into();
+        let tracking = |ref_name: &str| (ref_name == "refs/heads/main").then_some([1u8; 32]);
+
+        // Branches claim the last head we saw; tags claim to be new
+        let updates = plan_ref_updates(&local, &server, false, tracking);
+        assert_eq!(
+            updates,
+            vec![
+                PushRef {
+                    ref_name: "refs/heads/main".to_string(),
+                    old_target: [1u8; 32],
+                    new_target: [2u8; 32],
+                },
+                PushRef {
+                    ref_name: "refs/tags/v1".to_string(),
+                    old_target: [0u8; 32],
+                    new_target: [3u8; 32],
+                },
+            ]
+        );
+
+        // --force claims whatever the server has
+        let forced = plan_ref_updates(&local, &server, true, tracking);
+        assert_eq!(forced[0].old_target, [9u8; 32]);
+        assert_eq!(forced[1].old_target, [4u8; 32]);
+
+        // Deleting an untracked branch claims what the server has, a tracked one our copy
+        let delete = |ref_name: &str| {
+            plan_ref_updates(
+                &[(ref_name.to_string(), ZERO_HASH)],
+                &server,
+                false,
+                tracking,
+            )[0]
+            .old_target
+        };
+        assert_eq!(delete("refs/heads/same"), [5u8; 32]);
+        assert_eq!(delete("refs/heads/main"), [1u8; 32]);
+    }
 }
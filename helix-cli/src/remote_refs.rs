@@ -0,0 +1,134 @@
+/*
+Refs on a remote, and keeping the local remote-tracking refs in step with them.
+
+`list_remote_refs` asks the server for its refs with a ListRefs RPC
+(POST /rpc/refs). Servers that predate it answer 404 without an RpcError
+body, which is reported as not supporting /rpc/refs.
+
+Remote-tracking refs live in `.helix/refs/remotes/<remote>/<branch>` and are
+only ever written, by push and pull, so a branch deleted on the server keeps
+its local copy forever. `prune` deletes every tracking ref whose branch the
+server no longer has (or, for a dry run, just reports them).
+*/
+use crate::transport;
+use anyhow::{Context, Result};
+use helix_protocol::hash::Hash;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The server's refs under `prefix` ("refs/" for all), sorted by name
+pub async fn list_remote_refs(
+    remote_url: &str,
+    token: Option<&str>,
+    repo_name: &str,
+    prefix: &str,
+) -> Result<Vec<(String, Hash)>> {
+    transport::connect(remote_url, token, repo_name)
+        .list_refs(prefix)
+        .await
+}
+
+fn tracking_dir(repo_path: &Path, remote_name: &str) -> PathBuf {
+    repo_path
+        .join(".helix")
+        .join("refs")
+        .join("remotes")
+        .join(remote_name)
+}
+
+/// Branches tracked under refs/remotes/<remote>/ that aren't among the server's
+/// `refs/heads/*` in `remote_refs`, sorted
+pub fn stale_tracking_refs(
+    repo_path: &Path,
+    remote_name: &str,
+    remote_refs: &[(String, Hash)],
+) -> Vec<String> {
+    let dir = tracking_dir(repo_path, remote_name);
+    let mut stale: Vec<String> = WalkDir::new(&dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let branch = entry.path().strip_prefix(&dir).ok()?;
+            Some(branch.to_string_lossy().replace('\\', "/"))
+        })
+        .filter(|branch| {
+            let ref_name = format!("refs/heads/{}", branch);
+            !remote_refs.iter().any(|(name, _)| *name == ref_name)
+        })
+        .collect();
+    stale.sort();
+    stale
+}
+
+/// Remove refs/remotes/<remote>/<branch>, and directories it leaves empty
+pub fn delete_tracking_ref(repo_path: &Path, remote_name: &str, branch: &str) -> Result<()> {
+    let dir = tracking_dir(repo_path, remote_name);
+    let path = dir.join(branch);
+    fs::remove_file(&path).with_context(|| format!("Failed to delete {}", path.display()))?;
+
+    let mut parent = path.parent();
+    while let Some(current) = parent {
+        if current == dir || fs::remove_dir(current).is_err() {
+            break;
+        }
+        parent = current.parent();
+    }
+    Ok(())
+}
+
+/// Delete remote-tracking refs for branches that are gone from the server.
+/// Returns the branches pruned, or that would be with `dry_run`.
+pub async fn prune(
+    repo_path: &Path,
+    remote_name: &str,
+    remote_url: &str,
+    repo_name: &str,
+    token: Option<&str>,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let remote_refs = list_remote_refs(remote_url, token, repo_name, "refs/heads/").await?;
+
+    let stale = stale_tracking_refs(repo_path, remote_name, &remote_refs);
+    if !dry_run {
+        for branch in &stale {
+            delete_tracking_ref(repo_path, remote_name, branch)?;
+        }
+    }
+    Ok(stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helix_protocol::commit::write_remote_tracking;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stale_tracking_refs_are_pruned() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        write_remote_tracking(repo, "origin", "main", [1u8; 32])?;
+        write_remote_tracking(repo, "origin", "feature/gone", [2u8; 32])?;
+        write_remote_tracking(repo, "upstream", "old", [3u8; 32])?;
+
+        let remote_refs = vec![
+            ("refs/heads/main".to_string(), [1u8; 32]),
+            ("refs/tags/old".to_string(), [3u8; 32]),
+        ];
+        let stale = stale_tracking_refs(repo, "origin", &remote_refs);
+        assert_eq!(stale, vec!["feature/gone".to_string()]);
+
+        for branch in &stale {
+            delete_tracking_ref(repo, "origin", branch)?;
+        }
+        let origin = tracking_dir(repo, "origin");
+        assert!(origin.join("main").exists());
+        assert!(!origin.join("feature").exists());
+        // Other remotes are left alone
+        assert!(tracking_dir(repo, "upstream").join("old").exists());
+
+        Ok(())
+    }
+}
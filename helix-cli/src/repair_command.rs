@@ -0,0 +1,297 @@
+/*
+`helix repair` - replace corrupt or missing objects with copies from a remote.
+
+1. Walk every object reachable from HEAD and .helix/refs (commits -> parents
+   and trees, trees -> subtrees and blobs).
+2. Any object that is missing, fails its BLAKE3 check or can't be parsed is
+   marked damaged.
+3. Damaged objects are requested by hash from the remote (FetchObject RPC),
+   verified, and written over the local copy.
+
+Replacing a commit or tree can expose objects below it that were unreachable
+before, so steps 1-3 repeat until nothing is damaged or the remote can't
+supply anything more.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, is_zero_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::Tag;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::credential;
+use crate::helix_index::commit::Commit;
+use crate::helix_index::tree::{EntryType, Tree};
+use crate::push_command::resolve_remote;
+use crate::transport;
+
+#[derive(Default)]
+pub struct RepairOptions {
+    pub verbose: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    /// Objects fetched from the remote and rewritten locally
+    pub repaired: Vec<(ObjectType, Hash)>,
+    /// Damaged objects the remote could not supply
+    pub unrepaired: Vec<(ObjectType, Hash)>,
+}
+
+pub async fn repair(
+    repo_path: &Path,
+    remote_name: &str,
+    options: RepairOptions,
+) -> Result<RepairReport> {
+    if !repo_path.join(".helix").exists() {
+        bail!("Not a Helix repo (no .helix directory)");
+    }
+
+    let store = FsObjectStore::new(repo_path);
+    let roots = ref_targets(repo_path)?;
+    let mut report = RepairReport::default();
+
+    let mut damaged = find_damaged_objects(&store, &roots);
+    if damaged.is_empty() {
+        println!("No damaged objects reachable from refs.");
+        return Ok(report);
+    }
+
+    if options.dry_run {
+        for (ty, hash) in &damaged {
+            println!(
+                "(dry run) Would fetch {} {}",
+                kind_name(ty),
+                hash_to_hex(hash)
+            );
+        }
+        report.unrepaired = damaged;
+        return Ok(report);
+    }
+
+    let remote_config = resolve_remote(repo_path, remote_name)?;
+    let remote_url = remote_config.url.clone();
+    let repo_name = remote_config.repo_name(repo_path);
+
+    loop {
+        if options.verbose {
+            println!(
+                "Requesting {} damaged objects from {}",
+                damaged.len(),
+                remote_name
+            );
+        }
+
+        let token = credential::token_for(repo_path, &remote_url)?;
+        let received = transport::connect(&remote_url, token.as_deref(), &repo_name)
+            .fetch_objects(&damaged)
+            .await?;
+        let mut progress = false;
+
+        for obj in received {
+            let raw = zstd::decode_all(&obj.data[..])
+                .context("Failed to decompress object for hash verification")?;
+            if hash_bytes(&raw) != obj.hash {
+                eprintln!(
+                    "warning: remote sent a bad copy of {}, skipping",
+                    hash_to_hex(&obj.hash)
+                );
+                continue;
+            }
+
+            // The store skips writes for existing paths, so drop the corrupt copy first
+            store.remove_object(&obj.object_type, &obj.hash)?;
+            store.write_object_compressed_with_hash(&obj.object_type, &obj.hash, &obj.data)?;
+
+            if options.verbose {
+                println!(
+                    "  repaired {} {}",
+                    kind_name(&obj.object_type),
+                    hash_to_hex(&obj.hash)
+                );
+            }
+            report.repaired.push((obj.object_type, obj.hash));
+            progress = true;
+        }
+
+        damaged = find_damaged_objects(&store, &roots);
+        if damaged.is_empty() || !progress {
+            break;
+        }
+    }
+
+    report.unrepaired = damaged;
+
+    println!("Repaired {} objects", report.repaired.len());
+    for (ty, hash) in &report.unrepaired {
+        eprintln!(
+            "error: {} {} is damaged and not available on '{}'",
+            kind_name(ty),
+            hash_to_hex(hash),
+            remote_name
+        );
+    }
+
+    Ok(report)
+}
+
+/// Commits (or annotated tags) pointed to by a detached HEAD and every ref under .helix/refs
+fn ref_targets(repo_path: &Path) -> Result<Vec<Hash>> {
+    let helix_dir = repo_path.join(".helix");
+    let mut targets = Vec::new();
+
+    let head_path = helix_dir.join("HEAD");
+    if let Ok(head) = fs::read_to_string(&head_path) {
+        if let Ok(hash) = hex_to_hash(head.trim()) {
+            targets.push(hash);
+        }
+    }
+
+    let refs_dir = helix_dir.join("refs");
+    if refs_dir.exists() {
+        for entry in WalkDir::new(&refs_dir) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let content = fs::read_to_string(entry.path())
+                .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+            if let Ok(hash) = hex_to_hash(content.trim()) {
+                targets.push(hash);
+            }
+        }
+    }
+
+    targets.retain(|h| !is_zero_hash(h));
+    Ok(targets)
+}
+
+/// Walk everything reachable from `roots` and return objects that are missing or corrupt
+fn find_damaged_objects(store: &FsObjectStore, roots: &[Hash]) -> Vec<(ObjectType, Hash)> {
+    let mut stack: Vec<(ObjectType, Hash)> = roots
+        .iter()
+        .map(|h| {
+            if store.has_object(&ObjectType::Tag, h) {
+                (ObjectType::Tag, *h)
+            } else {
+                (ObjectType::Commit, *h)
+            }
+        })
+        .collect();
+    let mut seen: HashSet<(u8, Hash)> = HashSet::new();
+    let mut damaged = Vec::new();
+
+    while let Some((ty, hash)) = stack.pop() {
+        if !seen.insert((type_tag(&ty), hash)) {
+            continue;
+        }
+
+        let Ok(raw) = store.read_object(&ty, &hash) else {
+            damaged.push((ty, hash));
+            continue;
+        };
+
+        match ty {
+            ObjectType::Commit => match Commit::from_bytes(&raw) {
+                Ok(commit) => {
+                    stack.push((ObjectType::Tree, commit.tree_hash));
+                    stack.extend(commit.parents.iter().map(|p| (ObjectType::Commit, *p)));
+                }
+                Err(_) => damaged.push((ty, hash)),
+            },
+            ObjectType::Tree => match Tree::from_bytes(&raw) {
+                Ok(tree) => {
+                    for entry in tree.entries {
+                        let child_ty = match entry.entry_type {
+                            EntryType::Tree => ObjectType::Tree,
+                            _ => ObjectType::Blob,
+                        };
+                        stack.push((child_ty, entry.oid));
+                    }
+                }
+                Err(_) => damaged.push((ty, hash)),
+            },
+            ObjectType::Tag => match Tag::from_bytes(&raw) {
+                Ok(tag) => stack.push((ObjectType::Commit, tag.target)),
+                Err(_) => damaged.push((ty, hash)),
+            },
+            ObjectType::Blob => {}
+        }
+    }
+
+    damaged
+}
+
+fn type_tag(ty: &ObjectType) -> u8 {
+    match ty {
+        ObjectType::Blob => 0,
+        ObjectType::Tree => 1,
+        ObjectType::Commit => 2,
+        ObjectType::Tag => 3,
+    }
+}
+
+fn kind_name(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blob",
+        ObjectType::Tree => "tree",
+        ObjectType::Commit => "commit",
+        ObjectType::Tag => "tag",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::TreeEntry;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_damaged_objects() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        let store = FsObjectStore::new(repo);
+
+        let good = store.write_object(&ObjectType::Blob, b"good")?;
+        let bad = store.write_object(&ObjectType::Blob, b"bad")?;
+        let unreachable = store.write_object(&ObjectType::Blob, b"unreachable")?;
+        let missing = hash_bytes(b"never written");
+
+        let mut tree = Tree::new();
+        for (name, oid) in [("a", good), ("b", bad), ("c", missing)] {
+            tree.add_entry(TreeEntry::new_file(name.into(), oid, 0o100644, 0));
+        }
+        let tree_hash = store.write_object(&ObjectType::Tree, &tree.to_bytes())?;
+        let commit = Commit::initial(tree_hash, "Test".into(), "init".into());
+        let commit_hash = store.write_object(&ObjectType::Commit, &commit.to_bytes())?;
+        fs::write(
+            repo.join(".helix/refs/heads/main"),
+            hash_to_hex(&commit_hash),
+        )?;
+
+        // Corrupt two blobs on disk; only the reachable one should be reported
+        for hash in [bad, unreachable] {
+            let path = store.object_path(&ObjectType::Blob, &hash);
+            fs::write(path, zstd::encode_all(&b"tampered"[..], 3)?)?;
+        }
+
+        let roots = ref_targets(repo)?;
+        assert_eq!(roots, vec![commit_hash]);
+
+        let mut damaged: Vec<Hash> = find_damaged_objects(&store, &roots)
+            .into_iter()
+            .map(|(_, h)| h)
+            .collect();
+        damaged.sort();
+        let mut expected = vec![bad, missing];
+        expected.sort();
+        assert_eq!(damaged, expected);
+
+        Ok(())
+    }
+}
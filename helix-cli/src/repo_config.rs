@@ -0,0 +1,331 @@
+/*
+Loading and validating the repo-local helix.toml.
+
+The file is deserialized into `HelixConfig`, so a syntax error or a value of
+the wrong type fails the load. The TOML error carries the byte span of the
+offending value, which is turned into a `helix.toml:<line>:<column>` location
+plus the line itself with a caret under the problem.
+
+Keys the schema doesn't know are not errors: serde skips them, other tools
+may keep their own sections in the file, and a newer Helix may add settings
+an older one should tolerate. A misspelled key would otherwise be dropped
+without a word, though, so every known table is also walked against the
+schema below and each stray key is reported as a warning, with the closest
+known key (or the section it belongs in) as a suggestion.
+
+`validate` is run by every command on startup: it prints the warnings and
+returns the error, so a broken config is reported once, up front, instead
+of by whichever loader happens to read it first.
+*/
+use crate::init_command::HelixConfig;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use toml_edit::{ImDocument, Item, TableLike};
+
+pub const CONFIG_FILE: &str = "helix.toml";
+
+/// Known keys of each section; `None` means any key is allowed (remote names)
+const SCHEMA: &[(&str, Option<&[&str]>)] = &[
+    ("user", Some(&["name", "email"])),
+    ("core", Some(&["line_endings", "object_layout"])),
+    ("remotes", None),
+    ("ignore", Some(&["patterns"])),
+    (
+        "commit",
+        Some(&[
+            "template",
+            "signoff",
+            "co_authors",
+            "conventional",
+            "subject_pattern",
+            "max_subject_length",
+            "msg_hook",
+        ]),
+    ),
+    ("credential", Some(&["helper", "token"])),
+    (
+        "ui",
+        Some(&["color", "pager", "theme", "date_format", "keys", "mouse"]),
+    ),
+];
+
+/// Known keys of each `[remotes.<name>]` table
+const REMOTE_KEYS: &[&str] = &[
+    "url",
+    "push_url",
+    "repo",
+    "auth",
+    "compression",
+    "timeout_secs",
+    "tls",
+];
+
+/// Largest edit distance at which a known key is offered as a suggestion
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// A problem in helix.toml, located in the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// 1-based line and column, when the problem can be pinned down
+    pub location: Option<(usize, usize)>,
+    /// The offending line and the width of the span within it
+    pub excerpt: Option<(String, usize)>,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    fn new(text: &str, span: Option<Range<usize>>, message: impl Into<String>) -> Self {
+        let mut diagnostic = Self {
+            message: message.into(),
+            location: None,
+            excerpt: None,
+            suggestion: None,
+        };
+
+        if let Some(span) = span {
+            let start = span.start.min(text.len());
+            let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+            let line = text[..start].matches('\n').count() + 1;
+            let column = text[line_start..start].chars().count() + 1;
+            let width = text[start..span.end.clamp(start, line_end)]
+                .chars()
+                .count()
+                .max(1);
+
+            diagnostic.location = Some((line, column));
+            diagnostic.excerpt = Some((text[line_start..line_end].trim_end().to_string(), width));
+        }
+        diagnostic
+    }
+
+    fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    /// The one-line form: `helix.toml:3:1: message`
+    pub fn headline(&self) -> String {
+        match self.location {
+            Some((line, column)) => {
+                format!("{}:{}:{}: {}", CONFIG_FILE, line, column, self.message)
+            }
+            None => format!("{}: {}", CONFIG_FILE, self.message),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.headline())?;
+        if let (Some((source, width)), Some((line, column))) = (&self.excerpt, self.location) {
+            let gutter = line.to_string().len();
+            write!(f, "\n {:>gutter$} | {}", line, source)?;
+            write!(
+                f,
+                "\n {:>gutter$} | {}{}",
+                "",
+                " ".repeat(column - 1),
+                "^".repeat(*width)
+            )?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse helix.toml contents. A syntax or type error is returned as the
+/// error; unknown keys come back as warnings next to the config.
+pub fn check(text: &str) -> std::result::Result<(HelixConfig, Vec<Diagnostic>), Diagnostic> {
+    let config: HelixConfig =
+        toml::from_str(text).map_err(|e| Diagnostic::new(text, e.span(), e.message().trim()))?;
+
+    // toml accepted the text, so toml_edit will too
+    let warnings = match ImDocument::parse(text) {
+        Ok(doc) => unknown_keys(text, doc.as_table()),
+        Err(_) => Vec::new(),
+    };
+
+    Ok((config, warnings))
+}
+
+/// Parse helix.toml contents, failing with a located message
+pub fn parse(text: &str) -> Result<HelixConfig> {
+    match check(text) {
+        Ok((config, _)) => Ok(config),
+        Err(diagnostic) => bail!("{}", diagnostic),
+    }
+}
+
+/// Check the repository's helix.toml before a command runs: warnings are
+/// printed to stderr, errors abort. A missing file is fine.
+pub fn validate(repo_path: &Path) -> Result<()> {
+    let config_path = repo_path.join(CONFIG_FILE);
+    let Ok(text) = fs::read_to_string(&config_path) else {
+        return Ok(());
+    };
+
+    match check(&text) {
+        Ok((_, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            Ok(())
+        }
+        Err(diagnostic) => bail!("{}", diagnostic),
+    }
+}
+
+fn unknown_keys(text: &str, root: &dyn TableLike) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+
+    for (name, item) in root.iter() {
+        let span = key_span(root, name);
+        let Some((_, known)) = SCHEMA.iter().find(|(section, _)| *section == name) else {
+            let sections: Vec<&str> = SCHEMA.iter().map(|(section, _)| *section).collect();
+            let suggestion = closest(name, &sections)
+                .map(|s| format!("did you mean [{}]?", s))
+                .or_else(|| home_section(name).map(|s| format!("`{}` belongs in [{}]", name, s)));
+            warnings.push(
+                Diagnostic::new(text, span, format!("unknown key `{}`", name))
+                    .with_suggestion(suggestion),
+            );
+            continue;
+        };
+
+        let Some(table) = item.as_table_like() else {
+            continue;
+        };
+        match known {
+            Some(known) => unknown_keys_in(text, table, name, known, &mut warnings),
+            // Remote names are free-form, but each remote's table has a schema
+            None => {
+                for (remote, item) in table.iter() {
+                    if let Some(remote_table) = item.as_table_like() {
+                        let section = format!("{}.{}", name, remote);
+                        unknown_keys_in(text, remote_table, &section, REMOTE_KEYS, &mut warnings);
+                    }
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+fn unknown_keys_in(
+    text: &str,
+    table: &dyn TableLike,
+    name: &str,
+    known: &[&str],
+    warnings: &mut Vec<Diagnostic>,
+) {
+    for (key, _) in table.iter() {
+        if known.contains(&key) {
+            continue;
+        }
+        let suggestion = closest(key, known)
+            .map(|k| format!("did you mean `{}`?", k))
+            .or_else(|| home_section(key).map(|s| format!("`{}` belongs in [{}]", key, s)));
+        warnings.push(
+            Diagnostic::new(
+                text,
+                key_span(table, key),
+                format!("unknown key `{}` in [{}]", key, name),
+            )
+            .with_suggestion(suggestion),
+        );
+    }
+}
+
+fn key_span(table: &dyn TableLike, key: &str) -> Option<Range<usize>> {
+    let (key, item) = table.get_key_value(key)?;
+    key.span().or_else(|| match item {
+        Item::Table(t) => t.span(),
+        _ => item.span(),
+    })
+}
+
+/// The known key closest to `key`, if it's a plausible typo
+fn closest<'a>(key: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::levenshtein(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The section that owns `key`, for keys written in the wrong place
+fn home_section(key: &str) -> Option<&'static str> {
+    SCHEMA
+        .iter()
+        .find(|(_, known)| known.is_some_and(|known| known.contains(&key)))
+        .map(|(section, _)| *section)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_locations_and_suggestions() -> Result<()> {
+        let text = "[user]\nname = \"Ada\"\nemial = \"ada@example.com\"\nsignoff = true\n\n[ignore]\npatterns = []\n\n[comit]\nconventional = true\n";
+        let (config, warnings) = check(text).map_err(|d| anyhow::anyhow!("{}", d))?;
+        assert_eq!(config.user.and_then(|u| u.name).as_deref(), Some("Ada"));
+
+        let headlines: Vec<String> = warnings.iter().map(Diagnostic::headline).collect();
+        assert_eq!(
+            headlines,
+            vec![
+                "helix.toml:3:1: unknown key `emial` in [user]",
+                "helix.toml:4:1: unknown key `signoff` in [user]",
+                "helix.toml:9:2: unknown key `comit`",
+            ]
+        );
+        assert_eq!(
+            warnings[0].suggestion.as_deref(),
+            Some("did you mean `email`?")
+        );
+        assert_eq!(
+            warnings[1].suggestion.as_deref(),
+            Some("`signoff` belongs in [commit]")
+        );
+        assert_eq!(
+            warnings[2].suggestion.as_deref(),
+            Some("did you mean [commit]?")
+        );
+
+        // A type error is fatal and points at the value
+        let text = "[ignore]\npatterns = []\n\n[commit]\nsignoff = \"yes\"\n";
+        let error = check(text).unwrap_err();
+        assert_eq!(error.location, Some((5, 11)));
+        let rendered = error.to_string();
+        assert!(rendered.starts_with("helix.toml:5:11: invalid type"));
+        assert!(rendered.contains("5 | signoff = \"yes\"\n   |           ^^^^^"));
+
+        // Remote names are free-form, and [ignore] may be left out
+        let text = "[remotes]\norigin_push = \"https://example.com/repo\"\n";
+        let (config, warnings) = check(text).map_err(|d| anyhow::anyhow!("{}", d))?;
+        assert!(warnings.is_empty());
+        assert!(config.ignore.patterns.is_empty());
+
+        // A remote's own table is checked against the remote keys
+        let text =
+            "[remotes.origin]\nurl = \"https://example.com\"\nrepo = \"app\"\ntimeout = 30\n";
+        let (_, warnings) = check(text).map_err(|d| anyhow::anyhow!("{}", d))?;
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].headline(),
+            "helix.toml:4:1: unknown key `timeout` in [remotes.origin]"
+        );
+
+        Ok(())
+    }
+}
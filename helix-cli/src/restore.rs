@@ -0,0 +1,280 @@
+/*
+Undoing changes to individual paths.
+
+  unstage  index <- HEAD     the path's index entry goes back to HEAD's blob, or
+                             is dropped (leaving the file untracked) when HEAD
+                             doesn't have it. The working tree is untouched.
+  discard  worktree <- index tracked files are rewritten from their index blob;
+                             untracked files are deleted.
+
+Both work on the current context's index, so they do the right thing in a
+sandbox or linked worktree.
+
+`helix restore` is the command-line front end:
+
+  helix restore <paths>                      discard working tree changes
+  helix restore --staged <paths>             unstage
+  helix restore --staged --worktree <paths>  both, back to HEAD
+
+Unlike discarding from the status TUI, it only touches tracked paths; a
+directory or "." stands for every path under it.
+*/
+use anyhow::{bail, Context, Result};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commit_command::head_commit;
+use crate::file_mode::{self, SymlinkStrategy, MODE_EXECUTABLE, MODE_SYMLINK};
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::format::EntryFlags;
+use crate::helix_index::tree::TreeStore;
+use crate::line_endings::LineEndings;
+use crate::promisor;
+use crate::sandbox_command::RepoContext;
+
+#[derive(Debug, Default)]
+pub struct RestoreOptions {
+    /// Restore the index from HEAD
+    pub staged: bool,
+    /// Restore the working tree from the index (the default without --staged)
+    pub worktree: bool,
+}
+
+/// `helix restore`: returns the paths that were restored
+pub fn restore(
+    repo_path: &Path,
+    paths: &[PathBuf],
+    options: &RestoreOptions,
+) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        bail!("No paths specified. Use 'helix restore <paths>' or 'helix restore .'");
+    }
+    let worktree = options.worktree || !options.staged;
+
+    let context = RepoContext::detect(repo_path)?;
+    let index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+    let mut candidates: Vec<PathBuf> = index
+        .entries()
+        .iter()
+        .filter(|e| e.flags.contains(EntryFlags::TRACKED))
+        .map(|e| e.path.clone())
+        .collect();
+    if options.staged {
+        // Files staged for the first time aren't in HEAD but can still be unstaged
+        if let Ok(commit) = head_commit(repo_path) {
+            let head_files =
+                TreeStore::for_repo(&context.repo_root).collect_all_files(&commit.tree_hash)?;
+            candidates.extend(head_files.into_keys());
+        }
+    }
+    candidates.sort();
+    candidates.dedup();
+
+    let mut matched = Vec::new();
+    for pathspec in paths {
+        let before = matched.len();
+        matched.extend(
+            candidates
+                .iter()
+                .filter(|c| pathspec.as_os_str() == "." || c.starts_with(pathspec))
+                .cloned(),
+        );
+        if matched.len() == before {
+            bail!(
+                "pathspec '{}' did not match any tracked file",
+                pathspec.display()
+            );
+        }
+    }
+    matched.sort();
+    matched.dedup();
+
+    if options.staged {
+        unstage_paths(repo_path, &matched)?;
+    }
+    if worktree {
+        // Untracked paths (e.g. just unstaged new files) are left alone
+        let index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+        let tracked: Vec<PathBuf> = matched
+            .iter()
+            .filter(|p| index.is_tracked(p))
+            .cloned()
+            .collect();
+        discard_paths(repo_path, &tracked)?;
+    }
+
+    Ok(matched)
+}
+
+/// Reset the index entries for `paths` to HEAD
+pub fn unstage_paths(repo_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let context = RepoContext::detect(repo_path)?;
+    let mut index = HelixIndexData::load_for_update(&context.index_path, &context.repo_root)?;
+
+    let head_files = match head_commit(repo_path) {
+        Ok(commit) => {
+            TreeStore::for_repo(&context.repo_root).collect_all_files(&commit.tree_hash)?
+        }
+        Err(_) => HashMap::new(),
+    };
+
+    for path in paths {
+        match head_files.get(path) {
+            Some(oid) => {
+                if let Some(entry) = index.entry_mut(path) {
+                    entry.oid = *oid;
+                    entry.flags.remove(EntryFlags::STAGED | EntryFlags::DELETED);
+                    entry.flags.insert(EntryFlags::TRACKED);
+                    // Force the next status to rehash the file against HEAD's blob
+                    entry.mtime_sec = 0;
+                }
+            }
+            None => index.entries_mut().retain(|e| &e.path != path),
+        }
+    }
+
+    index.persist()
+}
+
+/// Throw away working tree changes to `paths`
+pub fn discard_paths(repo_path: &Path, paths: &[PathBuf]) -> Result<()> {
+    let context = RepoContext::detect(repo_path)?;
+    let mut index = HelixIndexData::load_for_update(&context.index_path, &context.repo_root)?;
+    let store = FsObjectStore::new(&context.repo_root);
+    let line_endings = LineEndings::load(&context.repo_root);
+    let symlinks = SymlinkStrategy::load(&context.repo_root);
+
+    let tracked = index
+        .entries()
+        .iter()
+        .filter(|e| paths.contains(&e.path) && e.flags.contains(EntryFlags::TRACKED));
+    promisor::fetch_promised(&context.repo_root, tracked.map(|e| e.oid))?;
+
+    for path in paths {
+        let full_path = context.workdir.join(path);
+        let entry = index
+            .entries_mut()
+            .iter_mut()
+            .find(|e| &e.path == path && e.flags.contains(EntryFlags::TRACKED));
+
+        let Some(entry) = entry else {
+            // Untracked: discarding it means deleting it
+            if full_path.is_file() || full_path.symlink_metadata().is_ok() {
+                fs::remove_file(&full_path)
+                    .with_context(|| format!("Failed to delete {}", path.display()))?;
+            }
+            continue;
+        };
+
+        let blob = store
+            .read_object(&ObjectType::Blob, &entry.oid)
+            .with_context(|| format!("Failed to read blob for {}", path.display()))?;
+
+        if full_path.symlink_metadata().is_ok() {
+            fs::remove_file(&full_path)
+                .with_context(|| format!("Failed to replace {}", path.display()))?;
+        }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if entry.file_mode == MODE_SYMLINK {
+            let target = String::from_utf8(blob).context("Symlink target is not valid UTF-8")?;
+            file_mode::write_symlink(&target, &full_path, symlinks)?;
+        } else {
+            fs::write(&full_path, line_endings.to_working_tree(&blob))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            if entry.file_mode == MODE_EXECUTABLE {
+                file_mode::set_executable(&full_path)?;
+            }
+        }
+
+        // The file now matches the index, so record its fresh stat data
+        let metadata = fs::symlink_metadata(&full_path)?;
+        entry.size = metadata.len();
+        entry.mtime_sec = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        entry
+            .flags
+            .remove(EntryFlags::MODIFIED | EntryFlags::DELETED | EntryFlags::ASSUME_UNCHANGED);
+    }
+
+    index.persist()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::commit_command::{commit, CommitOptions};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unstage_and_discard() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        let a = PathBuf::from("a.txt");
+        let b = PathBuf::from("b.txt");
+        fs::write(repo.join(&a), "one\n")?;
+        stage_paths(repo, std::slice::from_ref(&a))?;
+        commit(
+            repo,
+            CommitOptions {
+                message: "first".into(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )?;
+        let index_oid = |repo: &Path| -> Result<_> {
+            let index = HelixIndexData::load_or_rebuild(repo)?;
+            Ok(index.entries().iter().find(|e| e.path == a).map(|e| e.oid))
+        };
+        let head_oid = index_oid(repo)?;
+
+        // Stage a change and a new file, then unstage both
+        fs::write(repo.join(&a), "two\n")?;
+        fs::write(repo.join(&b), "new\n")?;
+        stage_paths(repo, &[a.clone(), b.clone()])?;
+        assert_ne!(index_oid(repo)?, head_oid);
+
+        unstage_paths(repo, &[a.clone(), b.clone()])?;
+        let index = HelixIndexData::load_or_rebuild(repo)?;
+        assert_eq!(index_oid(repo)?, head_oid);
+        assert!(index.get_staged().is_empty());
+        assert!(!index.is_tracked(&b));
+        assert_eq!(fs::read_to_string(repo.join(&a))?, "two\n");
+
+        // Discard restores the tracked file and deletes the untracked one
+        discard_paths(repo, &[a.clone(), b.clone()])?;
+        assert_eq!(fs::read_to_string(repo.join(&a))?, "one\n");
+        assert!(!repo.join(&b).exists());
+
+        // The command restores tracked files under a directory, and nothing else
+        fs::create_dir_all(repo.join("dir"))?;
+        fs::write(repo.join("dir/c.txt"), "three\n")?;
+        stage_paths(repo, &[PathBuf::from("dir/c.txt")])?;
+        fs::write(repo.join("dir/c.txt"), "changed\n")?;
+        fs::write(repo.join("dir/untracked.txt"), "keep\n")?;
+        let restored = restore(repo, &[PathBuf::from("dir")], &RestoreOptions::default())?;
+        assert_eq!(restored, vec![PathBuf::from("dir/c.txt")]);
+        assert_eq!(fs::read_to_string(repo.join("dir/c.txt"))?, "three\n");
+        assert!(repo.join("dir/untracked.txt").exists());
+
+        let options = RestoreOptions {
+            staged: true,
+            worktree: true,
+        };
+        restore(repo, &[PathBuf::from(".")], &options)?;
+        assert!(!HelixIndexData::load_or_rebuild(repo)?.is_tracked(Path::new("dir/c.txt")));
+        assert!(restore(repo, &[PathBuf::from("nope")], &options).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+/*
+`helix rev-map <git-sha|helix-hash>` - translate a commit id between Git and
+Helix using the map written by `helix init` and `helix import --update`.
+
+A full 40-character id is taken as a Git SHA and a 64-character one as a
+Helix hash; anything shorter is treated as a prefix of either and has to be
+unambiguous. Prints the counterpart id.
+*/
+use anyhow::{bail, Result};
+use std::path::Path;
+
+use crate::helix_index::rev_map::RevMap;
+use crate::sandbox_command::RepoContext;
+
+/// The id on the other side of `id`, in hex
+pub fn rev_map(repo_path: &Path, id: &str) -> Result<String> {
+    let context = RepoContext::detect(repo_path)?;
+    if !RevMap::exists(&context.repo_root) {
+        bail!("No Git history has been imported into this repository");
+    }
+
+    let map = RevMap::open(&context.repo_root)?;
+    Ok(map.lookup(id)?.counterpart_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::sync::SyncEngine;
+    use helix_protocol::hash::hash_to_hex;
+    use std::fs;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn git(path: &Path, args: &[&str]) -> Result<String> {
+        let output = Command::new("git").args(args).current_dir(path).output()?;
+        assert!(output.status.success(), "git {:?} failed", args);
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    #[test]
+    fn test_rev_map_translates_imported_commits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        git(repo, &["init", "-q"])?;
+        git(repo, &["config", "user.name", "Test"])?;
+        git(repo, &["config", "user.email", "test@test.com"])?;
+        fs::write(repo.join("a.txt"), "a")?;
+        git(repo, &["add", "a.txt"])?;
+        git(repo, &["commit", "-q", "-m", "first"])?;
+        let git_sha = git(repo, &["rev-parse", "HEAD"])?;
+
+        assert!(rev_map(repo, &git_sha).is_err());
+        SyncEngine::new(repo).import_from_git()?;
+
+        let helix_hex = rev_map(repo, &git_sha)?;
+        let head = crate::helix_index::commit::read_head(repo)?;
+        assert_eq!(helix_hex, hash_to_hex(&head));
+        assert_eq!(rev_map(repo, &helix_hex)?, git_sha);
+        assert_eq!(rev_map(repo, &git_sha[..10])?, helix_hex);
+
+        Ok(())
+    }
+}
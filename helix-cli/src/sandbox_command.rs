@@ -12,11 +12,16 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
 use crate::add_command::get_file_mode;
+use crate::author::resolve_author;
 use crate::branch_command::get_current_branch;
 use crate::checkout::{checkout_tree_to_path, CheckoutOptions};
 use crate::helix_index::commit::{read_head, Commit};
 use crate::helix_index::tree::{TreeBuilder, TreeStore};
 use crate::helix_index::{Entry, EntryFlags, Header, Reader, Writer};
+use crate::init_command::is_bare_repo;
+use crate::line_endings::LineEndings;
+use crate::promisor::Promised;
+use crate::worktree_command;
 use crate::{merge_tui, sandbox_tui};
 use helix_protocol::hash::{hash_bytes, hash_to_hex, hex_to_hash, Hash};
 
@@ -132,14 +137,18 @@ impl SandboxManifest {
     }
 }
 
-/// Represents the current working context - either main repo or a sandbox
+/// Represents the current working context - the main repo, a sandbox or a linked worktree
 #[derive(Clone, Debug)]
 pub struct RepoContext {
     pub repo_root: PathBuf,
     pub sandbox_root: Option<PathBuf>,
+    /// `.helix/worktrees/<name>` of the main repo when in a linked worktree
+    pub worktree_dir: Option<PathBuf>,
     pub workdir: PathBuf,
     pub index_path: PathBuf,
     pub head_path: PathBuf,
+    /// A bare repo has no working tree or index, only objects and refs
+    pub bare: bool,
 }
 
 impl RepoContext {
@@ -151,9 +160,25 @@ impl RepoContext {
             return Ok(Self {
                 repo_root: repo_root.clone(),
                 sandbox_root: Some(sandbox_root.clone()),
+                worktree_dir: None,
                 workdir: sandbox_root.join("workdir"),
                 index_path: sandbox_root.join(".helix").join("helix.idx"),
                 head_path: sandbox_root.join("HEAD"), // Sandbox HEAD
+                bare: false,
+            });
+        }
+
+        // Check if we're inside a linked worktree (its `.helix` is a pointer file)
+        if let Some((worktree_root, worktree_dir)) = detect_worktree_from_path(&start_path)? {
+            let repo_root = worktree_command::repo_root_of(&worktree_dir)?;
+            return Ok(Self {
+                repo_root,
+                sandbox_root: None,
+                index_path: worktree_dir.join(".helix").join("helix.idx"),
+                head_path: worktree_dir.join("HEAD"), // Worktree HEAD
+                worktree_dir: Some(worktree_dir),
+                workdir: worktree_root,
+                bare: false,
             });
         }
 
@@ -162,16 +187,35 @@ impl RepoContext {
         Ok(Self {
             repo_root: repo_root.clone(),
             sandbox_root: None,
+            worktree_dir: None,
             workdir: repo_root.clone(),
             index_path: repo_root.join(".helix").join("helix.idx"),
             head_path: repo_root.join(".helix").join("HEAD"), // Main repo HEAD
+            bare: is_bare_repo(&repo_root),
         })
     }
+
+    /// Fail for a bare repo, for commands that read or change the working tree or index
+    pub fn require_work_tree(&self) -> Result<()> {
+        if self.bare {
+            bail!(
+                "{} is a bare repository, so it has no working tree",
+                self.repo_root.display()
+            );
+        }
+        Ok(())
+    }
+
     /// Check if we're in a sandbox
     pub fn is_sandbox(&self) -> bool {
         self.sandbox_root.is_some()
     }
 
+    /// Check if we're in a linked worktree
+    pub fn is_worktree(&self) -> bool {
+        self.worktree_dir.is_some()
+    }
+
     /// Get sandbox name if in a sandbox
     pub fn sandbox_name(&self) -> Option<String> {
         self.sandbox_root.as_ref().and_then(|p| {
@@ -210,6 +254,22 @@ fn detect_sandbox_from_path(path: &Path) -> Option<(PathBuf, PathBuf)> {
     None
 }
 
+/// Find the nearest `.helix`; if it's a worktree pointer file rather than a directory,
+/// return the worktree root and the admin dir it points to
+fn detect_worktree_from_path(path: &Path) -> Result<Option<(PathBuf, PathBuf)>> {
+    for ancestor in path.ancestors() {
+        let helix = ancestor.join(".helix");
+        if helix.is_dir() {
+            return Ok(None);
+        }
+        if helix.is_file() {
+            let worktree_dir = worktree_command::read_link(&helix)?;
+            return Ok(Some((ancestor.to_path_buf(), worktree_dir)));
+        }
+    }
+    Ok(None)
+}
+
 /// Find repository root by looking for .helix directory
 fn find_repo_root(start: &Path) -> Result<PathBuf> {
     for ancestor in start.ancestors() {
@@ -379,7 +439,7 @@ fn print_sandbox_created(name: &str, workdir: &Path, branch_name: &str, files_co
 
 /// builds the index entries from commit
 // in the sync.rs module, we have a build_index_entries_from_git, we might at some point want to ccombine and generalize the two?
-fn build_index_entries_from_commit(
+pub(crate) fn build_index_entries_from_commit(
     repo_path: &Path,
     commit_hash: &Hash,
     workdir: &Path,
@@ -397,9 +457,17 @@ fn build_index_entries_from_commit(
 
     let files = tree_store.collect_all_files(&tree_hash)?;
 
+    // Files a partial clone left on the remote aren't on disk; they aren't deletions
+    let promised = Promised::load(repo_path);
     let mut entries: Vec<Entry> = files
         .into_iter()
-        .map(|(path, oid)| Entry::from_blob(path, oid, workdir))
+        .map(|(path, oid)| {
+            let mut entry = Entry::from_blob(path, oid, workdir);
+            if promised.contains(&oid) && !store.has_object(&ObjectType::Blob, &oid) {
+                entry.flags |= EntryFlags::ASSUME_UNCHANGED;
+            }
+            entry
+        })
         .collect();
 
     entries.sort_by(|a, b| a.path.cmp(&b.path));
@@ -407,7 +475,7 @@ fn build_index_entries_from_commit(
     Ok(entries)
 }
 
-fn write_sandbox_index(sandbox_root: &Path, entries: &[Entry]) -> Result<()> {
+pub(crate) fn write_sandbox_index(sandbox_root: &Path, entries: &[Entry]) -> Result<()> {
     // Create .helix subdir in sandbox (mirrors repo structure)
     let helix_dir = sandbox_root.join(".helix");
     fs::create_dir_all(&helix_dir)?;
@@ -621,7 +689,8 @@ pub fn get_sandbox_changes(repo_path: &Path, name: &str) -> Result<Vec<SandboxCh
 
     let workdir_files = collect_files_from_workdir(&workdir)?;
 
-    let changes = compute_sandbox_diff(&head_files, &workdir_files, &workdir)?;
+    let line_endings = LineEndings::load(repo_path);
+    let changes = compute_sandbox_diff(&head_files, &workdir_files, &workdir, line_endings)?;
 
     Ok(changes)
 }
@@ -692,6 +761,7 @@ fn compute_sandbox_diff(
     base_files: &HashMap<PathBuf, Hash>,
     workdir_files: &HashSet<PathBuf>,
     workdir: &Path,
+    line_endings: LineEndings,
 ) -> Result<Vec<SandboxChange>> {
     let mut changes = Vec::new();
 
@@ -699,7 +769,7 @@ fn compute_sandbox_diff(
     for path in workdir_files {
         let full_path = workdir.join(path);
         let content = fs::read(&full_path)?;
-        let current_hash = hash_bytes(&content);
+        let current_hash = hash_bytes(&line_endings.normalize(&content));
 
         match base_files.get(path) {
             None => {
@@ -766,10 +836,7 @@ pub fn commit_sandbox(repo_path: &Path, name: &str, options: CommitOptions) -> R
     let tree_hash = build_tree_from_workdir(&store, &workdir, repo_path)?;
 
     // Get author
-    let author = match options.author {
-        Some(a) => a,
-        None => get_author(repo_path)?,
-    };
+    let author = resolve_author(repo_path, options.author.as_deref())?;
 
     // Create commit with base as parent
     let commit_hash = Commit::new(tree_hash, vec![base_commit], author, options.message);
@@ -857,7 +924,7 @@ pub fn merge_sandbox(repo_path: &Path, name: &str, options: MergeOptions) -> Res
     }
 
     // Need a real merge - launch TUI
-    let author = get_author(repo_path)?;
+    let author = resolve_author(repo_path, None)?;
 
     let mut app = merge_tui::app::App::new(
         repo_path,
@@ -904,7 +971,7 @@ pub fn merge_sandbox(repo_path: &Path, name: &str, options: MergeOptions) -> Res
     }
 }
 
-fn update_index_from_commit(repo_path: &Path, commit_hash: &Hash) -> Result<()> {
+pub(crate) fn update_index_from_commit(repo_path: &Path, commit_hash: &Hash) -> Result<()> {
     let entries = build_index_entries_from_commit(repo_path, commit_hash, repo_path)?;
 
     // Load existing index to get current generation
@@ -931,12 +998,13 @@ fn build_tree_from_workdir(
     repo_path: &Path,
 ) -> Result<Hash> {
     let files = collect_files_from_workdir(workdir)?;
+    let line_endings = LineEndings::load(repo_path);
     let mut entries = Vec::new();
 
     for path in files {
         let full_path = workdir.join(&path);
         let content = fs::read(&full_path)?;
-        let oid = store.write_object(&ObjectType::Blob, &content)?;
+        let oid = store.write_object(&ObjectType::Blob, &line_endings.normalize(&content))?;
 
         let metadata = fs::metadata(&full_path)?;
         let file_mode = get_file_mode(&metadata);
@@ -958,27 +1026,6 @@ fn build_tree_from_workdir(
     tree_builder.build_from_entries(&entries)
 }
 
-fn get_author(repo_path: &Path) -> Result<String> {
-    let config_path = repo_path.join("helix.toml");
-
-    if config_path.exists() {
-        let content = fs::read_to_string(&config_path)?;
-
-        if let Ok(config) = content.parse::<toml::Value>() {
-            if let Some(user) = config.get("user") {
-                let name = user.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                let email = user.get("email").and_then(|v| v.as_str()).unwrap_or("");
-
-                if !name.is_empty() && !email.is_empty() {
-                    return Ok(format!("{} <{}>", name, email));
-                }
-            }
-        }
-    }
-
-    bail!("Author not configured. Add [user] section to helix.toml")
-}
-
 fn remove_empty_parents(dir: &Path, stop_at: &Path) {
     let mut current = dir;
     while current != stop_at {
@@ -0,0 +1,475 @@
+/*
+`helix serve --local` - a query daemon for editor integrations.
+
+Editor plugins keep one connection open and ask for status, diffs, blame and
+history instead of spawning `helix` for every keystroke. The protocol is
+JSON-RPC 2.0 with one message per line, over a Unix socket (by default
+helix.sock next to the context's index) or over stdin/stdout with --stdio.
+
+  {"jsonrpc":"2.0","id":1,"method":"status"}
+  {"jsonrpc":"2.0","id":1,"result":{"staged":[],"modified":["src/lib.rs"],...}}
+
+Methods:
+
+  status                                  staged, modified, deleted and untracked paths
+                                          (untracked directories as one "dir/" entry)
+  diff   {revs?, staged?, paths?, context?}  like `helix diff`, one patch per file
+  log    {rev?, limit?}                   commits reachable from rev (HEAD), newest first
+  blame  {path, rev?}                     the commit that last changed each line
+
+Status is the expensive query, so its answer is cached and reused until the
+file system monitor sees a working tree change or the index file changes
+(checked by its size and mtime too, since a sandbox index lives outside the
+watched tree). Everything else reads immutable objects and is computed per
+request.
+*/
+use anyhow::{anyhow, bail, Context, Result};
+use helix_protocol::hash::hash_to_hex;
+use helix_protocol::storage::FsObjectStore;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use crate::blame::blame;
+use crate::diff_command::{diff, resolve_revision, DiffOptions};
+use crate::fsmonitor::FSMonitor;
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::commit::CommitStore;
+use crate::helix_index::graph::walk_commits;
+use crate::helix_index::untracked::scan_untracked_cached;
+use crate::ignore::IgnoreRules;
+use crate::ls_files_command::{ls_files, LsFilesOptions};
+use crate::sandbox_command::RepoContext;
+
+const DEFAULT_LOG_LIMIT: usize = 100;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const QUERY_FAILED: i64 = -32000;
+
+/// Size and mtime of the index and its journal
+type IndexStamp = Vec<Option<(u64, SystemTime)>>;
+
+struct CachedStatus {
+    stamp: IndexStamp,
+    result: Value,
+}
+
+pub struct QueryServer {
+    repo_path: PathBuf,
+    index_path: PathBuf,
+    monitor: Option<Mutex<FSMonitor>>,
+    status: Mutex<Option<CachedStatus>>,
+}
+
+impl QueryServer {
+    /// With `watch`, status answers are cached and invalidated by a file
+    /// system monitor; without it every status query rescans.
+    pub fn new(repo_path: &Path, watch: bool) -> Result<Self> {
+        let context = RepoContext::detect(repo_path)?;
+        let monitor = if watch {
+            let mut monitor = FSMonitor::new(&context.workdir)?;
+            monitor.start_watching_repo()?;
+            Some(Mutex::new(monitor))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            repo_path: context.workdir.clone(),
+            index_path: context.index_path,
+            monitor,
+            status: Mutex::new(None),
+        })
+    }
+
+    /// Default socket path for the repository at `repo_path`
+    pub fn socket_path(repo_path: &Path) -> Result<PathBuf> {
+        Ok(RepoContext::detect(repo_path)?
+            .index_path
+            .with_file_name("helix.sock"))
+    }
+
+    /// Handle one request line. Returns None for notifications, which get no
+    /// response.
+    pub fn handle_line(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "Missing method".to_string(),
+            ));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "status" => self.status(),
+            "diff" => self.diff(&params),
+            "log" => self.log(&params),
+            "blame" => self.blame(&params),
+            _ => {
+                return id.map(|id| {
+                    error_response(id, METHOD_NOT_FOUND, format!("Unknown method '{}'", method))
+                })
+            }
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => match e.downcast_ref::<InvalidParams>() {
+                Some(InvalidParams(message)) => error_response(id, INVALID_PARAMS, message.clone()),
+                None => error_response(id, QUERY_FAILED, format!("{:#}", e)),
+            },
+        })
+    }
+
+    /// Answer requests from `reader` until it closes
+    pub fn serve_connection(&self, reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_line(&line) {
+                writeln!(writer, "{}", response)?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<Value> {
+        let mut cache = self.status.lock().unwrap();
+        let stamp = index_stamp(&self.index_path);
+
+        if let Some(monitor) = &self.monitor {
+            let monitor = monitor.lock().unwrap();
+            let unchanged = monitor.dirty_count() == 0 && !monitor.index_changed();
+            if let Some(cached) = cache.as_ref() {
+                if unchanged && cached.stamp == stamp {
+                    return Ok(cached.result.clone());
+                }
+            }
+            // Cleared before the scan, so changes made during it invalidate again
+            monitor.clear_dirty();
+            monitor.clear_index_flag();
+        }
+
+        let result = status_report(&self.repo_path)?;
+
+        if self.monitor.is_some() {
+            *cache = Some(CachedStatus {
+                stamp,
+                result: result.clone(),
+            });
+        }
+        Ok(result)
+    }
+
+    fn diff(&self, params: &Value) -> Result<Value> {
+        let revs = string_list(params, "revs")?;
+        let defaults = DiffOptions::default();
+        let options = DiffOptions {
+            staged: params
+                .get("staged")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            text: false,
+            context: params
+                .get("context")
+                .and_then(Value::as_u64)
+                .map_or(defaults.context, |c| c as usize),
+            paths: string_list(params, "paths")?
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            whitespace: defaults.whitespace,
+        };
+
+        let diffs = diff(&self.repo_path, &revs, &options)?;
+        Ok(Value::Array(
+            diffs
+                .iter()
+                .map(|d| {
+                    json!({
+                        "old_path": d.old_path.as_ref().map(|p| p.to_string_lossy()),
+                        "new_path": d.new_path.as_ref().map(|p| p.to_string_lossy()),
+                        "patch": d.render(options.text, options.context, options.whitespace),
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    fn log(&self, params: &Value) -> Result<Value> {
+        let rev = optional_str(params, "rev")?.unwrap_or("HEAD");
+        let limit = params
+            .get("limit")
+            .and_then(Value::as_u64)
+            .map_or(DEFAULT_LOG_LIMIT, |l| l as usize);
+
+        let context = RepoContext::detect(&self.repo_path)?;
+        let store = CommitStore::new(&context.repo_root, FsObjectStore::new(&context.repo_root))?;
+        let tip = resolve_revision(&self.repo_path, rev)?;
+        let commits = walk_commits(&store, &[tip], limit, None)?;
+
+        Ok(Value::Array(
+            commits
+                .iter()
+                .map(|c| {
+                    json!({
+                        "hash": hash_to_hex(&c.commit_hash),
+                        "parents": c.parents.iter().map(hash_to_hex).collect::<Vec<_>>(),
+                        "author": c.author,
+                        "author_time": c.author_time,
+                        "commit_time": c.commit_time,
+                        "message": c.message,
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    fn blame(&self, params: &Value) -> Result<Value> {
+        let path = optional_str(params, "path")?
+            .ok_or_else(|| InvalidParams("Missing 'path'".to_string()))?;
+        let rev = optional_str(params, "rev")?;
+
+        let lines = blame(&self.repo_path, Path::new(path), rev)?;
+        Ok(Value::Array(
+            lines
+                .iter()
+                .map(|l| {
+                    json!({
+                        "line": l.line,
+                        "commit": hash_to_hex(&l.commit),
+                        "author": l.author,
+                        "author_time": l.author_time,
+                        "text": l.text,
+                    })
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Serve on a Unix socket until the process is stopped
+pub fn serve_socket(repo_path: &Path, socket: Option<&Path>) -> Result<()> {
+    let socket = match socket {
+        Some(socket) => socket.to_path_buf(),
+        None => QueryServer::socket_path(repo_path)?,
+    };
+    if socket.exists() {
+        if UnixStream::connect(&socket).is_ok() {
+            bail!(
+                "A query server is already listening on {}",
+                socket.display()
+            );
+        }
+        // Left behind by a server that didn't shut down cleanly
+        fs::remove_file(&socket)
+            .with_context(|| format!("Failed to remove stale socket {}", socket.display()))?;
+    }
+
+    let server = Arc::new(QueryServer::new(repo_path, true)?);
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to bind {}", socket.display()))?;
+    eprintln!("Listening on {}", socket.display());
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let server = server.clone();
+        thread::spawn(move || {
+            let reader = match stream.try_clone() {
+                Ok(reader) => BufReader::new(reader),
+                Err(_) => return,
+            };
+            // A client going away mid-response is not the server's problem
+            let _ = server.serve_connection(reader, stream);
+        });
+    }
+
+    Ok(())
+}
+
+/// Serve a single client over stdin/stdout
+pub fn serve_stdio(repo_path: &Path) -> Result<()> {
+    let server = QueryServer::new(repo_path, true)?;
+    server.serve_connection(std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+/// Bad request parameters, reported with the JSON-RPC invalid params code
+#[derive(Debug)]
+struct InvalidParams(String);
+
+impl std::fmt::Display for InvalidParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidParams {}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn optional_str<'a>(params: &'a Value, key: &str) -> Result<Option<&'a str>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(anyhow!(InvalidParams(format!(
+            "'{}' must be a string",
+            key
+        )))),
+    }
+}
+
+fn string_list(params: &Value, key: &str) -> Result<Vec<String>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| {
+                item.as_str().map(str::to_string).ok_or_else(|| {
+                    anyhow!(InvalidParams(format!(
+                        "'{}' must be a list of strings",
+                        key
+                    )))
+                })
+            })
+            .collect(),
+        Some(_) => Err(anyhow!(InvalidParams(format!(
+            "'{}' must be a list of strings",
+            key
+        )))),
+    }
+}
+
+fn index_stamp(index_path: &Path) -> IndexStamp {
+    let journal = index_path.with_file_name("helix.idx.journal");
+    [index_path, journal.as_path()]
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.len(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+/// Staged, modified, deleted and untracked paths: the answer to `status`, and what
+/// `helix status --json` prints
+pub fn status_report(repo_path: &Path) -> Result<Value> {
+    let list = |options: LsFilesOptions| -> Result<Vec<String>> {
+        Ok(ls_files(repo_path, &options)?
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect())
+    };
+    Ok(json!({
+        "staged": list(LsFilesOptions { staged: true, ..Default::default() })?,
+        "modified": list(LsFilesOptions { modified: true, ..Default::default() })?,
+        "deleted": list(LsFilesOptions { deleted: true, ..Default::default() })?,
+        "untracked": untracked(repo_path)?,
+    }))
+}
+
+/// Untracked paths as status shows them, with untracked directories collapsed
+fn untracked(repo_path: &Path) -> Result<Vec<String>> {
+    let context = RepoContext::detect(repo_path)?;
+    let index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+    let rules = IgnoreRules::load(&context.workdir);
+    // The cache is only read here; status keeps it up to date
+    let (paths, _) =
+        scan_untracked_cached(&context.workdir, &index, &rules, index.untracked_cache())?;
+    Ok(paths
+        .iter()
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::stage_paths;
+    use crate::commit_command::{commit, CommitOptions};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_query_methods() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path();
+        crate::init_command::init_helix_repo(repo, None)?;
+
+        fs::write(repo.join("a.txt"), "one\n")?;
+        stage_paths(repo, &[PathBuf::from("a.txt")])?;
+        let head = commit(
+            repo,
+            CommitOptions {
+                message: "first".into(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )?;
+        fs::write(repo.join("a.txt"), "one\ntwo\n")?;
+
+        let server = QueryServer::new(repo, false)?;
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"status"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"log","params":{"limit":5}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"diff"}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"blame","params":{"path":"a.txt"}}"#,
+            r#"{"jsonrpc":"2.0","method":"status"}"#,
+            r#"{"jsonrpc":"2.0","id":5,"method":"blame"}"#,
+            r#"{"jsonrpc":"2.0","id":6,"method":"rebase"}"#,
+            "not json",
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        server.serve_connection(input.as_bytes(), &mut output)?;
+
+        let responses: Vec<Value> = String::from_utf8(output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<_, _>>()?;
+        // The notification gets no response
+        assert_eq!(responses.len(), 7);
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["modified"], json!(["a.txt"]));
+        assert_eq!(responses[0]["result"]["staged"], json!([]));
+
+        assert_eq!(responses[1]["result"][0]["hash"], hash_to_hex(&head));
+        assert_eq!(responses[1]["result"][0]["message"], "first");
+
+        let patch = responses[2]["result"][0]["patch"].as_str().unwrap_or("");
+        assert!(patch.contains("+two"));
+
+        assert_eq!(responses[3]["result"][0]["commit"], hash_to_hex(&head));
+        assert_eq!(responses[3]["result"][0]["text"], "one");
+
+        assert_eq!(responses[4]["error"]["code"], INVALID_PARAMS);
+        assert_eq!(responses[5]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[6]["error"]["code"], PARSE_ERROR);
+
+        Ok(())
+    }
+}
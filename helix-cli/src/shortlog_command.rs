@@ -0,0 +1,276 @@
+/*
+`helix shortlog` - commits grouped by author, for release notes and
+contribution counts.
+
+  helix shortlog [RANGE] [-- PATH...]
+      RANGE is REV (its whole history, default HEAD) or FROM..TO (what TO has
+      and FROM doesn't). With paths, only commits that changed something
+      under one of them count.
+  -s        one line per author: the number of commits and the name
+  -n        most commits first, instead of by name
+  -e        group by name and email rather than name alone
+  --no-merges        leave out merge commits
+  --no-mailmap       count identities as recorded, without .helixmailmap
+
+Authors go through .helixmailmap first (see mailmap), so one person committing
+under several spellings is counted once. A commit touches a path when the
+tree or blob at that path differs from its first parent's (a root commit
+touches every path it has), which only reads the trees along the path.
+Subjects under each author are listed oldest first.
+*/
+use anyhow::{bail, Result};
+use helix_protocol::hash::Hash;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::diff_command::resolve_revision;
+use crate::helix_index::commit::{Commit, CommitStore};
+use crate::mailmap::Mailmap;
+use crate::plumbing_command::lookup_path;
+
+#[derive(Debug, Default)]
+pub struct ShortlogOptions {
+    pub numbered: bool,
+    pub email: bool,
+    pub no_merges: bool,
+    pub no_mailmap: bool,
+    pub paths: Vec<PathBuf>,
+}
+
+/// One author's commits, oldest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorCommits {
+    pub author: String,
+    pub subjects: Vec<String>,
+}
+
+/// Group the commits in `range` by author, in the order the options ask for
+pub fn shortlog(
+    repo_path: &Path,
+    range: Option<&str>,
+    options: &ShortlogOptions,
+) -> Result<Vec<AuthorCommits>> {
+    let (from, to) = match range.unwrap_or("HEAD").split_once("..") {
+        Some((from, to)) => (
+            Some(if from.is_empty() { "HEAD" } else { from }),
+            if to.is_empty() { "HEAD" } else { to },
+        ),
+        None => (None, range.unwrap_or("HEAD")),
+    };
+    let tip = resolve_revision(repo_path, to)?;
+    let store = FsObjectStore::new(repo_path);
+    let commits = CommitStore::new(repo_path, store.clone())?;
+
+    let excluded: HashSet<Hash> = match from {
+        Some(from) => {
+            let from = resolve_revision(repo_path, from)?;
+            reachable(&commits, from, &HashSet::new())?
+                .into_iter()
+                .collect()
+        }
+        None => HashSet::new(),
+    };
+    let mailmap = if options.no_mailmap {
+        Mailmap::default()
+    } else {
+        Mailmap::load(repo_path)
+    };
+
+    let mut by_author: BTreeMap<String, Vec<(u64, String)>> = BTreeMap::new();
+    // Oldest first, so commits made in the same second keep their order
+    for hash in reachable(&commits, tip, &excluded)?.into_iter().rev() {
+        let commit = commits.read_commit(&hash)?;
+        if options.no_merges && commit.is_merge() {
+            continue;
+        }
+        if !options.paths.is_empty() && !touches_paths(&store, &commits, &commit, &options.paths) {
+            continue;
+        }
+        let author = mailmap.canonical(&commit.author);
+        let key = if options.email {
+            author
+        } else {
+            author
+                .rsplit_once('<')
+                .map(|(name, _)| name.trim().to_string())
+                .unwrap_or(author)
+        };
+        by_author
+            .entry(key)
+            .or_default()
+            .push((commit.author_time, commit.summary().to_string()));
+    }
+
+    let mut authors: Vec<AuthorCommits> = by_author
+        .into_iter()
+        .map(|(author, mut commits)| {
+            commits.sort_by_key(|(time, _)| *time);
+            AuthorCommits {
+                author,
+                subjects: commits.into_iter().map(|(_, subject)| subject).collect(),
+            }
+        })
+        .collect();
+    if options.numbered {
+        // Stable, so authors with as many commits stay in name order
+        authors.sort_by_key(|a| std::cmp::Reverse(a.subjects.len()));
+    }
+    Ok(authors)
+}
+
+pub fn print_shortlog(authors: &[AuthorCommits], summary: bool) {
+    for entry in authors {
+        if summary {
+            println!("{:6}\t{}", entry.subjects.len(), entry.author);
+            continue;
+        }
+        println!("{} ({}):", entry.author, entry.subjects.len());
+        for subject in &entry.subjects {
+            println!("      {subject}");
+        }
+        println!();
+    }
+}
+
+/// Commits reachable from `tip` through any parent, not going into `excluded`, newest
+/// first
+fn reachable(commits: &CommitStore, tip: Hash, excluded: &HashSet<Hash>) -> Result<Vec<Hash>> {
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    let mut queue = VecDeque::from([tip]);
+    while let Some(hash) = queue.pop_front() {
+        if excluded.contains(&hash) || !seen.insert(hash) {
+            continue;
+        }
+        // History that was never fetched ends the walk there
+        let Ok(commit) = commits.read_commit(&hash) else {
+            continue;
+        };
+        order.push(hash);
+        queue.extend(commit.parents);
+    }
+    if order.is_empty() {
+        bail!("No commits in range");
+    }
+    Ok(order)
+}
+
+/// Whether `commit` changed anything under one of `paths` relative to its first parent
+fn touches_paths(
+    store: &FsObjectStore,
+    commits: &CommitStore,
+    commit: &Commit,
+    paths: &[PathBuf],
+) -> bool {
+    let parent_tree = commit
+        .parents
+        .first()
+        .and_then(|parent| commits.read_commit(parent).ok())
+        .map(|parent| parent.tree_hash);
+    paths.iter().any(|path| {
+        let here = lookup_path(store, commit.tree_hash, path).ok();
+        let before = parent_tree.and_then(|tree| lookup_path(store, tree, path).ok());
+        here != before
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::{Tree, TreeEntry, TreeStore};
+    use helix_protocol::hash::hash_to_hex;
+    use helix_protocol::message::ObjectType;
+    use std::fs;
+
+    #[test]
+    fn test_shortlog_groups_by_canonical_author() -> Result<()> {
+        let temp = tempfile::tempdir()?;
+        let repo = temp.path();
+        fs::create_dir_all(repo.join(".helix"))?;
+        fs::write(
+            repo.join(".helixmailmap"),
+            "Ana Lima <ana@example.com> <ana@old.example>\n",
+        )?;
+        let store = FsObjectStore::new(repo);
+        let commits = CommitStore::new(repo, store.clone())?;
+
+        // Each commit adds one file to the same tree
+        let mut tree = Tree::new();
+        let mut parents = Vec::new();
+        let history = [
+            ("Ana Lima <ana@example.com>", "a.md", "Add docs"),
+            ("ana <ana@old.example>", "lib.rs", "Add lib"),
+            ("Bo Chen <bo@example.com>", "main.rs", "Add main"),
+            ("Bo Chen <bo@example.com>", "b.md", "More docs"),
+            ("Bo Chen <bo@example.com>", "README", "Readme"),
+        ];
+        let mut hashes = Vec::new();
+        for (i, (author, path, message)) in history.iter().enumerate() {
+            let blob = store.write_object(&ObjectType::Blob, path.as_bytes())?;
+            tree.add_entry(TreeEntry::new_file(
+                path.to_string(),
+                blob,
+                0o100644,
+                path.len() as u64,
+            ));
+            tree.sort();
+            let tree_hash = TreeStore::for_repo(repo).write(&tree)?;
+            let commit = Commit::new(tree_hash, parents, author.to_string(), message.to_string())
+                .with_author_time(1_700_000_000 + i as u64);
+            let hash = commits.write_commit(&commit)?;
+            hashes.push(hash);
+            parents = vec![hash];
+        }
+        let head = hash_to_hex(hashes.last().unwrap());
+
+        let all = shortlog(repo, Some(&head), &ShortlogOptions::default())?;
+        assert_eq!(
+            all,
+            vec![
+                AuthorCommits {
+                    author: "Ana Lima".into(),
+                    subjects: vec!["Add docs".into(), "Add lib".into()],
+                },
+                AuthorCommits {
+                    author: "Bo Chen".into(),
+                    subjects: vec!["Add main".into(), "More docs".into(), "Readme".into()],
+                },
+            ]
+        );
+
+        let numbered = ShortlogOptions {
+            numbered: true,
+            email: true,
+            no_mailmap: true,
+            ..Default::default()
+        };
+        let counts: Vec<(String, usize)> = shortlog(repo, Some(&head), &numbered)?
+            .into_iter()
+            .map(|a| (a.author, a.subjects.len()))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("Bo Chen <bo@example.com>".to_string(), 3),
+                ("Ana Lima <ana@example.com>".to_string(), 1),
+                ("ana <ana@old.example>".to_string(), 1),
+            ]
+        );
+
+        // A range, and a path filter
+        let range = format!("{}..{head}", hash_to_hex(&hashes[2]));
+        let recent = shortlog(repo, Some(&range), &ShortlogOptions::default())?;
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].subjects, vec!["More docs", "Readme"]);
+
+        let docs = ShortlogOptions {
+            paths: vec![PathBuf::from("b.md"), PathBuf::from("a.md")],
+            ..Default::default()
+        };
+        let docs = shortlog(repo, Some(&head), &docs)?;
+        assert_eq!(docs[0].subjects, vec!["Add docs"]);
+        assert_eq!(docs[1].subjects, vec!["More docs"]);
+        Ok(())
+    }
+}
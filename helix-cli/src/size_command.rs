@@ -0,0 +1,390 @@
+/*
+`helix size` - where a repository's bytes go.
+
+Reports, to help find what is bloating a repo:
+
+1. objects      - count and stored (compressed) size of each object type
+2. disk         - size of .helix against the size of the working tree
+3. largest      - the biggest blobs in the store, named by their path in HEAD
+                  when HEAD has them
+4. directories  - the biggest directories in HEAD, by the files under them
+5. growth       - for each of the last --commits commits on HEAD's first-parent
+                  line, the stored size of the blobs it added over its parent
+
+Object sizes are what each object takes in .helix/objects, i.e. compressed;
+directory sizes are the files' own (uncompressed) sizes as recorded in the
+trees. Nothing is decompressed except trees and commits.
+*/
+use anyhow::Result;
+use helix_protocol::hash::{hash_to_hex, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::binary::format_size;
+use crate::helix_index::commit::{read_head, CommitStore};
+use crate::helix_index::tree::{EntryType, TreeStore};
+
+#[derive(Debug, Clone)]
+pub struct SizeOptions {
+    /// How many blobs and directories to list
+    pub top: usize,
+    /// How many commits of history to report growth for
+    pub commits: usize,
+}
+
+impl Default for SizeOptions {
+    fn default() -> Self {
+        Self {
+            top: 10,
+            commits: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeStats {
+    pub count: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LargeBlob {
+    pub hash: Hash,
+    /// Stored size
+    pub bytes: u64,
+    /// Where HEAD has it, if it does
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommitGrowth {
+    pub hash: Hash,
+    pub summary: String,
+    /// Blobs in this commit's tree that its first parent's tree doesn't have
+    pub new_blobs: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SizeReport {
+    /// Blobs, trees, commits and tags, in that order
+    pub objects: [(&'static str, TypeStats); 4],
+    pub helix_bytes: u64,
+    /// None for a bare repo
+    pub work_tree_bytes: Option<u64>,
+    /// Largest first
+    pub largest_blobs: Vec<LargeBlob>,
+    /// Largest first, by the size of every file below them in HEAD
+    pub largest_dirs: Vec<(PathBuf, u64)>,
+    /// Newest first
+    pub growth: Vec<CommitGrowth>,
+}
+
+impl SizeReport {
+    pub fn print_summary(&self) {
+        println!("Objects:");
+        for (name, stats) in &self.objects {
+            println!(
+                "  {:<8} {:>8}  {:>10}",
+                name,
+                stats.count,
+                format_size(stats.bytes)
+            );
+        }
+        let total = self
+            .objects
+            .iter()
+            .fold(TypeStats::default(), |a, (_, s)| TypeStats {
+                count: a.count + s.count,
+                bytes: a.bytes + s.bytes,
+            });
+        println!(
+            "  {:<8} {:>8}  {:>10}",
+            "total",
+            total.count,
+            format_size(total.bytes)
+        );
+
+        println!();
+        println!("On disk:");
+        println!("  .helix      {}", format_size(self.helix_bytes));
+        match self.work_tree_bytes {
+            Some(bytes) => println!("  work tree   {}", format_size(bytes)),
+            None => println!("  work tree   (bare repository)"),
+        }
+
+        if !self.largest_blobs.is_empty() {
+            println!();
+            println!("Largest blobs (stored size):");
+            for blob in &self.largest_blobs {
+                let name = match &blob.path {
+                    Some(path) => path.display().to_string(),
+                    None => "(not in HEAD)".to_string(),
+                };
+                println!(
+                    "  {:>10}  {}  {}",
+                    format_size(blob.bytes),
+                    &hash_to_hex(&blob.hash)[..8],
+                    name
+                );
+            }
+        }
+
+        if !self.largest_dirs.is_empty() {
+            println!();
+            println!("Largest directories in HEAD:");
+            for (dir, bytes) in &self.largest_dirs {
+                println!("  {:>10}  {}/", format_size(*bytes), dir.display());
+            }
+        }
+
+        if !self.growth.is_empty() {
+            println!();
+            println!("Growth over the last {} commits:", self.growth.len());
+            for commit in &self.growth {
+                println!(
+                    "  {:>10}  {} blobs  {}  {}",
+                    format!("+{}", format_size(commit.bytes)),
+                    commit.new_blobs,
+                    &hash_to_hex(&commit.hash)[..8],
+                    commit.summary
+                );
+            }
+        }
+    }
+}
+
+pub fn size(repo_path: &Path, options: SizeOptions) -> Result<SizeReport> {
+    let store = FsObjectStore::new(repo_path);
+    let helix_dir = repo_path.join(".helix");
+
+    let mut report = SizeReport::default();
+    let mut blobs = Vec::new();
+    for (i, (name, ty)) in [
+        ("blobs", ObjectType::Blob),
+        ("trees", ObjectType::Tree),
+        ("commits", ObjectType::Commit),
+        ("tags", ObjectType::Tag),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let mut stats = TypeStats::default();
+        for hash in store.list_object_hashes(&ty)? {
+            let bytes = store.object_disk_size(&ty, &hash).unwrap_or(0);
+            stats.count += 1;
+            stats.bytes += bytes;
+            if ty == ObjectType::Blob {
+                blobs.push((hash, bytes));
+            }
+        }
+        report.objects[i] = (name, stats);
+    }
+
+    report.helix_bytes = dir_size(&helix_dir, None);
+    report.work_tree_bytes =
+        (!helix_dir.join("bare").exists()).then(|| dir_size(repo_path, Some(&helix_dir)));
+
+    // An unborn or bare HEAD just leaves the HEAD sections empty
+    let trees = TreeStore::for_repo(repo_path);
+    let commits = CommitStore::new(repo_path, store.clone())?;
+    let head = read_head(repo_path)
+        .ok()
+        .and_then(|hash| commits.read_commit(&hash).ok());
+
+    let mut head_paths = HashMap::new();
+    if let Some(head) = &head {
+        let mut dirs = HashMap::new();
+        walk_tree(
+            &trees,
+            &head.tree_hash,
+            Path::new(""),
+            &mut head_paths,
+            &mut dirs,
+        )?;
+        let mut dirs: Vec<(PathBuf, u64)> = dirs.into_iter().collect();
+        dirs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        dirs.truncate(options.top);
+        report.largest_dirs = dirs;
+    }
+
+    blobs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    report.largest_blobs = blobs
+        .into_iter()
+        .take(options.top)
+        .map(|(hash, bytes)| LargeBlob {
+            hash,
+            bytes,
+            path: head_paths.get(&hash).cloned(),
+        })
+        .collect();
+
+    let mut next = head;
+    while let Some(commit) = next.take() {
+        if report.growth.len() >= options.commits {
+            break;
+        }
+        let files: HashSet<Hash> = trees
+            .collect_all_files(&commit.tree_hash)?
+            .into_values()
+            .collect();
+        let parent = match commit.parents.first() {
+            Some(parent) => Some(commits.read_commit(parent)?),
+            None => None,
+        };
+        let parent_files: HashSet<Hash> = match &parent {
+            Some(parent) => trees
+                .collect_all_files(&parent.tree_hash)?
+                .into_values()
+                .collect(),
+            None => HashSet::new(),
+        };
+
+        let added: Vec<&Hash> = files.difference(&parent_files).collect();
+        report.growth.push(CommitGrowth {
+            hash: commit.commit_hash,
+            summary: commit.summary().to_string(),
+            new_blobs: added.len() as u64,
+            bytes: added
+                .iter()
+                .filter_map(|hash| store.object_disk_size(&ObjectType::Blob, hash))
+                .sum(),
+        });
+        next = parent;
+    }
+
+    Ok(report)
+}
+
+/// Record where each blob is in the tree, and add each file's size to every directory
+/// above it
+fn walk_tree(
+    trees: &TreeStore,
+    tree_hash: &Hash,
+    prefix: &Path,
+    paths: &mut HashMap<Hash, PathBuf>,
+    dirs: &mut HashMap<PathBuf, u64>,
+) -> Result<()> {
+    for entry in trees.read(tree_hash)?.entries {
+        let path = prefix.join(&entry.name);
+        match entry.entry_type {
+            EntryType::Tree => walk_tree(trees, &entry.oid, &path, paths, dirs)?,
+            _ => {
+                for dir in path.ancestors().skip(1) {
+                    if dir.as_os_str().is_empty() {
+                        break;
+                    }
+                    *dirs.entry(dir.to_path_buf()).or_default() += entry.size;
+                }
+                paths.entry(entry.oid).or_insert(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Total size of the files under `dir`, leaving out `skip`
+fn dir_size(dir: &Path, skip: Option<&Path>) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|e| Some(e.path()) != skip)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::commit::Commit;
+    use crate::helix_index::format::{Entry, EntryFlags};
+    use crate::helix_index::tree::TreeBuilder;
+    use helix_protocol::storage::FsRefStore;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn entry(path: &str, oid: Hash, size: u64) -> Entry {
+        Entry {
+            path: path.into(),
+            oid,
+            flags: EntryFlags::TRACKED,
+            size,
+            mtime_sec: 0,
+            mtime_nsec: 0,
+            file_mode: 0o100644,
+            merge_conflict_stage: 0,
+            reserved: [0u8; 33],
+        }
+    }
+
+    #[test]
+    fn test_size_report() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        crate::init_command::init_repo_layout(repo)?;
+        let store = FsObjectStore::new(repo);
+        let commits = CommitStore::new(repo, store.clone())?;
+
+        let readme = store.write_object(&ObjectType::Blob, b"hello\n")?;
+        let tree = TreeBuilder::new(repo).build_from_entries(&[entry("README", readme, 6)])?;
+        let first =
+            commits.write_commit(&Commit::new(tree, vec![], "T <t@x>".into(), "first".into()))?;
+
+        // Content zstd can't shrink, so the dataset is the largest blob
+        let mut seed = 7u64;
+        let data: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 56) as u8
+            })
+            .collect();
+        let dataset = store.write_object(&ObjectType::Blob, &data)?;
+        let tree = TreeBuilder::new(repo).build_from_entries(&[
+            entry("README", readme, 6),
+            entry("data/raw/set.bin", dataset, data.len() as u64),
+        ])?;
+        let second = commits.write_commit(&Commit::new(
+            tree,
+            vec![first],
+            "T <t@x>".into(),
+            "add dataset".into(),
+        ))?;
+        FsRefStore::new(repo).set_ref("refs/heads/main", second)?;
+        let before = size(repo, SizeOptions::default())?.work_tree_bytes.unwrap();
+        fs::write(repo.join("README"), "hello\n")?;
+
+        let report = size(repo, SizeOptions { top: 1, commits: 5 })?;
+
+        assert_eq!(report.objects[0].0, "blobs");
+        assert_eq!(report.objects[0].1.count, 2);
+        assert_eq!(report.objects[2].1.count, 2);
+        assert!(report.helix_bytes > report.objects[0].1.bytes);
+        assert_eq!(report.work_tree_bytes, Some(before + 6));
+
+        assert_eq!(report.largest_blobs.len(), 1);
+        assert_eq!(report.largest_blobs[0].hash, dataset);
+        assert_eq!(
+            report.largest_blobs[0].path.as_deref(),
+            Some(Path::new("data/raw/set.bin"))
+        );
+        assert_eq!(
+            report.largest_dirs,
+            vec![(PathBuf::from("data"), data.len() as u64)]
+        );
+
+        let growth: Vec<(Hash, u64)> = report
+            .growth
+            .iter()
+            .map(|c| (c.hash, c.new_blobs))
+            .collect();
+        assert_eq!(growth, vec![(second, 1), (first, 1)]);
+        assert!(report.growth[0].bytes > 64 * 1024);
+
+        Ok(())
+    }
+}
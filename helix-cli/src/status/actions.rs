@@ -8,12 +8,54 @@ pub enum Action {
     GoToTop,
     GoToBottom,
     ToggleStage,
+    Stage,          // s
+    Unstage,        // u
+    Discard,        // d, asks first
+    ConfirmDiscard, // y
+    CancelDiscard,  // any other key
     StageAll,
     UnstageAll,
     Refresh,
     ToggleUntracked,
     ToggleHelp,
-    SwitchSection,   // Tab
-    CollapseSection, // h
-    ExpandSection,   // l
+    SwitchSection,         // Tab
+    CollapseSection,       // h
+    ExpandSection,         // l
+    StartCommit,           // c
+    GenerateCommitMessage, // C, or Ctrl+G while writing
+    CommitChar(char),
+    CommitNewline,   // Alt+Enter
+    CommitBackspace, // Backspace
+    ConfirmCommit,   // Enter
+    CancelCommit,    // Esc
+}
+
+impl Action {
+    /// The action for a name in `tui::STATUS_KEYS`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "quit" => Action::Quit,
+            "up" => Action::MoveUp,
+            "down" => Action::MoveDown,
+            "page_up" => Action::PageUp,
+            "page_down" => Action::PageDown,
+            "top" => Action::GoToTop,
+            "bottom" => Action::GoToBottom,
+            "toggle_stage" => Action::ToggleStage,
+            "stage" => Action::Stage,
+            "unstage" => Action::Unstage,
+            "discard" => Action::Discard,
+            "stage_all" => Action::StageAll,
+            "unstage_all" => Action::UnstageAll,
+            "refresh" => Action::Refresh,
+            "toggle_untracked" => Action::ToggleUntracked,
+            "help" => Action::ToggleHelp,
+            "switch_section" => Action::SwitchSection,
+            "collapse" => Action::CollapseSection,
+            "expand" => Action::ExpandSection,
+            "commit" => Action::StartCommit,
+            "generate_commit" => Action::GenerateCommitMessage,
+            _ => return None,
+        })
+    }
 }
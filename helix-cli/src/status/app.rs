@@ -1,23 +1,54 @@
 use anyhow::{Context, Result};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use helix_cli::{branch_command::get_current_branch, fsmonitor::FSMonitor, ignore::IgnoreRules};
 use helix_cli::{
-    helix_index::{api::HelixIndexData, EntryFlags},
+    add_command::stage_paths,
+    branch_command::get_current_branch,
+    commit_command::{create_commit, CommitOptions},
+    diff_command::{diff, DiffOptions},
+    fsmonitor::FSMonitor,
+    ignore::IgnoreRules,
+    line_endings::LineEndings,
+    restore::{discard_paths, unstage_paths},
+    unified_diff::Whitespace,
+};
+use helix_cli::{
+    helix_index::{
+        api::HelixIndexData,
+        format::{Entry, UntrackedCache},
+        untracked::{expand_untracked, scan_untracked_cached},
+        verify::Verifier,
+        EntryFlags,
+    },
     sandbox_command::RepoContext,
+    tui::{self, STATUS_KEYS},
+};
+use helix_protocol::hash::hash_to_hex;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Position, Rect},
+    Terminal,
 };
-use ratatui::{backend::CrosstermBackend, Terminal};
 use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
 
 use super::actions::Action;
 use super::ui;
+use crate::config::Config;
+use crate::llm::LLM;
+
+/// How long a toast stays up
+const TOAST_DURATION: Duration = Duration::from_secs(4);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Section {
@@ -34,6 +65,14 @@ pub enum FileStatus {
     Untracked(PathBuf),
 }
 
+/// The message being written in the commit pane
+#[derive(Debug, Default)]
+pub struct CommitInput {
+    pub message: String,
+    /// Waiting on the LLM for a message
+    pub generating: bool,
+}
+
 impl FileStatus {
     pub fn path(&self) -> &Path {
         match self {
@@ -64,8 +103,8 @@ pub struct App {
     pub visible_height: usize,
     pub repo_path: PathBuf,
     pub repo_name: String,
+    /// Follow changes on disk as the file watcher reports them
     pub auto_refresh: bool,
-    pub last_refresh: std::time::Instant,
     pub staged_files: HashSet<PathBuf>,
     pub tracked_files: HashSet<PathBuf>,
     pub show_help: bool,
@@ -74,6 +113,19 @@ pub struct App {
     pub current_branch: Option<String>,
     pub helix_index: HelixIndexData,
     pub ignore_rules: IgnoreRules,
+    pub line_endings: LineEndings,
+    /// File waiting for y/n before its changes are thrown away
+    pub pending_discard: Option<PathBuf>,
+    /// Result of the last action, shown in the help bar
+    pub message: Option<String>,
+    /// Open while a commit message is being written
+    pub commit_input: Option<CommitInput>,
+    /// A generated commit message on its way back from the LLM
+    generated_message: Option<Receiver<Result<String>>>,
+    /// Short-lived notice in the top-right corner and when it went up
+    pub toast: Option<(String, Instant)>,
+    /// Where each file row was last drawn, for mouse clicks: (row, section, index)
+    pub row_targets: Vec<(Rect, Section, usize)>,
 }
 
 impl App {
@@ -98,7 +150,9 @@ impl App {
         let mut fsmonitor = FSMonitor::new(&workdir)?;
         fsmonitor.start_watching_repo()?;
 
+        tui::init(&context.repo_root);
         let ignore_rules = IgnoreRules::load(&workdir);
+        let line_endings = LineEndings::load(&workdir);
 
         let mut app = Self {
             files: Vec::new(),
@@ -111,7 +165,6 @@ impl App {
             repo_path: workdir, // ← Use workdir here!
             repo_name,
             auto_refresh: true,
-            last_refresh: std::time::Instant::now(),
             staged_files: HashSet::new(),
             tracked_files: HashSet::new(),
             show_help: false,
@@ -120,8 +173,26 @@ impl App {
             current_branch,
             helix_index,
             ignore_rules,
+            line_endings,
+            pending_discard: None,
+            message: None,
+            commit_input: None,
+            generated_message: None,
+            toast: None,
+            row_targets: Vec::new(),
         };
 
+        // Point out a damaged index up front rather than showing odd statuses
+        let index_root = context.index_path.parent().and_then(Path::parent);
+        if let Some(issues) = index_root.and_then(|root| Verifier::new(root).check_entries().ok()) {
+            if !issues.is_empty() {
+                app.message = Some(format!(
+                    "helix.idx has {} problem(s); run `helix index verify`",
+                    issues.len()
+                ));
+            }
+        }
+
         app.refresh_status()?;
         Ok(app)
     }
@@ -133,7 +204,6 @@ impl App {
     }
 
     pub fn refresh_status(&mut self) -> Result<()> {
-        println!("DEBUG: repo_path = {}", self.repo_path.display());
         self.files.clear();
         self.tracked_files.clear();
         self.staged_files.clear();
@@ -164,75 +234,11 @@ impl App {
 
         // Check each tracked entry for working tree changes
         for entry in entries {
-            let path = entry.path.clone();
-            let flags = entry.flags;
-
-            if !flags.contains(EntryFlags::TRACKED) {
-                continue;
-            }
-
-            let full_path = self.repo_path.join(&path);
-
-            // Check if file was deleted from disk
-            if !full_path.exists() {
-                if seen.insert(path.clone()) {
-                    self.files.push(FileStatus::Deleted(path));
-                }
-                continue;
-            }
-
-            //Check if file was modified on disk (compare mtime)
-            let is_modified_on_disk = if let Ok(metadata) = fs::metadata(&full_path) {
-                let current_mtime = metadata
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0);
-
-                let mtime_matches = current_mtime == entry.mtime_sec;
-                let size_matches = metadata.len() == entry.size;
-
-                if mtime_matches && size_matches {
-                    false
-                } else {
-                    fs::read(&full_path)
-                        .map(|content| helix_protocol::hash::hash_bytes(&content) != entry.oid)
-                        .unwrap_or(false)
-                }
-            } else {
-                false
-            };
-
-            // Determine file status
-            if flags.contains(EntryFlags::STAGED) {
-                if is_modified_on_disk {
-                    // Staged but modified again in working tree
-                    if seen.insert(path.clone()) {
-                        self.files.push(FileStatus::Modified(path));
-                    }
-                } else {
-                    // Staged and unchanged in working tree - show as Added/Staged
-                    if seen.insert(path.clone()) {
-                        self.files.push(FileStatus::Added(path));
-                    }
-                }
-            } else if is_modified_on_disk {
-                // Not staged but modified on disk
-                if seen.insert(path.clone()) {
-                    self.files.push(FileStatus::Modified(path));
-                }
-            } else if flags.contains(EntryFlags::MODIFIED) {
-                // Flag set but mtime matches (edge case)
-                if seen.insert(path.clone()) {
-                    self.files.push(FileStatus::Modified(path));
-                }
-            } else if flags.contains(EntryFlags::DELETED) {
-                if seen.insert(path.clone()) {
-                    self.files.push(FileStatus::Deleted(path));
+            if let Some(status) = self.entry_status(entry) {
+                if seen.insert(status.path().to_path_buf()) {
+                    self.files.push(status);
                 }
             }
-            // If not staged, not modified on disk, no flags - it's clean, skip
         }
 
         // Untracked files
@@ -250,68 +256,177 @@ impl App {
         Ok(())
     }
 
-    /// Scan working tree for untracked files
-    /// This catches files that existed before FSMonitor started
-    fn scan_for_untracked_files(&mut self) -> Result<Vec<PathBuf>> {
-        let mut untracked = Vec::new();
+    /// How a tracked entry differs from the index and the working tree; None if it's clean
+    fn entry_status(&self, entry: &Entry) -> Option<FileStatus> {
+        let path = entry.path.clone();
+        let flags = entry.flags;
 
-        // No filter_entry that closes over &self
-        for entry in WalkDir::new(&self.repo_path)
-            .follow_links(false)
-            .into_iter()
-        {
-            let entry = entry?;
+        // Not checked out, e.g. left out by a partial clone
+        if !flags.contains(EntryFlags::TRACKED) || flags.contains(EntryFlags::ASSUME_UNCHANGED) {
+            return None;
+        }
 
-            // Now the borrow of &self is only for this call, and ends right after
-            if !self.should_process_entry(&entry) {
-                continue;
-            }
+        let full_path = self.repo_path.join(&path);
 
-            if !entry.path().is_file() {
-                continue;
-            }
+        // Check if file was deleted from disk
+        if !full_path.exists() {
+            return Some(FileStatus::Deleted(path));
+        }
+
+        //Check if file was modified on disk (compare mtime)
+        let is_modified_on_disk = if let Ok(metadata) = fs::metadata(&full_path) {
+            let current_mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
 
-            let rel_path = entry.path().strip_prefix(&self.repo_path)?;
+            let mtime_matches = current_mtime == entry.mtime_sec;
+            let size_matches = metadata.len() == entry.size;
 
-            // Short immutable borrow for helix_index
-            let tracked = self.helix_index.is_tracked(rel_path);
-            if tracked {
-                // Now we can mutably borrow self.tracked_files safely
-                self.tracked_files.insert(rel_path.to_path_buf());
-                continue;
+            if mtime_matches && size_matches {
+                false
+            } else {
+                fs::read(&full_path)
+                    .map(|content| {
+                        let content = self.line_endings.normalize(&content);
+                        helix_protocol::hash::hash_bytes(&content) != entry.oid
+                    })
+                    .unwrap_or(false)
             }
+        } else {
+            false
+        };
 
-            if self.ignore_rules.should_ignore(rel_path) {
-                continue;
+        // Determine file status
+        if flags.contains(EntryFlags::STAGED) {
+            if is_modified_on_disk {
+                // Staged but modified again in working tree
+                Some(FileStatus::Modified(path))
+            } else {
+                // Staged and unchanged in working tree - show as Added/Staged
+                Some(FileStatus::Added(path))
             }
+        } else if is_modified_on_disk || flags.contains(EntryFlags::MODIFIED) {
+            // Modified on disk, or the flag is set but mtime matches (edge case)
+            Some(FileStatus::Modified(path))
+        } else if flags.contains(EntryFlags::DELETED) {
+            Some(FileStatus::Deleted(path))
+        } else {
+            // If not staged, not modified on disk, no flags - it's clean
+            None
+        }
+    }
 
-            untracked.push(rel_path.to_path_buf());
+    /// Apply what the file watcher saw since the last call. Only the rows for changed paths
+    /// are re-checked, unless the index itself changed. Returns whether anything changed.
+    pub fn apply_fs_changes(&mut self) -> Result<bool> {
+        if self.fsmonitor.index_changed() {
+            // A new index can change any row; refresh_status reloads it
+            self.fsmonitor.clear_dirty();
+            let selected = self.selected_path();
+            self.refresh_status()?;
+            self.reselect(selected);
+            return Ok(true);
         }
 
-        Ok(untracked)
+        let changed = self.fsmonitor.get_dirty_files();
+        if changed.is_empty() {
+            return Ok(false);
+        }
+        for path in &changed {
+            self.fsmonitor.clear_single_path(path);
+        }
+        self.refresh_paths(&changed)?;
+        Ok(true)
     }
 
-    fn should_process_entry(&self, entry: &walkdir::DirEntry) -> bool {
-        let name = entry.file_name().to_string_lossy();
+    /// Re-check the rows for `changed` paths (files or directories, relative to the
+    /// working tree) and leave the others alone
+    pub fn refresh_paths(&mut self, changed: &[PathBuf]) -> Result<()> {
+        let selected = self.selected_path();
+        let affected = |path: &Path| changed.iter().any(|c| path.starts_with(c));
+        self.files
+            .retain(|f| matches!(f, FileStatus::Untracked(_)) || !affected(f.path()));
+
+        // A path with no index entries at or below it is new or untracked
+        let mut untracked_changed = false;
+        let mut statuses = Vec::new();
+        for path in changed {
+            let entries = self.helix_index.entries_under(path);
+            if entries.is_empty() {
+                untracked_changed = true;
+            }
+            statuses.extend(entries.iter().filter_map(|e| self.entry_status(e)));
+        }
+        for status in statuses {
+            if !self.files.iter().any(|f| f.path() == status.path()) {
+                self.files.push(status);
+            }
+        }
 
-        // Skip .git and .helix
-        if name == ".git" || name == ".helix" {
-            return false;
+        // Untracked directories are collapsed, so the untracked rows are rebuilt as a whole;
+        // the index's untracked cache keeps that cheap
+        if untracked_changed && self.show_untracked {
+            self.files
+                .retain(|f| !matches!(f, FileStatus::Untracked(_)));
+            for path in self.scan_for_untracked_files()? {
+                if !self.files.iter().any(|f| f.path() == path) {
+                    self.files.push(FileStatus::Untracked(path));
+                }
+            }
         }
 
-        // For directories, check if ignored
-        if entry.path().is_dir() {
-            let rel_path = match entry.path().strip_prefix(&self.repo_path) {
-                Ok(p) => p,
-                Err(_) => return false,
-            };
+        self.files.sort_by(|a, b| a.path().cmp(b.path()));
+        self.reselect(selected);
+        Ok(())
+    }
 
-            if self.ignore_rules.should_ignore(rel_path) {
-                return false;
-            }
+    /// Keep the cursor on `path` if it still has a row, else within the list
+    fn reselect(&mut self, path: Option<PathBuf>) {
+        if let Some(index) = path.and_then(|p| self.files.iter().position(|f| f.path() == p)) {
+            self.selected_index = index;
+        } else if self.selected_index >= self.files.len() {
+            self.selected_index = self.files.len().saturating_sub(1);
         }
+        self.adjust_scroll();
+    }
+
+    /// Scan working tree for untracked files, with untracked directories
+    /// collapsed to one entry. This catches files that existed before
+    /// FSMonitor started. Unchanged directories come from the index's
+    /// untracked cache, which is written back when the scan changed it.
+    fn scan_for_untracked_files(&mut self) -> Result<Vec<PathBuf>> {
+        let (paths, cache) = scan_untracked_cached(
+            &self.repo_path,
+            &self.helix_index,
+            &self.ignore_rules,
+            self.helix_index.untracked_cache(),
+        )?;
 
-        true
+        if self.helix_index.untracked_cache() != Some(&cache) {
+            // Best effort: the in-memory flags may be refreshed past what's on
+            // disk, so only the cache is written, onto a fresh copy of the index
+            let _ = self.persist_untracked_cache(&cache);
+            self.helix_index.set_untracked_cache(Some(cache));
+        }
+        Ok(paths)
+    }
+
+    fn persist_untracked_cache(&self, cache: &UntrackedCache) -> Result<()> {
+        let context = RepoContext::detect(&self.repo_path)?;
+        let mut index = HelixIndexData::load_for_update(&context.index_path, &context.repo_root)?;
+        index.set_untracked_cache(Some(cache.clone()));
+        index.persist()
+    }
+
+    /// Replace collapsed untracked directories in `paths` with the files in them
+    fn expand_directories(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        paths
+            .into_iter()
+            .flat_map(|p| expand_untracked(&self.repo_path, &p, &self.ignore_rules))
+            .collect()
     }
 
     /// Get the currently selected file
@@ -322,51 +437,199 @@ impl App {
         self.files.get(self.selected_index)
     }
 
+    fn selected_path(&self) -> Option<PathBuf> {
+        self.get_selected_file().map(|f| f.path().to_path_buf())
+    }
+
     /// Toggle staging for the selected file
     pub fn toggle_stage(&mut self) -> Result<()> {
-        if let Some(file) = self.get_selected_file() {
-            let path = file.path().to_path_buf();
+        let Some(path) = self.selected_path() else {
+            return Ok(());
+        };
 
-            if self.staged_files.contains(&path) {
-                // Unstage: remove STAGED flag
-                self.helix_index.unstage_file(&path)?;
-                self.staged_files.remove(&path);
-            } else {
-                // Stage: add STAGED flag
-                self.helix_index.stage_file(&path)?;
-                self.staged_files.insert(path);
-            }
+        // Staged with no further edits: unstage. Otherwise stage what's on disk.
+        let staged_clean = self.staged_files.contains(&path)
+            && matches!(self.get_selected_file(), Some(FileStatus::Added(_)));
+        if staged_clean {
+            self.unstage(vec![path])
+        } else {
+            self.stage(vec![path])
+        }
+    }
+
+    /// Write blobs for `paths` and mark them staged
+    fn stage(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        stage_paths(&self.repo_path, &self.expand_directories(paths.clone()))?;
+        self.message = Some(format!("Staged {}", describe(&paths)));
+        self.reload_index()
+    }
 
-            // Persist changes to .helix/helix.idx
-            self.helix_index.persist()?;
+    /// Reset `paths` in the index to HEAD
+    fn unstage(&mut self, paths: Vec<PathBuf>) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
         }
+        unstage_paths(&self.repo_path, &paths)?;
+        self.message = Some(format!("Unstaged {}", describe(&paths)));
+        self.reload_index()
+    }
+
+    /// Ask before throwing away the selected file's working tree changes
+    pub fn request_discard(&mut self) {
+        self.pending_discard = self.selected_path();
+    }
 
+    pub fn confirm_discard(&mut self) -> Result<()> {
+        if let Some(path) = self.pending_discard.take() {
+            let paths = vec![path];
+            discard_paths(&self.repo_path, &self.expand_directories(paths.clone()))?;
+            self.message = Some(format!("Discarded changes to {}", describe(&paths)));
+            self.reload_index()?;
+        }
         Ok(())
     }
 
     /// Stage all files
     pub fn stage_all(&mut self) -> Result<()> {
-        for file in &self.files {
-            let path = file.path().to_path_buf();
-            self.helix_index.stage_file(&path)?;
-            self.staged_files.insert(path);
+        let paths = self.files.iter().map(|f| f.path().to_path_buf()).collect();
+        self.stage(paths)
+    }
+
+    /// Unstage all files
+    pub fn unstage_all(&mut self) -> Result<()> {
+        let mut paths: Vec<PathBuf> = self.staged_files.iter().cloned().collect();
+        paths.sort();
+        self.unstage(paths)
+    }
+
+    /// Open the commit pane, if anything is staged
+    pub fn start_commit(&mut self) {
+        if self.staged_files.is_empty() {
+            self.message = Some("Nothing staged to commit".to_string());
+            return;
+        }
+        self.commit_input.get_or_insert_with(CommitInput::default);
+    }
+
+    /// Ask the LLM for a message describing the staged changes. It runs on the tokio
+    /// runtime and fills in the commit pane when it arrives (see `poll_generated_message`).
+    pub fn generate_commit_message(&mut self) -> Result<()> {
+        self.start_commit();
+        let Some(input) = self.commit_input.as_mut() else {
+            return Ok(());
+        };
+        if input.generating {
+            return Ok(());
         }
 
-        // Persist changes to .helix/helix.idx
-        self.helix_index.persist()?;
+        let options = DiffOptions {
+            staged: true,
+            ..Default::default()
+        };
+        let diff_text: String = diff(&self.repo_path, &[], &options)?
+            .iter()
+            .map(|d| d.render(false, 3, Whitespace::Exact))
+            .collect();
+        let runtime = tokio::runtime::Handle::try_current()
+            .context("Message generation needs the async runtime")?;
+        let llm = LLM::new(Config::load()?);
+
+        let (tx, rx) = mpsc::channel();
+        runtime.spawn(async move {
+            let message =
+                llm.gen_commit_message(&diff_text)
+                    .await
+                    .map(|(subject, body)| match body {
+                        Some(body) => format!("{}\n\n{}", subject, body),
+                        None => subject,
+                    });
+            let _ = tx.send(message);
+        });
+
+        input.generating = true;
+        self.generated_message = Some(rx);
         Ok(())
     }
 
-    /// Unstage all files
-    pub fn unstage_all(&mut self) -> Result<()> {
-        for path in self.staged_files.clone() {
-            self.helix_index.unstage_file(&path)?;
+    /// Put a finished generated message into the commit pane
+    pub fn poll_generated_message(&mut self) {
+        let Some(rx) = &self.generated_message else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(anyhow::anyhow!("message generation stopped")),
+        };
+        self.generated_message = None;
+
+        let Some(input) = self.commit_input.as_mut() else {
+            return;
+        };
+        input.generating = false;
+        match result {
+            Ok(message) => input.message = message,
+            Err(e) => self.message = Some(format!("Error: {:#}", e)),
         }
+    }
 
-        self.staged_files.clear();
+    /// Commit what's staged with the pane's message. On failure the pane stays open.
+    pub fn confirm_commit(&mut self) -> Result<()> {
+        let Some(input) = &self.commit_input else {
+            return Ok(());
+        };
+        if input.generating {
+            return Ok(());
+        }
+        let message = input.message.trim().to_string();
+        if message.is_empty() {
+            anyhow::bail!("Commit message is empty");
+        }
 
-        // Persist changes to .helix/helix.idx
-        self.helix_index.persist()?;
+        let created = create_commit(
+            &self.repo_path,
+            CommitOptions {
+                message,
+                ..Default::default()
+            },
+        )?;
+        self.commit_input = None;
+
+        let hash = hash_to_hex(&created.hash);
+        self.toast = Some((
+            format!("Committed {} {}", &hash[..8], created.summary),
+            Instant::now(),
+        ));
+        self.message = Some(format!(
+            "Committed {}",
+            match created.files_changed {
+                1 => "1 file".to_string(),
+                n => format!("{} files", n),
+            }
+        ));
+        self.current_branch = get_current_branch(&self.repo_path).ok();
+        self.reload_index()
+    }
+
+    pub fn cancel_commit(&mut self) {
+        self.commit_input = None;
+        self.generated_message = None;
+    }
+
+    /// Re-read the index after changing it on disk and rebuild the file list in place
+    fn reload_index(&mut self) -> Result<()> {
+        let context = RepoContext::detect(&self.repo_path)?;
+        self.helix_index = HelixIndexData::load_from_path(&context.index_path, &context.repo_root)?;
+        self.refresh_status()?;
+
+        let visible_count = self.files.len();
+        if self.selected_index >= visible_count {
+            self.selected_index = visible_count.saturating_sub(1);
+        }
+        self.adjust_scroll();
         Ok(())
     }
 
@@ -377,7 +640,18 @@ impl App {
         if visible_count == 0
             && !matches!(
                 action,
-                Action::Quit | Action::Refresh | Action::ToggleHelp | Action::SwitchSection
+                Action::Quit
+                    | Action::Refresh
+                    | Action::ToggleHelp
+                    | Action::SwitchSection
+                    | Action::CancelDiscard
+                    | Action::StartCommit
+                    | Action::GenerateCommitMessage
+                    | Action::CommitChar(_)
+                    | Action::CommitNewline
+                    | Action::CommitBackspace
+                    | Action::ConfirmCommit
+                    | Action::CancelCommit
             )
         {
             return Ok(());
@@ -419,6 +693,27 @@ impl App {
             Action::ToggleStage => {
                 self.toggle_stage()?;
             }
+            Action::Stage => {
+                let paths = self.selected_path().into_iter().collect();
+                self.stage(paths)?;
+            }
+            Action::Unstage => {
+                let paths = self
+                    .selected_path()
+                    .filter(|p| self.staged_files.contains(p))
+                    .into_iter()
+                    .collect();
+                self.unstage(paths)?;
+            }
+            Action::Discard => {
+                self.request_discard();
+            }
+            Action::ConfirmDiscard => {
+                self.confirm_discard()?;
+            }
+            Action::CancelDiscard => {
+                self.pending_discard = None;
+            }
             Action::StageAll => {
                 self.stage_all()?;
             }
@@ -459,11 +754,64 @@ impl App {
                 // Expand the current section
                 self.sections_collapsed.remove(&self.current_section);
             }
+            Action::StartCommit => {
+                self.start_commit();
+            }
+            Action::GenerateCommitMessage => {
+                self.generate_commit_message()?;
+            }
+            Action::CommitChar(c) => {
+                if let Some(input) = self.commit_input.as_mut() {
+                    input.message.push(c);
+                }
+            }
+            Action::CommitNewline => {
+                if let Some(input) = self.commit_input.as_mut() {
+                    input.message.push('\n');
+                }
+            }
+            Action::CommitBackspace => {
+                if let Some(input) = self.commit_input.as_mut() {
+                    input.message.pop();
+                }
+            }
+            Action::ConfirmCommit => {
+                self.confirm_commit()?;
+            }
+            Action::CancelCommit => {
+                self.cancel_commit();
+            }
         }
 
         Ok(())
     }
 
+    /// A click selects the row under the pointer and the wheel moves the selection.
+    /// The mouse is ignored while a popup is open.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<()> {
+        if self.commit_input.is_some() || self.pending_discard.is_some() || self.show_help {
+            return Ok(());
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let position = Position::new(mouse.column, mouse.row);
+                if let Some(&(_, section, index)) = self
+                    .row_targets
+                    .iter()
+                    .find(|(rect, ..)| rect.contains(position))
+                {
+                    self.selected_index = index;
+                    self.current_section = section;
+                    self.adjust_scroll();
+                }
+            }
+            MouseEventKind::ScrollDown => self.handle_action(Action::MoveDown)?,
+            MouseEventKind::ScrollUp => self.handle_action(Action::MoveUp)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
     fn adjust_scroll(&mut self) {
         let visible_height = self.visible_height.max(1);
 
@@ -485,7 +833,10 @@ impl App {
     pub fn run(&mut self) -> Result<()> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        execute!(stdout, EnterAlternateScreen)?;
+        if tui::mouse_enabled() {
+            execute!(stdout, EnableMouseCapture)?;
+        }
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
@@ -507,9 +858,20 @@ impl App {
             let terminal_height = terminal.size()?.height;
             self.update_visible_height(terminal_height);
 
-            // Auto-refresh every 2 seconds if enabled
-            if self.auto_refresh && self.last_refresh.elapsed().as_secs() >= 2 {
-                self.refresh_status()?;
+            // Rows for files the watcher saw change since the last frame
+            if self.auto_refresh {
+                if let Err(e) = self.apply_fs_changes() {
+                    self.message = Some(format!("Error: {:#}", e));
+                }
+            }
+
+            self.poll_generated_message();
+            if self
+                .toast
+                .as_ref()
+                .is_some_and(|(_, shown)| shown.elapsed() > TOAST_DURATION)
+            {
+                self.toast = None;
             }
 
             terminal.draw(|f| {
@@ -517,41 +879,45 @@ impl App {
             })?;
 
             if event::poll(std::time::Duration::from_millis(100))? {
-                if let Event::Key(key) = event::read()? {
-                    let action = match key.code {
-                        KeyCode::Char('q') => Some(Action::Quit),
-                        KeyCode::Esc => Some(Action::Quit),
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::Quit)
-                        }
-                        KeyCode::Char('j') | KeyCode::Down => Some(Action::MoveDown),
-                        KeyCode::Char('k') | KeyCode::Up => Some(Action::MoveUp),
-                        KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::PageDown)
+                let event = event::read()?;
+                if let Event::Mouse(mouse) = event {
+                    if let Err(e) = self.handle_mouse(mouse) {
+                        self.message = Some(format!("Error: {:#}", e));
+                    }
+                }
+                if let Event::Key(key) = event {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    // A pending discard takes the next key as its answer
+                    let action = if self.pending_discard.is_some() {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => Some(Action::ConfirmDiscard),
+                            _ => Some(Action::CancelDiscard),
                         }
-                        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            Some(Action::PageUp)
+                    } else if self.commit_input.is_some() {
+                        // Keys go to the commit message while its pane is open
+                        match key.code {
+                            KeyCode::Esc => Some(Action::CancelCommit),
+                            KeyCode::Char('c') if ctrl => Some(Action::CancelCommit),
+                            KeyCode::Char('g') if ctrl => Some(Action::GenerateCommitMessage),
+                            KeyCode::Enter if key.modifiers.contains(KeyModifiers::ALT) => {
+                                Some(Action::CommitNewline)
+                            }
+                            KeyCode::Enter => Some(Action::ConfirmCommit),
+                            KeyCode::Backspace => Some(Action::CommitBackspace),
+                            KeyCode::Char(c) if !ctrl => Some(Action::CommitChar(c)),
+                            _ => None,
                         }
-                        KeyCode::Char('g') => Some(Action::GoToTop),
-                        KeyCode::Char('G') => Some(Action::GoToBottom),
-                        KeyCode::PageDown => Some(Action::PageDown),
-                        KeyCode::PageUp => Some(Action::PageUp),
-                        KeyCode::Home => Some(Action::GoToTop),
-                        KeyCode::End => Some(Action::GoToBottom),
-                        KeyCode::Char(' ') | KeyCode::Enter => Some(Action::ToggleStage),
-                        KeyCode::Char('a') => Some(Action::StageAll),
-                        KeyCode::Char('A') => Some(Action::UnstageAll),
-                        KeyCode::Char('r') => Some(Action::Refresh),
-                        KeyCode::Char('t') => Some(Action::ToggleUntracked),
-                        KeyCode::Char('?') => Some(Action::ToggleHelp),
-                        KeyCode::Tab => Some(Action::SwitchSection),
-                        KeyCode::Char('h') => Some(Action::CollapseSection),
-                        KeyCode::Char('l') => Some(Action::ExpandSection),
-                        _ => None,
+                    } else {
+                        tui::keys()
+                            .action(&key, STATUS_KEYS)
+                            .and_then(Action::from_name)
                     };
 
                     if let Some(action) = action {
-                        self.handle_action(action)?;
+                        // Show failures in the help bar instead of leaving the TUI
+                        if let Err(e) = self.handle_action(action) {
+                            self.message = Some(format!("Error: {:#}", e));
+                        }
                     }
                 }
             }
@@ -564,3 +930,138 @@ impl App {
         Ok(())
     }
 }
+
+/// "a.txt" or "3 files"
+fn describe(paths: &[PathBuf]) -> String {
+    match paths {
+        [path] => path.display().to_string(),
+        _ => format!("{} files", paths.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helix_cli::init_command::init_helix_repo;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_changed_paths_update_their_rows() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        init_helix_repo(repo, None)?;
+        fs::write(repo.join("keep.txt"), "keep")?;
+        let mut app = App::new(repo)?;
+        app.stage(vec![PathBuf::from("keep.txt")])?;
+
+        let new = PathBuf::from("new.txt");
+        fs::write(repo.join(&new), "one")?;
+        app.refresh_paths(std::slice::from_ref(&new))?;
+        assert!(app.files.contains(&FileStatus::Untracked(new.clone())));
+
+        app.stage(vec![new.clone()])?;
+        assert!(app.files.contains(&FileStatus::Added(new.clone())));
+        app.selected_index = app.files.iter().position(|f| f.path() == new).unwrap();
+
+        // Only the changed row moves on; the cursor stays with it
+        fs::write(repo.join(&new), "two, longer")?;
+        app.refresh_paths(std::slice::from_ref(&new))?;
+        assert!(app.files.contains(&FileStatus::Modified(new.clone())));
+        assert!(app
+            .files
+            .contains(&FileStatus::Added(PathBuf::from("keep.txt"))));
+        assert_eq!(
+            app.get_selected_file().map(|f| f.path()),
+            Some(new.as_path())
+        );
+
+        fs::remove_file(repo.join(&new))?;
+        app.refresh_paths(std::slice::from_ref(&new))?;
+        assert!(app.files.contains(&FileStatus::Deleted(new)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_every_key_name_has_an_action() {
+        for (name, _) in STATUS_KEYS {
+            assert!(Action::from_name(name).is_some(), "{}", name);
+        }
+        let key = crossterm::event::KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        let action = tui::KeyBindings::default().action(&key, STATUS_KEYS);
+        assert_eq!(
+            action.and_then(Action::from_name),
+            Some(Action::StartCommit)
+        );
+    }
+
+    #[test]
+    fn test_commit_from_the_pane() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        init_helix_repo(repo, None)?;
+        let config = fs::read_to_string(repo.join("helix.toml"))?;
+        fs::write(
+            repo.join("helix.toml"),
+            config + "\n[user]\nname = \"Test User\"\nemail = \"test@example.com\"\n",
+        )?;
+        fs::write(repo.join("a.txt"), "a")?;
+        let mut app = App::new(repo)?;
+
+        // Nothing staged yet, so the pane doesn't open
+        app.handle_action(Action::StartCommit)?;
+        assert!(app.commit_input.is_none());
+
+        app.stage(vec![PathBuf::from("a.txt")])?;
+        app.handle_action(Action::StartCommit)?;
+        assert!(app.handle_action(Action::ConfirmCommit).is_err());
+        assert!(app.commit_input.is_some());
+
+        for c in "Add a".chars() {
+            app.handle_action(Action::CommitChar(c))?;
+        }
+        app.handle_action(Action::ConfirmCommit)?;
+
+        assert!(app.commit_input.is_none());
+        assert!(app.staged_files.is_empty());
+        assert!(!app.files.iter().any(|f| f.path() == Path::new("a.txt")));
+        let (toast, _) = app.toast.as_ref().expect("toast after committing");
+        assert!(toast.starts_with("Committed "));
+        assert!(toast.ends_with(" Add a"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_click_selects_the_row_under_the_pointer() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        init_helix_repo(repo, None)?;
+        fs::write(repo.join("a.txt"), "a")?;
+        fs::write(repo.join("b.txt"), "b")?;
+        let mut app = App::new(repo)?;
+
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(120, 40))?;
+        terminal.draw(|f| ui::draw(f, &mut app))?;
+
+        let b = app
+            .files
+            .iter()
+            .position(|f| f.path() == Path::new("b.txt"));
+        let &(rect, ..) = app
+            .row_targets
+            .iter()
+            .find(|(_, _, index)| Some(*index) == b)
+            .expect("b.txt is drawn");
+        assert_ne!(Some(app.selected_index), b);
+        app.handle_mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: rect.x + 2,
+            row: rect.y,
+            modifiers: KeyModifiers::NONE,
+        })?;
+        assert_eq!(app.selected_index, b.unwrap());
+
+        Ok(())
+    }
+}
@@ -2,21 +2,25 @@
 The UI for the status command
 h - collapses a section
 l - expands a section
+s/u - stage/unstage the selected file
+d - discard the selected file's changes, after a y/n prompt
+c - write a commit message for the staged files; C asks the LLM for one
 */
 
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
     Frame,
 };
 
 use crate::status::app::Section;
+use helix_cli::tui::palette;
 
-use super::app::{App, FileStatus};
+use super::app::{App, CommitInput, FileStatus};
 
-pub fn draw(f: &mut Frame, app: &App) {
+pub fn draw(f: &mut Frame, app: &mut App) {
     if app.show_help {
         draw_help_overlay(f);
         return;
@@ -33,7 +37,17 @@ pub fn draw(f: &mut Frame, app: &App) {
 
     draw_header(f, chunks[0], app);
     draw_file_sections(f, chunks[1], app);
-    draw_help_bar(f, chunks[2]);
+    draw_help_bar(f, chunks[2], app);
+
+    if let Some(path) = &app.pending_discard {
+        draw_discard_prompt(f, path);
+    }
+    if let Some(input) = &app.commit_input {
+        draw_commit_pane(f, input, app.staged_files.len());
+    }
+    if let Some((toast, _)) = &app.toast {
+        draw_toast(f, toast);
+    }
 }
 
 fn draw_header(f: &mut Frame, area: Rect, app: &App) {
@@ -66,7 +80,7 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
 
     let stats_line_1 = Line::from(vec![
         Span::raw(repo_text),
-        Span::styled("│ ", Style::default().fg(Color::DarkGray)),
+        Span::styled("│ ", Style::default().fg(palette().muted)),
         Span::raw(branch_text),
     ]);
 
@@ -74,67 +88,70 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         Span::styled(
             "M:",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::styled(
             format!("{} ", modified_count),
-            Style::default().fg(Color::White),
+            Style::default().fg(palette().text),
         ),
         Span::styled(
             " A:",
             Style::default()
-                .fg(Color::Green)
+                .fg(palette().added)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::styled(
             format!("{} ", added_count),
-            Style::default().fg(Color::White),
+            Style::default().fg(palette().text),
         ),
         Span::styled(
             " D:",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            Style::default()
+                .fg(palette().removed)
+                .add_modifier(Modifier::BOLD),
         ),
         Span::styled(
             format!("{} ", deleted_count),
-            Style::default().fg(Color::White),
+            Style::default().fg(palette().text),
         ),
         Span::styled(
             " ?:",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         ),
         Span::styled(
             format!("{} ", untracked_count),
-            Style::default().fg(Color::White),
+            Style::default().fg(palette().text),
         ),
         Span::raw("  "),
-        Span::styled("(m)", Style::default().fg(Color::Yellow)),
+        Span::styled("(m)", Style::default().fg(palette().highlight)),
         Span::raw("od "),
-        Span::styled("(a)", Style::default().fg(Color::Green)),
+        Span::styled("(a)", Style::default().fg(palette().added)),
         Span::raw("dd "),
-        Span::styled("(d)", Style::default().fg(Color::Red)),
+        Span::styled("(d)", Style::default().fg(palette().removed)),
         Span::raw("el "),
-        Span::styled("(u)", Style::default().fg(Color::Cyan)),
+        Span::styled("(u)", Style::default().fg(palette().accent)),
         Span::raw("ntracked "),
-        Span::styled("(c)", Style::default().fg(Color::Magenta)),
+        Span::styled("(c)", Style::default().fg(palette().special)),
         Span::raw("onflicts "),
-        Span::styled("(.)", Style::default().fg(Color::White)),
+        Span::styled("(.)", Style::default().fg(palette().text)),
         Span::raw("All"),
     ]);
 
     let header = Paragraph::new(vec![stats_line_1, stats_line_2]).block(
         Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Blue)),
+            .border_style(Style::default().fg(palette().title)),
     );
 
     f.render_widget(header, area);
 }
 
-fn draw_file_sections(f: &mut Frame, area: Rect, app: &App) {
+fn draw_file_sections(f: &mut Frame, area: Rect, app: &mut App) {
     let visible_files = app.files.clone();
+    app.row_targets.clear();
 
     if visible_files.is_empty() {
         draw_empty_state(f, area);
@@ -144,6 +161,10 @@ fn draw_file_sections(f: &mut Frame, area: Rect, app: &App) {
     let mut unstaged_items = Vec::new();
     let mut staged_items = Vec::new();
     let mut untracked_items = Vec::new();
+    // The file index behind each row of each section
+    let mut unstaged_rows = Vec::new();
+    let mut staged_rows = Vec::new();
+    let mut untracked_rows = Vec::new();
 
     for (idx, file) in visible_files.iter().enumerate() {
         let is_selected = idx == app.selected_index;
@@ -163,17 +184,20 @@ fn draw_file_sections(f: &mut Frame, area: Rect, app: &App) {
         // only in “UNTRACKED”
         if is_untracked {
             untracked_items.push(item.clone());
+            untracked_rows.push(idx);
             continue;
         }
 
         //  file has something in the index differing from HEAD
         if is_staged {
             staged_items.push(item.clone());
+            staged_rows.push(idx);
         }
 
         // Unstaged changes: working tree differs from index
         if (is_modified || is_deleted) && is_tracked {
             unstaged_items.push(item.clone());
+            unstaged_rows.push(idx);
         }
     }
 
@@ -210,6 +234,32 @@ fn draw_file_sections(f: &mut Frame, area: Rect, app: &App) {
         ])
         .split(area);
 
+    for (section, area, rows, visible) in [
+        (
+            Section::Unstaged,
+            sections[0],
+            &unstaged_rows,
+            unstaged_visible,
+        ),
+        (Section::Staged, sections[1], &staged_rows, staged_visible),
+        (
+            Section::Untracked,
+            sections[2],
+            &untracked_rows,
+            untracked_visible,
+        ),
+    ] {
+        if !visible {
+            continue;
+        }
+        // Rows start inside the border and stop where the section does
+        let inner = area.inner(Margin::new(1, 1));
+        for (i, &index) in rows.iter().enumerate().take(inner.height as usize) {
+            let row = Rect::new(inner.x, inner.y + i as u16, inner.width, 1);
+            app.row_targets.push((row, section, index));
+        }
+    }
+
     draw_section(
         f,
         sections[0],
@@ -249,9 +299,9 @@ fn draw_section(
     let full_title = format!(" {} {} ", expand_symbol, title);
 
     let border_color = if is_focused {
-        Color::Cyan
+        palette().accent
     } else {
-        Color::DarkGray
+        palette().muted
     };
 
     if !expanded {
@@ -281,10 +331,10 @@ fn create_file_item(
 ) -> ListItem {
     let status_char = file.status_char();
     let status_color = match file {
-        FileStatus::Modified(_) => Color::Yellow,
-        FileStatus::Added(_) => Color::Green,
-        FileStatus::Deleted(_) => Color::Red,
-        FileStatus::Untracked(_) => Color::Cyan,
+        FileStatus::Modified(_) => palette().highlight,
+        FileStatus::Added(_) => palette().added,
+        FileStatus::Deleted(_) => palette().removed,
+        FileStatus::Untracked(_) => palette().accent,
     };
 
     let indicator = if is_selected { "▶ " } else { "  " };
@@ -294,15 +344,15 @@ fn create_file_item(
 
     let path_style = if highlight {
         Style::default()
-            .fg(Color::White)
+            .fg(palette().text)
             .add_modifier(Modifier::BOLD)
-            .bg(Color::DarkGray)
+            .bg(palette().selection)
     } else if is_selected {
         Style::default()
-            .fg(Color::White)
+            .fg(palette().text)
             .add_modifier(Modifier::BOLD)
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(palette().text)
     };
 
     let line = Line::from(vec![
@@ -317,9 +367,9 @@ fn create_file_item(
         Span::styled(
             format!("[{}] ", staged_marker),
             Style::default().fg(if is_staged {
-                Color::Green
+                palette().added
             } else {
-                Color::DarkGray
+                palette().muted
             }),
         ),
         Span::styled(path_str, path_style),
@@ -335,27 +385,29 @@ fn draw_empty_state(f: &mut Frame, area: Rect) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Green)),
+                .border_style(Style::default().fg(palette().added)),
         )
-        .style(Style::default().fg(Color::Green))
+        .style(Style::default().fg(palette().added))
         .alignment(Alignment::Center);
 
     f.render_widget(empty, area);
 }
 
-fn draw_help_bar(f: &mut Frame, area: Rect) {
-    let help_text = vec![
-        Line::from(vec![
-            Span::styled("Help: ", Style::default().fg(Color::Cyan)),
-            Span::raw("↑/↓ move • "),
-            Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" toggle stage/unstage • "),
-            Span::styled("A", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" stage visible • "),
-            Span::styled("U", Style::default().add_modifier(Modifier::BOLD)),
-            Span::raw(" unstage visible •"),
+fn draw_help_bar(f: &mut Frame, area: Rect, app: &App) {
+    // The last action's result replaces the second line until the next action
+    let second_line = match &app.message {
+        Some(message) => Line::from(vec![
+            Span::raw("       "),
+            Span::styled(
+                message.clone(),
+                Style::default().fg(if message.starts_with("Error") {
+                    palette().removed
+                } else {
+                    palette().added
+                }),
+            ),
         ]),
-        Line::from(vec![
+        None => Line::from(vec![
             Span::raw("       "),
             Span::styled("/", Style::default().add_modifier(Modifier::BOLD)),
             Span::raw(" search • "),
@@ -368,6 +420,24 @@ fn draw_help_bar(f: &mut Frame, area: Rect) {
             Span::styled("q", Style::default().add_modifier(Modifier::BOLD)),
             Span::raw(" quit"),
         ]),
+    };
+
+    let help_text = vec![
+        Line::from(vec![
+            Span::styled("Help: ", Style::default().fg(palette().accent)),
+            Span::raw("↑/↓ move • "),
+            Span::styled("Space", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" toggle • "),
+            Span::styled("s/u", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" stage/unstage • "),
+            Span::styled("d", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" discard • "),
+            Span::styled("a/A", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" stage/unstage all • "),
+            Span::styled("c", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" commit •"),
+        ]),
+        second_line,
     ];
 
     let help = Paragraph::new(help_text).block(Block::default().borders(Borders::ALL));
@@ -375,6 +445,101 @@ fn draw_help_bar(f: &mut Frame, area: Rect) {
     f.render_widget(help, area);
 }
 
+fn draw_discard_prompt(f: &mut Frame, path: &std::path::Path) {
+    let area = centered_rect(50, 20, f.area());
+
+    let prompt = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Discard all changes to "),
+            Span::styled(
+                path.display().to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw("?"),
+        ]),
+        Line::from("This cannot be undone."),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" discard • any other key cancels"),
+        ]),
+    ];
+
+    let popup = Paragraph::new(prompt)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette().removed))
+                .title(" Discard changes "),
+        )
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(popup, area);
+}
+
+fn draw_commit_pane(f: &mut Frame, input: &CommitInput, staged_count: usize) {
+    let area = centered_rect(70, 40, f.area());
+
+    let mut text: Vec<Line> = if input.generating {
+        vec![Line::from(Span::styled(
+            "Generating a message…",
+            Style::default().fg(palette().muted),
+        ))]
+    } else {
+        input.message.split('\n').map(Line::from).collect()
+    };
+    // A cursor after the last character
+    if !input.generating {
+        if let Some(last) = text.last_mut() {
+            last.push_span(Span::styled("█", Style::default().fg(palette().accent)));
+        }
+    }
+
+    let pane = Paragraph::new(text)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette().accent))
+                .title(format!(
+                    " Commit {} staged file{} ",
+                    staged_count,
+                    if staged_count == 1 { "" } else { "s" }
+                ))
+                .title_bottom(Line::from(
+                    " Enter commit • Alt+Enter newline • Ctrl+G generate • Esc cancel ",
+                )),
+        )
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+    f.render_widget(pane, area);
+}
+
+fn draw_toast(f: &mut Frame, toast: &str) {
+    let screen = f.area();
+    let width = (toast.chars().count() as u16 + 4).min(screen.width);
+    let area = Rect::new(
+        screen.right().saturating_sub(width),
+        screen.y,
+        width,
+        3.min(screen.height),
+    );
+
+    let toast = Paragraph::new(toast)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(palette().added)),
+        )
+        .style(Style::default().fg(palette().added));
+
+    f.render_widget(Clear, area);
+    f.render_widget(toast, area);
+}
+
 fn draw_help_overlay(f: &mut Frame) {
     let area = centered_rect(60, 70, f.area());
 
@@ -382,14 +547,14 @@ fn draw_help_overlay(f: &mut Frame) {
         Line::from(vec![Span::styled(
             "Git Status Help",
             Style::default()
-                .fg(Color::Cyan)
+                .fg(palette().accent)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from(""),
         Line::from(vec![Span::styled(
             "Navigation",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from("  j/k, ↓/↑      Move up/down"),
@@ -401,18 +566,23 @@ fn draw_help_overlay(f: &mut Frame) {
         Line::from(vec![Span::styled(
             "Actions",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from("  Space/Enter   Toggle stage file"),
-        Line::from("  A             Stage all visible files"),
-        Line::from("  U             Unstage all visible files"),
+        Line::from("  s             Stage file (writes its content)"),
+        Line::from("  u             Unstage file (back to HEAD)"),
+        Line::from("  d             Discard file changes (asks first)"),
+        Line::from("  a             Stage all visible files"),
+        Line::from("  A             Unstage all files"),
+        Line::from("  c             Commit staged files"),
+        Line::from("  C             Commit with a generated message"),
         Line::from("  r             Refresh status"),
         Line::from(""),
         Line::from(vec![Span::styled(
             "Filters & Search",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from("  /             Search files"),
@@ -422,7 +592,7 @@ fn draw_help_overlay(f: &mut Frame) {
         Line::from(vec![Span::styled(
             "Other",
             Style::default()
-                .fg(Color::Yellow)
+                .fg(palette().highlight)
                 .add_modifier(Modifier::BOLD),
         )]),
         Line::from("  ?             Toggle this help"),
@@ -430,7 +600,7 @@ fn draw_help_overlay(f: &mut Frame) {
         Line::from(""),
         Line::from(vec![Span::styled(
             "Press ? to close",
-            Style::default().fg(Color::DarkGray),
+            Style::default().fg(palette().muted),
         )]),
     ];
 
@@ -438,15 +608,15 @@ fn draw_help_overlay(f: &mut Frame) {
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Cyan))
+                .border_style(Style::default().fg(palette().accent))
                 .title(" Help ")
                 .title_style(
                     Style::default()
-                        .fg(Color::Cyan)
+                        .fg(palette().accent)
                         .add_modifier(Modifier::BOLD),
                 ),
         )
-        .style(Style::default().bg(Color::Black))
+        .style(Style::default().bg(palette().background))
         .wrap(Wrap { trim: false });
 
     f.render_widget(help, area);
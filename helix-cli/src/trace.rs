@@ -0,0 +1,213 @@
+/*
+Verbose tracing and crash reports, all kept on this machine.
+
+  helix --trace push origin main          helix's own modules at debug
+  helix --trace=helix_cli::pull_command=trace,warn pull
+                                          per-module levels, most specific wins
+
+With --trace, trace events go to stderr as they happen and are kept in memory
+too. If the command then fails, they are written out with the error to
+
+  .helix/logs/trace-YYYYMMDD-HHMMSS.log
+
+of the repo the command ran in (the system temp directory outside of one), and
+the path is printed, ready to attach to a bug report.
+
+A panic always writes .helix/logs/crash-YYYYMMDD-HHMMSS.log: the panic message
+and where it happened, the command line, a backtrace, and the trace events when
+--trace was on. Nothing is ever sent anywhere.
+*/
+use anyhow::{Context, Result};
+use std::backtrace::Backtrace;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+use crate::sandbox_command::RepoContext;
+
+/// The filter for a bare --trace
+pub const DEFAULT_FILTER: &str =
+    "helix=debug,helix_cli=debug,helix_client=debug,helix_protocol=debug,helix_server=debug,warn";
+
+/// Trace output kept for a log file; past this the oldest half is dropped
+const MAX_BUFFERED: usize = 16 * 1024 * 1024;
+
+/// Trace events since --trace turned tracing on, None when it's off
+static TRACE_LOG: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+/// Parse a --trace filter: comma-separated `module=level` directives and a bare level
+/// for everything else
+pub fn parse_filter(filter: &str) -> Result<Targets> {
+    Targets::from_str(filter).with_context(|| format!("Invalid --trace filter '{}'", filter))
+}
+
+/// Turn tracing on, to stderr and to the buffer a failure's trace file is written from
+pub fn init(filter: &str) -> Result<()> {
+    let targets = parse_filter(filter)?;
+    *lock_log() = Some(Vec::new());
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_filter(targets.clone()),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(|| TraceLog)
+                .with_filter(targets),
+        )
+        .try_init()
+        .context("Failed to start tracing")
+}
+
+/// Print where a crash report was written when helix panics
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let mut report = format!("helix {} panicked", env!("CARGO_PKG_VERSION"));
+        if let Some(location) = info.location() {
+            let _ = write!(report, " at {}", location);
+        }
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("(no message)");
+        let _ = writeln!(report, ":\n{}\n", message);
+        let _ = writeln!(report, "Command: {}\n", command_line());
+        let _ = writeln!(report, "Backtrace:\n{}", Backtrace::force_capture());
+        append_trace(&mut report);
+
+        match write_report(&logs_dir(), "crash", &report) {
+            Ok(path) => eprintln!(
+                "helix crashed. A crash report was written to {}\n\
+                 It has not been sent anywhere; please attach it to a bug report.",
+                path.display()
+            ),
+            Err(e) => eprintln!("helix crashed, and writing a crash report failed: {:#}", e),
+        }
+    }));
+}
+
+/// With --trace, write the trace of a command that failed with `error`. Returns the
+/// file written.
+pub fn write_error_log(error: &anyhow::Error) -> Option<PathBuf> {
+    if lock_log().is_none() {
+        return None;
+    }
+    let mut report = format!(
+        "helix {} failed\n\nCommand: {}\n\nError: {:?}\n\n",
+        env!("CARGO_PKG_VERSION"),
+        command_line(),
+        error
+    );
+    append_trace(&mut report);
+    write_report(&logs_dir(), "trace", &report).ok()
+}
+
+/// Write `contents` to `dir`/`kind`-<timestamp>.log
+pub fn write_report(dir: &Path, kind: &str, contents: &str) -> Result<PathBuf> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("{}-{}.log", kind, stamp));
+    // Two reports in the same second
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("{}-{}-{}.log", kind, stamp, n));
+    }
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// .helix/logs of the repo around the current directory, or a temp directory outside
+/// of one
+fn logs_dir() -> PathBuf {
+    env::current_dir()
+        .ok()
+        .and_then(|cwd| RepoContext::detect(&cwd).ok())
+        .map(|context| context.repo_root.join(".helix").join("logs"))
+        .unwrap_or_else(|| env::temp_dir().join("helix-logs"))
+}
+
+fn command_line() -> String {
+    env::args().collect::<Vec<_>>().join(" ")
+}
+
+fn append_trace(report: &mut String) {
+    if let Some(log) = lock_log().as_ref() {
+        let _ = write!(report, "\nTrace:\n{}", String::from_utf8_lossy(log));
+    }
+}
+
+/// The buffer, even if a panic elsewhere poisoned it
+fn lock_log() -> std::sync::MutexGuard<'static, Option<Vec<u8>>> {
+    TRACE_LOG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Appends trace output to TRACE_LOG
+struct TraceLog;
+
+impl Write for TraceLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(log) = lock_log().as_mut() {
+            if log.len() + buf.len() > MAX_BUFFERED {
+                log.drain(..log.len() / 2);
+            }
+            log.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::level_filters::LevelFilter;
+
+    #[test]
+    fn test_filter_and_report_files() -> Result<()> {
+        let targets = parse_filter("helix_cli::push_command=trace,helix_cli=info,warn")?;
+        assert!(targets.would_enable("helix_cli::push_command", &tracing::Level::TRACE));
+        assert!(!targets.would_enable("helix_cli::pull_command", &tracing::Level::DEBUG));
+        assert!(targets.would_enable("helix_cli::pull_command", &tracing::Level::INFO));
+        assert!(!targets.would_enable("hyper", &tracing::Level::INFO));
+        assert_eq!(
+            parse_filter(DEFAULT_FILTER)?.default_level(),
+            Some(LevelFilter::WARN)
+        );
+        assert!(parse_filter("helix_cli=loud").is_err());
+
+        let temp = tempfile::tempdir()?;
+        let dir = temp.path().join(".helix/logs");
+        let first = write_report(&dir, "crash", "one")?;
+        let second = write_report(&dir, "crash", "two")?;
+        assert_ne!(first, second);
+        assert!(first
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".log")));
+        assert_eq!(fs::read_to_string(&second)?, "two");
+        Ok(())
+    }
+}
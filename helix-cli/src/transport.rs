@@ -0,0 +1,148 @@
+/*
+Sending RPC bodies to a remote.
+
+A remote URL is either an HTTP(S) server, where each RPC is POSTed to
+`<url>/rpc/<endpoint>`, or `file://<path>`, a repository on local disk
+(normally a bare one, see `helix init --bare`). Commands that take a remote
+name also take a URL or a path in its place (`helix push /mnt/backup/app
+main`); such a one-off remote isn't in helix.toml and gets no
+remote-tracking refs. A file:// remote has no
+server: the body goes straight to helix-server's handlers running in this
+process against that directory's stores. So both kinds answer with the same
+status and message stream, and the callers can't tell them apart.
+
+The protocol itself is spoken by helix-client's HelixRemote; `connect` opens
+one for a remote URL.
+*/
+use crate::init_command::RemoteConfig;
+use anyhow::{Context, Result};
+use helix_client::{HelixRemote, HttpTransport};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Whether a remote given on the command line is a URL or a path rather than the name of
+/// one in helix.toml. Such a remote has no remote-tracking refs.
+pub fn is_url(remote: &str) -> bool {
+    remote.contains("://") || remote.starts_with('/') || remote.starts_with('.')
+}
+
+/// The URL of a remote given as a URL or a path; a path becomes an absolute `file://` URL
+pub fn url_for(remote: &str) -> Result<String> {
+    if remote.contains("://") {
+        return Ok(remote.trim_end_matches('/').to_string());
+    }
+    let path = Path::new(remote)
+        .canonicalize()
+        .with_context(|| format!("No repository at {remote}"))?;
+    Ok(format!("file://{}", path.display()))
+}
+
+/// The directory a `file://` remote URL points at
+pub fn local_path(remote_url: &str) -> Option<PathBuf> {
+    remote_url.strip_prefix("file://").map(PathBuf::from)
+}
+
+/// Split an HTTP(S) URL with a path into the server and the repository its last path
+/// component names: `http://host:8080/app` is repo `app` on `http://host:8080`. Other URLs
+/// come back whole, naming no repository.
+pub fn split_repo_url(url: &str) -> (&str, Option<&str>) {
+    let url = url.trim_end_matches('/');
+    let has_path = url
+        .split_once("://")
+        .is_some_and(|(_, rest)| rest.contains('/'));
+    match url.rsplit_once('/') {
+        Some((server, repo)) if has_path && local_path(url).is_none() => (server, Some(repo)),
+        _ => (url, None),
+    }
+}
+
+/// The server to send RPCs to, and the repository to ask it for: the one the URL's path
+/// names, else `repo_name`
+fn server_and_repo<'a>(remote_url: &'a str, repo_name: &'a str) -> (&'a str, &'a str) {
+    let (server, repo) = split_repo_url(remote_url);
+    (server, repo.unwrap_or(repo_name))
+}
+
+/// The repository `repo_name` at `remote_url`, speaking as this build of helix-cli. A
+/// repository named in the URL's path takes the place of `repo_name`. `token` only
+/// matters for HTTP remotes.
+pub fn connect(remote_url: &str, token: Option<&str>, repo_name: &str) -> HelixRemote {
+    let (remote_url, repo_name) = server_and_repo(remote_url, repo_name);
+    tracing::debug!(
+        url = remote_url,
+        repo = repo_name,
+        token = token.is_some(),
+        "connect"
+    );
+    HelixRemote::open(remote_url, repo_name, token.map(str::to_string))
+        .with_agent(format!("helix-cli {}", env!("CARGO_PKG_VERSION")))
+}
+
+/// Like `connect`, with the timeout and TLS settings of `remote`'s [remotes.<name>] table.
+/// They only apply to HTTP remotes.
+pub fn connect_remote(
+    remote: &RemoteConfig,
+    remote_url: &str,
+    token: Option<&str>,
+    repo_name: &str,
+) -> Result<HelixRemote> {
+    if local_path(remote_url).is_some() {
+        return Ok(connect(remote_url, token, repo_name));
+    }
+
+    let mut client = reqwest::Client::builder();
+    if let Some(secs) = remote.timeout_secs {
+        client = client.timeout(Duration::from_secs(secs));
+    }
+    if let Some(tls) = &remote.tls {
+        if let Some(ca_file) = &tls.ca_file {
+            let pem = fs::read(ca_file)
+                .with_context(|| format!("Failed to read CA file {}", ca_file.display()))?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("No PEM certificates in {}", ca_file.display()))?
+            {
+                client = client.add_root_certificate(cert);
+            }
+        }
+        client = client.danger_accept_invalid_certs(tls.insecure);
+    }
+    let client = client
+        .build()
+        .context("Failed to set up the HTTP client for the remote")?;
+
+    let (remote_url, repo_name) = server_and_repo(remote_url, repo_name);
+    tracing::debug!(
+        url = remote_url,
+        repo = repo_name,
+        token = token.is_some(),
+        timeout_secs = ?remote.timeout_secs,
+        "connect"
+    );
+    let http = HttpTransport::new(remote_url)
+        .with_token(token.map(str::to_string))
+        .with_client(client);
+    Ok(HelixRemote::with_transport(Box::new(http), repo_name)
+        .with_agent(format!("helix-cli {}", env!("CARGO_PKG_VERSION"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_path_names_the_repo() {
+        assert_eq!(
+            server_and_repo("http://host:8080/demo", "mydir"),
+            ("http://host:8080", "demo")
+        );
+        assert_eq!(
+            server_and_repo("http://host:8080/", "mydir"),
+            ("http://host:8080", "mydir")
+        );
+        assert_eq!(
+            server_and_repo("file:///srv/helix/demo", "mydir"),
+            ("file:///srv/helix/demo", "mydir")
+        );
+    }
+}
@@ -0,0 +1,483 @@
+/*
+Settings shared by the status, log and branch TUIs, from [ui] in helix.toml:
+
+  [ui]
+  theme = "light"                  # "dark" (the default) or "light", for light terminals
+  date_format = "%Y-%m-%d %H:%M"   # strftime, in UTC; "relative" for "3 hours ago"
+  mouse = false                    # leave the mouse to the terminal (text selection)
+
+  [ui.keys]
+  quit = "q"
+  down = ["j", "down", "ctrl+n"]
+  commit = "alt+c"
+
+Without a date_format each view keeps its own: relative ages in the branch
+list, timestamps in the log. A binding replaces all of its action's default
+keys. Keys are a character, or one of space, enter, esc, tab, backtab,
+backspace, delete, up, down, left, right, home, end, pageup, pagedown and
+f1-f12, with any of ctrl+, alt+ and shift+ in front. Action names are shared
+where the TUIs do the same thing (quit, up, down, search, ...); each TUI
+ignores the ones it doesn't have. Keys typed into a text box (a search, a
+commit message, a branch name) aren't remappable.
+
+With the mouse on (the default), a click selects a row, the wheel scrolls
+the list or diff under the pointer, and the log's split between its list and
+details can be dragged.
+
+Unknown actions and malformed keys fail the config like any other bad value,
+with the list of valid names. Each TUI calls `init` on startup, which loads
+the settings once for the process; the drawing code reads the palette and
+formats dates through `palette()` and `format_date`.
+*/
+use anyhow::{bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::DateTime;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::init_command::UiSection;
+
+/// Default keys of the status TUI's actions, in the order they're tried
+pub const STATUS_KEYS: &[(&str, &[&str])] = &[
+    ("quit", &["q", "esc", "ctrl+c"]),
+    ("down", &["j", "down"]),
+    ("up", &["k", "up"]),
+    ("page_down", &["ctrl+d", "pagedown"]),
+    ("page_up", &["ctrl+u", "pageup"]),
+    ("top", &["g", "home"]),
+    ("bottom", &["G", "end"]),
+    ("toggle_stage", &["space", "enter"]),
+    ("stage", &["s"]),
+    ("unstage", &["u"]),
+    ("discard", &["d"]),
+    ("stage_all", &["a"]),
+    ("unstage_all", &["A"]),
+    ("refresh", &["r"]),
+    ("toggle_untracked", &["t"]),
+    ("help", &["?"]),
+    ("switch_section", &["tab"]),
+    ("collapse", &["h"]),
+    ("expand", &["l"]),
+    ("commit", &["c"]),
+    ("generate_commit", &["C"]),
+];
+
+/// Default keys of the log TUI's actions
+pub const LOG_KEYS: &[(&str, &[&str])] = &[
+    ("quit", &["q", "esc", "ctrl+c"]),
+    ("down", &["j", "down"]),
+    ("up", &["k", "up"]),
+    ("page_down", &["ctrl+d", "pagedown"]),
+    ("page_up", &["ctrl+u", "pageup"]),
+    ("top", &["g", "home"]),
+    ("bottom", &["G", "end"]),
+    ("open", &["enter", "l", "right"]),
+    ("back", &["h", "left"]),
+    ("checkout", &["c"]),
+    ("search", &["/", "s"]),
+    ("whitespace", &["w"]),
+];
+
+/// Default keys of the branch TUI's actions
+pub const BRANCH_KEYS: &[(&str, &[&str])] = &[
+    ("quit", &["q", "esc", "ctrl+c"]),
+    ("down", &["j", "down"]),
+    ("up", &["k", "up"]),
+    ("top", &["g"]),
+    ("bottom", &["G"]),
+    ("open", &["l", "right"]),
+    ("back", &["h", "left"]),
+    ("checkout", &["c"]),
+    ("switch", &["enter"]),
+    ("delete", &["d"]),
+    ("rename", &["r"]),
+    ("search", &["/"]),
+];
+
+static SETTINGS: OnceLock<UiSection> = OnceLock::new();
+
+/// Load [ui] from the repository's helix.toml for the rest of the process. Only the
+/// first call reads it; helix.toml was validated on startup, so errors fall back to
+/// the defaults.
+pub fn init(repo_path: &Path) {
+    SETTINGS.get_or_init(|| crate::output::load_ui(repo_path));
+}
+
+fn settings() -> &'static UiSection {
+    SETTINGS.get_or_init(UiSection::default)
+}
+
+pub fn palette() -> Palette {
+    settings().theme.palette()
+}
+
+pub fn keys() -> &'static KeyBindings {
+    &settings().keys
+}
+
+pub fn mouse_enabled() -> bool {
+    settings().mouse.unwrap_or(true)
+}
+
+/// A timestamp in the configured date format, or as `default` shows it when there's none
+pub fn format_date(timestamp: u64, default: impl FnOnce(u64) -> String) -> String {
+    match &settings().date_format {
+        Some(format) => format.format(timestamp),
+        None => default(timestamp),
+    }
+}
+
+/// "5 minutes ago"
+pub fn relative_time(timestamp: u64) -> String {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let seconds = now.saturating_sub(timestamp);
+
+    if seconds < 60 {
+        format!("{} seconds ago", seconds)
+    } else if seconds < 3600 {
+        format!("{} minutes ago", seconds / 60)
+    } else if seconds < 86400 {
+        format!("{} hours ago", seconds / 3600)
+    } else if seconds < 2592000 {
+        format!("{} days ago", seconds / 86400)
+    } else if seconds < 31536000 {
+        format!("{} months ago", seconds / 2592000)
+    } else {
+        format!("{} years ago", seconds / 31536000)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    /// For terminals with a light background
+    Light,
+}
+
+/// The colors the TUIs draw with, by what they're used for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// Keys, labels and the focused pane's border
+    pub accent: Color,
+    /// Pane titles
+    pub title: Color,
+    pub text: Color,
+    /// Hints, unfocused borders and other secondary text
+    pub muted: Color,
+    /// Less prominent than `text`, more than `muted`
+    pub subtle: Color,
+    /// The selected row's text and other things to notice
+    pub highlight: Color,
+    /// Behind the selected row and the footer
+    pub selection: Color,
+    /// Behind popups
+    pub background: Color,
+    pub added: Color,
+    pub removed: Color,
+    /// The repository name, upstreams and graph lines
+    pub special: Color,
+}
+
+impl Theme {
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Dark => Palette {
+                accent: Color::Cyan,
+                title: Color::Blue,
+                text: Color::White,
+                muted: Color::DarkGray,
+                subtle: Color::Gray,
+                highlight: Color::Yellow,
+                selection: Color::DarkGray,
+                background: Color::Black,
+                added: Color::Green,
+                removed: Color::Red,
+                special: Color::Magenta,
+            },
+            Theme::Light => Palette {
+                accent: Color::Blue,
+                title: Color::Blue,
+                text: Color::Black,
+                muted: Color::Gray,
+                subtle: Color::DarkGray,
+                highlight: Color::Magenta,
+                selection: Color::Gray,
+                background: Color::White,
+                added: Color::Green,
+                removed: Color::Red,
+                special: Color::Cyan,
+            },
+        }
+    }
+}
+
+impl Palette {
+    /// The characters a search matched
+    pub fn search_match(&self) -> Style {
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    }
+}
+
+/// How the TUIs show dates: "relative" or a strftime format
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateFormat {
+    Relative,
+    Strftime(String),
+}
+
+impl DateFormat {
+    pub fn format(&self, timestamp: u64) -> String {
+        match self {
+            DateFormat::Relative => relative_time(timestamp),
+            DateFormat::Strftime(format) => DateTime::from_timestamp(timestamp as i64, 0)
+                .unwrap_or_default()
+                .format(format)
+                .to_string(),
+        }
+    }
+}
+
+impl TryFrom<String> for DateFormat {
+    type Error = String;
+
+    fn try_from(format: String) -> std::result::Result<Self, String> {
+        if format == "relative" {
+            return Ok(DateFormat::Relative);
+        }
+        // Formatting with a bad item panics, so they're caught here
+        if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+            return Err(format!(
+                "invalid date_format `{}`: expected \"relative\" or a strftime format",
+                format
+            ));
+        }
+        Ok(DateFormat::Strftime(format))
+    }
+}
+
+impl From<DateFormat> for String {
+    fn from(format: DateFormat) -> String {
+        match format {
+            DateFormat::Relative => "relative".to_string(),
+            DateFormat::Strftime(format) => format,
+        }
+    }
+}
+
+/// One key, with the modifiers that must be held
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyPress {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => bail!("unknown modifier `{}` in key `{}`", modifier, text),
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => bail!("unknown key `{}`", text),
+                },
+            },
+        };
+
+        // Shift is in the character itself: "G", not "shift+g"
+        if let KeyCode::Char(c) = code {
+            if modifiers.contains(KeyModifiers::SHIFT) {
+                modifiers.remove(KeyModifiers::SHIFT);
+                return Ok(Self {
+                    code: KeyCode::Char(c.to_ascii_uppercase()),
+                    modifiers,
+                });
+            }
+        }
+        Ok(Self { code, modifiers })
+    }
+
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Terminals report shift with uppercase letters and symbols; the char says it all
+        let ignored = match key.code {
+            KeyCode::Char(_) => KeyModifiers::SHIFT,
+            _ => KeyModifiers::NONE,
+        };
+        let modifiers = key.modifiers - ignored;
+        let wanted = KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+        self.code == key.code && modifiers & wanted == self.modifiers
+    }
+}
+
+/// [ui.keys]: the keys for each remapped action
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    try_from = "BTreeMap<String, OneOrMany>",
+    into = "BTreeMap<String, OneOrMany>"
+)]
+pub struct KeyBindings {
+    bindings: BTreeMap<String, (Vec<String>, Vec<KeyPress>)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyBindings {
+    /// Every action name a TUI knows
+    pub fn action_names() -> Vec<&'static str> {
+        let mut names: Vec<&str> = [STATUS_KEYS, LOG_KEYS, BRANCH_KEYS]
+            .iter()
+            .flat_map(|keys| keys.iter().map(|(name, _)| *name))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The action in `defaults` that `key` is bound to, if any
+    pub fn action(
+        &self,
+        key: &KeyEvent,
+        defaults: &[(&'static str, &[&str])],
+    ) -> Option<&'static str> {
+        defaults.iter().find_map(|&(action, default_keys)| {
+            let bound = match self.bindings.get(action) {
+                Some((_, keys)) => keys.iter().any(|k| k.matches(key)),
+                None => default_keys
+                    .iter()
+                    .filter_map(|k| KeyPress::parse(k).ok())
+                    .any(|k| k.matches(key)),
+            };
+            bound.then_some(action)
+        })
+    }
+}
+
+impl TryFrom<BTreeMap<String, OneOrMany>> for KeyBindings {
+    type Error = String;
+
+    fn try_from(table: BTreeMap<String, OneOrMany>) -> std::result::Result<Self, String> {
+        let names = Self::action_names();
+        let mut bindings = BTreeMap::new();
+        for (action, keys) in table {
+            if !names.contains(&action.as_str()) {
+                return Err(format!(
+                    "unknown action `{}` in [ui.keys]; valid actions are: {}",
+                    action,
+                    names.join(", ")
+                ));
+            }
+            let keys = match keys {
+                OneOrMany::One(key) => vec![key],
+                OneOrMany::Many(keys) => keys,
+            };
+            let parsed = keys
+                .iter()
+                .map(|key| KeyPress::parse(key))
+                .collect::<Result<Vec<_>>>()
+                .map_err(|e| format!("{} for `{}` in [ui.keys]", e, action))?;
+            bindings.insert(action, (keys, parsed));
+        }
+        Ok(Self { bindings })
+    }
+}
+
+impl From<KeyBindings> for BTreeMap<String, OneOrMany> {
+    fn from(keys: KeyBindings) -> Self {
+        keys.bindings
+            .into_iter()
+            .map(|(action, (keys, _))| (action, OneOrMany::Many(keys)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_command::HelixConfig;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_ui_section_keys_and_formats() -> Result<()> {
+        let config: HelixConfig = toml::from_str(
+            "[ui]\ntheme = \"light\"\ndate_format = \"%Y-%m-%d\"\n\n[ui.keys]\nquit = \"x\"\ndown = [\"j\", \"ctrl+n\"]\n",
+        )?;
+        let ui = config.ui.unwrap();
+        assert_eq!(ui.theme, Theme::Light);
+        assert_eq!(ui.date_format.unwrap().format(0), "1970-01-01");
+
+        // A binding replaces the defaults; other actions keep theirs
+        let quit = ui
+            .keys
+            .action(&press(KeyCode::Char('x'), KeyModifiers::NONE), STATUS_KEYS);
+        assert_eq!(quit, Some("quit"));
+        assert_eq!(
+            ui.keys
+                .action(&press(KeyCode::Char('q'), KeyModifiers::NONE), STATUS_KEYS),
+            None
+        );
+        let down = press(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert_eq!(ui.keys.action(&down, STATUS_KEYS), Some("down"));
+        let bottom = press(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(ui.keys.action(&bottom, LOG_KEYS), Some("bottom"));
+        // ctrl+c isn't c
+        let ctrl_c = press(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(
+            KeyBindings::default().action(&ctrl_c, STATUS_KEYS),
+            Some("quit")
+        );
+
+        let error = toml::from_str::<HelixConfig>("[ui.keys]\nquitt = \"x\"\n").unwrap_err();
+        assert!(error.message().contains("unknown action `quitt`"));
+        assert!(error.message().contains("generate_commit"));
+        let error = toml::from_str::<HelixConfig>("[ui.keys]\nquit = \"hyper+x\"\n").unwrap_err();
+        assert!(error.message().contains("unknown modifier `hyper`"));
+        assert!(toml::from_str::<HelixConfig>("[ui]\ndate_format = \"%Q\"\n").is_err());
+        assert!(toml::from_str::<HelixConfig>("[ui]\ntheme = \"solarized\"\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,442 @@
+/*
+Line diffs in unified format.
+
+Lines are compared with Myers' O(ND) algorithm after trimming the common
+prefix and suffix, then grouped into hunks with `context` unchanged lines on
+either side:
+
+  @@ -12,7 +12,8 @@
+   unchanged
+  -removed
+  +added
+
+Lines keep their terminators, so a missing newline at the end of a file is a
+change of its own and is printed with Git's "\ No newline at end of file"
+marker.
+
+With Whitespace::IgnoreChange (diff -b) lines are compared with runs of
+whitespace collapsed and trailing whitespace dropped, and with
+Whitespace::IgnoreAll (diff -w) with all whitespace removed. Only the
+comparison changes: hunks still print the lines as they are, and a file whose
+lines all compare equal has no hunks.
+
+The backtracking trace grows with the square of the number of edits, so when
+two files differ by more than MAX_EDIT_COST lines the differing middle is
+reported as one delete-all/insert-all block instead.
+*/
+use std::borrow::Cow;
+use std::fmt::Write;
+
+const MAX_EDIT_COST: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Line `old` of the old file equals line `new` of the new file
+    Equal {
+        old: usize,
+        new: usize,
+    },
+    Delete {
+        old: usize,
+    },
+    Insert {
+        new: usize,
+    },
+}
+
+impl Op {
+    fn is_change(&self) -> bool {
+        !matches!(self, Op::Equal { .. })
+    }
+}
+
+/// How whitespace counts when lines are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    #[default]
+    Exact,
+    /// Any run of whitespace equals any other, and trailing whitespace is ignored. Whitespace
+    /// where there was none is still a change.
+    IgnoreChange,
+    /// Whitespace is ignored entirely
+    IgnoreAll,
+}
+
+impl Whitespace {
+    /// What a line is compared by
+    fn key(self, line: &str) -> Cow<'_, str> {
+        match self {
+            Whitespace::Exact => Cow::Borrowed(line),
+            Whitespace::IgnoreChange => {
+                let mut key = String::with_capacity(line.len());
+                for c in line.trim_end().chars() {
+                    if !c.is_whitespace() {
+                        key.push(c);
+                    } else if !key.ends_with(' ') {
+                        key.push(' ');
+                    }
+                }
+                Cow::Owned(key)
+            }
+            Whitespace::IgnoreAll => {
+                Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    /// 1-based, or the line before the hunk when `old_len` is 0
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    /// Lines prefixed by ' ', '-' or '+', terminators included
+    pub lines: Vec<(char, &'a str)>,
+}
+
+impl Hunk<'_> {
+    pub fn header(&self) -> String {
+        format!(
+            "@@ -{} +{} @@",
+            format_range(self.old_start, self.old_len),
+            format_range(self.new_start, self.new_len)
+        )
+    }
+
+    pub fn write_to(&self, out: &mut String) {
+        let _ = writeln!(out, "{}", self.header());
+        for (prefix, line) in &self.lines {
+            out.push(*prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+}
+
+/// Git omits the length when it is 1
+fn format_range(start: usize, len: usize) -> String {
+    if len == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+/// Split text into lines, keeping each line's terminator
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Hunks turning `old` into `new`, empty when they are equal
+pub fn unified_diff<'a>(old: &'a str, new: &'a str, context: usize) -> Vec<Hunk<'a>> {
+    unified_diff_with(old, new, context, Whitespace::Exact)
+}
+
+/// Hunks turning `old` into `new`, comparing lines as `whitespace` says
+pub fn unified_diff_with<'a>(
+    old: &'a str,
+    new: &'a str,
+    context: usize,
+    whitespace: Whitespace,
+) -> Vec<Hunk<'a>> {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    hunks_with(&old_lines, &new_lines, context, whitespace)
+}
+
+/// Group an edit script into hunks with `context` lines around each change
+pub fn hunks<'a>(old: &[&'a str], new: &[&'a str], context: usize) -> Vec<Hunk<'a>> {
+    hunks_with(old, new, context, Whitespace::Exact)
+}
+
+/// `hunks`, comparing lines as `whitespace` says
+pub fn hunks_with<'a>(
+    old: &[&'a str],
+    new: &[&'a str],
+    context: usize,
+    whitespace: Whitespace,
+) -> Vec<Hunk<'a>> {
+    let ops = match whitespace {
+        Whitespace::Exact => diff_lines(old, new),
+        _ => {
+            let old_keys: Vec<_> = old.iter().map(|line| whitespace.key(line)).collect();
+            let new_keys: Vec<_> = new.iter().map(|line| whitespace.key(line)).collect();
+            diff_lines(&old_keys, &new_keys)
+        }
+    };
+
+    // Lines of each file before op i
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for op in &ops {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal { .. } => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Op::Delete { .. } => old_pos += 1,
+            Op::Insert { .. } => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    // Op ranges to print, merged when their context would overlap
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if !op.is_change() {
+            continue;
+        }
+        let start = i.saturating_sub(context);
+        let end = (i + 1 + context).min(ops.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let (old_from, new_from) = positions[start];
+            let (old_to, new_to) = positions[end];
+            let lines = ops[start..end]
+                .iter()
+                .map(|op| match *op {
+                    Op::Equal { old: o, .. } => (' ', old[o]),
+                    Op::Delete { old: o } => ('-', old[o]),
+                    Op::Insert { new: n } => ('+', new[n]),
+                })
+                .collect();
+
+            let old_len = old_to - old_from;
+            let new_len = new_to - new_from;
+            Hunk {
+                old_start: if old_len == 0 { old_from } else { old_from + 1 },
+                old_len,
+                new_start: if new_len == 0 { new_from } else { new_from + 1 },
+                new_len,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Shortest edit script turning `old` into `new`
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal { old: i, new: i }).collect();
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    let middle = myers(old_mid, new_mid).unwrap_or_else(|| {
+        (0..old_mid.len())
+            .map(|o| Op::Delete { old: o })
+            .chain((0..new_mid.len()).map(|n| Op::Insert { new: n }))
+            .collect()
+    });
+    ops.extend(middle.into_iter().map(|op| match op {
+        Op::Equal { old: o, new: n } => Op::Equal {
+            old: o + prefix,
+            new: n + prefix,
+        },
+        Op::Delete { old: o } => Op::Delete { old: o + prefix },
+        Op::Insert { new: n } => Op::Insert { new: n + prefix },
+    }));
+
+    let old_suffix = old.len() - suffix;
+    let new_suffix = new.len() - suffix;
+    ops.extend((0..suffix).map(|i| Op::Equal {
+        old: old_suffix + i,
+        new: new_suffix + i,
+    }));
+
+    ops
+}
+
+/// Myers' greedy algorithm. None when the edit cost exceeds MAX_EDIT_COST.
+fn myers<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<Op>> {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let max = (n + m) as usize;
+    if max == 0 {
+        return Some(Vec::new());
+    }
+
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    // trace[d] is v as it was before step d, restricted to diagonals -d..=d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        if d as usize > MAX_EDIT_COST {
+            return None;
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, saved) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| saved[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { at(prev_k) };
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(Op::Equal {
+                old: x as usize,
+                new: y as usize,
+            });
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(Op::Insert {
+                    new: (y - 1) as usize,
+                });
+            } else {
+                ops.push(Op::Delete {
+                    old: (x - 1) as usize,
+                });
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    Some(ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(old: &str, new: &str, context: usize) -> String {
+        let mut out = String::new();
+        for hunk in unified_diff(old, new, context) {
+            hunk.write_to(&mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn test_diff_lines_is_minimal() {
+        let old = ["a", "b", "c", "a", "b", "b", "a"];
+        let new = ["c", "b", "a", "b", "a", "c"];
+        let ops = diff_lines(&old, &new);
+
+        let changes = ops.iter().filter(|op| op.is_change()).count();
+        assert_eq!(changes, 5);
+
+        // Replaying the script reproduces the new file
+        let rebuilt: Vec<&str> = ops
+            .iter()
+            .filter_map(|op| match *op {
+                Op::Equal { old: o, .. } => Some(old[o]),
+                Op::Insert { new: n } => Some(new[n]),
+                Op::Delete { .. } => None,
+            })
+            .collect();
+        assert_eq!(rebuilt, new);
+    }
+
+    #[test]
+    fn test_unified_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n";
+
+        assert_eq!(
+            render(old, new, 1),
+            "@@ -2,3 +2,3 @@\n 2\n-3\n+three\n 4\n@@ -10 +10,2 @@\n 10\n+11\n"
+        );
+        // Enough context merges both changes into one hunk
+        assert_eq!(render(old, new, 4).matches("@@ -").count(), 1);
+        assert_eq!(render(old, old, 3), "");
+    }
+
+    #[test]
+    fn test_added_file_and_missing_newline() {
+        assert_eq!(render("", "a\nb\n", 3), "@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(
+            render("a\n", "a", 3),
+            "@@ -1 +1 @@\n-a\n+a\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_ignore_whitespace() {
+        let diff = |old, new, whitespace| {
+            let mut out = String::new();
+            for hunk in unified_diff_with(old, new, 1, whitespace) {
+                hunk.write_to(&mut out);
+            }
+            out
+        };
+        let old = "fn main() {\n    let x = 1;\n    run(x);\n}\n";
+        let reindented = "fn main() {\n\tlet x  =  1;  \n\trun(x);\n}\n";
+
+        let exact = diff(old, reindented, Whitespace::Exact);
+        assert_eq!(exact.lines().filter(|l| l.starts_with('-')).count(), 2);
+        assert_eq!(diff(old, reindented, Whitespace::IgnoreChange), "");
+        assert_eq!(diff(old, reindented, Whitespace::IgnoreAll), "");
+
+        // -b still sees whitespace appear inside a word, -w doesn't
+        let split = "fn main() {\n    let x = 1;\n    ru n(x);\n}\n";
+        assert_eq!(
+            diff(old, split, Whitespace::IgnoreChange),
+            "@@ -2,3 +2,3 @@\n     let x = 1;\n-    run(x);\n+    ru n(x);\n }\n"
+        );
+        assert_eq!(diff(old, split, Whitespace::IgnoreAll), "");
+        assert_eq!(
+            diff("x\n", " x\n", Whitespace::IgnoreChange)
+                .lines()
+                .count(),
+            3
+        );
+
+        // Real changes are printed with the lines as they are
+        let changed = "fn main() {\n\tlet x = 2;\n\trun(x);\n}\n";
+        assert_eq!(
+            diff(old, changed, Whitespace::IgnoreAll),
+            "@@ -1,3 +1,3 @@\n fn main() {\n-    let x = 1;\n+\tlet x = 2;\n     run(x);\n"
+        );
+    }
+}
@@ -0,0 +1,494 @@
+/*
+`helix verify` - repository integrity check (fsck).
+
+Without `--all` only .helix/helix.idx is verified (format, checksum, repo
+fingerprint). With `--all` the whole repository is checked:
+
+1. Every object under .helix/objects is decompressed and re-hashed; the BLAKE3
+   hash must match its file name.
+2. Every tree entry must reference an existing blob (files, symlinks) or tree.
+3. Every commit must reference an existing tree and existing parents.
+4. Every annotated tag must reference an existing commit.
+5. HEAD and every ref under .helix/refs must point to an existing commit, or
+   (tags) an existing annotated tag.
+6. Every tracked entry in helix.idx must reference an existing blob.
+
+Problems are collected instead of stopping at the first one, so a single run
+reports everything that is wrong. The caller exits nonzero when the report is
+not clean.
+*/
+use anyhow::{Context, Result};
+use helix_protocol::hash::{hash_to_hex, hex_to_hash, is_zero_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use helix_protocol::tag::Tag;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::helix_index::commit::Commit;
+use crate::helix_index::tree::{EntryType, Tree};
+use crate::helix_index::verify::{Verifier, VerifyResult};
+use crate::helix_index::{EntryFlags, Reader};
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Check objects, refs and index references, not just helix.idx
+    pub all: bool,
+    pub verbose: bool,
+}
+
+/// A single integrity problem found during verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// helix.idx is missing, corrupted or belongs to another repo
+    Index(String),
+    /// Object could not be read, decompressed or parsed, or its hash doesn't match
+    CorruptObject {
+        kind: &'static str,
+        hash: Hash,
+        reason: String,
+    },
+    /// An object references another object that doesn't exist
+    MissingObject {
+        kind: &'static str,
+        hash: Hash,
+        referenced_by: String,
+    },
+    /// A ref is unreadable or doesn't contain a valid hash
+    BadRef { name: String, reason: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Index(reason) => write!(f, "index: {}", reason),
+            Problem::CorruptObject { kind, hash, reason } => {
+                write!(f, "corrupt {} {}: {}", kind, hash_to_hex(hash), reason)
+            }
+            Problem::MissingObject {
+                kind,
+                hash,
+                referenced_by,
+            } => write!(
+                f,
+                "missing {} {} (referenced by {})",
+                kind,
+                hash_to_hex(hash),
+                referenced_by
+            ),
+            Problem::BadRef { name, reason } => write!(f, "bad ref {}: {}", name, reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub blobs: usize,
+    pub trees: usize,
+    pub commits: usize,
+    pub tags: usize,
+    pub refs: usize,
+    pub index_entries: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn print_summary(&self) {
+        for problem in &self.problems {
+            println!("error: {}", problem);
+        }
+
+        println!(
+            "Checked {} blobs, {} trees, {} commits, {} tags, {} refs, {} index entries",
+            self.blobs, self.trees, self.commits, self.tags, self.refs, self.index_entries
+        );
+
+        if self.is_ok() {
+            println!("No problems found");
+        } else {
+            println!("Found {} problem(s)", self.problems.len());
+        }
+    }
+}
+
+pub fn verify(repo_path: &Path, options: VerifyOptions) -> Result<VerifyReport> {
+    if !repo_path.join(".helix").exists() {
+        anyhow::bail!("Not a helix repository (no .helix directory)");
+    }
+
+    let mut report = VerifyReport::default();
+
+    match Verifier::new(repo_path).verify()? {
+        VerifyResult::Valid => {}
+        VerifyResult::Missing => report
+            .problems
+            .push(Problem::Index("helix.idx not found".into())),
+        VerifyResult::WrongRepo => report.problems.push(Problem::Index(
+            "helix.idx belongs to a different repository".into(),
+        )),
+        VerifyResult::Corrupted => report.problems.push(Problem::Index(
+            "helix.idx is corrupted (checksum or format error)".into(),
+        )),
+    }
+
+    if !options.all {
+        return Ok(report);
+    }
+
+    let store = FsObjectStore::new(repo_path);
+
+    let blobs = check_objects(&store, &ObjectType::Blob, &mut report, options.verbose)?;
+    let trees = check_objects(&store, &ObjectType::Tree, &mut report, options.verbose)?;
+    let commits = check_objects(&store, &ObjectType::Commit, &mut report, options.verbose)?;
+    let tags = check_objects(&store, &ObjectType::Tag, &mut report, options.verbose)?;
+    report.blobs = blobs.len();
+    report.trees = trees.len();
+    report.commits = commits.len();
+    report.tags = tags.len();
+
+    let blob_set: HashSet<Hash> = blobs.iter().map(|(h, _)| *h).collect();
+    let tree_set: HashSet<Hash> = trees.iter().map(|(h, _)| *h).collect();
+    let commit_set: HashSet<Hash> = commits.iter().map(|(h, _)| *h).collect();
+
+    // Tree -> blob / subtree references
+    for (hash, raw) in &trees {
+        let tree = match Tree::from_bytes(raw) {
+            Ok(tree) => tree,
+            Err(e) => {
+                report.problems.push(Problem::CorruptObject {
+                    kind: "tree",
+                    hash: *hash,
+                    reason: format!("cannot parse: {}", e),
+                });
+                continue;
+            }
+        };
+
+        for entry in &tree.entries {
+            let (kind, exists) = match entry.entry_type {
+                EntryType::Tree => ("tree", tree_set.contains(&entry.oid)),
+                _ => ("blob", blob_set.contains(&entry.oid)),
+            };
+            if !exists {
+                report.problems.push(Problem::MissingObject {
+                    kind,
+                    hash: entry.oid,
+                    referenced_by: format!("tree {} entry '{}'", short(hash), entry.name),
+                });
+            }
+        }
+    }
+
+    // Commit -> tree / parent references
+    for (hash, raw) in &commits {
+        let commit = match Commit::from_bytes(raw) {
+            Ok(commit) => commit,
+            Err(e) => {
+                report.problems.push(Problem::CorruptObject {
+                    kind: "commit",
+                    hash: *hash,
+                    reason: format!("cannot parse: {}", e),
+                });
+                continue;
+            }
+        };
+
+        if !tree_set.contains(&commit.tree_hash) {
+            report.problems.push(Problem::MissingObject {
+                kind: "tree",
+                hash: commit.tree_hash,
+                referenced_by: format!("commit {}", short(hash)),
+            });
+        }
+        for parent in &commit.parents {
+            if !commit_set.contains(parent) {
+                report.problems.push(Problem::MissingObject {
+                    kind: "commit",
+                    hash: *parent,
+                    referenced_by: format!("commit {} (parent)", short(hash)),
+                });
+            }
+        }
+    }
+
+    // Annotated tag -> commit references
+    let mut tag_set: HashSet<Hash> = HashSet::new();
+    for (hash, raw) in &tags {
+        match Tag::from_bytes(raw) {
+            Ok(tag) => {
+                if !commit_set.contains(&tag.target) {
+                    report.problems.push(Problem::MissingObject {
+                        kind: "commit",
+                        hash: tag.target,
+                        referenced_by: format!("tag {} ({})", short(hash), tag.name),
+                    });
+                }
+                tag_set.insert(*hash);
+            }
+            Err(e) => report.problems.push(Problem::CorruptObject {
+                kind: "tag",
+                hash: *hash,
+                reason: format!("cannot parse: {}", e),
+            }),
+        }
+    }
+
+    check_refs(repo_path, &commit_set, &tag_set, &mut report)?;
+    check_index_oids(repo_path, &blob_set, &mut report);
+
+    Ok(report)
+}
+
+/// Re-hash every object of one type. Returns the raw bytes of each intact object.
+fn check_objects(
+    store: &FsObjectStore,
+    ty: &ObjectType,
+    report: &mut VerifyReport,
+    verbose: bool,
+) -> Result<Vec<(Hash, Vec<u8>)>> {
+    let kind = kind_name(ty);
+    let hashes = store.list_object_hashes(ty)?;
+
+    if verbose {
+        println!("Checking {} {}s...", hashes.len(), kind);
+    }
+
+    let results: Vec<(Hash, Result<Vec<u8>>)> = hashes
+        .par_iter()
+        .map(|hash| (*hash, store.read_object(ty, hash)))
+        .collect();
+
+    let mut intact = Vec::with_capacity(results.len());
+    for (hash, result) in results {
+        match result {
+            // Blob contents aren't needed after the hash check
+            Ok(_) if matches!(ty, ObjectType::Blob) => intact.push((hash, Vec::new())),
+            Ok(raw) => intact.push((hash, raw)),
+            Err(e) => report.problems.push(Problem::CorruptObject {
+                kind,
+                hash,
+                reason: format!("{:#}", e),
+            }),
+        }
+    }
+
+    Ok(intact)
+}
+
+/// HEAD and every file under .helix/refs must point to an existing commit.
+/// Tag refs may also point to an existing annotated tag.
+fn check_refs(
+    repo_path: &Path,
+    commits: &HashSet<Hash>,
+    tags: &HashSet<Hash>,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let helix_dir = repo_path.join(".helix");
+    let mut refs: Vec<(String, PathBuf)> = Vec::new();
+
+    let head_path = helix_dir.join("HEAD");
+    if head_path.exists() {
+        let head = fs::read_to_string(&head_path).context("Failed to read HEAD")?;
+        // Symbolic HEAD is covered by checking the ref it points to
+        if !head.trim().starts_with("ref:") {
+            refs.push(("HEAD".to_string(), head_path));
+        }
+    }
+
+    let refs_dir = helix_dir.join("refs");
+    if refs_dir.exists() {
+        for entry in WalkDir::new(&refs_dir).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .strip_prefix(&helix_dir)
+                .unwrap_or(entry.path())
+                .to_string_lossy()
+                .replace('\\', "/");
+            refs.push((name, entry.path().to_path_buf()));
+        }
+    }
+
+    for (name, path) in refs {
+        report.refs += 1;
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                report.problems.push(Problem::BadRef {
+                    name,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        match hex_to_hash(content.trim()) {
+            Ok(hash) if is_zero_hash(&hash) || commits.contains(&hash) => {}
+            Ok(hash) if name.starts_with("refs/tags/") && tags.contains(&hash) => {}
+            Ok(hash) => report.problems.push(Problem::MissingObject {
+                kind: "commit",
+                hash,
+                referenced_by: format!("ref {}", name),
+            }),
+            Err(_) => report.problems.push(Problem::BadRef {
+                name,
+                reason: format!("invalid hash '{}'", content.trim()),
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+/// Every tracked, non-deleted index entry must have its blob in the object store
+fn check_index_oids(repo_path: &Path, blobs: &HashSet<Hash>, report: &mut VerifyReport) {
+    // An unreadable index was already reported by the Verifier
+    let Ok(index) = Reader::new(repo_path).read() else {
+        return;
+    };
+
+    report.index_entries = index.entries.len();
+
+    for entry in &index.entries {
+        if !entry.flags.contains(EntryFlags::TRACKED)
+            || entry.flags.contains(EntryFlags::DELETED)
+            || is_zero_hash(&entry.oid)
+        {
+            continue;
+        }
+
+        if !blobs.contains(&entry.oid) {
+            report.problems.push(Problem::MissingObject {
+                kind: "blob",
+                hash: entry.oid,
+                referenced_by: format!("index entry '{}'", entry.path.display()),
+            });
+        }
+    }
+}
+
+fn kind_name(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blob",
+        ObjectType::Tree => "tree",
+        ObjectType::Commit => "commit",
+        ObjectType::Tag => "tag",
+    }
+}
+
+fn short(hash: &Hash) -> String {
+    hash_to_hex(hash)[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_index::tree::TreeEntry;
+    use crate::helix_index::{Entry, Header, Writer};
+    use tempfile::TempDir;
+
+    /// Repo with one blob, one tree, one commit, a branch ref and an index entry
+    fn setup_repo(repo: &Path) -> Result<(Hash, Hash, Hash)> {
+        fs::create_dir_all(repo.join(".helix/refs/heads"))?;
+        let store = FsObjectStore::new(repo);
+
+        let blob = store.write_object(&ObjectType::Blob, b"hello")?;
+
+        let mut tree = Tree::new();
+        tree.add_entry(TreeEntry::new_file("hello.txt".into(), blob, 0o100644, 5));
+        let tree_hash = store.write_object(&ObjectType::Tree, &tree.to_bytes())?;
+
+        let commit = Commit::initial(tree_hash, "Test <test@test.com>".into(), "init".into());
+        let commit_hash = store.write_object(&ObjectType::Commit, &commit.to_bytes())?;
+
+        fs::write(repo.join(".helix/HEAD"), "ref: refs/heads/main\n")?;
+        fs::write(
+            repo.join(".helix/refs/heads/main"),
+            hash_to_hex(&commit_hash),
+        )?;
+
+        let mut entry = Entry::new(PathBuf::from("hello.txt"), 5, 0, blob, 0o100644);
+        entry.flags = EntryFlags::TRACKED;
+        let header = Header::new(1, 1);
+        Writer::new_canonical(repo).write(&header, &[entry])?;
+
+        Ok((blob, tree_hash, commit_hash))
+    }
+
+    #[test]
+    fn test_verify_all_clean_repo() -> Result<()> {
+        let temp = TempDir::new()?;
+        setup_repo(temp.path())?;
+
+        let report = verify(
+            temp.path(),
+            VerifyOptions {
+                all: true,
+                ..Default::default()
+            },
+        )?;
+
+        assert!(report.is_ok(), "unexpected problems: {:?}", report.problems);
+        assert_eq!(
+            (report.blobs, report.trees, report.commits, report.refs),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(report.index_entries, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_all_detects_corruption_and_missing_objects() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repo = temp.path();
+        let (blob, tree_hash, _) = setup_repo(repo)?;
+
+        // Overwrite the tree with a different (validly compressed) payload
+        let store = FsObjectStore::new(repo);
+        let tree_path = store.object_path(&ObjectType::Tree, &tree_hash);
+        fs::write(&tree_path, zstd::encode_all(&b"garbage"[..], 3)?)?;
+
+        // Remove the blob referenced by the index
+        fs::remove_file(store.object_path(&ObjectType::Blob, &blob))?;
+
+        // Ref pointing at nothing
+        fs::write(repo.join(".helix/refs/heads/broken"), "not-a-hash")?;
+
+        let report = verify(
+            repo,
+            VerifyOptions {
+                all: true,
+                ..Default::default()
+            },
+        )?;
+
+        assert!(!report.is_ok());
+        let has = |pred: &dyn Fn(&Problem) -> bool| report.problems.iter().any(pred);
+
+        assert!(has(
+            &|p| matches!(p, Problem::CorruptObject { kind: "tree", hash, .. } if *hash == tree_hash)
+        ));
+        assert!(has(
+            &|p| matches!(p, Problem::MissingObject { kind: "tree", hash, .. } if *hash == tree_hash)
+        ));
+        assert!(has(
+            &|p| matches!(p, Problem::MissingObject { kind: "blob", hash, referenced_by } if *hash == blob && referenced_by.contains("index"))
+        ));
+        assert!(has(
+            &|p| matches!(p, Problem::BadRef { name, .. } if name == "refs/heads/broken")
+        ));
+        Ok(())
+    }
+}
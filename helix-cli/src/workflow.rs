@@ -40,16 +40,9 @@ impl Workflow {
             ));
         }
 
-        // Step 3: Get the diff
-        println!("📊 Reading staged changes...");
-        let diff = Git::get_staged_diff().context("Failed to get staged diff")?;
-
-        println!("🤖 Generating commit message with AI...");
-
-        // Step 4: Generate commit message
+        // Step 3 & 4: Read the diff and generate a commit message
         let (subject, body) = self
-            .llm
-            .gen_commit_message(&diff)
+            .staged_commit_message()
             .await
             .context("Failed to generate commit message")?;
 
@@ -100,11 +93,7 @@ impl Workflow {
             }
         }
 
-        println!("📊 Reading staged changes...");
-        let diff = Git::get_staged_diff()?;
-
-        println!("🤖 Generating commit message...");
-        let (subject, body) = self.llm.gen_commit_message(&diff).await?;
+        let (subject, body) = self.staged_commit_message().await?;
 
         println!("\n📝 Generated commit message:");
         println!("Subject: {}", subject);
@@ -137,6 +126,26 @@ impl Workflow {
         self.generate_message_only().await
     }
 
+    /// Commit message for the staged changes. Binary-only changes get a plain
+    /// summary instead of a round trip to the LLM with an empty diff.
+    async fn staged_commit_message(&self) -> Result<(String, Option<String>)> {
+        if let Some(files) = Git::get_staged_binary_only_files()? {
+            println!("📦 Only binary files changed, skipping AI message generation");
+            let subject = match files.as_slice() {
+                [file] => format!("Update {}", file),
+                _ => format!("Update {} binary files", files.len()),
+            };
+            let body = (files.len() > 1).then(|| files.join("\n"));
+            return Ok((subject, body));
+        }
+
+        println!("📊 Reading staged changes...");
+        let diff = Git::get_staged_diff().context("Failed to get staged diff")?;
+
+        println!("🤖 Generating commit message with AI...");
+        self.llm.gen_commit_message(&diff).await
+    }
+
     fn confirm_commit(&self) -> Result<bool> {
         use std::io::{self, Write};
 
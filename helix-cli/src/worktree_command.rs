@@ -0,0 +1,398 @@
+/*
+Linked worktrees: more than one working directory for a single repository.
+
+  helix worktree add <path> <branch>   check out <branch> into a new directory
+  helix worktree list                  show the main and linked worktrees
+  helix worktree remove <path>         delete a linked worktree
+
+A linked worktree has its own HEAD and index but shares objects, refs and
+config with the main repository. Its bookkeeping lives in the main repo:
+
+  <repo>/.helix/worktrees/<name>/
+    HEAD              per-worktree HEAD (ref: refs/heads/<branch>)
+    .helix/helix.idx  per-worktree index
+    path              absolute path of the working directory
+
+and the working directory gets a `.helix` *file* pointing back at it:
+
+  helixdir: <repo>/.helix/worktrees/<name>
+
+RepoContext::detect follows that pointer, so add/status/commit run inside a
+worktree read its index and HEAD while using the main repo's object store.
+*/
+use anyhow::{bail, Context, Result};
+use console::style;
+use helix_protocol::hash::{hash_to_hex, Hash};
+use helix_protocol::storage::FsRefStore;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::branch_command::validate_branch_name;
+use crate::checkout::{checkout_tree_to_path, CheckoutOptions};
+use crate::helix_index::api::HelixIndexData;
+use crate::helix_index::EntryFlags;
+use crate::sandbox_command::{build_index_entries_from_commit, write_sandbox_index, RepoContext};
+
+const LINK_PREFIX: &str = "helixdir:";
+
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeOptions {
+    /// Check out a branch even if another worktree already has it checked out
+    pub force: bool,
+    pub verbose: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    pub name: String,
+    /// The working directory
+    pub path: PathBuf,
+    /// None when HEAD is detached
+    pub branch: Option<String>,
+    pub head: Option<Hash>,
+}
+
+/// Create a linked worktree at `path` with `branch` checked out
+pub fn add_worktree(
+    repo_path: &Path,
+    path: &Path,
+    branch: &str,
+    options: WorktreeOptions,
+) -> Result<Worktree> {
+    validate_branch_name(branch)?;
+    let repo_root = RepoContext::detect(repo_path)?.repo_root;
+
+    let ref_name = format!("refs/heads/{}", branch);
+    let commit = FsRefStore::new(&repo_root)
+        .get_ref(&ref_name)?
+        .with_context(|| {
+            format!(
+                "Branch '{}' does not exist. Create it with 'helix switch -c {}'",
+                branch, branch
+            )
+        })?;
+
+    if !options.force {
+        if let Some(holder) = checked_out_in(&repo_root, branch)? {
+            bail!(
+                "Branch '{}' is already checked out at {} (use --force to check it out anyway)",
+                branch,
+                holder.display()
+            );
+        }
+    }
+
+    if path.exists() && fs::read_dir(path)?.next().is_some() {
+        bail!("'{}' already exists and is not empty", path.display());
+    }
+    fs::create_dir_all(path)
+        .with_context(|| format!("Failed to create worktree directory {}", path.display()))?;
+    let path = path.canonicalize()?;
+
+    let name = unique_name(&repo_root, &path)?;
+    let worktree_dir = worktrees_dir(&repo_root).join(&name);
+    fs::create_dir_all(&worktree_dir)?;
+
+    let checkout_options = CheckoutOptions {
+        verbose: options.verbose,
+        force: true,
+    };
+    let files_count = checkout_tree_to_path(&repo_root, &commit, None, &path, &checkout_options)?;
+
+    let entries = build_index_entries_from_commit(&repo_root, &commit, &path)?;
+    write_sandbox_index(&worktree_dir, &entries)?;
+
+    fs::write(worktree_dir.join("HEAD"), format!("ref: {}\n", ref_name))?;
+    fs::write(worktree_dir.join("path"), format!("{}\n", path.display()))?;
+    fs::write(
+        path.join(".helix"),
+        format!("{} {}\n", LINK_PREFIX, worktree_dir.display()),
+    )?;
+
+    println!(
+        "Created worktree '{}' at {} ({} files, branch '{}' at {})",
+        name,
+        path.display(),
+        files_count,
+        branch,
+        &hash_to_hex(&commit)[..8]
+    );
+
+    Ok(Worktree {
+        name,
+        path,
+        branch: Some(branch.to_string()),
+        head: Some(commit),
+    })
+}
+
+/// Linked worktrees of the repository, sorted by name
+pub fn list_worktrees(repo_path: &Path) -> Result<Vec<Worktree>> {
+    let repo_root = RepoContext::detect(repo_path)?.repo_root;
+    let dir = worktrees_dir(&repo_root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut worktrees = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let worktree_dir = entry?.path();
+        if !worktree_dir.is_dir() {
+            continue;
+        }
+        let path = fs::read_to_string(worktree_dir.join("path"))
+            .map(|p| PathBuf::from(p.trim()))
+            .unwrap_or_default();
+        let (branch, head) = read_worktree_head(&repo_root, &worktree_dir.join("HEAD"))?;
+
+        worktrees.push(Worktree {
+            name: entry_name(&worktree_dir),
+            path,
+            branch,
+            head,
+        });
+    }
+
+    worktrees.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(worktrees)
+}
+
+/// Delete a linked worktree, refusing when it has staged changes unless `force`
+pub fn remove_worktree(repo_path: &Path, path: &Path, force: bool) -> Result<()> {
+    let repo_root = RepoContext::detect(repo_path)?.repo_root;
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("No worktree at {}", path.display()))?;
+
+    let worktree = list_worktrees(&repo_root)?
+        .into_iter()
+        .find(|w| w.path == path)
+        .with_context(|| format!("'{}' is not a linked worktree", path.display()))?;
+    let worktree_dir = worktrees_dir(&repo_root).join(&worktree.name);
+
+    if !force {
+        let index =
+            HelixIndexData::load_from_path(&worktree_dir.join(".helix/helix.idx"), &repo_root)?;
+        if index
+            .entries()
+            .iter()
+            .any(|e| e.flags.contains(EntryFlags::STAGED))
+        {
+            bail!(
+                "Worktree '{}' has staged changes (use --force to remove it anyway)",
+                worktree.name
+            );
+        }
+    }
+
+    if path.exists() {
+        fs::remove_dir_all(&path)
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    fs::remove_dir_all(&worktree_dir)?;
+
+    println!("Removed worktree '{}'", worktree.name);
+    Ok(())
+}
+
+pub fn print_worktrees(repo_path: &Path, worktrees: &[Worktree]) -> Result<()> {
+    let repo_root = RepoContext::detect(repo_path)?.repo_root;
+    let (branch, head) = read_worktree_head(&repo_root, &repo_root.join(".helix/HEAD"))?;
+    print_line(&repo_root, branch.as_deref(), head.as_ref());
+
+    for worktree in worktrees {
+        print_line(
+            &worktree.path,
+            worktree.branch.as_deref(),
+            worktree.head.as_ref(),
+        );
+    }
+    Ok(())
+}
+
+fn print_line(path: &Path, branch: Option<&str>, head: Option<&Hash>) {
+    let head = head
+        .map(|h| hash_to_hex(h)[..8].to_string())
+        .unwrap_or_else(|| "(no commits)".to_string());
+    let branch = branch
+        .map(|b| format!("[{}]", b))
+        .unwrap_or_else(|| "(detached HEAD)".to_string());
+    println!(
+        "{}  {}  {}",
+        path.display(),
+        style(head).yellow(),
+        style(branch).cyan()
+    );
+}
+
+/// Read the admin dir a worktree's `.helix` pointer file refers to
+pub fn read_link(helix_file: &Path) -> Result<PathBuf> {
+    let content = fs::read_to_string(helix_file)
+        .with_context(|| format!("Failed to read {}", helix_file.display()))?;
+    let target = content
+        .trim()
+        .strip_prefix(LINK_PREFIX)
+        .with_context(|| format!("{} is not a worktree link", helix_file.display()))?;
+
+    let worktree_dir = PathBuf::from(target.trim());
+    if !worktree_dir.join("HEAD").exists() {
+        bail!(
+            "Worktree {} points at {}, which no longer exists",
+            helix_file.display(),
+            worktree_dir.display()
+        );
+    }
+    Ok(worktree_dir)
+}
+
+/// The main repository root for `<repo>/.helix/worktrees/<name>`
+pub fn repo_root_of(worktree_dir: &Path) -> Result<PathBuf> {
+    worktree_dir
+        .ancestors()
+        .nth(3)
+        .map(Path::to_path_buf)
+        .with_context(|| format!("Invalid worktree directory {}", worktree_dir.display()))
+}
+
+fn worktrees_dir(repo_root: &Path) -> PathBuf {
+    repo_root.join(".helix").join("worktrees")
+}
+
+fn entry_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Name the admin dir after the worktree directory, adding a suffix on collision
+fn unique_name(repo_root: &Path, path: &Path) -> Result<String> {
+    let base: String = entry_name(path)
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let base = if base.is_empty() {
+        "worktree".to_string()
+    } else {
+        base
+    };
+
+    let dir = worktrees_dir(repo_root);
+    let mut name = base.clone();
+    let mut n = 1;
+    while dir.join(&name).exists() {
+        name = format!("{}-{}", base, n);
+        n += 1;
+    }
+    Ok(name)
+}
+
+/// Branch and commit of a HEAD file
+fn read_worktree_head(
+    repo_root: &Path,
+    head_path: &Path,
+) -> Result<(Option<String>, Option<Hash>)> {
+    let content = fs::read_to_string(head_path)
+        .with_context(|| format!("Failed to read {}", head_path.display()))?;
+    let content = content.trim();
+
+    match content.strip_prefix("ref:") {
+        Some(ref_name) => {
+            let ref_name = ref_name.trim();
+            let branch = ref_name
+                .strip_prefix("refs/heads/")
+                .unwrap_or(ref_name)
+                .to_string();
+            let head = FsRefStore::new(repo_root).get_ref(ref_name)?;
+            Ok((Some(branch), head))
+        }
+        None => Ok((None, helix_protocol::hash::hex_to_hash(content).ok())),
+    }
+}
+
+/// Where `branch` is checked out, if anywhere
+fn checked_out_in(repo_root: &Path, branch: &str) -> Result<Option<PathBuf>> {
+    let (main_branch, _) = read_worktree_head(repo_root, &repo_root.join(".helix/HEAD"))?;
+    if main_branch.as_deref() == Some(branch) {
+        return Ok(Some(repo_root.to_path_buf()));
+    }
+
+    Ok(list_worktrees(repo_root)?
+        .into_iter()
+        .find(|w| w.branch.as_deref() == Some(branch))
+        .map(|w| w.path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::add_command::{add, AddOptions};
+    use crate::branch_command::{create_branch, get_current_branch, BranchOptions};
+    use crate::commit_command::{commit, CommitOptions};
+    use crate::init_command::init_helix_repo;
+    use tempfile::TempDir;
+
+    fn commit_file(path: &Path, name: &str, content: &str, message: &str) -> Result<Hash> {
+        fs::write(path.join(name), content)?;
+        add(path, &[PathBuf::from(name)], AddOptions::default())?;
+        commit(
+            path,
+            CommitOptions {
+                message: message.to_string(),
+                author: Some("Test User <test@example.com>".into()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_worktree_has_own_head_and_index() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path().join("repo");
+        fs::create_dir_all(&repo)?;
+        init_helix_repo(&repo, None)?;
+        let repo = repo.canonicalize()?;
+
+        let first = commit_file(&repo, "a.txt", "one", "first")?;
+        create_branch(&repo, "feature", BranchOptions::default())?;
+
+        // The main repo's branch can't be checked out twice
+        let wt = temp_dir.path().join("wt");
+        assert!(add_worktree(&repo, &wt, "main", WorktreeOptions::default()).is_err());
+
+        let worktree = add_worktree(&repo, &wt, "feature", WorktreeOptions::default())?;
+        assert_eq!(fs::read_to_string(wt.join("a.txt"))?, "one");
+        assert!(wt.join(".helix").is_file());
+
+        let context = RepoContext::detect(&wt)?;
+        assert!(context.is_worktree());
+        assert_eq!(context.repo_root, repo);
+        assert_eq!(context.workdir, worktree.path);
+        assert_eq!(get_current_branch(&wt)?, "feature");
+
+        // Committing in the worktree moves its branch, not the main repo's
+        let second = commit_file(&worktree.path, "b.txt", "two", "second")?;
+        let refs = FsRefStore::new(&repo);
+        assert_eq!(refs.get_ref("refs/heads/feature")?, Some(second));
+        assert_eq!(refs.get_ref("refs/heads/main")?, Some(first));
+        assert_eq!(get_current_branch(&repo)?, "main");
+
+        let main_index = HelixIndexData::load_or_rebuild(&repo)?;
+        assert!(!main_index.is_tracked(Path::new("b.txt")));
+
+        let listed = list_worktrees(&repo)?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].head, Some(second));
+
+        remove_worktree(&repo, &wt, false)?;
+        assert!(!wt.exists());
+        assert!(list_worktrees(&repo)?.is_empty());
+
+        Ok(())
+    }
+}
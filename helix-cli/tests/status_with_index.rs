@@ -179,6 +179,12 @@ fn test_commit_workflow() -> Result<()> {
             allow_empty: false,
             amend: false,
             verbose: false,
+            signoff: false,
+            no_edit: false,
+            reset_author: false,
+            date: None,
+            metadata: Default::default(),
+            allow_missing_objects: false,
         },
     )?;
 
@@ -228,6 +234,12 @@ fn test_second_commit_workflow() -> Result<()> {
             allow_empty: false,
             amend: false,
             verbose: false,
+            signoff: false,
+            no_edit: false,
+            reset_author: false,
+            date: None,
+            metadata: Default::default(),
+            allow_missing_objects: false,
         },
     )?;
 
@@ -247,6 +259,12 @@ fn test_second_commit_workflow() -> Result<()> {
             allow_empty: false,
             amend: false,
             verbose: false,
+            signoff: false,
+            no_edit: false,
+            reset_author: false,
+            date: None,
+            metadata: Default::default(),
+            allow_missing_objects: false,
         },
     )?;
 
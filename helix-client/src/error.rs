@@ -0,0 +1,116 @@
+/// Errors reported by a Helix server.
+///
+/// The server tags every RpcError with an ErrorCode. `HelixRemote` returns these as
+/// RemoteError, so callers can tell the failures apart (helix's `main` prints guidance for
+/// each and exits with a code scripts can check):
+///
+///   1  other / internal error
+///   3  protocol mismatch (upgrade client or server)
+///   4  unauthorized
+///   5  repository or ref not found
+///   6  rejected: not a fast-forward, the ref changed concurrently, the
+///      ref is protected, or the push changes a path the pusher may not
+///   7  missing or invalid objects
+///   8  server limits: rate limited, payload too large, or over a storage quota
+use helix_protocol::message::{ErrorCode, RpcError};
+
+#[derive(thiserror::Error, Debug)]
+#[error("remote error ({}): {message}", kind_name(code))]
+pub struct RemoteError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// The variant name alone; the message already spells out any details it carries
+fn kind_name(code: &ErrorCode) -> String {
+    let debug = format!("{code:?}");
+    debug
+        .split([' ', '{'])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl From<RpcError> for RemoteError {
+    fn from(err: RpcError) -> Self {
+        Self {
+            code: err.kind,
+            message: err.message,
+        }
+    }
+}
+
+impl RemoteError {
+    /// What the user can do about it
+    pub fn hint(&self) -> Option<&'static str> {
+        match self.code {
+            ErrorCode::NotFastForward => Some(
+                "The remote has commits you don't have. Run `helix pull` to integrate them, \
+                 or `helix push --force` to overwrite them.",
+            ),
+            ErrorCode::Conflict => {
+                Some("The ref changed on the remote while you were pushing. Pull and try again.")
+            }
+            ErrorCode::RepoNotFound => Some(
+                "Check the remote URL in helix.toml, or push to create the repository first.",
+            ),
+            ErrorCode::RefNotFound => Some("Check the branch name; `helix branch` lists branches."),
+            ErrorCode::Unauthorized => {
+                Some("The server rejected your credentials for this repository.")
+            }
+            ErrorCode::ProtocolMismatch => {
+                Some("Client and server versions are incompatible; upgrade the older one.")
+            }
+            ErrorCode::ObjectMissing | ErrorCode::InvalidObject => Some(
+                "Run `helix verify --all` to check the local repository, and `helix repair` to fix it.",
+            ),
+            ErrorCode::RateLimited => {
+                Some("The server is limiting requests from your address; wait a moment and retry.")
+            }
+            ErrorCode::PayloadTooLarge => Some(
+                "The request exceeds the server's size limits; push fewer commits at a time \
+                 or ask the operator to raise [limits] in the server config.",
+            ),
+            ErrorCode::ObjectTooLarge { .. } => Some(
+                "Remove the file from the commits being pushed, or ask the operator to raise \
+                 [quotas] in the server config.",
+            ),
+            ErrorCode::QuotaExceeded { .. } => Some(
+                "The repository is out of storage on the server; ask the operator to raise \
+                 its [quotas] or clean up unused data.",
+            ),
+            ErrorCode::BranchCheckedOut => Some(
+                "Push to a bare repository (`helix init --bare`) instead, or switch the \
+                 target's working tree to another branch first.",
+            ),
+            ErrorCode::ProtectedRef => Some(
+                "The server protects this ref from deletion and forced updates; \
+                 ask the server operator if it really needs to change.",
+            ),
+            ErrorCode::PathForbidden { .. } => Some(
+                "The server restricts who may change this path; drop those changes from \
+                 the commits being pushed, or ask the operator for access.",
+            ),
+            ErrorCode::Internal | ErrorCode::BadRequest => None,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.code {
+            ErrorCode::ProtocolMismatch => 3,
+            ErrorCode::Unauthorized => 4,
+            ErrorCode::RepoNotFound | ErrorCode::RefNotFound => 5,
+            ErrorCode::NotFastForward
+            | ErrorCode::Conflict
+            | ErrorCode::ProtectedRef
+            | ErrorCode::BranchCheckedOut
+            | ErrorCode::PathForbidden { .. } => 6,
+            ErrorCode::ObjectMissing | ErrorCode::InvalidObject => 7,
+            ErrorCode::RateLimited
+            | ErrorCode::PayloadTooLarge
+            | ErrorCode::ObjectTooLarge { .. }
+            | ErrorCode::QuotaExceeded { .. } => 8,
+            ErrorCode::Internal | ErrorCode::BadRequest => 1,
+        }
+    }
+}
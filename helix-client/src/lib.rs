@@ -0,0 +1,14 @@
+//! A client for Helix remotes, for tools that sync with a Helix server without going
+//! through the helix CLI.
+//!
+//! `HelixRemote` speaks the protocol: the version handshake, listing refs, pushing and
+//! pulling objects. How its requests travel is up to a `Transport`; HTTP(S) servers and
+//! `file://` repositories are supported out of the box.
+pub mod error;
+pub mod remote;
+pub mod retry;
+pub mod transport;
+
+pub use error::RemoteError;
+pub use remote::{upload_batches, HelixRemote, Outgoing, ResponseStream};
+pub use transport::{HttpTransport, LocalTransport, Transport};
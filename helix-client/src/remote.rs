@@ -0,0 +1,560 @@
+/// One repository on a Helix server, and the RPCs that sync with it.
+///
+/// Every RPC starts with a Hello naming this client and what it can read back. The server's
+/// HelloAck is checked against this build's protocol version and kept, so after the first
+/// call `server()` tells the caller which features it may use (batched uploads, deltas).
+/// Protocol v1 servers send no HelloAck; their responses are read as before.
+///
+/// Pushes come in three shapes. `push` moves one ref and sends its objects in the same
+/// request. Against servers with the `resume` feature, `upload` sends objects in batches
+/// first (see `upload_batches`) and `push` then follows with no objects. `push_refs` moves
+/// several refs all-or-nothing. Object frames are only compressed for servers that said
+/// they can read them.
+use anyhow::{bail, Context, Result};
+use helix_protocol::filter::PathFilter;
+use helix_protocol::hash::Hash;
+use helix_protocol::message::{
+    write_message, write_message_with, AsyncMessageReader, Features, FetchObject, Hello, HelloAck,
+    ListRefsRequest, ObjectType, PullFilter, PullObject, PullRequest, PushAck, PushDelta,
+    PushObject, PushRef, PushRefsAck, PushRefsRequest, PushRequest, RefList, RpcMessage, WireError,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::RemoteError;
+use crate::transport::{self, Body, Request, Transport};
+
+/// How long the handshake before a push may take
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct HelixRemote {
+    transport: Box<dyn Transport>,
+    /// The repository's name on the server
+    repo: String,
+    /// Sent in Hello as our client_version
+    agent: String,
+    /// Whether we tell the server it may compress its responses, and compress our own
+    /// object frames when it can read them
+    compress: bool,
+    pusher: Option<String>,
+    server: Mutex<Option<HelloAck>>,
+}
+
+impl HelixRemote {
+    /// The repository `repo` at `url` (see `transport::for_url`)
+    pub fn open(url: &str, repo: &str, token: Option<String>) -> Self {
+        Self::with_transport(transport::for_url(url, token), repo)
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>, repo: &str) -> Self {
+        Self {
+            transport,
+            repo: repo.to_string(),
+            agent: format!("helix-client {}", env!("CARGO_PKG_VERSION")),
+            compress: true,
+            pusher: None,
+            server: Mutex::new(None),
+        }
+    }
+
+    pub fn with_agent(mut self, agent: impl Into<String>) -> Self {
+        self.agent = agent.into();
+        self
+    }
+
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Who pushes, for the server's post-receive hooks
+    pub fn with_pusher(mut self, pusher: Option<String>) -> Self {
+        self.pusher = pusher;
+        self
+    }
+
+    /// The HelloAck from the last response, if the server sent one
+    pub fn server(&self) -> Option<HelloAck> {
+        self.server.lock().unwrap().clone()
+    }
+
+    /// The features the server advertised; none for protocol v1 servers or before any call
+    pub fn features(&self) -> Features {
+        self.server().map(|ack| ack.features).unwrap_or_default()
+    }
+
+    /// Announce a push of `ref_name` and return the server's current head for it
+    pub async fn handshake(
+        &self,
+        ref_name: &str,
+        new_target: Hash,
+        old_target: Option<Hash>,
+    ) -> Result<Option<Hash>> {
+        let mut body = self.hello()?;
+        write_message(
+            &mut body,
+            &RpcMessage::PushRequest(self.push_request(ref_name, old_target, new_target)),
+        )?;
+        let mut request = Request::new("handshake", body);
+        request.timeout = Some(HANDSHAKE_TIMEOUT);
+
+        let mut response = self
+            .rpc(request)
+            .await
+            .context("Handshake with remote failed")?;
+        match response.next().await? {
+            RpcMessage::PushResponse(r) => Ok(r.remote_head),
+            RpcMessage::Error(err) => {
+                Err(RemoteError::from(err)).context("Handshake with remote failed")
+            }
+            _ => bail!("Unexpected response during handshake"),
+        }
+    }
+
+    /// The server's refs under `prefix` ("refs/" for all), sorted by name
+    pub async fn list_refs(&self, prefix: &str) -> Result<Vec<(String, Hash)>> {
+        Ok(self.ref_list(prefix).await?.refs)
+    }
+
+    /// Like `list_refs`, with the ref the server's HEAD names when it says
+    pub async fn ref_list(&self, prefix: &str) -> Result<RefList> {
+        let mut body = self.hello()?;
+        write_message(
+            &mut body,
+            &RpcMessage::ListRefs(ListRefsRequest {
+                repo: self.repo.clone(),
+                prefix: prefix.to_string(),
+            }),
+        )?;
+
+        let mut response = self.rpc(Request::new("refs", body)).await?;
+        match response.next().await? {
+            RpcMessage::RefList(list) => Ok(list),
+            RpcMessage::Error(err) => Err(RemoteError::from(err).into()),
+            other => bail!("Unexpected response from server: {:?}", other),
+        }
+    }
+
+    /// Move one ref, sending `objects` along. The server refuses a non-fast-forward update
+    /// unless `update.old_target` is its current head.
+    pub async fn push(&self, update: &PushRef, objects: &[Outgoing]) -> Result<PushAck> {
+        let request = RpcMessage::PushRequest(self.push_request(
+            &update.ref_name,
+            Some(update.old_target),
+            update.new_target,
+        ));
+        match self.send_objects("push", &request, objects).await? {
+            RpcMessage::PushAck(ack) => Ok(ack),
+            other => bail!("Unexpected response from server: {:?}", other),
+        }
+    }
+
+    /// Store objects on the server ahead of the push that needs them. Needs the `resume`
+    /// feature.
+    pub async fn upload(&self, objects: &[Outgoing]) -> Result<PushAck> {
+        let request = RpcMessage::PushRequest(self.push_request("", None, [0u8; 32]));
+        match self.send_objects("upload", &request, objects).await? {
+            RpcMessage::PushAck(ack) => Ok(ack),
+            other => bail!("Unexpected response from server: {:?}", other),
+        }
+    }
+
+    /// Move every ref in `updates`, or none of them. A rejection of some refs is reported
+    /// in the ack's results rather than as an error.
+    pub async fn push_refs(
+        &self,
+        updates: Vec<PushRef>,
+        objects: &[Outgoing],
+    ) -> Result<PushRefsAck> {
+        let request = RpcMessage::PushRefs(PushRefsRequest {
+            repo: self.repo.clone(),
+            updates,
+        });
+        match self.send_objects("push", &request, objects).await? {
+            RpcMessage::PushRefsAck(ack) => Ok(ack),
+            other => bail!("Unexpected response from server: {:?}", other),
+        }
+    }
+
+    /// Ask for the objects `ref_name` has gained since `last_known_remote`, only with the
+    /// blobs `filter` matches if one is given. The returned stream holds PullObjects,
+    /// PullDone and a PullAck, or just a PullAck when there is nothing to send.
+    pub async fn pull(
+        &self,
+        ref_name: &str,
+        last_known_remote: Option<Hash>,
+        filter: Option<&PathFilter>,
+    ) -> Result<ResponseStream> {
+        let mut body = self.hello()?;
+        write_message(
+            &mut body,
+            &RpcMessage::PullRequest(PullRequest {
+                repo: self.repo.clone(),
+                ref_name: ref_name.to_string(),
+                last_known_remote,
+            }),
+        )?;
+        if let Some(filter) = filter {
+            write_message(
+                &mut body,
+                &RpcMessage::PullFilter(PullFilter {
+                    paths: filter.paths().to_vec(),
+                }),
+            )?;
+        }
+        self.rpc(Request::new("pull", body)).await
+    }
+
+    /// Request specific objects; returns the ones the server has
+    pub async fn fetch_objects(&self, objects: &[(ObjectType, Hash)]) -> Result<Vec<PullObject>> {
+        let mut body = self.hello()?;
+        for (object_type, hash) in objects {
+            write_message(
+                &mut body,
+                &RpcMessage::FetchObject(FetchObject {
+                    repo: self.repo.clone(),
+                    object_type: object_type.clone(),
+                    hash: *hash,
+                }),
+            )?;
+        }
+        write_message(&mut body, &RpcMessage::FetchDone)?;
+
+        let mut response = self.rpc(Request::new("fetch", body)).await?;
+        let mut received = Vec::new();
+        loop {
+            match response.next().await {
+                Ok(RpcMessage::PullObject(obj)) => received.push(obj),
+                Ok(RpcMessage::PullDone) => break,
+                Ok(RpcMessage::Error(err)) => return Err(RemoteError::from(err).into()),
+                Ok(other) => bail!("Unexpected message: {:?}", other),
+                Err(e) => bail!("Error reading message: {}", e),
+            }
+        }
+        Ok(received)
+    }
+
+    fn hello(&self) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        let hello = Hello::new(
+            self.agent.clone(),
+            Features {
+                compression: self.compress,
+                ..Features::default()
+            },
+        );
+        write_message(&mut body, &RpcMessage::Hello(hello))?;
+        Ok(body)
+    }
+
+    fn push_request(
+        &self,
+        ref_name: &str,
+        old_target: Option<Hash>,
+        new_target: Hash,
+    ) -> PushRequest {
+        PushRequest {
+            repo: self.repo.clone(),
+            ref_name: ref_name.to_string(),
+            old_target: old_target.unwrap_or([0u8; 32]),
+            new_target,
+        }
+    }
+
+    /// Send `request` followed by the objects and PushDone, retrying transient failures
+    /// (pushes and uploads are idempotent), and return the server's answer
+    async fn send_objects(
+        &self,
+        endpoint: &'static str,
+        request: &RpcMessage,
+        objects: &[Outgoing],
+    ) -> Result<RpcMessage> {
+        let compress = self.compress && self.features().compression;
+        let mut body = self.hello()?;
+        write_message(&mut body, request)?;
+        for object in objects {
+            write_message_with(&mut body, &object.message(), compress)?;
+        }
+        write_message(&mut body, &RpcMessage::PushDone)?;
+
+        let mut request = Request::new(endpoint, body);
+        request.pusher = self.pusher.clone();
+        request.retry = true;
+        let mut response = self.rpc(request).await?;
+        match response.next().await? {
+            RpcMessage::Error(err) => Err(RemoteError::from(err).into()),
+            answer => Ok(answer),
+        }
+    }
+
+    /// Send one RPC. Failures the server explains come back as RemoteError; on success the
+    /// HelloAck is read and checked, leaving the rest of the response.
+    async fn rpc(&self, request: Request) -> Result<ResponseStream> {
+        let endpoint = request.endpoint;
+        let response = self.transport.call(request).await?;
+        let mut stream = ResponseStream::new(response.body);
+
+        if !(200..300).contains(&response.status) {
+            // Errors carry an RpcError body, e.g. a protocol version mismatch
+            if let Ok(RpcMessage::Error(err)) = stream.next().await {
+                return Err(RemoteError::from(err).into());
+            }
+            if response.status == 404 {
+                bail!("The server doesn't support /rpc/{endpoint}. Upgrade helix-server.");
+            }
+            bail!("Server returned error: {}", response.status);
+        }
+        if let Some(ack) = stream.hello_ack().await? {
+            *self.server.lock().unwrap() = Some(ack);
+        }
+        Ok(stream)
+    }
+}
+
+/// The messages of a response body, read as it arrives
+pub struct ResponseStream {
+    reader: AsyncMessageReader<Body>,
+    /// Read while looking for a HelloAck that wasn't there
+    peeked: Option<RpcMessage>,
+}
+
+impl ResponseStream {
+    pub fn new(body: Body) -> Self {
+        Self {
+            reader: AsyncMessageReader::new(body),
+            peeked: None,
+        }
+    }
+
+    pub async fn next(&mut self) -> Result<RpcMessage, WireError> {
+        match self.peeked.take() {
+            Some(msg) => Ok(msg),
+            None => self.reader.read_message().await,
+        }
+    }
+
+    /// Consume the server's HelloAck if the response starts with one. Servers speaking
+    /// protocol v1 never send it; their first message is then kept for `next`.
+    pub async fn hello_ack(&mut self) -> Result<Option<HelloAck>> {
+        match self.next().await {
+            Ok(RpcMessage::HelloAck(ack)) => {
+                check_server_version(&ack)?;
+                Ok(Some(ack))
+            }
+            Ok(other) => {
+                self.peeked = Some(other);
+                Ok(None)
+            }
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn check_server_version(ack: &HelloAck) -> Result<()> {
+    if ack.protocol_version < MIN_PROTOCOL_VERSION {
+        bail!(
+            "{} speaks Helix protocol v{}, but this helix requires v{} or newer. Upgrade the server.",
+            ack.server_version,
+            ack.protocol_version,
+            MIN_PROTOCOL_VERSION
+        );
+    }
+    if ack.min_protocol_version > PROTOCOL_VERSION {
+        bail!(
+            "{} requires Helix protocol v{} or newer, but this helix speaks v{}. Upgrade helix-cli.",
+            ack.server_version,
+            ack.min_protocol_version,
+            PROTOCOL_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// An object as sent: its stored (compressed) bytes, or a delta against a blob the server has
+pub enum Outgoing {
+    Whole(ObjectType, Hash, Vec<u8>),
+    Delta(PushDelta),
+}
+
+impl Outgoing {
+    pub fn len(&self) -> usize {
+        match self {
+            Outgoing::Whole(_, _, data) => data.len(),
+            Outgoing::Delta(delta) => delta.delta.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn message(&self) -> RpcMessage {
+        match self {
+            Outgoing::Whole(object_type, hash, data) => RpcMessage::PushObject(PushObject {
+                object_type: object_type.clone(),
+                hash: *hash,
+                data: data.clone(),
+            }),
+            Outgoing::Delta(delta) => RpcMessage::PushDelta(delta.clone()),
+        }
+    }
+}
+
+/// Split objects into consecutive batches of at most `max_bytes` of object data.
+/// An object larger than `max_bytes` gets a batch to itself.
+pub fn upload_batches(objects: &[Outgoing], max_bytes: usize) -> Vec<&[Outgoing]> {
+    let mut batches = Vec::new();
+    let (mut start, mut size) = (0, 0);
+    for (i, object) in objects.iter().enumerate() {
+        if i > start && size + object.len() > max_bytes {
+            batches.push(&objects[start..i]);
+            start = i;
+            size = 0;
+        }
+        size += object.len();
+    }
+    if start < objects.len() {
+        batches.push(&objects[start..]);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use helix_protocol::message::PushResponse;
+    use helix_protocol::storage::{FsObjectStore, FsRefStore};
+    use std::fs;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    fn ack(protocol_version: u32, min_protocol_version: u32) -> RpcMessage {
+        RpcMessage::HelloAck(HelloAck {
+            server_version: "helix-server test".into(),
+            protocol_version,
+            min_protocol_version,
+            features: Features::default(),
+        })
+    }
+
+    fn stream(messages: &[RpcMessage]) -> Result<ResponseStream> {
+        let mut buf = Vec::new();
+        for msg in messages {
+            write_message(&mut buf, msg)?;
+        }
+        Ok(ResponseStream::new(Box::new(Cursor::new(buf))))
+    }
+
+    #[tokio::test]
+    async fn test_read_hello_ack() -> Result<()> {
+        // v1 server: no HelloAck, the response is left for the caller
+        let mut response = stream(&[RpcMessage::PushResponse(PushResponse { remote_head: None })])?;
+        assert!(response.hello_ack().await?.is_none());
+        assert!(matches!(
+            response.next().await?,
+            RpcMessage::PushResponse(_)
+        ));
+
+        let mut response = stream(&[ack(PROTOCOL_VERSION, MIN_PROTOCOL_VERSION)])?;
+        let ack_read = response.hello_ack().await?.expect("HelloAck");
+        assert_eq!(ack_read.protocol_version, PROTOCOL_VERSION);
+
+        // Server that dropped support for our protocol version
+        let mut response = stream(&[ack(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 1)])?;
+        let err = response.hello_ack().await.unwrap_err();
+        assert!(err.to_string().contains("Upgrade helix-cli"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_upload_batches_respect_size_limit() {
+        let objects: Vec<Outgoing> = [3, 4, 10, 2, 2, 2]
+            .iter()
+            .enumerate()
+            .map(|(i, len)| Outgoing::Whole(ObjectType::Blob, [i as u8; 32], vec![0u8; *len]))
+            .collect();
+
+        let sizes: Vec<usize> = upload_batches(&objects, 8)
+            .iter()
+            .map(|batch| batch.len())
+            .collect();
+        // [3, 4] [10] [2, 2, 2]: the oversized object travels alone
+        assert_eq!(sizes, vec![2, 1, 3]);
+
+        assert!(upload_batches(&[], 8).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_list_and_pull_through_a_file_remote() -> Result<()> {
+        let temp = TempDir::new()?;
+        let root = temp.path().join("app.helix");
+        fs::create_dir_all(root.join(".helix/objects"))?;
+        fs::create_dir_all(root.join(".helix/refs/heads"))?;
+        let url = format!("file://{}", root.display());
+
+        // A commit with an empty tree, built in a scratch store and pushed whole
+        let scratch = TempDir::new()?;
+        let store = FsObjectStore::new(scratch.path());
+        let tree = store.write_object(&ObjectType::Tree, &0u32.to_le_bytes())?;
+        let mut commit = tree.to_vec();
+        commit.extend_from_slice(&0u32.to_le_bytes());
+        let commit = store.write_object(&ObjectType::Commit, &commit)?;
+        let objects: Vec<Outgoing> = [(ObjectType::Tree, tree), (ObjectType::Commit, commit)]
+            .into_iter()
+            .map(|(ty, hash)| {
+                Ok(Outgoing::Whole(
+                    ty.clone(),
+                    hash,
+                    store.read_object_compressed(&ty, &hash)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        let remote = HelixRemote::open(&url, "app", None);
+        assert_eq!(
+            remote.handshake("refs/heads/main", commit, None).await?,
+            None
+        );
+        assert!(remote.features().resume);
+
+        let update = PushRef {
+            ref_name: "refs/heads/main".to_string(),
+            old_target: [0u8; 32],
+            new_target: commit,
+        };
+        assert_eq!(remote.push(&update, &objects).await?.received_objects, 2);
+        assert_eq!(
+            FsRefStore::new(&root).get_ref("refs/heads/main")?,
+            Some(commit)
+        );
+        assert_eq!(
+            remote.list_refs("refs/heads/").await?,
+            vec![("refs/heads/main".to_string(), commit)]
+        );
+
+        let mut response = remote.pull("refs/heads/main", None, None).await?;
+        let mut pulled = Vec::new();
+        loop {
+            match response.next().await? {
+                RpcMessage::PullObject(obj) => pulled.push(obj.hash),
+                RpcMessage::PullDone => break,
+                other => bail!("Unexpected message: {:?}", other),
+            }
+        }
+        assert_eq!(pulled, vec![tree, commit]);
+        assert!(
+            matches!(response.next().await?, RpcMessage::PullAck(ack) if ack.new_remote_head == commit)
+        );
+
+        assert_eq!(
+            remote
+                .fetch_objects(&[(ObjectType::Tree, tree)])
+                .await?
+                .len(),
+            1
+        );
+        Ok(())
+    }
+}
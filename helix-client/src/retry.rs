@@ -0,0 +1,77 @@
+/// Retrying RPCs through transient failures.
+///
+/// A request is sent again, after an exponentially growing pause, when the connection fails
+/// or times out or the server answers 429, 502, 503 or 504. Every other response, including
+/// RpcErrors such as NotFastForward, goes back to the caller as-is.
+///
+/// Only use this for requests the server handles idempotently. Object uploads qualify
+/// because they are keyed by hash. A push also qualifies when its ref already points at
+/// new_target, since it then succeeds without changes.
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total tries, including the first
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Pause before retry number `retry` (1 for the first retry)
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Statuses that mean "try again later" rather than "this request is wrong"
+pub fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// How long a rate-limited response asks us to wait, from its Retry-After header
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<u64> = (1..=6)
+            .map(|r| policy.delay(r).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 8000, 8000]);
+
+        assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient(StatusCode::CONFLICT));
+        assert!(!is_transient(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+}
@@ -0,0 +1,223 @@
+/// How RPC bodies reach a remote.
+///
+/// Every Helix RPC is one request body in and one response body out, each a stream of
+/// framed messages, sent to a named endpoint (handshake, refs, push, upload, pull, fetch).
+/// A `Transport` carries that exchange and knows nothing about the messages, so
+/// `HelixRemote` speaks the protocol the same way over any of them. Two come with the crate:
+/// `HttpTransport` POSTs to `<url>/rpc/<endpoint>`, and `LocalTransport` runs helix-server's
+/// handlers in this process against a repository on disk (a `file://` remote). Tools that
+/// reach a server some other way, over SSH for instance, implement the trait themselves.
+use anyhow::{Context, Result};
+use futures_util::stream;
+use reqwest::header::HeaderValue;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+
+use crate::retry::{is_transient, retry_after, RetryPolicy};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A response body, readable while it arrives
+pub type Body = Box<dyn AsyncRead + Send + Unpin>;
+
+/// One RPC to send
+pub struct Request {
+    pub endpoint: &'static str,
+    pub body: Vec<u8>,
+    /// Who is pushing, for the server's post-receive hooks (sent as X-Helix-Pusher)
+    pub pusher: Option<String>,
+    /// Give up on a response that takes longer than this
+    pub timeout: Option<Duration>,
+    /// Whether transient failures may be retried. Every RPC is idempotent; this only says
+    /// whether the caller would rather wait than fail fast.
+    pub retry: bool,
+}
+
+impl Request {
+    pub fn new(endpoint: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            endpoint,
+            body,
+            pusher: None,
+            timeout: None,
+            retry: false,
+        }
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Body,
+}
+
+pub trait Transport: Send + Sync {
+    /// Send one RPC and return the response status, with the body still arriving
+    fn call(&self, request: Request) -> BoxFuture<'_, Result<Response>>;
+}
+
+/// The transport for a remote URL: `file://<path>` for a repository on local disk, anything
+/// else for an HTTP(S) server. `token` is sent as a bearer token to HTTP servers.
+pub fn for_url(url: &str, token: Option<String>) -> Box<dyn Transport> {
+    match url.strip_prefix("file://") {
+        Some(path) => Box::new(LocalTransport::new(path)),
+        None => Box::new(HttpTransport::new(url).with_token(token)),
+    }
+}
+
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    policy: RetryPolicy,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            token: None,
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    async fn send(&self, request: Request) -> Result<Response> {
+        let url = format!("{}/rpc/{}", self.url, request.endpoint);
+        let attempts = if request.retry {
+            self.policy.attempts.max(1)
+        } else {
+            1
+        };
+        // Cheap to resend: each attempt shares the same buffer
+        let body = bytes::Bytes::from(request.body);
+        let mut retry = 0;
+        loop {
+            let mut builder = self.client.post(&url).body(body.clone());
+            if let Some(token) = &self.token {
+                builder = builder.bearer_auth(token);
+            }
+            // A name that can't be a header value is left out rather than failing the push
+            if let Some(pusher) = request
+                .pusher
+                .as_deref()
+                .and_then(|p| HeaderValue::from_str(p).ok())
+            {
+                builder = builder.header("X-Helix-Pusher", pusher);
+            }
+            if let Some(timeout) = request.timeout {
+                builder = builder.timeout(timeout);
+            }
+
+            let last = retry + 1 >= attempts;
+            let (reason, wait) = match builder.send().await {
+                Ok(resp) if !last && is_transient(resp.status()) => (
+                    format!("server answered {}", resp.status()),
+                    retry_after(resp.headers()),
+                ),
+                Ok(resp) => {
+                    return Ok(Response {
+                        status: resp.status().as_u16(),
+                        body: stream_body(resp),
+                    })
+                }
+                Err(e) if !last => (e.to_string(), None),
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Remote server at {} is unreachable. Is the Helix server running?",
+                            self.url
+                        )
+                    })
+                }
+            };
+
+            retry += 1;
+            let delay = wait
+                .map(|d| d.min(self.policy.max_delay))
+                .unwrap_or_else(|| self.policy.delay(retry));
+            eprintln!(
+                "{}: {reason}; retrying in {:.1}s ({}/{})",
+                request.endpoint,
+                delay.as_secs_f64(),
+                retry,
+                attempts - 1
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn call(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(self.send(request))
+    }
+}
+
+/// Hand out the body of `resp` as it arrives
+fn stream_body(resp: reqwest::Response) -> Body {
+    let chunks = stream::unfold(Some(resp), |resp| async move {
+        let mut resp = resp?;
+        match resp.chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(resp))),
+            Ok(None) => None,
+            // Nothing more can be read after an error
+            Err(e) => Some((Err(std::io::Error::other(e)), None)),
+        }
+    });
+    Box::new(StreamReader::new(Box::pin(chunks)))
+}
+
+/// A repository on local disk, served by helix-server's handlers in this process. There is
+/// nothing transient to retry, and no timeout.
+pub struct LocalTransport {
+    path: PathBuf,
+}
+
+impl LocalTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Transport for LocalTransport {
+    fn call(&self, request: Request) -> BoxFuture<'_, Result<Response>> {
+        Box::pin(async move {
+            let (status, bytes) = helix_server::local::call(
+                &self.path,
+                request.endpoint,
+                request.body,
+                request.pusher.as_deref(),
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Remote repository at {} is unavailable",
+                    self.path.display()
+                )
+            })?;
+            Ok(Response {
+                status,
+                body: Box::new(std::io::Cursor::new(bytes)),
+            })
+        })
+    }
+}
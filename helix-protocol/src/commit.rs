@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fs,
     path::Path,
 };
@@ -7,7 +7,7 @@ use std::{
 use crate::{
     hash::{hash_to_hex, hex_to_hash, Hash},
     message::ObjectType,
-    storage::FsObjectStore,
+    storage::ObjectStore,
 };
 use anyhow::{bail, Context, Result};
 
@@ -50,26 +50,20 @@ pub fn parse_commit_for_walk(bytes: &[u8]) -> Result<(Hash, Vec<Hash>)> {
     Ok((tree_hash, parents))
 }
 
-/// Collect all objects needed: commits, trees, and blobs
+/// Collect all objects needed: commits, trees, and blobs.
+///
+/// Objects are returned in dependency order (reverse topological): every blob and subtree comes
+/// before the tree that references it, and a commit comes after its tree and after any parent
+/// commit in `commits`. A receiver can therefore validate each object's references on arrival.
 pub fn collect_objects_from_commits(
-    store: &FsObjectStore,
+    store: &dyn ObjectStore,
     commits: &[CommitData],
 ) -> anyhow::Result<Vec<(ObjectType, Hash, Vec<u8>)>> {
     let mut objects = Vec::new();
     let mut seen_trees = HashSet::new();
     let mut seen_blobs = HashSet::new();
 
-    // Add commits first
-    for commit in commits {
-        objects.push((
-            ObjectType::Commit,
-            commit.hash,
-            commit.compressed_bytes.clone(),
-        ));
-    }
-
-    // Collect trees and blobs from each commit's tree
-    for commit in commits {
+    for commit in order_parents_first(commits)? {
         collect_tree_recursive(
             store,
             commit.tree_hash,
@@ -77,20 +71,74 @@ pub fn collect_objects_from_commits(
             &mut seen_blobs,
             &mut objects,
         )?;
+
+        objects.push((
+            ObjectType::Commit,
+            commit.hash,
+            commit.compressed_bytes.clone(),
+        ));
     }
 
     Ok(objects)
 }
 
+/// Order commits so that parents (within `commits`) come before their children
+pub(crate) fn order_parents_first(commits: &[CommitData]) -> Result<Vec<&CommitData>> {
+    let index: HashMap<Hash, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.hash, i))
+        .collect();
+
+    let mut parents = Vec::with_capacity(commits.len());
+    for commit in commits {
+        let (_, commit_parents) = parse_commit_for_walk(&commit.raw_bytes)?;
+        parents.push(
+            commit_parents
+                .iter()
+                .filter_map(|p| index.get(p).copied())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // Iterative post-order DFS; `expanded` marks a commit whose parents are already queued
+    let mut ordered = Vec::with_capacity(commits.len());
+    let mut visited = vec![false; commits.len()];
+    let mut stack = Vec::new();
+
+    for start in 0..commits.len() {
+        stack.push((start, false));
+        while let Some((i, expanded)) = stack.pop() {
+            if expanded {
+                ordered.push(&commits[i]);
+                continue;
+            }
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+            stack.push((i, true));
+            for &p in &parents[i] {
+                if !visited[p] {
+                    stack.push((p, false));
+                }
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
 #[derive(Debug, Clone, Copy)]
-enum EntryKind {
+pub enum EntryKind {
     File,
     Tree,
 }
 
-/// Recursively collect a tree and all its blobs/subtrees.
+/// Recursively collect a tree and all its blobs/subtrees. The tree itself is pushed after its
+/// entries so receivers see dependencies first.
 pub fn collect_tree_recursive(
-    store: &FsObjectStore,
+    store: &dyn ObjectStore,
     tree_hash: Hash,
     seen_trees: &mut HashSet<Hash>,
     seen_blobs: &mut HashSet<Hash>,
@@ -103,8 +151,6 @@ pub fn collect_tree_recursive(
     let compressed = store.read_object_compressed(&ObjectType::Tree, &tree_hash)?;
     let raw = zstd::decode_all(&compressed[..]).context("Failed to decompress tree for parsing")?;
 
-    objects.push((ObjectType::Tree, tree_hash, compressed));
-
     // Parse tree entries from raw bytes
     let entries = parse_tree_entries(&raw)?;
 
@@ -122,12 +168,59 @@ pub fn collect_tree_recursive(
         }
     }
 
+    objects.push((ObjectType::Tree, tree_hash, compressed));
+
     Ok(())
 }
 
+/// The tree format version written today. Version 1 trees carry no marker: they start
+/// straight with the entry count, as every tree did before versions existed, so their
+/// hashes never change.
+pub const TREE_FORMAT_VERSION: u8 = 1;
+
+/// Leads a tree in any later format, followed by its version byte. It would otherwise
+/// be an entry count of u32::MAX, which no version 1 tree can have.
+pub const TREE_VERSION_MARKER: [u8; 4] = [0xff; 4];
+
+/// The format version of serialized tree bytes (see helix-cli's helix_index/tree.rs
+/// for the format itself)
+pub fn tree_format_version(bytes: &[u8]) -> Result<u8> {
+    if !bytes.starts_with(&TREE_VERSION_MARKER) {
+        return Ok(TREE_FORMAT_VERSION);
+    }
+    match bytes.get(TREE_VERSION_MARKER.len()) {
+        None => bail!("Tree version marker is not followed by a version"),
+        // One tree, one encoding: a marked version 1 would hash differently
+        Some(&TREE_FORMAT_VERSION) => {
+            bail!("Tree is marked as version {TREE_FORMAT_VERSION}, which is written unmarked")
+        }
+        Some(&version) => Ok(version),
+    }
+}
+
+/// Fail unless the tree bytes are in a format this version of helix reads
+pub fn check_tree_format(bytes: &[u8]) -> Result<()> {
+    match tree_format_version(bytes)? {
+        TREE_FORMAT_VERSION => Ok(()),
+        version => bail!(
+            "Tree format version {version} is newer than this helix understands \
+             (it reads version {TREE_FORMAT_VERSION}); upgrade helix"
+        ),
+    }
+}
+
 /// Parse tree entries from tree bytes.
 /// Format per entry: type(1) + mode(4) + size(8) + name_len(2) + name(var) + oid(32)
-fn parse_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, Hash)>> {
+pub fn parse_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, Hash)>> {
+    Ok(parse_named_tree_entries(bytes)?
+        .into_iter()
+        .map(|(kind, _, hash)| (kind, hash))
+        .collect())
+}
+
+/// Like `parse_tree_entries`, with each entry's name
+pub fn parse_named_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, String, Hash)>> {
+    check_tree_format(bytes)?;
     if bytes.len() < 4 {
         bail!("Tree too short");
     }
@@ -160,7 +253,11 @@ fn parse_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, Hash)>> {
         let name_len = u16::from_le_bytes(bytes[offset..offset + 2].try_into()?) as usize;
         offset += 2;
 
-        // Name (variable) - skip
+        // Name (variable)
+        if offset + name_len > bytes.len() {
+            bail!("Tree entry name truncated");
+        }
+        let name = String::from_utf8_lossy(&bytes[offset..offset + name_len]).into_owned();
         offset += name_len;
 
         // OID (32 bytes)
@@ -171,7 +268,7 @@ fn parse_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, Hash)>> {
         hash.copy_from_slice(&bytes[offset..offset + 32]);
         offset += 32;
 
-        entries.push((entry_kind, hash));
+        entries.push((entry_kind, name, hash));
     }
 
     Ok(entries)
@@ -179,7 +276,7 @@ fn parse_tree_entries(bytes: &[u8]) -> Result<Vec<(EntryKind, Hash)>> {
 
 /// Walk from `from` (remote_head) backwards until we hit `to` (last_known) or run out of parents.
 pub fn walk_commits_between(
-    store: &FsObjectStore,
+    store: &dyn ObjectStore,
     from: Hash,
     to: Option<Hash>,
 ) -> anyhow::Result<Vec<CommitData>> {
@@ -222,22 +319,165 @@ pub fn walk_commits_between(
     Ok(result)
 }
 
-/// Compute objects to push by walking from new_target back to server_head.
-/// Only sends commits, trees, and blobs that the server doesn't have.
+/// True if `ancestor` is `descendant` or reachable from it by following parents.
+/// Fails if a commit along the way is missing from the store.
+pub fn is_ancestor(store: &dyn ObjectStore, ancestor: Hash, descendant: Hash) -> Result<bool> {
+    let mut queue = VecDeque::from([descendant]);
+    let mut seen = HashSet::new();
+
+    while let Some(hash) = queue.pop_front() {
+        if hash == ancestor {
+            return Ok(true);
+        }
+        if !seen.insert(hash) {
+            continue;
+        }
+
+        queue.extend(commit_parents(store, &hash)?);
+    }
+
+    Ok(false)
+}
+
+/// Nearest common ancestor of `a` and `b`, or None if the histories are unrelated
+pub fn merge_base(store: &dyn ObjectStore, a: Hash, b: Hash) -> Result<Option<Hash>> {
+    let mut ancestors_of_a = HashSet::new();
+    let mut queue = VecDeque::from([a]);
+    while let Some(hash) = queue.pop_front() {
+        if !ancestors_of_a.insert(hash) {
+            continue;
+        }
+        queue.extend(commit_parents(store, &hash)?);
+    }
+
+    // Breadth-first from b, so the first hit is the closest to b
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([b]);
+    while let Some(hash) = queue.pop_front() {
+        if ancestors_of_a.contains(&hash) {
+            return Ok(Some(hash));
+        }
+        if !seen.insert(hash) {
+            continue;
+        }
+        queue.extend(commit_parents(store, &hash)?);
+    }
+
+    Ok(None)
+}
+
+fn commit_parents(store: &dyn ObjectStore, hash: &Hash) -> Result<Vec<Hash>> {
+    let raw = store
+        .read_object(&ObjectType::Commit, hash)
+        .with_context(|| format!("Missing commit {}", hex::encode(hash)))?;
+    Ok(parse_commit_for_walk(&raw)?.1)
+}
+
+/// Compute the objects to push so the server can take `wants`, given commits it is known
+/// to have (`haves`: its current heads and our remote-tracking refs for it; any we don't have
+/// locally are ignored).
+///
+/// Only commits reachable from a want and from no have are sent. Of their trees and blobs,
+/// those the server already has are left out: everything in the trees of the haves and of
+/// the boundary commits (the known commits the new ones build on). So a commit that changes
+/// one file sends that file's blob, the trees on its path and the commit, not the whole tree.
+/// Objects come in dependency order, as from `collect_objects_from_commits`.
 pub fn compute_objects_to_push(
-    store: &FsObjectStore,
-    new_target: Hash,
-    server_head: Option<Hash>,
+    store: &dyn ObjectStore,
+    wants: &[Hash],
+    haves: &[Hash],
 ) -> Result<Vec<(ObjectType, Hash, Vec<u8>)>> {
-    // Walk commits from new_target back to server_head
-    let missing_commits = walk_commits_between(store, new_target, server_head)?;
+    let haves: Vec<Hash> = haves
+        .iter()
+        .filter(|h| store.has_object(&ObjectType::Commit, h))
+        .copied()
+        .collect();
+
+    // Every commit the server has
+    let mut known = HashSet::new();
+    let mut queue: VecDeque<Hash> = haves.iter().copied().collect();
+    while let Some(hash) = queue.pop_front() {
+        if known.insert(hash) {
+            queue.extend(commit_parents(store, &hash)?);
+        }
+    }
+
+    // New commits, and the known commits where the walk stopped
+    let mut missing_commits = Vec::new();
+    let mut boundary: HashSet<Hash> = haves.iter().copied().collect();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<Hash> = wants.iter().copied().collect();
+    while let Some(hash) = queue.pop_front() {
+        if known.contains(&hash) {
+            boundary.insert(hash);
+            continue;
+        }
+        if !seen.insert(hash) {
+            continue;
+        }
+        let compressed_bytes = store.read_object_compressed(&ObjectType::Commit, &hash)?;
+        let raw_bytes =
+            zstd::decode_all(&compressed_bytes[..]).context("Failed to decompress commit")?;
+        let (tree_hash, parents) = parse_commit_for_walk(&raw_bytes)?;
+        queue.extend(parents);
+        missing_commits.push(CommitData {
+            hash,
+            tree_hash,
+            raw_bytes,
+            compressed_bytes,
+        });
+    }
 
     if missing_commits.is_empty() {
         return Ok(vec![]);
     }
 
-    // Collect all objects from those commits (returns compressed bytes)
-    collect_objects_from_commits(store, &missing_commits)
+    // Trees and blobs the server has count as already sent
+    let mut seen_trees = HashSet::new();
+    let mut seen_blobs = HashSet::new();
+    for commit in &boundary {
+        let (tree, _) = parse_commit_for_walk(&store.read_object(&ObjectType::Commit, commit)?)?;
+        mark_tree_known(store, tree, &mut seen_trees, &mut seen_blobs)?;
+    }
+
+    let mut objects = Vec::new();
+    for commit in order_parents_first(&missing_commits)? {
+        collect_tree_recursive(
+            store,
+            commit.tree_hash,
+            &mut seen_trees,
+            &mut seen_blobs,
+            &mut objects,
+        )?;
+        objects.push((
+            ObjectType::Commit,
+            commit.hash,
+            commit.compressed_bytes.clone(),
+        ));
+    }
+
+    Ok(objects)
+}
+
+/// Add a tree, its subtrees and its blobs to the seen sets without reading the blobs
+fn mark_tree_known(
+    store: &dyn ObjectStore,
+    tree_hash: Hash,
+    seen_trees: &mut HashSet<Hash>,
+    seen_blobs: &mut HashSet<Hash>,
+) -> Result<()> {
+    if !seen_trees.insert(tree_hash) {
+        return Ok(());
+    }
+    for (kind, hash) in parse_tree_entries(&store.read_object(&ObjectType::Tree, &tree_hash)?)? {
+        match kind {
+            EntryKind::File => {
+                seen_blobs.insert(hash);
+            }
+            EntryKind::Tree => mark_tree_known(store, hash, seen_trees, seen_blobs)?,
+        }
+    }
+    Ok(())
 }
 
 /// Read a local Helix ref from .helix/refs/<...>
@@ -282,11 +522,105 @@ pub fn write_remote_tracking(
         .join("remotes")
         .join(remote);
 
-    fs::create_dir_all(&path).with_context(|| format!("Failed to create {}", path.display()))?;
-
+    // Branch names may contain slashes ("feature/login")
     let full = path.join(branch);
+    let dir = full.parent().unwrap_or(&path);
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
     let hex = hash_to_hex(&target);
     fs::write(&full, hex + "\n").with_context(|| format!("Failed to write {}", full.display()))?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FsObjectStore;
+    use tempfile::TempDir;
+
+    /// Tree in the on-disk format; `subtrees` entries point at trees, `files` at blobs
+    fn tree_bytes(files: &[(&str, Hash)], subtrees: &[(&str, Hash)]) -> Vec<u8> {
+        let mut bytes = ((files.len() + subtrees.len()) as u32)
+            .to_le_bytes()
+            .to_vec();
+        let entries = files
+            .iter()
+            .map(|e| (0u8, e))
+            .chain(subtrees.iter().map(|e| (2u8, e)));
+        for (kind, (name, oid)) in entries {
+            bytes.push(kind);
+            bytes.extend_from_slice(&0o100644u32.to_le_bytes());
+            bytes.extend_from_slice(&0u64.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(oid);
+        }
+        bytes
+    }
+
+    fn commit_bytes(tree: &Hash, parents: &[Hash]) -> Vec<u8> {
+        let mut bytes = tree.to_vec();
+        bytes.extend_from_slice(&(parents.len() as u32).to_le_bytes());
+        for parent in parents {
+            bytes.extend_from_slice(parent);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_push_sends_only_objects_the_server_lacks() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = FsObjectStore::at_dir(dir.path());
+        let write = |ty: ObjectType, bytes: &[u8]| store.write_object(&ty, bytes);
+
+        // c1: readme + docs/guide; the server has it
+        let readme = write(ObjectType::Blob, b"readme")?;
+        let guide = write(ObjectType::Blob, b"guide")?;
+        let docs = write(ObjectType::Tree, &tree_bytes(&[("guide", guide)], &[]))?;
+        let root1 = write(
+            ObjectType::Tree,
+            &tree_bytes(&[("readme", readme)], &[("docs", docs)]),
+        )?;
+        let c1 = write(ObjectType::Commit, &commit_bytes(&root1, &[]))?;
+
+        // c2 changes only the readme; docs/ is untouched
+        let readme2 = write(ObjectType::Blob, b"readme v2")?;
+        let root2 = write(
+            ObjectType::Tree,
+            &tree_bytes(&[("readme", readme2)], &[("docs", docs)]),
+        )?;
+        let c2 = write(ObjectType::Commit, &commit_bytes(&root2, &[c1]))?;
+
+        let sent = |haves: &[Hash]| -> Result<Vec<Hash>> {
+            Ok(compute_objects_to_push(&store, &[c2], haves)?
+                .into_iter()
+                .map(|(_, hash, _)| hash)
+                .collect())
+        };
+
+        assert_eq!(sent(&[c1])?, vec![readme2, root2, c2]);
+        // Without haves, everything goes
+        assert_eq!(sent(&[])?.len(), 8);
+        // A have we don't know locally is ignored
+        assert_eq!(sent(&[[9u8; 32]])?.len(), 8);
+        // The server already having c2 means nothing to send
+        assert!(sent(&[c2])?.is_empty());
+
+        // A branch off c1 that the server knows through another ref: a new commit on top of
+        // it re-sends nothing the branch already carried
+        let notes = write(ObjectType::Blob, b"notes")?;
+        let root3 = write(
+            ObjectType::Tree,
+            &tree_bytes(&[("notes", notes), ("readme", readme)], &[("docs", docs)]),
+        )?;
+        let c3 = write(ObjectType::Commit, &commit_bytes(&root3, &[c1]))?;
+        let merge = write(ObjectType::Commit, &commit_bytes(&root3, &[c2, c3]))?;
+        let objects = compute_objects_to_push(&store, &[merge], &[c2, c3])?;
+        assert_eq!(
+            objects.iter().map(|(_, h, _)| *h).collect::<Vec<_>>(),
+            vec![merge]
+        );
+
+        Ok(())
+    }
+}
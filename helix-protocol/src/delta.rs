@@ -0,0 +1,269 @@
+/// Binary deltas between two versions of an object, for thin pushes.
+///
+/// A client that knows the server has an older version of a file (the blob at the same path
+/// in a commit the server already has) can send the new blob as a PushDelta against it
+/// instead of in full. The delta is a list of ops that rebuild the target from the base:
+///
+///   base_len    u64, length of the base
+///   target_len  u64, length of the object it rebuilds
+///   op*         0x00 offset:u64 len:u32   copy len bytes of the base from offset
+///               0x01 len:u32 bytes        insert the next len bytes of the delta
+///
+/// All integers are little-endian. The server applies the delta to its copy of the base and
+/// checks the result against the object's hash, so a wrong base or a corrupt delta is caught
+/// like any other bad object.
+use crate::commit::{parse_commit_for_walk, parse_named_tree_entries, EntryKind};
+use crate::hash::Hash;
+use crate::message::ObjectType;
+use crate::storage::ObjectStore;
+use anyhow::{bail, ensure, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Bytes per indexed block of the base. Shorter matches are sent as inserts.
+const BLOCK: usize = 16;
+
+const OP_COPY: u8 = 0;
+const OP_INSERT: u8 = 1;
+
+/// Largest copy or insert in one op
+const MAX_OP_LEN: usize = u32::MAX as usize;
+
+/// Encode `target` as a delta against `base`
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + target.len() / 8);
+    out.extend_from_slice(&(base.len() as u64).to_le_bytes());
+    out.extend_from_slice(&(target.len() as u64).to_le_bytes());
+
+    // First offset of every block-aligned chunk of the base
+    let mut index: HashMap<&[u8], usize> = HashMap::new();
+    for (i, block) in base.chunks_exact(BLOCK).enumerate() {
+        index.entry(block).or_insert(i * BLOCK);
+    }
+
+    let mut pos = 0;
+    let mut insert_start = 0;
+    while pos + BLOCK <= target.len() {
+        let Some(&offset) = index.get(&target[pos..pos + BLOCK]) else {
+            pos += 1;
+            continue;
+        };
+
+        // Grow the match backwards into the pending insert, then forwards
+        let (mut start, mut base_start) = (pos, offset);
+        while start > insert_start && base_start > 0 && target[start - 1] == base[base_start - 1] {
+            start -= 1;
+            base_start -= 1;
+        }
+        let mut len = pos + BLOCK - start;
+        while start + len < target.len()
+            && base_start + len < base.len()
+            && target[start + len] == base[base_start + len]
+            && len < MAX_OP_LEN
+        {
+            len += 1;
+        }
+
+        push_insert(&mut out, &target[insert_start..start]);
+        out.push(OP_COPY);
+        out.extend_from_slice(&(base_start as u64).to_le_bytes());
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+        pos = start + len;
+        insert_start = pos;
+    }
+    push_insert(&mut out, &target[insert_start..]);
+    out
+}
+
+fn push_insert(out: &mut Vec<u8>, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        let (chunk, rest) = bytes.split_at(bytes.len().min(MAX_OP_LEN));
+        out.push(OP_INSERT);
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(chunk);
+        bytes = rest;
+    }
+}
+
+/// Rebuild the target from `base` and a delta made by `encode`. Fails on a delta for a
+/// different base, one that reads out of bounds, or one whose target would be larger than
+/// `max_len` bytes.
+pub fn apply(base: &[u8], delta: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut reader = Reader(delta);
+    let base_len = reader.u64()?;
+    let target_len = reader.u64()?;
+    ensure!(
+        base_len == base.len() as u64,
+        "delta is against a {base_len} byte base, not {} bytes",
+        base.len()
+    );
+    ensure!(
+        target_len <= max_len as u64,
+        "delta target is {target_len} bytes, over the {max_len} byte limit"
+    );
+
+    let target_len = target_len as usize;
+    let mut out = Vec::with_capacity(target_len);
+    while !reader.0.is_empty() {
+        let bytes = match reader.take(1)?[0] {
+            OP_COPY => {
+                let offset = reader.u64()? as usize;
+                let len = reader.u32()? as usize;
+                match offset
+                    .checked_add(len)
+                    .and_then(|end| base.get(offset..end))
+                {
+                    Some(bytes) => bytes,
+                    None => bail!("delta copies past the end of its base"),
+                }
+            }
+            OP_INSERT => {
+                let len = reader.u32()? as usize;
+                reader.take(len)?
+            }
+            op => bail!("unknown delta op {op}"),
+        };
+        ensure!(
+            out.len() + bytes.len() <= target_len,
+            "delta writes past its {target_len} byte target"
+        );
+        out.extend_from_slice(bytes);
+    }
+    ensure!(
+        out.len() == target_len,
+        "delta rebuilt {} of {target_len} bytes",
+        out.len()
+    );
+    Ok(out)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            bail!("delta truncated");
+        }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+/// For the blobs among `objects` (as from `compute_objects_to_push`), the blob the server
+/// already has at the same path, if any: the file at that path in the tree of one of the
+/// `haves`. Only trees that are being sent are searched for new blobs, since a tree the
+/// server has holds no new blob. Blobs at paths `deltify` rejects are left to be sent whole.
+pub fn pick_bases(
+    store: &dyn ObjectStore,
+    objects: &[(ObjectType, Hash, Vec<u8>)],
+    haves: &[Hash],
+    deltify: &dyn Fn(&str) -> bool,
+) -> Result<HashMap<Hash, Hash>> {
+    let mut known = HashMap::new();
+    for have in haves {
+        if !store.has_object(&ObjectType::Commit, have) {
+            continue;
+        }
+        let (tree, _) = parse_commit_for_walk(&store.read_object(&ObjectType::Commit, have)?)?;
+        tree_files(store, tree, "", &|_| true, &mut known)?;
+    }
+    if known.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let sent_trees: HashSet<Hash> = objects
+        .iter()
+        .filter(|(ty, _, _)| *ty == ObjectType::Tree)
+        .map(|(_, hash, _)| *hash)
+        .collect();
+    let sent_blobs: HashSet<Hash> = objects
+        .iter()
+        .filter(|(ty, _, _)| *ty == ObjectType::Blob)
+        .map(|(_, hash, _)| *hash)
+        .collect();
+
+    let mut bases = HashMap::new();
+    for (ty, hash, _) in objects {
+        if *ty != ObjectType::Commit {
+            continue;
+        }
+        let (tree, _) = parse_commit_for_walk(&store.read_object(&ObjectType::Commit, hash)?)?;
+        let mut files = HashMap::new();
+        tree_files(store, tree, "", &|t| sent_trees.contains(t), &mut files)?;
+        for (path, blob) in files {
+            if !sent_blobs.contains(&blob) || !deltify(&path) {
+                continue;
+            }
+            if let Some(&base) = known.get(&path) {
+                bases.entry(blob).or_insert(base);
+            }
+        }
+    }
+    Ok(bases)
+}
+
+/// Collect path -> blob for the files under `tree`, descending only into trees `descend`
+/// accepts
+fn tree_files(
+    store: &dyn ObjectStore,
+    tree: Hash,
+    prefix: &str,
+    descend: &dyn Fn(&Hash) -> bool,
+    files: &mut HashMap<String, Hash>,
+) -> Result<()> {
+    if !descend(&tree) {
+        return Ok(());
+    }
+    let raw = store.read_object(&ObjectType::Tree, &tree)?;
+    for (kind, name, hash) in parse_named_tree_entries(&raw)? {
+        let path = format!("{prefix}{name}");
+        match kind {
+            EntryKind::File => {
+                files.insert(path, hash);
+            }
+            EntryKind::Tree => tree_files(store, hash, &format!("{path}/"), descend, files)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_roundtrip() -> Result<()> {
+        let base: Vec<u8> = (0..20_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let mut target = base.clone();
+        target[5_000..5_010].copy_from_slice(b"0123456789");
+        target.splice(40_000..40_000, b"inserted in the middle".iter().copied());
+        target.extend_from_slice(b"and a new tail");
+
+        let delta = encode(&base, &target);
+        assert!(delta.len() < 200, "delta is {} bytes", delta.len());
+        assert_eq!(apply(&base, &delta, target.len())?, target);
+
+        // Unrelated or empty inputs still round-trip
+        for (base, target) in [
+            (&b""[..], &b"new file"[..]),
+            (b"old", b""),
+            (b"abc", b"xyz"),
+        ] {
+            assert_eq!(apply(base, &encode(base, target), 1024)?, target);
+        }
+
+        // Wrong base, a target over the limit and a truncated delta are refused
+        assert!(apply(&base[1..], &delta, target.len()).is_err());
+        assert!(apply(&base, &delta, target.len() - 1).is_err());
+        assert!(apply(&base, &delta[..delta.len() - 1], target.len()).is_err());
+        Ok(())
+    }
+}
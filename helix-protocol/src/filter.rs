@@ -0,0 +1,264 @@
+/// Path filters for partial clones.
+///
+/// A client that only needs part of a repo sends a PullFilter right after its PullRequest
+/// (`helix clone --filter path:src/`). The server still sends every commit and tree, so the
+/// client has the full shape of history, but only the blobs whose paths match. The client
+/// records the blobs it didn't get as promised by the remote. Servers that predate filters
+/// never read past the PullRequest, so they simply send everything.
+///
+/// A filter matches a path that is one of its entries or lies under one: `src` and `src/`
+/// both match `src/main.rs`, but not `srcs/main.rs`.
+use crate::commit::{order_parents_first, parse_named_tree_entries, CommitData, EntryKind};
+use crate::hash::Hash;
+use crate::message::ObjectType;
+use crate::storage::ObjectStore;
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathFilter {
+    paths: Vec<String>,
+}
+
+impl PathFilter {
+    /// A filter for the given repo-relative paths
+    pub fn new(paths: Vec<String>) -> Result<Self> {
+        let mut normalized = Vec::with_capacity(paths.len());
+        for original in &paths {
+            let path = original
+                .trim()
+                .trim_start_matches("./")
+                .trim_matches('/')
+                .to_string();
+            if path.is_empty() {
+                bail!("Filter path '{original}' would match the whole repository");
+            }
+            if path.split('/').any(|part| part == ".." || part == ".") {
+                bail!("Filter path '{path}' must not contain '.' or '..'");
+            }
+            normalized.push(path);
+        }
+        if normalized.is_empty() {
+            bail!("Filter needs at least one path");
+        }
+        normalized.sort();
+        normalized.dedup();
+        Ok(Self { paths: normalized })
+    }
+
+    /// Parse a filter spec as given on the command line: `path:<dir>[,<dir>...]`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let Some(paths) = spec.strip_prefix("path:") else {
+            bail!("Unsupported filter '{spec}'; expected path:<dir>[,<dir>...]");
+        };
+        Self::new(paths.split(',').map(str::to_string).collect())
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Whether the file or directory at `path` is covered by the filter
+    pub fn matches(&self, path: &str) -> bool {
+        self.paths.iter().any(|p| {
+            path.strip_prefix(p.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// Whether some filter path lies under the directory `dir` ("" for the root)
+    fn reaches_into(&self, dir: &str) -> bool {
+        dir.is_empty()
+            || self.paths.iter().any(|p| {
+                p.strip_prefix(dir)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+}
+
+impl fmt::Display for PathFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "path:{}", self.paths.join(","))
+    }
+}
+
+/// Like `collect_objects_from_commits`, but with only the blobs `filter` matches. Every
+/// commit and tree is included, in the same dependency order.
+pub fn collect_filtered_objects(
+    store: &dyn ObjectStore,
+    commits: &[CommitData],
+    filter: &PathFilter,
+) -> Result<Vec<(ObjectType, Hash, Vec<u8>)>> {
+    let mut walk = FilteredWalk {
+        store,
+        filter,
+        visited: HashSet::new(),
+        sent_trees: HashSet::new(),
+        sent_blobs: HashSet::new(),
+        objects: Vec::new(),
+    };
+    for commit in order_parents_first(commits)? {
+        walk.tree(commit.tree_hash, "")?;
+        walk.objects.push((
+            ObjectType::Commit,
+            commit.hash,
+            commit.compressed_bytes.clone(),
+        ));
+    }
+    Ok(walk.objects)
+}
+
+/// How much of a tree the filter wants. The same tree can sit at several paths, so it's
+/// walked once per distinct scope.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Scope {
+    All,
+    Nothing,
+    Under(String),
+}
+
+struct FilteredWalk<'a> {
+    store: &'a dyn ObjectStore,
+    filter: &'a PathFilter,
+    visited: HashSet<(Hash, Scope)>,
+    sent_trees: HashSet<Hash>,
+    sent_blobs: HashSet<Hash>,
+    objects: Vec<(ObjectType, Hash, Vec<u8>)>,
+}
+
+impl FilteredWalk<'_> {
+    fn tree(&mut self, tree_hash: Hash, dir: &str) -> Result<()> {
+        let scope = if !dir.is_empty() && self.filter.matches(dir) {
+            Scope::All
+        } else if self.filter.reaches_into(dir) {
+            Scope::Under(dir.to_string())
+        } else {
+            Scope::Nothing
+        };
+        if !self.visited.insert((tree_hash, scope.clone())) {
+            return Ok(());
+        }
+
+        let compressed = self
+            .store
+            .read_object_compressed(&ObjectType::Tree, &tree_hash)?;
+        let raw = zstd::decode_all(&compressed[..]).context("Failed to decompress tree")?;
+        for (kind, name, hash) in parse_named_tree_entries(&raw)? {
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{dir}/{name}")
+            };
+            match kind {
+                EntryKind::Tree => self.tree(hash, &path)?,
+                EntryKind::File => {
+                    let wanted = match scope {
+                        Scope::All => true,
+                        Scope::Nothing => false,
+                        Scope::Under(_) => self.filter.matches(&path),
+                    };
+                    if wanted && self.sent_blobs.insert(hash) {
+                        let blob = self
+                            .store
+                            .read_object_compressed(&ObjectType::Blob, &hash)?;
+                        self.objects.push((ObjectType::Blob, hash, blob));
+                    }
+                }
+            }
+        }
+
+        if self.sent_trees.insert(tree_hash) {
+            self.objects.push((ObjectType::Tree, tree_hash, compressed));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::walk_commits_between;
+    use crate::storage::FsObjectStore;
+    use tempfile::TempDir;
+
+    /// A tree in the on-disk format, from (is_tree, name, oid) entries
+    fn tree_bytes(entries: &[(bool, &str, Hash)]) -> Vec<u8> {
+        let mut bytes = (entries.len() as u32).to_le_bytes().to_vec();
+        for (is_tree, name, oid) in entries {
+            bytes.push(if *is_tree { 2 } else { 0 });
+            bytes.extend_from_slice(&0o100644u32.to_le_bytes());
+            bytes.extend_from_slice(&0u64.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(oid);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_filter_matching() -> Result<()> {
+        let filter = PathFilter::parse("path:src/, ./docs/api")?;
+        assert_eq!(filter.to_string(), "path:docs/api,src");
+        assert!(filter.matches("src"));
+        assert!(filter.matches("src/main.rs"));
+        assert!(!filter.matches("srcs/main.rs"));
+        assert!(!filter.matches("docs/guide.md"));
+        assert!(filter.reaches_into("docs"));
+        assert!(!filter.reaches_into("assets"));
+
+        assert!(PathFilter::parse("blob:none").is_err());
+        assert!(PathFilter::parse("path:/").is_err());
+        assert!(PathFilter::parse("path:../x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filtered_objects_keep_trees_and_matching_blobs() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::new(temp.path());
+
+        let main_rs = store.write_object(&ObjectType::Blob, b"fn main() {}")?;
+        let video = store.write_object(&ObjectType::Blob, b"pretend this is huge")?;
+        let readme = store.write_object(&ObjectType::Blob, b"# app")?;
+        let src = store.write_object(
+            &ObjectType::Tree,
+            &tree_bytes(&[(false, "main.rs", main_rs)]),
+        )?;
+        let assets = store.write_object(
+            &ObjectType::Tree,
+            &tree_bytes(&[(false, "intro.mp4", video)]),
+        )?;
+        let root = store.write_object(
+            &ObjectType::Tree,
+            &tree_bytes(&[
+                (false, "README.md", readme),
+                (true, "assets", assets),
+                (true, "src", src),
+            ]),
+        )?;
+        let mut commit = root.to_vec();
+        commit.extend_from_slice(&0u32.to_le_bytes());
+        let commit = store.write_object(&ObjectType::Commit, &commit)?;
+
+        let commits = walk_commits_between(&store, commit, None)?;
+        let objects = collect_filtered_objects(&store, &commits, &PathFilter::parse("path:src/")?)?;
+        let sent: Vec<(ObjectType, Hash)> = objects
+            .into_iter()
+            .map(|(ty, hash, _)| (ty, hash))
+            .collect();
+
+        // Blobs and subtrees before the trees holding them; the commit last
+        assert_eq!(
+            sent,
+            vec![
+                (ObjectType::Tree, assets),
+                (ObjectType::Blob, main_rs),
+                (ObjectType::Tree, src),
+                (ObjectType::Tree, root),
+                (ObjectType::Commit, commit),
+            ]
+        );
+        Ok(())
+    }
+}
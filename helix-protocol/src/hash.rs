@@ -57,6 +57,30 @@ pub fn compute_blob_oid(content: &[u8]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
+/// Git blob id of a file, reading it in chunks rather than all at once
+pub fn compute_blob_oid_stream(path: &Path) -> Result<Vec<u8>> {
+    use sha1::{Digest, Sha1};
+
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", size).as_bytes());
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut read = 0u64;
+    loop {
+        let bytes_read = file.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+        read += bytes_read as u64;
+    }
+    anyhow::ensure!(read == size, "{} changed while hashing", path.display());
+
+    Ok(hasher.finalize().to_vec())
+}
+
 ///conevrt a [u8; 32] → hex string
 #[inline]
 pub fn hash_to_hex(h: &Hash) -> String {
@@ -158,6 +182,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_compute_blob_oid_stream() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file_path = temp_dir.path().join("large.txt");
+
+        let content = vec![b'y'; 200 * 1024];
+        fs::write(&file_path, &content)?;
+
+        assert_eq!(
+            compute_blob_oid_stream(&file_path)?,
+            compute_blob_oid(&content),
+            "Stream and in-memory blob ids should match"
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_hash_files_parallel() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -1,4 +1,8 @@
 pub mod commit;
+pub mod delta;
+pub mod filter;
 pub mod hash;
 pub mod message;
 pub mod storage;
+pub mod tag;
+pub mod validate;
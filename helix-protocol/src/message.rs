@@ -11,6 +11,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 use crate::hash::Hash;
 
@@ -31,14 +32,95 @@ pub enum RpcMessage {
     PullAck(PullAck),
 
     Error(RpcError),
+
+    // Appended after Error so existing variant indices stay stable on the wire
+    FetchObject(FetchObject),
+    FetchDone,
+
+    HelloAck(HelloAck),
+
+    ListRefs(ListRefsRequest),
+    RefList(RefList),
+
+    PushRefs(PushRefsRequest),
+    PushRefsAck(PushRefsAck),
+
+    PushDelta(PushDelta),
+
+    PullFilter(PullFilter),
 }
 
+/// Version of the RPC protocol spoken by this build.
+/// 1: Hello carried only client_version and the server never answered it
+/// 2: Hello carries protocol_version and servers reply with HelloAck first
+/// 3: Features gains `deltas`
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Oldest protocol version this build can still talk to
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Indices of RpcMessage variants whose v1 or v2 encoding differs from the current one
+const HELLO_VARIANT: u32 = 0;
+const ERROR_VARIANT: u32 = 11;
+const HELLO_ACK_VARIANT: u32 = 14;
+const REF_LIST_VARIANT: u32 = 16;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Hello {
     pub client_version: String,
+    pub protocol_version: u32, // v1 clients don't send this; read_message fills in 1
+    pub features: Features,    // what the client can handle in the response
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Hello {
+    pub fn new(client_version: impl Into<String>, features: Features) -> Self {
+        Self {
+            client_version: client_version.into(),
+            protocol_version: PROTOCOL_VERSION,
+            features,
+        }
+    }
+}
+
+/// Optional capabilities, advertised by the client in Hello and the server in HelloAck
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Features {
+    pub packfiles: bool,
+    pub resume: bool,
+    pub compression: bool,
+    /// Server: accepts PushDelta. Not sent by v2 peers; read_message fills in false.
+    pub deltas: bool,
+}
+
+/// Features as v2 peers encode them
+#[derive(Deserialize)]
+struct FeaturesV2 {
+    packfiles: bool,
+    resume: bool,
+    compression: bool,
+}
+
+impl From<FeaturesV2> for Features {
+    fn from(v2: FeaturesV2) -> Self {
+        Self {
+            packfiles: v2.packfiles,
+            resume: v2.resume,
+            compression: v2.compression,
+            deltas: false,
+        }
+    }
+}
+
+/// First message of every response to a client that sent protocol_version >= 2
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelloAck {
+    pub server_version: String,
+    pub protocol_version: u32,
+    pub min_protocol_version: u32,
+    pub features: Features,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PushRequest {
     pub repo: String,     // just repo name or path
     pub ref_name: String, // which pointer -> "refs/heads/main"
@@ -51,11 +133,12 @@ pub struct PushResponse {
     pub remote_head: Option<Hash>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ObjectType {
     Blob,
     Tree,
     Commit,
+    Tag,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,11 +148,63 @@ pub struct PushObject {
     pub data: Vec<u8>, // raw bytes from .helix/objects/*
 }
 
+/// A blob sent as a delta against `base`, a blob the server already has (see delta.rs).
+/// Only sent to servers that advertise `deltas`, in place of a PushObject.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushDelta {
+    pub object_type: ObjectType,
+    pub hash: Hash, // of the rebuilt object
+    pub base: Hash,
+    pub delta: Vec<u8>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PushAck {
     pub received_objects: u64,
 }
 
+/// Several ref updates applied all-or-nothing, e.g. for `helix push --all`.
+/// Sent on /rpc/push in place of PushRequest, followed by PushObject* and
+/// PushDone; the server answers with PushRefsAck.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushRefsRequest {
+    pub repo: String,
+    pub updates: Vec<PushRef>,
+}
+
+/// One ref to move, with the same old/new rules as PushRequest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushRef {
+    pub ref_name: String,
+    pub old_target: Hash,
+    pub new_target: Hash,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushRefsAck {
+    pub received_objects: u64,
+    pub results: Vec<RefResult>, // one per update, in request order
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefResult {
+    pub ref_name: String,
+    pub status: RefStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefStatus {
+    Updated,
+    UpToDate,
+    /// This update failed the server's checks
+    Rejected {
+        kind: ErrorCode,
+        message: String,
+    },
+    /// Valid, but not applied because another update in the push was rejected
+    Skipped,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PullRequest {
     pub repo: String,
@@ -77,6 +212,13 @@ pub struct PullRequest {
     pub last_known_remote: Option<Hash>, // from refs/remotes/origin/main
 }
 
+/// Only send blobs under these paths (see filter.rs). Optional; sent right after
+/// PullRequest, and ignored by servers that predate it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullFilter {
+    pub paths: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PullObject {
     pub object_type: ObjectType,
@@ -97,10 +239,125 @@ pub struct PullAck {
     pub ref_not_found: bool,
 }
 
+/// Request a single object by hash, e.g. to replace a corrupt local copy.
+/// Sent one or more times followed by FetchDone; the server answers with a
+/// PullObject for every object it has, then PullDone.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FetchObject {
+    pub repo: String,
+    pub object_type: ObjectType,
+    pub hash: Hash,
+}
+
+/// Ask for the refs a repo has, e.g. to find remote-tracking refs whose branch
+/// was deleted. The server answers with a single RefList.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListRefsRequest {
+    pub repo: String,
+    pub prefix: String, // only refs under this, e.g. "refs/heads/"; "refs/" for all
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefList {
+    pub refs: Vec<(String, Hash)>, // full ref names, sorted
+    /// The ref the repo's HEAD names ("refs/heads/main"), so a clone knows which branch to
+    /// check out. None for a detached HEAD, and from servers that predate it.
+    pub head: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcError {
-    pub code: u16,
+    pub code: u16, // HTTP status of the response
     pub message: String,
+    pub kind: ErrorCode, // v1 servers don't send this; read_message derives it from `code`
+}
+
+impl RpcError {
+    pub fn new(kind: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: kind.http_status(),
+            message: message.into(),
+            kind,
+        }
+    }
+}
+
+/// Machine-readable reason for an RpcError, so clients can react without parsing messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    Internal,
+    BadRequest,
+    ProtocolMismatch,
+    Unauthorized,
+    RepoNotFound,
+    RefNotFound,
+    /// The push would discard commits on the server the client hasn't seen
+    NotFastForward,
+    /// The ref changed on the server while the request was in flight
+    Conflict,
+    /// An object referenced by the request is not in the server's store
+    ObjectMissing,
+    /// An object failed hash or format checks
+    InvalidObject,
+    /// A message or request body exceeded the server's size limits
+    PayloadTooLarge,
+    /// The client sent too many requests; retry after a pause
+    RateLimited,
+    /// Server policy forbids the change, e.g. deleting or rewriting a protected branch
+    ProtectedRef,
+    /// A pushed object is bigger than the repo's quota allows (sizes as stored, compressed)
+    ObjectTooLarge {
+        hash: Hash,
+        size: u64,
+        limit: u64,
+    },
+    /// The push would take the repo's stored objects past its quota
+    QuotaExceeded {
+        used: u64,
+        limit: u64,
+    },
+    /// The branch is checked out in the target's working tree, so a push may not move it
+    BranchCheckedOut,
+    /// The pusher may not change this path (server access rules)
+    PathForbidden {
+        path: String,
+    },
+}
+
+impl ErrorCode {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ErrorCode::Internal => 500,
+            ErrorCode::BadRequest | ErrorCode::InvalidObject => 400,
+            ErrorCode::ProtocolMismatch => 426,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::RepoNotFound | ErrorCode::RefNotFound => 404,
+            ErrorCode::NotFastForward | ErrorCode::Conflict => 409,
+            ErrorCode::ObjectMissing => 422,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::RateLimited => 429,
+            ErrorCode::ProtectedRef => 403,
+            ErrorCode::ObjectTooLarge { .. } => 413,
+            ErrorCode::QuotaExceeded { .. } => 507,
+            ErrorCode::BranchCheckedOut => 409,
+            ErrorCode::PathForbidden { .. } => 403,
+        }
+    }
+
+    /// Best guess for errors from servers that only sent an HTTP status
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => ErrorCode::BadRequest,
+            401 | 403 => ErrorCode::Unauthorized,
+            404 => ErrorCode::RepoNotFound,
+            409 => ErrorCode::Conflict,
+            413 => ErrorCode::PayloadTooLarge,
+            429 => ErrorCode::RateLimited,
+            422 => ErrorCode::ObjectMissing,
+            426 => ErrorCode::ProtocolMismatch,
+            _ => ErrorCode::Internal,
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -113,20 +370,71 @@ pub enum WireError {
 
     #[error("Unexpected EOF")]
     Eof,
+
+    #[error("Message exceeds the {max} byte limit")]
+    TooLarge { max: usize },
 }
 
+/// Set on the length prefix when the payload is a zstd frame
+const COMPRESSED_FRAME: u32 = 1 << 31;
+
+/// Payloads smaller than this are sent as-is even when compression was negotiated,
+/// so control messages (Hello, requests, acks) never pay the zstd overhead
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+const WIRE_ZSTD_LEVEL: i32 = 3;
+
+/// Largest message read_message accepts, before and after decompression
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 256 * 1024 * 1024;
+
 /// length-prefixed bincode message:
 /// [len: u32 LE][payload: len bytes]
-/// can run this and read async too
-pub fn write_message<W: Write>(mut w: W, msg: &RpcMessage) -> Result<(), WireError> {
-    let payload = bincode::serialize(msg)?;
-    let len = payload.len() as u32;
-    w.write_all(&len.to_le_bytes())?;
-    w.write_all(&payload)?;
+///
+/// Async streams use write_message_async / read_message_async (or AsyncMessageReader)
+/// with the same framing.
+pub fn write_message<W: Write>(w: W, msg: &RpcMessage) -> Result<(), WireError> {
+    write_message_with(w, msg, false)
+}
+
+/// Like write_message, but zstd-compresses the payload when `compress` is set and the
+/// payload is at least COMPRESSION_THRESHOLD bytes. Compressed frames have the top bit
+/// of the length prefix set; only send them to a peer that advertised `compression`.
+pub fn write_message_with<W: Write>(
+    mut w: W,
+    msg: &RpcMessage,
+    compress: bool,
+) -> Result<(), WireError> {
+    w.write_all(&encode_frame(msg, compress)?)?;
     Ok(())
 }
 
-pub fn read_message<R: Read>(mut r: R) -> Result<RpcMessage, WireError> {
+/// The length prefix and payload of one message
+fn encode_frame(msg: &RpcMessage, compress: bool) -> Result<Vec<u8>, WireError> {
+    let mut payload = bincode::serialize(msg)?;
+    let mut len = payload.len() as u32;
+    if compress && payload.len() >= COMPRESSION_THRESHOLD {
+        let compressed = zstd::encode_all(&payload[..], WIRE_ZSTD_LEVEL)?;
+        // Already-compressed object data often doesn't shrink further
+        if compressed.len() < payload.len() {
+            len = compressed.len() as u32 | COMPRESSED_FRAME;
+            payload = compressed;
+        }
+    }
+
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(&payload);
+    Ok(frame)
+}
+
+pub fn read_message<R: Read>(r: R) -> Result<RpcMessage, WireError> {
+    read_message_limited(r, DEFAULT_MAX_MESSAGE_SIZE)
+}
+
+/// Like read_message, but rejects messages larger than `max_size` bytes. The length prefix
+/// comes from the peer, so the payload buffer grows with the bytes actually received instead
+/// of being allocated up front.
+pub fn read_message_limited<R: Read>(mut r: R, max_size: usize) -> Result<RpcMessage, WireError> {
     let mut len_buf = [0u8; 4];
     if let Err(e) = r.read_exact(&mut len_buf) {
         if e.kind() == std::io::ErrorKind::UnexpectedEof {
@@ -135,9 +443,412 @@ pub fn read_message<R: Read>(mut r: R) -> Result<RpcMessage, WireError> {
         return Err(WireError::Io(e));
     }
 
-    let len = u32::from_le_bytes(len_buf) as usize;
-    let mut payload = vec![0u8; len];
-    r.read_exact(&mut payload)?;
-    let msg: RpcMessage = bincode::deserialize(&payload)?;
-    Ok(msg)
+    let len = u32::from_le_bytes(len_buf);
+    let frame_len = frame_len(len, max_size)?;
+
+    let mut payload = Vec::new();
+    (&mut r).take(frame_len as u64).read_to_end(&mut payload)?;
+    if payload.len() < frame_len {
+        return Err(WireError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    decode_frame(len, payload, max_size)
+}
+
+/// Payload length from a length prefix, checked against `max_size`
+fn frame_len(len: u32, max_size: usize) -> Result<usize, WireError> {
+    let frame_len = (len & !COMPRESSED_FRAME) as usize;
+    if frame_len > max_size {
+        return Err(WireError::TooLarge { max: max_size });
+    }
+    Ok(frame_len)
+}
+
+/// Decode the payload of a frame whose length prefix was `len`
+fn decode_frame(len: u32, mut payload: Vec<u8>, max_size: usize) -> Result<RpcMessage, WireError> {
+    if len & COMPRESSED_FRAME != 0 {
+        // Cap the output too, so a small zstd bomb can't expand without bound
+        let mut decoded = Vec::new();
+        zstd::stream::read::Decoder::new(&payload[..])?
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut decoded)?;
+        if decoded.len() > max_size {
+            return Err(WireError::TooLarge { max: max_size });
+        }
+        payload = decoded;
+    }
+    match bincode::deserialize(&payload) {
+        Ok(msg) => Ok(msg),
+        Err(e) => decode_older(&payload).ok_or(WireError::Serialize(e)),
+    }
+}
+
+/// write_message for an async stream. The frame is written with one write_all, so a
+/// cancelled write may leave part of a frame behind; drop the stream if that happens.
+pub async fn write_message_async<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    msg: &RpcMessage,
+) -> Result<(), WireError> {
+    write_message_async_with(w, msg, false).await
+}
+
+/// write_message_with for an async stream
+pub async fn write_message_async_with<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    msg: &RpcMessage,
+    compress: bool,
+) -> Result<(), WireError> {
+    w.write_all(&encode_frame(msg, compress)?).await?;
+    Ok(())
+}
+
+/// read_message for an async stream. Not cancellation safe: if the future is dropped part
+/// way through a frame, the bytes already read are lost. Use AsyncMessageReader where the
+/// read races other futures, e.g. in `tokio::select!`.
+pub async fn read_message_async<R: AsyncRead + Unpin>(r: &mut R) -> Result<RpcMessage, WireError> {
+    AsyncMessageReader::with_limit(r, DEFAULT_MAX_MESSAGE_SIZE)
+        .read_message()
+        .await
+}
+
+/// Most bytes AsyncMessageReader asks its stream for at once
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Reads messages from an async stream. Bytes of a partly received frame are kept in the
+/// reader, so `read_message` is cancellation safe: dropping its future and calling it again
+/// picks up where the last call stopped.
+pub struct AsyncMessageReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    max_size: usize,
+}
+
+impl<R: AsyncRead + Unpin> AsyncMessageReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_limit(inner, DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// A reader that rejects messages larger than `max_size` bytes, like read_message_limited
+    pub fn with_limit(inner: R, max_size: usize) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            max_size,
+        }
+    }
+
+    /// The next message, or WireError::Eof when the stream ends between messages
+    pub async fn read_message(&mut self) -> Result<RpcMessage, WireError> {
+        self.fill(4).await?;
+        let len = u32::from_le_bytes(self.buf[..4].try_into().unwrap());
+        let frame_len = frame_len(len, self.max_size)?;
+
+        self.fill(4 + frame_len).await?;
+        let payload = self.buf[4..4 + frame_len].to_vec();
+        self.buf.drain(..4 + frame_len);
+        decode_frame(len, payload, self.max_size)
+    }
+
+    /// Read until at least `n` bytes are buffered. Only `read_buf` is awaited, and whatever
+    /// it returns is already in `buf`, so cancelling this loses nothing. Like the sync reader,
+    /// the buffer grows with the bytes received rather than with the peer's length prefix.
+    async fn fill(&mut self, n: usize) -> Result<(), WireError> {
+        while self.buf.len() < n {
+            self.buf.reserve((n - self.buf.len()).min(READ_CHUNK));
+            if self.inner.read_buf(&mut self.buf).await? == 0 {
+                return Err(if self.buf.is_empty() {
+                    WireError::Eof
+                } else {
+                    WireError::Io(std::io::ErrorKind::UnexpectedEof.into())
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// v1 peers send Hello without protocol_version/features and RpcError without kind, v2
+/// peers send Hello and HelloAck with fewer features, and older servers send RefList without
+/// head, all of which fail to decode as the current structs. bincode ignores trailing bytes, so older peers read the current
+/// encoding of these without any help.
+fn decode_older(payload: &[u8]) -> Option<RpcMessage> {
+    let variant = u32::from_le_bytes(payload.get(..4)?.try_into().ok()?);
+    let rest = &payload[4..];
+    match variant {
+        HELLO_VARIANT => {
+            if let Ok((client_version, protocol_version, features)) =
+                bincode::deserialize::<(String, u32, FeaturesV2)>(rest)
+            {
+                return Some(RpcMessage::Hello(Hello {
+                    client_version,
+                    protocol_version,
+                    features: features.into(),
+                }));
+            }
+            let client_version: String = bincode::deserialize(rest).ok()?;
+            Some(RpcMessage::Hello(Hello {
+                client_version,
+                protocol_version: 1,
+                features: Features::default(),
+            }))
+        }
+        HELLO_ACK_VARIANT => {
+            let (server_version, protocol_version, min_protocol_version, features) =
+                bincode::deserialize::<(String, u32, u32, FeaturesV2)>(rest).ok()?;
+            Some(RpcMessage::HelloAck(HelloAck {
+                server_version,
+                protocol_version,
+                min_protocol_version,
+                features: features.into(),
+            }))
+        }
+        ERROR_VARIANT => {
+            let (code, message): (u16, String) = bincode::deserialize(rest).ok()?;
+            Some(RpcMessage::Error(RpcError {
+                code,
+                message,
+                kind: ErrorCode::from_status(code),
+            }))
+        }
+        REF_LIST_VARIANT => {
+            let refs: Vec<(String, Hash)> = bincode::deserialize(rest).ok()?;
+            Some(RpcMessage::RefList(RefList { refs, head: None }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_v1_hello_is_read_as_protocol_1() -> Result<(), WireError> {
+        // What a v1 client put on the wire: the variant index and client_version only
+        #[derive(Serialize)]
+        enum V1Message {
+            Hello { client_version: String },
+        }
+        let payload = bincode::serialize(&V1Message::Hello {
+            client_version: "helix-cli".into(),
+        })?;
+        let mut buf = (payload.len() as u32).to_le_bytes().to_vec();
+        buf.extend_from_slice(&payload);
+
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::Hello(hello) => {
+                assert_eq!(hello.client_version, "helix-cli");
+                assert_eq!(hello.protocol_version, 1);
+            }
+            other => panic!("expected Hello, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_v1_error_gets_kind_from_status() -> Result<(), WireError> {
+        let mut payload = ERROR_VARIANT.to_le_bytes().to_vec();
+        payload.extend(bincode::serialize(&(404u16, "no such repo".to_string()))?);
+        let mut buf = (payload.len() as u32).to_le_bytes().to_vec();
+        buf.extend_from_slice(&payload);
+
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::Error(err) => {
+                assert_eq!(err.code, 404);
+                assert_eq!(err.kind, ErrorCode::RepoNotFound);
+            }
+            other => panic!("expected Error, got {other:?}"),
+        }
+
+        let mut buf = Vec::new();
+        write_message(
+            &mut buf,
+            &RpcMessage::Error(RpcError::new(ErrorCode::NotFastForward, "behind")),
+        )?;
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::Error(err) => {
+                assert_eq!(err.code, 409);
+                assert_eq!(err.kind, ErrorCode::NotFastForward);
+            }
+            other => panic!("expected Error, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ref_list_without_head_from_older_servers() -> Result<(), WireError> {
+        let refs = vec![("refs/heads/main".to_string(), [1u8; 32])];
+        let mut payload = REF_LIST_VARIANT.to_le_bytes().to_vec();
+        payload.extend(bincode::serialize(&refs)?);
+        let mut buf = (payload.len() as u32).to_le_bytes().to_vec();
+        buf.extend_from_slice(&payload);
+
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::RefList(list) => {
+                assert_eq!(list.refs, refs);
+                assert_eq!(list.head, None);
+            }
+            other => panic!("expected RefList, got {other:?}"),
+        }
+
+        let mut buf = Vec::new();
+        write_message(
+            &mut buf,
+            &RpcMessage::RefList(RefList {
+                refs,
+                head: Some("refs/heads/trunk".into()),
+            }),
+        )?;
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::RefList(list) => assert_eq!(list.head.as_deref(), Some("refs/heads/trunk")),
+            other => panic!("expected RefList, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_v2_hello_roundtrip() -> Result<(), WireError> {
+        let mut buf = Vec::new();
+        write_message(
+            &mut buf,
+            &RpcMessage::Hello(Hello::new("helix-cli", Features::default())),
+        )?;
+
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::Hello(hello) => assert_eq!(hello.protocol_version, PROTOCOL_VERSION),
+            other => panic!("expected Hello, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_v2_hello_ack_has_no_deltas() -> Result<(), WireError> {
+        // A v2 server's HelloAck: three features, no `deltas`
+        let mut payload = HELLO_ACK_VARIANT.to_le_bytes().to_vec();
+        payload.extend(bincode::serialize(&(
+            "helix-server 0.1.0".to_string(),
+            2u32,
+            1u32,
+            (false, true, true),
+        ))?);
+        let mut buf = (payload.len() as u32).to_le_bytes().to_vec();
+        buf.extend_from_slice(&payload);
+
+        match read_message(Cursor::new(buf))? {
+            RpcMessage::HelloAck(ack) => {
+                assert_eq!(ack.protocol_version, 2);
+                assert!(ack.features.resume && ack.features.compression);
+                assert!(!ack.features.deltas);
+            }
+            other => panic!("expected HelloAck, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_frames() -> Result<(), WireError> {
+        let big = RpcMessage::PullObject(PullObject {
+            object_type: ObjectType::Blob,
+            hash: [7u8; 32],
+            data: vec![b'a'; 64 * 1024],
+        });
+
+        let mut plain = Vec::new();
+        write_message(&mut plain, &big)?;
+        let mut compressed = Vec::new();
+        write_message_with(&mut compressed, &big, true)?;
+        assert!(compressed.len() < plain.len());
+
+        // Small control messages stay uncompressed
+        let mut small = Vec::new();
+        write_message_with(&mut small, &RpcMessage::PullDone, true)?;
+        assert_eq!(
+            u32::from_le_bytes(small[..4].try_into().unwrap()) & COMPRESSED_FRAME,
+            0
+        );
+
+        let mut cursor = Cursor::new([compressed, small].concat());
+        match read_message(&mut cursor)? {
+            RpcMessage::PullObject(obj) => assert_eq!(obj.data.len(), 64 * 1024),
+            other => panic!("expected PullObject, got {other:?}"),
+        }
+        assert!(matches!(read_message(&mut cursor)?, RpcMessage::PullDone));
+        Ok(())
+    }
+
+    #[test]
+    fn test_oversized_messages_are_rejected() -> Result<(), WireError> {
+        // A length prefix claiming ~2 GB must not allocate, just fail
+        let mut bogus = (!COMPRESSED_FRAME).to_le_bytes().to_vec();
+        bogus.extend_from_slice(b"tiny");
+        assert!(matches!(
+            read_message(Cursor::new(&bogus)),
+            Err(WireError::TooLarge { .. })
+        ));
+
+        // Truncated frames within the limit are still an I/O error
+        let mut short = 100u32.to_le_bytes().to_vec();
+        short.extend_from_slice(b"tiny");
+        assert!(matches!(
+            read_message(Cursor::new(&short)),
+            Err(WireError::Io(_))
+        ));
+
+        let big = RpcMessage::PullObject(PullObject {
+            object_type: ObjectType::Blob,
+            hash: [7u8; 32],
+            data: vec![b'a'; 64 * 1024],
+        });
+        let mut compressed = Vec::new();
+        write_message_with(&mut compressed, &big, true)?;
+        let frame_len = compressed.len() - 4;
+
+        // Small on the wire but over the limit once decompressed
+        assert!(matches!(
+            read_message_limited(Cursor::new(&compressed), frame_len + 1),
+            Err(WireError::TooLarge { .. })
+        ));
+        assert!(read_message_limited(Cursor::new(&compressed), 128 * 1024).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_survives_cancelled_reads() -> Result<(), WireError> {
+        let (mut client, server) = tokio::io::duplex(1024 * 1024);
+        let mut reader = AsyncMessageReader::new(server);
+
+        let big = RpcMessage::PullObject(PullObject {
+            object_type: ObjectType::Blob,
+            hash: [7u8; 32],
+            data: vec![b'a'; 10_000],
+        });
+        let mut frame = Vec::new();
+        write_message(&mut frame, &big)?;
+
+        // Half a frame arrives, and the read waiting for the rest is cancelled
+        client.write_all(&frame[..100]).await?;
+        let timeout = std::time::Duration::from_millis(20);
+        assert!(tokio::time::timeout(timeout, reader.read_message())
+            .await
+            .is_err());
+
+        client.write_all(&frame[100..]).await?;
+        write_message_async_with(&mut client, &RpcMessage::PullDone, true).await?;
+        drop(client);
+
+        match reader.read_message().await? {
+            RpcMessage::PullObject(obj) => assert_eq!(obj.data, vec![b'a'; 10_000]),
+            other => panic!("expected PullObject, got {other:?}"),
+        }
+        assert!(matches!(reader.read_message().await?, RpcMessage::PullDone));
+        assert!(matches!(reader.read_message().await, Err(WireError::Eof)));
+
+        // The async and sync framings are the same
+        let mut cursor = Cursor::new(frame);
+        assert!(matches!(
+            read_message_async(&mut cursor).await?,
+            RpcMessage::PullObject(_)
+        ));
+        Ok(())
+    }
 }
@@ -2,50 +2,402 @@
 /// Invariants:
 /// - ObjectId/Hash = BLAKE3(raw bytes)
 /// - On-disk representation may be encoded (e.g. zstd), but API always reads/writes RAW bytes.
+/// - Objects are immutable, so writes are safe to race: each writer fills its own temp file and
+///   renames it into place, and whoever loses the rename finds identical bytes already there.
+/// - Each type directory is either flat (blobs/<64 hex>) or fanned out two levels like Git
+///   (blobs/aa/bb/<60 hex>), as recorded in `objects/layout`. Writes use the recorded layout;
+///   reads look in both, so a store can be moved from one to the other while in use.
+/// - `ObjectStore` and `RefStore` are what a server needs from a repo's storage; the Fs stores
+///   here implement them on local disk and other backends (e.g. object storage) can stand in.
 use anyhow::{Context, Result};
 use rayon::iter::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::fs::OpenOptions;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::hash::{hash_bytes, Hash};
 use crate::message::ObjectType;
 
+/// Object type tag and hash, identifying an object within a store
+type ObjectKey = (u8, Hash);
+
+/// How many recently written objects each store remembers to skip repeat writes
+const RECENT_WRITES_CAPACITY: usize = 65_536;
+
+/// Default bytes of decompressed objects kept in memory per store
+pub const DEFAULT_READ_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Chunk size for objects streamed through `write_object_from_reader`
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// File in the objects directory naming its layout; a store without one is flat
+const LAYOUT_FILE: &str = "layout";
+
+/// How object files are spread over each type directory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectLayout {
+    /// Every object directly in its type directory, as stores were before fanout
+    #[default]
+    Flat,
+    /// Two levels of directories named by the first two bytes of the hash
+    Fanout,
+}
+
+impl ObjectLayout {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Fanout => "fanout",
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Self::Flat => Self::Fanout,
+            Self::Fanout => Self::Flat,
+        }
+    }
+
+    /// Path of an object relative to its type directory
+    fn relative_path(self, hash: &Hash) -> PathBuf {
+        let hex = hex::encode(hash);
+        match self {
+            Self::Flat => PathBuf::from(hex),
+            Self::Fanout => Path::new(&hex[..2]).join(&hex[2..4]).join(&hex[4..]),
+        }
+    }
+}
+
+impl std::fmt::Display for ObjectLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What `FsObjectStore::relayout` did
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RelayoutReport {
+    /// Objects moved into the new layout
+    pub moved: u64,
+    /// Objects already in both layouts whose old copy was dropped
+    pub duplicates: u64,
+    /// Temp files left behind by interrupted writes, removed
+    pub stale_temp_files: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct FsObjectStore {
-    repo_root: PathBuf,
+    objects_dir: PathBuf,
+    layout: ObjectLayout,
+    // Shared between clones so parallel import workers skip each other's writes
+    recent_writes: Arc<Mutex<RecentWrites>>,
+    // Shared between clones so every user of a store (TUI panes, diff) hits the same cache
+    read_cache: Arc<Mutex<ReadCache>>,
+}
+
+/// Least recently used objects, bounded by their total decompressed size.
+/// Objects bigger than an eighth of the capacity aren't cached, so one large
+/// blob can't evict everything else.
+#[derive(Debug)]
+struct ReadCache {
+    capacity: usize,
+    used: usize,
+    tick: u64,
+    entries: HashMap<ObjectKey, (Arc<Vec<u8>>, u64)>,
+    by_age: BTreeMap<u64, ObjectKey>,
+}
+
+impl ReadCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            used: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            by_age: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &ObjectKey) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let (data, age) = self.entries.get_mut(key)?;
+        self.by_age.remove(age);
+        *age = self.tick;
+        self.by_age.insert(self.tick, *key);
+        Some(data.clone())
+    }
+
+    fn accepts(&self, len: usize) -> bool {
+        len <= self.capacity / 8
+    }
+
+    fn insert(&mut self, key: ObjectKey, data: Arc<Vec<u8>>) {
+        if !self.accepts(data.len()) || self.entries.contains_key(&key) {
+            return;
+        }
+
+        self.tick += 1;
+        self.used += data.len();
+        self.entries.insert(key, (data, self.tick));
+        self.by_age.insert(self.tick, key);
+
+        while self.used > self.capacity {
+            let Some((_, oldest)) = self.by_age.pop_first() else {
+                break;
+            };
+            self.remove_entry(&oldest);
+        }
+    }
+
+    fn remove(&mut self, key: &ObjectKey) {
+        if let Some((_, age)) = self.entries.get(key) {
+            self.by_age.remove(age);
+            self.remove_entry(key);
+        }
+    }
+
+    fn remove_entry(&mut self, key: &ObjectKey) {
+        if let Some((data, _)) = self.entries.remove(key) {
+            self.used -= data.len();
+        }
+    }
+}
+
+/// Bounded set of (type, hash) pairs known to be on disk, oldest evicted first.
+/// Saves the existence check syscall when imports write the same blob or tree many times.
+#[derive(Debug, Default)]
+struct RecentWrites {
+    seen: HashSet<ObjectKey>,
+    order: VecDeque<ObjectKey>,
+}
+
+impl RecentWrites {
+    fn contains(&self, key: &ObjectKey) -> bool {
+        self.seen.contains(key)
+    }
+
+    fn insert(&mut self, key: ObjectKey) {
+        if !self.seen.insert(key) {
+            return;
+        }
+        self.order.push_back(key);
+        if self.order.len() > RECENT_WRITES_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &ObjectKey) {
+        if self.seen.remove(key) {
+            self.order.retain(|k| k != key);
+        }
+    }
+}
+
+fn cache_key(ty: &ObjectType, hash: &Hash) -> ObjectKey {
+    let tag = match ty {
+        ObjectType::Blob => 0,
+        ObjectType::Tree => 1,
+        ObjectType::Commit => 2,
+        ObjectType::Tag => 3,
+    };
+    (tag, *hash)
 }
 
 impl FsObjectStore {
     /// Creates a new ObjectStore for Commits, Blobs and Trees.
     pub fn new(repo_root: impl AsRef<Path>) -> Self {
-        let repo_root = repo_root.as_ref().to_path_buf();
-        let base = repo_root.join(".helix").join("objects");
+        Self::at_dir(repo_root.as_ref().join(".helix").join("objects"))
+    }
+
+    /// Creates an ObjectStore rooted directly at `objects_dir` instead of `<repo>/.helix/objects`.
+    /// Used by the server to share a single content-addressed store across many repos.
+    pub fn at_dir(objects_dir: impl AsRef<Path>) -> Self {
+        let objects_dir = objects_dir.as_ref().to_path_buf();
         // best-effort create
-        let _ = fs::create_dir_all(base.join("commits"));
-        let _ = fs::create_dir_all(base.join("trees"));
-        let _ = fs::create_dir_all(base.join("blobs"));
-        Self { repo_root }
+        let _ = fs::create_dir_all(objects_dir.join("commits"));
+        let _ = fs::create_dir_all(objects_dir.join("trees"));
+        let _ = fs::create_dir_all(objects_dir.join("blobs"));
+        let layout = match fs::read_to_string(objects_dir.join(LAYOUT_FILE)) {
+            Ok(text) if text.trim() == ObjectLayout::Fanout.name() => ObjectLayout::Fanout,
+            _ => ObjectLayout::Flat,
+        };
+        Self {
+            objects_dir,
+            layout,
+            recent_writes: Arc::default(),
+            read_cache: Arc::new(Mutex::new(ReadCache::new(DEFAULT_READ_CACHE_BYTES))),
+        }
+    }
+
+    /// Bound the in-memory read cache to `bytes` of decompressed objects; 0 disables it.
+    /// The new cache replaces the old one for this store and clones made from it afterwards.
+    pub fn with_read_cache(mut self, bytes: usize) -> Self {
+        self.read_cache = Arc::new(Mutex::new(ReadCache::new(bytes)));
+        self
+    }
+
+    /// Root directory holding the `blobs/`, `trees/`, `commits/` and `tags/` subdirectories.
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    /// Layout new objects are written in
+    pub fn layout(&self) -> ObjectLayout {
+        self.layout
+    }
+
+    /// Record `layout` for the store, so this and every store opened on it afterwards
+    /// writes new objects in it. Objects already stored stay where they are and are
+    /// still found; `relayout` moves them.
+    pub fn set_layout(&mut self, layout: ObjectLayout) -> Result<()> {
+        let path = self.objects_dir.join(LAYOUT_FILE);
+        let tmp_path = tmp_path_for(&path);
+        fs::write(&tmp_path, format!("{}\n", layout))
+            .and_then(|()| fs::rename(&tmp_path, &path))
+            .with_context(|| format!("write {}", path.display()))?;
+        self.layout = layout;
+        Ok(())
+    }
+
+    /// Switch the store to `layout` and move every object into it, dropping temp files
+    /// that interrupted writes left behind more than `stale_after` ago. Safe to run
+    /// while others read and write: objects are found in either layout throughout.
+    pub fn relayout(
+        &mut self,
+        layout: ObjectLayout,
+        stale_after: std::time::Duration,
+    ) -> Result<RelayoutReport> {
+        self.set_layout(layout)?;
+        let mut report = RelayoutReport::default();
+        let cutoff = std::time::SystemTime::now()
+            .checked_sub(stale_after)
+            .unwrap_or(std::time::UNIX_EPOCH);
+
+        for ty in [
+            ObjectType::Blob,
+            ObjectType::Tree,
+            ObjectType::Commit,
+            ObjectType::Tag,
+        ] {
+            let dir = self.type_dir(&ty);
+            for (path, hash) in object_files(&dir)? {
+                let Some(hash) = hash else {
+                    let stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .is_ok_and(|modified| modified < cutoff);
+                    if stale && fs::remove_file(&path).is_ok() {
+                        report.stale_temp_files += 1;
+                    }
+                    continue;
+                };
+                let target = dir.join(layout.relative_path(&hash));
+                if path == target {
+                    continue;
+                }
+                if target.exists() {
+                    fs::remove_file(&path).with_context(|| format!("remove {}", path.display()))?;
+                    report.duplicates += 1;
+                    continue;
+                }
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&path, &target)
+                    .with_context(|| format!("rename {:?} -> {:?}", path, target))?;
+                report.moved += 1;
+            }
+            if layout == ObjectLayout::Flat {
+                remove_empty_dirs(&dir);
+            }
+        }
+        Ok(report)
+    }
+
+    fn type_dir(&self, ty: &ObjectType) -> PathBuf {
+        self.objects_dir.join(type_dir_name(ty))
     }
-    /// Given an ObjectType, returns the directory path where those objects are stored.
+
+    /// Where an object is written in this store's layout
     fn get_obj_path(&self, ty: &ObjectType, hash: &Hash) -> PathBuf {
-        let subdir = match ty {
-            ObjectType::Blob => "blobs",
-            ObjectType::Tree => "trees",
-            ObjectType::Commit => "commits",
-        };
-        self.repo_root
-            .join(".helix")
-            .join("objects")
-            .join(subdir)
-            .join(hex::encode(hash))
+        self.type_dir(ty).join(self.layout.relative_path(hash))
+    }
+
+    /// The file holding an object: in this store's layout, or the other one for objects
+    /// not yet moved. Where it would be written when it is in neither.
+    pub fn object_path(&self, ty: &ObjectType, hash: &Hash) -> PathBuf {
+        let path = self.get_obj_path(ty, hash);
+        if path.exists() {
+            return path;
+        }
+        let other = self
+            .type_dir(ty)
+            .join(self.layout.other().relative_path(hash));
+        if other.exists() {
+            other
+        } else {
+            path
+        }
+    }
+
+    /// Size in bytes of the object as stored on disk (compressed), if present.
+    pub fn object_disk_size(&self, ty: &ObjectType, hash: &Hash) -> Option<u64> {
+        fs::metadata(self.object_path(ty, hash))
+            .ok()
+            .map(|m| m.len())
+    }
+
+    /// When the object was last written or touched, if present.
+    pub fn object_modified(&self, ty: &ObjectType, hash: &Hash) -> Option<std::time::SystemTime> {
+        fs::metadata(self.object_path(ty, hash))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+
+    /// Mark an existing object as just written, e.g. so a garbage collector that spares
+    /// recent objects keeps one a push is about to reference. Fails if it is missing.
+    pub fn touch_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()> {
+        let path = self.object_path(ty, hash);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+            .with_context(|| format!("touch {}", path.display()))
     }
 
     /// Checks if a hash exists given an ObjectType. For example, given a Blob Hash, checks if the Hash exists within the .helix/objects/blobs/{} path.
     pub fn has_object(&self, ty: &ObjectType, hash: &Hash) -> bool {
-        self.get_obj_path(ty, hash).exists()
+        self.object_path(ty, hash).exists()
+    }
+
+    /// Removes an object from disk, e.g. a corrupt copy about to be replaced. Missing objects are ignored.
+    pub fn remove_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()> {
+        let key = cache_key(ty, hash);
+        self.recent_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+        self.read_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+
+        for layout in [self.layout, self.layout.other()] {
+            let path = self.type_dir(ty).join(layout.relative_path(hash));
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("remove {}", path.display())),
+            }
+        }
+        Ok(())
     }
 
     /// Writes object bytes to disk and returns Hash of bytes.
@@ -66,25 +418,111 @@ impl FsObjectStore {
             hex::encode(computed),
         );
 
-        let path = self.get_obj_path(ty, hash);
-        if path.exists() {
+        let Some(path) = self.path_if_missing(ty, hash) else {
             return Ok(());
-        }
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+        };
 
         // Encode for storage
         let on_disk = zstd::encode_all(raw, 3).context("Failed to compress object")?;
 
-        atomic_write(&path, &on_disk)
-            .with_context(|| format!("write object ty={:?} {}", ty, hex::encode(hash)))?;
-        Ok(())
+        self.store_encoded(ty, hash, &path, &on_disk)
+    }
+
+    /// Write an object read from `reader` without holding it in memory: bytes
+    /// are hashed and compressed into a temp file in STREAM_BUFFER_SIZE chunks,
+    /// then moved into place once the hash is known. Returns the hash and the
+    /// raw size. Used for blobs too large to buffer, e.g. during Git import.
+    pub fn write_object_from_reader(
+        &self,
+        ty: &ObjectType,
+        mut reader: impl Read,
+    ) -> Result<(Hash, u64)> {
+        let dir = self.type_dir(ty);
+        fs::create_dir_all(&dir)?;
+        let tmp_path = tmp_path_for(&dir.join("stream"));
+
+        let written = (|| -> Result<(Hash, u64)> {
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp_path)
+                .with_context(|| format!("open temp object file {:?}", tmp_path))?;
+            let mut encoder = zstd::Encoder::new(file, 3).context("Failed to start compression")?;
+            let mut hasher = blake3::Hasher::new();
+            let mut buffer = vec![0u8; STREAM_BUFFER_SIZE];
+            let mut size = 0u64;
+
+            loop {
+                let n = match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e).context("Failed to read object content"),
+                };
+                hasher.update(&buffer[..n]);
+                encoder.write_all(&buffer[..n])?;
+                size += n as u64;
+            }
+
+            let file = encoder.finish().context("Failed to compress object")?;
+            file.sync_all()
+                .with_context(|| format!("sync temp object file {:?}", tmp_path))?;
+            Ok((*hasher.finalize().as_bytes(), size))
+        })();
+
+        let (hash, size) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+
+        let Some(path) = self.path_if_missing(ty, &hash) else {
+            let _ = fs::remove_file(&tmp_path);
+            return Ok((hash, size));
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(e) = fs::rename(&tmp_path, &path) {
+            let _ = fs::remove_file(&tmp_path);
+            if !path.exists() {
+                return Err(e).with_context(|| format!("rename {:?} -> {:?}", tmp_path, path));
+            }
+        }
+
+        self.recent_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cache_key(ty, &hash));
+        Ok((hash, size))
     }
 
     /// Read objects from disk by decompressing objects to get raw bytes
     pub fn read_object(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>> {
-        let path = self.get_obj_path(ty, hash);
+        let key = cache_key(ty, hash);
+        if let Some(cached) = self.cached(&key) {
+            return Ok(cached.as_ref().clone());
+        }
+
+        let raw = self.read_object_uncached(ty, hash)?;
+        let mut cache = self.read_cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.accepts(raw.len()) {
+            cache.insert(key, Arc::new(raw.clone()));
+        }
+        Ok(raw)
+    }
+
+    fn cached(&self, key: &ObjectKey) -> Option<Arc<Vec<u8>>> {
+        self.read_cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key)
+    }
+
+    fn read_object_uncached(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>> {
+        let path = self.object_path(ty, hash);
         let data = fs::read(&path).with_context(|| format!("read {}", path.display()))?;
 
         let raw = zstd::decode_all(&data[..]).context("Failed to decompress object")?;
@@ -104,7 +542,7 @@ impl FsObjectStore {
 
     /// Read compressed bytes directly from disk. Does not decompress bytes. Mainly used for transfer between client and server.
     pub fn read_object_compressed(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>> {
-        let path = self.get_obj_path(ty, hash);
+        let path = self.object_path(ty, hash);
         fs::read(&path).with_context(|| format!("read compressed {}", path.display()))
     }
 
@@ -130,64 +568,59 @@ impl FsObjectStore {
             hex::encode(computed),
         );
 
-        let path = self.get_obj_path(ty, hash);
-        if path.exists() {
+        let Some(path) = self.path_if_missing(ty, hash) else {
             return Ok(());
-        }
-        if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+        };
 
         // Store compressed bytes directly
-        atomic_write(&path, compressed)
-            .with_context(|| format!("write object ty={:?} {}", ty, hex::encode(hash)))?;
-        Ok(())
+        self.store_encoded(ty, hash, &path, compressed)
     }
 
-    /// List all object hashes on disk for a given ObjectType (not-recursive)
-    pub fn list_object_hashes(&self, ty: &ObjectType) -> Result<Vec<Hash>> {
-        let dir = self
-            .repo_root
-            .join(".helix")
-            .join("objects")
-            .join(match ty {
-                ObjectType::Blob => "blobs",
-                ObjectType::Tree => "trees",
-                ObjectType::Commit => "commits",
-            });
-
-        if !dir.exists() {
-            return Ok(vec![]);
+    /// Where to write an object, or None when it is already stored
+    fn path_if_missing(&self, ty: &ObjectType, hash: &Hash) -> Option<PathBuf> {
+        let key = cache_key(ty, hash);
+        let mut recent = self.recent_writes.lock().unwrap_or_else(|e| e.into_inner());
+        if recent.contains(&key) {
+            return None;
         }
 
-        let mut out = Vec::new();
-
-        for entry in fs::read_dir(&dir).with_context(|| format!("read_dir {}", dir.display()))? {
-            let entry = entry?;
-            if !entry.file_type()?.is_file() {
-                continue;
-            }
-            let path = entry.path();
-            let name = match path.file_name().and_then(|s| s.to_str()) {
-                Some(s) => s,
-                None => continue,
-            };
+        if self.object_path(ty, hash).exists() {
+            recent.insert(key);
+            return None;
+        }
+        Some(self.get_obj_path(ty, hash))
+    }
 
-            // skip temp
-            if name.ends_with(".tmp") || name.starts_with('.') || name.len() != 64 {
-                continue;
-            }
+    fn store_encoded(
+        &self,
+        ty: &ObjectType,
+        hash: &Hash,
+        path: &Path,
+        on_disk: &[u8],
+    ) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-            let bytes = match hex::decode(name) {
-                Ok(b) if b.len() == 32 => b,
-                _ => continue,
-            };
+        atomic_write(path, on_disk)
+            .with_context(|| format!("write object ty={:?} {}", ty, hex::encode(hash)))?;
 
-            let mut h = [0u8; 32];
-            h.copy_from_slice(&bytes);
-            out.push(h);
-        }
+        self.recent_writes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(cache_key(ty, hash));
+        Ok(())
+    }
 
+    /// List all object hashes on disk for a given ObjectType, in either layout
+    pub fn list_object_hashes(&self, ty: &ObjectType) -> Result<Vec<Hash>> {
+        let mut out: Vec<Hash> = object_files(&self.type_dir(ty))?
+            .into_iter()
+            .filter_map(|(_, hash)| hash)
+            .collect();
+        // Caught mid-move, an object can be in both layouts
+        out.sort_unstable();
+        out.dedup();
         Ok(out)
     }
 
@@ -239,6 +672,208 @@ impl FsObjectStore {
     }
 }
 
+/// Objects of a repo (or of a store shared by several), wherever they are kept. Objects are
+/// identified and verified as in `FsObjectStore`: hash = BLAKE3(raw), stored zstd-compressed.
+pub trait ObjectStore: Send + Sync {
+    fn has_object(&self, ty: &ObjectType, hash: &Hash) -> bool;
+
+    /// Raw bytes, checked against the hash
+    fn read_object(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>>;
+
+    /// Bytes as stored, for sending to clients without recompressing
+    fn read_object_compressed(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>>;
+
+    fn write_object(&self, ty: &ObjectType, raw: &[u8]) -> Result<Hash>;
+
+    /// Store compressed bytes received from a client once they decompress to `hash`
+    fn write_object_compressed_with_hash(
+        &self,
+        ty: &ObjectType,
+        hash: &Hash,
+        compressed: &[u8],
+    ) -> Result<()>;
+
+    /// Missing objects are not an error
+    fn remove_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()>;
+
+    fn list_object_hashes(&self, ty: &ObjectType) -> Result<Vec<Hash>>;
+
+    /// Size as stored (compressed), if present
+    fn object_disk_size(&self, ty: &ObjectType, hash: &Hash) -> Option<u64>;
+
+    /// When the object was last written or touched, if present
+    fn object_modified(&self, ty: &ObjectType, hash: &Hash) -> Option<std::time::SystemTime>;
+
+    /// Mark an existing object as just written; fails if it is missing
+    fn touch_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()>;
+
+    /// Bytes taken by every object in the store
+    fn stored_bytes(&self) -> Result<u64> {
+        let mut total = 0;
+        for ty in [
+            ObjectType::Blob,
+            ObjectType::Tree,
+            ObjectType::Commit,
+            ObjectType::Tag,
+        ] {
+            for hash in self.list_object_hashes(&ty)? {
+                total += self.object_disk_size(&ty, &hash).unwrap_or(0);
+            }
+        }
+        Ok(total)
+    }
+}
+
+/// Refs of a repo and their reflogs, wherever they are kept. Names are full ref paths
+/// such as "refs/heads/main".
+pub trait RefStore: Send + Sync {
+    fn get_ref(&self, name: &str) -> Result<Option<Hash>>;
+
+    fn set_ref(&self, name: &str, new: Hash) -> Result<()>;
+
+    /// Removing a ref that doesn't exist is not an error
+    fn delete_ref(&self, name: &str) -> Result<()>;
+
+    /// Point `name` at `new` (delete it for None) only if it still holds `expected` (None:
+    /// doesn't exist). Returns false, changing nothing, when the ref has moved since.
+    fn compare_and_swap_ref(
+        &self,
+        name: &str,
+        expected: Option<Hash>,
+        new: Option<Hash>,
+    ) -> Result<bool>;
+
+    /// Every ref whose name starts with `prefix`, sorted by name
+    fn list_refs(&self, prefix: &str) -> Result<Vec<(String, Hash)>>;
+
+    fn append_reflog(
+        &self,
+        name: &str,
+        old: Option<Hash>,
+        new: Option<Hash>,
+        who: &str,
+        message: &str,
+    ) -> Result<()>;
+
+    /// Reflog entries for a ref, oldest first
+    fn read_reflog(&self, name: &str) -> Result<Vec<ReflogEntry>>;
+}
+
+impl ObjectStore for FsObjectStore {
+    fn has_object(&self, ty: &ObjectType, hash: &Hash) -> bool {
+        FsObjectStore::has_object(self, ty, hash)
+    }
+
+    fn read_object(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>> {
+        FsObjectStore::read_object(self, ty, hash)
+    }
+
+    fn read_object_compressed(&self, ty: &ObjectType, hash: &Hash) -> Result<Vec<u8>> {
+        FsObjectStore::read_object_compressed(self, ty, hash)
+    }
+
+    fn write_object(&self, ty: &ObjectType, raw: &[u8]) -> Result<Hash> {
+        FsObjectStore::write_object(self, ty, raw)
+    }
+
+    fn write_object_compressed_with_hash(
+        &self,
+        ty: &ObjectType,
+        hash: &Hash,
+        compressed: &[u8],
+    ) -> Result<()> {
+        FsObjectStore::write_object_compressed_with_hash(self, ty, hash, compressed)
+    }
+
+    fn remove_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()> {
+        FsObjectStore::remove_object(self, ty, hash)
+    }
+
+    fn list_object_hashes(&self, ty: &ObjectType) -> Result<Vec<Hash>> {
+        FsObjectStore::list_object_hashes(self, ty)
+    }
+
+    fn object_disk_size(&self, ty: &ObjectType, hash: &Hash) -> Option<u64> {
+        FsObjectStore::object_disk_size(self, ty, hash)
+    }
+
+    fn object_modified(&self, ty: &ObjectType, hash: &Hash) -> Option<std::time::SystemTime> {
+        FsObjectStore::object_modified(self, ty, hash)
+    }
+
+    fn touch_object(&self, ty: &ObjectType, hash: &Hash) -> Result<()> {
+        FsObjectStore::touch_object(self, ty, hash)
+    }
+
+    /// Every file under the type directories, temp files included, without hashing names
+    fn stored_bytes(&self) -> Result<u64> {
+        let mut total = 0;
+        for ty in [
+            ObjectType::Blob,
+            ObjectType::Tree,
+            ObjectType::Commit,
+            ObjectType::Tag,
+        ] {
+            for (path, _) in object_files(&self.type_dir(&ty))? {
+                total += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            }
+        }
+        Ok(total)
+    }
+}
+
+impl RefStore for FsRefStore {
+    fn get_ref(&self, name: &str) -> Result<Option<Hash>> {
+        FsRefStore::get_ref(self, name)
+    }
+
+    fn set_ref(&self, name: &str, new: Hash) -> Result<()> {
+        FsRefStore::set_ref(self, name, new)
+    }
+
+    fn delete_ref(&self, name: &str) -> Result<()> {
+        FsRefStore::delete_ref(self, name)
+    }
+
+    fn compare_and_swap_ref(
+        &self,
+        name: &str,
+        expected: Option<Hash>,
+        new: Option<Hash>,
+    ) -> Result<bool> {
+        FsRefStore::compare_and_swap_ref(self, name, expected, new)
+    }
+
+    fn list_refs(&self, prefix: &str) -> Result<Vec<(String, Hash)>> {
+        FsRefStore::list_refs(self, prefix)
+    }
+
+    fn append_reflog(
+        &self,
+        name: &str,
+        old: Option<Hash>,
+        new: Option<Hash>,
+        who: &str,
+        message: &str,
+    ) -> Result<()> {
+        FsRefStore::append_reflog(self, name, old, new, who, message)
+    }
+
+    fn read_reflog(&self, name: &str) -> Result<Vec<ReflogEntry>> {
+        FsRefStore::read_reflog(self, name)
+    }
+}
+
+/// Directory (or key segment, for other backends) objects of a type are kept under
+pub fn type_dir_name(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blobs",
+        ObjectType::Tree => "trees",
+        ObjectType::Commit => "commits",
+        ObjectType::Tag => "tags",
+    }
+}
+
 fn atomic_write(final_path: &Path, bytes: &[u8]) -> Result<()> {
     let tmp_path = tmp_path_for(final_path);
 
@@ -256,19 +891,78 @@ fn atomic_write(final_path: &Path, bytes: &[u8]) -> Result<()> {
 
     drop(f);
 
-    fs::rename(&tmp_path, final_path)
-        .with_context(|| format!("rename {:?} -> {:?}", tmp_path, final_path))?;
+    if let Err(e) = fs::rename(&tmp_path, final_path) {
+        let _ = fs::remove_file(&tmp_path);
+        // Another writer got there first (rename doesn't replace files on Windows).
+        // Content-addressed, so what it wrote is what we were writing.
+        if final_path.exists() {
+            return Ok(());
+        }
+        return Err(e).with_context(|| format!("rename {:?} -> {:?}", tmp_path, final_path));
+    }
 
     Ok(())
 }
 
+/// Every file in a type directory that is an object, in either layout, with its hash, and
+/// every temp file, with None
+fn object_files(type_dir: &Path) -> Result<Vec<(PathBuf, Option<Hash>)>> {
+    let mut out = Vec::new();
+    if !type_dir.exists() {
+        return Ok(out);
+    }
+
+    // (directory, the hex its fanout levels stand for)
+    let mut pending = vec![(type_dir.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = pending.pop() {
+        for entry in fs::read_dir(&dir).with_context(|| format!("read_dir {}", dir.display()))? {
+            let entry = entry?;
+            let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            if entry.file_type()?.is_dir() {
+                if prefix.len() < 4 && name.len() == 2 && hex::decode(&name).is_ok() {
+                    pending.push((entry.path(), format!("{}{}", prefix, name)));
+                }
+                continue;
+            }
+            if name.starts_with('.') && name.contains(".tmp.") {
+                out.push((entry.path(), None));
+                continue;
+            }
+            if (prefix.is_empty() || prefix.len() == 4) && prefix.len() + name.len() == 64 {
+                let mut hash = [0u8; 32];
+                if hex::decode_to_slice(format!("{}{}", prefix, name), &mut hash).is_ok() {
+                    out.push((entry.path(), Some(hash)));
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Remove the fanout directories under `type_dir` that are empty, best effort
+fn remove_empty_dirs(type_dir: &Path) {
+    let Ok(first) = fs::read_dir(type_dir) else {
+        return;
+    };
+    for first in first.flatten().filter(|e| e.path().is_dir()) {
+        if let Ok(second) = fs::read_dir(first.path()) {
+            for second in second.flatten() {
+                let _ = fs::remove_dir(second.path());
+            }
+        }
+        let _ = fs::remove_dir(first.path());
+    }
+}
+
+/// Temp file next to `final_path`, unique across threads and processes
 fn tmp_path_for(final_path: &Path) -> PathBuf {
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
     let file_name = final_path.file_name().unwrap_or_default().to_string_lossy();
-    final_path.with_file_name(format!(".{}.tmp.{}", file_name, nanos))
+    final_path.with_file_name(format!(".{}.tmp.{}.{}", file_name, std::process::id(), n))
 }
 
 #[derive(Clone)]
@@ -308,4 +1002,430 @@ impl FsRefStore {
         fs::write(&path, hex::encode(new))?;
         Ok(())
     }
+
+    /// Remove a ref, and directories it leaves empty under refs/ so a later ref can use
+    /// the name as a file. Removing one that doesn't exist is not an error.
+    pub fn delete_ref(&self, name: &str) -> Result<()> {
+        let path = self.ref_path(name);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        let refs_dir = self.root.join(".helix").join("refs");
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if !dir.starts_with(&refs_dir) || dir == refs_dir || fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+        Ok(())
+    }
+
+    /// Move a ref only if it still holds `expected`. This is a check and a write, so
+    /// writers must be serialized by the caller (the server's ref lock).
+    pub fn compare_and_swap_ref(
+        &self,
+        name: &str,
+        expected: Option<Hash>,
+        new: Option<Hash>,
+    ) -> Result<bool> {
+        if self.get_ref(name)? != expected {
+            return Ok(false);
+        }
+        match new {
+            Some(new) => self.set_ref(name, new)?,
+            None => self.delete_ref(name)?,
+        }
+        Ok(true)
+    }
+
+    fn reflog_path(&self, name: &str) -> PathBuf {
+        self.root.join(".helix").join("logs").join(name)
+    }
+
+    /// Record a change to a ref in .helix/logs/<name>, one line per change:
+    /// `<old hex> <new hex> <who> <unix time>\t<message>`. A ref that didn't exist is
+    /// logged as zeros on the old side; a deleted ref as zeros on the new side.
+    /// The log outlives the ref, so deletions stay on record.
+    pub fn append_reflog(
+        &self,
+        name: &str,
+        old: Option<Hash>,
+        new: Option<Hash>,
+        who: &str,
+        message: &str,
+    ) -> Result<()> {
+        let path = self.reflog_path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", ReflogEntry::line(old, new, who, message))?;
+        Ok(())
+    }
+
+    /// Entries logged by `append_reflog` for a ref, oldest first
+    pub fn read_reflog(&self, name: &str) -> Result<Vec<ReflogEntry>> {
+        let content = match fs::read_to_string(self.reflog_path(name)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        ReflogEntry::parse_log(&content)
+    }
+
+    /// Every ref whose name starts with `prefix` (e.g. "refs/heads/"), sorted by name.
+    /// Files that don't hold a hash (locks, partial writes) are skipped.
+    pub fn list_refs(&self, prefix: &str) -> Result<Vec<(String, Hash)>> {
+        let helix_dir = self.root.join(".helix");
+        let mut refs = Vec::new();
+        let mut pending = vec![helix_dir.join("refs")];
+
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let Ok(name) = path.strip_prefix(&helix_dir) else {
+                    continue;
+                };
+                let name = name.to_string_lossy().replace('\\', "/");
+                if !name.starts_with(prefix) {
+                    continue;
+                }
+                let Ok(hex) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                if let Ok(bytes) = hex::decode(hex.trim()) {
+                    if let Ok(hash) = Hash::try_from(bytes.as_slice()) {
+                        refs.push((name, hash));
+                    }
+                }
+            }
+        }
+
+        refs.sort();
+        Ok(refs)
+    }
+}
+
+/// One change to a ref, as recorded by `FsRefStore::append_reflog`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    /// `None` when the ref was created
+    pub old: Option<Hash>,
+    /// `None` when the ref was deleted
+    pub new: Option<Hash>,
+    pub who: String,
+    pub timestamp: u64,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    /// The reflog line for a change made now, without its newline
+    pub fn line(old: Option<Hash>, new: Option<Hash>, who: &str, message: &str) -> String {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        // Keep each entry on one line whatever the client sent
+        let clean = |s: &str| s.replace(['\n', '\r', '\t'], " ");
+        format!(
+            "{} {} {} {}\t{}",
+            hex::encode(old.unwrap_or([0u8; 32])),
+            hex::encode(new.unwrap_or([0u8; 32])),
+            clean(who),
+            timestamp,
+            clean(message)
+        )
+    }
+
+    /// Every entry in the contents of a reflog, oldest first
+    pub fn parse_log(content: &str) -> Result<Vec<Self>> {
+        content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| Self::parse(line).with_context(|| format!("bad reflog line {line:?}")))
+            .collect()
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut fields = head.splitn(3, ' ');
+        let mut hash = || -> Result<Option<Hash>> {
+            let bytes = hex::decode(fields.next().unwrap_or_default())?;
+            let hash = Hash::try_from(bytes.as_slice())?;
+            Ok((hash != [0u8; 32]).then_some(hash))
+        };
+        let old = hash()?;
+        let new = hash()?;
+        let rest = fields.next().unwrap_or_default();
+        let (who, timestamp) = rest.rsplit_once(' ').unwrap_or(("", rest));
+
+        Ok(Self {
+            old,
+            new,
+            who: who.to_string(),
+            timestamp: timestamp.parse()?,
+            message: message.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_list_refs() -> Result<()> {
+        let temp = TempDir::new()?;
+        let refs = FsRefStore::new(temp.path());
+        refs.set_ref("refs/heads/main", [1u8; 32])?;
+        refs.set_ref("refs/heads/feature/login", [2u8; 32])?;
+        refs.set_ref("refs/tags/v1", [3u8; 32])?;
+        fs::write(temp.path().join(".helix/refs/heads/main.lock"), "")?;
+
+        assert_eq!(
+            refs.list_refs("refs/heads/")?,
+            vec![
+                ("refs/heads/feature/login".to_string(), [2u8; 32]),
+                ("refs/heads/main".to_string(), [1u8; 32]),
+            ]
+        );
+        assert_eq!(refs.list_refs("refs/")?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_and_swap_ref_through_trait() -> Result<()> {
+        let temp = TempDir::new()?;
+        let refs: Arc<dyn RefStore> = Arc::new(FsRefStore::new(temp.path()));
+        let name = "refs/heads/main";
+
+        assert!(refs.compare_and_swap_ref(name, None, Some([1u8; 32]))?);
+        // Someone else's view of the ref is stale
+        assert!(!refs.compare_and_swap_ref(name, None, Some([2u8; 32]))?);
+        assert_eq!(refs.get_ref(name)?, Some([1u8; 32]));
+        assert!(!refs.compare_and_swap_ref(name, Some([3u8; 32]), None)?);
+        assert!(refs.compare_and_swap_ref(name, Some([1u8; 32]), None)?);
+        assert_eq!(refs.get_ref(name)?, None);
+
+        let objects: Arc<dyn ObjectStore> = Arc::new(FsObjectStore::new(temp.path()));
+        let hash = objects.write_object(&ObjectType::Blob, b"hello")?;
+        assert!(objects.stored_bytes()? > 0);
+        assert_eq!(objects.list_object_hashes(&ObjectType::Blob)?, vec![hash]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_ref_and_reflog() -> Result<()> {
+        let temp = TempDir::new()?;
+        let refs = FsRefStore::new(temp.path());
+        refs.set_ref("refs/heads/feature/login", [1u8; 32])?;
+        refs.append_reflog(
+            "refs/heads/feature/login",
+            None,
+            Some([1u8; 32]),
+            "A <a@x>",
+            "push",
+        )?;
+
+        refs.delete_ref("refs/heads/feature/login")?;
+        refs.append_reflog(
+            "refs/heads/feature/login",
+            Some([1u8; 32]),
+            None,
+            "B",
+            "push: delete",
+        )?;
+        assert_eq!(refs.get_ref("refs/heads/feature/login")?, None);
+        // The now-empty directory goes too, so `feature` can become a branch
+        assert!(!temp.path().join(".helix/refs/heads/feature").exists());
+        // Deleting again is fine
+        refs.delete_ref("refs/heads/feature/login")?;
+        refs.set_ref("refs/heads/feature", [2u8; 32])?;
+
+        let log = refs.read_reflog("refs/heads/feature/login")?;
+        assert_eq!(log.len(), 2);
+        assert_eq!((log[0].old, log[0].new), (None, Some([1u8; 32])));
+        assert_eq!(log[0].who, "A <a@x>");
+        assert_eq!((log[1].old, log[1].new), (Some([1u8; 32]), None));
+        assert_eq!(log[1].message, "push: delete");
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_writes_of_same_object() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::new(temp.path());
+        let raw = b"same content from every thread".to_vec();
+
+        let hashes: Vec<Hash> = (0..32)
+            .into_par_iter()
+            .map(|_| FsObjectStore::new(temp.path()).write_object(&ObjectType::Blob, &raw))
+            .collect::<Result<_>>()?;
+
+        assert!(hashes.iter().all(|h| *h == hashes[0]));
+        assert_eq!(store.read_object(&ObjectType::Blob, &hashes[0])?, raw);
+
+        // No temp files left behind
+        let leftover = fs::read_dir(store.objects_dir().join("blobs"))?.count();
+        assert_eq!(leftover, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fanout_layout_and_relayout() -> Result<()> {
+        let temp = TempDir::new()?;
+        let mut store = FsObjectStore::new(temp.path());
+        assert_eq!(store.layout(), ObjectLayout::Flat);
+        let flat = store.write_object(&ObjectType::Blob, b"written flat")?;
+        let flat_hex = hex::encode(flat);
+        assert!(store.objects_dir().join("blobs").join(&flat_hex).is_file());
+
+        // New writes fan out; the flat object is still found
+        store.set_layout(ObjectLayout::Fanout)?;
+        let fanned = store.write_object(&ObjectType::Blob, b"written fanned out")?;
+        let fanned_hex = hex::encode(fanned);
+        let fanned_path = store
+            .objects_dir()
+            .join("blobs")
+            .join(&fanned_hex[..2])
+            .join(&fanned_hex[2..4])
+            .join(&fanned_hex[4..]);
+        assert!(fanned_path.is_file());
+        assert_eq!(
+            store.read_object(&ObjectType::Blob, &flat)?,
+            b"written flat"
+        );
+        assert!(store.has_object(&ObjectType::Blob, &flat));
+        let mut listed = store.list_object_hashes(&ObjectType::Blob)?;
+        listed.sort();
+        let mut expected = vec![flat, fanned];
+        expected.sort();
+        assert_eq!(listed, expected);
+
+        // A store opened later picks up the recorded layout
+        assert_eq!(
+            FsObjectStore::new(temp.path()).layout(),
+            ObjectLayout::Fanout
+        );
+
+        let stale = store.objects_dir().join("blobs").join(".stream.tmp.1.0");
+        fs::write(&stale, b"partial")?;
+        let report = store.relayout(ObjectLayout::Fanout, std::time::Duration::ZERO)?;
+        assert_eq!(
+            report,
+            RelayoutReport {
+                moved: 1,
+                duplicates: 0,
+                stale_temp_files: 1
+            }
+        );
+        assert!(!store.objects_dir().join("blobs").join(&flat_hex).exists());
+        assert!(store
+            .object_path(&ObjectType::Blob, &flat)
+            .ends_with(&flat_hex[4..]));
+        assert_eq!(
+            store.read_object(&ObjectType::Blob, &flat)?,
+            b"written flat"
+        );
+
+        // And back
+        let report = store.relayout(ObjectLayout::Flat, std::time::Duration::ZERO)?;
+        assert_eq!(report.moved, 2);
+        assert!(fs::read_dir(store.objects_dir().join("blobs"))?
+            .all(|e| e.is_ok_and(|e| e.path().is_file())));
+        assert_eq!(
+            store.read_object(&ObjectType::Blob, &fanned)?,
+            b"written fanned out"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_object_from_reader() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::new(temp.path());
+
+        // Several buffers' worth, so the content crosses chunk boundaries
+        let raw: Vec<u8> = (0..STREAM_BUFFER_SIZE * 3 + 17)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let (hash, size) = store.write_object_from_reader(&ObjectType::Blob, &raw[..])?;
+
+        assert_eq!(hash, hash_bytes(&raw));
+        assert_eq!(size, raw.len() as u64);
+        assert_eq!(store.read_object(&ObjectType::Blob, &hash)?, raw);
+
+        // Writing it again finds the stored copy and cleans up its temp file
+        store.write_object_from_reader(&ObjectType::Blob, &raw[..])?;
+        let stored = fs::read_dir(store.objects_dir().join("blobs"))?.count();
+        assert_eq!(stored, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_writes_skip_and_invalidate() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::new(temp.path());
+
+        let hash = store.write_object(&ObjectType::Blob, b"cached")?;
+        assert!(store.path_if_missing(&ObjectType::Blob, &hash).is_none());
+        // Same hash under another type is a different object
+        assert!(store.path_if_missing(&ObjectType::Tree, &hash).is_some());
+
+        // Removing forgets the object, so it can be written again
+        store.remove_object(&ObjectType::Blob, &hash)?;
+        assert!(store.path_if_missing(&ObjectType::Blob, &hash).is_some());
+        store.write_object(&ObjectType::Blob, b"cached")?;
+        assert!(store.has_object(&ObjectType::Blob, &hash));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_cache_serves_hits_and_evicts_by_size() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::new(temp.path()).with_read_cache(8 * 100);
+
+        let a = store.write_object(&ObjectType::Blob, &[b'a'; 100])?;
+        let b = store.write_object(&ObjectType::Blob, &[b'b'; 100])?;
+        let big = store.write_object(&ObjectType::Blob, &[b'c'; 101])?;
+        for hash in [&a, &b, &big] {
+            store.read_object(&ObjectType::Blob, hash)?;
+        }
+
+        // Cached objects survive their files going away (clones share the cache)
+        fs::remove_file(store.get_obj_path(&ObjectType::Blob, &a))?;
+        assert_eq!(
+            store.clone().read_object(&ObjectType::Blob, &a)?,
+            vec![b'a'; 100]
+        );
+        // Objects over an eighth of the capacity are never cached
+        assert!(store.cached(&cache_key(&ObjectType::Blob, &big)).is_none());
+
+        // Filling the cache evicts the least recently used object (b, since a was just read)
+        for i in 0..7u8 {
+            let hash = store.write_object(&ObjectType::Blob, &[i; 100])?;
+            store.read_object(&ObjectType::Blob, &hash)?;
+        }
+        assert!(store.cached(&cache_key(&ObjectType::Blob, &b)).is_none());
+
+        store.remove_object(&ObjectType::Blob, &a)?;
+        assert!(store.read_object(&ObjectType::Blob, &a).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,137 @@
+/// Annotated tags.
+///
+/// A lightweight tag is just a ref under refs/tags holding a commit hash. An
+/// annotated tag ref instead holds the hash of a tag object, which records who
+/// tagged what and why, like a Git tag object:
+///
+///   target    32 bytes, the tagged commit
+///   tag_time  u64 LE, seconds since the Unix epoch
+///   name      u16 LE length + UTF-8
+///   tagger    u16 LE length + UTF-8 ("Name <email>")
+///   message   u32 LE length + UTF-8
+///
+/// Anything reading a tag ref should go through `peel_to_commit`, which
+/// resolves both kinds to the commit.
+use anyhow::{bail, Context, Result};
+
+use crate::hash::{hash_bytes, Hash};
+use crate::message::ObjectType;
+use crate::storage::ObjectStore;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub target: Hash,
+    pub name: String,
+    pub tagger: String,
+    pub tag_time: u64,
+    pub message: String,
+}
+
+impl Tag {
+    pub fn hash(&self) -> Hash {
+        hash_bytes(&self.to_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            32 + 8 + 8 + self.name.len() + self.tagger.len() + self.message.len(),
+        );
+
+        bytes.extend_from_slice(&self.target);
+        bytes.extend_from_slice(&self.tag_time.to_le_bytes());
+        bytes.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&(self.tagger.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.tagger.as_bytes());
+        bytes.extend_from_slice(&(self.message.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.message.as_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, offset: 0 };
+
+        let target: Hash = reader.take(32)?.try_into()?;
+        let tag_time = u64::from_le_bytes(reader.take(8)?.try_into()?);
+        let name_len = u16::from_le_bytes(reader.take(2)?.try_into()?) as usize;
+        let name = reader.string(name_len).context("tag name")?;
+        let tagger_len = u16::from_le_bytes(reader.take(2)?.try_into()?) as usize;
+        let tagger = reader.string(tagger_len).context("tagger")?;
+        let message_len = u32::from_le_bytes(reader.take(4)?.try_into()?) as usize;
+        let message = reader.string(message_len).context("tag message")?;
+
+        Ok(Self {
+            target,
+            name,
+            tagger,
+            tag_time,
+            message,
+        })
+    }
+}
+
+/// Resolve a tag ref's value to the commit it names: the target of an
+/// annotated tag object, or the hash itself for a lightweight tag
+pub fn peel_to_commit(store: &dyn ObjectStore, hash: &Hash) -> Result<Hash> {
+    if !store.has_object(&ObjectType::Tag, hash) {
+        return Ok(*hash);
+    }
+
+    let raw = store.read_object(&ObjectType::Tag, hash)?;
+    Ok(Tag::from_bytes(&raw)?.target)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.offset + len > self.bytes.len() {
+            bail!("Tag truncated at byte {}", self.offset);
+        }
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(slice)
+    }
+
+    fn string(&mut self, len: usize) -> Result<String> {
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FsObjectStore;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tag_roundtrip_and_peel() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = FsObjectStore::at_dir(temp.path());
+
+        let commit = store.write_object(&ObjectType::Commit, b"commit")?;
+        let tag = Tag {
+            target: commit,
+            name: "v1.0".into(),
+            tagger: "Ada <ada@example.com>".into(),
+            tag_time: 1_700_000_000,
+            message: "Release 1.0\n".into(),
+        };
+
+        let bytes = tag.to_bytes();
+        assert_eq!(Tag::from_bytes(&bytes)?, tag);
+        assert!(Tag::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let tag_hash = store.write_object(&ObjectType::Tag, &bytes)?;
+        assert_eq!(tag_hash, tag.hash());
+
+        assert_eq!(peel_to_commit(&store, &tag_hash)?, commit);
+        assert_eq!(peel_to_commit(&store, &commit)?, commit);
+
+        Ok(())
+    }
+}
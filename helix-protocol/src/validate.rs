@@ -0,0 +1,347 @@
+/// Validation of objects received over the wire (pull, fetch).
+///
+/// Objects are expected in dependency order: blobs and subtrees before the tree that
+/// references them, a commit's tree and parents before the commit. That lets each object be
+/// checked on arrival: its hash must match its content, and everything it references must have
+/// arrived earlier in the stream or already exist in the local store. Nothing should be written
+/// (and no ref moved) until every object has been accepted.
+use std::collections::HashSet;
+
+use crate::commit::{parse_commit_for_walk, parse_tree_entries, EntryKind};
+use crate::hash::{hash_bytes, hash_to_hex, Hash};
+use crate::message::ObjectType;
+use crate::storage::ObjectStore;
+use crate::tag::Tag;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ObjectValidationError {
+    #[error("object {} could not be decompressed: {reason}", hash_to_hex(.hash))]
+    Decompress { hash: Hash, reason: String },
+
+    #[error("hash mismatch: expected {}, got {}", hash_to_hex(.expected), hash_to_hex(.actual))]
+    HashMismatch { expected: Hash, actual: Hash },
+
+    #[error("malformed {kind} {}: {reason}", hash_to_hex(.hash))]
+    Malformed {
+        kind: &'static str,
+        hash: Hash,
+        reason: String,
+    },
+
+    #[error(
+        "{kind} {} references {missing_kind} {} which was not received and is not present locally",
+        hash_to_hex(.hash),
+        hash_to_hex(.missing)
+    )]
+    MissingReference {
+        kind: &'static str,
+        hash: Hash,
+        missing_kind: &'static str,
+        missing: Hash,
+    },
+}
+
+/// Tracks objects accepted so far in a stream
+pub struct IncomingObjects<'a> {
+    store: &'a dyn ObjectStore,
+    blobs: HashSet<Hash>,
+    trees: HashSet<Hash>,
+    commits: HashSet<Hash>,
+    tags: HashSet<Hash>,
+    /// Set for filtered pulls: blobs trees reference but that weren't sent
+    promised: Option<HashSet<Hash>>,
+    /// Set when recovering: references that are missing but were let through
+    missing: Option<HashSet<Hash>>,
+}
+
+impl<'a> IncomingObjects<'a> {
+    pub fn new(store: &'a dyn ObjectStore) -> Self {
+        Self {
+            store,
+            blobs: HashSet::new(),
+            trees: HashSet::new(),
+            commits: HashSet::new(),
+            tags: HashSet::new(),
+            promised: None,
+            missing: None,
+        }
+    }
+
+    /// For a filtered pull (see filter.rs): let trees reference blobs that weren't sent and
+    /// aren't present locally, and collect them for `promised`
+    pub fn allow_promised_blobs(mut self) -> Self {
+        self.promised = Some(HashSet::new());
+        self
+    }
+
+    /// For recovering a repository that already lost objects: accept objects whose
+    /// references are missing instead of rejecting them, and collect those for `missing`
+    pub fn allow_missing_references(mut self) -> Self {
+        self.missing = Some(HashSet::new());
+        self
+    }
+
+    /// References let through by `allow_missing_references`
+    pub fn missing(&self) -> Vec<Hash> {
+        self.missing
+            .iter()
+            .flatten()
+            .filter(|hash| {
+                [ObjectType::Blob, ObjectType::Tree, ObjectType::Commit]
+                    .iter()
+                    .all(|ty| !self.has(ty, hash))
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Blobs referenced by the stream that neither arrived nor exist locally
+    pub fn promised(&self) -> Vec<Hash> {
+        let Some(promised) = &self.promised else {
+            return Vec::new();
+        };
+        promised
+            .iter()
+            .filter(|hash| !self.has(&ObjectType::Blob, hash))
+            .copied()
+            .collect()
+    }
+
+    /// Validate one compressed object and remember it as received
+    pub fn accept(
+        &mut self,
+        ty: &ObjectType,
+        hash: &Hash,
+        compressed: &[u8],
+    ) -> Result<(), ObjectValidationError> {
+        let raw = zstd::decode_all(compressed).map_err(|e| ObjectValidationError::Decompress {
+            hash: *hash,
+            reason: e.to_string(),
+        })?;
+
+        let actual = hash_bytes(&raw);
+        if &actual != hash {
+            return Err(ObjectValidationError::HashMismatch {
+                expected: *hash,
+                actual,
+            });
+        }
+
+        let kind = kind_name(ty);
+        let malformed = |e: anyhow::Error| ObjectValidationError::Malformed {
+            kind,
+            hash: *hash,
+            reason: e.to_string(),
+        };
+
+        match ty {
+            ObjectType::Blob => {}
+            ObjectType::Tree => {
+                for (entry_kind, oid) in parse_tree_entries(&raw).map_err(malformed)? {
+                    let child_ty = match entry_kind {
+                        EntryKind::Tree => ObjectType::Tree,
+                        EntryKind::File => ObjectType::Blob,
+                    };
+                    if let (ObjectType::Blob, Some(promised)) = (&child_ty, &mut self.promised) {
+                        if !self.blobs.contains(&oid) && !self.store.has_object(&child_ty, &oid) {
+                            promised.insert(oid);
+                        }
+                        continue;
+                    }
+                    self.require(kind, hash, &child_ty, &oid)?;
+                }
+            }
+            ObjectType::Commit => {
+                let (tree_hash, parents) = parse_commit_for_walk(&raw).map_err(malformed)?;
+                self.require(kind, hash, &ObjectType::Tree, &tree_hash)?;
+                for parent in &parents {
+                    self.require(kind, hash, &ObjectType::Commit, parent)?;
+                }
+            }
+            ObjectType::Tag => {
+                let tag = Tag::from_bytes(&raw).map_err(malformed)?;
+                self.require(kind, hash, &ObjectType::Commit, &tag.target)?;
+            }
+        }
+
+        self.set_for(ty).insert(*hash);
+        Ok(())
+    }
+
+    /// True if the object was received in this stream or exists locally
+    pub fn has(&self, ty: &ObjectType, hash: &Hash) -> bool {
+        let received = match ty {
+            ObjectType::Blob => &self.blobs,
+            ObjectType::Tree => &self.trees,
+            ObjectType::Commit => &self.commits,
+            ObjectType::Tag => &self.tags,
+        };
+        received.contains(hash) || self.store.has_object(ty, hash)
+    }
+
+    fn require(
+        &mut self,
+        kind: &'static str,
+        hash: &Hash,
+        ty: &ObjectType,
+        reference: &Hash,
+    ) -> Result<(), ObjectValidationError> {
+        if self.has(ty, reference) {
+            return Ok(());
+        }
+        if let Some(missing) = &mut self.missing {
+            missing.insert(*reference);
+            return Ok(());
+        }
+        Err(ObjectValidationError::MissingReference {
+            kind,
+            hash: *hash,
+            missing_kind: kind_name(ty),
+            missing: *reference,
+        })
+    }
+
+    fn set_for(&mut self, ty: &ObjectType) -> &mut HashSet<Hash> {
+        match ty {
+            ObjectType::Blob => &mut self.blobs,
+            ObjectType::Tree => &mut self.trees,
+            ObjectType::Commit => &mut self.commits,
+            ObjectType::Tag => &mut self.tags,
+        }
+    }
+}
+
+fn kind_name(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blob",
+        ObjectType::Tree => "tree",
+        ObjectType::Commit => "commit",
+        ObjectType::Tag => "tag",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::{
+        collect_objects_from_commits, is_ancestor, merge_base, walk_commits_between,
+    };
+    use crate::storage::FsObjectStore;
+    use tempfile::TempDir;
+
+    /// Tree with a single file entry, in the on-disk tree format
+    fn tree_bytes(name: &str, blob: &Hash) -> Vec<u8> {
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.push(0); // File
+        bytes.extend_from_slice(&0o100644u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(blob);
+        bytes
+    }
+
+    /// Commit prefix (tree + parents) is all the walker and validator look at
+    fn commit_bytes(tree: &Hash, parents: &[Hash], message: &str) -> Vec<u8> {
+        let mut bytes = tree.to_vec();
+        bytes.extend_from_slice(&(parents.len() as u32).to_le_bytes());
+        for parent in parents {
+            bytes.extend_from_slice(parent);
+        }
+        bytes.extend_from_slice(message.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_pull_stream_is_dependency_ordered_and_validates() -> anyhow::Result<()> {
+        let server_dir = TempDir::new()?;
+        let server = FsObjectStore::at_dir(server_dir.path());
+
+        let blob1 = server.write_object(&ObjectType::Blob, b"one")?;
+        let tree1 = server.write_object(&ObjectType::Tree, &tree_bytes("a", &blob1))?;
+        let c1 = server.write_object(&ObjectType::Commit, &commit_bytes(&tree1, &[], "c1"))?;
+
+        let blob2 = server.write_object(&ObjectType::Blob, b"two")?;
+        let tree2 = server.write_object(&ObjectType::Tree, &tree_bytes("a", &blob2))?;
+        let c2 = server.write_object(&ObjectType::Commit, &commit_bytes(&tree2, &[c1], "c2"))?;
+
+        let commits = walk_commits_between(&server, c2, None)?;
+        let objects = collect_objects_from_commits(&server, &commits)?;
+
+        let position = |h: &Hash| objects.iter().position(|(_, o, _)| o == h).unwrap();
+        assert!(position(&blob1) < position(&tree1));
+        assert!(position(&tree1) < position(&c1));
+        assert!(position(&c1) < position(&c2));
+
+        // An empty client accepts the whole stream in order
+        let client_dir = TempDir::new()?;
+        let client = FsObjectStore::at_dir(client_dir.path());
+        let mut incoming = IncomingObjects::new(&client);
+        for (ty, hash, data) in &objects {
+            incoming.accept(ty, hash, data)?;
+        }
+        assert!(incoming.has(&ObjectType::Commit, &c2));
+
+        // Out of order: the child commit arrives before its parent
+        let mut incoming = IncomingObjects::new(&client);
+        let (ty, hash, data) = objects.iter().find(|(_, h, _)| *h == c2).unwrap();
+        for (ty, hash, data) in objects
+            .iter()
+            .filter(|(_, h, _)| *h == blob2 || *h == tree2)
+        {
+            incoming.accept(ty, hash, data)?;
+        }
+        assert_eq!(
+            incoming.accept(ty, hash, data),
+            Err(ObjectValidationError::MissingReference {
+                kind: "commit",
+                hash: c2,
+                missing_kind: "commit",
+                missing: c1,
+            })
+        );
+
+        // Recovering, the same stream gets through and the gap is reported
+        let mut incoming = IncomingObjects::new(&client).allow_missing_references();
+        for (ty, hash, data) in objects.iter().filter(|(_, h, _)| *h != c1) {
+            incoming.accept(ty, hash, data)?;
+        }
+        assert!(incoming.has(&ObjectType::Commit, &c2));
+        assert_eq!(incoming.missing(), vec![c1]);
+
+        // Payload that doesn't match the claimed hash
+        let mut incoming = IncomingObjects::new(&client);
+        let (_, _, blob2_data) = objects.iter().find(|(_, h, _)| *h == blob2).unwrap();
+        assert!(matches!(
+            incoming.accept(&ObjectType::Blob, &blob1, blob2_data),
+            Err(ObjectValidationError::HashMismatch { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_ancestor() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let store = FsObjectStore::at_dir(dir.path());
+
+        let blob = store.write_object(&ObjectType::Blob, b"one")?;
+        let tree = store.write_object(&ObjectType::Tree, &tree_bytes("a", &blob))?;
+        let c1 = store.write_object(&ObjectType::Commit, &commit_bytes(&tree, &[], "c1"))?;
+        let c2 = store.write_object(&ObjectType::Commit, &commit_bytes(&tree, &[c1], "c2"))?;
+        let side = store.write_object(&ObjectType::Commit, &commit_bytes(&tree, &[c1], "side"))?;
+
+        assert!(is_ancestor(&store, c1, c2)?);
+        assert!(is_ancestor(&store, c2, c2)?);
+        assert!(!is_ancestor(&store, c2, c1)?);
+        assert!(!is_ancestor(&store, side, c2)?);
+        assert_eq!(merge_base(&store, side, c2)?, Some(c1));
+        assert_eq!(merge_base(&store, c1, c2)?, Some(c1));
+
+        // A history the store doesn't have can't be checked
+        let orphan =
+            store.write_object(&ObjectType::Commit, &commit_bytes(&tree, &[[9u8; 32]], "x"))?;
+        assert!(is_ancestor(&store, c2, orphan).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+/// Who may push changes to which paths.
+///
+/// ```toml
+/// [[access.paths]]
+/// prefix = "deploy/"
+/// allow = ["ops@example.com", "Release Bot <bot@example.com>"]
+///
+/// [[access.paths]]
+/// prefix = "src/billing"
+/// allow = ["payments@example.com"]
+/// ```
+///
+/// A push is rejected with PathForbidden, naming the path, if any commit it adds to a ref
+/// changes a file at or under a listed prefix and the pusher isn't in that rule's `allow`
+/// list. When several rules cover a path the pusher must be allowed by all of them. An
+/// `allow` entry matches the whole pusher identity or just the email in its `<...>`.
+///
+/// The pusher is the user whose `[[auth.users]]` token the push carried (see auth.rs); the
+/// rules aren't loaded without such users, as the client could then claim any name. A
+/// push without a known token may not touch any restricted path.
+///
+/// The commits checked are those the new ref value has and the old one doesn't (for a new
+/// ref, those no existing ref has), each compared with its first parent.
+use helix_protocol::commit::{parse_commit_for_walk, parse_named_tree_entries, EntryKind};
+use helix_protocol::hash::Hash;
+use helix_protocol::message::{ErrorCode, ObjectType, RpcError};
+use helix_protocol::storage::{ObjectStore, RefStore};
+use helix_protocol::tag::peel_to_commit;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AccessConfig {
+    pub paths: Vec<PathRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PathRule {
+    pub prefix: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+impl PathRule {
+    fn covers(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_matches('/');
+        prefix.is_empty()
+            || path == prefix
+            || path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    fn allows(&self, pusher: Option<&str>) -> bool {
+        let Some(pusher) = pusher else {
+            return false;
+        };
+        let email = pusher
+            .rsplit_once('<')
+            .and_then(|(_, rest)| rest.strip_suffix('>'));
+        self.allow
+            .iter()
+            .any(|allowed| allowed == pusher || Some(allowed.as_str()) == email)
+    }
+}
+
+impl AccessConfig {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Check the commits that moving a ref from `current` to `new_target` would add
+    pub fn check_push(
+        &self,
+        objects: &dyn ObjectStore,
+        refs: &dyn RefStore,
+        current: Option<Hash>,
+        new_target: Hash,
+        pusher: Option<&str>,
+    ) -> Result<(), RpcError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let internal = |e: anyhow::Error| RpcError::new(ErrorCode::Internal, format!("{e:#}"));
+
+        let new_target = peel_to_commit(objects, &new_target).map_err(internal)?;
+        let tips = match current {
+            Some(current) => vec![current],
+            None => refs
+                .list_refs("refs/")
+                .map_err(internal)?
+                .into_iter()
+                .map(|(_, hash)| hash)
+                .collect(),
+        };
+        let known = reachable(objects, tips).map_err(internal)?;
+
+        for path in changed_paths(objects, new_target, &known).map_err(internal)? {
+            if let Some(rule) = self
+                .paths
+                .iter()
+                .find(|rule| rule.covers(&path) && !rule.allows(pusher))
+            {
+                return Err(RpcError::new(
+                    ErrorCode::PathForbidden { path: path.clone() },
+                    format!(
+                        "{} may not push changes to {path} (restricted to {} under {})",
+                        pusher.unwrap_or("an anonymous pusher"),
+                        rule.allow.join(", "),
+                        rule.prefix
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every commit reachable from `tips`. Tips that aren't commits (tags are peeled) or are
+/// missing are skipped.
+fn reachable(objects: &dyn ObjectStore, tips: Vec<Hash>) -> anyhow::Result<HashSet<Hash>> {
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<Hash> = tips
+        .iter()
+        .filter_map(|tip| peel_to_commit(objects, tip).ok())
+        .collect();
+    while let Some(hash) = queue.pop_front() {
+        if !objects.has_object(&ObjectType::Commit, &hash) || !seen.insert(hash) {
+            continue;
+        }
+        let (_, parents) =
+            parse_commit_for_walk(&objects.read_object(&ObjectType::Commit, &hash)?)?;
+        queue.extend(parents);
+    }
+    Ok(seen)
+}
+
+/// Paths changed by the commits reachable from `tip` but not in `known`, sorted
+fn changed_paths(
+    objects: &dyn ObjectStore,
+    tip: Hash,
+    known: &HashSet<Hash>,
+) -> anyhow::Result<BTreeSet<String>> {
+    let mut paths = BTreeSet::new();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([tip]);
+    while let Some(hash) = queue.pop_front() {
+        if known.contains(&hash) || !seen.insert(hash) {
+            continue;
+        }
+        let (tree, parents) =
+            parse_commit_for_walk(&objects.read_object(&ObjectType::Commit, &hash)?)?;
+        let parent_tree = match parents.first() {
+            Some(parent) => {
+                Some(parse_commit_for_walk(&objects.read_object(&ObjectType::Commit, parent)?)?.0)
+            }
+            None => None,
+        };
+        diff_trees(objects, parent_tree, Some(tree), "", &mut paths)?;
+        queue.extend(parents);
+    }
+    Ok(paths)
+}
+
+/// Add the files that differ between two trees to `paths`, descending only into subtrees
+/// whose hashes differ
+fn diff_trees(
+    objects: &dyn ObjectStore,
+    old: Option<Hash>,
+    new: Option<Hash>,
+    prefix: &str,
+    paths: &mut BTreeSet<String>,
+) -> anyhow::Result<()> {
+    if old == new {
+        return Ok(());
+    }
+    let entries = |tree: Option<Hash>| -> anyhow::Result<BTreeMap<String, (EntryKind, Hash)>> {
+        let Some(tree) = tree else {
+            return Ok(BTreeMap::new());
+        };
+        Ok(
+            parse_named_tree_entries(&objects.read_object(&ObjectType::Tree, &tree)?)?
+                .into_iter()
+                .map(|(kind, name, hash)| (name, (kind, hash)))
+                .collect(),
+        )
+    };
+    let (old, new) = (entries(old)?, entries(new)?);
+
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let (a, b) = (old.get(name), new.get(name));
+        if a.map(|(_, h)| h) == b.map(|(_, h)| h) {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        let subtree = |entry: Option<&(EntryKind, Hash)>| match entry {
+            Some((EntryKind::Tree, hash)) => Some(*hash),
+            _ => None,
+        };
+        let (old_tree, new_tree) = (subtree(a), subtree(b));
+        if old_tree.is_some() || new_tree.is_some() {
+            diff_trees(objects, old_tree, new_tree, &format!("{path}/"), paths)?;
+        }
+        // A file on either side (including one replaced by a directory) changed too
+        if matches!(a, Some((EntryKind::File, _))) || matches!(b, Some((EntryKind::File, _))) {
+            paths.insert(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_rules_cover_prefixes_and_match_pushers() -> anyhow::Result<()> {
+        let config: crate::config::ServerConfig = toml::from_str(
+            r#"
+            [[access.paths]]
+            prefix = "deploy/"
+            allow = ["ops@example.com", "Release Bot <bot@example.com>"]
+            "#,
+        )?;
+        let rule = &config.access.paths[0];
+
+        assert!(rule.covers("deploy"));
+        assert!(rule.covers("deploy/prod.toml"));
+        assert!(!rule.covers("deployment.md"));
+        assert!(!rule.covers("src/deploy/mod.rs"));
+
+        assert!(rule.allows(Some("Ops Team <ops@example.com>")));
+        assert!(rule.allows(Some("Release Bot <bot@example.com>")));
+        assert!(!rule.allows(Some("Mallory <bot@example.com.evil>")));
+        assert!(!rule.allows(Some("ops@example.com.evil")));
+        assert!(!rule.allows(None));
+        Ok(())
+    }
+}
@@ -1,7 +1,231 @@
-use helix_protocol::storage::{FsObjectStore, FsRefStore};
+use crate::access::AccessConfig;
+use crate::auth::AuthConfig;
+use crate::global_store::{GlobalStore, StorageConfig};
+use crate::hooks::HooksConfig;
+use crate::limits::{LimitsConfig, RateLimiter};
+use crate::metrics::{Metrics, SINGLE_REPO_LABEL};
+use crate::quotas::QuotaConfig;
+use crate::ref_policy::RefPolicy;
+use crate::replication::Replicator;
+use crate::s3::S3Bucket;
+use anyhow::{bail, Result};
+use helix_protocol::storage::{FsObjectStore, FsRefStore, ObjectStore, RefStore};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Where the server keeps the repos it hosts.
+#[derive(Clone, Debug)]
+pub enum RepoLayout {
+    /// A single repo rooted at this path; the repo name sent by clients is ignored.
+    Single(PathBuf),
+    /// Many repos, each stored at `<dir>/<repo name>`.
+    Multi(PathBuf),
+    /// Many repos, each kept under `<prefix><repo name>/` in an S3-compatible bucket, so the
+    /// server itself holds no state.
+    Bucket(S3Bucket),
+}
 
 #[derive(Clone)]
 pub struct AppState {
-    pub objects: FsObjectStore,
-    pub refs: FsRefStore,
+    pub layout: RepoLayout,
+    /// When set, objects for every repo live in this shared store and only refs stay per-repo.
+    pub global: Option<GlobalStore>,
+    /// Shared store settings, e.g. how long gc spares new objects.
+    pub storage: StorageConfig,
+    /// Webhooks and scripts notified after a push updates a ref.
+    pub hooks: HooksConfig,
+    /// Counters served at /metrics, shared by every clone of the state.
+    pub metrics: Arc<Metrics>,
+    /// Size caps and per-IP request budget.
+    pub limits: LimitsConfig,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Per-repo caps on object size and total storage.
+    pub quotas: QuotaConfig,
+    /// Refs pushes may not delete or rewrite.
+    pub ref_policy: RefPolicy,
+    /// Paths only some pushers may change.
+    pub access: AccessConfig,
+    /// Tokens for the admin endpoints.
+    pub auth: AuthConfig,
+    /// Queue of refs to copy to the standby server, when one is configured.
+    pub replication: Option<Replicator>,
+    /// One lock per repo, held while a push writes its refs, so a multi-ref push is applied
+    /// all-or-nothing and never interleaves with another push to the same repo. Servers
+    /// sharing a bucket don't share them; each ref write is also a compare-and-swap for them.
+    ref_locks: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+}
+
+/// Object and ref stores for one hosted repo.
+#[derive(Clone)]
+pub struct RepoStores {
+    pub name: String,
+    pub objects: Arc<dyn ObjectStore>,
+    pub refs: Arc<dyn RefStore>,
+}
+
+impl AppState {
+    pub fn new(layout: RepoLayout, global: Option<GlobalStore>, hooks: HooksConfig) -> Self {
+        Self {
+            layout,
+            global,
+            storage: StorageConfig::default(),
+            hooks,
+            metrics: Arc::default(),
+            limits: LimitsConfig::default(),
+            rate_limiter: Arc::new(RateLimiter::new(&LimitsConfig::default())),
+            quotas: QuotaConfig::default(),
+            ref_policy: RefPolicy::default(),
+            access: AccessConfig::default(),
+            auth: AuthConfig::default(),
+            replication: None,
+            ref_locks: Arc::default(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.rate_limiter = Arc::new(RateLimiter::new(&limits));
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_storage(mut self, storage: StorageConfig) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn with_quotas(mut self, quotas: QuotaConfig) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    pub fn with_ref_policy(mut self, ref_policy: RefPolicy) -> Self {
+        self.ref_policy = ref_policy;
+        self
+    }
+
+    pub fn with_access(mut self, access: AccessConfig) -> Self {
+        self.access = access;
+        self
+    }
+
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_replication(mut self, replication: Replicator) -> Self {
+        self.replication = Some(replication);
+        self
+    }
+
+    /// Whether a repo has been pushed to before. Single-repo servers always have their repo.
+    pub fn repo_exists(&self, name: &str) -> bool {
+        match &self.layout {
+            RepoLayout::Single(_) => true,
+            RepoLayout::Multi(dir) => {
+                validate_repo_name(name).is_ok() && dir.join(name).join(".helix").exists()
+            }
+            RepoLayout::Bucket(bucket) => {
+                validate_repo_name(name).is_ok() && bucket.repo_exists(name)
+            }
+        }
+    }
+
+    /// The lock guarding a repo's refs. Blocks, so async code takes it in `spawn_blocking`.
+    pub fn ref_lock(&self, name: &str) -> Arc<Mutex<()>> {
+        // Single-repo servers ignore the name clients send
+        let name = match self.layout {
+            RepoLayout::Single(_) => SINGLE_REPO_LABEL,
+            _ => name,
+        };
+        let mut locks = self.ref_locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(name.to_string()).or_default().clone()
+    }
+
+    /// Every hosted repo, sorted. A single-repo server hosts one, labelled `default`.
+    pub fn repo_names(&self) -> Result<Vec<String>> {
+        match &self.layout {
+            RepoLayout::Single(_) => Ok(vec![SINGLE_REPO_LABEL.to_string()]),
+            RepoLayout::Multi(dir) => {
+                let mut names = Vec::new();
+                if dir.exists() {
+                    for entry in fs::read_dir(dir)? {
+                        let name = entry?.file_name().to_string_lossy().into_owned();
+                        if self.repo_exists(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                names.sort();
+                Ok(names)
+            }
+            RepoLayout::Bucket(bucket) => bucket.repo_names(),
+        }
+    }
+
+    /// The ref a repo's HEAD names ("refs/heads/main"); None when it has no HEAD or
+    /// HEAD is detached
+    pub fn head_ref(&self, name: &str) -> Option<String> {
+        let head = match &self.layout {
+            RepoLayout::Single(root) => fs::read_to_string(root.join(".helix/HEAD")).ok()?,
+            RepoLayout::Multi(dir) => {
+                validate_repo_name(name).ok()?;
+                fs::read_to_string(dir.join(name).join(".helix/HEAD")).ok()?
+            }
+            RepoLayout::Bucket(bucket) => bucket.read_head(name).ok()??,
+        };
+        head.trim().strip_prefix("ref: ").map(str::to_string)
+    }
+
+    /// Resolve the stores for the repo named in a client request. Repos are only made by
+    /// `helix-server admin create-repo`, never by using a name.
+    pub fn repo(&self, name: &str) -> Result<RepoStores> {
+        let root = match &self.layout {
+            RepoLayout::Single(root) => root.clone(),
+            RepoLayout::Multi(dir) => {
+                validate_repo_name(name)?;
+                if !self.repo_exists(name) {
+                    bail!("Repository '{name}' does not exist on this server");
+                }
+                dir.join(name)
+            }
+            RepoLayout::Bucket(bucket) => {
+                validate_repo_name(name)?;
+                return Ok(RepoStores {
+                    name: name.to_string(),
+                    objects: match &self.global {
+                        Some(global) => Arc::new(global.objects().clone()),
+                        None => Arc::new(bucket.objects(name)),
+                    },
+                    refs: Arc::new(bucket.refs(name)),
+                });
+            }
+        };
+
+        let objects: Arc<dyn ObjectStore> = match &self.global {
+            Some(global) => Arc::new(global.objects().clone()),
+            None => Arc::new(FsObjectStore::new(&root)),
+        };
+
+        Ok(RepoStores {
+            name: name.to_string(),
+            objects,
+            refs: Arc::new(FsRefStore::new(&root)),
+        })
+    }
+}
+
+/// Repo names are a single path segment that isn't hidden
+pub fn validate_repo_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.starts_with('.')
+        || name.contains(['/', '\\', '\0'])
+    {
+        bail!("Invalid repo name '{}'", name);
+    }
+    Ok(())
 }
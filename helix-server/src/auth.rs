@@ -0,0 +1,150 @@
+/// Credentials the server accepts.
+///
+/// ```toml
+/// [auth]
+/// admin_token = "long-random-string"   # for /admin/usage and /admin/gc
+///
+/// [[auth.users]]                        # one per pusher
+/// name = "ops@example.com"
+/// token = "another-random-string"
+/// ```
+///
+/// Requests send `Authorization: Bearer <token>` (a remote's `token` on the client side).
+/// Without an `admin_token` the admin endpoints refuse every request.
+///
+/// With `users` listed, a push is made by the user its token belongs to: that name is what
+/// access rules check and the reflog and hooks record, and a push without a known token
+/// has no pusher. Without them the server goes by the X-Helix-Pusher header, which anyone
+/// can set, so `[access]` rules are refused (see config.rs).
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub admin_token: Option<String>,
+    pub users: Vec<UserToken>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserToken {
+    pub name: String,
+    pub token: String,
+}
+
+impl AuthConfig {
+    /// Whether the admin endpoints can be used at all
+    pub fn admin_enabled(&self) -> bool {
+        self.admin_token.as_deref().is_some_and(|t| !t.is_empty())
+    }
+
+    /// Whether the request carries the admin token
+    pub fn is_admin(&self, headers: &HeaderMap) -> bool {
+        match (self.admin_token.as_deref(), bearer_token(headers)) {
+            (Some(expected), Some(sent)) if self.admin_enabled() => {
+                constant_time_eq(expected.as_bytes(), sent.as_bytes())
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether pushers are known by their token rather than by what they claim
+    pub fn authenticates_pushers(&self) -> bool {
+        !self.users.is_empty()
+    }
+
+    /// Who is making a push: the user its token belongs to, or with no users configured,
+    /// the X-Helix-Pusher header
+    pub fn pusher(&self, headers: &HeaderMap) -> Option<String> {
+        if !self.authenticates_pushers() {
+            return headers
+                .get("x-helix-pusher")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+        }
+        let sent = bearer_token(headers).filter(|t| !t.is_empty())?;
+        self.users
+            .iter()
+            .find(|user| constant_time_eq(user.token.as_bytes(), sent.as_bytes()))
+            .map(|user| user.name.clone())
+    }
+}
+
+/// The token in an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(str::trim)
+}
+
+/// Compare secrets without leaking how long a matching prefix is
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn with_auth(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_admin_token_must_match() {
+        let auth = AuthConfig {
+            admin_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        assert!(auth.is_admin(&with_auth("Bearer s3cret")));
+        assert!(!auth.is_admin(&with_auth("Bearer s3cre")));
+        assert!(!auth.is_admin(&with_auth("Basic s3cret")));
+        assert!(!auth.is_admin(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_no_admin_token_admits_nobody() {
+        for auth in [
+            AuthConfig::default(),
+            AuthConfig {
+                admin_token: Some(String::new()),
+                ..Default::default()
+            },
+        ] {
+            assert!(!auth.admin_enabled());
+            assert!(!auth.is_admin(&with_auth("Bearer ")));
+            assert!(!auth.is_admin(&with_auth("Bearer anything")));
+        }
+    }
+
+    #[test]
+    fn test_pusher_comes_from_the_token_once_users_are_listed() {
+        let mut headers = with_auth("Bearer t0ken");
+        headers.insert(
+            "x-helix-pusher",
+            HeaderValue::from_static("ops@example.com"),
+        );
+
+        // No users: the header is all there is
+        let auth = AuthConfig::default();
+        assert_eq!(auth.pusher(&headers).as_deref(), Some("ops@example.com"));
+
+        let auth = AuthConfig {
+            users: vec![UserToken {
+                name: "dev@example.com".to_string(),
+                token: "t0ken".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(auth.pusher(&headers).as_deref(), Some("dev@example.com"));
+
+        // Claiming an identity without its token gets nobody
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer guess"));
+        assert_eq!(auth.pusher(&headers), None);
+        headers.remove(AUTHORIZATION);
+        assert_eq!(auth.pusher(&headers), None);
+    }
+}
@@ -0,0 +1,123 @@
+/// Optional server config file, loaded from the path in HELIX_SERVER_CONFIG.
+///
+/// ```toml
+/// [hooks]
+/// dir = "/etc/helix/hooks"      # runs <dir>/post-receive after each ref update
+///
+/// [[hooks.webhooks]]
+/// url = "https://ci.example.com/helix"
+/// secret = "shared-secret"      # signs the body as X-Helix-Signature-256
+/// events = ["branch", "tag"]    # default: ["push"], every ref update
+///
+/// [storage]                     # see global_store.rs
+/// shared_objects = "/srv/helix/shared"
+///
+/// [storage.s3]                  # repos in a bucket instead, see s3.rs
+/// bucket = "helix-repos"
+/// endpoint = "https://s3.us-east-1.amazonaws.com"
+///
+/// [quotas]                      # see quotas.rs for per-repo overrides
+/// max_object_bytes = 104857600
+/// max_repo_bytes = 10737418240
+///
+/// [refs]                        # see ref_policy.rs
+/// protected = ["main", "refs/heads/release/*"]
+///
+/// [[access.paths]]              # see access.rs
+/// prefix = "deploy/"
+/// allow = ["ops@example.com"]
+///
+/// [replication]                 # see replication.rs
+/// peer = "https://standby.example.com"
+///
+/// [auth]                        # see auth.rs
+/// admin_token = "long-random-string"
+///
+/// [[auth.users]]                # required by [access]
+/// name = "ops@example.com"
+/// token = "another-random-string"
+///
+/// [limits]                      # see limits.rs for every key
+/// max_body_bytes = 1073741824
+/// requests_per_minute = 600
+/// ```
+use crate::access::AccessConfig;
+use crate::auth::AuthConfig;
+use crate::global_store::StorageConfig;
+use crate::hooks::HooksConfig;
+use crate::limits::LimitsConfig;
+use crate::quotas::QuotaConfig;
+use crate::ref_policy::RefPolicy;
+use crate::replication::ReplicationConfig;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub storage: StorageConfig,
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+    #[serde(default)]
+    pub refs: RefPolicy,
+    #[serde(default)]
+    pub access: AccessConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    pub replication: Option<ReplicationConfig>,
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+        let config: Self =
+            toml::from_str(&contents).with_context(|| format!("parse {}", path.display()))?;
+        config
+            .check()
+            .with_context(|| format!("check {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Settings that can't be used together
+    pub fn check(&self) -> Result<()> {
+        if !self.access.paths.is_empty() && !self.auth.authenticates_pushers() {
+            bail!(
+                "[access] rules need [[auth.users]]: without tokens the pusher is whatever the \
+                 client claims"
+            );
+        }
+        Ok(())
+    }
+
+    /// Config from HELIX_SERVER_CONFIG, or the defaults when it isn't set
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("HELIX_SERVER_CONFIG") {
+            Ok(path) => Self::load(Path::new(&path)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access_rules_need_authenticated_pushers() -> Result<()> {
+        let access = "[[access.paths]]\nprefix = \"deploy/\"\nallow = [\"ops@example.com\"]\n";
+        let config: ServerConfig = toml::from_str(access)?;
+        assert!(config.check().is_err());
+
+        let users = "[[auth.users]]\nname = \"ops@example.com\"\ntoken = \"t0ken\"\n";
+        let config: ServerConfig = toml::from_str(&format!("{access}\n{users}"))?;
+        config.check()?;
+        Ok(())
+    }
+}
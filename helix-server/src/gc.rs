@@ -0,0 +1,220 @@
+/// Garbage collection for the shared object store.
+///
+/// Repos share objects, so an object can only go once no repo reaches it. `collect_garbage`
+/// walks every hosted repo's refs, re-records each repo's ledger as exactly what it reaches
+/// (so usage stays accurate after force pushes and deleted branches), then deletes objects no
+/// repo reaches. Objects written within `[storage] gc_grace_secs` are always kept, because a
+/// push stores its objects before it moves any ref. Each repo's refs are locked only while
+/// they're read; pushes go on during the walks, and the sweep follows whatever they moved.
+use crate::app_state::{AppState, RepoStores};
+use crate::global_store::{object_key, GcReport, ObjectKey};
+use anyhow::{bail, Context, Result};
+use helix_protocol::commit::{parse_commit_for_walk, parse_tree_entries, EntryKind};
+use helix_protocol::hash::Hash;
+use helix_protocol::message::ObjectType;
+use helix_protocol::tag::Tag;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+pub fn collect_garbage(state: &AppState) -> Result<GcReport> {
+    let Some(global) = &state.global else {
+        bail!("Global object store is not enabled (set HELIX_GLOBAL_STORE)");
+    };
+    let grace = state.storage.gc_grace();
+
+    // Repos that own objects, plus every hosted repo (one that has never pushed since the
+    // store was enabled still needs its objects)
+    let mut repos: BTreeSet<String> = global.ledger_repos()?.into_iter().collect();
+    repos.extend(state.repo_names()?);
+
+    let mut live = HashSet::new();
+    let mut walked = Vec::with_capacity(repos.len());
+    for name in &repos {
+        if !state.repo_exists(name) {
+            // Deleted; it owns nothing now
+            global.retrack(name, &HashSet::new(), grace)?;
+            continue;
+        }
+        let repo = state.repo(name)?;
+        let refs: HashMap<String, Hash> = {
+            let lock = state.ref_lock(name);
+            let _refs = lock.lock().unwrap_or_else(|e| e.into_inner());
+            repo.refs.list_refs("refs/")?.into_iter().collect()
+        };
+        let reachable = reachable_from(&repo, refs.values().copied(), HashSet::new())
+            .with_context(|| format!("walk objects of repo '{name}'"))?;
+        global.retrack(name, &reachable, grace)?;
+        live.extend(reachable);
+        walked.push((repo, refs));
+    }
+
+    // A push since a repo was walked may have pointed a ref at an old object. Hold every
+    // repo's refs still while such moves are followed and the sweep runs.
+    let locks: Vec<_> = repos.iter().map(|name| state.ref_lock(name)).collect();
+    let _refs: Vec<_> = locks
+        .iter()
+        .map(|lock| lock.lock().unwrap_or_else(|e| e.into_inner()))
+        .collect();
+    for (repo, refs) in &walked {
+        if !state.repo_exists(&repo.name) {
+            continue;
+        }
+        let moved: Vec<Hash> = repo
+            .refs
+            .list_refs("refs/")?
+            .into_iter()
+            .filter(|(name, target)| refs.get(name) != Some(target))
+            .map(|(_, target)| target)
+            .collect();
+        live = reachable_from(repo, moved, live)
+            .with_context(|| format!("walk objects of repo '{}'", repo.name))?;
+    }
+
+    let mut report = global.sweep(&live, grace)?;
+    report.repos = repos.len() as u64;
+    tracing::info!(
+        repos = report.repos,
+        removed = report.removed_objects,
+        freed_bytes = report.freed_bytes,
+        "gc finished"
+    );
+    Ok(report)
+}
+
+/// Add to `seen` every object reachable from `targets` (ref values): tag objects, commits
+/// and their history, trees and blobs. The walk stops at objects already in `seen`.
+fn reachable_from(
+    repo: &RepoStores,
+    targets: impl IntoIterator<Item = Hash>,
+    mut seen: HashSet<ObjectKey>,
+) -> Result<HashSet<ObjectKey>> {
+    let store = repo.objects.as_ref();
+    let mut commits: Vec<Hash> = Vec::new();
+    let mut trees: Vec<Hash> = Vec::new();
+
+    for target in targets {
+        let mut target = target;
+        while store.has_object(&ObjectType::Tag, &target) {
+            if !seen.insert(object_key(&ObjectType::Tag, target)) {
+                break;
+            }
+            target = Tag::from_bytes(&store.read_object(&ObjectType::Tag, &target)?)?.target;
+        }
+        commits.push(target);
+    }
+
+    while let Some(commit) = commits.pop() {
+        if !seen.insert(object_key(&ObjectType::Commit, commit)) {
+            continue;
+        }
+        let (tree, parents) =
+            parse_commit_for_walk(&store.read_object(&ObjectType::Commit, &commit)?)?;
+        trees.push(tree);
+        commits.extend(parents);
+    }
+
+    while let Some(tree) = trees.pop() {
+        if !seen.insert(object_key(&ObjectType::Tree, tree)) {
+            continue;
+        }
+        for (kind, hash) in parse_tree_entries(&store.read_object(&ObjectType::Tree, &tree)?)? {
+            match kind {
+                EntryKind::Tree => trees.push(hash),
+                EntryKind::File => {
+                    seen.insert(object_key(&ObjectType::Blob, hash));
+                }
+            }
+        }
+    }
+
+    Ok(seen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::RepoLayout;
+    use crate::global_store::{GlobalStore, StorageConfig};
+    use crate::hooks::HooksConfig;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A tree with one file, in the format parse_tree_entries reads
+    fn tree_with(blob: Hash) -> Vec<u8> {
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&0o100644u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.push(b'f');
+        bytes.extend_from_slice(&blob);
+        bytes
+    }
+
+    fn commit_with(tree: Hash, parents: &[Hash]) -> Vec<u8> {
+        let mut bytes = tree.to_vec();
+        bytes.extend_from_slice(&(parents.len() as u32).to_le_bytes());
+        for parent in parents {
+            bytes.extend_from_slice(parent);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_gc_keeps_objects_any_repo_reaches() -> Result<()> {
+        let temp = TempDir::new()?;
+        let global = GlobalStore::new(temp.path().join("shared"))?;
+        let state = AppState::new(
+            RepoLayout::Multi(temp.path().join("repos")),
+            Some(global.clone()),
+            HooksConfig::default(),
+        )
+        .with_storage(StorageConfig {
+            gc_grace_secs: 0,
+            ..Default::default()
+        });
+        let objects = global.objects();
+
+        let shared_blob = objects.write_object(&ObjectType::Blob, b"shared")?;
+        let shared_tree = objects.write_object(&ObjectType::Tree, &tree_with(shared_blob))?;
+        let base = objects.write_object(&ObjectType::Commit, &commit_with(shared_tree, &[]))?;
+        let dropped_blob = objects.write_object(&ObjectType::Blob, b"force-pushed away")?;
+        let dropped_tree = objects.write_object(&ObjectType::Tree, &tree_with(dropped_blob))?;
+        let dropped =
+            objects.write_object(&ObjectType::Commit, &commit_with(dropped_tree, &[base]))?;
+
+        // The fork still has `base`; the original moved back to it after pushing `dropped`
+        for name in ["app", "fork"] {
+            fs::create_dir_all(temp.path().join("repos").join(name).join(".helix"))?;
+            let repo = state.repo(name)?;
+            repo.refs.set_ref("refs/heads/main", base)?;
+        }
+        global.record_owner(
+            "app",
+            &[
+                (ObjectType::Blob, dropped_blob),
+                (ObjectType::Tree, dropped_tree),
+                (ObjectType::Commit, dropped),
+            ],
+        )?;
+        // A repo that was deleted from disk
+        global.record_owner("gone", &[(ObjectType::Blob, shared_blob)])?;
+
+        let report = collect_garbage(&state)?;
+        assert_eq!(report.removed_objects, 3);
+        assert!(!objects.has_object(&ObjectType::Commit, &dropped));
+        assert!(!objects.has_object(&ObjectType::Blob, &dropped_blob));
+        assert!(objects.has_object(&ObjectType::Blob, &shared_blob));
+        assert!(objects.has_object(&ObjectType::Commit, &base));
+
+        // Both live repos now own exactly the shared history; the deleted one owns nothing
+        let usage = global.usage()?;
+        let owners: Vec<(&str, u64)> = usage
+            .repos
+            .iter()
+            .map(|r| (r.repo.as_str(), r.objects))
+            .collect();
+        assert_eq!(owners, vec![("app", 3), ("fork", 3)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+/// Optional content-addressed object store shared by every repo hosted on the server.
+///
+/// Forks of the same codebase mostly contain identical commits, trees and blobs. When a global
+/// store is configured, objects are written once under `<root>/objects` while refs stay per-repo.
+///
+/// Every repo that pushes an object is recorded as an owner of it in `<root>/owners/<repo>`
+/// (one `<type> <hex> <size>` line per object). Usage accounting splits the on-disk size of each
+/// object evenly between all of its owners, so a fork only pays for what it shares.
+///
+/// The store is enabled with HELIX_GLOBAL_STORE or, in the server config:
+///
+/// ```toml
+/// [storage]
+/// shared_objects = "/srv/helix/shared"
+/// gc_grace_secs = 86400           # objects newer than this are never collected
+/// ```
+///
+/// Ledgers only ever grow as objects are pushed; `retrack` and `sweep` (driven by gc.rs) bring
+/// them back in line with what each repo's refs reach and delete objects no repo needs.
+use crate::s3::S3Config;
+use anyhow::{Context, Result};
+use helix_protocol::hash::{hex_to_hash, Hash};
+use helix_protocol::message::ObjectType;
+use helix_protocol::storage::FsObjectStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Object type tag (as in the ledgers) and hash
+pub type ObjectKey = (u8, Hash);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Root of the shared object store; HELIX_GLOBAL_STORE takes precedence
+    pub shared_objects: Option<PathBuf>,
+    pub gc_grace_secs: u64,
+    /// Keep repos in an S3-compatible bucket instead of on disk; see s3.rs
+    pub s3: Option<S3Config>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            shared_objects: None,
+            gc_grace_secs: 24 * 60 * 60,
+            s3: None,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn gc_grace(&self) -> Duration {
+        Duration::from_secs(self.gc_grace_secs)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GlobalStore {
+    root: PathBuf,
+    objects: FsObjectStore,
+    // Serializes appends to the owner ledgers
+    ledger_lock: Arc<Mutex<()>>,
+}
+
+/// Storage attributed to a single repo in the global store.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepoUsage {
+    pub repo: String,
+    /// Number of distinct objects referenced by the repo
+    pub objects: u64,
+    /// Bytes the repo would use if it had its own object store
+    pub logical_bytes: u64,
+    /// Bytes of objects referenced only by this repo
+    pub exclusive_bytes: u64,
+    /// Exclusive bytes plus an even share of every object shared with other repos
+    pub attributed_bytes: u64,
+}
+
+/// Summary of the whole global store.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StoreUsage {
+    /// Bytes physically stored on disk for all owned objects
+    pub physical_bytes: u64,
+    /// Sum of logical bytes across all repos (what per-repo stores would use)
+    pub logical_bytes: u64,
+    pub repos: Vec<RepoUsage>,
+}
+
+/// What a garbage collection run did.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GcReport {
+    /// Repos whose ledgers were rewritten
+    pub repos: u64,
+    pub removed_objects: u64,
+    pub freed_bytes: u64,
+    /// Unreachable objects kept because they were written within the grace period
+    pub kept_recent: u64,
+}
+
+impl StoreUsage {
+    /// Bytes saved by deduplicating objects across repos.
+    pub fn saved_bytes(&self) -> u64 {
+        self.logical_bytes.saturating_sub(self.physical_bytes)
+    }
+}
+
+impl GlobalStore {
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join("owners"))
+            .with_context(|| format!("create global store at {}", root.display()))?;
+        let objects = FsObjectStore::at_dir(root.join("objects"));
+        Ok(Self {
+            root,
+            objects,
+            ledger_lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    pub fn objects(&self) -> &FsObjectStore {
+        &self.objects
+    }
+
+    fn ledger_path(&self, repo: &str) -> PathBuf {
+        self.root.join("owners").join(repo)
+    }
+
+    /// Record `repo` as an owner of the given objects. This also marks each object as fresh,
+    /// so a concurrent `sweep` keeps objects a push is about to point a ref at; one that a
+    /// sweep already removed is an error, and the push has to be retried.
+    pub fn record_owner(&self, repo: &str, objects: &[(ObjectType, Hash)]) -> Result<()> {
+        if objects.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = String::new();
+        for (ty, hash) in objects {
+            self.objects.touch_object(ty, hash)?;
+            let size = self.objects.object_disk_size(ty, hash).unwrap_or(0);
+            lines.push_str(&format!(
+                "{} {} {}\n",
+                type_tag(ty),
+                hex::encode(hash),
+                size
+            ));
+        }
+
+        let _guard = self.ledger_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let path = self.ledger_path(repo);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("open owner ledger {}", path.display()))?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Load the distinct objects owned by `repo` along with their on-disk sizes.
+    fn load_ledger(&self, repo: &str) -> Result<HashMap<(u8, Hash), u64>> {
+        let path = self.ledger_path(repo);
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+        };
+
+        let mut out = HashMap::new();
+        for line in contents.lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(tag), Some(hex), Some(size)) = (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            let (Some(ty), Ok(hash), Ok(size)) =
+                (tag_type(tag), hex_to_hash(hex), size.parse::<u64>())
+            else {
+                continue;
+            };
+            out.insert((ty, hash), size);
+        }
+        Ok(out)
+    }
+
+    /// Drop a deleted repo's ledger. Objects only it owned go at the next gc.
+    pub fn forget_repo(&self, repo: &str) -> Result<()> {
+        let _guard = self.ledger_lock.lock().unwrap_or_else(|e| e.into_inner());
+        match fs::remove_file(self.ledger_path(repo)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Repos with an owner ledger, sorted.
+    pub fn ledger_repos(&self) -> Result<Vec<String>> {
+        let mut repos = Vec::new();
+        for entry in fs::read_dir(self.root.join("owners"))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Skip ledgers being rewritten
+            if entry.file_type()?.is_file() && !name.starts_with('.') {
+                repos.push(name);
+            }
+        }
+        repos.sort();
+        Ok(repos)
+    }
+
+    /// Replace `repo`'s ledger with the objects its refs reach, plus any entries written
+    /// within `grace` (a push stores its objects before it moves a ref). A repo left with
+    /// nothing loses its ledger.
+    pub fn retrack(
+        &self,
+        repo: &str,
+        reachable: &HashSet<ObjectKey>,
+        grace: Duration,
+    ) -> Result<()> {
+        let _guard = self.ledger_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let old = self.load_ledger(repo)?;
+
+        let mut keys: Vec<ObjectKey> = reachable.iter().copied().collect();
+        keys.extend(
+            old.into_keys()
+                .filter(|key| !reachable.contains(key) && self.is_recent(key, grace)),
+        );
+        keys.sort();
+
+        let path = self.ledger_path(repo);
+        if keys.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    Err(e).with_context(|| format!("remove {}", path.display()))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        let mut lines = String::new();
+        for (tag, hash) in keys {
+            let ty = key_type(tag);
+            let size = self.objects.object_disk_size(&ty, &hash).unwrap_or(0);
+            lines.push_str(&format!(
+                "{} {} {}\n",
+                type_tag(&ty),
+                hex::encode(hash),
+                size
+            ));
+        }
+        let tmp = self.root.join("owners").join(format!(".{repo}.tmp"));
+        fs::write(&tmp, lines).with_context(|| format!("write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Delete every object outside `live` that is older than `grace`.
+    pub fn sweep(&self, live: &HashSet<ObjectKey>, grace: Duration) -> Result<GcReport> {
+        let mut report = GcReport::default();
+        for ty in [
+            ObjectType::Blob,
+            ObjectType::Tree,
+            ObjectType::Commit,
+            ObjectType::Tag,
+        ] {
+            for hash in self.objects.list_object_hashes(&ty)? {
+                let key = object_key(&ty, hash);
+                if live.contains(&key) {
+                    continue;
+                }
+                if self.is_recent(&key, grace) {
+                    report.kept_recent += 1;
+                    continue;
+                }
+                let size = self.objects.object_disk_size(&ty, &hash).unwrap_or(0);
+                self.objects.remove_object(&ty, &hash)?;
+                report.removed_objects += 1;
+                report.freed_bytes += size;
+            }
+        }
+        Ok(report)
+    }
+
+    fn is_recent(&self, (tag, hash): &ObjectKey, grace: Duration) -> bool {
+        self.objects
+            .object_modified(&key_type(*tag), hash)
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_none_or(|age| age < grace)
+    }
+
+    /// Compute per-repo usage, splitting shared objects evenly between their owners.
+    pub fn usage(&self) -> Result<StoreUsage> {
+        let mut ledgers: BTreeMap<String, HashMap<(u8, Hash), u64>> = BTreeMap::new();
+        for repo in self.ledger_repos()? {
+            let ledger = self.load_ledger(&repo)?;
+            ledgers.insert(repo, ledger);
+        }
+
+        let mut owner_counts: HashMap<(u8, Hash), u64> = HashMap::new();
+        for ledger in ledgers.values() {
+            for key in ledger.keys() {
+                *owner_counts.entry(*key).or_default() += 1;
+            }
+        }
+
+        let mut usage = StoreUsage::default();
+        let mut counted: HashSet<(u8, Hash)> = HashSet::new();
+
+        for (repo, ledger) in ledgers {
+            let mut repo_usage = RepoUsage {
+                repo,
+                objects: ledger.len() as u64,
+                ..Default::default()
+            };
+
+            for (key, size) in ledger {
+                let owners = owner_counts.get(&key).copied().unwrap_or(1).max(1);
+                repo_usage.logical_bytes += size;
+                repo_usage.attributed_bytes += size.div_ceil(owners);
+                if owners == 1 {
+                    repo_usage.exclusive_bytes += size;
+                }
+                if counted.insert(key) {
+                    usage.physical_bytes += size;
+                }
+            }
+
+            usage.logical_bytes += repo_usage.logical_bytes;
+            usage.repos.push(repo_usage);
+        }
+
+        Ok(usage)
+    }
+}
+
+fn type_tag(ty: &ObjectType) -> &'static str {
+    match ty {
+        ObjectType::Blob => "blob",
+        ObjectType::Tree => "tree",
+        ObjectType::Commit => "commit",
+        ObjectType::Tag => "tag",
+    }
+}
+
+pub fn object_key(ty: &ObjectType, hash: Hash) -> ObjectKey {
+    (tag_type(type_tag(ty)).unwrap_or_default(), hash)
+}
+
+fn key_type(tag: u8) -> ObjectType {
+    match tag {
+        0 => ObjectType::Blob,
+        1 => ObjectType::Tree,
+        2 => ObjectType::Commit,
+        _ => ObjectType::Tag,
+    }
+}
+
+fn tag_type(tag: &str) -> Option<u8> {
+    match tag {
+        "blob" => Some(0),
+        "tree" => Some(1),
+        "commit" => Some(2),
+        "tag" => Some(3),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_shared_objects_are_split_between_owners() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = GlobalStore::new(temp.path())?;
+
+        let shared = store
+            .objects()
+            .write_object(&ObjectType::Blob, b"shared content")?;
+        let only_a = store
+            .objects()
+            .write_object(&ObjectType::Blob, b"only in a")?;
+        let shared_size = store
+            .objects()
+            .object_disk_size(&ObjectType::Blob, &shared)
+            .unwrap();
+        let only_a_size = store
+            .objects()
+            .object_disk_size(&ObjectType::Blob, &only_a)
+            .unwrap();
+
+        store.record_owner(
+            "a",
+            &[(ObjectType::Blob, shared), (ObjectType::Blob, only_a)],
+        )?;
+        store.record_owner("b", &[(ObjectType::Blob, shared)])?;
+        // Re-recording must not double count
+        store.record_owner("b", &[(ObjectType::Blob, shared)])?;
+
+        let usage = store.usage()?;
+        assert_eq!(usage.physical_bytes, shared_size + only_a_size);
+        assert_eq!(usage.logical_bytes, 2 * shared_size + only_a_size);
+        assert_eq!(usage.saved_bytes(), shared_size);
+
+        let a = usage.repos.iter().find(|r| r.repo == "a").unwrap();
+        let b = usage.repos.iter().find(|r| r.repo == "b").unwrap();
+        assert_eq!(a.objects, 2);
+        assert_eq!(b.objects, 1);
+        assert_eq!(a.exclusive_bytes, only_a_size);
+        assert_eq!(b.exclusive_bytes, 0);
+        assert_eq!(b.attributed_bytes, shared_size.div_ceil(2));
+        assert_eq!(a.attributed_bytes, only_a_size + shared_size.div_ceil(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_usage_empty_store() -> Result<()> {
+        let temp = TempDir::new()?;
+        let store = GlobalStore::new(temp.path())?;
+        let usage = store.usage()?;
+        assert_eq!(usage.physical_bytes, 0);
+        assert!(usage.repos.is_empty());
+        Ok(())
+    }
+}
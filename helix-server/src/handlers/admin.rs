@@ -0,0 +1,171 @@
+use crate::app_state::{AppState, RepoLayout};
+use crate::gc::collect_garbage;
+use crate::metrics::repo_storage_sizes;
+/// Administrative endpoints that are not part of the client RPC protocol
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header::WWW_AUTHENTICATE;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::sync::Arc;
+
+/// Let through only requests that carry `[auth] admin_token` as a bearer token.
+pub async fn require_admin(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.auth.admin_enabled() {
+        return (
+            StatusCode::FORBIDDEN,
+            "Admin endpoints are disabled; set [auth] admin_token to use them",
+        )
+            .into_response();
+    }
+    if !state.auth.is_admin(request.headers()) {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(WWW_AUTHENTICATE, "Bearer")],
+            "Missing or wrong admin token",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Report per-repo storage usage for the global object store as JSON.
+pub async fn usage_handler(State(state): State<Arc<AppState>>) -> Response {
+    let Some(global) = &state.global else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Global object store is not enabled (set HELIX_GLOBAL_STORE)",
+        )
+            .into_response();
+    };
+
+    match global.usage() {
+        Ok(usage) => Json(serde_json::json!({
+            "physical_bytes": usage.physical_bytes,
+            "logical_bytes": usage.logical_bytes,
+            "saved_bytes": usage.saved_bytes(),
+            "repos": usage.repos,
+        }))
+        .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to compute usage: {e}"),
+        )
+            .into_response(),
+    }
+}
+
+/// Delete shared objects that no hosted repo reaches any more and report what was freed.
+pub async fn gc_handler(State(state): State<Arc<AppState>>) -> Response {
+    if state.global.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            "Global object store is not enabled (set HELIX_GLOBAL_STORE)",
+        )
+            .into_response();
+    }
+
+    // Walks every repo's history and the whole store, so keep it off the async workers
+    let report = {
+        let state = state.clone();
+        tokio::task::spawn_blocking(move || collect_garbage(&state)).await
+    };
+    match report {
+        Ok(Ok(report)) => Json(report).into_response(),
+        Ok(Err(e)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Garbage collection failed: {e:#}"),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Garbage collection failed: {e}"),
+        )
+            .into_response(),
+    }
+}
+
+/// Liveness probe: the server is up and can reach its storage.
+pub async fn health_handler(State(state): State<Arc<AppState>>) -> Response {
+    let root = match &state.layout {
+        RepoLayout::Single(root) | RepoLayout::Multi(root) => root,
+        RepoLayout::Bucket(bucket) => {
+            return match bucket.check() {
+                Ok(()) => (StatusCode::OK, "ok\n").into_response(),
+                Err(e) => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("bucket is unreachable: {e:#}\n"),
+                )
+                    .into_response(),
+            };
+        }
+    };
+    if root.exists() {
+        (StatusCode::OK, "ok\n").into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("storage root {} is missing\n", root.display()),
+        )
+            .into_response()
+    }
+}
+
+/// Prometheus metrics in the text exposition format.
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> Response {
+    // Sizing repos walks their object directories, so keep it off the async workers
+    let sizes = {
+        let state = state.clone();
+        tokio::task::spawn_blocking(move || repo_storage_sizes(&state)).await
+    };
+    let sizes = match sizes {
+        Ok(Ok(sizes)) => sizes,
+        Ok(Err(e)) => {
+            tracing::warn!("failed to measure repo storage: {e:#}");
+            Vec::new()
+        }
+        Err(e) => {
+            tracing::warn!("failed to measure repo storage: {e}");
+            Vec::new()
+        }
+    };
+
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        state.metrics.render(&sizes),
+    )
+        .into_response()
+}
+
+/// Count every routed request by route and status, and track in-flight RPC sessions.
+pub async fn track_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+
+    let response = if route.starts_with("/rpc/") {
+        let _session = state.metrics.session();
+        next.run(request).await
+    } else {
+        next.run(request).await
+    };
+
+    state
+        .metrics
+        .record_request(&route, response.status().as_u16());
+    response
+}
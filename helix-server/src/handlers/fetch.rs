@@ -0,0 +1,102 @@
+use crate::app_state::AppState;
+/// Serves individual objects by hash so clients can repair corrupt or missing local objects
+/// Request:  Hello, FetchObject+, FetchDone
+/// Response: PullObject for every object the server has, then PullDone
+use crate::handlers::utils::{
+    handle_handshake, open_repo, request_reader, respond_err, respond_read_err, respond_rpc_err,
+};
+use axum::{extract::State, response::IntoResponse};
+use helix_protocol::message::{ErrorCode, FetchObject, PullObject, RpcMessage};
+use std::sync::Arc;
+use tracing::field::Empty;
+
+#[tracing::instrument(name = "fetch", skip_all, fields(repo = Empty))]
+pub async fn fetch_handler(
+    State(state): State<Arc<AppState>>,
+    body: axum::body::Body,
+) -> impl IntoResponse {
+    let mut reader = request_reader(&state, body);
+
+    let (first, mut session) = match handle_handshake(
+        &mut reader,
+        |m| match m {
+            RpcMessage::FetchObject(req) => Some(req),
+            _ => None,
+        },
+        "FetchObject",
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
+        Err(response) => return response,
+    };
+
+    tracing::Span::current().record("repo", first.repo.as_str());
+
+    let repo = match open_repo(&state, &first.repo) {
+        Ok(repo) => repo,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    let mut requests: Vec<FetchObject> = vec![first];
+    loop {
+        match reader.read_message().await {
+            Ok(RpcMessage::FetchObject(req)) => requests.push(req),
+            Ok(RpcMessage::FetchDone) => break,
+            Ok(other) => {
+                return respond_err(
+                    ErrorCode::BadRequest,
+                    format!("Expected FetchObject or FetchDone, got {other:?}"),
+                )
+            }
+            Err(e) => return respond_read_err(e, "FetchObject"),
+        }
+    }
+
+    let requested = requests.len();
+    let (mut sent_objects, mut sent_bytes) = (0usize, 0u64);
+    for req in requests {
+        // Objects the server doesn't have (or can't read) are simply not returned
+        let Ok(data) = repo
+            .objects
+            .read_object_compressed(&req.object_type, &req.hash)
+        else {
+            continue;
+        };
+
+        sent_objects += 1;
+        sent_bytes += data.len() as u64;
+        let msg = RpcMessage::PullObject(PullObject {
+            object_type: req.object_type,
+            hash: req.hash,
+            data,
+        });
+        if let Err(e) = session.write(&msg) {
+            return respond_err(
+                ErrorCode::Internal,
+                format!("Failed to encode PullObject: {e}"),
+            );
+        }
+    }
+
+    state.metrics.add_bytes_sent(sent_bytes);
+    tracing::info!(
+        requested,
+        objects = sent_objects,
+        bytes = sent_bytes,
+        "objects sent"
+    );
+
+    if let Err(e) = session.write(&RpcMessage::PullDone) {
+        return respond_err(
+            ErrorCode::Internal,
+            format!("Failed to encode PullDone: {e}"),
+        );
+    }
+
+    axum::response::Response::builder()
+        .status(200)
+        .header("Content-Type", "application/octet-stream")
+        .body(axum::body::Body::from(session.out))
+        .unwrap()
+}
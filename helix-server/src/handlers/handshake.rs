@@ -1,70 +1,85 @@
+use crate::app_state::AppState;
 /// Handles the handshake between the client and the server
-/// we don't directly acknowledge the Hello request from the client
-/// by sending back a Push/Pull Response, we're acknowledging that everything is fine using only one request
-use crate::handlers::utils::respond_err;
+/// clients on protocol v2+ get a HelloAck with our version and features, followed by
+/// the Push/Pull Response; v1 clients only get the Push/Pull Response
+use crate::handlers::utils::{
+    is_too_large, negotiate, open_repo, request_reader, respond_err, respond_read_err,
+    respond_rpc_err,
+};
 use axum::extract::State;
 use axum::response::{IntoResponse, Response};
-use helix_protocol::message::{
-    read_message, write_message, PullRequest, PullResponse, PushResponse, RpcMessage,
-};
-use helix_server::app_state::AppState;
-use std::io::Cursor;
+use helix_protocol::message::{ErrorCode, PullRequest, PullResponse, PushResponse, RpcMessage};
 use std::sync::Arc;
 
+#[tracing::instrument(name = "handshake", skip_all)]
 pub async fn handshake_handler(
     State(state): State<Arc<AppState>>,
-    body: axum::body::Bytes,
+    body: axum::body::Body,
 ) -> Result<impl IntoResponse, Response> {
-    let mut cursor = Cursor::new(body.to_vec());
+    let mut reader = request_reader(&state, body);
 
     // read hello
-    match read_message(&mut cursor) {
-        Ok(RpcMessage::Hello(_)) => {} // handle version matches and stuff here
-        _ => return Err(respond_err(400, "Missing Hello".into())),
+    let mut session = match reader.read_message().await {
+        Ok(RpcMessage::Hello(hello)) => negotiate(&hello).map_err(respond_rpc_err)?,
+        Err(e) if is_too_large(&e) => return Err(respond_read_err(e, "Hello")),
+        _ => return Err(respond_err(ErrorCode::BadRequest, "Missing Hello".into())),
     };
 
     // read the next message
-    let msg = read_message(&mut cursor)
-        .map_err(|e| respond_err(400, format!("Failed to read next message after Hello: {e}")))?;
+    let msg = reader
+        .read_message()
+        .await
+        .map_err(|e| respond_read_err(e, "next message after Hello"))?;
 
     match msg {
         RpcMessage::PushRequest(req) => {
-            let mut out = Vec::new();
+            let repo = open_repo(&state, &req.repo).map_err(respond_rpc_err)?;
 
-            let remote_head = state
+            let remote_head = repo
                 .refs
                 .get_ref(&req.ref_name)
-                .map_err(|e| respond_err(500, format!("get_ref failed: {e}")))?;
+                .map_err(|e| respond_err(ErrorCode::Internal, format!("get_ref failed: {e}")))?;
 
             let reply = RpcMessage::PushResponse(PushResponse { remote_head });
 
-            write_message(&mut out, &reply)
-                .map_err(|e| respond_err(500, format!("Failed to write PushResponse: {e}")))?;
+            session.write(&reply).map_err(|e| {
+                respond_err(
+                    ErrorCode::Internal,
+                    format!("Failed to write PushResponse: {e}"),
+                )
+            })?;
 
-            Ok(out)
+            Ok(session.out)
         }
         RpcMessage::PullRequest(PullRequest {
+            repo,
             ref_name,
             last_known_remote: _,
-            ..
         }) => {
-            let mut out = Vec::new();
+            let repo = open_repo(&state, &repo).map_err(respond_rpc_err)?;
 
             // For now we just return the current remote head.
             // Later you can use last_known_remote to decide if the client is already up-to-date,
             // or to send "need these objects" hints.
-            let remote_head = state
+            let remote_head = repo
                 .refs
                 .get_ref(&ref_name)
-                .map_err(|e| respond_err(500, format!("get_ref failed: {e}")))?;
+                .map_err(|e| respond_err(ErrorCode::Internal, format!("get_ref failed: {e}")))?;
 
             let reply = RpcMessage::PullResponse(PullResponse { remote_head });
 
-            write_message(&mut out, &reply)
-                .map_err(|e| respond_err(500, format!("Failed to write PullResponse: {e}")))?;
+            session.write(&reply).map_err(|e| {
+                respond_err(
+                    ErrorCode::Internal,
+                    format!("Failed to write PullResponse: {e}"),
+                )
+            })?;
 
-            Ok(out)
+            Ok(session.out)
         }
-        other => Err(respond_err(400, format!("Unexpected message: {:?}", other))),
+        other => Err(respond_err(
+            ErrorCode::BadRequest,
+            format!("Unexpected message: {:?}", other),
+        )),
     }
 }
@@ -0,0 +1,74 @@
+use crate::app_state::AppState;
+/// Request safeguards applied before any handler runs: the per-IP rate limit and the
+/// request body cap. Both answer with typed RpcErrors so clients can tell them apart.
+use crate::handlers::utils::respond_err;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{CONTENT_LENGTH, RETRY_AFTER};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use helix_protocol::message::ErrorCode;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Routes that must keep answering while a client is being throttled
+const UNLIMITED_ROUTES: &[&str] = &["/healthz", "/metrics"];
+
+pub async fn enforce_limits(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !UNLIMITED_ROUTES.contains(&request.uri().path()) {
+        if let Some(ip) = client_ip(&request, state.limits.trust_forwarded_for) {
+            if let Err(wait) = state.rate_limiter.check(ip) {
+                let secs = wait.as_secs().max(1);
+                tracing::warn!(%ip, "rate limited");
+                let mut response = respond_err(
+                    ErrorCode::RateLimited,
+                    format!("Too many requests from {ip}; retry in {secs}s"),
+                );
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(secs));
+                return response;
+            }
+        }
+    }
+
+    // Bodies without a Content-Length are cut off by DefaultBodyLimit instead
+    let declared = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(len) = declared.filter(|len| *len > state.limits.max_body_bytes as u64) {
+        return respond_err(
+            ErrorCode::PayloadTooLarge,
+            format!(
+                "Request body is {len} bytes; this server accepts at most {}",
+                state.limits.max_body_bytes
+            ),
+        );
+    }
+
+    next.run(request).await
+}
+
+fn client_ip(request: &Request, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded = request
+            .headers()
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
@@ -1,4 +1,8 @@
+pub mod admin;
+pub mod fetch;
 pub mod handshake;
+pub mod limits;
 pub mod pull;
 pub mod push;
+pub mod refs;
 mod utils;
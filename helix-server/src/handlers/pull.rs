@@ -1,34 +1,64 @@
-use crate::handlers::utils::{handle_handshake, respond_err};
+use crate::app_state::AppState;
+use crate::handlers::utils::{
+    handle_handshake, open_repo, request_reader, respond_err, respond_read_err, respond_rpc_err,
+};
 use axum::{extract::State, response::IntoResponse};
 use helix_protocol::commit::{collect_objects_from_commits, walk_commits_between};
-use helix_protocol::message::{write_message, PullAck, PullObject, RpcMessage};
-use helix_server::app_state::AppState;
-use std::io::Cursor;
+use helix_protocol::filter::{collect_filtered_objects, PathFilter};
+use helix_protocol::message::{ErrorCode, PullAck, PullObject, RpcMessage, WireError};
 use std::sync::Arc;
+use tracing::field::Empty;
 
+#[tracing::instrument(name = "pull", skip_all, fields(repo = Empty, ref_name = Empty))]
 pub async fn pull_handler(
     State(state): State<Arc<AppState>>,
-    body: axum::body::Bytes,
+    body: axum::body::Body,
 ) -> impl IntoResponse {
-    let mut cursor = Cursor::new(body.to_vec());
-    let mut buf = Vec::<u8>::new();
+    let mut reader = request_reader(&state, body);
 
-    let pull_req = match handle_handshake(
-        &mut cursor,
+    let (pull_req, mut session) = match handle_handshake(
+        &mut reader,
         |m| match m {
             RpcMessage::PullRequest(req) => Some(req),
             _ => None,
         },
         "PullRequest",
-    ) {
-        Ok(req) => req,
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
         Err(response) => return response,
     };
 
+    let span = tracing::Span::current();
+    span.record("repo", pull_req.repo.as_str());
+    span.record("ref_name", pull_req.ref_name.as_str());
+
+    // A partial clone follows the request with the paths it wants blobs for
+    let filter = match reader.read_message().await {
+        Ok(RpcMessage::PullFilter(filter)) => match PathFilter::new(filter.paths) {
+            Ok(filter) => Some(filter),
+            Err(e) => return respond_err(ErrorCode::BadRequest, format!("Bad filter: {e}")),
+        },
+        Err(WireError::Eof) => None,
+        Ok(other) => {
+            return respond_err(
+                ErrorCode::BadRequest,
+                format!("Expected PullFilter or nothing after PullRequest, got {other:?}"),
+            )
+        }
+        Err(e) => return respond_read_err(e, "PullFilter"),
+    };
+
+    let repo = match open_repo(&state, &pull_req.repo) {
+        Ok(repo) => repo,
+        Err(err) => return respond_rpc_err(err),
+    };
+
     let ref_name = &pull_req.ref_name;
 
     // 1. Get remote head
-    let remote_head = match state.refs.get_ref(ref_name) {
+    let remote_head = match repo.refs.get_ref(ref_name) {
         Ok(Some(v)) => v,
         Ok(None) => {
             // Ref doesn't exist - return PullAck with ref_not_found flag
@@ -38,17 +68,20 @@ pub async fn pull_handler(
                 up_to_date: false,
                 ref_not_found: true,
             });
-            if let Err(e) = write_message(&mut buf, &ack) {
-                return respond_err(500, format!("Failed to encode PullAck: {e}"));
+            if let Err(e) = session.write(&ack) {
+                return respond_err(
+                    ErrorCode::Internal,
+                    format!("Failed to encode PullAck: {e}"),
+                );
             }
             return axum::response::Response::builder()
                 .status(200)
                 .header("Content-Type", "application/octet-stream")
-                .body(axum::body::Body::from(buf))
+                .body(axum::body::Body::from(session.out))
                 .unwrap();
         }
         Err(e) => {
-            return respond_err(500, format!("Failed to read ref: {e}"));
+            return respond_err(ErrorCode::Internal, format!("Failed to read ref: {e}"));
         }
     };
 
@@ -60,48 +93,76 @@ pub async fn pull_handler(
             up_to_date: true,
             ref_not_found: false,
         });
-        if let Err(e) = write_message(&mut buf, &ack) {
-            return respond_err(500, format!("Failed to encode PullAck: {e}"));
+        if let Err(e) = session.write(&ack) {
+            return respond_err(
+                ErrorCode::Internal,
+                format!("Failed to encode PullAck: {e}"),
+            );
         }
         return axum::response::Response::builder()
             .status(200)
             .header("Content-Type", "application/octet-stream")
-            .body(axum::body::Body::from(buf))
+            .body(axum::body::Body::from(session.out))
             .unwrap();
     }
 
     // 3. Walk commit graph to find missing commits
-    let missing_commits =
-        match walk_commits_between(&state.objects, remote_head, pull_req.last_known_remote) {
-            Ok(commits) => commits,
-            Err(e) => {
-                return respond_err(500, format!("Failed to walk commits: {e}"));
-            }
-        };
+    let missing_commits = match walk_commits_between(
+        repo.objects.as_ref(),
+        remote_head,
+        pull_req.last_known_remote,
+    ) {
+        Ok(commits) => commits,
+        Err(e) => {
+            return respond_err(ErrorCode::Internal, format!("Failed to walk commits: {e}"));
+        }
+    };
 
     // 4. Collect all objects (commits + trees + blobs)
-    let objects_to_send = match collect_objects_from_commits(&state.objects, &missing_commits) {
+    let collected = match &filter {
+        Some(filter) => collect_filtered_objects(repo.objects.as_ref(), &missing_commits, filter),
+        None => collect_objects_from_commits(repo.objects.as_ref(), &missing_commits),
+    };
+    let objects_to_send = match collected {
         Ok(objects) => objects,
         Err(e) => {
-            return respond_err(500, format!("Failed to collect objects: {e}"));
+            return respond_err(
+                ErrorCode::Internal,
+                format!("Failed to collect objects: {e}"),
+            );
         }
     };
 
     // 5. Stream objects
+    let mut sent_bytes = 0u64;
     for (ty, hash, data) in &objects_to_send {
+        sent_bytes += data.len() as u64;
         let msg = RpcMessage::PullObject(PullObject {
             object_type: ty.clone(),
             hash: *hash,
             data: data.clone(),
         });
-        if let Err(e) = write_message(&mut buf, &msg) {
-            return respond_err(500, format!("Failed to encode PullObject: {e}"));
+        if let Err(e) = session.write(&msg) {
+            return respond_err(
+                ErrorCode::Internal,
+                format!("Failed to encode PullObject: {e}"),
+            );
         }
     }
 
+    state.metrics.add_bytes_sent(sent_bytes);
+    tracing::info!(
+        objects = objects_to_send.len(),
+        bytes = sent_bytes,
+        "objects sent"
+    );
+
     // 6. Send PullDone
-    if let Err(e) = write_message(&mut buf, &RpcMessage::PullDone) {
-        return respond_err(500, format!("Failed to encode PullDone: {e}"));
+    if let Err(e) = session.write(&RpcMessage::PullDone) {
+        return respond_err(
+            ErrorCode::Internal,
+            format!("Failed to encode PullDone: {e}"),
+        );
     }
 
     // 7. Send PullAck
@@ -111,13 +172,16 @@ pub async fn pull_handler(
         up_to_date: false,
         ref_not_found: false,
     });
-    if let Err(e) = write_message(&mut buf, &ack) {
-        return respond_err(500, format!("Failed to encode PullAck: {e}"));
+    if let Err(e) = session.write(&ack) {
+        return respond_err(
+            ErrorCode::Internal,
+            format!("Failed to encode PullAck: {e}"),
+        );
     }
 
     axum::response::Response::builder()
         .status(200)
         .header("Content-Type", "application/octet-stream")
-        .body(axum::body::Body::from(buf))
+        .body(axum::body::Body::from(session.out))
         .unwrap()
 }
@@ -1,80 +1,648 @@
-use crate::handlers::utils::{handle_handshake, respond_err};
-use axum::{extract::State, response::IntoResponse};
-use helix_protocol::message::{read_message, write_message, PushAck, PushObject, RpcMessage};
-use helix_server::app_state::AppState;
-use std::io::Cursor;
+use crate::app_state::{AppState, RepoStores};
+use crate::handlers::utils::{
+    handle_handshake, open_repo, read_err, request_reader, respond_err, respond_rpc_err,
+    RequestReader, Session,
+};
+use crate::hooks::RefUpdate;
+use crate::metrics::repo_storage_size;
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use helix_protocol::commit::is_ancestor;
+use helix_protocol::hash::{Hash, ZERO_HASH};
+use helix_protocol::message::{
+    ErrorCode, ObjectType, PushAck, PushDelta, PushObject, PushRef, PushRefsAck, PushRefsRequest,
+    PushRequest, RefResult, RefStatus, RpcError, RpcMessage,
+};
 use std::sync::Arc;
+use tracing::field::Empty;
 
+/// What the client sent on /rpc/push: one ref (PushRequest) or several (PushRefs)
+enum PushKind {
+    Single(PushRequest),
+    Refs(PushRefsRequest),
+}
+
+#[tracing::instrument(name = "push", skip_all, fields(repo = Empty, ref_name = Empty))]
 pub async fn push_handler(
     State(state): State<Arc<AppState>>,
-    body: axum::body::Bytes,
+    headers: HeaderMap,
+    body: axum::body::Body,
 ) -> impl IntoResponse {
-    let mut cursor = Cursor::new(body.to_vec());
+    let mut reader = request_reader(&state, body);
 
-    let push_req = match handle_handshake(
-        &mut cursor,
+    let (push, session) = match handle_handshake(
+        &mut reader,
+        |m| match m {
+            RpcMessage::PushRequest(req) => Some(PushKind::Single(req)),
+            RpcMessage::PushRefs(req) => Some(PushKind::Refs(req)),
+            _ => None,
+        },
+        "PushRequest or PushRefs",
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
+        Err(response) => return response,
+    };
+
+    let repo_name = match &push {
+        PushKind::Single(req) => &req.repo,
+        PushKind::Refs(req) => &req.repo,
+    };
+    let span = tracing::Span::current();
+    span.record("repo", repo_name.as_str());
+    if let PushKind::Single(req) = &push {
+        span.record("ref_name", req.ref_name.as_str());
+    }
+
+    let repo = match open_repo(&state, repo_name) {
+        Ok(repo) => repo,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    let (received_objects, received_bytes) = match receive_objects(&state, &repo, &mut reader).await
+    {
+        Ok(received) => received,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    let pusher = state.auth.pusher(&headers);
+
+    // The checks walk history and the ref lock blocks, so neither runs on an async worker
+    let work = tokio::task::spawn_blocking(move || {
+        span.in_scope(|| match push {
+            PushKind::Single(req) => push_single(
+                &state,
+                &repo,
+                session,
+                req,
+                received_objects,
+                received_bytes,
+                pusher,
+            ),
+            PushKind::Refs(req) => push_refs(&state, &repo, session, req, received_objects, pusher),
+        })
+    });
+    work.await
+        .unwrap_or_else(|e| respond_err(ErrorCode::Internal, format!("Push failed: {e}")))
+}
+
+fn push_single(
+    state: &AppState,
+    repo: &RepoStores,
+    session: Session,
+    push_req: PushRequest,
+    received_objects: u64,
+    received_bytes: u64,
+    pusher: Option<String>,
+) -> Response {
+    let current = match check_update(
+        state,
+        repo,
+        &push_req.ref_name,
+        push_req.old_target,
+        push_req.new_target,
+        pusher.as_deref(),
+    ) {
+        Ok(current) => current,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    // A retry of a push whose ack was lost finds the ref already moved (or already
+    // deleted); succeed without logging or firing the hooks a second time
+    if is_up_to_date(current, push_req.new_target) {
+        tracing::info!(objects = received_objects, "ref already up to date");
+        return respond_ack(session, received_objects);
+    }
+
+    // Update ref to point to latest target; the swap fails if another push moved it since
+    // the checks read it
+    let lock = state.ref_lock(&repo.name);
+    let _refs = lock.lock().unwrap_or_else(|e| e.into_inner());
+    match apply_update(repo, &push_req.ref_name, current, push_req.new_target) {
+        Ok(true) => {}
+        Ok(false) => return respond_err(ErrorCode::Conflict, moved_meanwhile(&push_req.ref_name)),
+        Err(e) => return respond_err(ErrorCode::Internal, format!("Failed to update ref: {e}")),
+    }
+
+    record_update(
+        state,
+        repo,
+        &push_req.ref_name,
+        current,
+        push_req.new_target,
+        pusher,
+    );
+
+    tracing::info!(
+        objects = received_objects,
+        bytes = received_bytes,
+        new = %hex::encode(push_req.new_target),
+        "ref updated"
+    );
+
+    respond_ack(session, received_objects)
+}
+
+/// Check that `ref_name` may move to `new_target` (ZERO_HASH to delete it) and return its
+/// current value. Runs without the repo's ref lock, so the caller writes the ref with
+/// `apply_update`, which fails if it has moved since.
+fn check_update(
+    state: &AppState,
+    repo: &RepoStores,
+    ref_name: &str,
+    old_target: Hash,
+    new_target: Hash,
+    pusher: Option<&str>,
+) -> Result<Option<Hash>, RpcError> {
+    if new_target == ZERO_HASH {
+        return check_delete(state, repo, ref_name, old_target);
+    }
+    let current = check_move(state, repo, ref_name, old_target, new_target)?;
+    // Last, as it reads every commit the push adds
+    if !is_up_to_date(current, new_target) {
+        state.access.check_push(
+            repo.objects.as_ref(),
+            repo.refs.as_ref(),
+            current,
+            new_target,
+            pusher,
+        )?;
+    }
+    Ok(current)
+}
+
+/// Moving (or creating) a ref: the target must be here, protected refs only fast-forward,
+/// and nothing the client hasn't seen may be dropped
+fn check_move(
+    state: &AppState,
+    repo: &RepoStores,
+    ref_name: &str,
+    old_target: Hash,
+    new_target: Hash,
+) -> Result<Option<Hash>, RpcError> {
+    let is_tag = ref_name.starts_with("refs/tags/");
+
+    // Objects may have arrived in earlier /rpc/upload batches, but the ref must never point
+    // at an object the server doesn't have. Tags may name an annotated tag object.
+    let present = repo.objects.has_object(&ObjectType::Commit, &new_target)
+        || (is_tag && repo.objects.has_object(&ObjectType::Tag, &new_target));
+    if !present {
+        return Err(RpcError::new(
+            ErrorCode::ObjectMissing,
+            format!("Commit {} was not uploaded", hex::encode(new_target)),
+        ));
+    }
+
+    let current = repo
+        .refs
+        .get_ref(ref_name)
+        .map_err(|e| RpcError::new(ErrorCode::Internal, format!("Failed to read ref: {e}")))?;
+    if !is_up_to_date(current, new_target) {
+        state.ref_policy.check_not_checked_out(ref_name)?;
+    }
+
+    // Protected refs only ever fast-forward, whatever old_target claims
+    if let Some(current) = current.filter(|c| *c != new_target) {
+        if state.ref_policy.is_protected(ref_name) {
+            let fast_forward = !is_tag
+                && is_ancestor(repo.objects.as_ref(), current, new_target)
+                    .map_err(|e| RpcError::new(ErrorCode::ObjectMissing, format!("{e:#}")))?;
+            if !fast_forward {
+                return Err(RpcError::new(
+                    ErrorCode::ProtectedRef,
+                    format!("{ref_name} is protected and can only be fast-forwarded"),
+                ));
+            }
+        }
+    }
+
+    // Refuse to drop commits the client hasn't seen. The client proves it has seen the
+    // current head by sending it as old_target (--force sends the head it just read).
+    let Some(current) = current.filter(|c| *c != old_target && *c != new_target) else {
+        return Ok(current);
+    };
+    if is_tag {
+        return Err(RpcError::new(
+            ErrorCode::Conflict,
+            format!(
+                "{} already exists at {}; use --force to move it",
+                ref_name,
+                hex::encode(current)
+            ),
+        ));
+    }
+    match is_ancestor(repo.objects.as_ref(), current, new_target) {
+        Ok(true) => Ok(Some(current)),
+        Ok(false) => Err(RpcError::new(
+            ErrorCode::NotFastForward,
+            format!(
+                "{} is at {}, which is not an ancestor of {}",
+                ref_name,
+                hex::encode(current),
+                hex::encode(new_target)
+            ),
+        )),
+        Err(e) => Err(RpcError::new(ErrorCode::ObjectMissing, format!("{e:#}"))),
+    }
+}
+
+/// Deleting a ref: never a protected one, and only from the value the client last saw, so a
+/// branch that gained commits in the meantime isn't lost. Deleting a ref that is already
+/// gone succeeds, so a retried delete doesn't fail.
+fn check_delete(
+    state: &AppState,
+    repo: &RepoStores,
+    ref_name: &str,
+    old_target: Hash,
+) -> Result<Option<Hash>, RpcError> {
+    if state.ref_policy.is_protected(ref_name) {
+        return Err(RpcError::new(
+            ErrorCode::ProtectedRef,
+            format!("{ref_name} is protected and can't be deleted"),
+        ));
+    }
+    state.ref_policy.check_not_checked_out(ref_name)?;
+
+    let current = repo
+        .refs
+        .get_ref(ref_name)
+        .map_err(|e| RpcError::new(ErrorCode::Internal, format!("Failed to read ref: {e}")))?;
+    match current {
+        Some(current) if current != old_target => Err(RpcError::new(
+            ErrorCode::Conflict,
+            format!(
+                "{} is at {}, not {}; fetch it first or use --force",
+                ref_name,
+                hex::encode(current),
+                hex::encode(old_target)
+            ),
+        )),
+        current => Ok(current),
+    }
+}
+
+fn is_up_to_date(current: Option<Hash>, new_target: Hash) -> bool {
+    current.unwrap_or(ZERO_HASH) == new_target
+}
+
+/// Write a checked update: point the ref at `new_target`, or delete it for ZERO_HASH. Only
+/// happens if the ref is still at `current`, as another server sharing the storage may have
+/// moved it since it was checked; returns false when it has.
+fn apply_update(
+    repo: &RepoStores,
+    ref_name: &str,
+    current: Option<Hash>,
+    new_target: Hash,
+) -> anyhow::Result<bool> {
+    repo.refs.compare_and_swap_ref(
+        ref_name,
+        current,
+        (new_target != ZERO_HASH).then_some(new_target),
+    )
+}
+
+fn moved_meanwhile(ref_name: &str) -> String {
+    format!(
+        "{ref_name} was changed by another push while this one was checked; fetch and try again"
+    )
+}
+
+/// Log an applied update in the repo's reflog and notify the hooks
+fn record_update(
+    state: &AppState,
+    repo: &RepoStores,
+    ref_name: &str,
+    old: Option<Hash>,
+    new_target: Hash,
+    pusher: Option<String>,
+) {
+    let deleted = new_target == ZERO_HASH;
+    if let Err(e) = repo.refs.append_reflog(
+        ref_name,
+        old,
+        (!deleted).then_some(new_target),
+        pusher.as_deref().unwrap_or("unknown"),
+        if deleted { "push: delete" } else { "push" },
+    ) {
+        tracing::warn!(ref_name, "failed to write reflog: {e}");
+    }
+
+    state.hooks.fire(RefUpdate::new(
+        &repo.name, ref_name, old, new_target, pusher,
+    ));
+    if let Some(replication) = &state.replication {
+        replication.enqueue(&repo.name, ref_name);
+    }
+}
+
+/// Apply every update in a PushRefs request, or none of them
+fn push_refs(
+    state: &AppState,
+    repo: &RepoStores,
+    session: Session,
+    req: PushRefsRequest,
+    received_objects: u64,
+    pusher: Option<String>,
+) -> Response {
+    let checks: Vec<Result<Option<Hash>, RpcError>> = req
+        .updates
+        .iter()
+        .map(|u| {
+            check_update(
+                state,
+                repo,
+                &u.ref_name,
+                u.old_target,
+                u.new_target,
+                pusher.as_deref(),
+            )
+        })
+        .collect();
+
+    let rejected = checks.iter().filter(|check| check.is_err()).count();
+    let lock = state.ref_lock(&repo.name);
+    let _refs = lock.lock().unwrap_or_else(|e| e.into_inner());
+    let mut results = Vec::with_capacity(req.updates.len());
+    let mut applied: Vec<(&PushRef, Option<Hash>)> = Vec::new();
+
+    for (update, check) in req.updates.iter().zip(checks) {
+        let status = match check {
+            Err(err) => RefStatus::Rejected {
+                kind: err.kind,
+                message: err.message,
+            },
+            Ok(current) if is_up_to_date(current, update.new_target) => RefStatus::UpToDate,
+            Ok(_) if rejected > 0 => RefStatus::Skipped,
+            Ok(current) => {
+                match apply_update(repo, &update.ref_name, current, update.new_target) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        rollback(repo, &applied);
+                        return respond_err(ErrorCode::Conflict, moved_meanwhile(&update.ref_name));
+                    }
+                    Err(e) => {
+                        rollback(repo, &applied);
+                        return respond_err(
+                            ErrorCode::Internal,
+                            format!("Failed to update {}: {e}", update.ref_name),
+                        );
+                    }
+                }
+                applied.push((update, current));
+                RefStatus::Updated
+            }
+        };
+        results.push(RefResult {
+            ref_name: update.ref_name.clone(),
+            status,
+        });
+    }
+
+    for (update, old) in &applied {
+        record_update(
+            state,
+            repo,
+            &update.ref_name,
+            *old,
+            update.new_target,
+            pusher.clone(),
+        );
+    }
+    tracing::info!(
+        objects = received_objects,
+        updated = applied.len(),
+        rejected,
+        "refs pushed"
+    );
+
+    respond_message(
+        session,
+        RpcMessage::PushRefsAck(PushRefsAck {
+            received_objects,
+            results,
+        }),
+    )
+}
+
+/// Put refs written by a failed multi-ref push back the way they were, unless something
+/// else has moved them since
+fn rollback(repo: &RepoStores, applied: &[(&PushRef, Option<Hash>)]) {
+    for (update, old) in applied {
+        let written = (update.new_target != ZERO_HASH).then_some(update.new_target);
+        match repo
+            .refs
+            .compare_and_swap_ref(&update.ref_name, written, *old)
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::error!(ref_name = %update.ref_name, "ref moved before it was rolled back")
+            }
+            Err(e) => {
+                tracing::error!(ref_name = %update.ref_name, "failed to roll back ref: {e}")
+            }
+        }
+    }
+}
+
+/// Stores objects for a later push without touching any ref. Uploads are keyed by hash, so
+/// a client can resend a batch after a network error and objects already stored are skipped.
+/// Request:  Hello, PushRequest (only the repo is used), PushObject*, PushDone
+/// Response: PushAck with the number of objects in the batch
+#[tracing::instrument(name = "upload", skip_all, fields(repo = Empty))]
+pub async fn upload_handler(
+    State(state): State<Arc<AppState>>,
+    body: axum::body::Body,
+) -> impl IntoResponse {
+    let mut reader = request_reader(&state, body);
+
+    let (push_req, session) = match handle_handshake(
+        &mut reader,
         |m| match m {
             RpcMessage::PushRequest(req) => Some(req),
             _ => None,
         },
         "PushRequest",
-    ) {
-        Ok(req) => req,
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
         Err(response) => return response,
     };
+    tracing::Span::current().record("repo", push_req.repo.as_str());
+
+    let repo = match open_repo(&state, &push_req.repo) {
+        Ok(repo) => repo,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    let (received_objects, received_bytes) = match receive_objects(&state, &repo, &mut reader).await
+    {
+        Ok(received) => received,
+        Err(err) => return respond_rpc_err(err),
+    };
+    tracing::info!(
+        objects = received_objects,
+        bytes = received_bytes,
+        "objects stored"
+    );
+
+    respond_ack(session, received_objects)
+}
+
+/// Read PushObject* until PushDone, writing objects the store doesn't have yet.
+/// Returns the number of objects and object bytes received.
+async fn receive_objects(
+    state: &AppState,
+    repo: &RepoStores,
+    reader: &mut RequestReader,
+) -> Result<(u64, u64), RpcError> {
+    // Measuring usage walks the repo's objects, so only do it when there's a cap to check
+    let quota = state.quotas.for_repo(&repo.name);
+    let mut used = if quota.max_repo_bytes > 0 {
+        repo_storage_size(state, repo).map_err(|e| {
+            RpcError::new(
+                ErrorCode::Internal,
+                format!("Failed to measure repo storage: {e:#}"),
+            )
+        })?
+    } else {
+        0
+    };
 
     // Receive PushObject* until PushDone
     let mut received_objects = 0u64;
+    let mut received_bytes = 0u64;
+    let mut pushed = Vec::new();
 
     loop {
-        match read_message(&mut cursor) {
+        match reader.read_message().await {
             Ok(RpcMessage::PushObject(PushObject {
                 object_type,
                 hash,
                 data,
             })) => {
-                if !state.objects.has_object(&object_type, &hash) {
-                    if let Err(e) =
-                        state
-                            .objects
-                            .write_object_compressed_with_hash(&object_type, &hash, &data)
-                    {
-                        return respond_err(
-                            400,
-                            format!(
-                                "Failed to write {:?} object {}: {e}",
-                                object_type,
-                                hex::encode(hash)
-                            ),
-                        );
-                    }
+                if !repo.objects.has_object(&object_type, &hash) {
+                    quota.check_object(hash, data.len() as u64, used)?;
+                    used += data.len() as u64;
+                    store_object(repo, &object_type, &hash, &data)?;
+                }
+
+                pushed.push((object_type, hash));
+                received_objects += 1;
+                received_bytes += data.len() as u64;
+            }
+
+            Ok(RpcMessage::PushDelta(delta)) => {
+                if !repo.objects.has_object(&delta.object_type, &delta.hash) {
+                    let data = resolve_delta(state, repo, &delta)?;
+                    quota.check_object(delta.hash, data.len() as u64, used)?;
+                    used += data.len() as u64;
+                    store_object(repo, &delta.object_type, &delta.hash, &data)?;
                 }
 
+                pushed.push((delta.object_type, delta.hash));
                 received_objects += 1;
+                received_bytes += delta.delta.len() as u64;
             }
 
             Ok(RpcMessage::PushDone) => break,
             Ok(other) => {
-                return respond_err(400, format!("Unexpected message during push: {:?}", other));
+                return Err(RpcError::new(
+                    ErrorCode::BadRequest,
+                    format!("Unexpected message during push: {:?}", other),
+                ));
             }
-            Err(e) => return respond_err(400, format!("Error reading message during push: {e}")),
+            Err(e) => return Err(read_err(e, "PushObject, PushDelta or PushDone")),
         }
     }
 
-    // Update ref to point to latest target
-    if let Err(e) = state.refs.set_ref(&push_req.ref_name, push_req.new_target) {
-        return respond_err(500, format!("Failed to update ref: {e}"));
+    state.metrics.add_bytes_received(received_bytes);
+
+    // Attribute the pushed objects to this repo for quota accounting
+    if let Some(global) = &state.global {
+        if let Err(e) = global.record_owner(&repo.name, &pushed) {
+            return Err(RpcError::new(
+                ErrorCode::Internal,
+                format!("Failed to record object owners: {e}"),
+            ));
+        }
     }
 
-    let ack = RpcMessage::PushAck(PushAck { received_objects });
-    let mut out_buf = Vec::<u8>::new();
-    if let Err(e) = write_message(&mut out_buf, &ack) {
-        return respond_err(500, format!("Failed to encode PushAck: {e}"));
+    Ok((received_objects, received_bytes))
+}
+
+/// Write an object received in its stored (compressed) form; the store checks its hash
+fn store_object(
+    repo: &RepoStores,
+    object_type: &ObjectType,
+    hash: &Hash,
+    data: &[u8],
+) -> Result<(), RpcError> {
+    repo.objects
+        .write_object_compressed_with_hash(object_type, hash, data)
+        .map_err(|e| {
+            RpcError::new(
+                ErrorCode::BadRequest,
+                format!(
+                    "Failed to write {:?} object {}: {e}",
+                    object_type,
+                    hex::encode(hash)
+                ),
+            )
+        })
+}
+
+/// Rebuild a delta-encoded object against its base in the repo's store and return it
+/// compressed, as a PushObject would have carried it. A delta can't rebuild anything larger
+/// than a request body.
+fn resolve_delta(
+    state: &AppState,
+    repo: &RepoStores,
+    delta: &PushDelta,
+) -> Result<Vec<u8>, RpcError> {
+    let base = repo
+        .objects
+        .read_object(&delta.object_type, &delta.base)
+        .map_err(|_| {
+            RpcError::new(
+                ErrorCode::ObjectMissing,
+                format!(
+                    "Delta base {} of object {} is not on the server",
+                    hex::encode(delta.base),
+                    hex::encode(delta.hash)
+                ),
+            )
+        })?;
+    let invalid = |e: anyhow::Error| {
+        RpcError::new(
+            ErrorCode::InvalidObject,
+            format!("Bad delta for object {}: {e}", hex::encode(delta.hash)),
+        )
+    };
+    let raw = helix_protocol::delta::apply(&base, &delta.delta, state.limits.max_body_bytes)
+        .map_err(invalid)?;
+    zstd::encode_all(&raw[..], 3).map_err(|e| invalid(e.into()))
+}
+
+fn respond_ack(session: Session, received_objects: u64) -> Response {
+    respond_message(session, RpcMessage::PushAck(PushAck { received_objects }))
+}
+
+fn respond_message(mut session: Session, msg: RpcMessage) -> Response {
+    if let Err(e) = session.write(&msg) {
+        return respond_err(
+            ErrorCode::Internal,
+            format!("Failed to encode response: {e}"),
+        );
     }
 
     axum::response::Response::builder()
         .status(200)
         .header(axum::http::header::CONTENT_TYPE, "application/octet-stream")
-        .body(axum::body::Body::from(out_buf))
+        .body(axum::body::Body::from(session.out))
         .unwrap()
 }
@@ -0,0 +1,62 @@
+use crate::app_state::AppState;
+/// Lists a repo's refs so clients can see what exists on the server without pulling,
+/// e.g. to prune remote-tracking refs for deleted branches, along with the ref its HEAD
+/// names so a clone knows which branch to check out
+/// Request:  Hello, ListRefs
+/// Response: RefList
+use crate::handlers::utils::{
+    handle_handshake, open_repo, request_reader, respond_err, respond_rpc_err,
+};
+use axum::{extract::State, response::IntoResponse};
+use helix_protocol::message::{ErrorCode, RefList, RpcMessage};
+use std::sync::Arc;
+use tracing::field::Empty;
+
+#[tracing::instrument(name = "list_refs", skip_all, fields(repo = Empty))]
+pub async fn list_refs_handler(
+    State(state): State<Arc<AppState>>,
+    body: axum::body::Body,
+) -> impl IntoResponse {
+    let mut reader = request_reader(&state, body);
+
+    let (req, mut session) = match handle_handshake(
+        &mut reader,
+        |m| match m {
+            RpcMessage::ListRefs(req) => Some(req),
+            _ => None,
+        },
+        "ListRefs",
+    )
+    .await
+    {
+        Ok(handshake) => handshake,
+        Err(response) => return response,
+    };
+
+    tracing::Span::current().record("repo", req.repo.as_str());
+
+    let repo = match open_repo(&state, &req.repo) {
+        Ok(repo) => repo,
+        Err(err) => return respond_rpc_err(err),
+    };
+
+    let refs = match repo.refs.list_refs(&req.prefix) {
+        Ok(refs) => refs,
+        Err(e) => return respond_err(ErrorCode::Internal, format!("Failed to list refs: {e}")),
+    };
+    let head = state.head_ref(&req.repo);
+    tracing::info!(refs = refs.len(), head = head.as_deref(), "refs listed");
+
+    if let Err(e) = session.write(&RpcMessage::RefList(RefList { refs, head })) {
+        return respond_err(
+            ErrorCode::Internal,
+            format!("Failed to encode RefList: {e}"),
+        );
+    }
+
+    axum::response::Response::builder()
+        .status(200)
+        .header("Content-Type", "application/octet-stream")
+        .body(axum::body::Body::from(session.out))
+        .unwrap()
+}
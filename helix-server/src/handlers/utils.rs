@@ -1,48 +1,256 @@
 use axum::{body::Body, response::Response};
-use helix_protocol::message::{read_message, write_message, RpcError, RpcMessage};
-use std::io::Cursor;
+use futures_util::StreamExt;
+use helix_protocol::message::{
+    write_message, write_message_with, AsyncMessageReader, ErrorCode, Features, Hello, HelloAck,
+    RpcError, RpcMessage, WireError, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use std::fmt;
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
 
-pub fn handle_handshake<T>(
-    cursor: &mut Cursor<Vec<u8>>,
+use crate::app_state::{validate_repo_name, AppState, RepoLayout, RepoStores};
+
+/// Capabilities this server advertises in HelloAck. `resume` means objects can be uploaded
+/// in batches through /rpc/upload before the ref update in /rpc/push. `deltas` means blobs
+/// may arrive as PushDelta against a blob the repo already has.
+pub const SERVER_FEATURES: Features = Features {
+    packfiles: false,
+    resume: true,
+    compression: true,
+    deltas: true,
+};
+
+/// Response body being built for one RPC, plus what was negotiated with the client
+pub struct Session {
+    pub out: Vec<u8>,
+    /// Client advertised compression, so large frames may be sent as zstd
+    pub compress: bool,
+}
+
+impl Session {
+    pub fn write(&mut self, msg: &RpcMessage) -> Result<(), WireError> {
+        write_message_with(&mut self.out, msg, self.compress)
+    }
+}
+
+/// A request body read as messages while it arrives, instead of buffered whole
+pub type RequestReader = AsyncMessageReader<Box<dyn AsyncRead + Send + Unpin>>;
+
+/// Messages from a request body, each at most max_message_bytes, and the body as a whole
+/// cut off after max_body_bytes (DefaultBodyLimit only covers buffered bodies)
+pub fn request_reader(state: &AppState, body: Body) -> RequestReader {
+    let max_body = state.limits.max_body_bytes;
+    let mut received = 0usize;
+    let stream = body.into_data_stream().map(move |chunk| {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        received += chunk.len();
+        if received > max_body {
+            return Err(std::io::Error::other(BodyTooLarge { max: max_body }));
+        }
+        Ok(chunk)
+    });
+    AsyncMessageReader::with_limit(
+        Box::new(StreamReader::new(stream)),
+        state.limits.max_message_bytes,
+    )
+}
+
+/// The request body went past max_body_bytes
+#[derive(Debug)]
+struct BodyTooLarge {
+    max: usize,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body exceeds the {} byte limit", self.max)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// Reads Hello and the expected request. Returns the request together with the response
+/// session, whose body already holds a HelloAck for clients that negotiate (protocol >= 2).
+pub async fn handle_handshake<T>(
+    reader: &mut RequestReader,
     expect: fn(RpcMessage) -> Option<T>,
     expected_name: &'static str,
-) -> Result<T, Response<Body>> {
-    match read_message(&mut *cursor) {
-        Ok(RpcMessage::Hello(_)) => {} // TODO: handle version matches and stuff here
-        _ => return Err(respond_err(400, "Missing Hello".into())),
+) -> Result<(T, Session), Response<Body>> {
+    let session = match reader.read_message().await {
+        Ok(RpcMessage::Hello(hello)) => negotiate(&hello).map_err(respond_rpc_err)?,
+        Err(e) if is_too_large(&e) => return Err(respond_read_err(e, "Hello")),
+        _ => return Err(respond_err(ErrorCode::BadRequest, "Missing Hello".into())),
     };
 
     // Expect the next message (PushRequest, PullRequest, etc.)
-    let msg = match read_message(&mut *cursor) {
+    let msg = match reader.read_message().await {
         Ok(m) => m,
-        Err(e) => {
-            return Err(respond_err(
-                400,
-                format!("Failed to read {expected_name}: {e}"),
-            ))
-        }
+        Err(e) => return Err(respond_read_err(e, expected_name)),
     };
 
     let msg_debug = format!("{:?}", msg);
 
     match expect(msg) {
-        Some(v) => Ok(v),
+        Some(v) => Ok((v, session)),
         None => Err(respond_err(
-            400,
+            ErrorCode::BadRequest,
             format!("Expected {expected_name}, got {msg_debug}"),
         )),
     }
 }
 
-pub fn respond_err(status: u16, msg: String) -> Response {
-    let err = RpcMessage::Error(RpcError {
-        code: status,
-        message: msg,
-    });
+/// Check the client's protocol version and build the HelloAck it expects.
+/// v1 clients don't know about HelloAck, so they get an empty body.
+pub fn negotiate(hello: &Hello) -> Result<Session, RpcError> {
+    if hello.protocol_version < MIN_PROTOCOL_VERSION {
+        return Err(RpcError::new(
+            ErrorCode::ProtocolMismatch,
+            format!(
+                "{} speaks Helix protocol v{}, but this server requires v{}..=v{}. Upgrade helix-cli.",
+                hello.client_version, hello.protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        ));
+    }
+
+    let mut session = Session {
+        out: Vec::new(),
+        compress: SERVER_FEATURES.compression && hello.features.compression,
+    };
+    if hello.protocol_version >= 2 {
+        let ack = RpcMessage::HelloAck(HelloAck {
+            server_version: format!("helix-server {}", env!("CARGO_PKG_VERSION")),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            features: SERVER_FEATURES,
+        });
+        // Always uncompressed so any client can read it
+        write_message(&mut session.out, &ack).map_err(|e| {
+            RpcError::new(
+                ErrorCode::Internal,
+                format!("Failed to encode HelloAck: {e}"),
+            )
+        })?;
+    }
+    Ok(session)
+}
+
+/// Whether a read failed because a message or the whole body was over its limit
+pub fn is_too_large(err: &WireError) -> bool {
+    match err {
+        WireError::TooLarge { .. } => true,
+        WireError::Io(e) => e.get_ref().is_some_and(|e| e.is::<BodyTooLarge>()),
+        _ => false,
+    }
+}
+
+/// Error for a message that couldn't be read; oversized messages get PayloadTooLarge
+pub fn read_err(err: WireError, expected_name: &str) -> RpcError {
+    let kind = if is_too_large(&err) {
+        ErrorCode::PayloadTooLarge
+    } else {
+        ErrorCode::BadRequest
+    };
+    RpcError::new(kind, format!("Failed to read {expected_name}: {err}"))
+}
+
+pub fn respond_read_err(err: WireError, expected_name: &str) -> Response {
+    respond_rpc_err(read_err(err, expected_name))
+}
+
+/// The stores of the repo a request names. RepoNotFound unless it has been created; a
+/// request never creates one.
+pub fn open_repo(state: &AppState, name: &str) -> Result<RepoStores, RpcError> {
+    if !matches!(state.layout, RepoLayout::Single(_)) {
+        validate_repo_name(name)
+            .map_err(|e| RpcError::new(ErrorCode::BadRequest, e.to_string()))?;
+    }
+    if !state.repo_exists(name) {
+        return Err(RpcError::new(
+            ErrorCode::RepoNotFound,
+            format!("Repository '{name}' does not exist on this server"),
+        ));
+    }
+    state
+        .repo(name)
+        .map_err(|e| RpcError::new(ErrorCode::Internal, e.to_string()))
+}
+
+pub fn respond_err(kind: ErrorCode, msg: String) -> Response {
+    respond_rpc_err(RpcError::new(kind, msg))
+}
+
+pub fn respond_rpc_err(err: RpcError) -> Response {
+    let status = err.code;
     let mut buf = Vec::new();
-    write_message(&mut buf, &err).unwrap();
+    write_message(&mut buf, &RpcMessage::Error(err)).unwrap();
     Response::builder()
         .status(status)
         .body(axum::body::Body::from(buf))
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::RepoLayout;
+    use crate::hooks::HooksConfig;
+    use crate::limits::LimitsConfig;
+    use helix_protocol::message::PullObject;
+
+    #[tokio::test]
+    async fn test_request_reader_caps_the_body() -> Result<(), WireError> {
+        let mut done = Vec::new();
+        write_message(&mut done, &RpcMessage::PullDone)?;
+        let mut big = Vec::new();
+        let object = RpcMessage::PullObject(PullObject {
+            object_type: helix_protocol::message::ObjectType::Blob,
+            hash: [1u8; 32],
+            data: vec![0u8; 4096],
+        });
+        write_message(&mut big, &object)?;
+        let chunks = futures_util::stream::iter([done, big].map(Ok::<_, std::io::Error>));
+
+        let state = AppState::new(
+            RepoLayout::Single("/nonexistent".into()),
+            None,
+            HooksConfig::default(),
+        )
+        .with_limits(LimitsConfig {
+            max_body_bytes: 1024,
+            ..Default::default()
+        });
+
+        // Messages under the cap are read as they arrive; the body past it is refused
+        let mut reader = request_reader(&state, Body::from_stream(chunks));
+        assert!(matches!(reader.read_message().await?, RpcMessage::PullDone));
+        let err = reader.read_message().await.unwrap_err();
+        assert!(is_too_large(&err), "{err}");
+        assert_eq!(read_err(err, "PullObject").code, 413);
+        Ok(())
+    }
+
+    #[test]
+    fn test_requests_never_create_repos() -> anyhow::Result<()> {
+        let temp = tempfile::TempDir::new()?;
+        let repos = temp.path().join("repos");
+        let state = AppState::new(
+            RepoLayout::Multi(repos.clone()),
+            None,
+            HooksConfig::default(),
+        );
+
+        let err = open_repo(&state, "ghost").map(|_| ()).unwrap_err();
+        assert_eq!(err.kind, ErrorCode::RepoNotFound);
+        assert!(state.repo("ghost").is_err());
+        assert!(!repos.join("ghost").exists());
+        let err = open_repo(&state, "../ghost").map(|_| ()).unwrap_err();
+        assert_eq!(err.kind, ErrorCode::BadRequest);
+
+        crate::repos::create_repo(&state, "ghost", "main")?;
+        assert_eq!(
+            open_repo(&state, "ghost").map(|r| r.name).ok().as_deref(),
+            Some("ghost")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+/// Post-receive notifications, fired after a push updates a ref.
+///
+/// Every update produces one JSON payload:
+///
+/// ```json
+/// {"event": "push", "repo": "app", "ref": "refs/heads/main",
+///  "old": "<hex or null>", "new": "<hex>", "pusher": "Name <email>", "timestamp": 1700000000}
+/// ```
+///
+/// A push that deletes the ref sends `"event": "delete"` with `new` all zeros.
+///
+/// It is POSTed to each configured webhook whose events match the update (`push` matches every
+/// update, `branch` only refs/heads/*, `tag` only refs/tags/*, `delete` only deletions), with
+/// the event name in
+/// X-Helix-Event and, when the webhook has a secret, `sha256=<hex HMAC-SHA256 of the body>` in
+/// X-Helix-Signature-256. If the hook directory has an executable `post-receive`, it runs with
+/// the payload on stdin and HELIX_REPO, HELIX_REF, HELIX_OLD, HELIX_NEW and HELIX_PUSHER set.
+///
+/// Hooks run in the background: a slow or failing receiver never fails the push, it is only
+/// logged.
+use helix_protocol::hash::{Hash, ZERO_HASH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HooksConfig {
+    /// Directory holding a `post-receive` script
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub secret: Option<String>,
+    #[serde(default = "default_events")]
+    pub events: Vec<String>,
+}
+
+fn default_events() -> Vec<String> {
+    vec!["push".to_string()]
+}
+
+impl WebhookConfig {
+    fn wants(&self, update: &RefUpdate) -> bool {
+        self.events.iter().any(|event| match event.as_str() {
+            "push" => true,
+            "branch" => update.ref_name.starts_with("refs/heads/"),
+            "tag" => update.ref_name.starts_with("refs/tags/"),
+            "delete" => update.event == "delete",
+            _ => false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RefUpdate {
+    pub event: &'static str,
+    pub repo: String,
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub old: Option<String>,
+    pub new: String,
+    /// Identity the client sent in X-Helix-Pusher, if any
+    pub pusher: Option<String>,
+    pub timestamp: u64,
+}
+
+impl RefUpdate {
+    /// `new` is ZERO_HASH when the push deleted the ref
+    pub fn new(
+        repo: &str,
+        ref_name: &str,
+        old: Option<Hash>,
+        new: Hash,
+        pusher: Option<String>,
+    ) -> Self {
+        Self {
+            event: if new == ZERO_HASH { "delete" } else { "push" },
+            repo: repo.to_string(),
+            ref_name: ref_name.to_string(),
+            old: old.map(hex::encode),
+            new: hex::encode(new),
+            pusher,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl HooksConfig {
+    pub fn is_empty(&self) -> bool {
+        self.dir.is_none() && self.webhooks.is_empty()
+    }
+
+    /// Notify every matching webhook and the post-receive script without waiting for them.
+    pub fn fire(&self, update: RefUpdate) {
+        if self.is_empty() {
+            return;
+        }
+        let hooks = self.clone();
+        tokio::spawn(async move { hooks.run(&update).await });
+    }
+
+    async fn run(&self, update: &RefUpdate) {
+        let body = match serde_json::to_vec(update) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("hook failed to encode payload: {e}");
+                return;
+            }
+        };
+
+        let client = reqwest::Client::new();
+        for webhook in self.webhooks.iter().filter(|w| w.wants(update)) {
+            let mut request = client
+                .post(&webhook.url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Helix-Event", update.event)
+                .body(body.clone());
+            if let Some(secret) = &webhook.secret {
+                request = request.header("X-Helix-Signature-256", signature(secret, &body));
+            }
+
+            match request.send().await {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => tracing::warn!("hook {} returned {}", webhook.url, resp.status()),
+                Err(e) => tracing::warn!("hook {} failed: {e}", webhook.url),
+            }
+        }
+
+        if let Some(dir) = &self.dir {
+            let script = dir.join("post-receive");
+            if script.is_file() {
+                if let Err(e) = run_script(&script, update, &body).await {
+                    tracing::warn!("hook {} failed: {e}", script.display());
+                }
+            }
+        }
+    }
+}
+
+async fn run_script(script: &Path, update: &RefUpdate, body: &[u8]) -> anyhow::Result<()> {
+    let mut child = tokio::process::Command::new(script)
+        .env("HELIX_REPO", &update.repo)
+        .env("HELIX_REF", &update.ref_name)
+        .env("HELIX_OLD", update.old.as_deref().unwrap_or(""))
+        .env("HELIX_NEW", &update.new)
+        .env("HELIX_PUSHER", update.pusher.as_deref().unwrap_or(""))
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body).await?;
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("exited with {status}");
+    }
+    Ok(())
+}
+
+/// `sha256=<hex>` HMAC of the body, the format GitHub-style receivers expect
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        hex::encode(hmac_sha256(secret.as_bytes(), body))
+    )
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_sha256_rfc4231() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_webhook_events_and_config() -> anyhow::Result<()> {
+        let config: crate::config::ServerConfig = toml::from_str(
+            r#"
+            [hooks]
+            dir = "/srv/hooks"
+
+            [[hooks.webhooks]]
+            url = "http://ci/all"
+
+            [[hooks.webhooks]]
+            url = "http://ci/tags"
+            secret = "s3cret"
+            events = ["tag"]
+            "#,
+        )?;
+        let hooks = config.hooks;
+        assert_eq!(hooks.dir, Some(PathBuf::from("/srv/hooks")));
+        let branch = RefUpdate::new("app", "refs/heads/main", None, [1u8; 32], None);
+        let tag = RefUpdate::new("app", "refs/tags/v1.0", None, [1u8; 32], None);
+        assert!(hooks.webhooks[0].wants(&branch));
+        assert!(!hooks.webhooks[1].wants(&branch));
+        assert!(hooks.webhooks[1].wants(&tag));
+
+        let deleted = RefUpdate::new("app", "refs/heads/old", Some([1u8; 32]), ZERO_HASH, None);
+        assert_eq!(deleted.event, "delete");
+        assert!(hooks.webhooks[0].wants(&deleted));
+        let deletes_only = WebhookConfig {
+            url: "http://ci/deletes".to_string(),
+            secret: None,
+            events: vec!["delete".to_string()],
+        };
+        assert!(deletes_only.wants(&deleted));
+        assert!(!deletes_only.wants(&branch));
+
+        let update = branch;
+        let json = serde_json::to_value(&update)?;
+        assert_eq!(json["ref"], "refs/heads/main");
+        assert_eq!(json["old"], serde_json::Value::Null);
+        assert_eq!(json["new"], hex::encode([1u8; 32]));
+
+        Ok(())
+    }
+}
@@ -1,2 +1,17 @@
+pub mod access;
 pub mod app_state;
+pub mod auth;
+pub mod config;
+pub mod gc;
+pub mod global_store;
+pub mod handlers;
+pub mod hooks;
+pub mod limits;
+pub mod local;
+pub mod metrics;
+pub mod quotas;
+pub mod ref_policy;
+pub mod replication;
+pub mod repos;
+pub mod s3;
 pub mod walk;
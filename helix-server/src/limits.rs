@@ -0,0 +1,149 @@
+/// Safeguards against oversized and excessive requests.
+///
+/// ```toml
+/// [limits]
+/// max_message_bytes = 268435456   # largest single protocol message (256 MiB)
+/// max_body_bytes = 1073741824     # largest HTTP request body (1 GiB)
+/// requests_per_minute = 600       # per client IP, 0 disables rate limiting
+/// burst = 60                      # requests allowed at once before throttling
+/// trust_forwarded_for = false     # key clients by X-Forwarded-For (behind a proxy)
+/// ```
+///
+/// Rate limiting is a token bucket per client IP: each request takes a token, and tokens
+/// refill at `requests_per_minute` up to `burst`.
+use helix_protocol::message::DEFAULT_MAX_MESSAGE_SIZE;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before idle ones are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub max_message_bytes: usize,
+    pub max_body_bytes: usize,
+    pub requests_per_minute: u32,
+    pub burst: u32,
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: DEFAULT_MAX_MESSAGE_SIZE,
+            max_body_bytes: 1024 * 1024 * 1024,
+            requests_per_minute: 600,
+            burst: 60,
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            per_second: f64::from(config.requests_per_minute) / 60.0,
+            burst: f64::from(config.burst.max(1)),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_second > 0.0
+    }
+
+    /// Take a token for `ip`, or return how long until one is available
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&ip) {
+            self.drop_idle(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+
+    /// Forget clients whose buckets have refilled; they'd start full again anyway
+    fn drop_idle(&self, buckets: &mut HashMap<IpAddr, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens + elapsed * self.per_second < self.burst
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_throttles_and_refills() {
+        let limiter = RateLimiter::new(&LimitsConfig {
+            requests_per_minute: 60,
+            burst: 2,
+            ..Default::default()
+        });
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check_at(a, start).is_ok());
+        assert!(limiter.check_at(a, start).is_ok());
+        let wait = limiter.check_at(a, start).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // Other clients have their own budget
+        assert!(limiter.check_at(b, start).is_ok());
+
+        // One token per second at 60/min
+        assert!(limiter.check_at(a, start + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check_at(a, start + Duration::from_secs(1)).is_err());
+
+        let disabled = RateLimiter::new(&LimitsConfig {
+            requests_per_minute: 0,
+            ..Default::default()
+        });
+        for _ in 0..1000 {
+            assert!(disabled.check_at(a, start).is_ok());
+        }
+    }
+}
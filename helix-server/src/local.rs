@@ -0,0 +1,90 @@
+/// Serving a repository on local disk to a client in the same process.
+///
+/// A `file://` remote is just a directory, with no server in front of it. The client sends it
+/// the same RPC bodies it would POST over HTTP, and `call` runs them through the same
+/// handlers, with the directory as a single-repo layout. A local push is therefore checked
+/// exactly as a server would check it (fast-forward, old_target, ref locking and object
+/// validation). The directory's own repo-level settings don't apply: there are no hooks,
+/// quotas or protected refs, and the ref lock only covers pushes from this process.
+///
+/// The directory can also be an ordinary repo with a working tree. Its checked-out branch is
+/// then off limits to pushes, since its working tree and index would no longer match it.
+use crate::app_state::{AppState, RepoLayout};
+use crate::handlers::{
+    fetch::fetch_handler,
+    handshake::handshake_handler,
+    pull::pull_handler,
+    push::{push_handler, upload_handler},
+    refs::list_refs_handler,
+};
+use crate::hooks::HooksConfig;
+use crate::ref_policy::RefPolicy;
+use anyhow::{bail, Context, Result};
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Handle one RPC for the repo at `repo_root` as the server's `/rpc/<endpoint>` would, and
+/// return the response status and body. `pusher` is what a client sends as X-Helix-Pusher.
+pub async fn call(
+    repo_root: &Path,
+    endpoint: &str,
+    body: Vec<u8>,
+    pusher: Option<&str>,
+) -> Result<(u16, Vec<u8>)> {
+    if !repo_root.join(".helix").is_dir() {
+        bail!("No Helix repository at {}", repo_root.display());
+    }
+    let policy = RefPolicy {
+        checked_out: checked_out_branch(repo_root),
+        ..Default::default()
+    };
+    let state = Arc::new(
+        AppState::new(
+            RepoLayout::Single(repo_root.to_path_buf()),
+            None,
+            HooksConfig::default(),
+        )
+        .with_ref_policy(policy),
+    );
+    let body = Body::from(body);
+
+    let response: Response = match endpoint {
+        "handshake" => handshake_handler(State(state), body).await.into_response(),
+        "push" => {
+            let mut headers = HeaderMap::new();
+            if let Some(pusher) = pusher.and_then(|p| HeaderValue::from_str(p).ok()) {
+                headers.insert("x-helix-pusher", pusher);
+            }
+            push_handler(State(state), headers, body)
+                .await
+                .into_response()
+        }
+        "upload" => upload_handler(State(state), body).await.into_response(),
+        "pull" => pull_handler(State(state), body).await.into_response(),
+        "fetch" => fetch_handler(State(state), body).await.into_response(),
+        "refs" => list_refs_handler(State(state), body).await.into_response(),
+        other => bail!("Unknown RPC endpoint '{other}'"),
+    };
+
+    let status = response.status().as_u16();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .context("Failed to read local RPC response")?;
+    Ok((status, bytes.to_vec()))
+}
+
+/// The ref HEAD names in a repo with a working tree; bare repos (marked by `.helix/bare`, see
+/// `helix init --bare`) and detached HEADs have none
+fn checked_out_branch(repo_root: &Path) -> Option<String> {
+    let helix = repo_root.join(".helix");
+    if helix.join("bare").is_file() {
+        return None;
+    }
+    let head = fs::read_to_string(helix.join("HEAD")).ok()?;
+    head.trim().strip_prefix("ref: ").map(str::to_string)
+}
@@ -1,31 +1,209 @@
-mod handlers;
-
-use axum::{routing::post, Router};
-use helix_protocol::storage::{FsObjectStore, FsRefStore};
-use helix_server::app_state::AppState;
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use clap::{Parser, Subcommand};
+use helix_server::app_state::{AppState, RepoLayout};
+use helix_server::config::ServerConfig;
+use helix_server::global_store::GlobalStore;
+use helix_server::replication::Replicator;
+use helix_server::repos::{create_repo, delete_repo};
+use helix_server::s3::{Credentials, S3Bucket};
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use crate::handlers::{handshake::handshake_handler, pull::pull_handler, push::push_handler};
+use helix_server::handlers::{
+    admin::{
+        gc_handler, health_handler, metrics_handler, require_admin, track_requests, usage_handler,
+    },
+    fetch::fetch_handler,
+    handshake::handshake_handler,
+    limits::enforce_limits,
+    pull::pull_handler,
+    push::{push_handler, upload_handler},
+    refs::list_refs_handler,
+};
+
+#[derive(Parser)]
+#[command(name = "helix-server", about = "Serve Helix repositories over HTTP")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the hosted repos (what running with no command does)
+    Serve,
+    /// Manage hosted repos in the server's storage, with the server's environment and config
+    Admin {
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
+    /// Send refs to the [replication] peer now instead of waiting for pushes
+    Replicate {
+        /// Sync every ref of every repo, e.g. to seed a new peer
+        #[arg(long)]
+        full: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// Create an empty repo that can be cloned and pushed to
+    CreateRepo {
+        name: String,
+        /// Branch the repo's HEAD names
+        #[arg(long, default_value = "main")]
+        default_branch: String,
+    },
+    /// Delete a repo with its refs, reflogs and objects
+    DeleteRepo {
+        name: String,
+        /// Confirm the deletion, which can't be undone
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List the hosted repos
+    ListRepos,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // TODO: single root dir for one repo, update to support multiple
-    let repo_root = std::env::var("HELIX_REPO_ROOT").unwrap_or_else(|_| ".".to_string());
+    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+
+    // Optional TOML config (post-receive hooks, request limits, quotas, protected refs, path
+    // access rules, storage)
+    let config = ServerConfig::from_env()?;
+
+    // [storage.s3] keeps every repo in a bucket; otherwise HELIX_REPOS_DIR hosts one repo per
+    // subdirectory, or serve the single repo at HELIX_REPO_ROOT
+    let layout = match (&config.storage.s3, std::env::var("HELIX_REPOS_DIR")) {
+        (Some(s3), _) => RepoLayout::Bucket(S3Bucket::new(s3.clone(), Credentials::from_env()?)?),
+        (None, Ok(dir)) => RepoLayout::Multi(dir.into()),
+        (None, Err(_)) => RepoLayout::Single(
+            std::env::var("HELIX_REPO_ROOT")
+                .unwrap_or_else(|_| ".".to_string())
+                .into(),
+        ),
+    };
+
+    // Optional object store shared across all hosted repos; HELIX_GLOBAL_STORE wins over [storage]
+    let global = match std::env::var("HELIX_GLOBAL_STORE") {
+        Ok(dir) => Some(GlobalStore::new(dir)?),
+        Err(_) => match &config.storage.shared_objects {
+            Some(dir) => Some(GlobalStore::new(dir)?),
+            None => None,
+        },
+    };
 
-    let objects = FsObjectStore::new(&repo_root);
-    let refs = FsRefStore::new(&repo_root);
+    if global.is_some() && matches!(layout, RepoLayout::Bucket(_)) {
+        anyhow::bail!("A shared object store can't be combined with [storage.s3]");
+    }
 
-    let state = Arc::new(AppState { objects, refs });
+    let max_body_bytes = config.limits.max_body_bytes;
+    let mut state = AppState::new(layout, global, config.hooks)
+        .with_limits(config.limits)
+        .with_quotas(config.quotas)
+        .with_ref_policy(config.refs)
+        .with_access(config.access)
+        .with_auth(config.auth)
+        .with_storage(config.storage);
+    if let Some(replication) = config.replication {
+        state = state.with_replication(Replicator::new(replication)?);
+    }
+    let state = Arc::new(state);
+
+    match cli.command {
+        Some(Command::Admin { command }) => return admin(&state, command),
+        Some(Command::Replicate { full }) => return replicate(&state, full).await,
+        Some(Command::Serve) | None => {}
+    }
+
+    // Refs queued by pushes (and left over from before a restart) go to the peer in the
+    // background
+    if let Some(replicator) = &state.replication {
+        tokio::spawn(replicator.clone().run(state.clone()));
+    }
     // TODO: later let's move to a real streaming reader inside the handlers like from a TCP socket or chunked body since right nwo the entire HTTP body is buffered - would likely be more efficient
+    let admin = Router::new()
+        .route("/admin/usage", get(usage_handler))
+        .route("/admin/gc", post(gc_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin));
     let app = Router::new()
         .route("/rpc/handshake", post(handshake_handler))
         .route("/rpc/push", post(push_handler))
+        .route("/rpc/upload", post(upload_handler))
         .route("/rpc/pull", post(pull_handler))
+        .route("/rpc/fetch", post(fetch_handler))
+        .route("/rpc/refs", post(list_refs_handler))
+        .merge(admin)
+        .route("/healthz", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            enforce_limits,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
         .with_state(state);
 
     let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
-    println!("helix-server listening on {}", addr);
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
+    tracing::info!("helix-server listening on {}", addr);
+    axum::serve(
+        tokio::net::TcpListener::bind(addr).await?,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+    Ok(())
+}
+
+fn admin(state: &AppState, command: AdminCommand) -> anyhow::Result<()> {
+    match command {
+        AdminCommand::CreateRepo {
+            name,
+            default_branch,
+        } => {
+            create_repo(state, &name, &default_branch)?;
+            println!("Created repository '{name}'");
+        }
+        AdminCommand::DeleteRepo { name, yes } => {
+            if !yes {
+                anyhow::bail!("Deleting '{name}' can't be undone; pass --yes to confirm");
+            }
+            delete_repo(state, &name)?;
+            println!("Deleted repository '{name}'");
+        }
+        AdminCommand::ListRepos => {
+            for name in state.repo_names()? {
+                println!("{name}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn replicate(state: &AppState, full: bool) -> anyhow::Result<()> {
+    let Some(replicator) = &state.replication else {
+        anyhow::bail!("No [replication] peer is configured");
+    };
+    if full {
+        replicator.enqueue_all(state)?;
+    }
+
+    let report = replicator.drain(state).await;
+    println!(
+        "Replicated {} ref(s), {} failed",
+        report.synced, report.failed
+    );
+    if report.failed > 0 {
+        anyhow::bail!("Some refs could not be replicated; see the log");
+    }
     Ok(())
 }
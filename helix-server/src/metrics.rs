@@ -0,0 +1,254 @@
+/// Process-wide counters exported at GET /metrics in the Prometheus text format.
+///
+/// Request counts are labelled by route and status code; object bytes count the (compressed)
+/// object payloads carried by push, pull and fetch, not the HTTP framing around them. Storage
+/// sizes are measured when scraped rather than tracked, so they stay correct when objects are
+/// removed behind the server's back.
+use crate::app_state::{AppState, RepoStores};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Repo label used by single-repo servers, which ignore the name clients send
+pub const SINGLE_REPO_LABEL: &str = "default";
+
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    active_sessions: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: Mutex::new(BTreeMap::new()),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            active_sessions: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Counts an RPC session as active until dropped
+pub struct SessionGuard<'a> {
+    metrics: &'a Metrics,
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        self.metrics.active_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn record_request(&self, route: &str, status: u16) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests.entry((route.to_string(), status)).or_default() += 1;
+    }
+
+    pub fn add_bytes_received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn session(&self) -> SessionGuard<'_> {
+        self.active_sessions.fetch_add(1, Ordering::Relaxed);
+        SessionGuard { metrics: self }
+    }
+
+    pub fn active_sessions(&self) -> u64 {
+        self.active_sessions.load(Ordering::Relaxed)
+    }
+
+    /// Render every metric, with `repo_sizes` as (repo, bytes) pairs
+    pub fn render(&self, repo_sizes: &[(String, u64)]) -> String {
+        let mut out = String::new();
+
+        write_header(
+            &mut out,
+            "helix_uptime_seconds",
+            "gauge",
+            "Seconds since the server started",
+        );
+        let _ = writeln!(
+            out,
+            "helix_uptime_seconds {}",
+            self.started.elapsed().as_secs()
+        );
+
+        write_header(
+            &mut out,
+            "helix_requests_total",
+            "counter",
+            "HTTP requests handled, by route and status",
+        );
+        for ((route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "helix_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+                escape_label(route),
+                status,
+                count
+            );
+        }
+
+        write_header(
+            &mut out,
+            "helix_object_bytes_received_total",
+            "counter",
+            "Object bytes received from clients by push",
+        );
+        let _ = writeln!(
+            out,
+            "helix_object_bytes_received_total {}",
+            self.bytes_received.load(Ordering::Relaxed)
+        );
+
+        write_header(
+            &mut out,
+            "helix_object_bytes_sent_total",
+            "counter",
+            "Object bytes sent to clients by pull and fetch",
+        );
+        let _ = writeln!(
+            out,
+            "helix_object_bytes_sent_total {}",
+            self.bytes_sent.load(Ordering::Relaxed)
+        );
+
+        write_header(
+            &mut out,
+            "helix_active_sessions",
+            "gauge",
+            "RPC requests currently being served",
+        );
+        let _ = writeln!(out, "helix_active_sessions {}", self.active_sessions());
+
+        write_header(
+            &mut out,
+            "helix_repo_storage_bytes",
+            "gauge",
+            "Object bytes stored for each repo",
+        );
+        for (repo, bytes) in repo_sizes {
+            let _ = writeln!(
+                out,
+                "helix_repo_storage_bytes{{repo=\"{}\"}} {}",
+                escape_label(repo),
+                bytes
+            );
+        }
+
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Object bytes per hosted repo. With a global store each repo is charged its attributed share.
+pub fn repo_storage_sizes(state: &AppState) -> Result<Vec<(String, u64)>> {
+    if let Some(global) = &state.global {
+        return Ok(global
+            .usage()?
+            .repos
+            .into_iter()
+            .map(|usage| (usage.repo, usage.attributed_bytes))
+            .collect());
+    }
+
+    state
+        .repo_names()?
+        .into_iter()
+        .map(|name| {
+            let size = state.repo(&name)?.objects.stored_bytes()?;
+            Ok((name, size))
+        })
+        .collect()
+}
+
+/// Object bytes charged to one repo, measured like `repo_storage_sizes`
+pub fn repo_storage_size(state: &AppState, repo: &RepoStores) -> Result<u64> {
+    match &state.global {
+        Some(global) => Ok(global
+            .usage()?
+            .repos
+            .into_iter()
+            .find(|usage| usage.repo == repo.name)
+            .map_or(0, |usage| usage.attributed_bytes)),
+        None => repo.objects.stored_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::RepoLayout;
+    use crate::hooks::HooksConfig;
+    use helix_protocol::message::ObjectType;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_render_counters_and_repo_sizes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = AppState::new(
+            RepoLayout::Multi(temp_dir.path().to_path_buf()),
+            None,
+            HooksConfig::default(),
+        );
+        crate::repos::create_repo(&state, "app", "main")?;
+        let repo = state.repo("app")?;
+        repo.refs.set_ref("refs/heads/main", [1u8; 32])?;
+        repo.objects.write_object(&ObjectType::Blob, b"hello")?;
+
+        let metrics = &state.metrics;
+        metrics.record_request("/rpc/push", 200);
+        metrics.record_request("/rpc/push", 200);
+        metrics.record_request("/rpc/pull", 404);
+        metrics.add_bytes_received(10);
+        metrics.add_bytes_sent(7);
+        {
+            let _session = metrics.session();
+            assert_eq!(metrics.active_sessions(), 1);
+        }
+        assert_eq!(metrics.active_sessions(), 0);
+
+        let sizes = repo_storage_sizes(&state)?;
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[0].0, "app");
+        assert!(sizes[0].1 > 0);
+
+        let text = metrics.render(&sizes);
+        assert!(text.contains("helix_requests_total{route=\"/rpc/push\",status=\"200\"} 2\n"));
+        assert!(text.contains("helix_requests_total{route=\"/rpc/pull\",status=\"404\"} 1\n"));
+        assert!(text.contains("helix_object_bytes_received_total 10\n"));
+        assert!(text.contains("helix_object_bytes_sent_total 7\n"));
+        assert!(text.contains("helix_active_sessions 0\n"));
+        assert!(text.contains(&format!(
+            "helix_repo_storage_bytes{{repo=\"app\"}} {}\n",
+            sizes[0].1
+        )));
+        assert!(text.contains("# TYPE helix_requests_total counter\n"));
+
+        Ok(())
+    }
+}
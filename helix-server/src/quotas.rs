@@ -0,0 +1,142 @@
+/// Storage caps for hosted repos, enforced while a push's objects are received.
+///
+/// ```toml
+/// [quotas]
+/// max_object_bytes = 104857600    # largest single object (100 MiB), 0 = no cap
+/// max_repo_bytes = 10737418240    # total object storage per repo (10 GiB), 0 = no cap
+///
+/// [quotas.repos.media]            # overrides for one repo; unset keys use the defaults
+/// max_object_bytes = 1073741824
+/// ```
+///
+/// Sizes are as stored on disk, i.e. compressed, which is what a runaway binary commit costs
+/// the server. A repo's usage is measured the same way /metrics reports it: its object
+/// directory, or its attributed share of the global store. Only objects the server doesn't
+/// already have count against the quota.
+use helix_protocol::hash::Hash;
+use helix_protocol::message::{ErrorCode, RpcError};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub max_object_bytes: u64,
+    pub max_repo_bytes: u64,
+    pub repos: HashMap<String, RepoQuotaConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct RepoQuotaConfig {
+    pub max_object_bytes: Option<u64>,
+    pub max_repo_bytes: Option<u64>,
+}
+
+/// The caps that apply to one repo; 0 means no cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub max_object_bytes: u64,
+    pub max_repo_bytes: u64,
+}
+
+impl QuotaConfig {
+    pub fn for_repo(&self, repo: &str) -> Quota {
+        let overrides = self.repos.get(repo).copied().unwrap_or_default();
+        Quota {
+            max_object_bytes: overrides.max_object_bytes.unwrap_or(self.max_object_bytes),
+            max_repo_bytes: overrides.max_repo_bytes.unwrap_or(self.max_repo_bytes),
+        }
+    }
+}
+
+impl Quota {
+    /// Reject an incoming object of `size` bytes when it is too big on its own, or when
+    /// storing it would take the repo from `used` bytes past its cap
+    pub fn check_object(&self, hash: Hash, size: u64, used: u64) -> Result<(), RpcError> {
+        if self.max_object_bytes > 0 && size > self.max_object_bytes {
+            return Err(RpcError::new(
+                ErrorCode::ObjectTooLarge {
+                    hash,
+                    size,
+                    limit: self.max_object_bytes,
+                },
+                format!(
+                    "Object {} is {} bytes, over the {} byte limit for a single object",
+                    hex::encode(hash),
+                    size,
+                    self.max_object_bytes
+                ),
+            ));
+        }
+        if self.max_repo_bytes > 0 && used.saturating_add(size) > self.max_repo_bytes {
+            return Err(RpcError::new(
+                ErrorCode::QuotaExceeded {
+                    used,
+                    limit: self.max_repo_bytes,
+                },
+                format!(
+                    "Repository storage quota exceeded: {} of {} bytes used, and object {} \
+                     needs {} more",
+                    used,
+                    self.max_repo_bytes,
+                    hex::encode(hash),
+                    size
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_overrides_and_checks() -> anyhow::Result<()> {
+        let config: crate::config::ServerConfig = toml::from_str(
+            r#"
+            [quotas]
+            max_object_bytes = 100
+            max_repo_bytes = 1000
+
+            [quotas.repos.media]
+            max_object_bytes = 500
+            "#,
+        )?;
+        let quotas = config.quotas;
+
+        let app = quotas.for_repo("app");
+        assert_eq!(
+            quotas.for_repo("media"),
+            Quota {
+                max_object_bytes: 500,
+                max_repo_bytes: 1000,
+            }
+        );
+
+        assert!(app.check_object([1u8; 32], 100, 0).is_ok());
+        let err = app.check_object([1u8; 32], 101, 0).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorCode::ObjectTooLarge {
+                hash: [1u8; 32],
+                size: 101,
+                limit: 100,
+            }
+        );
+        assert_eq!(err.code, 413);
+
+        let err = app.check_object([2u8; 32], 50, 960).unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorCode::QuotaExceeded { used: 960, .. }
+        ));
+        assert!(Quota::default()
+            .check_object([3u8; 32], u64::MAX, u64::MAX)
+            .is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+/// Rules for which refs a push may change.
+///
+/// ```toml
+/// [refs]
+/// protected = ["main", "refs/heads/release/*"]
+/// ```
+///
+/// A protected ref can be created and fast-forwarded, but never deleted or moved to a commit
+/// that doesn't descend from its current one, even with --force. A bare name like `main`
+/// means the branch `refs/heads/main`; a pattern ending in `*` covers every ref under that
+/// prefix.
+use helix_protocol::message::{ErrorCode, RpcError};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RefPolicy {
+    pub protected: Vec<String>,
+    /// Branch checked out in the repo's working tree, which pushes must leave alone. Only
+    /// set when serving a non-bare repo to a `file://` client.
+    #[serde(skip)]
+    pub checked_out: Option<String>,
+}
+
+impl RefPolicy {
+    /// Refuse to move or delete the checked-out branch: the working tree and index there
+    /// would silently stop matching it
+    pub fn check_not_checked_out(&self, ref_name: &str) -> Result<(), RpcError> {
+        if self.checked_out.as_deref() != Some(ref_name) {
+            return Ok(());
+        }
+        Err(RpcError::new(
+            ErrorCode::BranchCheckedOut,
+            format!("{ref_name} is checked out in the target's working tree"),
+        ))
+    }
+
+    pub fn is_protected(&self, ref_name: &str) -> bool {
+        self.protected.iter().any(|pattern| {
+            let pattern = if pattern.starts_with("refs/") {
+                pattern.clone()
+            } else {
+                format!("refs/heads/{pattern}")
+            };
+            match pattern.strip_suffix('*') {
+                Some(prefix) => ref_name.starts_with(prefix),
+                None => ref_name == pattern,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_patterns() -> anyhow::Result<()> {
+        let config: crate::config::ServerConfig = toml::from_str(
+            r#"
+            [refs]
+            protected = ["main", "refs/heads/release/*", "refs/tags/*"]
+            "#,
+        )?;
+        let policy = config.refs;
+
+        assert!(policy.is_protected("refs/heads/main"));
+        assert!(!policy.is_protected("refs/heads/main2"));
+        assert!(policy.is_protected("refs/heads/release/1.0"));
+        assert!(!policy.is_protected("refs/heads/release"));
+        assert!(policy.is_protected("refs/tags/v1"));
+        assert!(!RefPolicy::default().is_protected("refs/heads/main"));
+
+        Ok(())
+    }
+}
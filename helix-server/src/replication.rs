@@ -0,0 +1,611 @@
+/// Replication to a standby server. After each accepted push the server queues the refs it
+/// moved, and a background worker brings the peer's copy of each one up to date over the
+/// peer's ordinary RPCs: it asks where the ref is there, uploads the objects the peer is
+/// missing and then moves (or deletes) the ref to match.
+///
+/// ```toml
+/// [replication]
+/// peer = "https://standby.example.com"
+/// token = "replica-secret"      # sent as a bearer token; list it in the peer's [[auth.users]]
+/// retry_secs = 30               # pause after a failed attempt
+/// queue_file = "/var/lib/helix/replication.queue"  # keeps pending refs across restarts
+/// ```
+///
+/// The queue holds (repo, ref) pairs rather than individual updates, so a ref pushed several
+/// times while the peer is down is sent once, at its latest value. The peer is treated as a
+/// copy: its ref is moved to ours whatever it held before. Its own ref policy still applies,
+/// and an update it refuses outright (a protected ref, a quota) is logged and dropped rather
+/// than retried forever; network failures, conflicts and server errors are retried.
+///
+/// `helix-server replicate --full` seeds a new peer by syncing every ref of every repo once.
+/// The peer only accepts refs for repos it hosts, so create each one there first
+/// (`helix-server admin create-repo`).
+use crate::app_state::AppState;
+use anyhow::{anyhow, Context, Result};
+use helix_protocol::commit::{collect_objects_from_commits, walk_commits_between};
+use helix_protocol::hash::{Hash, ZERO_HASH};
+use helix_protocol::message::{
+    read_message, write_message, ErrorCode, Features, Hello, ListRefsRequest, ObjectType,
+    PushObject, PushRequest, RpcError, RpcMessage, WireError,
+};
+use helix_protocol::storage::ObjectStore;
+use helix_protocol::tag::peel_to_commit;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Objects are uploaded in batches of about this many (compressed) bytes, well under the
+/// default body limit
+const UPLOAD_BATCH_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReplicationConfig {
+    /// Base URL of the standby server
+    pub peer: String,
+    pub token: Option<String>,
+    #[serde(default = "default_retry_secs")]
+    pub retry_secs: u64,
+    pub queue_file: Option<PathBuf>,
+}
+
+fn default_retry_secs() -> u64 {
+    30
+}
+
+/// Why syncing a ref failed
+#[derive(Debug)]
+enum SyncError {
+    /// Worth trying again later: the peer was unreachable, busy or raced us
+    Retry(anyhow::Error),
+    /// The peer won't take this update however often it's sent
+    Refused(RpcError),
+}
+
+impl From<anyhow::Error> for SyncError {
+    fn from(e: anyhow::Error) -> Self {
+        SyncError::Retry(e)
+    }
+}
+
+impl From<WireError> for SyncError {
+    fn from(e: WireError) -> Self {
+        SyncError::Retry(e.into())
+    }
+}
+
+impl From<RpcError> for SyncError {
+    fn from(e: RpcError) -> Self {
+        match e.kind {
+            ErrorCode::Internal
+            | ErrorCode::Conflict
+            | ErrorCode::NotFastForward
+            | ErrorCode::ObjectMissing
+            | ErrorCode::RateLimited
+            | ErrorCode::Unauthorized => {
+                SyncError::Retry(anyhow!("peer answered {}: {}", e.code, e.message))
+            }
+            _ => SyncError::Refused(e),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    refs: VecDeque<(String, String)>,
+    /// Taken off the queue and being sent; still written to the queue file
+    in_flight: Option<(String, String)>,
+}
+
+/// Handle to the replication queue, shared by the push handlers and the worker.
+#[derive(Clone)]
+pub struct Replicator {
+    config: Arc<ReplicationConfig>,
+    pending: Arc<Mutex<Pending>>,
+    wake: Arc<Notify>,
+    client: reqwest::Client,
+}
+
+/// What a pass over the queue did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplicationReport {
+    pub synced: usize,
+    pub failed: usize,
+}
+
+impl Replicator {
+    /// A replicator for `config`, picking up refs left in its queue file
+    pub fn new(config: ReplicationConfig) -> Result<Self> {
+        let mut pending = Pending::default();
+        if let Some(path) = &config.queue_file {
+            if path.exists() {
+                let contents =
+                    fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+                for line in contents.lines() {
+                    if let Some((repo, ref_name)) = line.split_once('\t') {
+                        let entry = (repo.to_string(), ref_name.to_string());
+                        if !pending.refs.contains(&entry) {
+                            pending.refs.push_back(entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            config: Arc::new(config),
+            pending: Arc::new(Mutex::new(pending)),
+            wake: Arc::default(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Queue a ref to be brought up to date on the peer. Already-queued refs aren't added twice.
+    pub fn enqueue(&self, repo: &str, ref_name: &str) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = (repo.to_string(), ref_name.to_string());
+        if !pending.refs.contains(&entry) {
+            pending.refs.push_back(entry);
+            self.save(&pending);
+        }
+        drop(pending);
+        self.wake.notify_one();
+    }
+
+    /// Queue every ref of every hosted repo
+    pub fn enqueue_all(&self, state: &AppState) -> Result<()> {
+        for name in state.repo_names()? {
+            let repo = state.repo(&name)?;
+            for (ref_name, _) in repo.refs.list_refs("refs/")? {
+                self.enqueue(&name, &ref_name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of refs waiting to be sent
+    pub fn pending(&self) -> usize {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.refs.len() + usize::from(pending.in_flight.is_some())
+    }
+
+    /// Send queued refs forever, pausing after each failure. Spawned by the server at startup.
+    pub async fn run(self, state: Arc<AppState>) {
+        loop {
+            match self.take() {
+                Some(entry) => {
+                    if !self.send(&state, entry).await {
+                        tokio::time::sleep(Duration::from_secs(self.config.retry_secs)).await;
+                    }
+                }
+                None => self.wake.notified().await,
+            }
+        }
+    }
+
+    /// Try each queued ref once. Refs worth retrying are left queued.
+    pub async fn drain(&self, state: &AppState) -> ReplicationReport {
+        let mut report = ReplicationReport::default();
+        for _ in 0..self.pending() {
+            let Some(entry) = self.take() else { break };
+            if self.send(state, entry).await {
+                report.synced += 1;
+            } else {
+                report.failed += 1;
+            }
+        }
+        report
+    }
+
+    fn take(&self) -> Option<(String, String)> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = pending.refs.pop_front()?;
+        pending.in_flight = Some(entry.clone());
+        Some(entry)
+    }
+
+    /// Sync one ref, re-queueing it if that's worth retrying. False if the attempt failed.
+    async fn send(&self, state: &AppState, (repo, ref_name): (String, String)) -> bool {
+        let result = self.sync_ref(state, &repo, &ref_name).await;
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.in_flight = None;
+        let ok = match result {
+            Ok(()) => {
+                tracing::debug!(repo, ref_name, "replicated");
+                true
+            }
+            Err(SyncError::Retry(e)) => {
+                tracing::warn!(repo, ref_name, "replication failed, will retry: {e:#}");
+                let entry = (repo, ref_name);
+                if !pending.refs.contains(&entry) {
+                    pending.refs.push_back(entry);
+                }
+                false
+            }
+            Err(SyncError::Refused(e)) => {
+                tracing::error!(
+                    repo,
+                    ref_name,
+                    "peer refused replicated update: {}",
+                    e.message
+                );
+                false
+            }
+        };
+        self.save(&pending);
+        ok
+    }
+
+    /// Make the peer's `ref_name` match ours, sending whatever objects it needs first
+    async fn sync_ref(
+        &self,
+        state: &AppState,
+        repo: &str,
+        ref_name: &str,
+    ) -> Result<(), SyncError> {
+        let stores = state.repo(repo)?;
+        let local = stores.refs.get_ref(ref_name)?;
+        let remote = self.peer_ref(repo, ref_name).await?;
+        if local == remote {
+            return Ok(());
+        }
+
+        if let Some(target) = local {
+            let objects = objects_to_send(stores.objects.as_ref(), target, remote)?;
+            for batch in batches(objects) {
+                self.call("/rpc/upload", repo, ref_name, remote, target, batch)
+                    .await?;
+            }
+        }
+
+        self.call(
+            "/rpc/push",
+            repo,
+            ref_name,
+            remote,
+            local.unwrap_or(ZERO_HASH),
+            Vec::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Where `ref_name` points on the peer; None if it or the repo isn't there
+    async fn peer_ref(&self, repo: &str, ref_name: &str) -> Result<Option<Hash>, SyncError> {
+        let mut body = hello()?;
+        write_message(
+            &mut body,
+            &RpcMessage::ListRefs(ListRefsRequest {
+                repo: repo.to_string(),
+                prefix: ref_name.to_string(),
+            }),
+        )?;
+
+        match self.post("/rpc/refs", body).await {
+            Ok(RpcMessage::RefList(list)) => Ok(list
+                .refs
+                .into_iter()
+                .find(|(name, _)| name == ref_name)
+                .map(|(_, hash)| hash)),
+            Ok(other) => Err(SyncError::Retry(anyhow!("expected RefList, got {other:?}"))),
+            Err(SyncError::Refused(e)) if e.kind == ErrorCode::RepoNotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A PushRequest moving `ref_name` from `old` to `new`, with `objects` before PushDone
+    async fn call(
+        &self,
+        path: &str,
+        repo: &str,
+        ref_name: &str,
+        old: Option<Hash>,
+        new: Hash,
+        objects: Vec<(ObjectType, Hash, Vec<u8>)>,
+    ) -> Result<(), SyncError> {
+        let mut body = hello()?;
+        write_message(
+            &mut body,
+            &RpcMessage::PushRequest(PushRequest {
+                repo: repo.to_string(),
+                ref_name: ref_name.to_string(),
+                old_target: old.unwrap_or(ZERO_HASH),
+                new_target: new,
+            }),
+        )?;
+        for (object_type, hash, data) in objects {
+            write_message(
+                &mut body,
+                &RpcMessage::PushObject(PushObject {
+                    object_type,
+                    hash,
+                    data,
+                }),
+            )?;
+        }
+        write_message(&mut body, &RpcMessage::PushDone)?;
+
+        match self.post(path, body).await? {
+            RpcMessage::PushAck(_) => Ok(()),
+            other => Err(SyncError::Retry(anyhow!("expected PushAck, got {other:?}"))),
+        }
+    }
+
+    /// POST a request body to the peer and return its answer, skipping the HelloAck
+    async fn post(&self, path: &str, body: Vec<u8>) -> Result<RpcMessage, SyncError> {
+        let url = format!("{}{}", self.config.peer.trim_end_matches('/'), path);
+        let mut request = self
+            .client
+            .post(&url)
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header("X-Helix-Pusher", "helix-server replication")
+            .body(body);
+        if let Some(token) = &self.config.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("POST {url}"))?;
+        let status = response.status();
+        let bytes = response
+            .bytes()
+            .await
+            .with_context(|| format!("read response from {url}"))?;
+
+        let mut reader = Cursor::new(bytes);
+        loop {
+            match read_message(&mut reader) {
+                Ok(RpcMessage::HelloAck(_)) => continue,
+                Ok(RpcMessage::Error(e)) => return Err(e.into()),
+                Ok(msg) => return Ok(msg),
+                Err(_) if !status.is_success() => {
+                    return Err(RpcError::new(
+                        ErrorCode::from_status(status.as_u16()),
+                        format!("{url} returned {status}"),
+                    )
+                    .into())
+                }
+                Err(e) => return Err(anyhow!("bad response from {url}: {e}").into()),
+            }
+        }
+    }
+
+    fn save(&self, pending: &Pending) {
+        let Some(path) = &self.config.queue_file else {
+            return;
+        };
+        let contents: String = pending
+            .in_flight
+            .iter()
+            .chain(pending.refs.iter())
+            .map(|(repo, ref_name)| format!("{repo}\t{ref_name}\n"))
+            .collect();
+        let tmp = path.with_extension("tmp");
+        if let Err(e) = fs::write(&tmp, contents).and_then(|_| fs::rename(&tmp, path)) {
+            tracing::warn!("failed to save replication queue {}: {e}", path.display());
+        }
+    }
+}
+
+fn hello() -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    write_message(
+        &mut body,
+        &RpcMessage::Hello(Hello::new(
+            concat!("helix-server/", env!("CARGO_PKG_VERSION")),
+            Features::default(),
+        )),
+    )?;
+    Ok(body)
+}
+
+/// Objects reachable from `target` that the peer lacks, in dependency order, compressed. When
+/// the peer's head isn't in our store (it diverged), the whole history is sent and the peer
+/// skips what it already has.
+fn objects_to_send(
+    store: &dyn ObjectStore,
+    target: Hash,
+    remote: Option<Hash>,
+) -> Result<Vec<(ObjectType, Hash, Vec<u8>)>> {
+    let commit = peel_to_commit(store, &target)?;
+    let known = remote
+        .map(|r| peel_to_commit(store, &r).unwrap_or(r))
+        .filter(|r| store.has_object(&ObjectType::Commit, r));
+    let commits = walk_commits_between(store, commit, known)?;
+    let mut objects = collect_objects_from_commits(store, &commits)?;
+    if commit != target {
+        objects.push((
+            ObjectType::Tag,
+            target,
+            store.read_object_compressed(&ObjectType::Tag, &target)?,
+        ));
+    }
+    Ok(objects)
+}
+
+/// Split dependency-ordered objects into upload-sized runs, keeping their order
+fn batches(objects: Vec<(ObjectType, Hash, Vec<u8>)>) -> Vec<Vec<(ObjectType, Hash, Vec<u8>)>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut size = 0;
+    for object in objects {
+        if size > 0 && size + object.2.len() > UPLOAD_BATCH_BYTES {
+            batches.push(std::mem::take(&mut batch));
+            size = 0;
+        }
+        size += object.2.len();
+        batch.push(object);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::RepoLayout;
+    use crate::handlers::push::{push_handler, upload_handler};
+    use crate::handlers::refs::list_refs_handler;
+    use crate::hooks::HooksConfig;
+    use crate::repos::create_repo;
+    use axum::routing::post;
+    use axum::Router;
+    use tempfile::TempDir;
+
+    fn tree_with(blob: Hash) -> Vec<u8> {
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(&0o100644u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.push(b'f');
+        bytes.extend_from_slice(&blob);
+        bytes
+    }
+
+    fn commit(store: &dyn ObjectStore, content: &[u8], parents: &[Hash]) -> Result<Hash> {
+        let blob = store.write_object(&ObjectType::Blob, content)?;
+        let tree = store.write_object(&ObjectType::Tree, &tree_with(blob))?;
+        let mut bytes = tree.to_vec();
+        bytes.extend_from_slice(&(parents.len() as u32).to_le_bytes());
+        for parent in parents {
+            bytes.extend_from_slice(parent);
+        }
+        store.write_object(&ObjectType::Commit, &bytes)
+    }
+
+    /// Serve the push and refs RPCs for `state` on a free local port
+    async fn serve(state: Arc<AppState>) -> Result<String> {
+        let app = Router::new()
+            .route("/rpc/push", post(push_handler))
+            .route("/rpc/upload", post(upload_handler))
+            .route("/rpc/refs", post(list_refs_handler))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Ok(format!("http://{addr}"))
+    }
+
+    fn config(peer: String, queue_file: PathBuf) -> ReplicationConfig {
+        ReplicationConfig {
+            peer,
+            token: None,
+            retry_secs: 0,
+            queue_file: Some(queue_file),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refs_follow_the_primary() -> Result<()> {
+        let temp = TempDir::new()?;
+        let primary = AppState::new(
+            RepoLayout::Multi(temp.path().join("primary")),
+            None,
+            HooksConfig::default(),
+        );
+        let peer = Arc::new(AppState::new(
+            RepoLayout::Multi(temp.path().join("peer")),
+            None,
+            HooksConfig::default(),
+        ));
+        let replicator = Replicator::new(config(
+            serve(peer.clone()).await?,
+            temp.path().join("queue"),
+        ))?;
+        for state in [&primary, &peer] {
+            create_repo(state, "app", "main")?;
+        }
+
+        let app = primary.repo("app")?;
+        let c1 = commit(app.objects.as_ref(), b"one", &[])?;
+        let c2 = commit(app.objects.as_ref(), b"two", &[c1])?;
+        app.refs.set_ref("refs/heads/main", c2)?;
+        app.refs.set_ref("refs/tags/v1", c1)?;
+
+        // Seeding sends both refs and all of their history
+        replicator.enqueue_all(&primary)?;
+        let report = replicator.drain(&primary).await;
+        assert_eq!(
+            report,
+            ReplicationReport {
+                synced: 2,
+                failed: 0
+            }
+        );
+        let copy = peer.repo("app")?;
+        assert_eq!(copy.refs.get_ref("refs/heads/main")?, Some(c2));
+        assert_eq!(copy.refs.get_ref("refs/tags/v1")?, Some(c1));
+        assert!(copy.objects.has_object(&ObjectType::Commit, &c1));
+
+        // A rewritten branch is forced to match and a deleted tag goes away on the peer too
+        let c3 = commit(app.objects.as_ref(), b"three", &[c1])?;
+        app.refs.set_ref("refs/heads/main", c3)?;
+        app.refs.delete_ref("refs/tags/v1")?;
+        replicator.enqueue("app", "refs/heads/main");
+        replicator.enqueue("app", "refs/tags/v1");
+        replicator.enqueue("app", "refs/heads/main");
+        assert_eq!(replicator.pending(), 2);
+        let report = replicator.drain(&primary).await;
+        assert_eq!(
+            report,
+            ReplicationReport {
+                synced: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(copy.refs.get_ref("refs/heads/main")?, Some(c3));
+        assert_eq!(copy.refs.get_ref("refs/tags/v1")?, None);
+        assert_eq!(fs::read_to_string(temp.path().join("queue"))?, "");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_refs_stay_queued_across_restarts() -> Result<()> {
+        let temp = TempDir::new()?;
+        let primary = AppState::new(
+            RepoLayout::Multi(temp.path().join("primary")),
+            None,
+            HooksConfig::default(),
+        );
+        create_repo(&primary, "app", "main")?;
+        let app = primary.repo("app")?;
+        let c1 = commit(app.objects.as_ref(), b"one", &[])?;
+        app.refs.set_ref("refs/heads/main", c1)?;
+
+        // Nothing listens on the peer's port
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let peer = format!("http://{}", closed.local_addr()?);
+        drop(closed);
+        let queue = temp.path().join("queue");
+
+        let replicator = Replicator::new(config(peer.clone(), queue.clone()))?;
+        replicator.enqueue("app", "refs/heads/main");
+        let report = replicator.drain(&primary).await;
+        assert_eq!(
+            report,
+            ReplicationReport {
+                synced: 0,
+                failed: 1
+            }
+        );
+        assert_eq!(replicator.pending(), 1);
+
+        let restarted = Replicator::new(config(peer, queue))?;
+        assert_eq!(restarted.pending(), 1);
+
+        Ok(())
+    }
+}
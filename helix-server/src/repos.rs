@@ -0,0 +1,135 @@
+/// Creating, deleting and listing hosted repos, behind `helix-server admin`.
+///
+/// This is the only way a repo comes to exist: pushes, clones and pulls of a repo that
+/// hasn't been created are refused with RepoNotFound. A created repo is clonable while still
+/// empty, and its HEAD names the branch given here. On disk a created repo is laid out like
+/// `helix init --bare`; in a bucket it starts as just its HEAD key.
+///
+/// These work on the server's storage directly, so they run with the same environment and
+/// config as the server. A single-repo server (HELIX_REPO_ROOT) has nothing to manage.
+use crate::app_state::{validate_repo_name, AppState, RepoLayout};
+use anyhow::{bail, Context, Result};
+use helix_protocol::storage::{FsObjectStore, ObjectLayout};
+use std::fs;
+
+/// HEAD of a new repo whose default branch is `branch`
+fn head_for(branch: &str) -> Result<String> {
+    if branch.is_empty() || branch.starts_with(['/', '-']) || branch.contains(['\0', ' ', '\n']) {
+        bail!("Invalid branch name '{branch}'");
+    }
+    Ok(format!("ref: refs/heads/{branch}\n"))
+}
+
+pub fn create_repo(state: &AppState, name: &str, default_branch: &str) -> Result<()> {
+    let head = head_for(default_branch)?;
+    validate_repo_name(name)?;
+    match &state.layout {
+        RepoLayout::Single(root) => single_repo_err(root),
+        RepoLayout::Multi(dir) => {
+            if state.repo_exists(name) {
+                bail!("Repository '{name}' already exists");
+            }
+            let helix_dir = dir.join(name).join(".helix");
+            for sub in ["objects", "refs/heads", "refs/tags"] {
+                fs::create_dir_all(helix_dir.join(sub)).with_context(|| {
+                    format!("Failed to create {}", helix_dir.join(sub).display())
+                })?;
+            }
+            FsObjectStore::at_dir(helix_dir.join("objects")).set_layout(ObjectLayout::Fanout)?;
+            fs::write(helix_dir.join("HEAD"), head)?;
+            fs::write(helix_dir.join("bare"), "")?;
+            Ok(())
+        }
+        RepoLayout::Bucket(bucket) => bucket.create_repo(name, &head),
+    }
+}
+
+/// Delete a repo with its refs, reflogs and objects. With a shared object store its objects
+/// stay until gc finds no other repo needs them.
+pub fn delete_repo(state: &AppState, name: &str) -> Result<()> {
+    validate_repo_name(name)?;
+    if let RepoLayout::Single(root) = &state.layout {
+        return single_repo_err(root);
+    }
+    if !state.repo_exists(name) {
+        bail!("Repository '{name}' does not exist");
+    }
+
+    // No push may land half-way through
+    let lock = state.ref_lock(name);
+    let _refs = lock.lock().unwrap_or_else(|e| e.into_inner());
+    match &state.layout {
+        RepoLayout::Single(_) => unreachable!("checked above"),
+        RepoLayout::Multi(dir) => {
+            let root = dir.join(name);
+            fs::remove_dir_all(&root).with_context(|| format!("remove {}", root.display()))?;
+        }
+        RepoLayout::Bucket(bucket) => {
+            bucket.delete_repo(name)?;
+        }
+    }
+    if let Some(global) = &state.global {
+        global.forget_repo(name)?;
+    }
+    Ok(())
+}
+
+fn single_repo_err(root: &std::path::Path) -> Result<()> {
+    bail!(
+        "This server only serves the repo at {}; set HELIX_REPOS_DIR or [storage.s3] to host several",
+        root.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::global_store::GlobalStore;
+    use crate::hooks::HooksConfig;
+    use helix_protocol::message::ObjectType;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_list_and_delete_repos() -> Result<()> {
+        let temp = TempDir::new()?;
+        let repos = temp.path().join("repos");
+        let global = GlobalStore::new(temp.path().join("shared"))?;
+        let state = AppState::new(
+            RepoLayout::Multi(repos.clone()),
+            Some(global.clone()),
+            HooksConfig::default(),
+        );
+
+        create_repo(&state, "app", "trunk")?;
+        create_repo(&state, "lib", "main")?;
+        assert!(create_repo(&state, "app", "main").is_err());
+        assert!(create_repo(&state, "../escape", "main").is_err());
+        assert!(create_repo(&state, "web", "-x").is_err());
+        assert_eq!(state.repo_names()?, vec!["app", "lib"]);
+        assert_eq!(
+            fs::read_to_string(repos.join("app/.helix/HEAD"))?,
+            "ref: refs/heads/trunk\n"
+        );
+
+        let app = state.repo("app")?;
+        let blob = app.objects.write_object(&ObjectType::Blob, b"hi")?;
+        global.record_owner("app", &[(ObjectType::Blob, blob)])?;
+        app.refs.set_ref("refs/heads/trunk", [1u8; 32])?;
+        assert_eq!(global.ledger_repos()?, vec!["app"]);
+
+        delete_repo(&state, "app")?;
+        assert!(!repos.join("app").exists());
+        assert!(global.ledger_repos()?.is_empty());
+        assert_eq!(state.repo_names()?, vec!["lib"]);
+        assert!(delete_repo(&state, "app").is_err());
+        assert!(!repos.join("app").exists());
+
+        let single = AppState::new(
+            RepoLayout::Single(temp.path().to_path_buf()),
+            None,
+            HooksConfig::default(),
+        );
+        assert!(create_repo(&single, "app", "main").is_err());
+        Ok(())
+    }
+}
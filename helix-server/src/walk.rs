@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Result};
 use helix_protocol::message::ObjectType;
-use helix_protocol::storage::FsObjectStore;
+use helix_protocol::storage::ObjectStore;
 use std::collections::HashSet;
 use std::io::Cursor;
 
@@ -20,7 +20,7 @@ For each tree:
 /// Collect all objects (commits + trees + blobs) reachable from `remote_head`.
 /// Returns tuples of (ObjectType, hash, raw_bytes) suitable for sending over the wire.
 pub fn collect_all_objects(
-    objects: &FsObjectStore,
+    objects: &dyn ObjectStore,
     remote_head: &[u8; 32],
 ) -> Result<Vec<(ObjectType, [u8; 32], Vec<u8>)>> {
     let mut result: Vec<(ObjectType, [u8; 32], Vec<u8>)> = Vec::new();
@@ -115,7 +115,7 @@ fn parse_commit_header(bytes: &[u8]) -> Result<ParsedCommit> {
 
 fn walk_tree(
     tree_hash: &[u8; 32],
-    objects: &FsObjectStore,
+    objects: &dyn ObjectStore,
     seen_trees: &mut HashSet<[u8; 32]>,
     seen_blobs: &mut HashSet<[u8; 32]>,
     result: &mut Vec<(ObjectType, [u8; 32], Vec<u8>)>,
@@ -136,7 +136,7 @@ fn walk_tree(
 
 fn parse_tree(
     bytes: &[u8],
-    objects: &FsObjectStore,
+    objects: &dyn ObjectStore,
     seen_trees: &mut HashSet<[u8; 32]>,
     seen_blobs: &mut HashSet<[u8; 32]>,
     result: &mut Vec<(ObjectType, [u8; 32], Vec<u8>)>,